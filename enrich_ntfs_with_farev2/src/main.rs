@@ -0,0 +1,130 @@
+// Copyright 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, FixedOffset};
+use log::info;
+use slog::{slog_o, Drain};
+use slog_async::OverflowStrategy;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use transit_model::{
+    fares::{self, FaresMode, FaresOptions},
+    read_utils::ReadMode,
+    Result,
+};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "enrich_ntfs_with_farev2",
+    about = "Enrich a NTFS with fares V2."
+)]
+struct Opt {
+    /// Input directory.
+    #[structopt(short = "i", long = "input", parse(from_os_str), default_value = ".")]
+    input: PathBuf,
+
+    /// Zip file or directory of the fares V2, directory of zip files of
+    /// fares V2 (e.g. one per network) which are all merged, or JSON
+    /// manifest of zip files with the networks they cover.
+    #[structopt(short = "f", long = "fare", parse(from_os_str))]
+    fare: PathBuf,
+
+    /// Output directory.
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: PathBuf,
+
+    /// Output file of the report, as CSV if it has a `.csv` extension, as
+    /// JSON otherwise.
+    #[structopt(short = "r", long = "report", parse(from_os_str))]
+    report: PathBuf,
+
+    /// Keep the fares of the input and add the new ones, reporting the
+    /// conflicts, instead of replacing them.
+    #[structopt(long)]
+    merge: bool,
+
+    /// Reject a fare archive when the ratio of its perimeters and
+    /// restrictions referencing unknown objects is above this value (e.g.
+    /// `0.1`).
+    #[structopt(long)]
+    max_error_ratio: Option<f64>,
+
+    /// Skip the records of the fare files which can't be read (with a
+    /// warning) and the corrupted files of a fare archive (reported), instead
+    /// of failing.
+    #[structopt(long)]
+    lenient: bool,
+
+    /// Current datetime.
+    #[structopt(
+        short = "x",
+        long,
+        parse(try_from_str),
+        default_value = &transit_model::CURRENT_DATETIME
+    )]
+    current_datetime: DateTime<FixedOffset>,
+}
+
+fn init_logger() -> slog_scope::GlobalLoggerGuard {
+    let decorator = slog_term::TermDecorator::new().stdout().build();
+    let drain = slog_term::CompactFormat::new(decorator).build().fuse();
+    let mut builder = slog_envlogger::LogBuilder::new(drain).filter(None, slog::FilterLevel::Info);
+    if let Ok(s) = std::env::var("RUST_LOG") {
+        builder = builder.parse(&s);
+    }
+    let drain = slog_async::Async::new(builder.build())
+        .chan_size(256) // Double the default size
+        .overflow_strategy(OverflowStrategy::Block)
+        .build()
+        .fuse();
+    let logger = slog::Logger::root(drain, slog_o!());
+
+    let scope_guard = slog_scope::set_global_logger(logger);
+    slog_stdlog::init().unwrap();
+    scope_guard
+}
+
+fn run(opt: Opt) -> Result<()> {
+    info!("Launching enrich_ntfs_with_farev2...");
+
+    let mode = if opt.merge {
+        FaresMode::Merge
+    } else {
+        FaresMode::Replace
+    };
+    let model = transit_model::ntfs::read(opt.input)?;
+    let options = FaresOptions {
+        max_error_ratio: opt.max_error_ratio,
+        read_mode: if opt.lenient {
+            ReadMode::Lenient
+        } else {
+            ReadMode::Strict
+        },
+    };
+    let model = fares::enrich_with_farev2(model, opt.fare, mode, &options, opt.report)?;
+    transit_model::ntfs::write(&model, opt.output, opt.current_datetime)?;
+    Ok(())
+}
+
+fn main() {
+    let _log_guard = init_logger();
+    if let Err(err) = run(Opt::from_args()) {
+        for cause in err.iter_chain() {
+            eprintln!("{}", cause);
+        }
+        std::process::exit(1);
+    }
+}
@@ -28,6 +28,13 @@ fn run() -> Result<()> {
         prefix_conf: None,
         on_demand_transport: false,
         on_demand_transport_comment: None,
+        route_type_mappings: BTreeMap::new(),
+        keep_frequencies: false,
+        crs: None,
+        company_parents: Default::default(),
+        network_contacts: Default::default(),
+        read_mode: Default::default(),
+        trip_operators: false,
     };
     // read GTFS from current directory
     let objects = gtfs::read_from_path(".", configuration)?;
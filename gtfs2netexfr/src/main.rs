@@ -96,7 +96,8 @@ fn init_logger() -> slog_scope::GlobalLoggerGuard {
 fn run(opt: Opt) -> Result<()> {
     info!("Launching gtfs2netexfr...");
 
-    let (contributor, dataset, feed_infos) = read_utils::read_config(opt.config)?;
+    let (contributor, dataset, feed_infos, trip_operators) =
+        read_utils::read_gtfs_config(opt.config)?;
     let configuration = transit_model::gtfs::Configuration {
         contributor,
         dataset,
@@ -104,6 +105,13 @@ fn run(opt: Opt) -> Result<()> {
         prefix_conf: None,
         on_demand_transport: opt.odt,
         on_demand_transport_comment: opt.odt_comment,
+        route_type_mappings: Default::default(),
+        keep_frequencies: false,
+        crs: None,
+        company_parents: Default::default(),
+        network_contacts: Default::default(),
+        read_mode: Default::default(),
+        trip_operators,
     };
 
     let model = if opt.input.is_file() {
@@ -21,7 +21,13 @@ use slog::{slog_o, Drain};
 use slog_async::OverflowStrategy;
 use std::path::PathBuf;
 use structopt::StructOpt;
-use transit_model::{read_utils, transfers::generates_transfers, PrefixConfiguration, Result};
+use transit_model::{
+    crs::CoordinateSystem,
+    read_utils::{self, ReadMode},
+    stop_areas,
+    transfers::generates_transfers,
+    PrefixConfiguration, Result,
+};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "gtfs2ntfs", about = "Convert a GTFS to an NTFS.")]
@@ -31,8 +37,8 @@ struct Opt {
     input: PathBuf,
 
     /// Output directory.
-    #[structopt(short, long, parse(from_os_str))]
-    output: PathBuf,
+    #[structopt(short, long, parse(from_os_str), required_unless = "explain")]
+    output: Option<PathBuf>,
 
     /// JSON file containing additional configuration.
     ///
@@ -58,6 +64,51 @@ struct Opt {
     #[structopt(long = "odt-comment")]
     odt_comment: Option<String>,
 
+    /// JSON file mapping `route_type` values (e.g. the extended values like
+    /// `715`) to physical and commercial modes.
+    #[structopt(long, parse(from_os_str))]
+    route_type_mappings: Option<PathBuf>,
+
+    /// Keep the frequencies in the NTFS `frequencies.txt` instead of
+    /// generating a trip for each departure.
+    #[structopt(long)]
+    keep_frequencies: bool,
+
+    /// Coordinate system of the coordinates of the GTFS (e.g. `EPSG:27700`)
+    /// when not in WGS84, requires the `proj` feature.
+    #[structopt(long)]
+    input_crs: Option<CoordinateSystem>,
+
+    /// JSON file giving the parent company (e.g. the holding) of companies,
+    /// by company identifier.
+    #[structopt(long, parse(from_os_str))]
+    company_parents: Option<PathBuf>,
+
+    /// JSON file giving the fare URL, email and phone of networks, by
+    /// network identifier.
+    #[structopt(long, parse(from_os_str))]
+    network_contacts: Option<PathBuf>,
+
+    /// Skip the records of the GTFS files which can't be read and the
+    /// corrupted files of a zip file, with a warning, instead of failing.
+    #[structopt(long)]
+    lenient: bool,
+
+    /// Group the stops without parent station into generated stop areas,
+    /// writing the grouped stop areas in this report file.
+    #[structopt(long, parse(from_os_str))]
+    generate_stop_areas: Option<PathBuf>,
+
+    /// Maximum distance in meters between 2 stops grouped into a generated
+    /// stop area.
+    #[structopt(long, default_value = "200")]
+    stop_area_max_distance: f64,
+
+    /// Minimum similarity of the names of 2 stops grouped into a generated
+    /// stop area, between 0 (no word in common) and 1 (same words).
+    #[structopt(long, default_value = "0.5")]
+    stop_area_min_name_similarity: f64,
+
     /// Current datetime.
     #[structopt(
         short = "x",
@@ -79,6 +130,10 @@ struct Opt {
     /// Waiting time at stop in seconds.
     #[structopt(long, short = "t", default_value = transit_model::TRANSFER_WAITING_TIME)]
     waiting_time: u32,
+
+    /// Describe the columns of a GTFS file (e.g. `stops.txt`) and exit.
+    #[structopt(long)]
+    explain: Option<String>,
 }
 
 fn init_logger() -> slog_scope::GlobalLoggerGuard {
@@ -101,9 +156,18 @@ fn init_logger() -> slog_scope::GlobalLoggerGuard {
 }
 
 fn run(opt: Opt) -> Result<()> {
+    if let Some(file_name) = opt.explain {
+        match transit_model::gtfs::explain(&file_name) {
+            Some(file_schema) => print!("{}", file_schema),
+            None => bail!("{} is not a GTFS file", file_name),
+        }
+        return Ok(());
+    }
+
     info!("Launching gtfs2ntfs...");
 
-    let (contributor, dataset, feed_infos) = read_utils::read_config(opt.config)?;
+    let (contributor, dataset, feed_infos, trip_operators) =
+        read_utils::read_gtfs_config(opt.config)?;
     let mut prefix_conf = PrefixConfiguration::default();
     if let Some(data_prefix) = opt.prefix {
         prefix_conf.set_data_prefix(data_prefix);
@@ -111,6 +175,18 @@ fn run(opt: Opt) -> Result<()> {
     if let Some(schedule_subprefix) = opt.schedule_subprefix {
         prefix_conf.set_schedule_subprefix(schedule_subprefix);
     }
+    let route_type_mappings = match opt.route_type_mappings {
+        Some(path) => transit_model::gtfs::read_route_type_mappings(path)?,
+        None => Default::default(),
+    };
+    let company_parents = match opt.company_parents {
+        Some(path) => transit_model::gtfs::read_company_parents(path)?,
+        None => Default::default(),
+    };
+    let network_contacts = match opt.network_contacts {
+        Some(path) => transit_model::gtfs::read_network_contacts(path)?,
+        None => Default::default(),
+    };
     let configuration = transit_model::gtfs::Configuration {
         contributor,
         dataset,
@@ -118,6 +194,17 @@ fn run(opt: Opt) -> Result<()> {
         prefix_conf: Some(prefix_conf),
         on_demand_transport: opt.odt,
         on_demand_transport_comment: opt.odt_comment,
+        route_type_mappings,
+        keep_frequencies: opt.keep_frequencies,
+        crs: opt.input_crs,
+        company_parents,
+        network_contacts,
+        read_mode: if opt.lenient {
+            ReadMode::Lenient
+        } else {
+            ReadMode::Strict
+        },
+        trip_operators,
     };
 
     let model = if opt.input.is_file() {
@@ -128,6 +215,18 @@ fn run(opt: Opt) -> Result<()> {
         bail!("Invalid input data: must be an existing directory or a ZIP archive");
     };
 
+    let model = match opt.generate_stop_areas {
+        Some(report_path) => stop_areas::generate_stop_areas(
+            model,
+            &stop_areas::StopAreaGenerationConfiguration {
+                max_distance: opt.stop_area_max_distance,
+                min_name_similarity: opt.stop_area_min_name_similarity,
+            },
+            report_path,
+        )?,
+        None => model,
+    };
+
     let model = generates_transfers(
         model,
         opt.max_distance,
@@ -136,7 +235,9 @@ fn run(opt: Opt) -> Result<()> {
         None,
     )?;
 
-    transit_model::ntfs::write(&model, opt.output, opt.current_datetime)?;
+    if let Some(output) = opt.output {
+        transit_model::ntfs::write(&model, output, opt.current_datetime)?;
+    }
     Ok(())
 }
 
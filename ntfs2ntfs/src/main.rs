@@ -14,13 +14,20 @@
 // along with this program.  If not, see
 // <http://www.gnu.org/licenses/>.
 
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use failure::bail;
 use log::info;
 use slog::{slog_o, Drain};
 use slog_async::OverflowStrategy;
 use std::path::PathBuf;
 use structopt::StructOpt;
-use transit_model::{transfers::generates_transfers, Result};
+#[cfg(feature = "proj")]
+use transit_model::crs::CoordinateSystem;
+use transit_model::{
+    clean::{self, CleanOptions},
+    ntfs::{self, ReadOptions, WriteOptions},
+    transfers, zones, Model, Result,
+};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "ntfs2ntfs", about = "Convert an NTFS to an NTFS.")]
@@ -54,6 +61,65 @@ struct Opt {
     /// Waiting time at stop in seconds.
     #[structopt(long, short = "t", default_value = transit_model::TRANSFER_WAITING_TIME)]
     waiting_time: u32,
+
+    /// JSON file configuring the transfers generation by physical mode,
+    /// replacing the max distance, walking speed and waiting time options.
+    #[structopt(long, parse(from_os_str))]
+    transfers_configuration: Option<PathBuf>,
+
+    /// Don't write the legacy fares V1 files (prices.csv, od_fares.csv and
+    /// fares.csv).
+    #[structopt(long)]
+    no_fares_v1: bool,
+
+    /// Write the minimal NTFS profile: only the mandatory files and
+    /// frequencies.txt, without the columns referencing the other files.
+    #[structopt(long)]
+    minimal: bool,
+
+    /// Keep the columns unknown to the model and write them back.
+    #[structopt(long)]
+    keep_extra_columns: bool,
+
+    /// Check the references of the trips and stop times before reading the
+    /// NTFS, failing with all the missing references at once.
+    #[structopt(long)]
+    precheck: bool,
+
+    /// GeoJSON file of the zones of zonal on-demand transport services to
+    /// add as stop points, as in a GTFS `locations.geojson`.
+    #[structopt(long, parse(from_os_str))]
+    zones: Option<PathBuf>,
+
+    /// Merge the duplicated comments and geometries and reorder the stop times whose
+    /// sequences don't follow their times.
+    #[structopt(long)]
+    sanitize: bool,
+
+    /// Start of the validity period [included], e.g. 2019-01-01. Data out of
+    /// the validity period is purged.
+    #[structopt(long, requires = "end-validity-date")]
+    start_validity_date: Option<NaiveDate>,
+
+    /// End of the validity period [included], e.g. 2019-12-31.
+    #[structopt(long, requires = "start-validity-date")]
+    end_validity_date: Option<NaiveDate>,
+
+    /// Reproject the coordinates to this coordinate system (e.g. `EPSG:2154`)
+    /// before writing. The input coordinate system is the `feed_crs` of
+    /// `feed_infos.txt`, WGS84 if absent.
+    #[cfg(feature = "proj")]
+    #[structopt(long)]
+    output_crs: Option<CoordinateSystem>,
+
+    /// Re-read the written NTFS and check it matches the model before
+    /// writing it in the output directory.
+    #[structopt(long)]
+    validate: bool,
+
+    /// Describe the columns of an NTFS file (e.g. `stops.txt`) and exit.
+    #[structopt(long)]
+    explain: Option<String>,
 }
 
 fn init_logger() -> slog_scope::GlobalLoggerGuard {
@@ -76,19 +142,80 @@ fn init_logger() -> slog_scope::GlobalLoggerGuard {
 }
 
 fn run(opt: Opt) -> Result<()> {
+    if let Some(file_name) = opt.explain {
+        match ntfs::explain(&file_name) {
+            Some(file_schema) => print!("{}", file_schema),
+            None => bail!("{} is not a NTFS file", file_name),
+        }
+        return Ok(());
+    }
+
     info!("Launching ntfs2ntfs...");
 
-    let model = transit_model::ntfs::read(opt.input)?;
-    let model = generates_transfers(
-        model,
-        opt.max_distance,
-        opt.walking_speed,
-        opt.waiting_time,
-        None,
-    )?;
+    let read_options = ReadOptions {
+        keep_extra_columns: opt.keep_extra_columns,
+        precheck: opt.precheck,
+        ..Default::default()
+    };
+    let model = ntfs::read_with_options(opt.input, &read_options)?;
+    let model = match opt.zones {
+        Some(path) => {
+            let mut collections = model.into_collections();
+            zones::read_zones(path, &mut collections)?;
+            Model::new(collections)?
+        }
+        None => model,
+    };
+    let model = match opt.transfers_configuration {
+        Some(path) => {
+            let configuration = transfers::read_transfers_configuration(path)?;
+            transfers::generates_transfers_by_physical_mode(model, &configuration, None)?
+        }
+        None => transfers::generates_transfers(
+            model,
+            opt.max_distance,
+            opt.walking_speed,
+            opt.waiting_time,
+            None,
+        )?,
+    };
+
+    let clean_options = CleanOptions {
+        dedup_comments: opt.sanitize,
+        dedup_geometries: opt.sanitize,
+        fix_stop_time_order: opt.sanitize,
+        clean_transfers: true,
+        validity_period: match (opt.start_validity_date, opt.end_validity_date) {
+            (Some(start_date), Some(end_date)) => Some((start_date, end_date)),
+            _ => None,
+        },
+    };
+    let model = clean::clean(model, &clean_options)?;
+
+    #[cfg(feature = "proj")]
+    let model = match opt.output_crs {
+        Some(crs) => {
+            let mut collections = model.into_collections();
+            collections.reproject(crs)?;
+            Model::new(collections)?
+        }
+        None => model,
+    };
 
     if let Some(output) = opt.output {
-        transit_model::ntfs::write(&model, output, opt.current_datetime)?;
+        let options = if opt.minimal {
+            WriteOptions::minimal()
+        } else {
+            WriteOptions {
+                fares_v1: !opt.no_fares_v1,
+                ..Default::default()
+            }
+        };
+        if opt.validate {
+            ntfs::write_validated(&model, output, opt.current_datetime, &options)?;
+        } else {
+            ntfs::write_with_options(&model, output, opt.current_datetime, &options)?;
+        }
     }
     Ok(())
 }
@@ -0,0 +1,83 @@
+// Copyright 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+use log::info;
+use slog::{slog_o, Drain};
+use slog_async::OverflowStrategy;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use transit_model::{diff, Result};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "ntfs_diff", about = "Compare two NTFS datasets.")]
+struct Opt {
+    /// Directory of the old NTFS.
+    #[structopt(long, parse(from_os_str))]
+    old: PathBuf,
+
+    /// Directory of the new NTFS.
+    #[structopt(long, parse(from_os_str))]
+    new: PathBuf,
+
+    /// Output JSON file of the differences.
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: PathBuf,
+}
+
+fn init_logger() -> slog_scope::GlobalLoggerGuard {
+    let decorator = slog_term::TermDecorator::new().stdout().build();
+    let drain = slog_term::CompactFormat::new(decorator).build().fuse();
+    let mut builder = slog_envlogger::LogBuilder::new(drain).filter(None, slog::FilterLevel::Info);
+    if let Ok(s) = std::env::var("RUST_LOG") {
+        builder = builder.parse(&s);
+    }
+    let drain = slog_async::Async::new(builder.build())
+        .chan_size(256) // Double the default size
+        .overflow_strategy(OverflowStrategy::Block)
+        .build()
+        .fuse();
+    let logger = slog::Logger::root(drain, slog_o!());
+
+    let scope_guard = slog_scope::set_global_logger(logger);
+    slog_stdlog::init().unwrap();
+    scope_guard
+}
+
+fn run(opt: Opt) -> Result<()> {
+    info!("Launching ntfs_diff...");
+
+    let diffs = diff::ntfs_diff(opt.old, opt.new)?;
+    for file_diff in &diffs {
+        info!(
+            "{}: {} added, {} removed, {} modified",
+            file_diff.file_name,
+            file_diff.added.len(),
+            file_diff.removed.len(),
+            file_diff.modified.len()
+        );
+    }
+    diff::write_diff(&diffs, opt.output)
+}
+
+fn main() {
+    let _log_guard = init_logger();
+    if let Err(err) = run(Opt::from_args()) {
+        for cause in err.iter_chain() {
+            eprintln!("{}", cause);
+        }
+        std::process::exit(1);
+    }
+}
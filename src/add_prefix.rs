@@ -141,16 +141,16 @@ where
     }
 }
 
-fn add_prefix_on_vehicle_journey_ids(
-    vehicle_journey_ids: &HashMap<(String, u32), String>,
+fn add_prefix_on_vehicle_journey_ids<T: Clone>(
+    vehicle_journey_ids: &HashMap<(String, u32), T>,
     prefix_conf: &PrefixConfiguration,
-) -> HashMap<(String, u32), String> {
+) -> HashMap<(String, u32), T> {
     vehicle_journey_ids
         .iter()
         .map(|((trip_id, sequence), value)| {
             (
                 (prefix_conf.schedule_prefix(trip_id.as_str()), *sequence),
-                value.to_string(),
+                value.clone(),
             )
         })
         .collect()
@@ -191,6 +191,13 @@ impl AddPrefix for Collections {
         self.trip_properties.prefix(prefix_conf);
         self.geometries.prefix(prefix_conf);
         self.admin_stations.prefix(prefix_conf);
+        self.stop_area_memberships.prefix(prefix_conf);
+        self.company_parents.prefix(prefix_conf);
+        self.translations.prefix(prefix_conf);
+        self.booking_rules.prefix(prefix_conf);
+        self.booking_rule_links.prefix(prefix_conf);
+        self.disruptions.prefix(prefix_conf);
+        self.disruption_impacts.prefix(prefix_conf);
         self.prices_v1.prefix(prefix_conf);
         self.od_fares_v1.prefix(prefix_conf);
         self.fares_v1.prefix(prefix_conf);
@@ -199,6 +206,7 @@ impl AddPrefix for Collections {
         self.ticket_uses.prefix(prefix_conf);
         self.ticket_use_perimeters.prefix(prefix_conf);
         self.ticket_use_restrictions.prefix(prefix_conf);
+        self.fare_zones.prefix(prefix_conf);
         self.pathways.prefix(prefix_conf);
         self.levels.prefix(prefix_conf);
         self.grid_calendars.prefix(prefix_conf);
@@ -207,6 +215,8 @@ impl AddPrefix for Collections {
         self.grid_rel_calendar_line.prefix(prefix_conf);
         self.stop_time_headsigns =
             add_prefix_on_vehicle_journey_ids(&self.stop_time_headsigns, prefix_conf);
+        self.stop_time_continuous_stoppings =
+            add_prefix_on_vehicle_journey_ids(&self.stop_time_continuous_stoppings, prefix_conf);
         self.stop_time_ids =
             add_prefix_on_vehicle_journey_ids_and_values(&self.stop_time_ids, prefix_conf);
         self.stop_time_comments =
@@ -0,0 +1,461 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Propagate the accessibility of lines and stops (wheelchair, bike) to the
+//! trip properties of their vehicle journeys and to the equipments of their
+//! stops.
+
+use crate::{
+    changelog::ChangeLog,
+    model::Collections,
+    objects::{Availability, Equipment, ObjectType, StopPoint, TripProperty},
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::{fs::File, path::PathBuf};
+use typed_index_collection::{CollectionWithId, Id, Idx};
+
+#[derive(Debug, Default, Deserialize)]
+struct AccessibilityRules {
+    #[serde(default)]
+    lines: Vec<LineAccessibility>,
+    #[serde(default)]
+    stops: Vec<StopAccessibility>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LineAccessibility {
+    line_id: String,
+    #[serde(default)]
+    wheelchair_accessible: Option<Availability>,
+    #[serde(default)]
+    bike_accepted: Option<Availability>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StopAccessibility {
+    stop_id: String,
+    #[serde(default)]
+    wheelchair_boarding: Option<Availability>,
+    #[serde(default)]
+    bike_accepted: Option<Availability>,
+}
+
+// Returns the identifier of a trip property similar to `trip_property`,
+// creating it if needed
+fn trip_property_id(
+    trip_properties: &mut CollectionWithId<TripProperty>,
+    mut trip_property: TripProperty,
+) -> Result<String> {
+    if let Some(existing) = trip_properties
+        .values()
+        .find(|tp| tp.is_similar(&trip_property))
+    {
+        return Ok(existing.id.clone());
+    }
+    trip_property.id = unused_id(trip_properties);
+    let id = trip_property.id.clone();
+    trip_properties.push(trip_property)?;
+    Ok(id)
+}
+
+// Returns the identifier of an equipment identical to `equipment` (its
+// identifier aside), creating it if needed
+fn equipment_id(
+    equipments: &mut CollectionWithId<Equipment>,
+    mut equipment: Equipment,
+) -> Result<String> {
+    let is_identical = |eq: &Equipment| {
+        Equipment {
+            id: equipment.id.clone(),
+            ..eq.clone()
+        } == equipment
+    };
+    if let Some(existing) = equipments.values().find(|eq| is_identical(eq)) {
+        return Ok(existing.id.clone());
+    }
+    equipment.id = unused_id(equipments);
+    let id = equipment.id.clone();
+    equipments.push(equipment)?;
+    Ok(id)
+}
+
+fn unused_id<T: Id<T>>(collection: &CollectionWithId<T>) -> String {
+    (collection.len()..)
+        .map(|i| format!("accessibility:{}", i))
+        .find(|id| !collection.contains_id(id))
+        .unwrap()
+}
+
+fn apply_line_rule(
+    collections: &mut Collections,
+    rule: LineAccessibility,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    if !collections.lines.contains_id(&rule.line_id) {
+        report.add_error(
+            format!("line {:?} not found, rule skipped", rule.line_id),
+            ReportType::ObjectNotFound,
+        );
+        return Ok(());
+    }
+    let vj_idxs: Vec<_> = collections
+        .vehicle_journeys
+        .iter()
+        .filter(|(_, vj)| {
+            collections
+                .routes
+                .get(&vj.route_id)
+                .map_or(false, |route| route.line_id == rule.line_id)
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+    let mut nb_updated = 0;
+    for vj_idx in &vj_idxs {
+        let old_id = collections.vehicle_journeys[*vj_idx]
+            .trip_property_id
+            .clone();
+        let old_trip_property = old_id
+            .as_ref()
+            .and_then(|id| collections.trip_properties.get(id))
+            .cloned()
+            .unwrap_or_default();
+        let mut trip_property = old_trip_property.clone();
+        if let Some(wheelchair_accessible) = rule.wheelchair_accessible {
+            trip_property.wheelchair_accessible = wheelchair_accessible;
+        }
+        if let Some(bike_accepted) = rule.bike_accepted {
+            trip_property.bike_accepted = bike_accepted;
+        }
+        // the trip already has this accessibility
+        if trip_property == old_trip_property {
+            continue;
+        }
+        let new_id = trip_property_id(&mut collections.trip_properties, trip_property)?;
+        let mut vj = collections.vehicle_journeys.index_mut(*vj_idx);
+        changelog.add(
+            ObjectType::VehicleJourney,
+            &vj.id,
+            "trip_property_id",
+            old_id,
+            Some(new_id.clone()),
+        );
+        vj.trip_property_id = Some(new_id);
+        nb_updated += 1;
+    }
+    report.add(
+        Severity::Info,
+        ReportEntry::new(
+            ReportType::PropertyUpdated,
+            format!(
+                "line {:?}: accessibility set on {} trip(s)",
+                rule.line_id, nb_updated
+            ),
+        )
+        .with_object(ObjectType::Line, &rule.line_id),
+    );
+    Ok(())
+}
+
+fn set_stop_equipment<T, F>(
+    collection: &mut CollectionWithId<T>,
+    idx: Idx<T>,
+    equipments: &mut CollectionWithId<Equipment>,
+    rule: &StopAccessibility,
+    object_type: ObjectType,
+    equipment_id_of: F,
+    changelog: &mut ChangeLog,
+) -> Result<()>
+where
+    T: Id<T>,
+    F: Fn(&mut T) -> &mut Option<String>,
+{
+    let mut stop = collection.index_mut(idx);
+    let stop_id = stop.id().to_string();
+    let stop_equipment_id = equipment_id_of(&mut stop);
+    let old_equipment = stop_equipment_id
+        .as_ref()
+        .and_then(|id| equipments.get(id))
+        .cloned()
+        .unwrap_or_default();
+    let mut equipment = old_equipment.clone();
+    if let Some(wheelchair_boarding) = rule.wheelchair_boarding {
+        equipment.wheelchair_boarding = wheelchair_boarding;
+    }
+    if let Some(bike_accepted) = rule.bike_accepted {
+        equipment.bike_accepted = bike_accepted;
+    }
+    // the stop already has this accessibility
+    if equipment == old_equipment {
+        return Ok(());
+    }
+    let new_id = equipment_id(equipments, equipment)?;
+    changelog.add(
+        object_type,
+        &stop_id,
+        "equipment_id",
+        stop_equipment_id.clone(),
+        Some(new_id.clone()),
+    );
+    *stop_equipment_id = Some(new_id);
+    Ok(())
+}
+
+fn apply_stop_rule(
+    collections: &mut Collections,
+    rule: StopAccessibility,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    let (object_type, stop_point_idxs): (_, Vec<Idx<StopPoint>>) =
+        if let Some(idx) = collections.stop_points.get_idx(&rule.stop_id) {
+            (ObjectType::StopPoint, vec![idx])
+        } else if let Some(idx) = collections.stop_areas.get_idx(&rule.stop_id) {
+            set_stop_equipment(
+                &mut collections.stop_areas,
+                idx,
+                &mut collections.equipments,
+                &rule,
+                ObjectType::StopArea,
+                |sa| &mut sa.equipment_id,
+                changelog,
+            )?;
+            let stop_point_idxs = collections
+                .stop_points
+                .iter()
+                .filter(|(_, sp)| sp.stop_area_id == rule.stop_id)
+                .map(|(idx, _)| idx)
+                .collect();
+            (ObjectType::StopArea, stop_point_idxs)
+        } else {
+            report.add_error(
+                format!("stop {:?} not found, rule skipped", rule.stop_id),
+                ReportType::ObjectNotFound,
+            );
+            return Ok(());
+        };
+    for idx in stop_point_idxs {
+        set_stop_equipment(
+            &mut collections.stop_points,
+            idx,
+            &mut collections.equipments,
+            &rule,
+            ObjectType::StopPoint,
+            |sp| &mut sp.equipment_id,
+            changelog,
+        )?;
+    }
+    report.add(
+        Severity::Info,
+        ReportEntry::new(
+            ReportType::PropertyUpdated,
+            format!(
+                "{} {:?}: accessibility set",
+                object_type.as_str(),
+                rule.stop_id
+            ),
+        )
+        .with_object(object_type, &rule.stop_id),
+    );
+    Ok(())
+}
+
+pub(crate) fn apply_rules(
+    collections: &mut Collections,
+    rule_files: &[PathBuf],
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    for rule_file in rule_files {
+        info!("Reading accessibility rules {:?}", rule_file);
+        let file =
+            File::open(rule_file).with_context(|_| format!("Error reading {:?}", rule_file))?;
+        let rules: AccessibilityRules = serde_json::from_reader(file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        for rule in rules.lines {
+            apply_line_rule(collections, rule, report, changelog)?;
+        }
+        for rule in rules.stops {
+            apply_stop_rule(collections, rule, report, changelog)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        objects::{Line, Route, StopArea, VehicleJourney},
+        test_utils::*,
+    };
+    use pretty_assertions::assert_eq;
+    use std::{fs::File, io::Write};
+
+    fn collections() -> Collections {
+        let mut collections = Collections::default();
+        collections.lines = CollectionWithId::from(Line {
+            id: "line:1".to_string(),
+            ..Default::default()
+        });
+        collections.routes = CollectionWithId::from(Route {
+            id: "route:1".to_string(),
+            line_id: "line:1".to_string(),
+            ..Default::default()
+        });
+        collections.vehicle_journeys = CollectionWithId::new(vec![
+            VehicleJourney {
+                id: "vj:1".to_string(),
+                route_id: "route:1".to_string(),
+                ..Default::default()
+            },
+            VehicleJourney {
+                id: "vj:2".to_string(),
+                route_id: "route:1".to_string(),
+                trip_property_id: Some("tp:1".to_string()),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        collections.trip_properties = CollectionWithId::from(TripProperty {
+            id: "tp:1".to_string(),
+            bike_accepted: Availability::Available,
+            ..Default::default()
+        });
+        collections.stop_areas = CollectionWithId::from(StopArea {
+            id: "sa:1".to_string(),
+            ..Default::default()
+        });
+        collections.stop_points = CollectionWithId::new(vec![
+            StopPoint {
+                id: "sp:1".to_string(),
+                stop_area_id: "sa:1".to_string(),
+                ..Default::default()
+            },
+            StopPoint {
+                id: "sp:2".to_string(),
+                stop_area_id: "sa:1".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        collections
+    }
+
+    #[test]
+    fn propagate_accessibility() {
+        let rules = r#"{
+            "lines": [
+                {"line_id": "line:1", "wheelchair_accessible": "1"},
+                {"line_id": "line:2", "wheelchair_accessible": "1"}
+            ],
+            "stops": [
+                {"stop_id": "sa:1", "wheelchair_boarding": "1"},
+                {"stop_id": "sp:2", "bike_accepted": "2"}
+            ]
+        }"#;
+        let mut collections = collections();
+        let mut report = Report::default();
+        let mut changelog = ChangeLog::default();
+        test_in_tmp_dir(|path| {
+            let rule_path = path.join("accessibility.json");
+            File::create(&rule_path)
+                .unwrap()
+                .write_all(rules.as_bytes())
+                .unwrap();
+            apply_rules(&mut collections, &[rule_path], &mut report, &mut changelog).unwrap();
+        });
+
+        let trip_property = |vj_id: &str| {
+            let vj = collections.vehicle_journeys.get(vj_id).unwrap();
+            let tp_id = vj.trip_property_id.as_ref().unwrap();
+            collections.trip_properties.get(tp_id).unwrap().clone()
+        };
+        let tp1 = trip_property("vj:1");
+        assert_eq!(Availability::Available, tp1.wheelchair_accessible);
+        assert_eq!(Availability::InformationNotAvailable, tp1.bike_accepted);
+        let tp2 = trip_property("vj:2");
+        assert_eq!(Availability::Available, tp2.wheelchair_accessible);
+        assert_eq!(Availability::Available, tp2.bike_accepted);
+        assert_eq!(3, collections.trip_properties.len());
+
+        let equipment = |equipment_id: &Option<String>| {
+            collections
+                .equipments
+                .get(equipment_id.as_ref().unwrap())
+                .unwrap()
+                .clone()
+        };
+        let sa = collections.stop_areas.get("sa:1").unwrap();
+        let sp1 = collections.stop_points.get("sp:1").unwrap();
+        let sp2 = collections.stop_points.get("sp:2").unwrap();
+        assert_eq!(sa.equipment_id, sp1.equipment_id);
+        assert_eq!(
+            Availability::Available,
+            equipment(&sp1.equipment_id).wheelchair_boarding
+        );
+        let sp2_equipment = equipment(&sp2.equipment_id);
+        assert_eq!(Availability::Available, sp2_equipment.wheelchair_boarding);
+        assert_eq!(Availability::NotAvailable, sp2_equipment.bike_accepted);
+        assert_eq!(2, collections.equipments.len());
+
+        let errors: Vec<ReportType> = report
+            .entries(Severity::Error)
+            .iter()
+            .map(|e| e.category)
+            .collect();
+        assert_eq!(vec![ReportType::ObjectNotFound], errors);
+    }
+
+    #[test]
+    fn record_only_actual_changes() {
+        let rules = r#"{
+            "lines": [{"line_id": "line:1", "bike_accepted": "1"}],
+            "stops": [{"stop_id": "sa:1", "wheelchair_boarding": "0"}]
+        }"#;
+        let mut collections = collections();
+        let mut report = Report::default();
+        let mut changelog = ChangeLog::default();
+        test_in_tmp_dir(|path| {
+            let rule_path = path.join("accessibility.json");
+            File::create(&rule_path)
+                .unwrap()
+                .write_all(rules.as_bytes())
+                .unwrap();
+            apply_rules(&mut collections, &[rule_path], &mut report, &mut changelog).unwrap();
+        });
+        // vj:2 already accepts bikes and the stops have no wheelchair
+        // information by default
+        let changes: Vec<(&str, &str)> = changelog
+            .changes()
+            .iter()
+            .map(|change| (change.object_id.as_str(), change.field.as_str()))
+            .collect();
+        assert_eq!(vec![("vj:1", "trip_property_id")], changes);
+        assert_eq!(
+            Some(&"tp:1".to_string()),
+            collections
+                .vehicle_journeys
+                .get("vj:1")
+                .unwrap()
+                .trip_property_id
+                .as_ref()
+        );
+        assert!(collections.equipments.is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+//! Add complementary codes (e.g. the identifiers of an external referential)
+//! to the stops, lines, routes and networks.
+
+use crate::{
+    changelog::ChangeLog,
+    model::Collections,
+    objects::{Codes, ObjectType},
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::path::PathBuf;
+use typed_index_collection::{CollectionWithId, Id};
+
+#[derive(Debug, Deserialize)]
+struct ComplementaryCode {
+    object_type: ObjectType,
+    object_id: String,
+    #[serde(alias = "code_system")]
+    object_system: String,
+    #[serde(alias = "code_value")]
+    object_code: String,
+}
+
+fn insert_code<T>(
+    collection: &mut CollectionWithId<T>,
+    code: ComplementaryCode,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) where
+    T: Codes + Id<T>,
+{
+    match collection.get_idx(&code.object_id) {
+        Some(idx) => {
+            let field = format!("object_code:{}", code.object_system);
+            if collection
+                .index_mut(idx)
+                .codes_mut()
+                .insert((code.object_system, code.object_code.clone()))
+            {
+                changelog.add(
+                    code.object_type,
+                    &code.object_id,
+                    &field,
+                    None,
+                    Some(code.object_code),
+                );
+            }
+        }
+        None => report.add(
+            Severity::Error,
+            ReportEntry::new(
+                ReportType::ObjectNotFound,
+                format!(
+                    "object_type={} object_id={:?} not found",
+                    code.object_type.as_str(),
+                    code.object_id
+                ),
+            )
+            .with_object(code.object_type, &code.object_id),
+        ),
+    }
+}
+
+pub(crate) fn apply_rules(
+    collections: &mut Collections,
+    rule_files: &[PathBuf],
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    for rule_file in rule_files {
+        info!("Reading complementary codes {:?}", rule_file);
+        let mut rdr = csv::Reader::from_path(rule_file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        for code in rdr.deserialize() {
+            let code: ComplementaryCode =
+                code.with_context(|_| format!("Error reading {:?}", rule_file))?;
+            match code.object_type {
+                ObjectType::StopArea => {
+                    insert_code(&mut collections.stop_areas, code, report, changelog)
+                }
+                ObjectType::StopPoint => {
+                    insert_code(&mut collections.stop_points, code, report, changelog)
+                }
+                ObjectType::Network => {
+                    insert_code(&mut collections.networks, code, report, changelog)
+                }
+                ObjectType::Line => insert_code(&mut collections.lines, code, report, changelog),
+                ObjectType::Route => insert_code(&mut collections.routes, code, report, changelog),
+                _ => report.add_error(
+                    format!(
+                        "complementary codes are not supported for object_type={} (object_id={:?})",
+                        code.object_type.as_str(),
+                        code.object_id
+                    ),
+                    ReportType::InvalidValue,
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        objects::{Line, StopPoint},
+        test_utils::*,
+    };
+    use pretty_assertions::assert_eq;
+    use std::{fs::File, io::Write};
+
+    #[test]
+    fn add_complementary_codes() {
+        let rules = "object_type,object_id,code_system,code_value\n\
+                     line,line:1,source,L1\n\
+                     line,line:1,source,L1\n\
+                     stop_point,sp:1,UIC,8727100\n\
+                     stop_point,sp:unknown,UIC,8727101\n\
+                     trip,vj:1,source,1";
+        test_in_tmp_dir(|path| {
+            let rule_path = path.join("complementary_codes.csv");
+            File::create(&rule_path)
+                .unwrap()
+                .write_all(rules.as_bytes())
+                .unwrap();
+            let mut collections = Collections::default();
+            collections.lines = CollectionWithId::from(Line {
+                id: "line:1".to_string(),
+                ..Default::default()
+            });
+            collections.stop_points = CollectionWithId::from(StopPoint {
+                id: "sp:1".to_string(),
+                ..Default::default()
+            });
+            let mut report = Report::default();
+            let mut changelog = ChangeLog::default();
+            apply_rules(&mut collections, &[rule_path], &mut report, &mut changelog).unwrap();
+
+            let line_codes: Vec<_> = collections
+                .lines
+                .get("line:1")
+                .unwrap()
+                .codes
+                .iter()
+                .collect();
+            assert_eq!(vec![&("source".to_string(), "L1".to_string())], line_codes);
+            let stop_point_codes: Vec<_> = collections
+                .stop_points
+                .get("sp:1")
+                .unwrap()
+                .codes
+                .iter()
+                .collect();
+            assert_eq!(
+                vec![&("UIC".to_string(), "8727100".to_string())],
+                stop_point_codes
+            );
+            let categories: Vec<ReportType> =
+                report.errors.iter().map(|error| error.category).collect();
+            assert_eq!(
+                vec![ReportType::ObjectNotFound, ReportType::InvalidValue],
+                categories
+            );
+            // the duplicated code is only logged once
+            let changes: Vec<(&str, &str)> = changelog
+                .changes()
+                .iter()
+                .map(|change| (change.object_id.as_str(), change.field.as_str()))
+                .collect();
+            assert_eq!(
+                vec![
+                    ("line:1", "object_code:source"),
+                    ("sp:1", "object_code:UIC")
+                ],
+                changes
+            );
+        });
+    }
+}
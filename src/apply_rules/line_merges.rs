@@ -0,0 +1,857 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Merge the lines sharing a code (e.g. the same line coming from several
+//! source files of an operator), with their routes and vehicle journeys.
+
+use crate::{
+    changelog::ChangeLog,
+    model::Collections,
+    objects::{Line, ObjectType, Time, VehicleJourney},
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+};
+use typed_index_collection::{Collection, CollectionWithId};
+
+// Value of `object_code` merging the lines for every code of the system
+const ANY_CODE: &str = "*";
+
+#[derive(Debug, Deserialize)]
+struct LineMerge {
+    object_system: String,
+    object_code: String,
+    // Maximum number of seconds a vehicle journey of a merged line can run
+    // after (forward) or before (backward) a vehicle journey of the kept line
+    // to be considered as the same journey
+    #[serde(default)]
+    max_forward_tolerance: Option<u32>,
+    #[serde(default)]
+    max_backward_tolerance: Option<u32>,
+    // Dataset of the vehicle journey kept when two are the same journey (the
+    // one of the kept line otherwise)
+    #[serde(default)]
+    preferred_dataset_id: Option<String>,
+}
+
+impl LineMerge {
+    // The forward and backward tolerances, if the same journeys of the
+    // merged lines are to be reconciled
+    fn tolerance(&self) -> Option<(u32, u32)> {
+        if self.max_forward_tolerance.is_none() && self.max_backward_tolerance.is_none() {
+            None
+        } else {
+            Some((
+                self.max_forward_tolerance.unwrap_or(0),
+                self.max_backward_tolerance.unwrap_or(0),
+            ))
+        }
+    }
+}
+
+// Lines sharing a code, by code
+fn line_groups(lines: &CollectionWithId<Line>, rule: &LineMerge) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for line in lines.values() {
+        for (system, code) in &line.codes {
+            if *system == rule.object_system
+                && (rule.object_code == ANY_CODE || *code == rule.object_code)
+            {
+                groups
+                    .entry(code.clone())
+                    .or_insert_with(Vec::new)
+                    .push(line.id.clone());
+            }
+        }
+    }
+    groups
+}
+
+enum Reconciliation {
+    Unchanged,
+    // The missing value of the kept line taken from the merged line
+    Filled(String),
+    // The different values of the kept and merged lines
+    Conflict(String, String),
+}
+
+fn reconcile_string(kept: &str, merged: &str) -> Reconciliation {
+    if kept == merged {
+        Reconciliation::Unchanged
+    } else {
+        Reconciliation::Conflict(kept.to_string(), merged.to_string())
+    }
+}
+
+fn reconcile_option<T>(kept: &mut Option<T>, merged: &Option<T>) -> Reconciliation
+where
+    T: Clone + PartialEq + ToString,
+{
+    match (kept.as_ref(), merged) {
+        (_, None) => Reconciliation::Unchanged,
+        (None, Some(merged)) => {
+            *kept = Some(merged.clone());
+            Reconciliation::Filled(merged.to_string())
+        }
+        (Some(kept), Some(merged)) if kept != merged => {
+            Reconciliation::Conflict(kept.to_string(), merged.to_string())
+        }
+        (Some(_), Some(_)) => Reconciliation::Unchanged,
+    }
+}
+
+// Reconciles the attributes of the kept line with a merged line: the
+// missing attributes are taken from the merged line, the different ones are
+// reported
+fn merge_attributes(
+    kept_line: &mut Line,
+    merged_line: &Line,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) {
+    let reconciliations = vec![
+        (
+            "line_name",
+            reconcile_string(&kept_line.name, &merged_line.name),
+        ),
+        (
+            "network_id",
+            reconcile_string(&kept_line.network_id, &merged_line.network_id),
+        ),
+        (
+            "commercial_mode_id",
+            reconcile_string(
+                &kept_line.commercial_mode_id,
+                &merged_line.commercial_mode_id,
+            ),
+        ),
+        (
+            "line_code",
+            reconcile_option(&mut kept_line.code, &merged_line.code),
+        ),
+        (
+            "forward_line_name",
+            reconcile_option(&mut kept_line.forward_name, &merged_line.forward_name),
+        ),
+        (
+            "backward_line_name",
+            reconcile_option(&mut kept_line.backward_name, &merged_line.backward_name),
+        ),
+        (
+            "line_color",
+            reconcile_option(&mut kept_line.color, &merged_line.color),
+        ),
+        (
+            "line_text_color",
+            reconcile_option(&mut kept_line.text_color, &merged_line.text_color),
+        ),
+        (
+            "line_sort_order",
+            reconcile_option(&mut kept_line.sort_order, &merged_line.sort_order),
+        ),
+        (
+            "geometry_id",
+            reconcile_option(&mut kept_line.geometry_id, &merged_line.geometry_id),
+        ),
+        (
+            "line_opening_time",
+            reconcile_option(&mut kept_line.opening_time, &merged_line.opening_time),
+        ),
+        (
+            "line_closing_time",
+            reconcile_option(&mut kept_line.closing_time, &merged_line.closing_time),
+        ),
+    ];
+    for (field, reconciliation) in reconciliations {
+        match reconciliation {
+            Reconciliation::Unchanged => {}
+            Reconciliation::Filled(value) => {
+                changelog.add(ObjectType::Line, &kept_line.id, field, None, Some(value))
+            }
+            Reconciliation::Conflict(kept_value, merged_value) => report.add(
+                Severity::Warning,
+                ReportEntry::new(
+                    ReportType::Conflict,
+                    format!(
+                        "line {:?} merged into line {:?}: {} {:?} kept instead of {:?}",
+                        merged_line.id, kept_line.id, field, kept_value, merged_value
+                    ),
+                )
+                .with_object(ObjectType::Line, &kept_line.id),
+            ),
+        }
+    }
+    kept_line.codes.extend(merged_line.codes.iter().cloned());
+    kept_line
+        .object_properties
+        .extend(merged_line.object_properties.iter().cloned());
+    kept_line
+        .comment_links
+        .extend(merged_line.comment_links.iter().cloned());
+}
+
+// Whether the vehicle journey `other` runs like `reference`: at the same
+// stops on the same days, at most `max_forward` seconds after and
+// `max_backward` seconds before it
+fn is_same_journey(
+    collections: &Collections,
+    reference: &VehicleJourney,
+    other: &VehicleJourney,
+    (max_forward, max_backward): (u32, u32),
+) -> bool {
+    if reference.stop_times.is_empty() || reference.stop_times.len() != other.stop_times.len() {
+        return false;
+    }
+    let same_days = reference.service_id == other.service_id
+        || match (
+            collections.calendars.get(&reference.service_id),
+            collections.calendars.get(&other.service_id),
+        ) {
+            (Some(reference_calendar), Some(other_calendar)) => {
+                reference_calendar.dates == other_calendar.dates
+            }
+            _ => false,
+        };
+    let within_tolerance = |reference: Time, other: Time| {
+        let (reference, other) = (reference.total_seconds(), other.total_seconds());
+        if other >= reference {
+            other - reference <= max_forward
+        } else {
+            reference - other <= max_backward
+        }
+    };
+    same_days
+        && reference
+            .stop_times
+            .iter()
+            .zip(&other.stop_times)
+            .all(|(reference_st, other_st)| {
+                reference_st.stop_point_idx == other_st.stop_point_idx
+                    && within_tolerance(reference_st.arrival_time, other_st.arrival_time)
+                    && within_tolerance(reference_st.departure_time, other_st.departure_time)
+            })
+}
+
+// Removes the vehicle journeys of the merged lines running like a vehicle
+// journey of the kept line, within the tolerance of the `rule`: only one of
+// them is kept, the one of the preferred dataset if any
+fn reconcile_vehicle_journeys(
+    collections: &mut Collections,
+    rule: &LineMerge,
+    tolerance: (u32, u32),
+    kept_vj_ids: &[String],
+    merged_vj_ids: &[String],
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    let mut removed_vj_ids: HashSet<String> = HashSet::new();
+    {
+        let collections: &Collections = collections;
+        let mut matched_vj_ids: HashSet<&str> = HashSet::new();
+        for merged_vj in merged_vj_ids
+            .iter()
+            .filter_map(|id| collections.vehicle_journeys.get(id))
+        {
+            let reference = kept_vj_ids
+                .iter()
+                .filter(|id| !matched_vj_ids.contains(id.as_str()))
+                .filter_map(|id| collections.vehicle_journeys.get(id))
+                .find(|reference| is_same_journey(collections, reference, merged_vj, tolerance));
+            let reference = match reference {
+                Some(reference) => reference,
+                None => continue,
+            };
+            matched_vj_ids.insert(&reference.id);
+            let prefers_merged = rule
+                .preferred_dataset_id
+                .as_ref()
+                .map_or(false, |dataset_id| {
+                    *dataset_id == merged_vj.dataset_id && *dataset_id != reference.dataset_id
+                });
+            let (kept_vj, removed_vj) = if prefers_merged {
+                (merged_vj, reference)
+            } else {
+                (reference, merged_vj)
+            };
+            report.add(
+                Severity::Info,
+                ReportEntry::new(
+                    ReportType::ObjectMerged,
+                    format!(
+                        "trip {:?} merged into trip {:?} running at the same stops and times",
+                        removed_vj.id, kept_vj.id
+                    ),
+                )
+                .with_object(ObjectType::VehicleJourney, &removed_vj.id),
+            );
+            changelog.add(
+                ObjectType::VehicleJourney,
+                &removed_vj.id,
+                "merged_into",
+                None,
+                Some(kept_vj.id.clone()),
+            );
+            removed_vj_ids.insert(removed_vj.id.clone());
+        }
+    }
+    if !removed_vj_ids.is_empty() {
+        let mut vehicle_journeys = collections.vehicle_journeys.take();
+        vehicle_journeys.retain(|vj| !removed_vj_ids.contains(&vj.id));
+        collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
+    }
+    Ok(())
+}
+
+fn remap_objects<T, F>(
+    collection: &mut Collection<T>,
+    new_ids: &HashMap<(ObjectType, String), String>,
+    object: F,
+) where
+    F: Fn(&mut T) -> (&ObjectType, &mut String),
+{
+    let mut objects = collection.take();
+    for obj in &mut objects {
+        let (object_type, object_id) = object(obj);
+        if let Some(new_id) = new_ids.get(&(object_type.clone(), object_id.clone())) {
+            *object_id = new_id.clone();
+        }
+    }
+    *collection = Collection::new(objects);
+}
+
+// Merges the `line_ids` into the line with the most vehicle journeys (the
+// first by identifier on a tie)
+fn merge_lines(
+    collections: &mut Collections,
+    rule: &LineMerge,
+    code: &str,
+    mut line_ids: Vec<String>,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    let mut nb_vjs: HashMap<&str, usize> = HashMap::new();
+    for vj in collections.vehicle_journeys.values() {
+        if let Some(route) = collections.routes.get(&vj.route_id) {
+            *nb_vjs.entry(route.line_id.as_str()).or_insert(0) += 1;
+        }
+    }
+    let nb_vjs_of = |line_id: &String| nb_vjs.get(line_id.as_str()).cloned().unwrap_or(0);
+    line_ids.sort_by(|id1, id2| {
+        nb_vjs_of(id2)
+            .cmp(&nb_vjs_of(id1))
+            .then_with(|| id1.cmp(id2))
+    });
+    let kept_id = line_ids.remove(0);
+    let merged_ids: HashSet<String> = line_ids.into_iter().collect();
+    let mut kept_vj_ids = vec![];
+    let mut merged_vj_ids = vec![];
+    for vj in collections.vehicle_journeys.values() {
+        if let Some(route) = collections.routes.get(&vj.route_id) {
+            if route.line_id == kept_id {
+                kept_vj_ids.push(vj.id.clone());
+            } else if merged_ids.contains(&route.line_id) {
+                merged_vj_ids.push(vj.id.clone());
+            }
+        }
+    }
+
+    // the route of the kept line with each name and direction
+    let kept_routes: HashMap<(String, Option<String>), String> = collections
+        .routes
+        .values()
+        .filter(|route| route.line_id == kept_id)
+        .map(|route| {
+            (
+                (route.name.clone(), route.direction_type.clone()),
+                route.id.clone(),
+            )
+        })
+        .collect();
+    let mut new_ids: HashMap<(ObjectType, String), String> = HashMap::new();
+    let mut merged_ids_sorted: Vec<&String> = merged_ids.iter().collect();
+    merged_ids_sorted.sort();
+    for merged_id in merged_ids_sorted {
+        let merged_line = match collections.lines.get(merged_id) {
+            Some(line) => line.clone(),
+            None => continue,
+        };
+        if let Some(mut kept_line) = collections.lines.get_mut(&kept_id) {
+            merge_attributes(&mut *kept_line, &merged_line, report, changelog);
+        }
+        report.add(
+            Severity::Info,
+            ReportEntry::new(
+                ReportType::ObjectMerged,
+                format!(
+                    "line {:?} merged into line {:?} sharing the code {:?}",
+                    merged_id, kept_id, code
+                ),
+            )
+            .with_object(ObjectType::Line, merged_id),
+        );
+        changelog.add(
+            ObjectType::Line,
+            merged_id,
+            "merged_into",
+            None,
+            Some(kept_id.clone()),
+        );
+        new_ids.insert((ObjectType::Line, merged_id.clone()), kept_id.clone());
+    }
+
+    // the routes of the merged lines are moved to the kept line, or merged
+    // into its route with the same name and direction
+    let mut routes = collections.routes.take();
+    let mut merged_routes: HashMap<String, String> = HashMap::new();
+    for route in &mut routes {
+        if !merged_ids.contains(&route.line_id) {
+            continue;
+        }
+        let key = (route.name.clone(), route.direction_type.clone());
+        match kept_routes.get(&key) {
+            Some(kept_route_id) => {
+                report.add(
+                    Severity::Info,
+                    ReportEntry::new(
+                        ReportType::ObjectMerged,
+                        format!(
+                            "route {:?} merged into route {:?} of line {:?}",
+                            route.id, kept_route_id, kept_id
+                        ),
+                    )
+                    .with_object(ObjectType::Route, &route.id),
+                );
+                merged_routes.insert(route.id.clone(), kept_route_id.clone());
+            }
+            None => {
+                changelog.add(
+                    ObjectType::Route,
+                    &route.id,
+                    "line_id",
+                    Some(route.line_id.clone()),
+                    Some(kept_id.clone()),
+                );
+                route.line_id = kept_id.clone();
+            }
+        }
+    }
+    // the codes, properties and comments of a merged route are kept on the
+    // route it's merged into
+    let mut removed_routes = vec![];
+    routes.retain(|route| {
+        let kept = !merged_routes.contains_key(&route.id);
+        if !kept {
+            removed_routes.push(route.clone());
+        }
+        kept
+    });
+    for removed_route in removed_routes {
+        let kept_route_id = &merged_routes[&removed_route.id];
+        if let Some(route) = routes.iter_mut().find(|route| route.id == *kept_route_id) {
+            route.codes.extend(removed_route.codes);
+            route
+                .object_properties
+                .extend(removed_route.object_properties);
+            route.comment_links.extend(removed_route.comment_links);
+        }
+    }
+    collections.routes = CollectionWithId::new(routes)?;
+    for (merged_route_id, kept_route_id) in &merged_routes {
+        new_ids.insert(
+            (ObjectType::Route, merged_route_id.clone()),
+            kept_route_id.clone(),
+        );
+    }
+
+    let mut vehicle_journeys = collections.vehicle_journeys.take();
+    for vj in &mut vehicle_journeys {
+        if let Some(kept_route_id) = merged_routes.get(&vj.route_id) {
+            changelog.add(
+                ObjectType::VehicleJourney,
+                &vj.id,
+                "route_id",
+                Some(vj.route_id.clone()),
+                Some(kept_route_id.clone()),
+            );
+            vj.route_id = kept_route_id.clone();
+        }
+    }
+    collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
+
+    // the references to the merged lines and routes
+    let mut lines = collections.lines.take();
+    lines.retain(|line| !merged_ids.contains(&line.id));
+    collections.lines = CollectionWithId::new(lines)?;
+    remap_objects(
+        &mut collections.ticket_use_perimeters,
+        &new_ids,
+        |perimeter| (&perimeter.object_type, &mut perimeter.object_id),
+    );
+    remap_objects(&mut collections.booking_rule_links, &new_ids, |link| {
+        (&link.object_type, &mut link.object_id)
+    });
+    remap_objects(&mut collections.disruption_impacts, &new_ids, |impact| {
+        (&impact.object_type, &mut impact.object_id)
+    });
+    let mut grid_rel_calendar_lines = collections.grid_rel_calendar_line.take();
+    for rel in &mut grid_rel_calendar_lines {
+        if merged_ids.contains(&rel.line_id) {
+            rel.line_id = kept_id.clone();
+        }
+    }
+    collections.grid_rel_calendar_line = Collection::new(grid_rel_calendar_lines);
+    // the translations of the kept objects are used
+    let mut translations = collections.translations.take();
+    translations.retain(|translation| {
+        !new_ids.contains_key(&(
+            translation.object_type.clone(),
+            translation.object_id.clone(),
+        ))
+    });
+    collections.translations = Collection::new(translations);
+
+    if let Some(tolerance) = rule.tolerance() {
+        reconcile_vehicle_journeys(
+            collections,
+            rule,
+            tolerance,
+            &kept_vj_ids,
+            &merged_vj_ids,
+            report,
+            changelog,
+        )?;
+    }
+    Ok(())
+}
+
+pub(crate) fn apply_rules(
+    collections: &mut Collections,
+    rule_files: &[PathBuf],
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    for rule_file in rule_files {
+        info!("Reading line merges {:?}", rule_file);
+        let mut rdr = csv::Reader::from_path(rule_file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        for rule in rdr.deserialize() {
+            let rule: LineMerge =
+                rule.with_context(|_| format!("Error reading {:?}", rule_file))?;
+            let groups = line_groups(&collections.lines, &rule);
+            if groups.is_empty() {
+                report.add_error(
+                    format!(
+                        "no line with the code {:?} in {:?}, lines not merged",
+                        rule.object_code, rule.object_system
+                    ),
+                    ReportType::ObjectNotFound,
+                );
+            }
+            for (code, line_ids) in groups {
+                // with `*`, a line may have been merged for another code
+                let line_ids: Vec<String> = line_ids
+                    .into_iter()
+                    .filter(|line_id| collections.lines.contains_id(line_id))
+                    .collect();
+                if line_ids.len() > 1 {
+                    merge_lines(collections, &rule, &code, line_ids, report, changelog)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        objects::{Rgb, Route, StopPoint, StopTime, VehicleJourney},
+        test_utils::*,
+    };
+    use pretty_assertions::assert_eq;
+    use std::{fs::File, io::Write};
+
+    fn line(id: &str, name: &str, code: &str, color: Option<Rgb>) -> Line {
+        let mut line = Line {
+            id: id.to_string(),
+            name: name.to_string(),
+            color,
+            ..Default::default()
+        };
+        line.codes.insert(("source".to_string(), code.to_string()));
+        line
+    }
+
+    fn route(id: &str, line_id: &str, name: &str) -> Route {
+        Route {
+            id: id.to_string(),
+            name: name.to_string(),
+            line_id: line_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn vehicle_journey(id: &str, route_id: &str) -> VehicleJourney {
+        VehicleJourney {
+            id: id.to_string(),
+            route_id: route_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn collections() -> Collections {
+        let red = Rgb {
+            red: 255,
+            green: 0,
+            blue: 0,
+        };
+        let mut collections = Collections::default();
+        collections.lines = CollectionWithId::new(vec![
+            line("l1", "Line 1", "L1", None),
+            line("l2", "Line 1 bis", "L1", Some(red)),
+            line("l3", "Line 3", "L3", None),
+        ])
+        .unwrap();
+        collections.routes = CollectionWithId::new(vec![
+            route("r1", "l1", "A"),
+            route("r2", "l2", "A"),
+            route("r3", "l2", "B"),
+            route("r4", "l3", "A"),
+        ])
+        .unwrap();
+        collections.vehicle_journeys = CollectionWithId::new(vec![
+            vehicle_journey("vj1", "r1"),
+            vehicle_journey("vj2", "r1"),
+            vehicle_journey("vj3", "r2"),
+            vehicle_journey("vj4", "r4"),
+        ])
+        .unwrap();
+        collections
+    }
+
+    fn apply(rules: &str, collections: &mut Collections) -> (Report, ChangeLog) {
+        let mut report = Report::default();
+        let mut changelog = ChangeLog::default();
+        test_in_tmp_dir(|path| {
+            let rule_path = path.join("line_merges.csv");
+            File::create(&rule_path)
+                .unwrap()
+                .write_all(rules.as_bytes())
+                .unwrap();
+            apply_rules(collections, &[rule_path], &mut report, &mut changelog).unwrap();
+        });
+        (report, changelog)
+    }
+
+    #[test]
+    fn merge_lines_sharing_a_code() {
+        let mut collections = collections();
+        let (report, changelog) = apply(
+            "object_system,object_code\nsource,L1\nsource,L2\n",
+            &mut collections,
+        );
+
+        let line_ids: Vec<&str> = collections.lines.values().map(|l| l.id.as_str()).collect();
+        assert_eq!(vec!["l1", "l3"], line_ids);
+        // l1 has the most vehicle journeys: its name is kept, its missing
+        // color is taken from l2
+        let line = collections.lines.get("l1").unwrap();
+        assert_eq!("Line 1", line.name);
+        assert_eq!("FF0000", line.color.as_ref().unwrap().to_string());
+
+        let routes: Vec<(&str, &str)> = collections
+            .routes
+            .values()
+            .map(|r| (r.id.as_str(), r.line_id.as_str()))
+            .collect();
+        assert_eq!(vec![("r1", "l1"), ("r3", "l1"), ("r4", "l3")], routes);
+        assert_eq!(
+            "r1",
+            collections.vehicle_journeys.get("vj3").unwrap().route_id
+        );
+
+        let warnings: Vec<ReportType> = report.warnings.iter().map(|e| e.category).collect();
+        assert_eq!(vec![ReportType::Conflict], warnings);
+        let infos: Vec<ReportType> = report.infos.iter().map(|e| e.category).collect();
+        assert_eq!(vec![ReportType::ObjectMerged; 2], infos);
+        let errors: Vec<ReportType> = report.errors.iter().map(|e| e.category).collect();
+        assert_eq!(vec![ReportType::ObjectNotFound], errors);
+
+        let fields: Vec<(&str, &str)> = changelog
+            .changes()
+            .iter()
+            .map(|change| (change.object_id.as_str(), change.field.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("l1", "line_color"),
+                ("l2", "merged_into"),
+                ("r3", "line_id"),
+                ("vj3", "route_id"),
+            ],
+            fields
+        );
+    }
+
+    #[test]
+    fn merge_routes_with_their_properties_and_comments() {
+        let mut collections = collections();
+        {
+            let mut route = collections.routes.get_mut("r2").unwrap();
+            route.codes.insert(("source".to_string(), "R2".to_string()));
+            route
+                .object_properties
+                .insert(("night".to_string(), "true".to_string()));
+            route.comment_links.insert("comment:1".to_string());
+        }
+        apply("object_system,object_code\nsource,L1\n", &mut collections);
+
+        assert!(collections.routes.get("r2").is_none());
+        let route = collections.routes.get("r1").unwrap();
+        assert!(route
+            .codes
+            .contains(&("source".to_string(), "R2".to_string())));
+        assert!(route
+            .object_properties
+            .contains(&("night".to_string(), "true".to_string())));
+        assert!(route.comment_links.contains("comment:1"));
+    }
+
+    #[test]
+    fn merge_lines_for_every_code() {
+        let mut collections = collections();
+        collections
+            .lines
+            .get_mut("l3")
+            .unwrap()
+            .codes
+            .insert(("source".to_string(), "L1".to_string()));
+        let (report, _) = apply("object_system,object_code\nsource,*\n", &mut collections);
+
+        let line_ids: Vec<&str> = collections.lines.values().map(|l| l.id.as_str()).collect();
+        assert_eq!(vec!["l1"], line_ids);
+        assert!(report.errors.is_empty());
+    }
+
+    fn set_stop_times(
+        collections: &mut Collections,
+        vj_id: &str,
+        dataset_id: &str,
+        times: &[Time],
+    ) {
+        let stop_point_idxs = ["sp1", "sp2"]
+            .iter()
+            .map(|id| collections.stop_points.get_idx(id).unwrap())
+            .collect::<Vec<_>>();
+        let mut vj = collections.vehicle_journeys.get_mut(vj_id).unwrap();
+        vj.dataset_id = dataset_id.to_string();
+        vj.stop_times = times
+            .iter()
+            .zip(stop_point_idxs)
+            .enumerate()
+            .map(|(sequence, (time, stop_point_idx))| StopTime {
+                stop_point_idx,
+                sequence: sequence as u32,
+                arrival_time: *time,
+                departure_time: *time,
+                boarding_duration: 0,
+                alighting_duration: 0,
+                pickup_type: 0,
+                drop_off_type: 0,
+                datetime_estimated: false,
+                local_zone_id: None,
+                precision: None,
+            })
+            .collect();
+    }
+
+    fn collections_with_stop_times() -> Collections {
+        let mut collections = collections();
+        collections.stop_points = CollectionWithId::new(vec![
+            StopPoint {
+                id: "sp1".to_string(),
+                ..Default::default()
+            },
+            StopPoint {
+                id: "sp2".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        let times = [
+            ("vj1", "d1", [Time::new(8, 0, 0), Time::new(8, 10, 0)]),
+            ("vj2", "d1", [Time::new(9, 0, 0), Time::new(9, 10, 0)]),
+            ("vj3", "d2", [Time::new(8, 0, 30), Time::new(8, 10, 45)]),
+        ];
+        for (vj_id, dataset_id, vj_times) in &times {
+            set_stop_times(&mut collections, vj_id, dataset_id, vj_times);
+        }
+        collections
+    }
+
+    #[test]
+    fn reconcile_same_journeys_within_tolerance() {
+        let mut collections = collections_with_stop_times();
+        let (report, changelog) = apply(
+            "object_system,object_code,max_forward_tolerance,max_backward_tolerance,preferred_dataset_id\n\
+             source,L1,60,60,d2\n",
+            &mut collections,
+        );
+        let vj_ids: Vec<&str> = collections
+            .vehicle_journeys
+            .values()
+            .map(|vj| vj.id.as_str())
+            .collect();
+        assert_eq!(vec!["vj2", "vj3", "vj4"], vj_ids);
+        assert_eq!(
+            "r1",
+            collections.vehicle_journeys.get("vj3").unwrap().route_id
+        );
+        let infos: Vec<ReportType> = report.infos.iter().map(|e| e.category).collect();
+        assert_eq!(vec![ReportType::ObjectMerged; 3], infos);
+        assert!(changelog
+            .changes()
+            .iter()
+            .any(|change| change.object_id == "vj1" && change.field == "merged_into"));
+
+        // without preferred dataset, the journey of the kept line is kept
+        let mut collections = collections_with_stop_times();
+        apply(
+            "object_system,object_code,max_forward_tolerance,max_backward_tolerance,preferred_dataset_id\n\
+             source,L1,60,0,\n",
+            &mut collections,
+        );
+        let vj_ids: Vec<&str> = collections
+            .vehicle_journeys
+            .values()
+            .map(|vj| vj.id.as_str())
+            .collect();
+        assert_eq!(vec!["vj1", "vj2", "vj4"], vj_ids);
+
+        // out of the tolerance, the journeys are kept
+        let mut collections = collections_with_stop_times();
+        apply(
+            "object_system,object_code,max_forward_tolerance\nsource,L1,30\n",
+            &mut collections,
+        );
+        assert_eq!(4, collections.vehicle_journeys.len());
+    }
+}
@@ -0,0 +1,455 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+//! See function apply_rules
+
+mod accessibility;
+mod complementary_codes;
+mod line_merges;
+mod physical_modes;
+mod property_rules;
+mod route_variants;
+mod tag_rules;
+mod vehicle_journey_codes;
+
+use crate::{
+    changelog::ChangeLog,
+    model::{Collections, Model},
+    objects::ObjectType,
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use log::info;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+/// Rules files applied by [apply_rules](./fn.apply_rules.html)
+#[derive(Debug, Default)]
+pub struct Rules {
+    /// CSV files adding codes to stop areas, stop points, networks, lines and
+    /// routes, with the columns `object_type`, `object_id`, `object_system`
+    /// and `object_code` (`code_system` and `code_value` are also accepted).
+    pub complementary_code_files: Vec<PathBuf>,
+    /// CSV files merging the lines sharing a code (e.g. the same line coming
+    /// from several source files), with the columns `object_system` and
+    /// `object_code` (`*` merging the lines for every code of the system).
+    ///
+    /// The line with the most vehicle journeys is kept: its missing
+    /// attributes are taken from the merged lines and its different ones are
+    /// reported as conflicts. The routes of the merged lines are moved to the
+    /// kept line, or merged into its route with the same name and direction.
+    ///
+    /// With the optional `max_forward_tolerance` and `max_backward_tolerance`
+    /// columns (in seconds), a vehicle journey of a merged line running at
+    /// the same stops and days as a vehicle journey of the kept line, at most
+    /// this long after or before it, is considered as the same journey: only
+    /// one of them is kept, the one of the optional `preferred_dataset_id` or
+    /// else the one of the kept line.
+    pub line_merge_files: Vec<PathBuf>,
+    /// CSV files attaching operational codes (e.g. the identifiers of an AVL
+    /// system) to vehicle journeys, with the columns `line_id`,
+    /// `departure_time`, `days`, `object_system` and `object_code`.
+    ///
+    /// `departure_time` is the departure time at the first stop of the
+    /// vehicle journey and `days` its days of operation, from monday to
+    /// sunday (`1111100` for a vehicle journey running on weekdays only).
+    pub vehicle_journey_codes_files: Vec<PathBuf>,
+    /// CSV (or JSON, with a `.json` extension) files updating properties of
+    /// networks, lines, routes, trips, stop areas and stop points, with the
+    /// fields `object_type`, `object_id`, `object_system`, `property_name`,
+    /// `property_old_value` and `property_value`.
+    ///
+    /// The object is found by its identifier, or by its code when
+    /// `object_system` is given. A rule is only applied if the current value
+    /// of the property is `property_old_value` (`*` matches any value).
+    pub property_rule_files: Vec<PathBuf>,
+    /// CSV files labeling route variants (e.g. a summer route or a detour)
+    /// with the period when they run, with the columns `route_id`,
+    /// `variant_label`, `start_date`, `end_date` (`YYYYMMDD`) and an optional
+    /// `variant_description`.
+    ///
+    /// The variant is exported in the object properties of the route and in
+    /// a comment linked to it.
+    pub route_variant_files: Vec<PathBuf>,
+    /// JSON files setting the accessibility (wheelchair, bike) of lines and
+    /// stops, with a `lines` list of objects with a `line_id` and optional
+    /// `wheelchair_accessible` and `bike_accepted` fields, and a `stops` list
+    /// of objects with a `stop_id` and optional `wheelchair_boarding` and
+    /// `bike_accepted` fields (`0` for unknown, `1` for available and `2` for
+    /// not available).
+    ///
+    /// The accessibility of a line is set on the trip properties of its
+    /// vehicle journeys, the one of a stop on its equipment; the one of a
+    /// stop area is also set on its stop points. Trip properties and
+    /// equipments are created as needed.
+    pub accessibility_files: Vec<PathBuf>,
+    /// CSV files attaching tags to lines, routes, trips, stop areas and stop
+    /// points (see [tags](../tags/index.html)), with the columns
+    /// `object_type`, `object_id` and `tag`.
+    pub tag_files: Vec<PathBuf>,
+    /// CSV files listing the physical modes allowed for the lines of a
+    /// commercial mode, with the columns `commercial_mode_id` and
+    /// `physical_mode_id`, checked once the other rules are applied.
+    ///
+    /// The lines with vehicle journeys of a physical mode not allowed for
+    /// their commercial mode are reported (the commercial modes not listed
+    /// allow every physical mode).
+    pub physical_mode_files: Vec<PathBuf>,
+    /// Whether the vehicle journeys of a physical mode not allowed by the
+    /// `physical_mode_files` are fixed: they are given the allowed physical
+    /// mode of the most vehicle journeys of their line, or else the first
+    /// allowed physical mode (by identifier) which exists.
+    pub fix_physical_modes: bool,
+}
+
+/// Applies the `rules` on the `model`.
+///
+/// The errors encountered (unknown objects, unmatched rules, etc.) don't
+/// stop the process: they are written in `report_path`, along with the
+/// changes applied. The report is written as CSV if `report_path` has a
+/// `.csv` extension, as JSON otherwise.
+pub fn apply_rules<P: AsRef<Path>>(model: Model, rules: &Rules, report_path: P) -> Result<Model> {
+    apply(model, rules, report_path.as_ref(), None)
+}
+
+/// Applies the `rules` on the `model` like [apply_rules](./fn.apply_rules.html),
+/// also writing in `changelog_path` each change made to an object (its type,
+/// identifier, field, old and new values), see
+/// [ChangeLog](../changelog/struct.ChangeLog.html).
+///
+/// The added codes are logged with an `object_code:<object_system>` field.
+/// The changelog is written as CSV if `changelog_path` has a `.csv`
+/// extension, as JSON otherwise.
+pub fn apply_rules_with_changelog<P: AsRef<Path>, Q: AsRef<Path>>(
+    model: Model,
+    rules: &Rules,
+    report_path: P,
+    changelog_path: Q,
+) -> Result<Model> {
+    apply(
+        model,
+        rules,
+        report_path.as_ref(),
+        Some(changelog_path.as_ref()),
+    )
+}
+
+/// Checks the `rules` on the `model` without producing a model, e.g. to
+/// validate rule files.
+///
+/// The rules are applied like with [apply_rules](./fn.apply_rules.html), but
+/// the resulting model is dropped: only the report is written in
+/// `report_path`. Besides the errors and changes of the rules, its `dry_run`
+/// section sums up the changes that would occur: the objects created and
+/// removed by the rules, the objects then removed as unused when building
+/// the model (e.g. a route left without trips), and the number of objects
+/// of each type with a field changed (e.g. the trips reassigned to another
+/// route).
+pub fn dry_run_rules<P: AsRef<Path>>(model: Model, rules: &Rules, report_path: P) -> Result<()> {
+    let ids_before = object_ids(&model);
+    let mut report = Report::default();
+    let (collections, changelog) = run(model, rules, &mut report)?;
+    let ids_of_rules = object_ids(&collections);
+    let model = Model::new(collections)?;
+    let ids_after = object_ids(&model);
+
+    let summary = report.section("dry_run");
+    add_differences(summary, &ids_before, &ids_of_rules, "");
+    add_differences(summary, &ids_of_rules, &ids_after, " as unused");
+    let mut changed_objects: BTreeMap<(&str, &str), BTreeSet<&str>> = BTreeMap::new();
+    for change in changelog.changes() {
+        changed_objects
+            .entry((change.object_type.as_str(), change.field.as_str()))
+            .or_insert_with(BTreeSet::new)
+            .insert(change.object_id.as_str());
+    }
+    for ((object_type, field), object_ids) in changed_objects {
+        summary.add_info(
+            format!(
+                "{} {} object(s) would have their {} changed",
+                object_ids.len(),
+                object_type,
+                field
+            ),
+            ReportType::PropertyUpdated,
+        );
+    }
+
+    let report_path = report_path.as_ref();
+    info!("Writing report {:?}", report_path);
+    report.write(report_path)
+}
+
+// Adds to the `summary` the objects of `after` not in `before` as created
+// and the ones of `before` not in `after` as removed, `reason` ending the
+// messages
+fn add_differences(
+    summary: &mut Report,
+    before: &[(ObjectType, BTreeSet<String>)],
+    after: &[(ObjectType, BTreeSet<String>)],
+    reason: &str,
+) {
+    for ((object_type, before), (_, after)) in before.iter().zip(after) {
+        for (ids, category, action) in &[
+            (
+                after.difference(before),
+                ReportType::ObjectCreated,
+                "created",
+            ),
+            (
+                before.difference(after),
+                ReportType::ObjectRemoved,
+                "removed",
+            ),
+        ] {
+            for id in ids.clone() {
+                summary.add(
+                    Severity::Info,
+                    ReportEntry::new(
+                        *category,
+                        format!(
+                            "{} {:?} would be {}{}",
+                            object_type.as_str(),
+                            id,
+                            action,
+                            reason
+                        ),
+                    )
+                    .with_object(object_type.clone(), id),
+                );
+            }
+        }
+    }
+}
+
+// Identifiers of the objects the rules can create, change or remove
+fn object_ids(collections: &Collections) -> Vec<(ObjectType, BTreeSet<String>)> {
+    vec![
+        (
+            ObjectType::Network,
+            collections
+                .networks
+                .values()
+                .map(|n| n.id.clone())
+                .collect(),
+        ),
+        (
+            ObjectType::Line,
+            collections.lines.values().map(|l| l.id.clone()).collect(),
+        ),
+        (
+            ObjectType::Route,
+            collections.routes.values().map(|r| r.id.clone()).collect(),
+        ),
+        (
+            ObjectType::VehicleJourney,
+            collections
+                .vehicle_journeys
+                .values()
+                .map(|vj| vj.id.clone())
+                .collect(),
+        ),
+        (
+            ObjectType::StopArea,
+            collections
+                .stop_areas
+                .values()
+                .map(|sa| sa.id.clone())
+                .collect(),
+        ),
+        (
+            ObjectType::StopPoint,
+            collections
+                .stop_points
+                .values()
+                .map(|sp| sp.id.clone())
+                .collect(),
+        ),
+    ]
+}
+
+fn apply(
+    model: Model,
+    rules: &Rules,
+    report_path: &Path,
+    changelog_path: Option<&Path>,
+) -> Result<Model> {
+    let mut report = Report::default();
+    let (collections, changelog) = run(model, rules, &mut report)?;
+
+    info!("Writing report {:?}", report_path);
+    report.write(report_path)?;
+    if let Some(changelog_path) = changelog_path {
+        info!("Writing changelog {:?}", changelog_path);
+        changelog.write(changelog_path)?;
+    }
+
+    Model::new(collections)
+}
+
+// Applies the `rules` on the `model`, adding the errors and changes to the
+// `report`
+pub(crate) fn run(
+    model: Model,
+    rules: &Rules,
+    report: &mut Report,
+) -> Result<(Collections, ChangeLog)> {
+    info!("Applying rules...");
+    let mut changelog = ChangeLog::default();
+    let mut collections = model.into_collections();
+
+    complementary_codes::apply_rules(
+        &mut collections,
+        &rules.complementary_code_files,
+        report,
+        &mut changelog,
+    )?;
+    line_merges::apply_rules(
+        &mut collections,
+        &rules.line_merge_files,
+        report,
+        &mut changelog,
+    )?;
+    vehicle_journey_codes::apply_rules(
+        &mut collections,
+        &rules.vehicle_journey_codes_files,
+        report,
+        &mut changelog,
+    )?;
+    property_rules::apply_rules(
+        &mut collections,
+        &rules.property_rule_files,
+        report,
+        &mut changelog,
+    )?;
+    route_variants::apply_rules(
+        &mut collections,
+        &rules.route_variant_files,
+        report,
+        &mut changelog,
+    )?;
+    accessibility::apply_rules(
+        &mut collections,
+        &rules.accessibility_files,
+        report,
+        &mut changelog,
+    )?;
+    tag_rules::apply_rules(&mut collections, &rules.tag_files, report, &mut changelog)?;
+    physical_modes::apply_rules(
+        &mut collections,
+        &rules.physical_mode_files,
+        rules.fix_physical_modes,
+        report,
+        &mut changelog,
+    )?;
+    Ok((collections, changelog))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{model_builder::ModelBuilder, ntfs, test_utils::*};
+    use std::fs;
+
+    #[test]
+    fn dry_run_summary() {
+        test_in_tmp_dir(|path| {
+            let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+            let code_path = path.join("complementary_codes.csv");
+            create_file_with_content(
+                path,
+                "complementary_codes.csv",
+                "object_type,object_id,object_system,object_code\n\
+                 line,M1,source,L1\n\
+                 line,B42,source,L1\n",
+            );
+            let merge_path = path.join("line_merges.csv");
+            create_file_with_content(
+                path,
+                "line_merges.csv",
+                "object_system,object_code\nsource,L1\n",
+            );
+            let rules = Rules {
+                complementary_code_files: vec![code_path],
+                line_merge_files: vec![merge_path],
+                ..Default::default()
+            };
+            let report_path = path.join("report.csv");
+            dry_run_rules(model, &rules, &report_path).unwrap();
+
+            let report = fs::read_to_string(report_path).unwrap();
+            assert!(report.contains(
+                "dry_run,info,object_removed,line,M1,\"line \"\"M1\"\" would be removed\",1\n"
+            ));
+            assert!(report.contains(
+                "dry_run,info,property_updated,,,2 route object(s) would have their line_id changed,1\n"
+            ));
+        });
+    }
+
+    #[test]
+    fn dry_run_removals_as_unused() {
+        let source_code = ("source".to_string(), "L".to_string());
+        let model = ModelBuilder::default()
+            .line("L1", |line| {
+                line.codes.insert(source_code.clone());
+            })
+            .line("L2", |line| {
+                line.codes.insert(source_code.clone());
+            })
+            .route("R1", |route| {
+                route.line_id = "L1".to_string();
+            })
+            .route("R2", |route| {
+                route.name = "Other route".to_string();
+                route.line_id = "L2".to_string();
+            })
+            .vj("vj1", |vj| {
+                vj.route("R1").st("A", "10:00").st("B", "10:10");
+            })
+            .vj("vj2", |vj| {
+                vj.route("R1").st("A", "11:00").st("B", "11:10");
+            })
+            // the same journey as vj1, the only trip of R2
+            .vj("vj3", |vj| {
+                vj.route("R2").st("A", "10:01").st("B", "10:11");
+            })
+            .build();
+        test_in_tmp_dir(|path| {
+            let merge_path = path.join("line_merges.csv");
+            create_file_with_content(
+                path,
+                "line_merges.csv",
+                "object_system,object_code,max_forward_tolerance\nsource,L,60\n",
+            );
+            let rules = Rules {
+                line_merge_files: vec![merge_path],
+                ..Default::default()
+            };
+            let report_path = path.join("report.csv");
+            dry_run_rules(model, &rules, &report_path).unwrap();
+
+            let report = fs::read_to_string(report_path).unwrap();
+            assert!(report.contains(
+                "dry_run,info,object_removed,trip,vj3,\"trip \"\"vj3\"\" would be removed\",1\n"
+            ));
+            // R2 is moved to L1 by the rules, then left without trips
+            assert!(report.contains(
+                "dry_run,info,object_removed,route,R2,\"route \"\"R2\"\" would be removed as unused\",1\n"
+            ));
+            assert!(!report.contains("route \"\"R2\"\" would be removed\""));
+        });
+    }
+}
@@ -0,0 +1,302 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Check that the physical modes of the vehicle journeys of a line are
+//! allowed for the commercial mode of the line (e.g. a `Metro` line with
+//! vehicle journeys of the `Bus` physical mode after a line merge), and
+//! optionally fix them.
+
+use crate::{
+    changelog::ChangeLog,
+    model::Collections,
+    objects::ObjectType,
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+#[derive(Debug, Deserialize)]
+struct AllowedPhysicalMode {
+    commercial_mode_id: String,
+    physical_mode_id: String,
+}
+
+// The physical modes allowed by commercial mode
+fn read_allowed_modes(rule_files: &[PathBuf]) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let mut allowed_modes: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for rule_file in rule_files {
+        info!("Reading physical mode consistency rules {:?}", rule_file);
+        let mut rdr = csv::Reader::from_path(rule_file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        for rule in rdr.deserialize() {
+            let rule: AllowedPhysicalMode =
+                rule.with_context(|_| format!("Error reading {:?}", rule_file))?;
+            allowed_modes
+                .entry(rule.commercial_mode_id)
+                .or_insert_with(BTreeSet::new)
+                .insert(rule.physical_mode_id);
+        }
+    }
+    Ok(allowed_modes)
+}
+
+// The physical mode replacing the ones not allowed on a line: the allowed
+// mode of the most vehicle journeys of the line (the first by identifier on
+// a tie), or else the first allowed mode which exists
+fn main_allowed_mode(
+    collections: &Collections,
+    allowed: &BTreeSet<String>,
+    line_modes: &BTreeMap<String, usize>,
+) -> Option<String> {
+    line_modes
+        .iter()
+        .filter(|(mode, _)| allowed.contains(*mode))
+        .max_by(|(id1, count1), (id2, count2)| count1.cmp(count2).then_with(|| id2.cmp(id1)))
+        .map(|(mode, _)| mode)
+        .or_else(|| {
+            allowed
+                .iter()
+                .find(|mode| collections.physical_modes.contains_id(mode))
+        })
+        .cloned()
+}
+
+pub(crate) fn apply_rules(
+    collections: &mut Collections,
+    rule_files: &[PathBuf],
+    fix: bool,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    if rule_files.is_empty() {
+        return Ok(());
+    }
+    let allowed_modes = read_allowed_modes(rule_files)?;
+
+    // number of vehicle journeys of each physical mode, by line
+    let mut modes_by_line: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+    for vj in collections.vehicle_journeys.values() {
+        if let Some(route) = collections.routes.get(&vj.route_id) {
+            *modes_by_line
+                .entry(route.line_id.clone())
+                .or_insert_with(BTreeMap::new)
+                .entry(vj.physical_mode_id.clone())
+                .or_insert(0) += 1;
+        }
+    }
+
+    let mut fixed_modes: BTreeMap<String, (&BTreeSet<String>, String)> = BTreeMap::new();
+    for (line_id, line_modes) in &modes_by_line {
+        let line = match collections.lines.get(line_id) {
+            Some(line) => line,
+            None => continue,
+        };
+        let allowed = match allowed_modes.get(&line.commercial_mode_id) {
+            Some(allowed) => allowed,
+            None => continue,
+        };
+        let invalid_modes: Vec<&str> = line_modes
+            .keys()
+            .filter(|mode| !allowed.contains(*mode))
+            .map(String::as_str)
+            .collect();
+        if invalid_modes.is_empty() {
+            continue;
+        }
+        report.add(
+            Severity::Warning,
+            ReportEntry::new(
+                ReportType::InconsistentPhysicalMode,
+                format!(
+                    "line {:?}: the physical modes {} are not allowed for the commercial mode {:?}",
+                    line_id,
+                    invalid_modes.join(", "),
+                    line.commercial_mode_id
+                ),
+            )
+            .with_object(ObjectType::Line, line_id),
+        );
+        if !fix {
+            continue;
+        }
+        match main_allowed_mode(collections, allowed, line_modes) {
+            Some(mode) => {
+                fixed_modes.insert(line_id.clone(), (allowed, mode));
+            }
+            None => report.add(
+                Severity::Error,
+                ReportEntry::new(
+                    ReportType::InconsistentPhysicalMode,
+                    format!(
+                        "line {:?}: no allowed physical mode exists, its vehicle journeys are not fixed",
+                        line_id
+                    ),
+                )
+                .with_object(ObjectType::Line, line_id),
+            ),
+        }
+    }
+
+    let vj_idxs: Vec<_> = collections
+        .vehicle_journeys
+        .iter()
+        .map(|(idx, _)| idx)
+        .collect();
+    for vj_idx in vj_idxs {
+        let line_id = match collections
+            .routes
+            .get(&collections.vehicle_journeys[vj_idx].route_id)
+        {
+            Some(route) => &route.line_id,
+            None => continue,
+        };
+        let (allowed, mode) = match fixed_modes.get(line_id) {
+            Some(fixed_mode) => fixed_mode,
+            None => continue,
+        };
+        let mut vj = collections.vehicle_journeys.index_mut(vj_idx);
+        if allowed.contains(&vj.physical_mode_id) {
+            continue;
+        }
+        changelog.add(
+            ObjectType::VehicleJourney,
+            &vj.id,
+            "physical_mode_id",
+            Some(vj.physical_mode_id.clone()),
+            Some(mode.clone()),
+        );
+        report.add(
+            Severity::Info,
+            ReportEntry::new(
+                ReportType::PropertyUpdated,
+                format!(
+                    "vehicle journey {:?}: physical mode {:?} replaced by {:?}",
+                    vj.id, vj.physical_mode_id, mode
+                ),
+            )
+            .with_object(ObjectType::VehicleJourney, &vj.id),
+        );
+        vj.physical_mode_id = mode.clone();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{model_builder::ModelBuilder, objects::PhysicalMode, test_utils::*};
+    use pretty_assertions::assert_eq;
+    use typed_index_collection::CollectionWithId;
+
+    fn collections() -> Collections {
+        let model = ModelBuilder::default()
+            .line("metro", |line| {
+                line.commercial_mode_id = "Metro".to_string();
+            })
+            .route("r1", |route| {
+                route.line_id = "metro".to_string();
+            })
+            .vj("vj1", |vj| {
+                vj.route("r1")
+                    .physical_mode("Metro")
+                    .st("A", "10:00")
+                    .st("B", "10:10");
+            })
+            .vj("vj2", |vj| {
+                vj.route("r1")
+                    .physical_mode("Bus")
+                    .st("A", "11:00")
+                    .st("B", "11:10");
+            })
+            .build();
+        let mut collections = model.into_collections();
+        collections.physical_modes = CollectionWithId::new(vec![
+            PhysicalMode {
+                id: "Bus".to_string(),
+                name: "Bus".to_string(),
+                co2_emission: None,
+            },
+            PhysicalMode {
+                id: "Metro".to_string(),
+                name: "Metro".to_string(),
+                co2_emission: None,
+            },
+        ])
+        .unwrap();
+        collections
+    }
+
+    fn check(collections: &mut Collections, fix: bool) -> (Report, ChangeLog) {
+        let mut report = Report::default();
+        let mut changelog = ChangeLog::default();
+        test_in_tmp_dir(|path| {
+            create_file_with_content(
+                path,
+                "physical_modes.csv",
+                "commercial_mode_id,physical_mode_id\nMetro,Metro\nMetro,RapidTransit\n",
+            );
+            apply_rules(
+                collections,
+                &[path.join("physical_modes.csv")],
+                fix,
+                &mut report,
+                &mut changelog,
+            )
+            .unwrap();
+        });
+        (report, changelog)
+    }
+
+    #[test]
+    fn report_inconsistent_physical_modes() {
+        let mut collections = collections();
+        let (report, changelog) = check(&mut collections, false);
+
+        let warnings = report.entries(Severity::Warning);
+        assert_eq!(1, warnings.len());
+        assert_eq!(ReportType::InconsistentPhysicalMode, warnings[0].category);
+        assert!(changelog.changes().is_empty());
+        assert_eq!(
+            "Bus",
+            collections
+                .vehicle_journeys
+                .get("vj2")
+                .unwrap()
+                .physical_mode_id
+        );
+    }
+
+    #[test]
+    fn fix_inconsistent_physical_modes() {
+        let mut collections = collections();
+        let (report, changelog) = check(&mut collections, true);
+
+        assert_eq!(1, report.entries(Severity::Warning).len());
+        assert_eq!(1, changelog.changes().len());
+        assert_eq!(
+            "Metro",
+            collections
+                .vehicle_journeys
+                .get("vj2")
+                .unwrap()
+                .physical_mode_id
+        );
+    }
+}
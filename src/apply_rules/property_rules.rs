@@ -0,0 +1,485 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+//! Update the properties (name, color, visibility, etc.) of networks, lines,
+//! routes, trips, stop areas and stop points.
+
+use crate::{
+    changelog::ChangeLog,
+    model::Collections,
+    objects::{Codes, Line, Network, ObjectType, Route, StopArea, StopPoint, VehicleJourney},
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::{
+    fmt::Display,
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use typed_index_collection::{CollectionWithId, Id, Idx};
+
+// Value of `property_old_value` matching any current value
+const ANY_VALUE: &str = "*";
+
+#[derive(Debug, Deserialize)]
+struct PropertyRule {
+    object_type: ObjectType,
+    object_id: String,
+    // When given, `object_id` is the code of the object in this system
+    #[serde(default)]
+    object_system: Option<String>,
+    property_name: String,
+    #[serde(default)]
+    property_old_value: String,
+    #[serde(default)]
+    property_value: String,
+}
+
+impl PropertyRule {
+    fn object(&self) -> String {
+        match &self.object_system {
+            Some(system) => format!(
+                "{} with code {:?} in {:?}",
+                self.object_type.as_str(),
+                self.object_id,
+                system
+            ),
+            None => format!("{} {:?}", self.object_type.as_str(), self.object_id),
+        }
+    }
+}
+
+// The value of the property before the update, or why the rule was skipped
+type RuleResult = std::result::Result<String, (String, ReportType)>;
+
+fn check_old_value(current: &str, rule: &PropertyRule) -> RuleResult {
+    if rule.property_old_value == ANY_VALUE || rule.property_old_value == current {
+        Ok(current.to_string())
+    } else {
+        Err((
+            format!(
+                "{}: {} is {:?} instead of {:?}, rule skipped",
+                rule.object(),
+                rule.property_name,
+                current,
+                rule.property_old_value
+            ),
+            ReportType::OldPropertyValueDoesNotMatch,
+        ))
+    }
+}
+
+fn invalid_value<E: Display>(rule: &PropertyRule, error: E) -> (String, ReportType) {
+    (
+        format!(
+            "{}: invalid value {:?} for {} ({}), rule skipped",
+            rule.object(),
+            rule.property_value,
+            rule.property_name,
+            error
+        ),
+        ReportType::InvalidValue,
+    )
+}
+
+fn update_string(field: &mut String, rule: &PropertyRule) -> RuleResult {
+    let old_value = check_old_value(field, rule)?;
+    *field = rule.property_value.clone();
+    Ok(old_value)
+}
+
+fn update_option<T>(field: &mut Option<T>, rule: &PropertyRule) -> RuleResult
+where
+    T: FromStr + ToString,
+    T::Err: Display,
+{
+    let current = field.as_ref().map(T::to_string).unwrap_or_default();
+    let old_value = check_old_value(&current, rule)?;
+    *field = if rule.property_value.is_empty() {
+        None
+    } else {
+        let value = rule
+            .property_value
+            .parse()
+            .map_err(|e| invalid_value(rule, e))?;
+        Some(value)
+    };
+    Ok(old_value)
+}
+
+// Booleans are written `0` or `1` in the NTFS
+fn update_bool(field: &mut bool, rule: &PropertyRule) -> RuleResult {
+    let current = if *field { "1" } else { "0" };
+    let old_value = check_old_value(current, rule)?;
+    *field = match rule.property_value.as_str() {
+        "1" => true,
+        "0" => false,
+        _ => return Err(invalid_value(rule, "0 or 1 expected")),
+    };
+    Ok(old_value)
+}
+
+fn unknown_property(rule: &PropertyRule) -> RuleResult {
+    Err((
+        format!(
+            "{}: unknown property {:?}, rule skipped",
+            rule.object(),
+            rule.property_name
+        ),
+        ReportType::UnknownPropertyName,
+    ))
+}
+
+fn update_network(network: &mut Network, rule: &PropertyRule) -> RuleResult {
+    match rule.property_name.as_str() {
+        "network_name" => update_string(&mut network.name, rule),
+        "network_url" => update_option(&mut network.url, rule),
+        "network_timezone" => update_option(&mut network.timezone, rule),
+        "network_lang" => update_option(&mut network.lang, rule),
+        "network_phone" => update_option(&mut network.phone, rule),
+        "network_address" => update_option(&mut network.address, rule),
+        "network_sort_order" => update_option(&mut network.sort_order, rule),
+        "network_fare_url" => update_option(&mut network.fare_url, rule),
+        "network_email" => update_option(&mut network.email, rule),
+        _ => unknown_property(rule),
+    }
+}
+
+fn update_line(line: &mut Line, rule: &PropertyRule) -> RuleResult {
+    match rule.property_name.as_str() {
+        "line_code" => update_option(&mut line.code, rule),
+        "line_name" => update_string(&mut line.name, rule),
+        "forward_line_name" => update_option(&mut line.forward_name, rule),
+        "backward_line_name" => update_option(&mut line.backward_name, rule),
+        "line_color" => update_option(&mut line.color, rule),
+        "line_text_color" => update_option(&mut line.text_color, rule),
+        "line_sort_order" => update_option(&mut line.sort_order, rule),
+        _ => unknown_property(rule),
+    }
+}
+
+fn update_route(route: &mut Route, rule: &PropertyRule) -> RuleResult {
+    match rule.property_name.as_str() {
+        "route_name" => update_string(&mut route.name, rule),
+        "direction_type" => update_option(&mut route.direction_type, rule),
+        _ => unknown_property(rule),
+    }
+}
+
+fn update_vehicle_journey(vehicle_journey: &mut VehicleJourney, rule: &PropertyRule) -> RuleResult {
+    match rule.property_name.as_str() {
+        "trip_headsign" => update_option(&mut vehicle_journey.headsign, rule),
+        "trip_short_name" => update_option(&mut vehicle_journey.short_name, rule),
+        "block_id" => update_option(&mut vehicle_journey.block_id, rule),
+        _ => unknown_property(rule),
+    }
+}
+
+fn update_stop_area(stop_area: &mut StopArea, rule: &PropertyRule) -> RuleResult {
+    match rule.property_name.as_str() {
+        "stop_name" => update_string(&mut stop_area.name, rule),
+        "stop_visible" => update_bool(&mut stop_area.visible, rule),
+        "stop_timezone" => update_option(&mut stop_area.timezone, rule),
+        _ => unknown_property(rule),
+    }
+}
+
+fn update_stop_point(stop_point: &mut StopPoint, rule: &PropertyRule) -> RuleResult {
+    match rule.property_name.as_str() {
+        "stop_name" => update_string(&mut stop_point.name, rule),
+        "stop_code" => update_option(&mut stop_point.code, rule),
+        "stop_visible" => update_bool(&mut stop_point.visible, rule),
+        "stop_timezone" => update_option(&mut stop_point.timezone, rule),
+        "platform_code" => update_option(&mut stop_point.platform_code, rule),
+        _ => unknown_property(rule),
+    }
+}
+
+fn find_idxs<T>(collection: &CollectionWithId<T>, rule: &PropertyRule) -> Vec<Idx<T>>
+where
+    T: Id<T> + Codes,
+{
+    match &rule.object_system {
+        Some(system) => {
+            let code = (system.clone(), rule.object_id.clone());
+            collection
+                .iter()
+                .filter(|(_, obj)| obj.codes().contains(&code))
+                .map(|(idx, _)| idx)
+                .collect()
+        }
+        None => collection.get_idx(&rule.object_id).into_iter().collect(),
+    }
+}
+
+fn apply_rule<T, F>(
+    collection: &mut CollectionWithId<T>,
+    rule: &PropertyRule,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+    update: F,
+) where
+    T: Id<T> + Codes,
+    F: Fn(&mut T, &PropertyRule) -> RuleResult,
+{
+    let idxs = find_idxs(collection, rule);
+    if idxs.is_empty() {
+        report.add_error(
+            format!("{} not found, rule skipped", rule.object()),
+            ReportType::ObjectNotFound,
+        );
+    }
+    for idx in idxs {
+        let mut obj = collection.index_mut(idx);
+        let (severity, entry) = match update(&mut *obj, rule) {
+            Ok(old_value) => {
+                let empty_as_none = |value: String| Some(value).filter(|value| !value.is_empty());
+                changelog.add(
+                    rule.object_type.clone(),
+                    obj.id(),
+                    &rule.property_name,
+                    empty_as_none(old_value),
+                    empty_as_none(rule.property_value.clone()),
+                );
+                (
+                    Severity::Info,
+                    ReportEntry::new(
+                        ReportType::PropertyUpdated,
+                        format!(
+                            "{} {:?}: {} set to {:?}",
+                            rule.object_type.as_str(),
+                            obj.id(),
+                            rule.property_name,
+                            rule.property_value
+                        ),
+                    ),
+                )
+            }
+            Err((message, category)) => (Severity::Error, ReportEntry::new(category, message)),
+        };
+        report.add(
+            severity,
+            entry.with_object(rule.object_type.clone(), obj.id()),
+        );
+    }
+}
+
+fn read_rules(rule_file: &Path) -> Result<Vec<PropertyRule>> {
+    if rule_file.extension().map_or(false, |ext| ext == "json") {
+        let file =
+            File::open(rule_file).with_context(|_| format!("Error reading {:?}", rule_file))?;
+        let rules = serde_json::from_reader(file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        Ok(rules)
+    } else {
+        let mut rdr = csv::Reader::from_path(rule_file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        let rules = rdr
+            .deserialize()
+            .collect::<std::result::Result<_, _>>()
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        Ok(rules)
+    }
+}
+
+pub(crate) fn apply_rules(
+    collections: &mut Collections,
+    rule_files: &[PathBuf],
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    for rule_file in rule_files {
+        info!("Reading property rules {:?}", rule_file);
+        for rule in read_rules(rule_file)? {
+            match rule.object_type {
+                ObjectType::Network => apply_rule(
+                    &mut collections.networks,
+                    &rule,
+                    report,
+                    changelog,
+                    update_network,
+                ),
+                ObjectType::Line => apply_rule(
+                    &mut collections.lines,
+                    &rule,
+                    report,
+                    changelog,
+                    update_line,
+                ),
+                ObjectType::Route => apply_rule(
+                    &mut collections.routes,
+                    &rule,
+                    report,
+                    changelog,
+                    update_route,
+                ),
+                ObjectType::VehicleJourney => apply_rule(
+                    &mut collections.vehicle_journeys,
+                    &rule,
+                    report,
+                    changelog,
+                    update_vehicle_journey,
+                ),
+                ObjectType::StopArea => apply_rule(
+                    &mut collections.stop_areas,
+                    &rule,
+                    report,
+                    changelog,
+                    update_stop_area,
+                ),
+                ObjectType::StopPoint => apply_rule(
+                    &mut collections.stop_points,
+                    &rule,
+                    report,
+                    changelog,
+                    update_stop_point,
+                ),
+                _ => report.add_error(
+                    format!(
+                        "property rules are not supported for object_type={}, rule skipped",
+                        rule.object_type.as_str()
+                    ),
+                    ReportType::InvalidValue,
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{objects::Rgb, test_utils::*};
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    fn collections() -> Collections {
+        let mut collections = Collections::default();
+        let mut line = Line {
+            id: "line:1".to_string(),
+            name: "Line 1".to_string(),
+            ..Default::default()
+        };
+        line.codes.insert(("source".to_string(), "L1".to_string()));
+        collections.lines = CollectionWithId::from(line);
+        collections.stop_points = CollectionWithId::from(StopPoint {
+            id: "sp:1".to_string(),
+            visible: true,
+            ..Default::default()
+        });
+        collections
+    }
+
+    fn apply(rules: &str, file_name: &str, collections: &mut Collections) -> (Report, ChangeLog) {
+        let mut report = Report::default();
+        let mut changelog = ChangeLog::default();
+        test_in_tmp_dir(|path| {
+            let rule_path = path.join(file_name);
+            File::create(&rule_path)
+                .unwrap()
+                .write_all(rules.as_bytes())
+                .unwrap();
+            apply_rules(collections, &[rule_path], &mut report, &mut changelog).unwrap();
+        });
+        (report, changelog)
+    }
+
+    #[test]
+    fn update_properties_from_csv() {
+        let rules =
+            "object_type,object_id,object_system,property_name,property_old_value,property_value\n\
+                     line,line:1,,line_name,Line 1,Line One\n\
+                     line,L1,source,line_color,*,FF0000\n\
+                     line,line:1,,line_sort_order,3,4\n\
+                     line,line:1,,line_text_color,,blue\n\
+                     line,line:1,,line_shape,*,round\n\
+                     line,line:2,,line_name,*,Line Two\n\
+                     stop_point,sp:1,,stop_visible,1,0";
+        let mut collections = collections();
+        let (report, changelog) = apply(rules, "property_rules.csv", &mut collections);
+
+        let line = collections.lines.get("line:1").unwrap();
+        assert_eq!("Line One", line.name);
+        assert_eq!(
+            Some(Rgb {
+                red: 255,
+                green: 0,
+                blue: 0
+            }),
+            line.color
+        );
+        assert_eq!(None, line.sort_order);
+        assert_eq!(None, line.text_color);
+        assert!(!collections.stop_points.get("sp:1").unwrap().visible);
+
+        let infos: Vec<ReportType> = report.infos.iter().map(|e| e.category).collect();
+        assert_eq!(vec![ReportType::PropertyUpdated; 3], infos);
+        let errors: Vec<ReportType> = report.errors.iter().map(|e| e.category).collect();
+        assert_eq!(
+            vec![
+                ReportType::OldPropertyValueDoesNotMatch,
+                ReportType::InvalidValue,
+                ReportType::UnknownPropertyName,
+                ReportType::ObjectNotFound,
+            ],
+            errors
+        );
+
+        let changes: Vec<(&str, &str, Option<&str>, Option<&str>)> = changelog
+            .changes()
+            .iter()
+            .map(|change| {
+                (
+                    change.object_id.as_str(),
+                    change.field.as_str(),
+                    change.old_value.as_deref(),
+                    change.new_value.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("line:1", "line_name", Some("Line 1"), Some("Line One")),
+                ("line:1", "line_color", None, Some("FF0000")),
+                ("sp:1", "stop_visible", Some("1"), Some("0")),
+            ],
+            changes
+        );
+    }
+
+    #[test]
+    fn update_properties_from_json() {
+        let rules = r#"[
+            {
+                "object_type": "line",
+                "object_id": "line:1",
+                "property_name": "line_code",
+                "property_old_value": "",
+                "property_value": "1"
+            }
+        ]"#;
+        let mut collections = collections();
+        let (report, _) = apply(rules, "property_rules.json", &mut collections);
+
+        assert_eq!(
+            Some("1".to_string()),
+            collections.lines.get("line:1").unwrap().code
+        );
+        assert!(report.errors.is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Label the variants of the routes (e.g. a summer route or a detour) with
+//! the period when they run, so passenger applications can explain the
+//! differences between the patterns of a line.
+
+use crate::{
+    changelog::ChangeLog,
+    model::Collections,
+    objects::{Comment, CommentLinks, Date, ObjectType, RouteVariant},
+    report::{Report, ReportEntry, ReportType, Severity},
+    utils::de_from_date_string,
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize)]
+struct RouteVariantRule {
+    route_id: String,
+    variant_label: String,
+    #[serde(deserialize_with = "de_from_date_string")]
+    start_date: Date,
+    #[serde(deserialize_with = "de_from_date_string")]
+    end_date: Date,
+    #[serde(default)]
+    variant_description: Option<String>,
+}
+
+// Whether a vehicle journey of the route runs between the dates
+fn runs_during(collections: &Collections, rule: &RouteVariantRule) -> bool {
+    collections
+        .vehicle_journeys
+        .values()
+        .filter(|vj| vj.route_id == rule.route_id)
+        .filter_map(|vj| collections.calendars.get(&vj.service_id))
+        .any(|calendar| {
+            calendar
+                .dates
+                .range(rule.start_date..=rule.end_date)
+                .next()
+                .is_some()
+        })
+}
+
+fn apply_rule(
+    collections: &mut Collections,
+    rule: RouteVariantRule,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    if rule.start_date > rule.end_date {
+        report.add(
+            Severity::Error,
+            ReportEntry::new(
+                ReportType::InvalidValue,
+                format!(
+                    "route {:?}: variant {:?} ends on {} before it starts on {}, rule skipped",
+                    rule.route_id, rule.variant_label, rule.end_date, rule.start_date
+                ),
+            )
+            .with_object(ObjectType::Route, &rule.route_id),
+        );
+        return Ok(());
+    }
+    let route_idx = match collections.routes.get_idx(&rule.route_id) {
+        Some(idx) => idx,
+        None => {
+            report.add_error(
+                format!("route {:?} not found, rule skipped", rule.route_id),
+                ReportType::ObjectNotFound,
+            );
+            return Ok(());
+        }
+    };
+    if !runs_during(collections, &rule) {
+        report.add(
+            Severity::Warning,
+            ReportEntry::new(
+                ReportType::UnmatchedVehicleJourney,
+                format!(
+                    "route {:?}: no vehicle journey runs from {} to {} for the variant {:?}",
+                    rule.route_id, rule.start_date, rule.end_date, rule.variant_label
+                ),
+            )
+            .with_object(ObjectType::Route, &rule.route_id),
+        );
+    }
+
+    let variant = RouteVariant {
+        label: rule.variant_label.clone(),
+        start_date: rule.start_date,
+        end_date: rule.end_date,
+    };
+    let comment_id = format!("route_variant:{}", rule.route_id);
+    let comment = Comment {
+        id: comment_id.clone(),
+        label: Some(rule.variant_label.clone()),
+        name: rule.variant_description.clone().unwrap_or_else(|| {
+            format!(
+                "{} from {} to {}",
+                rule.variant_label,
+                rule.start_date.format("%d/%m/%Y"),
+                rule.end_date.format("%d/%m/%Y")
+            )
+        }),
+        ..Default::default()
+    };
+    match collections.comments.get_mut(&comment_id) {
+        Some(mut existing) => *existing = comment,
+        None => collections.comments.push(comment)?,
+    }
+
+    let mut route = collections.routes.index_mut(route_idx);
+    let old_variant = RouteVariant::of_route(&route);
+    let old_properties = old_variant.as_ref().map(RouteVariant::properties);
+    for (i, (name, value)) in variant.properties().into_iter().enumerate() {
+        let old_value = old_properties
+            .as_ref()
+            .map(|properties| properties[i].1.clone());
+        changelog.add(ObjectType::Route, &route.id, &name, old_value, Some(value));
+    }
+    variant.set_on_route(&mut route);
+    route.comment_links_mut().insert(comment_id);
+    report.add(
+        Severity::Info,
+        ReportEntry::new(
+            ReportType::PropertyUpdated,
+            format!(
+                "route {:?} labeled as the variant {:?} from {} to {}",
+                rule.route_id, rule.variant_label, rule.start_date, rule.end_date
+            ),
+        )
+        .with_object(ObjectType::Route, &rule.route_id),
+    );
+    Ok(())
+}
+
+pub(crate) fn apply_rules(
+    collections: &mut Collections,
+    rule_files: &[PathBuf],
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    for rule_file in rule_files {
+        info!("Reading route variants {:?}", rule_file);
+        let mut rdr = csv::Reader::from_path(rule_file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        for rule in rdr.deserialize() {
+            let rule: RouteVariantRule =
+                rule.with_context(|_| format!("Error reading {:?}", rule_file))?;
+            apply_rule(collections, rule, report, changelog)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        objects::{Calendar, Route, VehicleJourney},
+        test_utils::*,
+    };
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+    use std::{fs::File, io::Write};
+    use typed_index_collection::CollectionWithId;
+
+    fn collections() -> Collections {
+        let mut collections = Collections::default();
+        collections.routes = CollectionWithId::new(vec![
+            Route {
+                id: "route:1".to_string(),
+                ..Default::default()
+            },
+            Route {
+                id: "route:2".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        let mut calendar = Calendar::new("summer".to_string());
+        calendar.dates.insert(NaiveDate::from_ymd(2020, 7, 14));
+        collections.calendars = CollectionWithId::from(calendar);
+        collections.vehicle_journeys = CollectionWithId::from(VehicleJourney {
+            id: "vj:1".to_string(),
+            route_id: "route:1".to_string(),
+            service_id: "summer".to_string(),
+            ..Default::default()
+        });
+        collections
+    }
+
+    #[test]
+    fn label_route_variants() {
+        let rules = "route_id,variant_label,start_date,end_date,variant_description\n\
+                     route:1,Summer,20200701,20200831,Summer route to the beach\n\
+                     route:2,Detour,20200101,20200131,\n\
+                     route:3,Detour,20200101,20200131,\n\
+                     route:1,Winter,20201231,20201201,";
+        let mut collections = collections();
+        let mut report = Report::default();
+        let mut changelog = ChangeLog::default();
+        test_in_tmp_dir(|path| {
+            let rule_path = path.join("route_variants.csv");
+            File::create(&rule_path)
+                .unwrap()
+                .write_all(rules.as_bytes())
+                .unwrap();
+            apply_rules(&mut collections, &[rule_path], &mut report, &mut changelog).unwrap();
+        });
+
+        let route = collections.routes.get("route:1").unwrap();
+        assert_eq!(
+            Some(RouteVariant {
+                label: "Summer".to_string(),
+                start_date: NaiveDate::from_ymd(2020, 7, 1),
+                end_date: NaiveDate::from_ymd(2020, 8, 31),
+            }),
+            RouteVariant::of_route(&route)
+        );
+        assert!(route.comment_links.contains("route_variant:route:1"));
+        let comment = collections.comments.get("route_variant:route:1").unwrap();
+        assert_eq!("Summer route to the beach", comment.name);
+        let comment = collections.comments.get("route_variant:route:2").unwrap();
+        assert_eq!("Detour from 01/01/2020 to 31/01/2020", comment.name);
+
+        let warnings: Vec<ReportType> = report
+            .entries(Severity::Warning)
+            .iter()
+            .map(|e| e.category)
+            .collect();
+        assert_eq!(vec![ReportType::UnmatchedVehicleJourney], warnings);
+        let errors: Vec<ReportType> = report
+            .entries(Severity::Error)
+            .iter()
+            .map(|e| e.category)
+            .collect();
+        assert_eq!(
+            vec![ReportType::ObjectNotFound, ReportType::InvalidValue],
+            errors
+        );
+        assert_eq!(6, changelog.changes().len());
+    }
+}
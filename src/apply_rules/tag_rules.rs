@@ -0,0 +1,179 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Attach tags to lines, routes, trips, stop areas and stop points, see
+//! [tags](../../tags/index.html).
+
+use crate::{
+    changelog::ChangeLog,
+    model::Collections,
+    objects::{ObjectType, Properties},
+    report::{Report, ReportEntry, ReportType, Severity},
+    tags::{self, TAG_PROPERTY},
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::path::PathBuf;
+use typed_index_collection::{CollectionWithId, Id};
+
+#[derive(Debug, Deserialize)]
+struct TagRule {
+    object_type: ObjectType,
+    object_id: String,
+    tag: String,
+}
+
+fn apply_rule<T>(
+    collection: &mut CollectionWithId<T>,
+    rule: &TagRule,
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) where
+    T: Id<T> + Properties,
+{
+    let mut obj = match collection.get_mut(&rule.object_id) {
+        Some(obj) => obj,
+        None => {
+            report.add_error(
+                format!(
+                    "{} {:?} not found, rule skipped",
+                    rule.object_type.as_str(),
+                    rule.object_id
+                ),
+                ReportType::ObjectNotFound,
+            );
+            return;
+        }
+    };
+    if tags::add_tag(&mut *obj, &rule.tag) {
+        changelog.add(
+            rule.object_type.clone(),
+            &rule.object_id,
+            TAG_PROPERTY,
+            None,
+            Some(rule.tag.clone()),
+        );
+        report.add(
+            Severity::Info,
+            ReportEntry::new(
+                ReportType::PropertyUpdated,
+                format!(
+                    "{} {:?} tagged {:?}",
+                    rule.object_type.as_str(),
+                    rule.object_id,
+                    rule.tag
+                ),
+            )
+            .with_object(rule.object_type.clone(), &rule.object_id),
+        );
+    }
+}
+
+pub(crate) fn apply_rules(
+    collections: &mut Collections,
+    rule_files: &[PathBuf],
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    for rule_file in rule_files {
+        info!("Reading tag rules {:?}", rule_file);
+        let mut rdr = csv::Reader::from_path(rule_file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        for rule in rdr.deserialize() {
+            let rule: TagRule = rule.with_context(|_| format!("Error reading {:?}", rule_file))?;
+            match rule.object_type {
+                ObjectType::Line => apply_rule(&mut collections.lines, &rule, report, changelog),
+                ObjectType::Route => apply_rule(&mut collections.routes, &rule, report, changelog),
+                ObjectType::VehicleJourney => {
+                    apply_rule(&mut collections.vehicle_journeys, &rule, report, changelog)
+                }
+                ObjectType::StopArea => {
+                    apply_rule(&mut collections.stop_areas, &rule, report, changelog)
+                }
+                ObjectType::StopPoint => {
+                    apply_rule(&mut collections.stop_points, &rule, report, changelog)
+                }
+                _ => report.add_error(
+                    format!(
+                        "tags are not supported for object_type={}, rule skipped",
+                        rule.object_type.as_str()
+                    ),
+                    ReportType::InvalidValue,
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        objects::{Line, StopArea},
+        test_utils::*,
+    };
+    use pretty_assertions::assert_eq;
+    use std::{fs::File, io::Write};
+
+    #[test]
+    fn tag_objects() {
+        let rules = "object_type,object_id,tag\n\
+                     line,line:1,school\n\
+                     line,line:1,school\n\
+                     stop_area,sa:1,school\n\
+                     line,line:2,school\n\
+                     network,network:1,school\n";
+        let mut collections = Collections::default();
+        collections.lines = CollectionWithId::from(Line {
+            id: "line:1".to_string(),
+            ..Default::default()
+        });
+        collections.stop_areas = CollectionWithId::from(StopArea {
+            id: "sa:1".to_string(),
+            ..Default::default()
+        });
+        let mut report = Report::default();
+        let mut changelog = ChangeLog::default();
+        test_in_tmp_dir(|path| {
+            let rule_path = path.join("tags.csv");
+            File::create(&rule_path)
+                .unwrap()
+                .write_all(rules.as_bytes())
+                .unwrap();
+            apply_rules(&mut collections, &[rule_path], &mut report, &mut changelog).unwrap();
+        });
+
+        assert!(tags::has_tag(
+            collections.lines.get("line:1").unwrap(),
+            "school"
+        ));
+        assert!(tags::has_tag(
+            collections.stop_areas.get("sa:1").unwrap(),
+            "school"
+        ));
+        assert_eq!(2, changelog.changes().len());
+        let errors: Vec<ReportType> = report
+            .entries(Severity::Error)
+            .iter()
+            .map(|e| e.category)
+            .collect();
+        assert_eq!(
+            vec![ReportType::ObjectNotFound, ReportType::InvalidValue],
+            errors
+        );
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+//! Attach operational codes to the vehicle journeys, matched by line,
+//! departure time at the first stop and days of operation.
+
+use crate::{
+    changelog::ChangeLog,
+    model::Collections,
+    objects::{Date, ObjectType, Time, VehicleJourney},
+    report::{Report, ReportType},
+    Result,
+};
+use chrono::Datelike;
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+use typed_index_collection::Idx;
+
+type DeparturesMap = BTreeMap<(String, Time, String), Vec<Idx<VehicleJourney>>>;
+
+#[derive(Debug, Deserialize)]
+struct VehicleJourneyCode {
+    line_id: String,
+    departure_time: Time,
+    days: String,
+    object_system: String,
+    object_code: String,
+}
+
+// Days of operation, from monday to sunday (e.g. `1111100`)
+fn days_of_operation(dates: &BTreeSet<Date>) -> String {
+    let mut days = ['0'; 7];
+    for date in dates {
+        days[date.weekday().num_days_from_monday() as usize] = '1';
+    }
+    days.iter().collect()
+}
+
+fn is_valid_days(days: &str) -> bool {
+    days.len() == 7 && days.chars().all(|c| c == '0' || c == '1')
+}
+
+fn make_departures_map(collections: &Collections) -> DeparturesMap {
+    let mut departures = DeparturesMap::new();
+    for (vj_idx, vj) in collections.vehicle_journeys.iter() {
+        let line_id = match collections.routes.get(&vj.route_id) {
+            Some(route) => route.line_id.clone(),
+            None => continue,
+        };
+        let departure_time = match vj.stop_times.first() {
+            Some(stop_time) => stop_time.departure_time,
+            None => continue,
+        };
+        let days = match collections.calendars.get(&vj.service_id) {
+            Some(calendar) => days_of_operation(&calendar.dates),
+            None => continue,
+        };
+        departures
+            .entry((line_id, departure_time, days))
+            .or_insert_with(Vec::new)
+            .push(vj_idx);
+    }
+    departures
+}
+
+pub(crate) fn apply_rules(
+    collections: &mut Collections,
+    rule_files: &[PathBuf],
+    report: &mut Report,
+    changelog: &mut ChangeLog,
+) -> Result<()> {
+    if rule_files.is_empty() {
+        return Ok(());
+    }
+    let departures = make_departures_map(collections);
+    for rule_file in rule_files {
+        info!("Reading vehicle journey codes {:?}", rule_file);
+        let mut rdr = csv::Reader::from_path(rule_file)
+            .with_context(|_| format!("Error reading {:?}", rule_file))?;
+        for vj_code in rdr.deserialize() {
+            let vj_code: VehicleJourneyCode =
+                vj_code.with_context(|_| format!("Error reading {:?}", rule_file))?;
+            if !is_valid_days(&vj_code.days) {
+                report.add_error(
+                    format!(
+                        "invalid days {:?} for line_id={:?} and departure_time={}",
+                        vj_code.days, vj_code.line_id, vj_code.departure_time
+                    ),
+                    ReportType::InvalidValue,
+                );
+                continue;
+            }
+            let key = (
+                vj_code.line_id.clone(),
+                vj_code.departure_time,
+                vj_code.days.clone(),
+            );
+            match departures.get(&key).map(Vec::as_slice).unwrap_or_default() {
+                [] => report.add_error(
+                    format!(
+                        "no vehicle journey found for line_id={:?}, departure_time={} and days={:?}",
+                        vj_code.line_id, vj_code.departure_time, vj_code.days
+                    ),
+                    ReportType::UnmatchedVehicleJourney,
+                ),
+                [vj_idx] => {
+                    let mut vj = collections.vehicle_journeys.index_mut(*vj_idx);
+                    let field = format!("object_code:{}", vj_code.object_system);
+                    let code = (vj_code.object_system, vj_code.object_code);
+                    if !vj.codes.contains(&code) {
+                        changelog.add(
+                            ObjectType::VehicleJourney,
+                            &vj.id,
+                            &field,
+                            None,
+                            Some(code.1.clone()),
+                        );
+                        vj.codes.insert(code);
+                    }
+                }
+                vj_idxs => {
+                    let vj_ids: Vec<&str> = vj_idxs
+                        .iter()
+                        .map(|vj_idx| collections.vehicle_journeys[*vj_idx].id.as_str())
+                        .collect();
+                    report.add_error(
+                        format!(
+                            "several vehicle journeys found for line_id={:?}, departure_time={} and days={:?}: {}",
+                            vj_code.line_id,
+                            vj_code.departure_time,
+                            vj_code.days,
+                            vj_ids.join(", ")
+                        ),
+                        ReportType::AmbiguousVehicleJourney,
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        objects::{Calendar, Line, Route, StopPoint, StopTime},
+        test_utils::*,
+    };
+    use pretty_assertions::assert_eq;
+    use std::{fs::File, io::Write};
+    use typed_index_collection::CollectionWithId;
+
+    fn vehicle_journey(
+        id: &str,
+        departure_time: Time,
+        service_id: &str,
+        collections: &Collections,
+    ) -> VehicleJourney {
+        VehicleJourney {
+            id: id.to_string(),
+            route_id: "route:1".to_string(),
+            service_id: service_id.to_string(),
+            stop_times: vec![StopTime {
+                stop_point_idx: collections.stop_points.get_idx("sp:1").unwrap(),
+                sequence: 0,
+                arrival_time: departure_time,
+                departure_time,
+                boarding_duration: 0,
+                alighting_duration: 0,
+                pickup_type: 0,
+                drop_off_type: 0,
+                datetime_estimated: false,
+                local_zone_id: None,
+                precision: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn calendar(id: &str, dates: &[(i32, u32, u32)]) -> Calendar {
+        Calendar {
+            id: id.to_string(),
+            dates: dates
+                .iter()
+                .map(|&(y, m, d)| Date::from_ymd(y, m, d))
+                .collect(),
+        }
+    }
+
+    fn collections() -> Collections {
+        let mut collections = Collections::default();
+        collections.lines = CollectionWithId::from(Line {
+            id: "line:1".to_string(),
+            ..Default::default()
+        });
+        collections.routes = CollectionWithId::from(Route {
+            id: "route:1".to_string(),
+            line_id: "line:1".to_string(),
+            ..Default::default()
+        });
+        collections.stop_points = CollectionWithId::from(StopPoint {
+            id: "sp:1".to_string(),
+            ..Default::default()
+        });
+        // 2020-01-06 is a monday
+        collections.calendars = CollectionWithId::new(vec![
+            calendar("weekdays", &[(2020, 1, 6), (2020, 1, 7), (2020, 1, 10)]),
+            calendar("sunday", &[(2020, 1, 12)]),
+        ])
+        .unwrap();
+        collections.vehicle_journeys = CollectionWithId::new(vec![
+            vehicle_journey("vj:1", Time::new(8, 0, 0), "weekdays", &collections),
+            vehicle_journey("vj:2", Time::new(8, 0, 0), "sunday", &collections),
+            vehicle_journey("vj:3", Time::new(9, 0, 0), "sunday", &collections),
+            vehicle_journey("vj:4", Time::new(9, 0, 0), "sunday", &collections),
+        ])
+        .unwrap();
+        collections
+    }
+
+    #[test]
+    fn compute_days_of_operation() {
+        let calendar = calendar(
+            "c",
+            &[(2020, 1, 6), (2020, 1, 8), (2020, 1, 13), (2020, 1, 11)],
+        );
+        assert_eq!("1010010", days_of_operation(&calendar.dates));
+    }
+
+    #[test]
+    fn attach_vehicle_journey_codes() {
+        let rules = "line_id,departure_time,days,object_system,object_code\n\
+                     line:1,08:00:00,1100100,AVL,1001\n\
+                     line:1,08:00:00,0000001,AVL,1002\n\
+                     line:1,09:00:00,0000001,AVL,1003\n\
+                     line:1,10:00:00,0000001,AVL,1004\n\
+                     line:2,08:00:00,1100100,AVL,1005\n\
+                     line:1,08:00:00,weekday,AVL,1006";
+        test_in_tmp_dir(|path| {
+            let rule_path = path.join("vehicle_journey_codes.csv");
+            File::create(&rule_path)
+                .unwrap()
+                .write_all(rules.as_bytes())
+                .unwrap();
+            let mut collections = collections();
+            let mut report = Report::default();
+            let mut changelog = ChangeLog::default();
+            apply_rules(&mut collections, &[rule_path], &mut report, &mut changelog).unwrap();
+
+            let codes = |id: &str| -> Vec<(String, String)> {
+                collections
+                    .vehicle_journeys
+                    .get(id)
+                    .unwrap()
+                    .codes
+                    .iter()
+                    .cloned()
+                    .collect()
+            };
+            assert_eq!(vec![("AVL".to_string(), "1001".to_string())], codes("vj:1"));
+            assert_eq!(vec![("AVL".to_string(), "1002".to_string())], codes("vj:2"));
+            assert!(codes("vj:3").is_empty());
+            assert!(codes("vj:4").is_empty());
+            assert_eq!(2, changelog.changes().len());
+
+            let categories: Vec<ReportType> =
+                report.errors.iter().map(|error| error.category).collect();
+            assert_eq!(
+                vec![
+                    ReportType::AmbiguousVehicleJourney,
+                    ReportType::UnmatchedVehicleJourney,
+                    ReportType::UnmatchedVehicleJourney,
+                    ReportType::InvalidValue,
+                ],
+                categories
+            );
+        });
+    }
+}
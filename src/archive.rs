@@ -0,0 +1,251 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Archives of the files of a directory, see
+//! [ntfs::write_to_archive](../ntfs/fn.write_to_archive.html).
+//!
+//! A ZIP archive is always available, this module needing the `zip` feature.
+//! The `.tar.gz` archives need the `tar-gz` feature, the `.tar.zst` archives
+//! need the `tar-zst` feature.
+
+use crate::Result;
+use failure::bail;
+use log::debug;
+use std::{
+    fs,
+    io::{Read, Write},
+    path::Path,
+};
+use walkdir::WalkDir;
+
+/// Format of an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArchiveFormat {
+    /// ZIP archive, compressed with deflate
+    Zip,
+    /// tar archive compressed with gzip
+    #[cfg(feature = "tar-gz")]
+    TarGz,
+    /// tar archive compressed with zstd
+    #[cfg(feature = "tar-zst")]
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// Format of the archive given by the extension of `path`: `.zip`,
+    /// `.tar.gz` (or `.tgz`) or `.tar.zst`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        if name.ends_with(".zip") {
+            return Ok(ArchiveFormat::Zip);
+        }
+        #[cfg(feature = "tar-gz")]
+        {
+            if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+                return Ok(ArchiveFormat::TarGz);
+            }
+        }
+        #[cfg(feature = "tar-zst")]
+        {
+            if name.ends_with(".tar.zst") {
+                return Ok(ArchiveFormat::TarZst);
+            }
+        }
+        bail!("unsupported archive format for {:?}", path)
+    }
+}
+
+fn zip_to(source_path: &Path, zip_file: &Path) -> Result<()> {
+    let file = fs::File::create(zip_file)?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut buffer = Vec::new();
+    for entry in WalkDir::new(source_path) {
+        let path = entry?.path().to_owned();
+        if path.is_file() {
+            let name = path.strip_prefix(source_path)?.to_owned();
+            if let Some(name) = name.to_str() {
+                debug!("adding {:?} as {:?} ...", path, name);
+                zip.start_file(name, options)?;
+                let mut f = fs::File::open(path)?;
+
+                f.read_to_end(&mut buffer)?;
+                zip.write_all(&*buffer)?;
+                buffer.clear();
+            }
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(any(feature = "tar-gz", feature = "tar-zst"))]
+fn create_file(path: &Path) -> Result<std::fs::File> {
+    use failure::ResultExt;
+    Ok(std::fs::File::create(path).with_context(|_| format!("Error writing {:?}", path))?)
+}
+
+#[cfg(any(feature = "tar-gz", feature = "tar-zst"))]
+fn tar_to<W: std::io::Write>(source_path: &Path, writer: W) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    for entry in WalkDir::new(source_path) {
+        let path = entry?.path().to_owned();
+        if path.is_file() {
+            let name = path.strip_prefix(source_path)?;
+            debug!("adding {:?} as {:?} ...", path, name);
+            builder.append_path_with_name(&path, name)?;
+        }
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// Archives the files of the directory `source_path` into `archive_file`,
+/// in the given `format`. The paths in the archive are relative to
+/// `source_path`.
+pub fn archive_to<P, R>(source_path: P, archive_file: R, format: ArchiveFormat) -> Result<()>
+where
+    P: AsRef<Path>,
+    R: AsRef<Path>,
+{
+    let source_path = source_path.as_ref();
+    let archive_file = archive_file.as_ref();
+    match format {
+        ArchiveFormat::Zip => zip_to(source_path, archive_file)?,
+        #[cfg(feature = "tar-gz")]
+        ArchiveFormat::TarGz => {
+            let file = create_file(archive_file)?;
+            let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            tar_to(source_path, encoder)?.finish()?;
+        }
+        #[cfg(feature = "tar-zst")]
+        ArchiveFormat::TarZst => {
+            let file = create_file(archive_file)?;
+            let encoder = zstd::Encoder::new(file, 0)?;
+            tar_to(source_path, encoder)?.finish()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    #[cfg(any(feature = "tar-gz", feature = "tar-zst"))]
+    use {crate::test_utils::test_in_tmp_dir, std::path::PathBuf};
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(
+            ArchiveFormat::Zip,
+            ArchiveFormat::from_path("/tmp/ntfs.ZIP").unwrap()
+        );
+        assert!(ArchiveFormat::from_path("/tmp/ntfs.rar").is_err());
+    }
+
+    #[cfg(feature = "tar-gz")]
+    #[test]
+    fn tar_gz_format_from_path() {
+        assert_eq!(
+            ArchiveFormat::TarGz,
+            ArchiveFormat::from_path("ntfs.tar.gz").unwrap()
+        );
+        assert_eq!(
+            ArchiveFormat::TarGz,
+            ArchiveFormat::from_path("ntfs.tgz").unwrap()
+        );
+    }
+
+    // Writes an archive of a directory holding `a.txt` and `dir/b.txt` and
+    // returns the path of the archive
+    #[cfg(any(feature = "tar-gz", feature = "tar-zst"))]
+    fn write_archive(path: &Path, archive_name: &str, format: ArchiveFormat) -> PathBuf {
+        let source_path = path.join("source");
+        fs::create_dir_all(source_path.join("dir")).unwrap();
+        fs::write(source_path.join("a.txt"), "a").unwrap();
+        fs::write(source_path.join("dir").join("b.txt"), "b").unwrap();
+        let archive_path = path.join(archive_name);
+        archive_to(&source_path, &archive_path, format).unwrap();
+        archive_path
+    }
+
+    // The names and contents of the files of a tar archive, sorted by name
+    #[cfg(any(feature = "tar-gz", feature = "tar-zst"))]
+    fn tar_files<R: Read>(reader: R) -> Vec<(String, String)> {
+        let mut archive = tar::Archive::new(reader);
+        let mut files: Vec<(String, String)> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                (name, content)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[cfg(feature = "tar-gz")]
+    #[test]
+    fn write_tar_gz() {
+        test_in_tmp_dir(|path| {
+            let archive_path = write_archive(path, "archive.tar.gz", ArchiveFormat::TarGz);
+
+            let decoder = flate2::read::GzDecoder::new(fs::File::open(&archive_path).unwrap());
+            assert_eq!(
+                vec![
+                    ("a.txt".to_string(), "a".to_string()),
+                    ("dir/b.txt".to_string(), "b".to_string()),
+                ],
+                tar_files(decoder)
+            );
+        });
+    }
+
+    #[cfg(feature = "tar-zst")]
+    #[test]
+    fn write_tar_zst() {
+        test_in_tmp_dir(|path| {
+            let archive_path = write_archive(path, "archive.tar.zst", ArchiveFormat::TarZst);
+
+            let decoder = zstd::Decoder::new(fs::File::open(&archive_path).unwrap()).unwrap();
+            assert_eq!(
+                vec![
+                    ("a.txt".to_string(), "a".to_string()),
+                    ("dir/b.txt".to_string(), "b".to_string()),
+                ],
+                tar_files(decoder)
+            );
+        });
+    }
+
+    #[cfg(feature = "tar-zst")]
+    #[test]
+    fn tar_zst_format_from_path() {
+        assert_eq!(
+            ArchiveFormat::TarZst,
+            ArchiveFormat::from_path("ntfs.tar.zst").unwrap()
+        );
+    }
+}
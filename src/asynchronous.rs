@@ -0,0 +1,144 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Async variants of the reading and writing functions, for the services
+//! converting data in an async runtime (e.g. in the handlers of a web
+//! server).
+//!
+//! The conversions are run on the blocking thread pool of
+//! [tokio](https://tokio.rs), so they don't block the threads of the
+//! runtime. This module needs the `async` feature.
+
+#[cfg(feature = "zip")]
+use crate::archive::ArchiveFormat;
+use crate::{gtfs, ntfs, Model, Result};
+use chrono::{DateTime, FixedOffset};
+use failure::format_err;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+async fn spawn_blocking<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| format_err!("{}", e))?
+}
+
+fn to_path_buf<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().to_path_buf()
+}
+
+/// Async variant of [ntfs::read](../ntfs/fn.read.html)
+pub async fn read_ntfs<P: AsRef<Path>>(path: P) -> Result<Model> {
+    let path = to_path_buf(path);
+    spawn_blocking(move || ntfs::read(path)).await
+}
+
+/// Async variant of [ntfs::write](../ntfs/fn.write.html)
+pub async fn write_ntfs<P: AsRef<Path>>(
+    model: Arc<Model>,
+    path: P,
+    current_datetime: DateTime<FixedOffset>,
+) -> Result<()> {
+    let path = to_path_buf(path);
+    spawn_blocking(move || ntfs::write(&model, path, current_datetime)).await
+}
+
+/// Async variant of [ntfs::write_to_zip](../ntfs/fn.write_to_zip.html)
+#[cfg(feature = "zip")]
+pub async fn write_ntfs_to_zip<P: AsRef<Path>>(
+    model: Arc<Model>,
+    path: P,
+    current_datetime: DateTime<FixedOffset>,
+) -> Result<()> {
+    let path = to_path_buf(path);
+    spawn_blocking(move || ntfs::write_to_zip(&model, path, current_datetime)).await
+}
+
+/// Async variant of [ntfs::write_to_archive](../ntfs/fn.write_to_archive.html)
+#[cfg(feature = "zip")]
+pub async fn write_ntfs_to_archive<P: AsRef<Path>>(
+    model: Arc<Model>,
+    path: P,
+    current_datetime: DateTime<FixedOffset>,
+    format: ArchiveFormat,
+) -> Result<()> {
+    let path = to_path_buf(path);
+    spawn_blocking(move || ntfs::write_to_archive(&model, path, current_datetime, format)).await
+}
+
+/// Async variant of [gtfs::read_from_path](../gtfs/fn.read_from_path.html)
+pub async fn read_gtfs_from_path<P: AsRef<Path>>(
+    path: P,
+    configuration: gtfs::Configuration,
+) -> Result<Model> {
+    let path = to_path_buf(path);
+    spawn_blocking(move || gtfs::read_from_path(path, configuration)).await
+}
+
+/// Async variant of [gtfs::read_from_zip](../gtfs/fn.read_from_zip.html)
+#[cfg(feature = "zip")]
+pub async fn read_gtfs_from_zip<P: AsRef<Path>>(
+    path: P,
+    configuration: gtfs::Configuration,
+) -> Result<Model> {
+    let path = to_path_buf(path);
+    spawn_blocking(move || gtfs::read_from_zip(path, configuration)).await
+}
+
+/// Async variant of [gtfs::write](../gtfs/fn.write.html)
+pub async fn write_gtfs<P: AsRef<Path>>(model: Model, path: P) -> Result<()> {
+    let path = to_path_buf(path);
+    spawn_blocking(move || gtfs::write(model, path)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn read_and_write_ntfs() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let model = runtime
+            .block_on(read_ntfs("tests/fixtures/minimal_ntfs/"))
+            .unwrap();
+        let model = Arc::new(model);
+        test_in_tmp_dir(|path| {
+            runtime
+                .block_on(write_ntfs(model.clone(), path, get_test_datetime()))
+                .unwrap();
+            let written = runtime.block_on(read_ntfs(path)).unwrap();
+            assert_eq!(model.vehicle_journeys.len(), written.vehicle_journeys.len());
+        });
+    }
+
+    #[test]
+    fn read_unknown_ntfs() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert!(runtime
+            .block_on(read_ntfs("tests/fixtures/unknown/"))
+            .is_err());
+    }
+}
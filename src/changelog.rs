@@ -0,0 +1,143 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Machine-readable log of the changes made to the objects of a model by an
+//! operation (e.g. [apply_rules](../apply_rules/fn.apply_rules.html)), so the
+//! systems using the model can update only the changed objects.
+
+use crate::{objects::ObjectType, Result};
+use failure::ResultExt;
+use serde::Serialize;
+use std::{fs::File, path::Path};
+
+/// A change of a field of an object
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    /// Type of the changed object
+    pub object_type: ObjectType,
+    /// Identifier of the changed object
+    pub object_id: String,
+    /// Name of the changed field, as in the NTFS (e.g. `line_name`)
+    pub field: String,
+    /// Value of the field before the change, `None` for an added value (e.g.
+    /// a new code)
+    pub old_value: Option<String>,
+    /// Value of the field after the change, `None` for a removed value
+    pub new_value: Option<String>,
+}
+
+/// The changes made by an operation, in the order they were made
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct ChangeLog {
+    changes: Vec<Change>,
+}
+
+impl ChangeLog {
+    /// Records the change of `field` of an object from `old_value` to
+    /// `new_value`; nothing is recorded if both values are the same
+    pub fn add(
+        &mut self,
+        object_type: ObjectType,
+        object_id: &str,
+        field: &str,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    ) {
+        if old_value != new_value {
+            self.changes.push(Change {
+                object_type,
+                object_id: object_id.to_string(),
+                field: field.to_string(),
+                old_value,
+                new_value,
+            });
+        }
+    }
+
+    /// The recorded changes
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Writes the changes as CSV if `path` has a `.csv` extension, as a JSON
+    /// array otherwise.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        match path.extension() {
+            Some(extension) if extension == "csv" => {
+                let mut wtr = csv::Writer::from_path(path)
+                    .with_context(|_| format!("Error writing {:?}", path))?;
+                for change in &self.changes {
+                    wtr.serialize(change)
+                        .with_context(|_| format!("Error writing {:?}", path))?;
+                }
+                wtr.flush()
+                    .with_context(|_| format!("Error writing {:?}", path))?;
+            }
+            _ => {
+                let file =
+                    File::create(path).with_context(|_| format!("Error writing {:?}", path))?;
+                serde_json::to_writer_pretty(file, self)
+                    .with_context(|_| format!("Error writing {:?}", path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn write_changelog_as_csv() {
+        test_in_tmp_dir(|path| {
+            let mut changelog = ChangeLog::default();
+            changelog.add(
+                ObjectType::Line,
+                "line:1",
+                "line_name",
+                Some("Line 1".to_string()),
+                Some("Line One".to_string()),
+            );
+            changelog.add(
+                ObjectType::Line,
+                "line:1",
+                "line_code",
+                Some("1".to_string()),
+                Some("1".to_string()),
+            );
+            changelog.add(
+                ObjectType::StopPoint,
+                "sp:1",
+                "object_code:UIC",
+                None,
+                Some("8727100".to_string()),
+            );
+            assert_eq!(2, changelog.changes().len());
+            let changelog_path = path.join("changelog.csv");
+            changelog.write(&changelog_path).unwrap();
+            assert_eq!(
+                "object_type,object_id,field,old_value,new_value\n\
+                 line,line:1,line_name,Line 1,Line One\n\
+                 stop_point,sp:1,object_code:UIC,,8727100\n",
+                fs::read_to_string(changelog_path).unwrap()
+            );
+        });
+    }
+}
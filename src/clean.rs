@@ -0,0 +1,595 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! See functions clean, dedup_vehicle_journeys and Collections::clean_transfers
+
+use crate::{
+    model::{Collections, Model},
+    objects::{
+        Calendar, CommentLinks, CommentLinksT, CommentType, ContinuousStopping, KeysValues,
+        ObjectType, StopPoint, StopTime, StopTimePrecision, Transfer, VehicleJourney,
+    },
+    report::{Report, ReportEntry, ReportType, Severity},
+    transfers::NOT_POSSIBLE_TRANSFER_TIME,
+    Result,
+};
+use chrono::NaiveDate;
+use log::{info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+use typed_index_collection::{Collection, CollectionWithId, Id, Idx};
+use wkt::ToWkt;
+
+/// Maximum time, in seconds, of a transfer from a stop point to itself
+pub const MAX_SELF_TRANSFER_TIME: u32 = 3600;
+
+/// Options of [clean](fn.clean.html)
+#[derive(Debug, Default)]
+pub struct CleanOptions {
+    /// Merges the comments with the same type, label, name and url
+    pub dedup_comments: bool,
+    /// Merges the identical geometries (see
+    /// [dedup_geometries](../model/struct.Collections.html#method.dedup_geometries))
+    pub dedup_geometries: bool,
+    /// Reorders the stop times of each vehicle journey by time (see
+    /// [fix_stop_time_order](../model/struct.Collections.html#method.fix_stop_time_order))
+    pub fix_stop_time_order: bool,
+    /// Removes the duplicated and invalid transfers (see
+    /// [clean_transfers](../model/struct.Collections.html#method.clean_transfers)),
+    /// the transfers removed being logged
+    pub clean_transfers: bool,
+    /// Restricts the validity period to these dates (both included)
+    pub validity_period: Option<(NaiveDate, NaiveDate)>,
+}
+
+/// Cleans the `model` with the given `options`.
+///
+/// The objects that are not referenced anymore (orphan stop areas, unused
+/// comments, calendars without vehicle journeys, etc.) are always removed.
+pub fn clean(model: Model, options: &CleanOptions) -> Result<Model> {
+    info!("Cleaning the model...");
+    let mut collections = model.into_collections();
+    if let Some((start_date, end_date)) = options.validity_period {
+        collections.restrict_period(start_date, end_date)?;
+    }
+    if options.dedup_comments {
+        collections.dedup_comments()?;
+    }
+    if options.dedup_geometries {
+        collections.dedup_geometries()?;
+    }
+    if options.fix_stop_time_order {
+        collections.fix_stop_time_order();
+    }
+    if options.clean_transfers {
+        let mut report = Report::default();
+        collections.clean_transfers(&mut report);
+        for warning in report.entries(Severity::Warning) {
+            warn!("{}", warning.message);
+        }
+    }
+    Model::new(collections)
+}
+
+/// Merges the duplicated vehicle journeys of the `model`, see
+/// [Collections::dedup_vehicle_journeys](../model/struct.Collections.html#method.dedup_vehicle_journeys).
+///
+/// The merged vehicle journeys are written in `report_path`, as CSV if it has
+/// a `.csv` extension, as JSON otherwise.
+pub fn dedup_vehicle_journeys<P: AsRef<Path>>(model: Model, report_path: P) -> Result<Model> {
+    info!("Merging the duplicated vehicle journeys...");
+    let mut report = Report::default();
+    let mut collections = model.into_collections();
+    collections.dedup_vehicle_journeys(&mut report)?;
+
+    let report_path = report_path.as_ref();
+    info!("Writing report {:?}", report_path);
+    report.write(report_path)?;
+
+    Model::new(collections)
+}
+
+// What makes 2 vehicle journeys identical: everything but their identifier,
+// calendar, dataset and the identifiers local to their dataset (block,
+// geometry, journey pattern), and for each of their stop times, everything
+// but their sequence and identifier
+#[derive(PartialEq, Eq, Hash)]
+struct VehicleJourneyKey<'a> {
+    route_id: &'a str,
+    physical_mode_id: &'a str,
+    company_id: &'a str,
+    headsign: Option<&'a str>,
+    short_name: Option<&'a str>,
+    trip_property_id: Option<&'a str>,
+    stop_times: Vec<StopTimeKey<'a>>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct StopTimeKey<'a> {
+    stop_point_idx: Idx<StopPoint>,
+    arrival_time: u32,
+    departure_time: u32,
+    boarding_duration: u16,
+    alighting_duration: u16,
+    pickup_type: u8,
+    drop_off_type: u8,
+    datetime_estimated: bool,
+    local_zone_id: Option<u16>,
+    precision: Option<&'a StopTimePrecision>,
+    headsign: Option<&'a str>,
+    continuous_stopping: Option<ContinuousStopping>,
+    // the text of the comment, its identifier being local to its dataset
+    comment: Option<&'a str>,
+}
+
+fn vehicle_journey_key<'a>(
+    collections: &'a Collections,
+    vj: &'a VehicleJourney,
+) -> VehicleJourneyKey<'a> {
+    let stop_times = vj
+        .stop_times
+        .iter()
+        .map(|st| {
+            let stop_time_id = (vj.id.clone(), st.sequence);
+            StopTimeKey {
+                stop_point_idx: st.stop_point_idx,
+                arrival_time: st.arrival_time.total_seconds(),
+                departure_time: st.departure_time.total_seconds(),
+                boarding_duration: st.boarding_duration,
+                alighting_duration: st.alighting_duration,
+                pickup_type: st.pickup_type,
+                drop_off_type: st.drop_off_type,
+                datetime_estimated: st.datetime_estimated,
+                local_zone_id: st.local_zone_id,
+                precision: st.precision.as_ref(),
+                headsign: collections
+                    .stop_time_headsigns
+                    .get(&stop_time_id)
+                    .map(String::as_str),
+                continuous_stopping: collections
+                    .stop_time_continuous_stoppings
+                    .get(&stop_time_id)
+                    .cloned(),
+                comment: collections
+                    .stop_time_comments
+                    .get(&stop_time_id)
+                    .and_then(|comment_id| collections.comments.get(comment_id))
+                    .map(|comment| comment.name.as_str()),
+            }
+        })
+        .collect();
+    VehicleJourneyKey {
+        route_id: &vj.route_id,
+        physical_mode_id: &vj.physical_mode_id,
+        company_id: &vj.company_id,
+        headsign: vj.headsign.as_deref(),
+        short_name: vj.short_name.as_deref(),
+        trip_property_id: vj.trip_property_id.as_deref(),
+        stop_times,
+    }
+}
+
+// The identifier `<vj_id>:merged`, or `<vj_id>:merged:<n>` with the smallest
+// `n` such that the identifier is not used
+fn merged_calendar_id<F>(vj_id: &str, is_used: F) -> String
+where
+    F: Fn(&str) -> bool,
+{
+    let id = format!("{}:merged", vj_id);
+    if !is_used(&id) {
+        return id;
+    }
+    (1..)
+        .map(|n| format!("{}:{}", id, n))
+        .find(|merged_id| !is_used(merged_id))
+        .unwrap()
+}
+
+// The codes, comments and object properties of the vehicle journeys merged
+// into a kept vehicle journey, with its new calendar if its dates change
+#[derive(Default)]
+struct MergedVehicleJourneys {
+    calendar: Option<Calendar>,
+    codes: KeysValues,
+    comment_links: CommentLinksT,
+    object_properties: KeysValues,
+}
+
+fn replace_comment_links<T>(
+    collection: &mut CollectionWithId<T>,
+    replacements: &HashMap<String, String>,
+) -> Result<()>
+where
+    T: CommentLinks + Id<T>,
+{
+    let mut objects = collection.take();
+    for object in &mut objects {
+        let comment_links = object.comment_links_mut();
+        *comment_links = comment_links
+            .iter()
+            .map(|comment_id| replacements.get(comment_id).unwrap_or(comment_id).clone())
+            .collect();
+    }
+    *collection = CollectionWithId::new(objects)?;
+    Ok(())
+}
+
+fn replace_geometry_ids<T, F>(
+    collection: &mut CollectionWithId<T>,
+    replacements: &HashMap<String, String>,
+    geometry_id: F,
+) -> Result<()>
+where
+    T: Id<T>,
+    F: Fn(&mut T) -> &mut Option<String>,
+{
+    let mut objects = collection.take();
+    for object in &mut objects {
+        let geometry_id = geometry_id(object);
+        let kept_id = geometry_id
+            .as_ref()
+            .and_then(|id| replacements.get(id))
+            .cloned();
+        if kept_id.is_some() {
+            *geometry_id = kept_id;
+        }
+    }
+    *collection = CollectionWithId::new(objects)?;
+    Ok(())
+}
+
+// Whether the times of a transfer from a stop point to itself make sense, a
+// transfer not possible being kept
+fn is_sensible_self_transfer(transfer: &Transfer) -> bool {
+    let times = [transfer.min_transfer_time, transfer.real_min_transfer_time];
+    times
+        .iter()
+        .flatten()
+        .all(|time| *time <= MAX_SELF_TRANSFER_TIME || *time == NOT_POSSIBLE_TRANSFER_TIME)
+        && match times {
+            [Some(min_transfer_time), Some(real_min_transfer_time)] => {
+                real_min_transfer_time >= min_transfer_time
+            }
+            _ => true,
+        }
+}
+
+// The `transfers` without the ones removed by
+// [Collections::clean_transfers](../model/struct.Collections.html#method.clean_transfers)
+fn cleaned_transfers(
+    transfers: Vec<Transfer>,
+    stop_points: &CollectionWithId<StopPoint>,
+    report: &mut Report,
+) -> Vec<Transfer> {
+    let (mut nb_dangling, mut nb_self_transfers, mut nb_duplicates) = (0, 0, 0);
+    let mut stop_point_pairs = HashSet::new();
+    let transfers = transfers
+        .into_iter()
+        .filter(|transfer| {
+            if !stop_points.contains_id(&transfer.from_stop_id)
+                || !stop_points.contains_id(&transfer.to_stop_id)
+            {
+                nb_dangling += 1;
+                false
+            } else if transfer.from_stop_id == transfer.to_stop_id
+                && !is_sensible_self_transfer(transfer)
+            {
+                nb_self_transfers += 1;
+                false
+            } else if !stop_point_pairs
+                .insert((transfer.from_stop_id.clone(), transfer.to_stop_id.clone()))
+            {
+                nb_duplicates += 1;
+                false
+            } else {
+                true
+            }
+        })
+        .collect();
+    if nb_dangling > 0 {
+        report.add_warning(
+            format!(
+                "{} transfer(s) referencing unknown stop points removed",
+                nb_dangling
+            ),
+            ReportType::DanglingReference,
+        );
+    }
+    if nb_self_transfers > 0 {
+        report.add_warning(
+            format!(
+                "{} transfer(s) from a stop point to itself with nonsensical times removed",
+                nb_self_transfers
+            ),
+            ReportType::InvalidValue,
+        );
+    }
+    if nb_duplicates > 0 {
+        report.add_warning(
+            format!("{} duplicated transfer(s) removed", nb_duplicates),
+            ReportType::OverlappingTransfers,
+        );
+    }
+    transfers
+}
+
+fn renumber_stop_times<T>(
+    stop_time_values: &mut HashMap<(String, u32), T>,
+    vj_id: &str,
+    new_sequences: &HashMap<u32, u32>,
+) {
+    // all the values are removed before being inserted again, as a new
+    // sequence may be the old sequence of another stop time
+    let moved: Vec<(u32, T)> = new_sequences
+        .iter()
+        .filter_map(|(old_sequence, new_sequence)| {
+            stop_time_values
+                .remove(&(vj_id.to_string(), *old_sequence))
+                .map(|value| (*new_sequence, value))
+        })
+        .collect();
+    for (sequence, value) in moved {
+        stop_time_values.insert((vj_id.to_string(), sequence), value);
+    }
+}
+
+impl Collections {
+    /// Merges the comments with the same type, label, name and url into the
+    /// first one of them, updating the links to the others. The duplicates
+    /// are removed by the sanitizing of the model.
+    pub fn dedup_comments(&mut self) -> Result<()> {
+        let mut kept_ids: HashMap<(&CommentType, Option<&str>, &str, Option<&str>), &str> =
+            HashMap::new();
+        let mut replacements: HashMap<String, String> = HashMap::new();
+        for comment in self.comments.values() {
+            let key = (
+                &comment.comment_type,
+                comment.label.as_deref(),
+                comment.name.as_str(),
+                comment.url.as_deref(),
+            );
+            match kept_ids.get(&key) {
+                Some(kept_id) => {
+                    replacements.insert(comment.id.clone(), kept_id.to_string());
+                }
+                None => {
+                    kept_ids.insert(key, &comment.id);
+                }
+            }
+        }
+        if replacements.is_empty() {
+            return Ok(());
+        }
+        info!("{} duplicated comments merged", replacements.len());
+        replace_comment_links(&mut self.lines, &replacements)?;
+        replace_comment_links(&mut self.routes, &replacements)?;
+        replace_comment_links(&mut self.vehicle_journeys, &replacements)?;
+        replace_comment_links(&mut self.stop_areas, &replacements)?;
+        replace_comment_links(&mut self.stop_points, &replacements)?;
+        replace_comment_links(&mut self.stop_locations, &replacements)?;
+        for comment_id in self.stop_time_comments.values_mut() {
+            if let Some(kept_id) = replacements.get(comment_id) {
+                *comment_id = kept_id.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the identical geometries (e.g. the shape of a route repeated
+    /// for each of its trips) into the first one of them, updating the
+    /// lines, routes, vehicle journeys and stops referencing the others. The
+    /// duplicates are removed by the sanitizing of the model.
+    pub fn dedup_geometries(&mut self) -> Result<()> {
+        let mut kept_ids: HashMap<String, &str> = HashMap::new();
+        let mut replacements: HashMap<String, String> = HashMap::new();
+        for geometry in self.geometries.values() {
+            let wkt = geometry.geometry.to_wkt().items[0].to_string();
+            match kept_ids.get(&wkt) {
+                Some(kept_id) => {
+                    replacements.insert(geometry.id.clone(), kept_id.to_string());
+                }
+                None => {
+                    kept_ids.insert(wkt, &geometry.id);
+                }
+            }
+        }
+        if replacements.is_empty() {
+            return Ok(());
+        }
+        info!("{} duplicated geometries merged", replacements.len());
+        replace_geometry_ids(&mut self.lines, &replacements, |line| &mut line.geometry_id)?;
+        replace_geometry_ids(&mut self.routes, &replacements, |route| {
+            &mut route.geometry_id
+        })?;
+        replace_geometry_ids(&mut self.vehicle_journeys, &replacements, |vj| {
+            &mut vj.geometry_id
+        })?;
+        replace_geometry_ids(&mut self.stop_areas, &replacements, |stop_area| {
+            &mut stop_area.geometry_id
+        })?;
+        replace_geometry_ids(&mut self.stop_points, &replacements, |stop_point| {
+            &mut stop_point.geometry_id
+        })?;
+        replace_geometry_ids(&mut self.stop_locations, &replacements, |stop_location| {
+            &mut stop_location.geometry_id
+        })?;
+        Ok(())
+    }
+
+    /// Merges the identical vehicle journeys, e.g. the same trip in 2
+    /// merged datasets: same route, physical mode, company, headsign, short
+    /// name, trip property and same stop times (stop points, times, pick-up,
+    /// drop-off, headsigns, continuous stoppings, comments, etc.), whatever their identifiers,
+    /// calendars and datasets. The first of them is kept, running on the
+    /// union of their calendars (a new calendar `<vj_id>:merged` being
+    /// created when needed) and with the union of their codes, comments and
+    /// object properties; each merged vehicle journey is added to the
+    /// `report`.
+    ///
+    /// The vehicle journeys with frequencies are not merged.
+    pub fn dedup_vehicle_journeys(&mut self, report: &mut Report) -> Result<()> {
+        let with_frequencies: HashSet<&str> = self
+            .frequencies
+            .values()
+            .map(|frequency| frequency.vehicle_journey_id.as_str())
+            .collect();
+        // the vehicle journeys of each key, in the order of the collection,
+        // the groups being in the order of their first vehicle journey so
+        // that the report and the new calendars don't change between runs
+        let mut group_indexes: HashMap<VehicleJourneyKey<'_>, usize> = HashMap::new();
+        let mut groups: Vec<Vec<&VehicleJourney>> = Vec::new();
+        for vj in self.vehicle_journeys.values() {
+            if vj.stop_times.is_empty()
+                || with_frequencies.contains(vj.id.as_str())
+                || !self.calendars.contains_id(&vj.service_id)
+            {
+                continue;
+            }
+            let index = *group_indexes
+                .entry(vehicle_journey_key(self, vj))
+                .or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+            groups[index].push(vj);
+        }
+
+        let mut updates: HashMap<String, MergedVehicleJourneys> = HashMap::new();
+        let mut merged_ids: HashSet<String> = HashSet::new();
+        let mut new_calendar_ids: HashSet<String> = HashSet::new();
+        for vjs in groups.iter().filter(|vjs| vjs.len() > 1) {
+            let kept = vjs[0];
+            let kept_dates = &self.calendars.get(&kept.service_id).unwrap().dates;
+            let mut dates = kept_dates.clone();
+            let mut update = MergedVehicleJourneys::default();
+            for merged in &vjs[1..] {
+                report.add(
+                    Severity::Info,
+                    ReportEntry::new(
+                        ReportType::ObjectMerged,
+                        format!(
+                            "vehicle journey {:?} merged into the identical vehicle journey {:?}",
+                            merged.id, kept.id
+                        ),
+                    )
+                    .with_object(ObjectType::VehicleJourney, &merged.id),
+                );
+                if let Some(calendar) = self.calendars.get(&merged.service_id) {
+                    dates.extend(calendar.dates.iter().cloned());
+                }
+                update.codes.extend(merged.codes.iter().cloned());
+                update
+                    .comment_links
+                    .extend(merged.comment_links.iter().cloned());
+                update
+                    .object_properties
+                    .extend(merged.object_properties.iter().cloned());
+                merged_ids.insert(merged.id.clone());
+            }
+            if dates != *kept_dates {
+                let calendars = &self.calendars;
+                let id = merged_calendar_id(&kept.id, |id| {
+                    calendars.contains_id(id) || new_calendar_ids.contains(id)
+                });
+                new_calendar_ids.insert(id.clone());
+                update.calendar = Some(Calendar { id, dates });
+            }
+            updates.insert(kept.id.clone(), update);
+        }
+        if merged_ids.is_empty() {
+            return Ok(());
+        }
+        info!("{} duplicated vehicle journeys merged", merged_ids.len());
+
+        let mut vehicle_journeys = self.vehicle_journeys.take();
+        vehicle_journeys.retain(|vj| !merged_ids.contains(&vj.id));
+        for vj in &mut vehicle_journeys {
+            if let Some(update) = updates.remove(&vj.id) {
+                if let Some(calendar) = update.calendar {
+                    vj.service_id = calendar.id.clone();
+                    self.calendars.push(calendar)?;
+                }
+                vj.codes.extend(update.codes);
+                vj.comment_links.extend(update.comment_links);
+                vj.object_properties.extend(update.object_properties);
+            }
+        }
+        self.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
+        for stop_time_values in &mut [
+            &mut self.stop_time_ids,
+            &mut self.stop_time_headsigns,
+            &mut self.stop_time_comments,
+        ] {
+            stop_time_values.retain(|(vj_id, _), _| !merged_ids.contains(vj_id));
+        }
+        self.stop_time_continuous_stoppings
+            .retain(|(vj_id, _), _| !merged_ids.contains(vj_id));
+        Ok(())
+    }
+
+    /// Removes the transfers referencing unknown stop points, the transfers
+    /// from a stop point to itself with nonsensical times (above
+    /// [MAX_SELF_TRANSFER_TIME](../clean/constant.MAX_SELF_TRANSFER_TIME.html)
+    /// but for a transfer not possible, or a `real_min_transfer_time` below
+    /// the `min_transfer_time`) and the duplicated transfers between the same
+    /// stop points, the first one being kept. The number of transfers removed
+    /// for each reason is reported.
+    pub fn clean_transfers(&mut self, report: &mut Report) {
+        let transfers = cleaned_transfers(self.transfers.take(), &self.stop_points, report);
+        self.transfers = Collection::new(transfers);
+    }
+
+    /// Reorders the stop times of each vehicle journey by arrival then
+    /// departure time, when their sequences don't follow the times.
+    ///
+    /// The sequences of the vehicle journey are reassigned in the new order,
+    /// so the identifiers, headsigns, comments and continuous stoppings of the
+    /// stop times follow
+    /// their stop time.
+    pub fn fix_stop_time_order(&mut self) {
+        let mut vehicle_journeys = self.vehicle_journeys.take();
+        for vj in &mut vehicle_journeys {
+            let is_ordered = vj.stop_times.windows(2).all(|stop_times| {
+                (stop_times[0].arrival_time, stop_times[0].departure_time)
+                    <= (stop_times[1].arrival_time, stop_times[1].departure_time)
+            });
+            if is_ordered {
+                continue;
+            }
+            warn!("stop times of vehicle journey {:?} reordered", vj.id);
+            let sequences: Vec<u32> = vj.stop_times.iter().map(|st| st.sequence).collect();
+            vj.stop_times
+                .sort_by_key(|st: &StopTime| (st.arrival_time, st.departure_time));
+            let mut new_sequences = HashMap::new();
+            for (stop_time, sequence) in vj.stop_times.iter_mut().zip(sequences) {
+                if stop_time.sequence != sequence {
+                    new_sequences.insert(stop_time.sequence, sequence);
+                    stop_time.sequence = sequence;
+                }
+            }
+            renumber_stop_times(&mut self.stop_time_ids, &vj.id, &new_sequences);
+            renumber_stop_times(&mut self.stop_time_headsigns, &vj.id, &new_sequences);
+            renumber_stop_times(&mut self.stop_time_comments, &vj.id, &new_sequences);
+            renumber_stop_times(
+                &mut self.stop_time_continuous_stoppings,
+                &vj.id,
+                &new_sequences,
+            );
+        }
+        // the identifiers are unchanged, they are still unique
+        self.vehicle_journeys = CollectionWithId::new(vehicle_journeys).unwrap();
+    }
+}
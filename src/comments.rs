@@ -0,0 +1,141 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Deduplication of the comments and attachment of comments to objects.
+
+use crate::{
+    error::TransitModelError,
+    model::Collections,
+    objects::{Comment, CommentLinks},
+    Result,
+};
+use typed_index_collection::{CollectionWithId, Id};
+
+/// Merges the identical comments of all the objects (lines, routes, trips,
+/// stops and stop times), see
+/// [Collections::dedup_comments](../model/struct.Collections.html#method.dedup_comments).
+pub fn dedup(collections: &mut Collections) -> Result<()> {
+    collections.dedup_comments()
+}
+
+/// Attaches the `comment` to the object `object_id` of `objects` (e.g. the
+/// lines or the stop points of the collections), and returns the identifier
+/// of the comment linked.
+///
+/// If a comment with the same text already exists in `comments`, the object
+/// is linked to it instead of adding a duplicate.
+///
+/// ```
+/// # use transit_model::{comments, model::Collections, objects::{Comment, Line}};
+/// # use typed_index_collection::CollectionWithId;
+/// # fn run() -> transit_model::Result<()> {
+/// let mut collections = Collections::default();
+/// collections.lines = CollectionWithId::from(Line {
+///     id: "line:1".to_string(),
+///     ..Default::default()
+/// });
+/// let comment = Comment {
+///     id: "comment:1".to_string(),
+///     name: "Accessible".to_string(),
+///     ..Default::default()
+/// };
+/// let comment_id = comments::add_comment(
+///     &mut collections.comments,
+///     &mut collections.lines,
+///     "line:1",
+///     comment,
+/// )?;
+/// assert!(collections.lines.get("line:1").unwrap().comment_links.contains(&comment_id));
+/// # Ok(())
+/// # }
+/// # run().unwrap();
+/// ```
+pub fn add_comment<T>(
+    comments: &mut CollectionWithId<Comment>,
+    objects: &mut CollectionWithId<T>,
+    object_id: &str,
+    comment: Comment,
+) -> Result<String>
+where
+    T: Id<T> + CommentLinks,
+{
+    let mut object = objects.get_mut(object_id).ok_or_else(|| {
+        TransitModelError::dangling(
+            "comment_link.object_id",
+            object_id,
+            format!("object {:?} not found to attach a comment", object_id),
+        )
+    })?;
+    let comment_id = match comments.values().find(|c| c.name == comment.name) {
+        Some(existing) => existing.id.clone(),
+        None => {
+            let comment_id = comment.id.clone();
+            comments.push(comment)?;
+            comment_id
+        }
+    };
+    object.comment_links_mut().insert(comment_id.clone());
+    Ok(comment_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Line;
+    use pretty_assertions::assert_eq;
+
+    fn comment(id: &str, name: &str) -> Comment {
+        Comment {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn add_comment_reusing_identical_text() {
+        let mut comments = CollectionWithId::from(comment("c:1", "on demand"));
+        let mut lines = CollectionWithId::new(vec![
+            Line {
+                id: "line:1".to_string(),
+                ..Default::default()
+            },
+            Line {
+                id: "line:2".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+
+        let id = add_comment(
+            &mut comments,
+            &mut lines,
+            "line:1",
+            comment("c:2", "on demand"),
+        )
+        .unwrap();
+        assert_eq!("c:1", id);
+        let id = add_comment(
+            &mut comments,
+            &mut lines,
+            "line:2",
+            comment("c:3", "no bikes"),
+        )
+        .unwrap();
+        assert_eq!("c:3", id);
+        assert_eq!(2, comments.len());
+        assert!(lines.get("line:1").unwrap().comment_links.contains("c:1"));
+        assert!(add_comment(&mut comments, &mut lines, "line:3", comment("c:4", "x")).is_err());
+    }
+}
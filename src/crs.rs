@@ -0,0 +1,329 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Coordinate system of the coordinates of a dataset and, with the `proj`
+//! feature, reprojection of these coordinates
+//!
+//! The coordinate system is stored in `feed_infos.txt` with the `feed_crs`
+//! key (e.g. `EPSG:2154`). A dataset without `feed_crs` is in WGS84.
+//!
+//! The readers of formats with projected coordinates use a
+//! [Reprojection](struct.Reprojection.html) to convert them to WGS84.
+
+#[cfg(feature = "proj")]
+use crate::objects::Coord;
+use crate::{model::Collections, Result};
+use failure::format_err;
+#[cfg(feature = "proj")]
+use geo::algorithm::map_coords::MapCoordsInplace;
+#[cfg(feature = "proj")]
+use proj::Proj;
+#[cfg(feature = "proj")]
+use std::cell::Cell;
+use std::{fmt, str::FromStr};
+#[cfg(feature = "proj")]
+use typed_index_collection::CollectionWithId;
+
+/// Key of the coordinate system of the dataset in `feed_infos.txt`
+pub const FEED_CRS: &str = "feed_crs";
+
+/// A coordinate system supported by `transit_model`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateSystem {
+    /// Longitude and latitude in degrees in WGS84 (`EPSG:4326`), the default
+    Wgs84,
+    /// Lambert 93 (`EPSG:2154`), the French national referential, in meters
+    Lambert93,
+    /// Longitude and latitude in degrees in ETRS89 (`EPSG:4258`)
+    Etrs89,
+    /// Any other coordinate system of the [EPSG](https://epsg.io) registry,
+    /// by its code (e.g. `27700` for the British National Grid)
+    Epsg(u32),
+}
+
+impl CoordinateSystem {
+    /// Code of the coordinate system in the [EPSG](https://epsg.io) registry
+    pub fn epsg_code(self) -> u32 {
+        match self {
+            CoordinateSystem::Wgs84 => 4326,
+            CoordinateSystem::Lambert93 => 2154,
+            CoordinateSystem::Etrs89 => 4258,
+            CoordinateSystem::Epsg(code) => code,
+        }
+    }
+
+    #[cfg(feature = "proj")]
+    fn proj_definition(self) -> String {
+        // The geographic coordinate systems are given by their definition as
+        // their EPSG code fails at runtime (see the NeTEx France exporter)
+        match self {
+            CoordinateSystem::Wgs84 => "+proj=longlat +datum=WGS84 +no_defs".to_string(),
+            CoordinateSystem::Etrs89 => "+proj=longlat +ellps=GRS80 +no_defs".to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CoordinateSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EPSG:{}", self.epsg_code())
+    }
+}
+
+impl FromStr for CoordinateSystem {
+    type Err = failure::Error;
+
+    /// Parses an EPSG code (`EPSG:2154` or `2154`) or a name (`WGS84`,
+    /// `Lambert93` or `ETRS89`, in any case)
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let code = if s.len() > 5 && s[..5].eq_ignore_ascii_case("epsg:") {
+            &s[5..]
+        } else {
+            s
+        };
+        match code.to_lowercase().as_str() {
+            "4326" | "wgs84" => Ok(CoordinateSystem::Wgs84),
+            "2154" | "lambert93" => Ok(CoordinateSystem::Lambert93),
+            "4258" | "etrs89" => Ok(CoordinateSystem::Etrs89),
+            _ => code
+                .parse()
+                .map(CoordinateSystem::Epsg)
+                .map_err(|_| format_err!("unsupported coordinate system {:?}", s)),
+        }
+    }
+}
+
+/// Converter of coordinates from a coordinate system to another
+#[cfg(feature = "proj")]
+pub struct Reprojection {
+    from: CoordinateSystem,
+    to: CoordinateSystem,
+    converter: Proj,
+}
+
+#[cfg(feature = "proj")]
+impl Reprojection {
+    /// Builds a converter from `from` to `to`, failing if Proj doesn't know
+    /// one of the coordinate systems
+    pub fn new(from: CoordinateSystem, to: CoordinateSystem) -> Result<Self> {
+        let converter = Proj::new_known_crs(&from.proj_definition(), &to.proj_definition(), None)
+            .ok_or_else(|| {
+            format_err!("Proj cannot build a converter from '{}' to '{}'", from, to)
+        })?;
+        Ok(Reprojection {
+            from,
+            to,
+            converter,
+        })
+    }
+
+    /// Converts the coordinates of a point. The coordinates (0, 0), meaning
+    /// an unknown location, are kept as is.
+    pub fn convert(&self, coord: Coord) -> Result<Coord> {
+        if coord == Coord::default() {
+            return Ok(coord);
+        }
+        self.converter.convert(coord).map(Coord::from).map_err(|e| {
+            format_err!(
+                "cannot reproject {:?} from {} to {}: {}",
+                coord,
+                self.from,
+                self.to,
+                e
+            )
+        })
+    }
+}
+
+impl Collections {
+    /// Coordinate system of the dataset, from the `feed_crs` of
+    /// `feed_infos.txt` (WGS84 if absent)
+    pub fn coordinate_system(&self) -> Result<CoordinateSystem> {
+        self.feed_infos
+            .get(FEED_CRS)
+            .map_or(Ok(CoordinateSystem::Wgs84), |crs| crs.parse())
+    }
+
+    /// Tags the dataset with the coordinate system `crs`, without changing
+    /// its coordinates
+    pub fn set_coordinate_system(&mut self, crs: CoordinateSystem) {
+        self.feed_infos
+            .insert(FEED_CRS.to_string(), crs.to_string());
+    }
+
+    /// Reprojects the coordinates of the stop points, stop areas, stop
+    /// locations and geometries from the coordinate system of the dataset to
+    /// `crs`, and tags the dataset with `crs`.
+    ///
+    /// The coordinates (0, 0), meaning an unknown location, are kept as is.
+    #[cfg(feature = "proj")]
+    pub fn reproject(&mut self, crs: CoordinateSystem) -> Result<()> {
+        let from = self.coordinate_system()?;
+        if from == crs {
+            return Ok(());
+        }
+        let reprojection = Reprojection::new(from, crs)?;
+        let convert = |coord: &mut Coord| -> Result<()> {
+            *coord = reprojection.convert(*coord)?;
+            Ok(())
+        };
+
+        let mut stop_points = self.stop_points.take();
+        for stop_point in &mut stop_points {
+            convert(&mut stop_point.coord)?;
+        }
+        self.stop_points = CollectionWithId::new(stop_points)?;
+        let mut stop_areas = self.stop_areas.take();
+        for stop_area in &mut stop_areas {
+            convert(&mut stop_area.coord)?;
+        }
+        self.stop_areas = CollectionWithId::new(stop_areas)?;
+        let mut stop_locations = self.stop_locations.take();
+        for stop_location in &mut stop_locations {
+            convert(&mut stop_location.coord)?;
+        }
+        self.stop_locations = CollectionWithId::new(stop_locations)?;
+
+        let failed = Cell::new(false);
+        let mut geometries = self.geometries.take();
+        for geometry in &mut geometries {
+            geometry.geometry.map_coords_inplace(|&(x, y)| {
+                match reprojection.converter.convert(geo::Point::new(x, y)) {
+                    Ok(point) => (point.x(), point.y()),
+                    Err(_) => {
+                        failed.set(true);
+                        (x, y)
+                    }
+                }
+            });
+            if failed.get() {
+                return Err(format_err!(
+                    "cannot reproject the geometry {:?} to {}",
+                    geometry.id,
+                    crs
+                ));
+            }
+        }
+        self.geometries = CollectionWithId::new(geometries)?;
+
+        self.set_coordinate_system(crs);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_coordinate_systems() {
+        for (s, crs) in &[
+            ("EPSG:4326", CoordinateSystem::Wgs84),
+            ("epsg:2154", CoordinateSystem::Lambert93),
+            ("2154", CoordinateSystem::Lambert93),
+            ("Lambert93", CoordinateSystem::Lambert93),
+            ("ETRS89", CoordinateSystem::Etrs89),
+        ] {
+            assert_eq!(*crs, s.parse::<CoordinateSystem>().unwrap());
+        }
+        assert_eq!(
+            CoordinateSystem::Epsg(27700),
+            "EPSG:27700".parse::<CoordinateSystem>().unwrap()
+        );
+        assert!("EPSG:OSGB".parse::<CoordinateSystem>().is_err());
+        assert!("Mercator".parse::<CoordinateSystem>().is_err());
+        assert_eq!("EPSG:2154", CoordinateSystem::Lambert93.to_string());
+        assert_eq!("EPSG:3857", CoordinateSystem::Epsg(3857).to_string());
+    }
+
+    #[test]
+    fn tag_coordinate_system() {
+        let mut collections = Collections::default();
+        assert_eq!(
+            CoordinateSystem::Wgs84,
+            collections.coordinate_system().unwrap()
+        );
+        collections.set_coordinate_system(CoordinateSystem::Lambert93);
+        assert_eq!(
+            CoordinateSystem::Lambert93,
+            collections.coordinate_system().unwrap()
+        );
+        assert_eq!("EPSG:2154", collections.feed_infos[FEED_CRS]);
+    }
+
+    #[cfg(feature = "proj")]
+    #[test]
+    fn reproject_to_lambert93_and_back() {
+        use crate::objects::StopPoint;
+        use approx::assert_relative_eq;
+
+        let mut collections = Collections::default();
+        collections.stop_points = CollectionWithId::new(vec![
+            StopPoint {
+                id: "sp:1".to_string(),
+                coord: Coord {
+                    lon: 2.37715,
+                    lat: 48.846781,
+                },
+                ..Default::default()
+            },
+            StopPoint {
+                id: "sp:2".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+
+        collections.reproject(CoordinateSystem::Lambert93).unwrap();
+        assert_eq!(
+            CoordinateSystem::Lambert93,
+            collections.coordinate_system().unwrap()
+        );
+        let coord = collections.stop_points.get("sp:1").unwrap().coord;
+        assert_relative_eq!(654_100.0, coord.lon, epsilon = 1_000.0);
+        assert_relative_eq!(6_860_600.0, coord.lat, epsilon = 1_000.0);
+        assert_eq!(
+            Coord::default(),
+            collections.stop_points.get("sp:2").unwrap().coord
+        );
+
+        collections.reproject(CoordinateSystem::Wgs84).unwrap();
+        let coord = collections.stop_points.get("sp:1").unwrap().coord;
+        assert_relative_eq!(2.37715, coord.lon, epsilon = 1e-6);
+        assert_relative_eq!(48.846781, coord.lat, epsilon = 1e-6);
+    }
+
+    #[cfg(feature = "proj")]
+    #[test]
+    fn reproject_to_an_epsg_code() {
+        use approx::assert_relative_eq;
+
+        let reprojection =
+            Reprojection::new(CoordinateSystem::Wgs84, CoordinateSystem::Epsg(3857)).unwrap();
+        let coord = reprojection
+            .convert(Coord {
+                lon: 2.37715,
+                lat: 48.846781,
+            })
+            .unwrap();
+        assert_relative_eq!(264_623.0, coord.lon, epsilon = 1.0);
+        assert_relative_eq!(6_248_903.0, coord.lat, epsilon = 1.0);
+        assert_eq!(
+            Coord::default(),
+            reprojection.convert(Coord::default()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Structured diff of two NTFS datasets, to audit what a change of a
+//! pipeline did to its output. See function ntfs_diff.
+
+use crate::{
+    ntfs::{self, WriteOptions},
+    Result,
+};
+use chrono::{FixedOffset, TimeZone};
+use failure::ResultExt;
+use log::info;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    path::Path,
+};
+use tempfile::{tempdir, TempDir};
+
+/// Maximum number of rows of a file for the field changes of its modified
+/// objects to be detailed
+pub const SMALL_FILE_SIZE: usize = 10_000;
+
+// Columns identifying a row of a file; the rows of the other files are
+// identified by all their values
+const KEY_COLUMNS: &[(&str, &[&str])] = &[
+    ("booking_rules.txt", &["booking_rule_id"]),
+    ("calendar.txt", &["service_id"]),
+    ("calendar_dates.txt", &["service_id", "date"]),
+    ("comments.txt", &["comment_id"]),
+    ("commercial_modes.txt", &["commercial_mode_id"]),
+    ("companies.txt", &["company_id"]),
+    ("contributors.txt", &["contributor_id"]),
+    ("datasets.txt", &["dataset_id"]),
+    ("disruptions.txt", &["disruption_id"]),
+    ("equipments.txt", &["equipment_id"]),
+    ("fare_zones.txt", &["fare_zone_id"]),
+    ("feed_infos.txt", &["feed_info_param"]),
+    ("frequencies.txt", &["trip_id", "start_time"]),
+    ("geometries.txt", &["geometry_id"]),
+    ("grid_calendars.txt", &["grid_calendar_id"]),
+    ("levels.txt", &["level_id"]),
+    ("lines.txt", &["line_id"]),
+    ("networks.txt", &["network_id"]),
+    (
+        "object_properties.txt",
+        &["object_type", "object_id", "object_property_name"],
+    ),
+    ("pathways.txt", &["pathway_id"]),
+    ("physical_modes.txt", &["physical_mode_id"]),
+    ("routes.txt", &["route_id"]),
+    ("stop_times.txt", &["trip_id", "stop_sequence"]),
+    ("stops.txt", &["stop_id"]),
+    ("ticket_uses.txt", &["ticket_use_id"]),
+    ("tickets.txt", &["ticket_id"]),
+    ("transfers.txt", &["from_stop_id", "to_stop_id"]),
+    ("trip_properties.txt", &["trip_property_id"]),
+    ("trips.txt", &["trip_id"]),
+];
+
+/// A change of a field of an object
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    /// Identifier of the changed object
+    pub object_id: String,
+    /// Name of the changed field, as in the NTFS (e.g. `line_name`)
+    pub field: String,
+    /// Value of the field in the old dataset, `None` if empty
+    pub old_value: Option<String>,
+    /// Value of the field in the new dataset, `None` if empty
+    pub new_value: Option<String>,
+}
+
+/// Differences of a NTFS file between two datasets. The objects are
+/// identified by their identifier (e.g. `trip_id`), by a combination of
+/// columns for the files without identifier (e.g. `trip_id,stop_sequence`
+/// for `stop_times.txt`), or by all their values for the files of links
+/// (e.g. `comment_links.txt`), which have no modified objects.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct FileDiff {
+    /// Name of the file (e.g. `lines.txt`)
+    pub file_name: String,
+    /// Identifiers of the objects only in the new dataset
+    pub added: Vec<String>,
+    /// Identifiers of the objects only in the old dataset
+    pub removed: Vec<String>,
+    /// Identifiers of the objects with different values
+    pub modified: Vec<String>,
+    /// Field changes of the modified objects, if neither file has more than
+    /// [SMALL_FILE_SIZE](./constant.SMALL_FILE_SIZE.html) rows
+    pub changes: Vec<FieldChange>,
+}
+
+impl FileDiff {
+    /// Whether the file is the same in both datasets
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+// Reads and writes back the NTFS at `path`, so both datasets are compared
+// with the same formatting and order of columns
+fn normalized_ntfs(path: &Path) -> Result<TempDir> {
+    let model = ntfs::read(path)?;
+    let tmp_dir = tempdir()?;
+    // a fixed datetime, so the computed feed infos don't differ
+    let datetime = FixedOffset::east(0).timestamp(0, 0);
+    let options = WriteOptions {
+        fares_v1: false,
+        ..Default::default()
+    };
+    ntfs::write_with_options(&model, tmp_dir.path(), datetime, &options)?;
+    Ok(tmp_dir)
+}
+
+fn ntfs_file_names(path: &Path) -> Result<BTreeSet<String>> {
+    let mut file_names = BTreeSet::new();
+    for entry in fs::read_dir(path)? {
+        let file_name = entry?.file_name().to_string_lossy().to_string();
+        if file_name.ends_with(".txt") {
+            file_names.insert(file_name);
+        }
+    }
+    Ok(file_names)
+}
+
+struct Rows {
+    headers: Vec<String>,
+    rows: BTreeMap<String, Vec<String>>,
+}
+
+impl Rows {
+    fn value<'a>(&'a self, row: &'a [String], column: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .position(|header| header == column)
+            .and_then(|i| row.get(i))
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+}
+
+fn read_rows(path: &Path, file_name: &str) -> Result<Rows> {
+    let mut rows = Rows {
+        headers: vec![],
+        rows: BTreeMap::new(),
+    };
+    if !path.exists() {
+        return Ok(rows);
+    }
+    let mut rdr =
+        csv::Reader::from_path(path).with_context(|_| format!("Error reading {:?}", path))?;
+    rows.headers = rdr
+        .headers()
+        .with_context(|_| format!("Error reading {:?}", path))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    let key_indexes: Option<Vec<usize>> = KEY_COLUMNS
+        .iter()
+        .find(|(name, _)| *name == file_name)
+        .and_then(|(_, columns)| {
+            columns
+                .iter()
+                .map(|column| rows.headers.iter().position(|header| header == column))
+                .collect()
+        });
+    for record in rdr.records() {
+        let record = record.with_context(|_| format!("Error reading {:?}", path))?;
+        let row: Vec<String> = record.iter().map(str::to_string).collect();
+        let key = match &key_indexes {
+            Some(indexes) => indexes
+                .iter()
+                .map(|i| row[*i].as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+            None => row.join(","),
+        };
+        rows.rows.insert(key, row);
+    }
+    Ok(rows)
+}
+
+fn diff_file(file_name: &str, old_path: &Path, new_path: &Path) -> Result<FileDiff> {
+    let old = read_rows(&old_path.join(file_name), file_name)?;
+    let new = read_rows(&new_path.join(file_name), file_name)?;
+    let detailed = old.rows.len() <= SMALL_FILE_SIZE && new.rows.len() <= SMALL_FILE_SIZE;
+    let columns: BTreeSet<&String> = old.headers.iter().chain(new.headers.iter()).collect();
+    let mut diff = FileDiff {
+        file_name: file_name.to_string(),
+        ..Default::default()
+    };
+    for (key, old_row) in &old.rows {
+        let new_row = match new.rows.get(key) {
+            Some(new_row) => new_row,
+            None => {
+                diff.removed.push(key.clone());
+                continue;
+            }
+        };
+        let mut modified = false;
+        for column in &columns {
+            let old_value = old.value(old_row, column);
+            let new_value = new.value(new_row, column);
+            if old_value != new_value {
+                modified = true;
+                if detailed {
+                    diff.changes.push(FieldChange {
+                        object_id: key.clone(),
+                        field: column.to_string(),
+                        old_value: old_value.map(str::to_string),
+                        new_value: new_value.map(str::to_string),
+                    });
+                }
+            }
+        }
+        if modified {
+            diff.modified.push(key.clone());
+        }
+    }
+    diff.added = new
+        .rows
+        .keys()
+        .filter(|key| !old.rows.contains_key(*key))
+        .cloned()
+        .collect();
+    Ok(diff)
+}
+
+/// Loads the NTFS datasets at `old_path` and `new_path` and computes the
+/// differences of each of their files, sorted by file name. The files
+/// without differences are omitted.
+///
+/// Both datasets are read and written back before being compared, so only
+/// the differences of their content are reported, not the ones of their
+/// formatting.
+pub fn ntfs_diff<P: AsRef<Path>, Q: AsRef<Path>>(
+    old_path: P,
+    new_path: Q,
+) -> Result<Vec<FileDiff>> {
+    let old_dir = normalized_ntfs(old_path.as_ref())?;
+    let new_dir = normalized_ntfs(new_path.as_ref())?;
+    let mut file_names = ntfs_file_names(old_dir.path())?;
+    file_names.extend(ntfs_file_names(new_dir.path())?);
+    let mut diffs = Vec::new();
+    for file_name in file_names {
+        let diff = diff_file(&file_name, old_dir.path(), new_dir.path())?;
+        if !diff.is_empty() {
+            diffs.push(diff);
+        }
+    }
+    old_dir.close()?;
+    new_dir.close()?;
+    Ok(diffs)
+}
+
+/// Writes the differences computed by [ntfs_diff](./fn.ntfs_diff.html) as
+/// JSON in `path`.
+pub fn write_diff<P: AsRef<Path>>(diffs: &[FileDiff], path: P) -> Result<()> {
+    let path = path.as_ref();
+    info!("Writing diff {:?}", path);
+    let file = File::create(path).with_context(|_| format!("Error writing {:?}", path))?;
+    serde_json::to_writer_pretty(file, diffs)
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
@@ -0,0 +1,273 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Geodesic distances, see [haversine_distance](fn.haversine_distance.html),
+//! and lengths of the vehicle journeys, see
+//! [Collections::compute_vehicle_journey_lengths](../model/struct.Collections.html#method.compute_vehicle_journey_lengths)
+
+use crate::{
+    model::Collections,
+    objects::{Coord, StopPoint, StopTime, VehicleJourney},
+};
+use geo::{Coordinate, Geometry as GeoGeometry, LineString};
+use std::collections::HashMap;
+use typed_index_collection::CollectionWithId;
+
+/// Mean Earth radius in meters
+pub const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Calculates the orthodromic distance in meters between 2 geographic
+/// coordinates, with the haversine formula.
+pub fn haversine_distance(from: &Coord, to: &Coord) -> f64 {
+    let phi1 = from.lat.to_radians();
+    let phi2 = to.lat.to_radians();
+    let lambda1 = from.lon.to_radians();
+    let lambda2 = to.lon.to_radians();
+
+    let x = f64::sin((phi2 - phi1) / 2.).powi(2);
+    let y = f64::cos(phi1) * f64::cos(phi2) * f64::sin((lambda2 - lambda1) / 2.).powi(2);
+
+    2. * EARTH_RADIUS * f64::asin(f64::sqrt(x + y))
+}
+
+fn to_coord(coordinate: Coordinate<f64>) -> Coord {
+    Coord {
+        lon: coordinate.x,
+        lat: coordinate.y,
+    }
+}
+
+/// Calculates the length in meters of a `LineString` of geographic
+/// coordinates.
+pub fn line_string_length(line_string: &LineString<f64>) -> f64 {
+    line_string
+        .lines()
+        .map(|line| haversine_distance(&to_coord(line.start), &to_coord(line.end)))
+        .sum()
+}
+
+/// Calculates the length in meters of a `LineString` or a
+/// `MultiLineString`, `None` for the other geometries.
+pub fn geometry_length(geometry: &GeoGeometry<f64>) -> Option<f64> {
+    match geometry {
+        GeoGeometry::LineString(line_string) => Some(line_string_length(line_string)),
+        GeoGeometry::MultiLineString(multi_line_string) => {
+            Some(multi_line_string.0.iter().map(line_string_length).sum())
+        }
+        _ => None,
+    }
+}
+
+/// Calculates the length in meters of the straight lines between the stop
+/// points of consecutive `stop_times`. Returns `None` with less than 2 stop
+/// times or if a stop point isn't geolocated (its geolocation is (0, 0)).
+pub fn stop_to_stop_length(
+    stop_points: &CollectionWithId<StopPoint>,
+    stop_times: &[StopTime],
+) -> Option<f64> {
+    let mut coords: Vec<&Coord> = Vec::with_capacity(stop_times.len());
+    for stop_time in stop_times {
+        let coord = &stop_points[stop_time.stop_point_idx].coord;
+        if *coord == Coord::default() {
+            return None;
+        }
+        coords.push(coord);
+    }
+    if coords.len() < 2 {
+        return None;
+    }
+    Some(
+        coords
+            .windows(2)
+            .map(|window| haversine_distance(window[0], window[1]))
+            .sum(),
+    )
+}
+
+impl Collections {
+    /// Computes the `length` of each vehicle journey, in meters: the length
+    /// of its geometry if it's linear, or else the distance from stop point
+    /// to stop point. The length is `None` when neither can be computed.
+    pub fn compute_vehicle_journey_lengths(&mut self) {
+        let (geometries, stop_points) = (&self.geometries, &self.stop_points);
+        let mut geometry_lengths: HashMap<&str, Option<f64>> = HashMap::new();
+        let mut vehicle_journeys = self.vehicle_journeys.take();
+        for vehicle_journey in &mut vehicle_journeys {
+            let length_along_geometry = vehicle_journey
+                .geometry_id
+                .as_ref()
+                .and_then(|geometry_id| geometries.get(geometry_id))
+                .and_then(|geometry| {
+                    *geometry_lengths
+                        .entry(geometry.id.as_str())
+                        .or_insert_with(|| geometry_length(&geometry.geometry))
+                });
+            vehicle_journey.length = length_along_geometry
+                .or_else(|| stop_to_stop_length(stop_points, &vehicle_journey.stop_times));
+        }
+        self.vehicle_journeys = CollectionWithId::new(vehicle_journeys).unwrap();
+    }
+
+    /// Calculates the CO2 emission, in grams, of a passenger over the whole
+    /// `vehicle_journey`, from its length and the `co2_emission` (in grams per
+    /// kilometer) of its physical mode.
+    pub fn vehicle_journey_co2_emission(&self, vehicle_journey: &VehicleJourney) -> Option<f64> {
+        let length = vehicle_journey.length?;
+        let co2_emission = self
+            .physical_modes
+            .get(&vehicle_journey.physical_mode_id)?
+            .co2_emission?;
+        Some(length / 1_000. * f64::from(co2_emission))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Geometry, PhysicalMode, Time};
+    use approx::assert_relative_eq;
+    use geo::line_string;
+    use typed_index_collection::Idx;
+
+    const EPSILON: f64 = 0.001;
+
+    fn stop_time(stop_point_idx: Idx<StopPoint>, sequence: u32) -> StopTime {
+        StopTime {
+            stop_point_idx,
+            sequence,
+            arrival_time: Time::new(10, 0, 0),
+            departure_time: Time::new(10, 0, 0),
+            boarding_duration: 0,
+            alighting_duration: 0,
+            pickup_type: 0,
+            drop_off_type: 0,
+            datetime_estimated: false,
+            local_zone_id: None,
+            precision: None,
+        }
+    }
+
+    #[test]
+    fn lengths() {
+        let line_string = line_string![
+            (x: 2.37715, y: 48.846_781),
+            (x: 2.374_377, y: 48.844_304),
+            (x: 2.37715, y: 48.846_781),
+        ];
+        assert_relative_eq!(
+            line_string_length(&line_string),
+            2. * 357.644,
+            epsilon = EPSILON
+        );
+        assert_eq!(
+            None,
+            geometry_length(&GeoGeometry::Point(geo::Point::new(2.37715, 48.846_781)))
+        );
+    }
+
+    #[test]
+    fn compute_vehicle_journey_lengths() {
+        let mut collections = Collections::default();
+        collections.stop_points = CollectionWithId::new(vec![
+            StopPoint {
+                id: "sp:1".to_string(),
+                coord: Coord {
+                    lon: 2.37715,
+                    lat: 48.846_781,
+                },
+                ..Default::default()
+            },
+            StopPoint {
+                id: "sp:2".to_string(),
+                coord: Coord {
+                    lon: 2.374_377,
+                    lat: 48.844_304,
+                },
+                ..Default::default()
+            },
+            StopPoint {
+                id: "not_geolocated".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        let idx = |id| collections.stop_points.get_idx(id).unwrap();
+        let stop_times = vec![stop_time(idx("sp:1"), 1), stop_time(idx("sp:2"), 2)];
+        let not_geolocated = vec![
+            stop_time(idx("sp:1"), 1),
+            stop_time(idx("not_geolocated"), 2),
+        ];
+        collections.geometries = CollectionWithId::new(vec![Geometry {
+            id: "geo:1".to_string(),
+            geometry: line_string![
+                (x: 2.37715, y: 48.846_781),
+                (x: 2.374_377, y: 48.844_304),
+                (x: 2.37715, y: 48.846_781),
+            ]
+            .into(),
+        }])
+        .unwrap();
+        collections.physical_modes = CollectionWithId::new(vec![PhysicalMode {
+            id: "Bus".to_string(),
+            name: "Bus".to_string(),
+            co2_emission: Some(100.0),
+        }])
+        .unwrap();
+        collections.vehicle_journeys = CollectionWithId::new(vec![
+            VehicleJourney {
+                id: "stop_to_stop".to_string(),
+                physical_mode_id: "Bus".to_string(),
+                stop_times: stop_times.clone(),
+                ..Default::default()
+            },
+            VehicleJourney {
+                id: "along_geometry".to_string(),
+                geometry_id: Some("geo:1".to_string()),
+                stop_times,
+                ..Default::default()
+            },
+            VehicleJourney {
+                id: "not_geolocated".to_string(),
+                stop_times: not_geolocated,
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+
+        collections.compute_vehicle_journey_lengths();
+        let vehicle_journey = |id| collections.vehicle_journeys.get(id).unwrap();
+        assert_relative_eq!(
+            vehicle_journey("stop_to_stop").length.unwrap(),
+            357.644,
+            epsilon = EPSILON
+        );
+        assert_relative_eq!(
+            vehicle_journey("along_geometry").length.unwrap(),
+            2. * 357.644,
+            epsilon = EPSILON
+        );
+        assert_eq!(None, vehicle_journey("not_geolocated").length);
+        assert_relative_eq!(
+            collections
+                .vehicle_journey_co2_emission(vehicle_journey("stop_to_stop"))
+                .unwrap(),
+            35.764,
+            epsilon = EPSILON
+        );
+        assert_eq!(
+            None,
+            collections.vehicle_journey_co2_emission(vehicle_journey("along_geometry"))
+        );
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Minimum dwell times at stops, for the physical modes needing time to
+//! board and alight (e.g. boats), as the
+//! [StopTime](../objects/struct.StopTime.html) `boarding_duration` and
+//! `alighting_duration`.
+
+use crate::{model::Model, Result};
+use failure::ResultExt;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
+use typed_index_collection::CollectionWithId;
+
+/// A row of a dwell times file
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DwellTime {
+    /// Identifier of the physical mode
+    pub physical_mode_id: String,
+    /// Identifier of the stop point or stop area, if the dwell time applies
+    /// to a stop rather than to every stop of the physical mode
+    pub stop_id: Option<String>,
+    /// Duration, in seconds, needed to board before the departure
+    pub boarding_duration: u16,
+    /// Duration, in seconds, needed to alight after the arrival
+    pub alighting_duration: u16,
+}
+
+/// Imports dwell times from the CSV file `path`, with the columns
+/// `physical_mode_id`, `stop_id` (empty for every stop of the physical
+/// mode), `boarding_duration` and `alighting_duration`.
+///
+/// The durations are applied to the stop times of the vehicle journeys of
+/// the physical mode having no dwell: same arrival and departure times and
+/// no boarding or alighting duration. The dwell time of the stop point is
+/// used first, then the one of its stop area, then the one of the physical
+/// mode. Dwell times on an unknown physical mode or stop are logged and
+/// ignored.
+pub fn read_dwell_times<P: AsRef<Path>>(model: Model, path: P) -> Result<Model> {
+    let path = path.as_ref();
+    info!("Reading dwell times {:?}", path);
+    let mut collections = model.into_collections();
+    let mut rdr =
+        csv::Reader::from_path(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let mut dwell_times: HashMap<(String, Option<String>), (u16, u16)> = HashMap::new();
+    for dwell_time in rdr.deserialize() {
+        let dwell_time: DwellTime =
+            dwell_time.with_context(|_| format!("Error reading {:?}", path))?;
+        if !collections
+            .physical_modes
+            .contains_id(&dwell_time.physical_mode_id)
+        {
+            warn!(
+                "physical mode {:?} not found, dwell time ignored",
+                dwell_time.physical_mode_id
+            );
+            continue;
+        }
+        if let Some(stop_id) = &dwell_time.stop_id {
+            if !collections.stop_points.contains_id(stop_id)
+                && !collections.stop_areas.contains_id(stop_id)
+            {
+                warn!("stop {:?} not found, dwell time ignored", stop_id);
+                continue;
+            }
+        }
+        let key = (dwell_time.physical_mode_id, dwell_time.stop_id);
+        let durations = (dwell_time.boarding_duration, dwell_time.alighting_duration);
+        if dwell_times.insert(key.clone(), durations).is_some() {
+            warn!(
+                "several dwell times for physical mode {:?} and stop {:?}, the last one is kept",
+                key.0, key.1
+            );
+        }
+    }
+
+    let mut nb_stop_times = 0;
+    let mut vehicle_journeys = collections.vehicle_journeys.take();
+    for vj in &mut vehicle_journeys {
+        for stop_time in &mut vj.stop_times {
+            if stop_time.arrival_time != stop_time.departure_time
+                || stop_time.boarding_duration != 0
+                || stop_time.alighting_duration != 0
+            {
+                continue;
+            }
+            let stop_point = &collections.stop_points[stop_time.stop_point_idx];
+            let durations = [Some(&stop_point.id), Some(&stop_point.stop_area_id), None]
+                .iter()
+                .filter_map(|stop_id| {
+                    dwell_times.get(&(vj.physical_mode_id.clone(), stop_id.cloned()))
+                })
+                .next();
+            if let Some((boarding_duration, alighting_duration)) = durations {
+                stop_time.boarding_duration = *boarding_duration;
+                stop_time.alighting_duration = *alighting_duration;
+                nb_stop_times += 1;
+            }
+        }
+    }
+    collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
+    info!("Dwell times applied to {} stop times", nb_stop_times);
+
+    Model::new(collections)
+}
@@ -0,0 +1,240 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Structured errors of the crate, to handle some failures programmatically.
+//!
+//! The functions of the crate keep returning the [Error](../type.Error.html)
+//! type, into which a [TransitModelError](enum.TransitModelError.html)
+//! converts; it can be found back with [find](fn.find.html).
+//!
+//! ```
+//! use transit_model::error::{self, TransitModelError};
+//!
+//! fn describe(error: &transit_model::Error) -> String {
+//!     match error::find(error) {
+//!         Some(TransitModelError::MissingFile { path }) => format!("{:?} is missing", path),
+//!         Some(TransitModelError::CsvParse { path, line: Some(line), .. }) => {
+//!             format!("{:?} is invalid at line {}", path, line)
+//!         }
+//!         _ => error.to_string(),
+//!     }
+//! }
+//! ```
+
+use failure::Fail;
+use std::{
+    fmt,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// A structured error of the crate
+#[derive(Debug)]
+pub enum TransitModelError {
+    /// A file to read is missing
+    MissingFile {
+        /// Path of the missing file
+        path: PathBuf,
+    },
+    /// A CSV file can't be parsed
+    CsvParse {
+        /// Path of the file
+        path: PathBuf,
+        /// Line of the file, when known
+        line: Option<u64>,
+        /// The raw record which can't be read, when known
+        record: Option<String>,
+        /// Description of the error
+        message: String,
+        /// The CSV error, the [cause](#method.cause) of this one
+        source: csv::Error,
+    },
+    /// An object references an object that does not exist
+    ReferentialIntegrity {
+        /// The referencing field, e.g. `transfer.from_stop_id`
+        field: String,
+        /// Identifier of the missing object
+        object_id: String,
+    },
+}
+
+impl TransitModelError {
+    /// Error reading the CSV file `path`: a missing file is a
+    /// [MissingFile](#variant.MissingFile), any other error a
+    /// [CsvParse](#variant.CsvParse).
+    pub(crate) fn csv(path: &Path, error: csv::Error) -> Self {
+        if let csv::ErrorKind::Io(io_error) = error.kind() {
+            if io_error.kind() == ErrorKind::NotFound {
+                return TransitModelError::MissingFile {
+                    path: path.to_path_buf(),
+                };
+            }
+        }
+        TransitModelError::CsvParse {
+            path: path.to_path_buf(),
+            line: error.position().map(|position| position.line()),
+            record: None,
+            message: error.to_string(),
+            source: error,
+        }
+    }
+
+    /// Error deserializing the `record` of the CSV file `path`, a
+    /// [CsvParse](#variant.CsvParse) with the content of the record.
+    pub(crate) fn invalid_record(path: &Path, record: &csv::ByteRecord, error: csv::Error) -> Self {
+        let fields: Vec<_> = record.iter().map(String::from_utf8_lossy).collect();
+        TransitModelError::CsvParse {
+            path: path.to_path_buf(),
+            line: error
+                .position()
+                .or_else(|| record.position())
+                .map(|position| position.line()),
+            record: Some(fields.join(",")),
+            message: error.to_string(),
+            source: error,
+        }
+    }
+
+    /// Error of the `field` referencing the missing object `object_id`, a
+    /// [ReferentialIntegrity](#variant.ReferentialIntegrity) described by
+    /// `message`.
+    pub(crate) fn dangling(field: &str, object_id: &str, message: String) -> crate::Error {
+        TransitModelError::ReferentialIntegrity {
+            field: field.to_string(),
+            object_id: object_id.to_string(),
+        }
+        .context(message)
+        .into()
+    }
+}
+
+impl fmt::Display for TransitModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransitModelError::MissingFile { path } => write!(f, "file {:?} not found", path),
+            TransitModelError::CsvParse {
+                path,
+                line,
+                record,
+                message,
+                ..
+            } => {
+                write!(f, "Error reading {:?}", path)?;
+                if let Some(line) = line {
+                    write!(f, " at line {}", line)?;
+                }
+                write!(f, ": {}", message)?;
+                if let Some(record) = record {
+                    write!(f, ", record {:?}", record)?;
+                }
+                Ok(())
+            }
+            TransitModelError::ReferentialIntegrity { field, object_id } => {
+                write!(f, "Invalid id: {}={:?}", field, object_id)
+            }
+        }
+    }
+}
+
+impl Fail for TransitModelError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            TransitModelError::CsvParse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// The structured error among the causes of `error`, if any.
+pub fn find(error: &crate::Error) -> Option<&TransitModelError> {
+    error
+        .iter_chain()
+        .find_map(|cause| cause.downcast_ref::<TransitModelError>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{objects::Line, test_utils::*, utils::make_collection_with_id};
+    use pretty_assertions::assert_eq;
+    use std::fs::File;
+    use std::io::Write;
+    use typed_index_collection::CollectionWithId;
+
+    fn read_lines(path: &Path) -> crate::Result<CollectionWithId<Line>> {
+        make_collection_with_id(path, "lines.txt")
+    }
+
+    #[test]
+    fn missing_file() {
+        test_in_tmp_dir(|path| {
+            let error = read_lines(path).unwrap_err();
+            match find(&error) {
+                Some(TransitModelError::MissingFile { path: file }) => {
+                    assert_eq!(&path.join("lines.txt"), file);
+                }
+                other => panic!("unexpected error {:?}", other),
+            }
+        });
+    }
+
+    #[test]
+    fn csv_parse_error() {
+        test_in_tmp_dir(|path| {
+            File::create(path.join("lines.txt"))
+                .unwrap()
+                .write_all(
+                    b"line_id,line_name,network_id,commercial_mode_id,line_sort_order\n\
+                      l1,Line 1,n1,Bus,1\n\
+                      l2,Line 2,n1,Bus,second\n",
+                )
+                .unwrap();
+            let error = read_lines(path).unwrap_err();
+            match find(&error) {
+                Some(TransitModelError::CsvParse {
+                    path: file,
+                    line,
+                    record,
+                    ..
+                }) => {
+                    assert_eq!(&path.join("lines.txt"), file);
+                    assert_eq!(&Some(3), line);
+                    assert_eq!(&Some("l2,Line 2,n1,Bus,second".to_string()), record);
+                }
+                other => panic!("unexpected error {:?}", other),
+            }
+            let csv_error = error
+                .iter_chain()
+                .find_map(|cause| cause.downcast_ref::<csv::Error>());
+            assert!(matches!(
+                csv_error.map(csv::Error::kind),
+                Some(csv::ErrorKind::Deserialize { .. })
+            ));
+        });
+    }
+
+    #[test]
+    fn dangling_reference() {
+        let error =
+            TransitModelError::dangling("stop_time.trip_id", "vj1", "vj1 not found".to_string());
+        assert_eq!("vj1 not found", error.to_string());
+        match find(&error) {
+            Some(TransitModelError::ReferentialIntegrity { field, object_id }) => {
+                assert_eq!("stop_time.trip_id", field);
+                assert_eq!("vj1", object_id);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,1664 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+//! See functions enrich_with_farev2, validate and price_summaries
+
+use crate::{
+    model::{Collections, Model},
+    objects::{
+        Currency, FareZone, Line, Network, ObjectType, PerimeterAction, RestrictionType, Ticket,
+        TicketPrice, TicketUse, TicketUsePerimeter, TicketUseRestriction,
+    },
+    read_utils::{self, FileHandler, PathFileHandler, ReadMode},
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use failure::{bail, format_err, ResultExt};
+use log::info;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// How the fares V2 read by [read_farev2](./fn.read_farev2.html) are
+/// combined with the fares already in the collections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaresMode {
+    /// The existing fares are removed
+    Replace,
+    /// The existing fares are kept and the new ones are added. A ticket (or
+    /// a ticket use) whose identifier already exists with different
+    /// properties, prices (or perimeters and restrictions) is reported as a
+    /// conflict and ignored.
+    Merge,
+}
+
+/// Options of the reading of the fares V2, see
+/// [read_farev2](./fn.read_farev2.html)
+#[derive(Debug, Clone, Default)]
+pub struct FaresOptions {
+    /// Rejects the fares as a whole, leaving the collections unchanged, when
+    /// the ratio of the perimeters and restrictions referencing unknown
+    /// objects is above this value (e.g. `0.1`)
+    pub max_error_ratio: Option<f64>,
+    /// How the records of the fare files which can't be read are handled; in
+    /// [lenient](../read_utils/enum.ReadMode.html#variant.Lenient) mode, they
+    /// are skipped with a warning, and so are the corrupted files of a zip
+    /// file: they are reported, and handled as missing files (so a corrupted
+    /// `tickets.txt` still fails the reading)
+    pub read_mode: ReadMode,
+}
+
+struct FaresV2 {
+    tickets: Vec<Ticket>,
+    ticket_uses: Vec<TicketUse>,
+    ticket_prices: Vec<TicketPrice>,
+    ticket_use_perimeters: Vec<TicketUsePerimeter>,
+    ticket_use_restrictions: Vec<TicketUseRestriction>,
+    fare_zones: Vec<FareZone>,
+}
+
+fn read_fares<H>(file_handler: &mut H) -> Result<FaresV2>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    Ok(FaresV2 {
+        tickets: read_utils::read_objects(file_handler, "tickets.txt")?,
+        ticket_uses: read_utils::read_objects(file_handler, "ticket_uses.txt")?,
+        ticket_prices: read_utils::read_objects(file_handler, "ticket_prices.txt")?,
+        ticket_use_perimeters: read_utils::read_objects(file_handler, "ticket_use_perimeters.txt")?,
+        ticket_use_restrictions: read_utils::read_opt_objects(
+            file_handler,
+            "ticket_use_restrictions.txt",
+        )?,
+        fare_zones: read_utils::read_opt_objects(file_handler, "fare_zones.txt")?,
+    })
+}
+
+#[cfg(feature = "zip")]
+fn read_zip_fares(path: &Path, read_mode: ReadMode, report: &mut Report) -> Result<FaresV2> {
+    let mut file_handler = read_utils::ZipHandler::new(path)?.with_read_mode(read_mode);
+    let fares = read_fares(&mut file_handler);
+    // the files are checked when they are read
+    for (file_name, error) in file_handler.corrupted_files() {
+        report.add_error(
+            format!("{} is corrupted and ignored: {}", file_name, error),
+            ReportType::UnreadableFile,
+        );
+    }
+    fares
+}
+
+#[cfg(not(feature = "zip"))]
+fn read_zip_fares(path: &Path, _: ReadMode, _: &mut Report) -> Result<FaresV2> {
+    bail!(
+        "{:?} can't be read, the ZIP archives need the `zip` feature",
+        path
+    )
+}
+
+fn group_by_id<T, F>(objects: Vec<T>, get_id: F) -> BTreeMap<String, Vec<T>>
+where
+    F: Fn(&T) -> &str,
+{
+    let mut groups: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    for object in objects {
+        groups
+            .entry(get_id(&object).to_string())
+            .or_insert_with(Vec::new)
+            .push(object);
+    }
+    groups
+}
+
+fn same_objects<T: PartialEq>(existing: &[&T], new: &[T]) -> bool {
+    existing.len() == new.len() && new.iter().all(|object| existing.contains(&object))
+}
+
+fn perimeter_object_exists(collections: &Collections, perimeter: &TicketUsePerimeter) -> bool {
+    match perimeter.object_type {
+        ObjectType::Line => collections.lines.contains_id(&perimeter.object_id),
+        ObjectType::Network => collections.networks.contains_id(&perimeter.object_id),
+        _ => false,
+    }
+}
+
+// The fare zones of the collections and the ones referenced by stop points
+pub(crate) fn fare_zones(collections: &Collections) -> HashSet<String> {
+    collections
+        .fare_zones
+        .values()
+        .map(|fare_zone| fare_zone.id.clone())
+        .chain(
+            collections
+                .stop_points
+                .values()
+                .filter_map(|stop_point| stop_point.fare_zone_id.clone()),
+        )
+        .collect()
+}
+
+// The origin and destination of an OD restriction are stop areas, the ones
+// of a zone restriction are fare zones
+pub(crate) fn restriction_objects_exist(
+    collections: &Collections,
+    fare_zones: &HashSet<String>,
+    restriction: &TicketUseRestriction,
+) -> bool {
+    let exists = |id: &String| match restriction.restriction_type {
+        RestrictionType::OriginDestination => collections.stop_areas.contains_id(id),
+        RestrictionType::Zone => fare_zones.contains(id),
+    };
+    exists(&restriction.use_origin) && exists(&restriction.use_destination)
+}
+
+// Fails when the ratio of the perimeters and restrictions referencing unknown
+// objects is above `max_error_ratio`
+fn check_error_ratio(
+    collections: &Collections,
+    fares: &FaresV2,
+    max_error_ratio: f64,
+) -> Result<()> {
+    let nb_objects = fares.ticket_use_perimeters.len() + fares.ticket_use_restrictions.len();
+    if nb_objects == 0 {
+        return Ok(());
+    }
+    let mut fare_zones = fare_zones(collections);
+    fare_zones.extend(
+        fares
+            .fare_zones
+            .iter()
+            .map(|fare_zone| fare_zone.id.clone()),
+    );
+    let nb_errors = fares
+        .ticket_use_perimeters
+        .iter()
+        .filter(|perimeter| !perimeter_object_exists(collections, perimeter))
+        .count()
+        + fares
+            .ticket_use_restrictions
+            .iter()
+            .filter(|restriction| !restriction_objects_exist(collections, &fare_zones, restriction))
+            .count();
+    let error_ratio = nb_errors as f64 / nb_objects as f64;
+    if error_ratio > max_error_ratio {
+        bail!(
+            "{} of the {} perimeters and restrictions reference unknown objects, above the maximum error ratio of {}",
+            nb_errors,
+            nb_objects,
+            max_error_ratio
+        );
+    }
+    Ok(())
+}
+
+fn add_tickets(
+    collections: &mut Collections,
+    tickets: Vec<Ticket>,
+    ticket_prices: Vec<TicketPrice>,
+    report: &mut Report,
+) -> Result<()> {
+    let mut prices_by_ticket = group_by_id(ticket_prices, |price| price.ticket_id.as_str());
+    for ticket in tickets {
+        let prices = prices_by_ticket.remove(&ticket.id).unwrap_or_default();
+        if let Some(existing_ticket) = collections.tickets.get(&ticket.id) {
+            let existing_prices: Vec<&TicketPrice> = collections
+                .ticket_prices
+                .values()
+                .filter(|price| price.ticket_id == ticket.id)
+                .collect();
+            if *existing_ticket != ticket || !same_objects(&existing_prices, &prices) {
+                report.add(
+                    Severity::Error,
+                    ReportEntry::new(
+                        ReportType::Conflict,
+                        format!(
+                            "ticket {} already exists with different properties or prices, the new one is ignored",
+                            ticket.id
+                        ),
+                    )
+                    .with_object(ObjectType::Ticket, &ticket.id),
+                );
+            }
+            continue;
+        }
+        collections
+            .tickets
+            .push(ticket)
+            .map_err(|e| format_err!("{}", e))?;
+        for price in prices {
+            collections.ticket_prices.push(price);
+        }
+    }
+    for ticket_id in prices_by_ticket.keys() {
+        report.add(
+            Severity::Warning,
+            ReportEntry::new(
+                ReportType::DanglingReference,
+                format!("prices of the unknown ticket {} are ignored", ticket_id),
+            )
+            .with_object(ObjectType::Ticket, ticket_id),
+        );
+    }
+    Ok(())
+}
+
+fn add_fare_zones(
+    collections: &mut Collections,
+    fare_zones: Vec<FareZone>,
+    report: &mut Report,
+) -> Result<()> {
+    for fare_zone in fare_zones {
+        if let Some(existing_fare_zone) = collections.fare_zones.get(&fare_zone.id) {
+            if *existing_fare_zone != fare_zone {
+                report.add_error(
+                    format!(
+                        "fare zone {} already exists with a different name, the new one is ignored",
+                        fare_zone.id
+                    ),
+                    ReportType::Conflict,
+                );
+            }
+            continue;
+        }
+        collections
+            .fare_zones
+            .push(fare_zone)
+            .map_err(|e| format_err!("{}", e))?;
+    }
+    Ok(())
+}
+
+fn add_ticket_uses(
+    collections: &mut Collections,
+    ticket_uses: Vec<TicketUse>,
+    ticket_use_perimeters: Vec<TicketUsePerimeter>,
+    ticket_use_restrictions: Vec<TicketUseRestriction>,
+    report: &mut Report,
+) -> Result<()> {
+    let mut perimeters_by_use = group_by_id(ticket_use_perimeters, |perimeter| {
+        perimeter.ticket_use_id.as_str()
+    });
+    let mut restrictions_by_use = group_by_id(ticket_use_restrictions, |restriction| {
+        restriction.ticket_use_id.as_str()
+    });
+    let fare_zones = fare_zones(collections);
+    for ticket_use in ticket_uses {
+        let perimeters = perimeters_by_use.remove(&ticket_use.id).unwrap_or_default();
+        let restrictions = restrictions_by_use
+            .remove(&ticket_use.id)
+            .unwrap_or_default();
+        if let Some(existing_ticket_use) = collections.ticket_uses.get(&ticket_use.id) {
+            let existing_perimeters: Vec<&TicketUsePerimeter> = collections
+                .ticket_use_perimeters
+                .values()
+                .filter(|perimeter| perimeter.ticket_use_id == ticket_use.id)
+                .collect();
+            let existing_restrictions: Vec<&TicketUseRestriction> = collections
+                .ticket_use_restrictions
+                .values()
+                .filter(|restriction| restriction.ticket_use_id == ticket_use.id)
+                .collect();
+            if *existing_ticket_use != ticket_use
+                || !same_objects(&existing_perimeters, &perimeters)
+                || !same_objects(&existing_restrictions, &restrictions)
+            {
+                report.add_error(
+                    format!(
+                        "ticket use {} already exists with different properties, perimeters or restrictions, the new one is ignored",
+                        ticket_use.id
+                    ),
+                    ReportType::Conflict,
+                );
+            }
+            continue;
+        }
+        if !collections.tickets.contains_id(&ticket_use.ticket_id) {
+            report.add(
+                Severity::Error,
+                ReportEntry::new(
+                    ReportType::DanglingReference,
+                    format!(
+                        "ticket use {} references the unknown ticket {}, it is ignored",
+                        ticket_use.id, ticket_use.ticket_id
+                    ),
+                )
+                .with_object(ObjectType::Ticket, &ticket_use.ticket_id),
+            );
+            continue;
+        }
+        for perimeter in perimeters {
+            if perimeter_object_exists(collections, &perimeter) {
+                collections.ticket_use_perimeters.push(perimeter);
+            } else {
+                report.add(
+                    Severity::Warning,
+                    ReportEntry::new(
+                        ReportType::ObjectNotFound,
+                        format!(
+                            "the perimeter of the ticket use {} references the unknown object {}, it is ignored",
+                            perimeter.ticket_use_id, perimeter.object_id
+                        ),
+                    )
+                    .with_object(perimeter.object_type.clone(), &perimeter.object_id),
+                );
+            }
+        }
+        for restriction in restrictions {
+            if restriction_objects_exist(collections, &fare_zones, &restriction) {
+                collections.ticket_use_restrictions.push(restriction);
+            } else {
+                report.add_warning(
+                    format!(
+                        "the restriction of the ticket use {} from {} to {} references an unknown origin or destination, it is ignored",
+                        restriction.ticket_use_id, restriction.use_origin, restriction.use_destination
+                    ),
+                    ReportType::ObjectNotFound,
+                );
+            }
+        }
+        collections
+            .ticket_uses
+            .push(ticket_use)
+            .map_err(|e| format_err!("{}", e))?;
+    }
+    for ticket_use_id in perimeters_by_use.keys().chain(restrictions_by_use.keys()) {
+        report.add_warning(
+            format!(
+                "perimeters or restrictions of the unknown ticket use {} are ignored",
+                ticket_use_id
+            ),
+            ReportType::DanglingReference,
+        );
+    }
+    Ok(())
+}
+
+/// Reads the fares V2 files (`tickets.txt`, `ticket_uses.txt`,
+/// `ticket_prices.txt`, `ticket_use_perimeters.txt` and the optional
+/// `ticket_use_restrictions.txt` and `fare_zones.txt`) from a directory or a
+/// zip file (with the `zip` feature) into the `collections`.
+///
+/// The perimeters referencing unknown lines or networks, the restrictions
+/// referencing unknown stop areas (OD) or fare zones (zone, the fare zones
+/// being the ones of `fare_zones.txt` and the ones of the stop points) and
+/// the ticket uses referencing unknown tickets are ignored. They are added to
+/// the `report`, along with the conflicts found in
+/// [Merge](./enum.FaresMode.html) mode.
+///
+/// The `options` give the maximum ratio of these ignored objects and how
+/// the records and files which can't be read are handled (see
+/// [FaresOptions](./struct.FaresOptions.html)).
+pub fn read_farev2<P: AsRef<Path>>(
+    collections: &mut Collections,
+    path: P,
+    mode: FaresMode,
+    options: &FaresOptions,
+    report: &mut Report,
+) -> Result<()> {
+    read_farev2_of_networks(collections, path.as_ref(), None, mode, options, report)
+}
+
+// Removes the included perimeters and the restrictions of the `fares`
+// outside of the `networks`: the networks not listed, the lines of the other
+// networks, and the stop areas and fare zones not served by the lines of the
+// networks (the unknown objects are reported later)
+fn remove_perimeter_leaks(
+    collections: &Collections,
+    fares: &mut FaresV2,
+    networks: &BTreeSet<String>,
+    report: &mut Report,
+) {
+    let is_leak = |perimeter: &TicketUsePerimeter| {
+        perimeter.perimeter_action == PerimeterAction::Included
+            && match perimeter.object_type {
+                ObjectType::Network => !networks.contains(&perimeter.object_id),
+                ObjectType::Line => collections
+                    .lines
+                    .get(&perimeter.object_id)
+                    .map_or(false, |line| !networks.contains(&line.network_id)),
+                _ => false,
+            }
+    };
+    let (leaks, perimeters): (Vec<_>, Vec<_>) = fares
+        .ticket_use_perimeters
+        .drain(..)
+        .partition(|perimeter| is_leak(perimeter));
+    fares.ticket_use_perimeters = perimeters;
+    for perimeter in leaks {
+        report.add(
+            Severity::Error,
+            ReportEntry::new(
+                ReportType::PerimeterLeak,
+                format!(
+                    "the perimeter of the ticket use {} includes the {} {} outside of the networks {}, it is ignored",
+                    perimeter.ticket_use_id,
+                    perimeter.object_type.as_str(),
+                    perimeter.object_id,
+                    networks.iter().cloned().collect::<Vec<_>>().join(", ")
+                ),
+            )
+            .with_object(perimeter.object_type.clone(), &perimeter.object_id),
+        );
+    }
+
+    let mut served_stop_areas = HashSet::new();
+    let mut served_fare_zones = HashSet::new();
+    for vj in collections.vehicle_journeys.values() {
+        let in_networks = collections
+            .routes
+            .get(&vj.route_id)
+            .and_then(|route| collections.lines.get(&route.line_id))
+            .map_or(false, |line| networks.contains(&line.network_id));
+        if !in_networks {
+            continue;
+        }
+        for stop_time in &vj.stop_times {
+            let stop_point = &collections.stop_points[stop_time.stop_point_idx];
+            served_stop_areas.insert(stop_point.stop_area_id.as_str());
+            if let Some(fare_zone_id) = &stop_point.fare_zone_id {
+                served_fare_zones.insert(fare_zone_id.as_str());
+            }
+        }
+    }
+    let stop_fare_zones: HashSet<&str> = collections
+        .stop_points
+        .values()
+        .filter_map(|stop_point| stop_point.fare_zone_id.as_deref())
+        .collect();
+    let is_leak = |restriction: &TicketUseRestriction| {
+        let is_outside = |id: &String| match restriction.restriction_type {
+            RestrictionType::OriginDestination => {
+                collections.stop_areas.contains_id(id) && !served_stop_areas.contains(id.as_str())
+            }
+            RestrictionType::Zone => {
+                stop_fare_zones.contains(id.as_str()) && !served_fare_zones.contains(id.as_str())
+            }
+        };
+        is_outside(&restriction.use_origin) || is_outside(&restriction.use_destination)
+    };
+    let (leaks, restrictions): (Vec<_>, Vec<_>) = fares
+        .ticket_use_restrictions
+        .drain(..)
+        .partition(|restriction| is_leak(restriction));
+    fares.ticket_use_restrictions = restrictions;
+    for restriction in leaks {
+        let mut entry = ReportEntry::new(
+            ReportType::PerimeterLeak,
+            format!(
+                "the restriction of the ticket use {} from {} to {} is outside of the networks {}, it is ignored",
+                restriction.ticket_use_id,
+                restriction.use_origin,
+                restriction.use_destination,
+                networks.iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        );
+        if let Some(ticket_use) = fares
+            .ticket_uses
+            .iter()
+            .find(|ticket_use| ticket_use.id == restriction.ticket_use_id)
+        {
+            entry = entry.with_object(ObjectType::Ticket, &ticket_use.ticket_id);
+        }
+        report.add(Severity::Error, entry);
+    }
+}
+
+// Reads the fares V2 of `path`, whose perimeters must be within the
+// `networks` if given
+fn read_farev2_of_networks(
+    collections: &mut Collections,
+    path: &Path,
+    networks: Option<&BTreeSet<String>>,
+    mode: FaresMode,
+    options: &FaresOptions,
+    report: &mut Report,
+) -> Result<()> {
+    info!("Reading fares V2 from {:?}", path);
+    let mut fares = if path.is_file() {
+        read_zip_fares(path, options.read_mode, report)?
+    } else {
+        let mut file_handler = PathFileHandler::new(path).with_read_mode(options.read_mode);
+        read_fares(&mut file_handler)?
+    };
+    if let Some(networks) = networks {
+        remove_perimeter_leaks(collections, &mut fares, networks, report);
+    }
+    if let Some(max_error_ratio) = options.max_error_ratio {
+        check_error_ratio(collections, &fares, max_error_ratio)?;
+    }
+
+    if mode == FaresMode::Replace {
+        collections.tickets = Default::default();
+        collections.ticket_uses = Default::default();
+        collections.ticket_prices = Default::default();
+        collections.ticket_use_perimeters = Default::default();
+        collections.ticket_use_restrictions = Default::default();
+        collections.fare_zones = Default::default();
+    }
+    add_fare_zones(collections, fares.fare_zones, report)?;
+    add_tickets(collections, fares.tickets, fares.ticket_prices, report)?;
+    add_ticket_uses(
+        collections,
+        fares.ticket_uses,
+        fares.ticket_use_perimeters,
+        fares.ticket_use_restrictions,
+        report,
+    )
+}
+
+// The fares V2 objects to validate, borrowed from the collections or from
+// the fares V2 files
+struct FaresRefs<'a> {
+    ticket_ids: HashSet<&'a str>,
+    ticket_uses: Vec<&'a TicketUse>,
+    ticket_prices: Vec<&'a TicketPrice>,
+    ticket_use_perimeters: Vec<&'a TicketUsePerimeter>,
+    ticket_use_restrictions: Vec<&'a TicketUseRestriction>,
+    fare_zone_ids: Vec<&'a str>,
+}
+
+impl<'a> From<&'a Collections> for FaresRefs<'a> {
+    fn from(collections: &'a Collections) -> Self {
+        FaresRefs {
+            ticket_ids: collections
+                .tickets
+                .values()
+                .map(|ticket| ticket.id.as_str())
+                .collect(),
+            ticket_uses: collections.ticket_uses.values().collect(),
+            ticket_prices: collections.ticket_prices.values().collect(),
+            ticket_use_perimeters: collections.ticket_use_perimeters.values().collect(),
+            ticket_use_restrictions: collections.ticket_use_restrictions.values().collect(),
+            fare_zone_ids: vec![],
+        }
+    }
+}
+
+impl<'a> From<&'a FaresV2> for FaresRefs<'a> {
+    fn from(fares: &'a FaresV2) -> Self {
+        FaresRefs {
+            ticket_ids: fares
+                .tickets
+                .iter()
+                .map(|ticket| ticket.id.as_str())
+                .collect(),
+            ticket_uses: fares.ticket_uses.iter().collect(),
+            ticket_prices: fares.ticket_prices.iter().collect(),
+            ticket_use_perimeters: fares.ticket_use_perimeters.iter().collect(),
+            ticket_use_restrictions: fares.ticket_use_restrictions.iter().collect(),
+            fare_zone_ids: fares
+                .fare_zones
+                .iter()
+                .map(|fare_zone| fare_zone.id.as_str())
+                .collect(),
+        }
+    }
+}
+
+fn validate_fares(collections: &Collections, fares: FaresRefs, report: &mut Report) {
+    let mut fare_zones = fare_zones(collections);
+    fare_zones.extend(fares.fare_zone_ids.iter().map(|id| id.to_string()));
+    let mut ticket_use_ids = HashSet::new();
+    for ticket_use in &fares.ticket_uses {
+        ticket_use_ids.insert(ticket_use.id.as_str());
+        if !fares.ticket_ids.contains(ticket_use.ticket_id.as_str()) {
+            report.add(
+                Severity::Error,
+                ReportEntry::new(
+                    ReportType::DanglingReference,
+                    format!(
+                        "ticket use {} references the unknown ticket {}",
+                        ticket_use.id, ticket_use.ticket_id
+                    ),
+                )
+                .with_object(ObjectType::Ticket, &ticket_use.ticket_id),
+            );
+        }
+    }
+    let ticket_ids_of_prices: BTreeSet<&str> = fares
+        .ticket_prices
+        .iter()
+        .map(|price| price.ticket_id.as_str())
+        .collect();
+    for ticket_id in ticket_ids_of_prices {
+        if !fares.ticket_ids.contains(ticket_id) {
+            report.add(
+                Severity::Error,
+                ReportEntry::new(
+                    ReportType::DanglingReference,
+                    format!("prices reference the unknown ticket {}", ticket_id),
+                )
+                .with_object(ObjectType::Ticket, ticket_id),
+            );
+        }
+    }
+    for perimeter in &fares.ticket_use_perimeters {
+        if !ticket_use_ids.contains(perimeter.ticket_use_id.as_str()) {
+            report.add_error(
+                format!(
+                    "a perimeter references the unknown ticket use {}",
+                    perimeter.ticket_use_id
+                ),
+                ReportType::DanglingReference,
+            );
+        }
+        if !perimeter_object_exists(collections, perimeter) {
+            report.add(
+                Severity::Error,
+                ReportEntry::new(
+                    ReportType::ObjectNotFound,
+                    format!(
+                        "the perimeter of the ticket use {} references the unknown {} {}",
+                        perimeter.ticket_use_id,
+                        perimeter.object_type.as_str(),
+                        perimeter.object_id
+                    ),
+                )
+                .with_object(perimeter.object_type.clone(), &perimeter.object_id),
+            );
+        }
+    }
+    for restriction in &fares.ticket_use_restrictions {
+        if !ticket_use_ids.contains(restriction.ticket_use_id.as_str()) {
+            report.add_error(
+                format!(
+                    "a restriction references the unknown ticket use {}",
+                    restriction.ticket_use_id
+                ),
+                ReportType::DanglingReference,
+            );
+        }
+        if !restriction_objects_exist(collections, &fare_zones, restriction) {
+            report.add_error(
+                format!(
+                    "the restriction of the ticket use {} from {} to {} references an unknown origin or destination",
+                    restriction.ticket_use_id, restriction.use_origin, restriction.use_destination
+                ),
+                ReportType::ObjectNotFound,
+            );
+        }
+    }
+}
+
+/// Validates the fares V2 of the `collections`: the returned report has an
+/// error for each ticket use or price referencing an unknown ticket, each
+/// perimeter or restriction referencing an unknown ticket use, each
+/// perimeter referencing an unknown line or network, and each restriction
+/// referencing an unknown stop area (OD) or fare zone (zone).
+pub fn validate(collections: &Collections) -> Report {
+    let mut report = Report::default();
+    validate_fares(collections, FaresRefs::from(collections), &mut report);
+    report
+}
+
+/// Validates the fares V2 files of `path` (a directory or a zip file, see
+/// [read_farev2](./fn.read_farev2.html)) against the lines, networks, stop
+/// areas and fare zones of the `collections`, without adding them to the
+/// `collections`. The returned report has the errors of
+/// [validate](./fn.validate.html), the tickets and ticket uses being the
+/// ones of the files, and the corrupted files of a zip file in
+/// [lenient](../read_utils/enum.ReadMode.html#variant.Lenient) `read_mode`.
+pub fn validate_farev2<P: AsRef<Path>>(
+    collections: &Collections,
+    path: P,
+    read_mode: ReadMode,
+) -> Result<Report> {
+    let path = path.as_ref();
+    info!("Validating fares V2 of {:?}", path);
+    let mut report = Report::default();
+    let fares = if path.is_file() {
+        read_zip_fares(path, read_mode, &mut report)?
+    } else {
+        let mut file_handler = PathFileHandler::new(path).with_read_mode(read_mode);
+        read_fares(&mut file_handler)?
+    };
+    validate_fares(collections, FaresRefs::from(&fares), &mut report);
+    Ok(report)
+}
+
+// The zip files of a directory, ordered by name
+fn zip_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut zip_files = vec![];
+    for entry in fs::read_dir(path).with_context(|_| format!("Error reading {:?}", path))? {
+        let entry_path = entry
+            .with_context(|_| format!("Error reading {:?}", path))?
+            .path();
+        if entry_path.is_file() && entry_path.extension().map_or(false, |e| e == "zip") {
+            zip_files.push(entry_path);
+        }
+    }
+    zip_files.sort();
+    Ok(zip_files)
+}
+
+/// A fare archive of a [FaresManifest](./type.FaresManifest.html)
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct FareArchive {
+    /// Zip file (or directory) of the fares V2, relative to the directory of
+    /// the manifest
+    pub path: PathBuf,
+    /// Identifiers of the networks covered by the fares of the archive
+    pub networks: BTreeSet<String>,
+}
+
+/// Fare archives (e.g. one per transport authority) with the networks they
+/// cover, read by [read_fares_manifest](./fn.read_fares_manifest.html)
+pub type FaresManifest = Vec<FareArchive>;
+
+/// Reads a [FaresManifest](./type.FaresManifest.html) from a JSON file. The
+/// paths of the archives are made relative to the directory of the
+/// manifest.
+///
+/// # Example
+///
+/// ```json
+/// [
+///     { "path": "authority_1.zip", "networks": ["network:1", "network:2"] },
+///     { "path": "authority_2.zip", "networks": ["network:3"] }
+/// ]
+/// ```
+pub fn read_fares_manifest<P: AsRef<Path>>(path: P) -> Result<FaresManifest> {
+    let path = path.as_ref();
+    info!("Reading fares manifest {:?}", path);
+    let file = fs::File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let mut manifest: FaresManifest =
+        serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+    if let Some(directory) = path.parent() {
+        for archive in &mut manifest {
+            archive.path = directory.join(&archive.path);
+        }
+    }
+    Ok(manifest)
+}
+
+// Reads the fare `archives`, with the perimeters within their networks if
+// given, in a section of the report per archive. An archive which can't be
+// read is skipped.
+fn read_fare_archives<'a, I>(
+    collections: &mut Collections,
+    archives: I,
+    mode: FaresMode,
+    options: &FaresOptions,
+    report: &mut Report,
+) where
+    I: IntoIterator<Item = (&'a Path, Option<&'a BTreeSet<String>>)>,
+{
+    let mut mode = mode;
+    for (path, networks) in archives {
+        let file_name = path
+            .file_name()
+            .map_or_else(|| path.to_string_lossy(), |f| f.to_string_lossy())
+            .to_string();
+        let section = report.section(&file_name);
+        match read_farev2_of_networks(collections, path, networks, mode, options, section) {
+            Ok(()) => mode = FaresMode::Merge,
+            Err(e) => section.add_error(
+                format!(
+                    "{} is ignored: {}",
+                    file_name,
+                    e.iter_chain()
+                        .map(|cause| cause.to_string())
+                        .collect::<Vec<_>>()
+                        .join(": ")
+                ),
+                ReportType::UnreadableFile,
+            ),
+        }
+    }
+}
+
+/// Reads the fare archives of the `manifest` into the `collections`, see
+/// [read_farev2](./fn.read_farev2.html): the first one in the given `mode`,
+/// the following ones are merged. The included perimeters and the
+/// restrictions of an archive outside of its networks (the networks not
+/// listed, the lines of the other networks, and the stop areas and fare zones
+/// not served by the lines of its networks) are reported as leaks, and
+/// ignored.
+///
+/// An archive that can't be read is skipped, as is an archive rejected
+/// because of the `max_error_ratio` of the `options`. The `report` has a
+/// section per archive.
+pub fn read_farev2_manifest(
+    collections: &mut Collections,
+    manifest: &[FareArchive],
+    mode: FaresMode,
+    options: &FaresOptions,
+    report: &mut Report,
+) {
+    read_fare_archives(
+        collections,
+        manifest
+            .iter()
+            .map(|archive| (archive.path.as_path(), Some(&archive.networks))),
+        mode,
+        options,
+        report,
+    )
+}
+
+/// Enriches the `model` with the fares V2 of `fare_path`, see
+/// [read_farev2](./fn.read_farev2.html).
+///
+/// `fare_path` is a zip file, a directory containing the fares V2 files, a
+/// directory of zip files (e.g. one per network), or a JSON
+/// [manifest](./fn.read_fares_manifest.html) (a file with the `.json`
+/// extension) of zip files with the networks they cover. The zip files of a
+/// directory are read in the order of their names, the ones of a manifest
+/// in its order (see [read_farev2_manifest](./fn.read_farev2_manifest.html)):
+/// the first one in the given `mode`, the following ones are merged. A zip
+/// file that can't be read is skipped, as is a zip file rejected because of
+/// the `max_error_ratio` of the `options`. The report has a section per zip
+/// file.
+///
+/// The report is written in `report_path`, as CSV if it has a `.csv`
+/// extension, as JSON otherwise.
+pub fn enrich_with_farev2<P: AsRef<Path>, Q: AsRef<Path>>(
+    model: Model,
+    fare_path: P,
+    mode: FaresMode,
+    options: &FaresOptions,
+    report_path: Q,
+) -> Result<Model> {
+    let fare_path = fare_path.as_ref();
+    let mut report = Report::default();
+    let mut collections = model.into_collections();
+    if fare_path.is_file() && fare_path.extension().map_or(false, |e| e == "json") {
+        let manifest = read_fares_manifest(fare_path)?;
+        read_farev2_manifest(&mut collections, &manifest, mode, options, &mut report);
+    } else {
+        let zip_paths = if fare_path.is_dir() {
+            zip_files(fare_path)?
+        } else {
+            vec![]
+        };
+        if zip_paths.is_empty() {
+            read_farev2(&mut collections, fare_path, mode, options, &mut report)?;
+        } else {
+            read_fare_archives(
+                &mut collections,
+                zip_paths.iter().map(|zip_path| (zip_path.as_path(), None)),
+                mode,
+                options,
+                &mut report,
+            );
+        }
+    }
+
+    let report_path = report_path.as_ref();
+    info!("Writing report {:?}", report_path);
+    report.write(report_path)?;
+
+    Model::new(collections)
+}
+
+/// Summary of the prices, in a currency, of the tickets usable on a network
+/// or a line, for the applications not processing the fares V2
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PriceSummary {
+    /// Type of the object, `network` or `line`
+    pub object_type: ObjectType,
+    /// Identifier of the network or line
+    pub object_id: String,
+    /// Currency of the prices
+    pub currency: Currency,
+    /// Lowest price of the tickets
+    pub min_price: Decimal,
+    /// Highest price of the tickets
+    pub max_price: Decimal,
+    /// Sorted names of the tickets (their identifier if they have no name)
+    pub ticket_names: Vec<String>,
+}
+
+/// Computes the price summaries of the networks, then of the lines, of the
+/// `model`, sorted by identifier and currency.
+///
+/// The tickets usable on a network are the ones of the ticket uses whose
+/// perimeter includes the network. The tickets usable on a line are the
+/// ones of the ticket uses whose perimeter includes the line, or its
+/// network without excluding the line. Every price of the tickets is taken
+/// into account, whatever its validity period. The networks and lines
+/// without priced tickets have no summary.
+pub fn price_summaries(model: &Model) -> Vec<PriceSummary> {
+    let mut included: HashMap<(ObjectType, &str), BTreeSet<&str>> = HashMap::new();
+    let mut excluded: HashSet<(&str, ObjectType, &str)> = HashSet::new();
+    for perimeter in model.ticket_use_perimeters.values() {
+        match perimeter.perimeter_action {
+            PerimeterAction::Included => {
+                included
+                    .entry((perimeter.object_type.clone(), perimeter.object_id.as_str()))
+                    .or_default()
+                    .insert(perimeter.ticket_use_id.as_str());
+            }
+            PerimeterAction::Excluded => {
+                excluded.insert((
+                    perimeter.ticket_use_id.as_str(),
+                    perimeter.object_type.clone(),
+                    perimeter.object_id.as_str(),
+                ));
+            }
+        }
+    }
+    let mut prices: HashMap<&str, Vec<&TicketPrice>> = HashMap::new();
+    for price in model.ticket_prices.values() {
+        prices
+            .entry(price.ticket_id.as_str())
+            .or_default()
+            .push(price);
+    }
+    let ticket_uses_of = |object_type: ObjectType, object_id: &str| {
+        included
+            .get(&(object_type, object_id))
+            .cloned()
+            .unwrap_or_default()
+    };
+    let summarize = |object_type: ObjectType, object_id: &str, ticket_use_ids: BTreeSet<&str>| {
+        let mut by_currency: BTreeMap<&str, (Currency, Decimal, Decimal, BTreeSet<String>)> =
+            BTreeMap::new();
+        let tickets = ticket_use_ids
+            .into_iter()
+            .filter_map(|ticket_use_id| model.ticket_uses.get(ticket_use_id))
+            .filter_map(|ticket_use| model.tickets.get(&ticket_use.ticket_id));
+        for ticket in tickets {
+            let name = if ticket.name.is_empty() {
+                ticket.id.clone()
+            } else {
+                ticket.name.clone()
+            };
+            for price in prices.get(ticket.id.as_str()).into_iter().flatten() {
+                let (_, min_price, max_price, names) = by_currency
+                    .entry(price.currency.code())
+                    .or_insert((price.currency, price.price, price.price, BTreeSet::new()));
+                *min_price = (*min_price).min(price.price);
+                *max_price = (*max_price).max(price.price);
+                names.insert(name.clone());
+            }
+        }
+        by_currency
+            .into_iter()
+            .map(
+                |(_, (currency, min_price, max_price, names))| PriceSummary {
+                    object_type: object_type.clone(),
+                    object_id: object_id.to_string(),
+                    currency,
+                    min_price,
+                    max_price,
+                    ticket_names: names.into_iter().collect(),
+                },
+            )
+            .collect::<Vec<_>>()
+    };
+
+    let mut networks: Vec<&Network> = model.networks.values().collect();
+    networks.sort_by(|n1, n2| n1.id.cmp(&n2.id));
+    let mut lines: Vec<&Line> = model.lines.values().collect();
+    lines.sort_by(|l1, l2| l1.id.cmp(&l2.id));
+    let network_summaries = networks.into_iter().flat_map(|network| {
+        summarize(
+            ObjectType::Network,
+            &network.id,
+            ticket_uses_of(ObjectType::Network, &network.id),
+        )
+    });
+    let line_summaries = lines.into_iter().flat_map(|line| {
+        let ticket_use_ids = ticket_uses_of(ObjectType::Network, &line.network_id)
+            .into_iter()
+            .filter(|ticket_use_id| {
+                !excluded.contains(&(*ticket_use_id, ObjectType::Line, line.id.as_str()))
+            })
+            .chain(ticket_uses_of(ObjectType::Line, &line.id))
+            .collect();
+        summarize(ObjectType::Line, &line.id, ticket_use_ids)
+    });
+    network_summaries.chain(line_summaries).collect()
+}
+
+/// Writes the price summaries of the `model` (see
+/// [price_summaries](./fn.price_summaries.html)) as JSON in `path`.
+pub fn write_price_summaries<P: AsRef<Path>>(model: &Model, path: P) -> Result<()> {
+    let path = path.as_ref();
+    info!("Writing price summaries {:?}", path);
+    let file = fs::File::create(path).with_context(|_| format!("Error writing {:?}", path))?;
+    serde_json::to_writer_pretty(file, &price_summaries(model))
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        model_builder::ModelBuilder,
+        objects::{StopArea, StopPoint},
+        test_utils::*,
+    };
+    use pretty_assertions::assert_eq;
+    use typed_index_collection::CollectionWithId;
+
+    fn collections() -> Collections {
+        let mut collections = Collections::default();
+        collections.networks = CollectionWithId::new(vec![Network {
+            id: "network:1".to_string(),
+            ..Default::default()
+        }])
+        .unwrap();
+        collections.lines = CollectionWithId::new(vec![Line {
+            id: "line:1".to_string(),
+            network_id: "network:1".to_string(),
+            ..Default::default()
+        }])
+        .unwrap();
+        collections.stop_areas = CollectionWithId::new(vec![
+            StopArea {
+                id: "sa:1".to_string(),
+                ..Default::default()
+            },
+            StopArea {
+                id: "sa:2".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        collections.stop_points = CollectionWithId::new(vec![StopPoint {
+            id: "sp:1".to_string(),
+            stop_area_id: "sa:1".to_string(),
+            fare_zone_id: Some("zone:1".to_string()),
+            ..Default::default()
+        }])
+        .unwrap();
+        collections
+    }
+
+    fn perimeter(object_type: ObjectType, object_id: &str) -> TicketUsePerimeter {
+        TicketUsePerimeter {
+            ticket_use_id: "ticket_use:1".to_string(),
+            object_type,
+            object_id: object_id.to_string(),
+            perimeter_action: PerimeterAction::Included,
+        }
+    }
+
+    fn restriction(
+        restriction_type: RestrictionType,
+        origin: &str,
+        destination: &str,
+    ) -> TicketUseRestriction {
+        TicketUseRestriction {
+            ticket_use_id: "ticket_use:1".to_string(),
+            restriction_type,
+            use_origin: origin.to_string(),
+            use_destination: destination.to_string(),
+        }
+    }
+
+    #[test]
+    fn network_and_line_perimeters() {
+        let collections = collections();
+        let exists = |perimeter| perimeter_object_exists(&collections, &perimeter);
+        assert!(exists(perimeter(ObjectType::Network, "network:1")));
+        assert!(!exists(perimeter(ObjectType::Network, "unknown")));
+        assert!(!exists(perimeter(ObjectType::Network, "line:1")));
+        assert!(exists(perimeter(ObjectType::Line, "line:1")));
+        assert!(!exists(perimeter(ObjectType::Line, "unknown")));
+        assert!(!exists(perimeter(ObjectType::Line, "network:1")));
+        // only networks and lines can be perimeters
+        assert!(!exists(perimeter(ObjectType::StopArea, "sa:1")));
+    }
+
+    #[test]
+    fn od_and_zone_restrictions() {
+        let collections = collections();
+        let fare_zones = fare_zones(&collections);
+        let exists =
+            |restriction| restriction_objects_exist(&collections, &fare_zones, &restriction);
+        use RestrictionType::{OriginDestination, Zone};
+        assert!(exists(restriction(OriginDestination, "sa:1", "sa:2")));
+        assert!(!exists(restriction(OriginDestination, "sa:1", "unknown")));
+        assert!(!exists(restriction(OriginDestination, "unknown", "sa:2")));
+        // the stop points and fare zones are not stop areas
+        assert!(!exists(restriction(OriginDestination, "sa:1", "sp:1")));
+        assert!(!exists(restriction(OriginDestination, "zone:1", "sa:1")));
+        assert!(exists(restriction(Zone, "zone:1", "zone:1")));
+        assert!(!exists(restriction(Zone, "zone:1", "unknown")));
+        assert!(!exists(restriction(Zone, "sa:1", "zone:1")));
+    }
+
+    #[test]
+    fn ignore_unknown_perimeters_and_restrictions() {
+        let mut collections = collections();
+        let mut report = Report::default();
+        test_in_tmp_dir(|path| {
+            create_file_with_content(
+                path,
+                "tickets.txt",
+                "ticket_id,ticket_name,ticket_comment\nticket:1,,\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_prices.txt",
+                "ticket_id,ticket_price,ticket_currency,ticket_validity_start,ticket_validity_end\n\
+                 ticket:1,1.50,EUR,20200101,20201231\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_uses.txt",
+                "ticket_use_id,ticket_id,max_transfers,boarding_time_limit,alighting_time_limit\n\
+                 ticket_use:1,ticket:1,,,\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_use_perimeters.txt",
+                "ticket_use_id,object_type,object_id,perimeter_action\n\
+                 ticket_use:1,network,network:1,1\n\
+                 ticket_use:1,line,unknown,2\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_use_restrictions.txt",
+                "ticket_use_id,restriction_type,use_origin,use_destination\n\
+                 ticket_use:1,OD,sa:1,sa:2\n\
+                 ticket_use:1,OD,sa:1,unknown\n",
+            );
+            // 2 of the 4 perimeters and restrictions are unknown
+            assert!(read_farev2(
+                &mut collections,
+                path,
+                FaresMode::Replace,
+                &FaresOptions {
+                    max_error_ratio: Some(0.4),
+                    ..Default::default()
+                },
+                &mut report
+            )
+            .is_err());
+            assert!(collections.tickets.is_empty());
+            assert!(report.entries(Severity::Warning).is_empty());
+
+            read_farev2(
+                &mut collections,
+                path,
+                FaresMode::Replace,
+                &FaresOptions {
+                    max_error_ratio: Some(0.5),
+                    ..Default::default()
+                },
+                &mut report,
+            )
+            .unwrap();
+        });
+        assert_eq!(1, collections.ticket_uses.len());
+        assert_eq!(
+            vec!["network:1"],
+            collections
+                .ticket_use_perimeters
+                .values()
+                .map(|perimeter| perimeter.object_id.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["sa:2"],
+            collections
+                .ticket_use_restrictions
+                .values()
+                .map(|restriction| restriction.use_destination.as_str())
+                .collect::<Vec<_>>()
+        );
+        let warnings = report.entries(Severity::Warning);
+        assert_eq!(2, warnings.len());
+        assert!(warnings
+            .iter()
+            .all(|warning| warning.category == ReportType::ObjectNotFound));
+    }
+
+    #[test]
+    fn zone_restrictions_on_fare_zones() {
+        let mut collections = collections();
+        let mut report = Report::default();
+        test_in_tmp_dir(|path| {
+            create_file_with_content(
+                path,
+                "tickets.txt",
+                "ticket_id,ticket_name,ticket_comment\nticket:1,,\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_prices.txt",
+                "ticket_id,ticket_price,ticket_currency,ticket_validity_start,ticket_validity_end\n\
+                 ticket:1,1.50,EUR,20200101,20201231\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_uses.txt",
+                "ticket_use_id,ticket_id,max_transfers,boarding_time_limit,alighting_time_limit\n\
+                 ticket_use:1,ticket:1,,,\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_use_perimeters.txt",
+                "ticket_use_id,object_type,object_id,perimeter_action\n\
+                 ticket_use:1,network,network:1,1\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_use_restrictions.txt",
+                "ticket_use_id,restriction_type,use_origin,use_destination\n\
+                 ticket_use:1,zone,zone:1,zone:2\n\
+                 ticket_use:1,zone,zone:2,zone:3\n",
+            );
+            create_file_with_content(
+                path,
+                "fare_zones.txt",
+                "fare_zone_id,fare_zone_name\nzone:2,Suburbs\n",
+            );
+            read_farev2(
+                &mut collections,
+                path,
+                FaresMode::Replace,
+                &FaresOptions::default(),
+                &mut report,
+            )
+            .unwrap();
+        });
+        assert_eq!(
+            Some("Suburbs"),
+            collections
+                .fare_zones
+                .get("zone:2")
+                .unwrap()
+                .name
+                .as_deref()
+        );
+        // zone:1 is the fare zone of a stop point, zone:3 is unknown
+        assert_eq!(
+            vec!["zone:2"],
+            collections
+                .ticket_use_restrictions
+                .values()
+                .map(|restriction| restriction.use_destination.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, report.entries(Severity::Warning).len());
+    }
+
+    #[test]
+    fn validate_fares_of_collections() {
+        let mut collections = collections();
+        collections.tickets = CollectionWithId::new(vec![Ticket {
+            id: "ticket:1".to_string(),
+            name: String::new(),
+            comment: None,
+        }])
+        .unwrap();
+        collections.ticket_uses = CollectionWithId::new(vec![
+            TicketUse {
+                id: "ticket_use:1".to_string(),
+                ticket_id: "ticket:1".to_string(),
+                max_transfers: None,
+                boarding_time_limit: None,
+                alighting_time_limit: None,
+            },
+            TicketUse {
+                id: "ticket_use:2".to_string(),
+                ticket_id: "unknown".to_string(),
+                max_transfers: None,
+                boarding_time_limit: None,
+                alighting_time_limit: None,
+            },
+        ])
+        .unwrap();
+        collections
+            .ticket_use_perimeters
+            .push(perimeter(ObjectType::Line, "line:1"));
+        collections
+            .ticket_use_perimeters
+            .push(perimeter(ObjectType::Line, "unknown"));
+        collections.ticket_use_restrictions.push(restriction(
+            RestrictionType::Zone,
+            "zone:1",
+            "zone:1",
+        ));
+        collections.ticket_use_restrictions.push(restriction(
+            RestrictionType::OriginDestination,
+            "sa:1",
+            "unknown",
+        ));
+
+        let report = validate(&collections);
+        let errors: Vec<ReportType> = report
+            .entries(Severity::Error)
+            .iter()
+            .map(|error| error.category)
+            .collect();
+        assert_eq!(
+            vec![
+                ReportType::DanglingReference,
+                ReportType::ObjectNotFound,
+                ReportType::ObjectNotFound
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn validate_fares_files() {
+        let collections = collections();
+        let mut report = Report::default();
+        test_in_tmp_dir(|path| {
+            create_file_with_content(
+                path,
+                "tickets.txt",
+                "ticket_id,ticket_name,ticket_comment\nticket:1,,\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_prices.txt",
+                "ticket_id,ticket_price,ticket_currency,ticket_validity_start,ticket_validity_end\n\
+                 ticket:1,1.50,EUR,20200101,20201231\n\
+                 ticket:2,2.00,EUR,20200101,20201231\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_uses.txt",
+                "ticket_use_id,ticket_id,max_transfers,boarding_time_limit,alighting_time_limit\n\
+                 ticket_use:1,ticket:1,,,\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_use_perimeters.txt",
+                "ticket_use_id,object_type,object_id,perimeter_action\n\
+                 ticket_use:1,network,network:1,1\n\
+                 ticket_use:2,line,line:1,1\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_use_restrictions.txt",
+                "ticket_use_id,restriction_type,use_origin,use_destination\n\
+                 ticket_use:1,zone,zone:1,zone:2\n\
+                 ticket_use:1,zone,zone:1,zone:3\n",
+            );
+            create_file_with_content(
+                path,
+                "fare_zones.txt",
+                "fare_zone_id,fare_zone_name\nzone:2,Suburbs\n",
+            );
+            report = validate_farev2(&collections, path, ReadMode::Strict).unwrap();
+        });
+        assert!(collections.tickets.is_empty());
+        assert!(collections.fare_zones.is_empty());
+        let errors: Vec<&str> = report
+            .entries(Severity::Error)
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "prices reference the unknown ticket ticket:2",
+                "a perimeter references the unknown ticket use ticket_use:2",
+                "the restriction of the ticket use ticket_use:1 from zone:1 to zone:3 references an unknown origin or destination",
+            ],
+            errors
+        );
+    }
+
+    fn create_fares(path: &Path, ticket_use_id: &str, perimeters: &str) {
+        fs::create_dir(path).unwrap();
+        let ticket_id = format!("ticket:{}", ticket_use_id);
+        create_file_with_content(
+            path,
+            "tickets.txt",
+            &format!("ticket_id,ticket_name,ticket_comment\n{},,\n", ticket_id),
+        );
+        create_file_with_content(
+            path,
+            "ticket_prices.txt",
+            &format!(
+                "ticket_id,ticket_price,ticket_currency,ticket_validity_start,ticket_validity_end\n\
+                 {},1.50,EUR,20200101,20201231\n",
+                ticket_id
+            ),
+        );
+        create_file_with_content(
+            path,
+            "ticket_uses.txt",
+            &format!(
+                "ticket_use_id,ticket_id,max_transfers,boarding_time_limit,alighting_time_limit\n\
+                 {},{},,,\n",
+                ticket_use_id, ticket_id
+            ),
+        );
+        create_file_with_content(
+            path,
+            "ticket_use_perimeters.txt",
+            &format!(
+                "ticket_use_id,object_type,object_id,perimeter_action\n{}",
+                perimeters
+            ),
+        );
+    }
+
+    #[test]
+    fn report_perimeter_leaks_of_manifest() {
+        let mut collections = collections();
+        collections
+            .networks
+            .push(Network {
+                id: "network:2".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        collections
+            .lines
+            .push(Line {
+                id: "line:2".to_string(),
+                network_id: "network:2".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut report = Report::default();
+        test_in_tmp_dir(|path| {
+            create_fares(
+                &path.join("authority_1"),
+                "ticket_use:1",
+                "ticket_use:1,network,network:1,1\n\
+                 ticket_use:1,line,line:2,1\n\
+                 ticket_use:1,line,line:2,2\n",
+            );
+            create_fares(
+                &path.join("authority_2"),
+                "ticket_use:2",
+                "ticket_use:2,network,network:2,1\n",
+            );
+            create_file_with_content(
+                path,
+                "manifest.json",
+                r#"[
+                    { "path": "authority_1", "networks": ["network:1"] },
+                    { "path": "authority_2", "networks": ["network:2"] }
+                ]"#,
+            );
+            let manifest = read_fares_manifest(path.join("manifest.json")).unwrap();
+            assert_eq!(path.join("authority_2"), manifest[1].path);
+            read_farev2_manifest(
+                &mut collections,
+                &manifest,
+                FaresMode::Replace,
+                &FaresOptions::default(),
+                &mut report,
+            );
+        });
+        assert_eq!(2, collections.ticket_uses.len());
+        let perimeters: Vec<(&str, &str)> = collections
+            .ticket_use_perimeters
+            .values()
+            .map(|perimeter| {
+                (
+                    perimeter.ticket_use_id.as_str(),
+                    perimeter.object_id.as_str(),
+                )
+            })
+            .collect();
+        // the exclusion of a line of another network is not a leak
+        assert_eq!(
+            vec![
+                ("ticket_use:1", "network:1"),
+                ("ticket_use:1", "line:2"),
+                ("ticket_use:2", "network:2")
+            ],
+            perimeters
+        );
+        let sections: Vec<(&str, Vec<ReportType>)> = report
+            .sections()
+            .map(|(name, section)| {
+                (
+                    name,
+                    section
+                        .entries(Severity::Error)
+                        .iter()
+                        .map(|error| error.category)
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("authority_1", vec![ReportType::PerimeterLeak]),
+                ("authority_2", vec![])
+            ],
+            sections
+        );
+    }
+
+    #[test]
+    fn report_restriction_leaks_of_manifest() {
+        let mut collections = ModelBuilder::default()
+            .line("line:1", |line| line.network_id = "network:1".to_string())
+            .line("line:2", |line| line.network_id = "network:2".to_string())
+            .route("route:1", |route| route.line_id = "line:1".to_string())
+            .route("route:2", |route| route.line_id = "line:2".to_string())
+            .stop_point("sp:1", |stop_point| {
+                stop_point.stop_area_id = "sa:1".to_string();
+                stop_point.fare_zone_id = Some("zone:1".to_string());
+            })
+            .stop_point("sp:2", |stop_point| {
+                stop_point.stop_area_id = "sa:2".to_string();
+                stop_point.fare_zone_id = Some("zone:2".to_string());
+            })
+            .vj("vj:1", |vj| {
+                vj.route("route:1").st("sp:1", "10:00").st("sp:1", "10:10");
+            })
+            .vj("vj:2", |vj| {
+                vj.route("route:2").st("sp:2", "10:00").st("sp:2", "10:10");
+            })
+            .build()
+            .into_collections();
+        let mut report = Report::default();
+        test_in_tmp_dir(|path| {
+            create_fares(
+                &path.join("authority_1"),
+                "ticket_use:1",
+                "ticket_use:1,network,network:1,1\n",
+            );
+            // sa:2 and zone:2 are only served by network:2
+            create_file_with_content(
+                &path.join("authority_1"),
+                "ticket_use_restrictions.txt",
+                "ticket_use_id,restriction_type,use_origin,use_destination\n\
+                 ticket_use:1,OD,sa:1,sa:1\n\
+                 ticket_use:1,OD,sa:1,sa:2\n\
+                 ticket_use:1,zone,zone:1,zone:1\n\
+                 ticket_use:1,zone,zone:2,zone:1\n",
+            );
+            create_file_with_content(
+                path,
+                "manifest.json",
+                r#"[{ "path": "authority_1", "networks": ["network:1"] }]"#,
+            );
+            let manifest = read_fares_manifest(path.join("manifest.json")).unwrap();
+            read_farev2_manifest(
+                &mut collections,
+                &manifest,
+                FaresMode::Replace,
+                &FaresOptions::default(),
+                &mut report,
+            );
+        });
+        let restrictions: Vec<(&str, &str)> = collections
+            .ticket_use_restrictions
+            .values()
+            .map(|restriction| {
+                (
+                    restriction.use_origin.as_str(),
+                    restriction.use_destination.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(vec![("sa:1", "sa:1"), ("zone:1", "zone:1")], restrictions);
+        let leaks: Vec<(ReportType, Option<&str>)> = report
+            .section("authority_1")
+            .entries(Severity::Error)
+            .iter()
+            .map(|error| (error.category, error.object_id.as_deref()))
+            .collect();
+        assert_eq!(
+            vec![
+                (ReportType::PerimeterLeak, Some("ticket:ticket_use:1")),
+                (ReportType::PerimeterLeak, Some("ticket:ticket_use:1"))
+            ],
+            leaks
+        );
+    }
+
+    #[test]
+    fn reject_unknown_currency() {
+        let mut collections = collections();
+        let mut report = Report::default();
+        test_in_tmp_dir(|path| {
+            create_file_with_content(
+                path,
+                "tickets.txt",
+                "ticket_id,ticket_name,ticket_comment\nticket:1,,\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_prices.txt",
+                "ticket_id,ticket_price,ticket_currency,ticket_validity_start,ticket_validity_end\n\
+                 ticket:1,1.50,EURO,20200101,20201231\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_uses.txt",
+                "ticket_use_id,ticket_id,max_transfers,boarding_time_limit,alighting_time_limit\n",
+            );
+            create_file_with_content(
+                path,
+                "ticket_use_perimeters.txt",
+                "ticket_use_id,object_type,object_id,perimeter_action\n",
+            );
+            let error = read_farev2(
+                &mut collections,
+                path,
+                FaresMode::Replace,
+                &FaresOptions::default(),
+                &mut report,
+            )
+            .unwrap_err();
+            assert!(error
+                .iter_chain()
+                .any(|cause| cause.to_string().contains("\"EURO\"")));
+        });
+        assert!(collections.tickets.is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Filters restricting a `Model` to a subset of its objects
+
+use crate::{model::Model, objects::Line, ridership::line_ridership, tags, zones, Result};
+use log::info;
+use std::{cmp::Ordering, collections::HashSet};
+
+/// Keeps only the lines for which `keep` returns `true`, with their routes and
+/// vehicle journeys. The objects that are not used anymore (stop points,
+/// networks, calendars, etc.) are removed.
+pub fn keep_lines<F>(model: Model, keep: F) -> Result<Model>
+where
+    F: Fn(&Line) -> bool,
+{
+    let line_ids: HashSet<String> = model
+        .lines
+        .values()
+        .filter(|line| keep(line))
+        .map(|line| line.id.clone())
+        .collect();
+    let route_ids: HashSet<String> = model
+        .routes
+        .values()
+        .filter(|route| line_ids.contains(&route.line_id))
+        .map(|route| route.id.clone())
+        .collect();
+    let mut collections = model.into_collections();
+    collections
+        .vehicle_journeys
+        .retain(|vj| route_ids.contains(&vj.route_id));
+    Model::new(collections)
+}
+
+/// Keeps only the lines with the `tag` (see [tags](../tags/index.html)),
+/// like [keep_lines](fn.keep_lines.html).
+pub fn keep_tagged_lines(model: Model, tag: &str) -> Result<Model> {
+    info!("Keeping the lines tagged {:?}", tag);
+    keep_lines(model, |line| tags::has_tag(line, tag))
+}
+
+/// Keeps only the lines of the zonal on-demand services: the lines with a
+/// vehicle journey serving a zone (see [zones](../zones/index.html)), like
+/// [keep_lines](fn.keep_lines.html).
+pub fn keep_zonal_lines(model: Model) -> Result<Model> {
+    info!("Keeping the zonal lines");
+    let line_ids: HashSet<String> = model
+        .lines
+        .iter()
+        .filter(|(line_idx, _)| !zones::line_zones(&model, *line_idx).is_empty())
+        .map(|(_, line)| line.id.clone())
+        .collect();
+    keep_lines(model, |line| line_ids.contains(&line.id))
+}
+
+/// Keeps only the `n` lines with the highest ridership, as imported by
+/// [read_ridership](../ridership/fn.read_ridership.html).
+///
+/// Lines without ridership are considered less used than any other line. Lines
+/// with the same ridership are ranked by identifier.
+pub fn keep_top_lines(model: Model, n: usize) -> Result<Model> {
+    info!("Keeping the {} lines with the highest ridership", n);
+    let mut lines: Vec<(&str, Option<f64>)> = model
+        .lines
+        .values()
+        .map(|line| (line.id.as_str(), line_ridership(line)))
+        .collect();
+    lines.sort_by(|(id1, ridership1), (id2, ridership2)| {
+        ridership2
+            .partial_cmp(ridership1)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| id1.cmp(id2))
+    });
+    let line_ids: HashSet<String> = lines
+        .into_iter()
+        .take(n)
+        .map(|(id, _)| id.to_string())
+        .collect();
+    keep_lines(model, |line| line_ids.contains(&line.id))
+}
@@ -0,0 +1,267 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Footnotes of timetables: short notes attached to vehicle journeys or to
+//! stop times, referenced by a code (`a`, `b`, ...) as in rail timetables.
+//!
+//! A footnote is a [Comment](../objects/struct.Comment.html) whose code is
+//! the `label`. See functions read_footnotes, assign_footnote_codes and
+//! write_footnotes.
+
+use crate::{
+    model::{Collections, Model},
+    objects::Comment,
+    Result,
+};
+use failure::ResultExt;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
+use typed_index_collection::CollectionWithId;
+
+/// Prefix of the identifiers of the comments created from footnotes
+pub const FOOTNOTE_PREFIX: &str = "footnote:";
+
+/// A row of a footnotes file
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Footnote {
+    /// Identifier of the vehicle journey
+    pub trip_id: String,
+    /// Sequence of the stop time, if the footnote applies to a stop time
+    /// rather than to the whole vehicle journey
+    pub stop_sequence: Option<u32>,
+    /// Code of the footnote, if any
+    pub footnote_code: Option<String>,
+    /// Text of the footnote
+    pub footnote_text: String,
+}
+
+/// Imports footnotes from the CSV file `path`, with the columns `trip_id`,
+/// `stop_sequence` (empty for a footnote on the whole vehicle journey),
+/// `footnote_code` (optional) and `footnote_text`.
+///
+/// Each distinct pair of code and text gives a comment, linked to the
+/// vehicle journeys and stop times it applies to. Footnotes on an unknown
+/// vehicle journey or stop time are logged and ignored. A stop time holds
+/// only one comment: a second footnote on the same stop time replaces the
+/// first one, with a warning.
+pub fn read_footnotes<P: AsRef<Path>>(model: Model, path: P) -> Result<Model> {
+    let path = path.as_ref();
+    info!("Reading footnotes {:?}", path);
+    let mut collections = model.into_collections();
+    let mut rdr =
+        csv::Reader::from_path(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let mut comment_ids: HashMap<(Option<String>, String), String> = HashMap::new();
+    for footnote in rdr.deserialize() {
+        let footnote: Footnote = footnote.with_context(|_| format!("Error reading {:?}", path))?;
+        let vj_idx = match collections.vehicle_journeys.get_idx(&footnote.trip_id) {
+            Some(vj_idx) => vj_idx,
+            None => {
+                warn!("trip {:?} not found, footnote ignored", footnote.trip_id);
+                continue;
+            }
+        };
+        if let Some(sequence) = footnote.stop_sequence {
+            let has_stop_time = collections.vehicle_journeys[vj_idx]
+                .stop_times
+                .iter()
+                .any(|st| st.sequence == sequence);
+            if !has_stop_time {
+                warn!(
+                    "stop time {} of trip {:?} not found, footnote ignored",
+                    sequence, footnote.trip_id
+                );
+                continue;
+            }
+        }
+        let key = (
+            footnote.footnote_code.clone(),
+            footnote.footnote_text.clone(),
+        );
+        let comment_id = match comment_ids.get(&key) {
+            Some(comment_id) => comment_id.clone(),
+            None => {
+                let mut index = comment_ids.len() + 1;
+                while collections
+                    .comments
+                    .contains_id(&format!("{}{}", FOOTNOTE_PREFIX, index))
+                {
+                    index += 1;
+                }
+                let comment_id = format!("{}{}", FOOTNOTE_PREFIX, index);
+                collections
+                    .comments
+                    .push(Comment {
+                        id: comment_id.clone(),
+                        label: footnote.footnote_code,
+                        name: footnote.footnote_text,
+                        ..Default::default()
+                    })
+                    .with_context(|_| format!("Error reading {:?}", path))?;
+                comment_ids.insert(key, comment_id.clone());
+                comment_id
+            }
+        };
+        match footnote.stop_sequence {
+            Some(sequence) => {
+                let previous = collections
+                    .stop_time_comments
+                    .insert((footnote.trip_id.clone(), sequence), comment_id);
+                if previous.is_some() {
+                    warn!(
+                        "stop time {} of trip {:?} already has a comment, replaced by a footnote",
+                        sequence, footnote.trip_id
+                    );
+                }
+            }
+            None => {
+                collections
+                    .vehicle_journeys
+                    .index_mut(vj_idx)
+                    .comment_links
+                    .insert(comment_id);
+            }
+        }
+    }
+    Model::new(collections)
+}
+
+// Codes `a` to `z`, then `aa`, `ab`, ...
+fn footnote_code(mut index: usize) -> String {
+    let mut code = Vec::new();
+    loop {
+        code.push(b'a' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    code.reverse();
+    String::from_utf8(code).unwrap()
+}
+
+impl Collections {
+    /// Assigns a code to the comments of the vehicle journeys and stop times
+    /// without a label, one code per unique text.
+    ///
+    /// Comments with the same text share the same code, reusing the label
+    /// of one of them if any. New codes are `a`, `b`, ..., `z`, `aa`, ...,
+    /// in the order of the texts, skipping the labels already used.
+    pub fn assign_footnote_codes(&mut self) {
+        let footnote_ids: BTreeSet<&str> = self
+            .vehicle_journeys
+            .values()
+            .flat_map(|vj| vj.comment_links.iter())
+            .chain(self.stop_time_comments.values())
+            .map(String::as_str)
+            .collect();
+        let mut codes: BTreeMap<String, Option<String>> = BTreeMap::new();
+        for comment in self.comments.values() {
+            if footnote_ids.contains(comment.id.as_str()) {
+                let code = codes.entry(comment.name.clone()).or_insert(None);
+                if code.is_none() {
+                    *code = comment.label.clone();
+                }
+            }
+        }
+        let mut used_codes: BTreeSet<String> = self
+            .comments
+            .values()
+            .filter_map(|comment| comment.label.clone())
+            .collect();
+        let mut index = 0;
+        for code in codes.values_mut().filter(|code| code.is_none()) {
+            let mut new_code = footnote_code(index);
+            while used_codes.contains(&new_code) {
+                index += 1;
+                new_code = footnote_code(index);
+            }
+            index += 1;
+            used_codes.insert(new_code.clone());
+            *code = Some(new_code);
+        }
+
+        let mut comments = self.comments.take();
+        for comment in comments
+            .iter_mut()
+            .filter(|comment| comment.label.is_none())
+            .filter(|comment| footnote_ids.contains(comment.id.as_str()))
+        {
+            comment.label = codes[&comment.name].clone();
+        }
+        // the identifiers are unchanged, they are still unique
+        self.comments = CollectionWithId::new(comments).unwrap();
+    }
+}
+
+/// Exports the comments of the vehicle journeys and stop times of the
+/// `model` as footnotes, in the CSV file `path` read by
+/// [read_footnotes](fn.read_footnotes.html).
+///
+/// The footnotes are sorted by trip and stop sequence, the footnotes of a
+/// whole vehicle journey first.
+pub fn write_footnotes<P: AsRef<Path>>(model: &Model, path: P) -> Result<()> {
+    let path = path.as_ref();
+    info!("Writing footnotes {:?}", path);
+    let mut footnotes: BTreeMap<(&str, Option<u32>, &str), &Comment> = BTreeMap::new();
+    for vj in model.vehicle_journeys.values() {
+        for comment_id in &vj.comment_links {
+            if let Some(comment) = model.comments.get(comment_id) {
+                footnotes.insert((vj.id.as_str(), None, comment.id.as_str()), comment);
+            }
+        }
+    }
+    for ((vj_id, sequence), comment_id) in &model.stop_time_comments {
+        if let Some(comment) = model.comments.get(comment_id) {
+            footnotes.insert(
+                (vj_id.as_str(), Some(*sequence), comment.id.as_str()),
+                comment,
+            );
+        }
+    }
+    let mut wtr =
+        csv::Writer::from_path(path).with_context(|_| format!("Error writing {:?}", path))?;
+    for ((trip_id, stop_sequence, _), comment) in footnotes {
+        wtr.serialize(Footnote {
+            trip_id: trip_id.to_string(),
+            stop_sequence,
+            footnote_code: comment.label.clone(),
+            footnote_text: comment.name.clone(),
+        })
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    wtr.flush()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn footnote_codes() {
+        assert_eq!("a", footnote_code(0));
+        assert_eq!("z", footnote_code(25));
+        assert_eq!("aa", footnote_code(26));
+        assert_eq!("az", footnote_code(51));
+        assert_eq!("ba", footnote_code(52));
+        assert_eq!("zz", footnote_code(701));
+        assert_eq!("aaa", footnote_code(702));
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! See function generate_geometries
+
+use crate::{
+    model::Model,
+    objects::{Coord, Geometry, StopPoint},
+    Result,
+};
+use geo::{algorithm::simplify::Simplify, Geometry as GeoGeometry, LineString};
+use log::{info, warn};
+use std::collections::HashMap;
+use typed_index_collection::{CollectionWithId, Idx};
+
+fn make_line_string(
+    stop_points: &CollectionWithId<StopPoint>,
+    stop_point_idxs: &[Idx<StopPoint>],
+    simplify_tolerance: Option<f64>,
+) -> Option<LineString<f64>> {
+    let mut coords: Vec<(f64, f64)> = Vec::with_capacity(stop_point_idxs.len());
+    for stop_point_idx in stop_point_idxs {
+        let stop_point = &stop_points[*stop_point_idx];
+        if stop_point.coord == Coord::default() {
+            warn!(
+                "Stop Point {} geolocation is (0, 0), no geometry will be generated through it.",
+                stop_point.id
+            );
+            return None;
+        }
+        let coord = (stop_point.coord.lon, stop_point.coord.lat);
+        if coords.last() != Some(&coord) {
+            coords.push(coord);
+        }
+    }
+    if coords.len() < 2 {
+        return None;
+    }
+    let line_string = LineString::from(coords);
+    Some(match simplify_tolerance {
+        Some(tolerance) => line_string.simplify(&tolerance),
+        None => line_string,
+    })
+}
+
+// The identifier `geometry:<vj_id>`, or `geometry:<vj_id>:<n>` with the
+// smallest `n` such that the identifier is not used
+fn generated_geometry_id(vj_id: &str, geometries: &CollectionWithId<Geometry>) -> String {
+    let id = format!("geometry:{}", vj_id);
+    if !geometries.contains_id(&id) {
+        return id;
+    }
+    (1..)
+        .map(|n| format!("{}:{}", id, n))
+        .find(|geometry_id| !geometries.contains_id(geometry_id))
+        .unwrap()
+}
+
+impl Model {
+    /// Generates the geometries of the vehicle journeys and routes which
+    /// don't have one.
+    ///
+    /// The geometry of a vehicle journey is the `LineString` going through the
+    /// coordinates of its stop points, in order. Vehicle journeys stopping at
+    /// the same stop points share the same geometry. A route gets the geometry
+    /// of its vehicle journey with the most stop times. The identifier of a
+    /// generated geometry is `geometry:<vj_id>` (suffixed with `:<n>` when
+    /// already used).
+    ///
+    /// With a `simplify_tolerance` (in degrees, as the coordinates), the
+    /// geometries are simplified with the Ramer–Douglas–Peucker algorithm.
+    ///
+    /// WARNING: no geometry is generated through a `StopPoint` whose
+    /// geolocation is (0, 0).
+    pub fn generate_geometries(self, simplify_tolerance: Option<f64>) -> Result<Model> {
+        info!("Generating geometries...");
+        let mut collections = self.into_collections();
+
+        let mut geometry_ids: HashMap<Vec<Idx<StopPoint>>, Option<String>> = HashMap::new();
+        let vj_idxs: Vec<_> = collections
+            .vehicle_journeys
+            .iter()
+            .filter(|(_, vj)| vj.geometry_id.is_none())
+            .map(|(vj_idx, _)| vj_idx)
+            .collect();
+        for vj_idx in vj_idxs {
+            let vj = &collections.vehicle_journeys[vj_idx];
+            let stop_point_idxs: Vec<Idx<StopPoint>> =
+                vj.stop_times.iter().map(|st| st.stop_point_idx).collect();
+            let geometry_id = match geometry_ids.get(&stop_point_idxs) {
+                Some(geometry_id) => geometry_id.clone(),
+                None => {
+                    let geometry_id = match make_line_string(
+                        &collections.stop_points,
+                        &stop_point_idxs,
+                        simplify_tolerance,
+                    ) {
+                        Some(line_string) => {
+                            let geometry_id =
+                                generated_geometry_id(&vj.id, &collections.geometries);
+                            collections.geometries.push(Geometry {
+                                id: geometry_id.clone(),
+                                geometry: GeoGeometry::LineString(line_string),
+                            })?;
+                            Some(geometry_id)
+                        }
+                        None => None,
+                    };
+                    geometry_ids.insert(stop_point_idxs, geometry_id.clone());
+                    geometry_id
+                }
+            };
+            collections.vehicle_journeys.index_mut(vj_idx).geometry_id = geometry_id;
+        }
+
+        let mut route_geometries: HashMap<String, (usize, String)> = HashMap::new();
+        for vj in collections.vehicle_journeys.values() {
+            if let Some(geometry_id) = &vj.geometry_id {
+                let nb_stop_times = vj.stop_times.len();
+                let longest = route_geometries
+                    .entry(vj.route_id.clone())
+                    .or_insert_with(|| (nb_stop_times, geometry_id.clone()));
+                if nb_stop_times > longest.0 {
+                    *longest = (nb_stop_times, geometry_id.clone());
+                }
+            }
+        }
+        let route_idxs: Vec<_> = collections
+            .routes
+            .iter()
+            .filter(|(_, route)| route.geometry_id.is_none())
+            .map(|(route_idx, _)| route_idx)
+            .collect();
+        for route_idx in route_idxs {
+            let mut route = collections.routes.index_mut(route_idx);
+            route.geometry_id = route_geometries
+                .get(&route.id)
+                .map(|(_, geometry_id)| geometry_id.clone());
+        }
+
+        Model::new(collections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{model::Model, ntfs, objects::Geometry};
+    use geo::{Geometry as GeoGeometry, LineString, Point};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn generate_geometries_from_stop_points() {
+        let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+        let model = model.generate_geometries(None).unwrap();
+
+        let vj = model.vehicle_journeys.get("B42F1").unwrap();
+        assert_eq!(Some("geometry:B42F1".to_string()), vj.geometry_id);
+        let route = model.routes.get("B42F").unwrap();
+        assert_eq!(vj.geometry_id, route.geometry_id);
+        let geometry = model.geometries.get("geometry:B42F1").unwrap();
+        assert_eq!(
+            GeoGeometry::LineString(LineString::from(vec![
+                (2.372987, 48.844746),
+                (2.321783, 48.842481)
+            ])),
+            geometry.geometry
+        );
+        assert!(model
+            .vehicle_journeys
+            .values()
+            .all(|vj| vj.geometry_id.is_some()));
+    }
+
+    #[test]
+    fn simplify_generated_geometries() {
+        let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+        let model = model.generate_geometries(Some(1.0)).unwrap();
+
+        let geometry = model.geometries.get("geometry:M1F1").unwrap();
+        match &geometry.geometry {
+            GeoGeometry::LineString(line_string) => assert_eq!(2, line_string.0.len()),
+            _ => panic!("a LineString is expected"),
+        }
+    }
+
+    #[test]
+    fn generate_geometries_with_used_identifier() {
+        let mut collections = ntfs::read("tests/fixtures/minimal_ntfs/")
+            .unwrap()
+            .into_collections();
+        collections
+            .geometries
+            .push(Geometry {
+                id: "geometry:B42F1".to_string(),
+                geometry: GeoGeometry::Point(Point::new(2.37, 48.84)),
+            })
+            .unwrap();
+        collections
+            .lines
+            .index_mut(collections.lines.get_idx("B42").unwrap())
+            .geometry_id = Some("geometry:B42F1".to_string());
+        let model = Model::new(collections).unwrap();
+        let model = model.generate_geometries(None).unwrap();
+
+        let vj = model.vehicle_journeys.get("B42F1").unwrap();
+        assert_eq!(Some("geometry:B42F1:1".to_string()), vj.geometry_id);
+        assert_eq!(
+            GeoGeometry::Point(Point::new(2.37, 48.84)),
+            model.geometries.get("geometry:B42F1").unwrap().geometry
+        );
+    }
+}
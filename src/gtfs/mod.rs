@@ -17,20 +17,27 @@
 mod read;
 mod write;
 
+#[cfg(feature = "zip")]
+use crate::report::{Report, ReportType};
 use crate::{
-    calendars::{manage_calendars, write_calendar_dates},
+    calendars::{self, manage_calendars, write_calendar_dates},
+    crs::CoordinateSystem,
     gtfs::read::EquipmentList,
     model::{Collections, Model},
+    ntfs,
     objects::{self, Availability, Contributor, Dataset, StopPoint, StopType, Time},
-    read_utils,
+    read_utils::{self, ReadMode},
+    schema::{self, FileSchema},
     utils::*,
     validity_period, AddPrefix, PrefixConfiguration, Result,
 };
+use chrono::{DateTime, FixedOffset};
 use chrono_tz::Tz;
 use derivative::Derivative;
+use failure::ResultExt;
 use log::info;
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, fmt, path::Path};
+use std::{collections::BTreeMap, fmt, fs::File, io::BufWriter, path::Path};
 use typed_index_collection::{CollectionWithId, Idx};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -47,6 +54,8 @@ struct Agency {
     lang: Option<String>,
     #[serde(rename = "agency_phone")]
     phone: Option<String>,
+    #[serde(rename = "agency_fare_url")]
+    fare_url: Option<String>,
     #[serde(rename = "agency_email")]
     email: Option<String>,
 }
@@ -66,7 +75,8 @@ impl<'a> From<&'a objects::Network> for Agency {
                 .unwrap_or_else(|| chrono_tz::Europe::Paris),
             lang: obj.lang.clone(),
             phone: obj.phone.clone(),
-            email: None,
+            fare_url: obj.fare_url.clone(),
+            email: obj.email.clone(),
         }
     }
 }
@@ -179,6 +189,23 @@ struct Trip {
     wheelchair_accessible: Availability,
     #[serde(deserialize_with = "de_with_empty_default", default)]
     bikes_allowed: Availability,
+    // extension columns of the NTFS trip properties, read but not written
+    #[serde(deserialize_with = "de_with_empty_default", default, skip_serializing)]
+    air_conditioned: Availability,
+    #[serde(deserialize_with = "de_with_empty_default", default, skip_serializing)]
+    visual_announcement: Availability,
+    #[serde(deserialize_with = "de_with_empty_default", default, skip_serializing)]
+    audible_announcement: Availability,
+    #[serde(deserialize_with = "de_with_empty_default", default, skip_serializing)]
+    appropriate_escort: Availability,
+    #[serde(deserialize_with = "de_with_empty_default", default, skip_serializing)]
+    appropriate_signage: Availability,
+    #[serde(deserialize_with = "de_with_empty_default", default, skip_serializing)]
+    school_vehicle_type: objects::TransportType,
+    // extension column of the operator of the trip, see
+    // `Configuration::trip_operators`
+    #[serde(default, deserialize_with = "de_option_empty_string", skip_serializing)]
+    operator_id: Option<String>,
 }
 
 fn default_true_bool() -> bool {
@@ -190,7 +217,7 @@ struct StopTime {
     trip_id: String,
     arrival_time: Option<Time>,
     departure_time: Option<Time>,
-    #[serde(deserialize_with = "de_without_slashes")]
+    #[serde(deserialize_with = "de_without_slashes", default)]
     stop_id: String,
     stop_sequence: u32,
     #[serde(deserialize_with = "de_with_empty_default", default)]
@@ -205,6 +232,23 @@ struct StopTime {
         default = "default_true_bool"
     )]
     timepoint: bool,
+    // GTFS-Flex zone (a location of `locations.geojson` or a group of stops
+    // of `stop_areas.txt`) and time window of a zonal on-demand stop time
+    #[serde(default, skip_serializing)]
+    location_id: Option<String>,
+    #[serde(default, skip_serializing)]
+    start_pickup_drop_off_window: Option<Time>,
+    #[serde(default, skip_serializing)]
+    end_pickup_drop_off_window: Option<Time>,
+    #[serde(deserialize_with = "de_with_invalid_option", default)]
+    continuous_pickup: Option<objects::ContinuousStoppingType>,
+    #[serde(deserialize_with = "de_with_invalid_option", default)]
+    continuous_drop_off: Option<objects::ContinuousStoppingType>,
+    // GTFS-Flex booking rules, see `booking_rules.txt`
+    #[serde(default, skip_serializing)]
+    pickup_booking_rule_id: Option<String>,
+    #[serde(default, skip_serializing)]
+    drop_off_booking_rule_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Derivative, PartialEq, Clone)]
@@ -229,6 +273,7 @@ struct Transfer {
     to_stop_id: String,
     #[serde(deserialize_with = "de_with_empty_default")]
     transfer_type: TransferType,
+    #[serde(default, deserialize_with = "de_option_negative_as_none")]
     min_transfer_time: Option<u32>,
 }
 
@@ -255,6 +300,146 @@ struct Shape {
     sequence: u32,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct FeedInfo {
+    feed_lang: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Translation {
+    table_name: String,
+    field_name: String,
+    language: String,
+    translation: String,
+    #[serde(default)]
+    record_id: Option<String>,
+    #[serde(default)]
+    field_value: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Attribution {
+    attribution_id: Option<String>,
+    agency_id: Option<String>,
+    route_id: Option<String>,
+    trip_id: Option<String>,
+    organization_name: String,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    is_producer: u8,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    is_operator: u8,
+    #[serde(deserialize_with = "de_with_empty_default", default)]
+    is_authority: u8,
+    attribution_url: Option<String>,
+    attribution_email: Option<String>,
+    attribution_phone: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BookingRule {
+    booking_rule_id: String,
+    prior_notice_duration_min: Option<u32>,
+    prior_notice_last_day: Option<u32>,
+    prior_notice_last_time: Option<Time>,
+    message: Option<String>,
+    phone_number: Option<String>,
+    info_url: Option<String>,
+    booking_url: Option<String>,
+}
+
+/// Physical and commercial modes of a GTFS `route_type` value, see
+/// [read_route_type_mappings](fn.read_route_type_mappings.html)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RouteTypeMapping {
+    /// Identifier of the physical mode (e.g. `Bus`)
+    pub physical_mode: String,
+    /// Identifier of the commercial mode (e.g. `OnDemandBus`)
+    pub commercial_mode: String,
+    /// Name of the commercial mode, its identifier if absent
+    #[serde(default)]
+    pub commercial_mode_name: Option<String>,
+}
+
+/// Modes of GTFS `route_type` values
+pub type RouteTypeMappings = BTreeMap<u16, RouteTypeMapping>;
+
+/// Reads [RouteTypeMappings](type.RouteTypeMappings.html) from a JSON file.
+///
+/// # Example
+///
+/// ```json
+/// {
+///     "715": { "physical_mode": "Bus", "commercial_mode": "OnDemandBus", "commercial_mode_name": "On demand bus" },
+///     "1500": { "physical_mode": "Taxi", "commercial_mode": "Taxi" }
+/// }
+/// ```
+pub fn read_route_type_mappings<P: AsRef<Path>>(path: P) -> Result<RouteTypeMappings> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let mappings =
+        serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+    Ok(mappings)
+}
+
+/// Parent company of the companies, by company identifier (e.g. the holding
+/// of an operator, see [CompanyParent](../objects/struct.CompanyParent.html)).
+pub type CompanyParents = BTreeMap<String, String>;
+
+/// Fare URL and contacts of a network, set by its identifier in the
+/// [NetworkContacts](type.NetworkContacts.html) of the configuration
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct NetworkContact {
+    /// URL of the fares of the network
+    pub fare_url: Option<String>,
+    /// Email address of the customer service of the network
+    pub email: Option<String>,
+    /// Phone number of the customer service of the network
+    pub phone: Option<String>,
+}
+
+/// Fare URL and contacts of the networks, by network identifier, replacing
+/// the values of `agency.txt` when given.
+pub type NetworkContacts = BTreeMap<String, NetworkContact>;
+
+/// Reads [NetworkContacts](type.NetworkContacts.html) from a JSON file.
+///
+/// # Example
+///
+/// ```json
+/// {
+///     "agency:1": {
+///         "fare_url": "https://example.com/fares",
+///         "email": "contact@example.com",
+///         "phone": "+33 1 23 45 67 89"
+///     }
+/// }
+/// ```
+pub fn read_network_contacts<P: AsRef<Path>>(path: P) -> Result<NetworkContacts> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let network_contacts =
+        serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+    Ok(network_contacts)
+}
+
+/// Reads [CompanyParents](type.CompanyParents.html) from a JSON file.
+///
+/// # Example
+///
+/// ```json
+/// {
+///     "operator:1": "holding",
+///     "operator:2": "holding"
+/// }
+/// ```
+pub fn read_company_parents<P: AsRef<Path>>(path: P) -> Result<CompanyParents> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let company_parents =
+        serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+    Ok(company_parents)
+}
+
 ///parameters consolidation
 pub struct Configuration {
     /// The Contributor providing the Dataset
@@ -269,53 +454,140 @@ pub struct Configuration {
     pub on_demand_transport: bool,
     /// on demand transport comment template
     pub on_demand_transport_comment: Option<String>,
+    /// Modes of the `route_type` values, replacing the default rules for
+    /// the values listed (e.g. the extended values like `715`)
+    pub route_type_mappings: RouteTypeMappings,
+    /// Keep the frequencies as NTFS frequencies instead of generating a
+    /// vehicle journey for each departure
+    pub keep_frequencies: bool,
+    /// Coordinate system of the coordinates of `stops.txt` and `shapes.txt`
+    /// when they are not in WGS84, reprojected to WGS84 while reading
+    /// (requires the `proj` feature)
+    pub crs: Option<CoordinateSystem>,
+    /// Parent companies of the companies, replacing the ones deduced from
+    /// `attributions.txt`
+    pub company_parents: CompanyParents,
+    /// Fare URL and contacts of the networks, replacing the ones of
+    /// `agency.txt`
+    pub network_contacts: NetworkContacts,
+    /// How the records of the files which can't be read are handled; in
+    /// [lenient](../read_utils/enum.ReadMode.html#variant.Lenient) mode,
+    /// they are skipped with a warning in the logs, and so are the corrupted
+    /// files of a zip file (handled as missing files, and reported by
+    /// [read_from_zip_with_report](fn.read_from_zip_with_report.html))
+    pub read_mode: ReadMode,
+    /// Create a company for each operator of the `trips.operator_id`
+    /// extension column, as the company of the vehicle journeys of the trips
+    /// instead of the agency of their route (which becomes the parent
+    /// company of the operator), see the `trip_operators` option of
+    /// [read_config](../read_utils/fn.read_config.html)
+    pub trip_operators: bool,
 }
 
-fn read<H>(file_handler: &mut H, configuration: Configuration) -> Result<Model>
+// The stops and shapes, read in `crs`, are reprojected to WGS84; the flex
+// zones of `locations.geojson` are always in WGS84 so they are read after
+#[cfg(feature = "proj")]
+fn reproject_to_wgs84(collections: &mut Collections, crs: CoordinateSystem) -> Result<()> {
+    collections.set_coordinate_system(crs);
+    collections.reproject(CoordinateSystem::Wgs84)?;
+    collections.feed_infos.remove(crate::crs::FEED_CRS);
+    Ok(())
+}
+
+#[cfg(not(feature = "proj"))]
+fn reproject_to_wgs84(_: &mut Collections, crs: CoordinateSystem) -> Result<()> {
+    if crs == CoordinateSystem::Wgs84 {
+        Ok(())
+    } else {
+        failure::bail!(
+            "reading a GTFS in {} requires transit_model to be built with the proj feature",
+            crs
+        )
+    }
+}
+
+// Reads the objects of the GTFS which the stop times refer to, the objects
+// depending on the stop times are read afterwards
+fn read_stop_times_references<H>(
+    file_handler: &mut H,
+    configuration: &Configuration,
+) -> Result<Collections>
 where
     for<'a> &'a mut H: read_utils::FileHandler,
 {
     let mut collections = Collections::default();
     let mut equipments = EquipmentList::default();
-
-    let Configuration {
-        contributor,
-        mut dataset,
-        feed_infos,
-        prefix_conf,
-        on_demand_transport,
-        on_demand_transport_comment,
-    } = configuration;
+    let mut dataset = configuration.dataset.clone();
 
     manage_calendars(file_handler, &mut collections)?;
     validity_period::compute_dataset_validity_period(&mut dataset, &collections.calendars)?;
 
-    collections.contributors = CollectionWithId::from(contributor);
+    collections.contributors = CollectionWithId::from(configuration.contributor.clone());
     collections.datasets = CollectionWithId::from(dataset);
-    collections.feed_infos = feed_infos;
+    collections.feed_infos = configuration.feed_infos.clone();
+    read::manage_feed_lang(file_handler, &mut collections)?;
 
     let (networks, companies) = read::read_agency(file_handler)?;
     collections.networks = networks;
     collections.companies = companies;
     let (stop_areas, stop_points, stop_locations) =
         read::read_stops(file_handler, &mut collections.comments, &mut equipments)?;
-    collections.transfers = read::read_transfers(file_handler, &stop_points, &stop_areas)?;
     collections.stop_areas = stop_areas;
     collections.stop_points = stop_points;
     collections.stop_locations = stop_locations;
-
     read::manage_shapes(&mut collections, file_handler)?;
+    if let Some(crs) = configuration.crs {
+        reproject_to_wgs84(&mut collections, crs)?;
+    }
+    collections.transfers = read::read_transfers(
+        file_handler,
+        &collections.stop_points,
+        &collections.stop_areas,
+    )?;
+    read::manage_flex_zones(&mut collections, file_handler)?;
 
-    read::read_routes(file_handler, &mut collections)?;
+    let trip_operators = read::read_routes(
+        file_handler,
+        &mut collections,
+        &configuration.route_type_mappings,
+    )?;
+    if configuration.trip_operators {
+        read::manage_trip_operators(&mut collections, trip_operators)?;
+    }
     collections.equipments = CollectionWithId::new(equipments.into_equipments())?;
+    Ok(collections)
+}
+
+fn read<H>(file_handler: &mut H, configuration: Configuration) -> Result<Model>
+where
+    for<'a> &'a mut H: read_utils::FileHandler,
+{
+    let mut collections = read_stop_times_references(file_handler, &configuration)?;
+
+    let Configuration {
+        prefix_conf,
+        on_demand_transport,
+        on_demand_transport_comment,
+        keep_frequencies,
+        company_parents,
+        network_contacts,
+        ..
+    } = configuration;
+
+    read::manage_network_contacts(&mut collections, network_contacts);
     read::manage_stop_times(
         &mut collections,
         file_handler,
         on_demand_transport,
         on_demand_transport_comment,
     )?;
-    read::manage_frequencies(&mut collections, file_handler)?;
+    read::manage_booking_rules(&mut collections, file_handler)?;
+    read::manage_frequencies(&mut collections, file_handler, keep_frequencies)?;
+    read::normalize_timezones(&mut collections)?;
     read::manage_pathways(&mut collections, file_handler)?;
+    read::manage_translations(&mut collections, file_handler)?;
+    read::manage_attributions(&mut collections, file_handler)?;
+    read::manage_company_parents(&mut collections, company_parents);
     collections.levels = read_utils::read_opt_collection(file_handler, "levels.txt")?;
 
     //add prefixes
@@ -338,7 +610,8 @@ where
 /// identifiers, allowing to namespace the dataset. By default, no
 /// prefix will be added to the identifiers.
 pub fn read_from_path<P: AsRef<Path>>(p: P, configuration: Configuration) -> Result<Model> {
-    let mut file_handle = read_utils::PathFileHandler::new(p.as_ref().to_path_buf());
+    let mut file_handle = read_utils::PathFileHandler::new(p.as_ref().to_path_buf())
+        .with_read_mode(configuration.read_mode);
     read(&mut file_handle, configuration)
 }
 
@@ -352,9 +625,125 @@ pub fn read_from_path<P: AsRef<Path>>(p: P, configuration: Configuration) -> Res
 /// The `prefix` argument is a string that will be prepended to every
 /// identifiers, allowing to namespace the dataset. By default, no
 /// prefix will be added to the identifiers.
+///
+/// It needs the `zip` feature.
+#[cfg(feature = "zip")]
 pub fn read_from_zip<P: AsRef<Path>>(path: P, configuration: Configuration) -> Result<Model> {
-    let mut file_handler = read_utils::ZipHandler::new(path)?;
-    read(&mut file_handler, configuration)
+    read_from_zip_with_report(path, configuration, &mut Report::default())
+}
+
+/// Imports a `Model` from a zip file containing the
+/// [GTFS](https://gtfs.org/reference/static), as
+/// [read_from_zip](fn.read_from_zip.html). The corrupted files of the zip
+/// file skipped in lenient `read_mode` are added to the `report`.
+///
+/// It needs the `zip` feature.
+#[cfg(feature = "zip")]
+pub fn read_from_zip_with_report<P: AsRef<Path>>(
+    path: P,
+    configuration: Configuration,
+    report: &mut Report,
+) -> Result<Model> {
+    let mut file_handler =
+        read_utils::ZipHandler::new(path)?.with_read_mode(configuration.read_mode);
+    let model = read(&mut file_handler, configuration);
+    // the files are checked when they are read
+    for (file_name, error) in file_handler.corrupted_files() {
+        report.add_error(
+            format!("{} is corrupted and ignored: {}", file_name, error),
+            ReportType::UnreadableFile,
+        );
+    }
+    model
+}
+
+/// Describes the columns expected by [read_from_path](fn.read_from_path.html)
+/// in the GTFS file `file_name` (e.g. `stops.txt`), or `None` if the file is
+/// not read.
+pub fn explain(file_name: &str) -> Option<FileSchema> {
+    let columns = match file_name {
+        "agency.txt" => schema::columns::<Agency>(),
+        "stops.txt" => schema::columns::<Stop>(),
+        "routes.txt" => schema::columns::<Route>(),
+        "trips.txt" => schema::columns::<Trip>(),
+        "stop_times.txt" => schema::columns::<StopTime>(),
+        "calendar.txt" => schema::columns::<calendars::Calendar>(),
+        "calendar_dates.txt" => schema::columns::<calendars::CalendarDate>(),
+        "transfers.txt" => schema::columns::<Transfer>(),
+        "shapes.txt" => schema::columns::<Shape>(),
+        "frequencies.txt" => schema::columns::<read::Frequency>(),
+        "pathways.txt" => schema::columns::<objects::Pathway>(),
+        "levels.txt" => schema::columns::<objects::Level>(),
+        "feed_info.txt" => schema::columns::<FeedInfo>(),
+        "translations.txt" => schema::columns::<Translation>(),
+        "attributions.txt" => schema::columns::<Attribution>(),
+        "booking_rules.txt" => schema::columns::<BookingRule>(),
+        _ => return None,
+    };
+    Some(FileSchema {
+        file_name: file_name.to_string(),
+        columns,
+    })
+}
+
+/// Converts the [GTFS](https://gtfs.org/reference/static) in the `gtfs_path`
+/// directory into the NTFS of the `ntfs_path` directory, as
+/// [read_from_path](fn.read_from_path.html) and
+/// [ntfs::write](../ntfs/fn.write.html) do, without holding all the stop
+/// times in memory.
+///
+/// The other files of the GTFS are read first, then the stop times are read,
+/// converted and written by batches of whole trips, so the GTFS
+/// `stop_times.txt` must be grouped by `trip_id`. The trips of a block are
+/// converted in the same batch, for their stay-ins. Only the first and last
+/// stop times of the trips are then kept to build the `Model` whose other
+/// files are written.
+pub fn stream_to_ntfs<P: AsRef<Path>, Q: AsRef<Path>>(
+    gtfs_path: P,
+    ntfs_path: Q,
+    mut configuration: Configuration,
+    current_datetime: DateTime<FixedOffset>,
+) -> Result<()> {
+    let mut file_handler = read_utils::PathFileHandler::new(gtfs_path.as_ref().to_path_buf())
+        .with_read_mode(configuration.read_mode);
+    let mut collections = read_stop_times_references(&mut file_handler, &configuration)?;
+    let network_contacts = std::mem::replace(&mut configuration.network_contacts, BTreeMap::new());
+    read::manage_network_contacts(&mut collections, network_contacts);
+
+    let ntfs_path = ntfs_path.as_ref();
+    let path = ntfs_path.join("stop_times.txt");
+    let file = File::create(&path).with_context(|_| format!("Error writing {:?}", path))?;
+    let stop_point_idxs = read::stream_stop_times(
+        &mut collections,
+        &mut file_handler,
+        BufWriter::new(file),
+        &configuration,
+        read::STOP_TIMES_BATCH_SIZE,
+    )?;
+    read::manage_booking_rules(&mut collections, &mut file_handler)?;
+    read::manage_pathways(&mut collections, &mut file_handler)?;
+    read::manage_translations(&mut collections, &mut file_handler)?;
+    read::manage_attributions(&mut collections, &mut file_handler)?;
+    read::manage_company_parents(&mut collections, configuration.company_parents);
+    collections.levels = read_utils::read_opt_collection(&mut file_handler, "levels.txt")?;
+
+    if let Some(prefix_conf) = &configuration.prefix_conf {
+        collections.prefix(prefix_conf);
+    }
+    collections.calendar_deduplication();
+    // the prefixes keep the indexes of the stop points
+    let stop_points_used = stop_point_idxs
+        .into_iter()
+        .map(|idx| collections.stop_points[idx].id.clone())
+        .collect();
+    let model = Model::new_keeping_stop_points(collections, stop_points_used)?;
+    ntfs::write_files(
+        &model,
+        ntfs_path,
+        current_datetime,
+        &ntfs::WriteOptions::default(),
+        false,
+    )
 }
 
 #[derive(PartialOrd, Ord, Debug, Clone, Eq, PartialEq, Hash)]
@@ -389,7 +778,7 @@ struct Route {
     long_name: String,
     #[serde(rename = "route_desc")]
     desc: Option<String>,
-    route_type: RouteType,
+    route_type: u16,
     #[serde(rename = "route_url")]
     url: Option<String>,
     #[serde(
@@ -435,7 +824,7 @@ pub fn write<P: AsRef<Path>>(model: Model, path: P) -> Result<()> {
     info!("Writing GTFS to {:?}", path);
 
     write::write_transfers(path, &model.transfers)?;
-    write::write_agencies(path, &model.networks)?;
+    write::write_agencies(path, &model.networks, model.feed_language())?;
     write_calendar_dates(path, &model.calendars)?;
     write::write_stops(
         path,
@@ -453,10 +842,12 @@ pub fn write<P: AsRef<Path>>(model: Model, path: P) -> Result<()> {
         &model.vehicle_journeys,
         &model.stop_points,
         &model.stop_time_headsigns,
+        &model.stop_time_continuous_stoppings,
     )?;
     write::write_shapes(path, &model.geometries)?;
     write_collection_with_id(path, "pathways.txt", &model.pathways)?;
     write_collection_with_id(path, "levels.txt", &model.levels)?;
+    write::write_translations(path, &model)?;
 
     Ok(())
 }
@@ -13,26 +13,43 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>
 
 use super::{
-    Agency, DirectionType, Route, RouteType, Shape, Stop, StopLocationType, StopTime, Transfer,
-    TransferType, Trip,
+    Agency, Attribution, BookingRule, CompanyParents, Configuration, DirectionType, FeedInfo,
+    NetworkContacts, Route, RouteType, RouteTypeMappings, Shape, Stop, StopLocationType, StopTime,
+    Transfer, TransferType, Translation, Trip,
 };
 use crate::{
+    error::TransitModelError,
+    languages::{self, FEED_LANG},
     model::Collections,
+    ntfs,
     objects::{
-        self, Availability, CommentLinksT, Coord, KeysValues, Pathway, StopLocation, StopPoint,
-        StopTime as NtfsStopTime, StopTimePrecision, StopType, Time, TransportType, VehicleJourney,
+        self, Availability, CommentLinksT, ContinuousStopping, Coord, Date, KeysValues, ObjectType,
+        Pathway, StopLocation, StopPoint, StopTime as NtfsStopTime, StopTimePrecision, StopType,
+        Time, TransportType, VehicleJourney,
     },
-    read_utils::{read_collection, read_objects, FileHandler},
+    read_utils::{
+        deserialize_in_parallel, for_each_record, lenient_report, read_collection, read_objects,
+        read_objects_in_parallel, read_opt_objects, skip_dangling_reference, warn_skipped_records,
+        FileHandler,
+    },
+    report::Report,
+    transfers::NOT_POSSIBLE_TRANSFER_TIME,
     utils::*,
+    zones::{self, make_zone, Zone},
     Result,
 };
+use chrono::{offset::LocalResult, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
 use derivative::Derivative;
 use failure::{bail, format_err, Error, ResultExt};
-use geo::{LineString, Point};
+use geo::{
+    algorithm::{centroid::Centroid, convexhull::ConvexHull},
+    LineString, Point,
+};
 use log::{info, warn, Level as LogLevel};
 use serde::Deserialize;
 use skip_error::skip_error_and_log;
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
 use typed_index_collection::{impl_id, Collection, CollectionWithId, Idx};
 
@@ -67,6 +84,8 @@ impl From<Agency> for objects::Network {
             phone: agency.phone,
             address: None,
             sort_order: None,
+            fare_url: agency.fare_url,
+            email: agency.email,
         }
     }
 }
@@ -212,41 +231,28 @@ impl TryFrom<Stop> for objects::StopLocation {
 }
 
 impl RouteType {
-    fn to_gtfs_value(&self) -> String {
+    pub(in crate::gtfs) fn to_gtfs_value(&self) -> u16 {
         match *self {
-            RouteType::Tramway => "0".to_string(),
-            RouteType::Metro => "1".to_string(),
-            RouteType::Train => "2".to_string(),
+            RouteType::Tramway => 0,
+            RouteType::Metro => 1,
+            RouteType::Train => 2,
             RouteType::Bus
             | RouteType::UnknownMode
             | RouteType::Coach
             | RouteType::Air
-            | RouteType::Taxi => "3".to_string(),
-            RouteType::Ferry => "4".to_string(),
-            RouteType::CableCar => "5".to_string(),
-            RouteType::SuspendedCableCar => "6".to_string(),
-            RouteType::Funicular => "7".to_string(),
+            | RouteType::Taxi => 3,
+            RouteType::Ferry => 4,
+            RouteType::CableCar => 5,
+            RouteType::SuspendedCableCar => 6,
+            RouteType::Funicular => 7,
         }
     }
 }
 
-impl ::serde::Serialize for RouteType {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: ::serde::Serializer,
-    {
-        serializer.serialize_str(&self.to_gtfs_value())
-    }
-}
-
-impl<'de> ::serde::Deserialize<'de> for RouteType {
-    fn deserialize<D>(deserializer: D) -> Result<RouteType, D::Error>
-    where
-        D: ::serde::Deserializer<'de>,
-    {
-        let i = u16::deserialize(deserializer)?;
-        let hundreds = i / 100;
-        Ok(match (i, hundreds) {
+impl From<u16> for RouteType {
+    fn from(route_type: u16) -> RouteType {
+        let hundreds = route_type / 100;
+        match (route_type, hundreds) {
             (0, _) | (_, 9) => RouteType::Tramway,
             (1, _) | (_, 4) | (_, 5) | (_, 6) => RouteType::Metro,
             (2, _) | (_, 1) | (_, 3) => RouteType::Train,
@@ -259,7 +265,7 @@ impl<'de> ::serde::Deserialize<'de> for RouteType {
             (_, 11) => RouteType::Air,
             (_, 15) => RouteType::Taxi,
             _ => RouteType::UnknownMode,
-        })
+        }
     }
 }
 
@@ -285,19 +291,67 @@ impl Route {
     }
 }
 
+// The trip properties, ordered as the columns of `trips.txt`
+type TripPropertyKey = (
+    Availability,
+    Availability,
+    Availability,
+    Availability,
+    Availability,
+    Availability,
+    Availability,
+    TransportType,
+);
+
 impl Trip {
+    fn trip_property_key(&self) -> TripPropertyKey {
+        (
+            self.wheelchair_accessible,
+            self.bikes_allowed,
+            self.air_conditioned,
+            self.visual_announcement,
+            self.audible_announcement,
+            self.appropriate_escort,
+            self.appropriate_signage,
+            self.school_vehicle_type.clone(),
+        )
+    }
+
+    // The NTFS trip property of the standard columns and of the extension
+    // columns of the trip, without identifier
+    fn trip_property(&self) -> objects::TripProperty {
+        objects::TripProperty {
+            id: String::new(),
+            wheelchair_accessible: self.wheelchair_accessible,
+            bike_accepted: self.bikes_allowed,
+            air_conditioned: self.air_conditioned,
+            visual_announcement: self.visual_announcement,
+            audible_announcement: self.audible_announcement,
+            appropriate_escort: self.appropriate_escort,
+            appropriate_signage: self.appropriate_signage,
+            school_vehicle_type: self.school_vehicle_type.clone(),
+        }
+    }
+
     fn to_ntfs_vehicle_journey(
         &self,
         routes: &CollectionWithId<Route>,
         dataset: &objects::Dataset,
         trip_property_id: &Option<String>,
         networks: &CollectionWithId<objects::Network>,
+        route_type_mappings: &RouteTypeMappings,
     ) -> Result<objects::VehicleJourney> {
         let route = match routes.get(&self.route_id) {
             Some(route) => route,
-            None => bail!("Coudn't find route {} for trip {}", self.route_id, self.id),
+            None => {
+                return Err(TransitModelError::dangling(
+                    "trip.route_id",
+                    &self.route_id,
+                    format!("Coudn't find route {} for trip {}", self.route_id, self.id),
+                ))
+            }
         };
-        let physical_mode = get_physical_mode(&route.route_type);
+        let (_, physical_mode) = get_route_modes(route, route_type_mappings);
         let mut codes = KeysValues::default();
         codes.insert(("source".to_string(), self.id.clone()));
 
@@ -311,13 +365,14 @@ impl Trip {
             dataset_id: dataset.id.clone(),
             service_id: self.service_id.clone(),
             headsign: self.short_name.clone().or_else(|| self.headsign.clone()),
-            short_name: None,
+            short_name: self.short_name.clone(),
             block_id: self.block_id.clone(),
             company_id: get_agency_id(route, networks)?,
             trip_property_id: trip_property_id.clone(),
             geometry_id: self.shape_id.clone(),
             stop_times: vec![],
             journey_pattern_id: None,
+            length: None,
         })
     }
 }
@@ -339,14 +394,11 @@ where
         Some(reader) => {
             info!("Reading {}", file);
             let mut rdr = csv::Reader::from_reader(reader);
-            let mut shapes = vec![];
-            for shape in rdr.deserialize() {
-                let shape: Shape = skip_error_and_log!(
-                    shape.with_context(|_| format!("Error reading {:?}", path)),
-                    LogLevel::Warn
-                );
-                shapes.push(shape);
-            }
+            // the invalid shapes are skipped
+            let mut skipped = Report::default();
+            let mut shapes =
+                deserialize_in_parallel::<_, Shape>(&mut rdr, &path, Some(&mut skipped))?;
+            warn_skipped_records(&skipped);
 
             shapes.sort_unstable_by_key(|s| s.sequence);
             let mut map: HashMap<String, Vec<Point<f64>>> = HashMap::new();
@@ -384,27 +436,68 @@ where
     for<'a> &'a mut H: FileHandler,
 {
     let file_name = "stop_times.txt";
+    let read_mode = file_handler.read_mode();
     let (reader, path) = file_handler.get_file(file_name)?;
     info!("Reading stop_times.txt");
 
     let mut rdr = csv::ReaderBuilder::new()
         .trim(csv::Trim::All)
         .from_reader(reader);
+    // in lenient mode, the stop times which can't be read or reference an
+    // unknown trip or stop are skipped
+    let mut skipped = Report::default();
+    let mut report = lenient_report(read_mode, &mut skipped);
+    let stop_times = deserialize_in_parallel::<_, StopTime>(
+        &mut rdr,
+        &path,
+        report.as_mut().map(|report| &mut **report),
+    )?;
+    add_stop_times(
+        collections,
+        stop_times,
+        &mut report,
+        on_demand_transport,
+        on_demand_transport_comment.as_deref(),
+    )?;
+    warn_skipped_records(&skipped);
+    Ok(())
+}
+
+// Adds the `stop_times` to their vehicle journeys, the ones referencing an
+// unknown trip or stop are skipped in lenient mode (when there's a `report`)
+fn add_stop_times(
+    collections: &mut Collections,
+    stop_times: Vec<StopTime>,
+    report: &mut Option<&mut Report>,
+    on_demand_transport: bool,
+    on_demand_transport_comment: Option<&str>,
+) -> Result<()> {
+    let file_name = "stop_times.txt";
     let mut headsigns = HashMap::new();
+    let mut continuous_stoppings = HashMap::new();
+    let mut booking_rule_links = BTreeSet::new();
     let mut tmp_vjs = BTreeMap::new();
-    for stop_time in rdr.deserialize() {
-        let mut stop_time: StopTime =
-            stop_time.with_context(|_| format!("Error reading {:?}", path))?;
-        let vj_idx = collections
-            .vehicle_journeys
-            .get_idx(&stop_time.trip_id)
-            .ok_or_else(|| {
-                format_err!(
+    for mut stop_time in stop_times {
+        manage_flex_stop_time(&mut stop_time);
+        let vj_idx = match collections.vehicle_journeys.get_idx(&stop_time.trip_id) {
+            Some(vj_idx) => vj_idx,
+            None => {
+                let message = format!(
                     "Problem reading {:?}: trip_id={:?} not found",
-                    file_name,
-                    stop_time.trip_id
-                )
-            })?;
+                    file_name, stop_time.trip_id
+                );
+                skip_dangling_reference(message, "stop_time.trip_id", &stop_time.trip_id, report)?;
+                continue;
+            }
+        };
+        if !collections.stop_points.contains_id(&stop_time.stop_id) {
+            let message = format!(
+                "Problem reading {:?}: stop_id={:?} not found",
+                file_name, stop_time.stop_id
+            );
+            skip_dangling_reference(message, "stop_time.stop_id", &stop_time.stop_id, report)?;
+            continue;
+        }
 
         // consume the stop headsign
         let headsign = std::mem::replace(&mut stop_time.stop_headsign, None);
@@ -414,6 +507,23 @@ where
                 headsign,
             );
         }
+        if let Some(continuous_stopping) = ContinuousStopping::from_values(
+            stop_time.continuous_pickup,
+            stop_time.continuous_drop_off,
+        ) {
+            continuous_stoppings.insert(
+                (stop_time.trip_id.clone(), stop_time.stop_sequence),
+                continuous_stopping,
+            );
+        }
+        let booking_rule_ids = stop_time
+            .pickup_booking_rule_id
+            .take()
+            .into_iter()
+            .chain(stop_time.drop_off_booking_rule_id.take());
+        for booking_rule_id in booking_rule_ids {
+            booking_rule_links.insert((stop_time.trip_id.clone(), booking_rule_id));
+        }
 
         tmp_vjs
             .entry(vj_idx)
@@ -421,6 +531,19 @@ where
             .push(stop_time);
     }
     collections.stop_time_headsigns = headsigns;
+    collections.stop_time_continuous_stoppings = continuous_stoppings;
+    collections.booking_rule_links = Collection::new(
+        booking_rule_links
+            .into_iter()
+            .map(
+                |(vehicle_journey_id, booking_rule_id)| objects::BookingRuleLink {
+                    object_type: ObjectType::VehicleJourney,
+                    object_id: vehicle_journey_id,
+                    booking_rule_id,
+                },
+            )
+            .collect(),
+    );
 
     for (vj_idx, mut stop_times) in tmp_vjs {
         stop_times.sort_unstable_by_key(|st| st.stop_sequence);
@@ -438,20 +561,22 @@ where
                 .stop_points
                 .get_idx(&stop_time.stop_id)
                 .ok_or_else(|| {
-                    format_err!(
-                        "Problem reading {:?}: stop_id={:?} not found",
-                        file_name,
-                        stop_time.stop_id
+                    TransitModelError::dangling(
+                        "stop_time.stop_id",
+                        &stop_time.stop_id,
+                        format!(
+                            "Problem reading {:?}: stop_id={:?} not found",
+                            file_name, stop_time.stop_id
+                        ),
                     )
                 })?;
 
-            let precision = match (on_demand_transport, st_values.datetime_estimated) {
-                (_, false) => Some(StopTimePrecision::Exact),
-                (false, true) => Some(StopTimePrecision::Approximate),
-                (true, true) => Some(StopTimePrecision::Estimated),
-            };
+            let precision = Some(stop_time_precision(
+                on_demand_transport,
+                st_values.datetime_estimated,
+            ));
 
-            if let Some(message) = on_demand_transport_comment.as_ref() {
+            if let Some(message) = on_demand_transport_comment {
                 if stop_time.pickup_type == 2 || stop_time.drop_off_type == 2 {
                     if let Some(company_idx) = company_idx {
                         manage_odt_comment_from_stop_time(
@@ -486,6 +611,315 @@ where
     Ok(())
 }
 
+// A zonal stop time of GTFS-Flex is served on demand in its zone during its
+// time window: it's read as an estimated on-demand stop time at the zone
+fn manage_flex_stop_time(stop_time: &mut StopTime) {
+    if stop_time.stop_id.is_empty() {
+        if let Some(location_id) = stop_time.location_id.take() {
+            stop_time.stop_id = location_id;
+        }
+    }
+    let (start, end) = (
+        stop_time.start_pickup_drop_off_window,
+        stop_time.end_pickup_drop_off_window,
+    );
+    if start.is_none() && end.is_none() {
+        return;
+    }
+    stop_time.arrival_time = stop_time.arrival_time.or(start).or(end);
+    stop_time.departure_time = stop_time.departure_time.or(end).or(start);
+    stop_time.timepoint = false;
+    if stop_time.pickup_type == 0 {
+        stop_time.pickup_type = 2;
+    }
+    if stop_time.drop_off_type == 0 {
+        stop_time.drop_off_type = 2;
+    }
+}
+
+fn stop_time_precision(on_demand_transport: bool, datetime_estimated: bool) -> StopTimePrecision {
+    match (on_demand_transport, datetime_estimated) {
+        (_, false) => StopTimePrecision::Exact,
+        (false, true) => StopTimePrecision::Approximate,
+        (true, true) => StopTimePrecision::Estimated,
+    }
+}
+
+// The number of stop times from which a batch of trips is converted
+pub(in crate::gtfs) const STOP_TIMES_BATCH_SIZE: usize = 100_000;
+
+// The stop times of `stop_times.txt` grouped in batches of whole trips. The
+// trips of a block are held until all of them are read, so a block is never
+// split across batches and its stay-ins are the ones of `read`.
+struct StopTimeBatches<'a> {
+    vehicle_journeys: &'a CollectionWithId<VehicleJourney>,
+    batch_size: usize,
+    // the number of trips of each block whose stop times are not read yet
+    unread_block_trips: HashMap<&'a str, usize>,
+    pending_blocks: BTreeMap<&'a str, Vec<StopTime>>,
+    batch: Vec<StopTime>,
+}
+
+impl<'a> StopTimeBatches<'a> {
+    fn new(vehicle_journeys: &'a CollectionWithId<VehicleJourney>, batch_size: usize) -> Self {
+        let mut unread_block_trips = HashMap::new();
+        for block_id in vehicle_journeys
+            .values()
+            .filter_map(|vj| vj.block_id.as_deref())
+        {
+            *unread_block_trips.entry(block_id).or_insert(0) += 1;
+        }
+        StopTimeBatches {
+            vehicle_journeys,
+            batch_size,
+            unread_block_trips,
+            pending_blocks: BTreeMap::new(),
+            batch: vec![],
+        }
+    }
+
+    // Adds the stop times of a trip, returns the batch once it's full
+    fn add_trip(&mut self, stop_times: Vec<StopTime>) -> Option<Vec<StopTime>> {
+        let vehicle_journeys = self.vehicle_journeys;
+        let block_id = stop_times
+            .first()
+            .and_then(|stop_time| vehicle_journeys.get(&stop_time.trip_id))
+            .and_then(|vj| vj.block_id.as_deref());
+        match block_id {
+            Some(block_id) => {
+                self.pending_blocks
+                    .entry(block_id)
+                    .or_insert_with(Vec::new)
+                    .extend(stop_times);
+                if let Some(unread_trips) = self.unread_block_trips.get_mut(block_id) {
+                    *unread_trips -= 1;
+                    if *unread_trips == 0 {
+                        if let Some(block_stop_times) = self.pending_blocks.remove(block_id) {
+                            self.batch.extend(block_stop_times);
+                        }
+                    }
+                }
+            }
+            None => self.batch.extend(stop_times),
+        }
+        if self.batch.len() >= self.batch_size {
+            Some(std::mem::replace(&mut self.batch, vec![]))
+        } else {
+            None
+        }
+    }
+
+    // The stop times left, with the ones of the blocks whose trips are not
+    // all in `stop_times.txt`
+    fn into_last_batch(mut self) -> Vec<StopTime> {
+        for block_stop_times in self.pending_blocks.values_mut() {
+            self.batch.append(block_stop_times);
+        }
+        self.batch
+    }
+}
+
+// Converts the batches of stop times as `read` and `Model::new` do, with the
+// other objects of the GTFS read beforehand in the `collections`, and writes
+// them. The vehicle journeys written are kept with their first and last stop
+// times only, along with the stop points of all their stop times.
+struct StopTimeBatchWriter<'a, W: std::io::Write> {
+    vehicle_journeys: &'a CollectionWithId<VehicleJourney>,
+    // the networks as read, their timezones being normalized with each batch
+    networks: CollectionWithId<objects::Network>,
+    frequencies: HashMap<String, Vec<Frequency>>,
+    configuration: &'a Configuration,
+    wtr: csv::Writer<W>,
+    written_vehicle_journeys: Vec<VehicleJourney>,
+    stop_points_used: BTreeSet<Idx<StopPoint>>,
+}
+
+impl<'a, W: std::io::Write> StopTimeBatchWriter<'a, W> {
+    fn write(
+        &mut self,
+        collections: &mut Collections,
+        stop_times: Vec<StopTime>,
+        report: &mut Option<&mut Report>,
+    ) -> Result<()> {
+        let trip_ids: BTreeSet<&str> = stop_times.iter().map(|st| st.trip_id.as_str()).collect();
+        let batch_vehicle_journeys: Vec<VehicleJourney> = trip_ids
+            .iter()
+            .filter_map(|trip_id| self.vehicle_journeys.get(trip_id).cloned())
+            .collect();
+        let batch_frequencies: Vec<Frequency> = trip_ids
+            .iter()
+            .filter_map(|trip_id| self.frequencies.remove(*trip_id))
+            .flatten()
+            .collect();
+        let mut batch = Collections {
+            vehicle_journeys: CollectionWithId::new(batch_vehicle_journeys)?,
+            networks: self.networks.clone(),
+            ..Default::default()
+        };
+        swap_stop_times_references(collections, &mut batch);
+        add_stop_times(
+            &mut batch,
+            stop_times,
+            report,
+            self.configuration.on_demand_transport,
+            self.configuration.on_demand_transport_comment.as_deref(),
+        )?;
+        add_frequencies(
+            &mut batch,
+            batch_frequencies,
+            self.configuration.keep_frequencies,
+        )?;
+        normalize_timezones(&mut batch)?;
+        // as `Model::new`, drops the vehicle journeys without stop times or
+        // running days
+        let batch_calendars = &batch.calendars;
+        batch.vehicle_journeys.retain(|vj| {
+            !vj.stop_times.is_empty()
+                && batch_calendars
+                    .get(&vj.service_id)
+                    .map_or(false, |calendar| !calendar.dates.is_empty())
+        });
+        batch.enhance_pickup_dropoff();
+
+        let prefix_conf = self.configuration.prefix_conf.as_ref();
+        for vj in batch.vehicle_journeys.values() {
+            let stop_times = ntfs::make_stop_times(
+                vj,
+                &batch.stop_points,
+                &batch.stop_time_headsigns,
+                &batch.stop_time_ids,
+                &batch.stop_time_continuous_stoppings,
+            );
+            for mut stop_time in stop_times {
+                if let Some(prefix_conf) = prefix_conf {
+                    stop_time.trip_id = prefix_conf.schedule_prefix(&stop_time.trip_id);
+                    stop_time.stop_id = prefix_conf.referential_prefix(&stop_time.stop_id);
+                    stop_time.stop_time_id = stop_time
+                        .stop_time_id
+                        .map(|stop_time_id| prefix_conf.schedule_prefix(&stop_time_id));
+                }
+                self.wtr
+                    .serialize(stop_time)
+                    .with_context(|_| format!("Error writing stop times of trip_id={:?}", vj.id))?;
+            }
+        }
+        swap_stop_times_references(collections, &mut batch);
+
+        collections.networks = batch.networks;
+        collections
+            .stop_time_comments
+            .extend(batch.stop_time_comments);
+        collections.stop_time_ids.extend(batch.stop_time_ids);
+        for booking_rule_link in batch.booking_rule_links.take() {
+            collections.booking_rule_links.push(booking_rule_link);
+        }
+        for frequency in batch.frequencies.take() {
+            collections.frequencies.push(frequency);
+        }
+        for mut vj in batch.vehicle_journeys.take() {
+            self.stop_points_used
+                .extend(vj.stop_times.iter().map(|st| st.stop_point_idx));
+            let last_stop_time = vj.stop_times.pop();
+            vj.stop_times.truncate(1);
+            vj.stop_times.extend(last_stop_time);
+            self.written_vehicle_journeys.push(vj);
+        }
+        Ok(())
+    }
+}
+
+// Stop times are read by batches of whole trips: `stop_times.txt` must be
+// grouped by `trip_id`. The vehicle journeys of the `collections` are
+// replaced by the ones written, with their first and last stop times, and
+// the stop points of all their stop times are returned.
+pub(in crate::gtfs) fn stream_stop_times<H, W>(
+    collections: &mut Collections,
+    file_handler: &mut H,
+    writer: W,
+    configuration: &Configuration,
+    batch_size: usize,
+) -> Result<BTreeSet<Idx<StopPoint>>>
+where
+    for<'a> &'a mut H: FileHandler,
+    W: std::io::Write,
+{
+    let mut frequencies: HashMap<String, Vec<Frequency>> = HashMap::new();
+    for frequency in read_frequencies(file_handler)? {
+        frequencies
+            .entry(frequency.trip_id.clone())
+            .or_insert_with(Vec::new)
+            .push(frequency);
+    }
+    let vehicle_journeys = CollectionWithId::new(collections.vehicle_journeys.take())?;
+
+    let file_name = "stop_times.txt";
+    let read_mode = file_handler.read_mode();
+    let (reader, path) = file_handler.get_file(file_name)?;
+    info!("Streaming stop_times.txt");
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let mut batches = StopTimeBatches::new(&vehicle_journeys, batch_size);
+    let mut batch_writer = StopTimeBatchWriter {
+        vehicle_journeys: &vehicle_journeys,
+        networks: collections.networks.clone(),
+        frequencies,
+        configuration,
+        wtr: csv::Writer::from_writer(writer),
+        written_vehicle_journeys: vec![],
+        stop_points_used: BTreeSet::new(),
+    };
+    let mut skipped = Report::default();
+    let mut report = lenient_report(read_mode, &mut skipped);
+    let mut streamed_trip_ids = HashSet::new();
+    let mut trip_stop_times: Vec<StopTime> = vec![];
+    for_each_record(
+        &mut rdr,
+        &path,
+        report.as_mut().map(|report| &mut **report),
+        |stop_time: StopTime, report| {
+            let previous_trip_id = trip_stop_times.last().map(|st| st.trip_id.as_str());
+            if previous_trip_id != Some(stop_time.trip_id.as_str()) {
+                let stop_times = std::mem::replace(&mut trip_stop_times, vec![]);
+                if let Some(batch) = batches.add_trip(stop_times) {
+                    batch_writer.write(collections, batch, report)?;
+                }
+                if !streamed_trip_ids.insert(stop_time.trip_id.clone()) {
+                    bail!(
+                        "stop times of trip_id={:?} are not contiguous in stop_times.txt, they can't be streamed",
+                        stop_time.trip_id
+                    );
+                }
+            }
+            trip_stop_times.push(stop_time);
+            Ok(())
+        },
+    )?;
+    if let Some(batch) = batches.add_trip(trip_stop_times) {
+        batch_writer.write(collections, batch, &mut report)?;
+    }
+    batch_writer.write(collections, batches.into_last_batch(), &mut report)?;
+    warn_skipped_records(&skipped);
+    batch_writer
+        .wtr
+        .flush()
+        .with_context(|_| format!("Error writing stop times from {:?}", path))?;
+    collections.vehicle_journeys = CollectionWithId::new(batch_writer.written_vehicle_journeys)?;
+    Ok(batch_writer.stop_points_used)
+}
+
+// Moves the objects of the `collections` which the conversion of the stop
+// times of a batch refers to, and back. The calendars and comments created
+// for a batch are thus kept for the next ones.
+fn swap_stop_times_references(collections: &mut Collections, batch: &mut Collections) {
+    std::mem::swap(&mut collections.calendars, &mut batch.calendars);
+    std::mem::swap(&mut collections.comments, &mut batch.comments);
+    std::mem::swap(&mut collections.companies, &mut batch.companies);
+    std::mem::swap(&mut collections.lines, &mut batch.lines);
+    std::mem::swap(&mut collections.routes, &mut batch.routes);
+    std::mem::swap(&mut collections.stop_points, &mut batch.stop_points);
+}
+
 fn ventilate_stop_times(
     undefined_stop_times: &[&StopTime],
     before: &StopTimesValues,
@@ -581,11 +1015,12 @@ where
         for agency in gtfs_agencies.iter().skip(1) {
             if referent_agency.timezone != agency.timezone {
                 warn!(
-                    "different agency timezone: {} ({}) - {} ({})",
+                    "different agency timezone: {} ({}) - {} ({}), the times are normalized to {}",
                     referent_agency.timezone,
                     referent_agency.id.clone().unwrap_or_default(),
                     agency.timezone,
                     agency.id.clone().unwrap_or_default(),
+                    referent_agency.timezone,
                 );
                 break;
             }
@@ -606,6 +1041,547 @@ where
     Ok((networks, companies))
 }
 
+/// Sets the default language of the dataset from the `feed_lang` of
+/// `feed_info.txt`, unless it is already configured in the feed infos
+pub(in crate::gtfs) fn manage_feed_lang<H>(
+    file_handler: &mut H,
+    collections: &mut Collections,
+) -> Result<()>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    let feed_infos = read_opt_objects::<_, FeedInfo>(file_handler, "feed_info.txt")?;
+    let feed_lang = match feed_infos.into_iter().next().and_then(|f| f.feed_lang) {
+        Some(feed_lang) => feed_lang,
+        None => return Ok(()),
+    };
+    if collections.feed_infos.contains_key(FEED_LANG) {
+        info!(
+            "feed_lang {:?} of feed_info.txt ignored, the configured one is kept",
+            feed_lang
+        );
+    } else if !languages::is_valid_language_tag(&feed_lang) {
+        warn!(
+            "feed_lang {:?} of feed_info.txt is not a valid BCP-47 language tag, ignored",
+            feed_lang
+        );
+    } else {
+        collections
+            .feed_infos
+            .insert(FEED_LANG.to_string(), feed_lang);
+    }
+    Ok(())
+}
+
+// The objects translated by a row of translations.txt, identified by
+// `record_id`
+fn translated_objects_by_id(
+    collections: &Collections,
+    table_name: &str,
+    record_id: &str,
+) -> Vec<(ObjectType, String)> {
+    let mut objects = vec![];
+    match table_name {
+        "stops" => {
+            if collections.stop_points.contains_id(record_id) {
+                objects.push((ObjectType::StopPoint, record_id.to_string()));
+            }
+            // the stop areas created for the stops without parent station
+            // are also translated
+            let generated_stop_area_id = format!("Navitia:{}", record_id);
+            for stop_area_id in &[record_id.to_string(), generated_stop_area_id] {
+                if collections.stop_areas.contains_id(stop_area_id) {
+                    objects.push((ObjectType::StopArea, stop_area_id.clone()));
+                }
+            }
+        }
+        "routes" => {
+            if collections.lines.contains_id(record_id) {
+                objects.push((ObjectType::Line, record_id.to_string()));
+            }
+        }
+        "agency" => {
+            if collections.networks.contains_id(record_id) {
+                objects.push((ObjectType::Network, record_id.to_string()));
+            }
+        }
+        _ => {}
+    }
+    objects
+}
+
+// The objects translated by a row of translations.txt, identified by the
+// translated `field_value`
+fn translated_objects_by_value(
+    collections: &Collections,
+    field_name: &str,
+    field_value: &str,
+) -> Vec<(ObjectType, String)> {
+    match field_name {
+        "stop_name" => collections
+            .stop_points
+            .values()
+            .filter(|sp| sp.name == field_value)
+            .map(|sp| (ObjectType::StopPoint, sp.id.clone()))
+            .chain(
+                collections
+                    .stop_areas
+                    .values()
+                    .filter(|sa| sa.name == field_value)
+                    .map(|sa| (ObjectType::StopArea, sa.id.clone())),
+            )
+            .collect(),
+        "line_name" => collections
+            .lines
+            .values()
+            .filter(|line| line.name == field_value)
+            .map(|line| (ObjectType::Line, line.id.clone()))
+            .collect(),
+        "line_code" => collections
+            .lines
+            .values()
+            .filter(|line| line.code.as_deref() == Some(field_value))
+            .map(|line| (ObjectType::Line, line.id.clone()))
+            .collect(),
+        "network_name" => collections
+            .networks
+            .values()
+            .filter(|network| network.name == field_value)
+            .map(|network| (ObjectType::Network, network.id.clone()))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Reads the translations of the names of the stops, routes and agencies
+/// from the optional translations.txt. The other translations are ignored.
+pub(in crate::gtfs) fn manage_translations<H>(
+    collections: &mut Collections,
+    file_handler: &mut H,
+) -> Result<()>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    let gtfs_translations = read_opt_objects::<_, Translation>(file_handler, "translations.txt")?;
+    let mut translations = vec![];
+    let mut ignored = 0;
+    for gtfs_translation in gtfs_translations {
+        let field_name = match (
+            gtfs_translation.table_name.as_str(),
+            gtfs_translation.field_name.as_str(),
+        ) {
+            ("stops", "stop_name") => "stop_name",
+            ("routes", "route_long_name") => "line_name",
+            ("routes", "route_short_name") => "line_code",
+            ("agency", "agency_name") => "network_name",
+            _ => {
+                ignored += 1;
+                continue;
+            }
+        };
+        let translated_objects = match (&gtfs_translation.record_id, &gtfs_translation.field_value)
+        {
+            (Some(record_id), _) => {
+                translated_objects_by_id(collections, &gtfs_translation.table_name, record_id)
+            }
+            (None, Some(field_value)) => {
+                translated_objects_by_value(collections, field_name, field_value)
+            }
+            (None, None) => vec![],
+        };
+        if translated_objects.is_empty() {
+            warn!(
+                "translations.txt: no object found for the {} translation {:?} of {}.{}",
+                gtfs_translation.language,
+                gtfs_translation.translation,
+                gtfs_translation.table_name,
+                gtfs_translation.field_name
+            );
+            continue;
+        }
+        for (object_type, object_id) in translated_objects {
+            translations.push(objects::Translation {
+                object_type,
+                object_id,
+                field_name: field_name.to_string(),
+                language: gtfs_translation.language.clone(),
+                translation: gtfs_translation.translation.clone(),
+            });
+        }
+    }
+    if ignored > 0 {
+        info!(
+            "{} translations of translations.txt ignored, only the names of stops, routes and agencies are read",
+            ignored
+        );
+    }
+    collections.translations = Collection::new(translations);
+    Ok(())
+}
+
+fn attribution_roles(attribution: &Attribution) -> Vec<&'static str> {
+    let mut roles = vec![];
+    if attribution.is_producer == 1 {
+        roles.push("producer");
+    }
+    if attribution.is_operator == 1 {
+        roles.push("operator");
+    }
+    if attribution.is_authority == 1 {
+        roles.push("authority");
+    }
+    roles
+}
+
+// The company of the attributed organization, created if no company has its
+// name
+fn attribution_company_id(
+    companies: &mut CollectionWithId<objects::Company>,
+    attribution: &Attribution,
+) -> Option<String> {
+    if let Some(company) = companies
+        .values()
+        .find(|company| company.name == attribution.organization_name)
+    {
+        return Some(company.id.clone());
+    }
+    let company_id = format!(
+        "attribution:{}",
+        attribution
+            .attribution_id
+            .as_ref()
+            .unwrap_or(&attribution.organization_name)
+    );
+    let company = objects::Company {
+        id: company_id.clone(),
+        name: attribution.organization_name.clone(),
+        address: None,
+        url: attribution.attribution_url.clone(),
+        mail: attribution.attribution_email.clone(),
+        phone: attribution.attribution_phone.clone(),
+    };
+    match companies.push(company) {
+        Ok(_) => Some(company_id),
+        Err(e) => {
+            warn!("attributions.txt: {}", e);
+            None
+        }
+    }
+}
+
+/// Reads the attributions of `attributions.txt`. A company is created for
+/// each attributed organization, and the attributed objects get an
+/// `attribution_producer`, `attribution_operator` or `attribution_authority`
+/// object property with the name of the organization:
+/// * the vehicle journey of a `trip_id`,
+/// * the routes of a `route_id`,
+/// * the lines of the network of an `agency_id`,
+/// * every line when none of them is given.
+///
+/// The operator of a trip, a route or an agency also becomes the company of
+/// the corresponding vehicle journeys. The operator of an agency (a
+/// sub-agency) gets the company of the agency as parent company.
+pub(in crate::gtfs) fn manage_attributions<H>(
+    collections: &mut Collections,
+    file_handler: &mut H,
+) -> Result<()>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    let attributions = read_opt_objects::<_, Attribution>(file_handler, "attributions.txt")?;
+    for attribution in attributions {
+        let roles = attribution_roles(&attribution);
+        if roles.is_empty() {
+            warn!(
+                "attributions.txt: no role given to {:?}, the attribution is ignored",
+                attribution.organization_name
+            );
+            continue;
+        }
+        let mut lines = vec![];
+        let mut routes = vec![];
+        let mut vehicle_journeys = vec![];
+        if let Some(trip_id) = &attribution.trip_id {
+            vehicle_journeys.extend(collections.vehicle_journeys.get_idx(trip_id));
+        } else if let Some(route_id) = &attribution.route_id {
+            for id in &[route_id.clone(), format!("{}_R", route_id)] {
+                routes.extend(collections.routes.get_idx(id));
+            }
+        } else if let Some(agency_id) = &attribution.agency_id {
+            lines.extend(
+                collections
+                    .lines
+                    .iter()
+                    .filter(|(_, line)| &line.network_id == agency_id)
+                    .map(|(idx, _)| idx),
+            );
+        } else {
+            lines.extend(collections.lines.iter().map(|(idx, _)| idx));
+        }
+        if lines.is_empty() && routes.is_empty() && vehicle_journeys.is_empty() {
+            warn!(
+                "attributions.txt: no object found for the attribution to {:?}",
+                attribution.organization_name
+            );
+            continue;
+        }
+        let company_id = match attribution_company_id(&mut collections.companies, &attribution) {
+            Some(company_id) => company_id,
+            None => continue,
+        };
+        let properties: Vec<(String, String)> = roles
+            .iter()
+            .map(|role| {
+                (
+                    format!("attribution_{}", role),
+                    attribution.organization_name.clone(),
+                )
+            })
+            .collect();
+
+        let is_targeted = attribution.trip_id.is_some()
+            || attribution.route_id.is_some()
+            || attribution.agency_id.is_some();
+        let mut operated_vehicle_journeys = vehicle_journeys.clone();
+        if attribution.is_operator == 1 && is_targeted {
+            let route_ids: HashSet<&str> = routes
+                .iter()
+                .map(|idx| collections.routes[*idx].id.as_str())
+                .collect();
+            let line_ids: HashSet<&str> = lines
+                .iter()
+                .map(|idx| collections.lines[*idx].id.as_str())
+                .collect();
+            operated_vehicle_journeys.extend(
+                collections
+                    .vehicle_journeys
+                    .iter()
+                    .filter(|(_, vj)| {
+                        route_ids.contains(vj.route_id.as_str())
+                            || collections
+                                .routes
+                                .get(&vj.route_id)
+                                .map(|route| line_ids.contains(route.line_id.as_str()))
+                                .unwrap_or(false)
+                    })
+                    .map(|(idx, _)| idx),
+            );
+        } else {
+            operated_vehicle_journeys.clear();
+        }
+
+        for idx in lines {
+            let mut line = collections.lines.index_mut(idx);
+            line.object_properties.extend(properties.iter().cloned());
+        }
+        for idx in routes {
+            let mut route = collections.routes.index_mut(idx);
+            route.object_properties.extend(properties.iter().cloned());
+        }
+        for idx in vehicle_journeys {
+            let mut vj = collections.vehicle_journeys.index_mut(idx);
+            vj.object_properties.extend(properties.iter().cloned());
+        }
+        for idx in operated_vehicle_journeys {
+            collections.vehicle_journeys.index_mut(idx).company_id = company_id.clone();
+        }
+        if let (1, Some(agency_id)) = (attribution.is_operator, &attribution.agency_id) {
+            let has_parent = collections
+                .company_parents
+                .values()
+                .any(|link| link.company_id == company_id);
+            if &company_id != agency_id
+                && collections.companies.contains_id(agency_id)
+                && !has_parent
+            {
+                collections.company_parents.push(objects::CompanyParent {
+                    company_id,
+                    parent_company_id: agency_id.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+// The identifier `<operator_id>`, or `<operator_id>:operator` (then
+// `<operator_id>:operator:<n>`) when it is already the identifier of a company
+fn operator_company_id(
+    operator_id: &str,
+    companies: &CollectionWithId<objects::Company>,
+) -> String {
+    if !companies.contains_id(operator_id) {
+        return operator_id.to_string();
+    }
+    let id = format!("{}:operator", operator_id);
+    if !companies.contains_id(&id) {
+        return id;
+    }
+    (1..)
+        .map(|n| format!("{}:{}", id, n))
+        .find(|company_id| !companies.contains_id(company_id))
+        .unwrap()
+}
+
+/// Sets the operators of the `operator_id` extension column of `trips.txt`
+/// (given by trip identifier, see [read_routes](fn.read_routes.html)). The
+/// operator of a trip becomes the company of its vehicle journey instead of
+/// the agency of the route, unless it is this agency. A dedicated company is
+/// created for each operator, with the identifier of the operator (suffixed
+/// with `:operator` when it is already the identifier of a company, e.g.
+/// another agency), whose parent company is the agency of the first trip of
+/// the operator.
+pub(in crate::gtfs) fn manage_trip_operators(
+    collections: &mut Collections,
+    trip_operators: Vec<(String, String)>,
+) -> Result<()> {
+    info!("Creating the companies of the operators of trips.txt");
+    // the company of each operator
+    let mut operator_companies: HashMap<String, String> = HashMap::new();
+    let mut companies_with_parent: HashSet<String> = collections
+        .company_parents
+        .values()
+        .map(|link| link.company_id.clone())
+        .collect();
+    for (trip_id, operator_id) in trip_operators {
+        let vj_idx = match collections.vehicle_journeys.get_idx(&trip_id) {
+            Some(vj_idx) => vj_idx,
+            None => continue,
+        };
+        let agency_id = collections.vehicle_journeys[vj_idx].company_id.clone();
+        if operator_id == agency_id {
+            continue;
+        }
+        let company_id = match operator_companies.get(&operator_id) {
+            Some(company_id) => company_id.clone(),
+            None => {
+                let company_id = operator_company_id(&operator_id, &collections.companies);
+                collections.companies.push(objects::Company {
+                    id: company_id.clone(),
+                    name: operator_id.clone(),
+                    ..Default::default()
+                })?;
+                operator_companies.insert(operator_id, company_id.clone());
+                company_id
+            }
+        };
+        collections.vehicle_journeys.index_mut(vj_idx).company_id = company_id.clone();
+        if collections.companies.contains_id(&agency_id)
+            && !companies_with_parent.contains(&company_id)
+        {
+            companies_with_parent.insert(company_id.clone());
+            collections.company_parents.push(objects::CompanyParent {
+                company_id,
+                parent_company_id: agency_id,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Sets the fare URL and contacts of the networks of `network_contacts`,
+/// replacing the ones read in `agency.txt`.
+pub(in crate::gtfs) fn manage_network_contacts(
+    collections: &mut Collections,
+    network_contacts: NetworkContacts,
+) {
+    for (network_id, contact) in network_contacts {
+        let mut network = match collections.networks.get_mut(&network_id) {
+            Some(network) => network,
+            None => {
+                warn!(
+                    "the contacts of the network {:?} are ignored as it doesn't exist",
+                    network_id
+                );
+                continue;
+            }
+        };
+        if contact.fare_url.is_some() {
+            network.fare_url = contact.fare_url;
+        }
+        if contact.email.is_some() {
+            network.email = contact.email;
+        }
+        if contact.phone.is_some() {
+            network.phone = contact.phone;
+        }
+    }
+}
+
+/// Sets the parent company of the companies of `company_parents`, replacing
+/// the ones deduced from the attributions.
+pub(in crate::gtfs) fn manage_company_parents(
+    collections: &mut Collections,
+    company_parents: CompanyParents,
+) {
+    for (company_id, parent_company_id) in company_parents {
+        if !collections.companies.contains_id(&company_id)
+            || !collections.companies.contains_id(&parent_company_id)
+        {
+            warn!(
+                "the parent company {:?} of the company {:?} is ignored as one of them doesn't exist",
+                parent_company_id, company_id
+            );
+            continue;
+        }
+        let mut links = collections.company_parents.take();
+        links.retain(|link| link.company_id != company_id);
+        links.push(objects::CompanyParent {
+            company_id,
+            parent_company_id,
+        });
+        collections.company_parents = Collection::new(links);
+    }
+}
+
+impl From<BookingRule> for objects::BookingRule {
+    fn from(booking_rule: BookingRule) -> objects::BookingRule {
+        objects::BookingRule {
+            id: booking_rule.booking_rule_id,
+            name: None,
+            phone: booking_rule.phone_number,
+            info_url: booking_rule.info_url,
+            booking_url: booking_rule.booking_url,
+            message: booking_rule.message,
+            deadline_duration: booking_rule.prior_notice_duration_min,
+            deadline_day: booking_rule.prior_notice_last_day,
+            deadline_time: booking_rule.prior_notice_last_time,
+        }
+    }
+}
+
+/// Reads the booking rules of `booking_rules.txt` (GTFS-Flex). The vehicle
+/// journeys are linked to the `pickup_booking_rule_id` and
+/// `drop_off_booking_rule_id` of their stop times when reading them, the
+/// links to an unknown booking rule are removed.
+pub(in crate::gtfs) fn manage_booking_rules<H>(
+    collections: &mut Collections,
+    file_handler: &mut H,
+) -> Result<()>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    let gtfs_booking_rules = read_opt_objects::<_, BookingRule>(file_handler, "booking_rules.txt")?;
+    let booking_rules = CollectionWithId::new(
+        gtfs_booking_rules
+            .into_iter()
+            .map(objects::BookingRule::from)
+            .collect(),
+    )?;
+    collections.booking_rule_links.retain(|link| {
+        let exists = booking_rules.contains_id(&link.booking_rule_id);
+        if !exists {
+            warn!(
+                "stop_times.txt: booking rule {} of trip {} not found",
+                link.booking_rule_id, link.object_id
+            );
+        }
+        exists
+    });
+    collections.booking_rules = booking_rules;
+    Ok(())
+}
+
 fn manage_comment_from_stop(
     comments: &mut CollectionWithId<objects::Comment>,
     stop: &Stop,
@@ -786,6 +1762,104 @@ where
     Ok((stopareas, stoppoints, stoplocations))
 }
 
+#[derive(Deserialize, Debug)]
+struct Area {
+    area_id: String,
+    area_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AreaStop {
+    area_id: String,
+    #[serde(deserialize_with = "de_without_slashes")]
+    stop_id: String,
+}
+
+fn read_locations<H>(file_handler: &mut H) -> Result<Vec<Zone>>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    let file = "locations.geojson";
+    let (reader, path) = file_handler.get_file_if_exists(file)?;
+    let reader = match reader {
+        Some(reader) => reader,
+        None => {
+            info!("Skipping {}", file);
+            return Ok(vec![]);
+        }
+    };
+    info!("Reading {}", file);
+    zones::read_geojson_zones(reader, &path)
+}
+
+// The groups of stops of `stop_areas.txt`, which may be used as zones by the
+// stop times
+fn read_stop_area_zones<H>(
+    file_handler: &mut H,
+    stop_points: &CollectionWithId<objects::StopPoint>,
+) -> Result<Vec<Zone>>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    let area_stops = read_opt_objects::<_, AreaStop>(file_handler, "stop_areas.txt")?;
+    if area_stops.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut area_points: BTreeMap<String, Vec<Point<f64>>> = BTreeMap::new();
+    for area_stop in area_stops {
+        if let Some(stop_point) = stop_points.get(&area_stop.stop_id) {
+            area_points
+                .entry(area_stop.area_id)
+                .or_insert_with(Vec::new)
+                .push((stop_point.coord.lon, stop_point.coord.lat).into());
+        }
+    }
+    let area_names: HashMap<String, String> =
+        read_opt_objects::<_, Area>(file_handler, "areas.txt")?
+            .into_iter()
+            .filter_map(|area| area.area_name.map(|name| (area.area_id, name)))
+            .collect();
+    let mut zones = vec![];
+    for (area_id, points) in area_points {
+        if stop_points.contains_id(&area_id) {
+            continue;
+        }
+        let hull = geo::MultiPoint(points).convex_hull();
+        let name = area_names
+            .get(&area_id)
+            .cloned()
+            .unwrap_or_else(|| area_id.clone());
+        zones.push(make_zone(
+            area_id,
+            name,
+            geo::Geometry::Polygon(hull.clone()),
+            hull.centroid(),
+        ));
+    }
+    Ok(zones)
+}
+
+/// Reads the zones of GTFS-Flex: the locations of `locations.geojson` and
+/// the groups of stops of `stop_areas.txt`. A zone is read as a stop point
+/// of type zone, with its geometry, in a stop area of its own. The zones are
+/// read before the stop times, which reference them; the ones not served by
+/// a stop time (e.g. the areas only used by the fares) are removed with the
+/// other unused stop points when the `Model` is built.
+pub(in crate::gtfs) fn manage_flex_zones<H>(
+    collections: &mut Collections,
+    file_handler: &mut H,
+) -> Result<()>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    let mut zones = read_locations(file_handler)?;
+    zones.extend(read_stop_area_zones(
+        file_handler,
+        &collections.stop_points,
+    )?);
+    zones::add_zones(collections, zones)
+}
+
 pub(in crate::gtfs) fn manage_pathways<H>(
     collections: &mut Collections,
     file_handler: &mut H,
@@ -926,7 +2000,10 @@ where
                                 }
                                 (transfer.min_transfer_time, transfer.min_transfer_time)
                             }
-                            TransferType::NotPossible => (Some(86400), Some(86400)),
+                            TransferType::NotPossible => (
+                                Some(NOT_POSSIBLE_TRANSFER_TIME),
+                                Some(NOT_POSSIBLE_TRANSFER_TIME),
+                            ),
                         };
 
                         transfers.push(objects::Transfer {
@@ -971,23 +2048,92 @@ fn get_physical_mode(route_type: &RouteType) -> objects::PhysicalMode {
     }
 }
 
+// The commercial and physical modes of a GTFS route, from the route type
+// mappings if its `route_type` is mapped
+fn get_route_modes(
+    route: &Route,
+    route_type_mappings: &RouteTypeMappings,
+) -> (objects::CommercialMode, objects::PhysicalMode) {
+    match route_type_mappings.get(&route.route_type) {
+        Some(mapping) => (
+            objects::CommercialMode {
+                id: mapping.commercial_mode.clone(),
+                name: mapping
+                    .commercial_mode_name
+                    .clone()
+                    .unwrap_or_else(|| mapping.commercial_mode.clone()),
+            },
+            objects::PhysicalMode {
+                id: mapping.physical_mode.clone(),
+                name: mapping.physical_mode.clone(),
+                co2_emission: None,
+            },
+        ),
+        None => {
+            let route_type = RouteType::from(route.route_type);
+            (
+                get_commercial_mode(&route_type),
+                get_physical_mode(&route_type),
+            )
+        }
+    }
+}
+
 fn get_modes_from_gtfs(
     gtfs_routes: &CollectionWithId<Route>,
+    route_type_mappings: &RouteTypeMappings,
 ) -> (Vec<objects::CommercialMode>, Vec<objects::PhysicalMode>) {
-    let gtfs_mode_types: BTreeSet<RouteType> =
-        gtfs_routes.values().map(|r| r.route_type.clone()).collect();
+    let mut gtfs_mode_types = BTreeSet::new();
+    let mut mapped_route_types = BTreeSet::new();
+    let mut unmapped_route_types = BTreeSet::new();
+    for route in gtfs_routes.values() {
+        if route_type_mappings.contains_key(&route.route_type) {
+            mapped_route_types.insert(route.route_type);
+        } else {
+            if !route_type_mappings.is_empty() && route.route_type >= 100 {
+                unmapped_route_types.insert(route.route_type);
+            }
+            gtfs_mode_types.insert(RouteType::from(route.route_type));
+        }
+    }
+    for route_type in unmapped_route_types {
+        warn!(
+            "route_type {} is not in the route type mappings, default modes used",
+            route_type
+        );
+    }
 
-    let commercial_modes = gtfs_mode_types
+    let mut commercial_modes: Vec<objects::CommercialMode> = gtfs_mode_types
         .iter()
         .map(|mt| get_commercial_mode(mt))
         .collect();
-    let physical_modes = gtfs_mode_types
+    let mut physical_modes: BTreeSet<objects::PhysicalMode> = gtfs_mode_types
         .iter()
         .map(|mt| get_physical_mode(mt))
-        .collect::<BTreeSet<_>>()
-        .into_iter()
         .collect();
-    (commercial_modes, physical_modes)
+    for mapping in mapped_route_types
+        .iter()
+        .filter_map(|route_type| route_type_mappings.get(route_type))
+    {
+        if !commercial_modes
+            .iter()
+            .any(|cm| cm.id == mapping.commercial_mode)
+        {
+            commercial_modes.push(objects::CommercialMode {
+                id: mapping.commercial_mode.clone(),
+                name: mapping
+                    .commercial_mode_name
+                    .clone()
+                    .unwrap_or_else(|| mapping.commercial_mode.clone()),
+            });
+        }
+        physical_modes.insert(objects::PhysicalMode {
+            id: mapping.physical_mode.clone(),
+            name: mapping.physical_mode.clone(),
+            co2_emission: None,
+        });
+    }
+    (commercial_modes, physical_modes.into_iter().collect())
 }
 
 fn get_route_with_smallest_name<'a>(routes: &'a [&Route]) -> &'a Route {
@@ -1016,6 +2162,7 @@ fn map_line_routes<'a>(
 fn make_lines(
     map_line_routes: &MapLineRoutes<'_>,
     networks: &CollectionWithId<objects::Network>,
+    route_type_mappings: &RouteTypeMappings,
 ) -> Result<Vec<objects::Line>> {
     let mut lines = vec![];
 
@@ -1029,6 +2176,7 @@ fn make_lines(
 
     for routes in map_line_routes.values() {
         let r = get_route_with_smallest_name(routes);
+        let (commercial_mode, _) = get_route_modes(r, route_type_mappings);
 
         lines.push(objects::Line {
             id: r.id.clone(),
@@ -1045,7 +2193,7 @@ fn make_lines(
             text_color: r.text_color.clone(),
             sort_order: r.sort_order,
             network_id: get_agency_id(r, networks)?,
-            commercial_mode_id: r.route_type.to_string(),
+            commercial_mode_id: commercial_mode.id,
             geometry_id: None,
             opening_time: None,
             closing_time: None,
@@ -1107,45 +2255,42 @@ fn make_ntfs_vehicle_journeys(
     routes: &CollectionWithId<Route>,
     datasets: &CollectionWithId<objects::Dataset>,
     networks: &CollectionWithId<objects::Network>,
+    route_type_mappings: &RouteTypeMappings,
 ) -> Result<(Vec<objects::VehicleJourney>, Vec<objects::TripProperty>)> {
     // there always is one dataset from config or a default one
     let (_, dataset) = datasets.iter().next().unwrap();
     let mut vehicle_journeys: Vec<objects::VehicleJourney> = vec![];
     let mut trip_properties: Vec<objects::TripProperty> = vec![];
-    let mut map_tps_trips: BTreeMap<(Availability, Availability), Vec<&Trip>> = BTreeMap::new();
-    let mut id_incr: u8 = 1;
+    let mut map_tps_trips: BTreeMap<TripPropertyKey, Vec<&Trip>> = BTreeMap::new();
+    let mut id_incr: u32 = 1;
     let mut property_id: Option<String>;
 
     for t in gtfs_trips {
         map_tps_trips
-            .entry((t.wheelchair_accessible, t.bikes_allowed))
+            .entry(t.trip_property_key())
             .or_insert_with(Vec::new)
             .push(t);
     }
 
-    for ((wheelchair, bike), trips) in &map_tps_trips {
-        if *wheelchair == Availability::InformationNotAvailable
-            && *bike == Availability::InformationNotAvailable
-        {
+    for trips in map_tps_trips.values() {
+        let mut trip_property = trips[0].trip_property();
+        if trip_property == objects::TripProperty::default() {
             property_id = None;
         } else {
             property_id = Some(id_incr.to_string());
-            trip_properties.push(objects::TripProperty {
-                id: id_incr.to_string(),
-                wheelchair_accessible: *wheelchair,
-                bike_accepted: *bike,
-                air_conditioned: Availability::InformationNotAvailable,
-                visual_announcement: Availability::InformationNotAvailable,
-                audible_announcement: Availability::InformationNotAvailable,
-                appropriate_escort: Availability::InformationNotAvailable,
-                appropriate_signage: Availability::InformationNotAvailable,
-                school_vehicle_type: TransportType::Regular,
-            });
+            trip_property.id = id_incr.to_string();
+            trip_properties.push(trip_property);
             id_incr += 1;
         }
         for t in trips {
             vehicle_journeys.push(skip_error_and_log!(
-                t.to_ntfs_vehicle_journey(routes, dataset, &property_id, networks,),
+                t.to_ntfs_vehicle_journey(
+                    routes,
+                    dataset,
+                    &property_id,
+                    networks,
+                    route_type_mappings
+                ),
                 LogLevel::Warn
             ));
         }
@@ -1154,22 +2299,28 @@ fn make_ntfs_vehicle_journeys(
     Ok((vehicle_journeys, trip_properties))
 }
 
+/// Reads the lines, routes and vehicle journeys of `routes.txt` and
+/// `trips.txt`, returning the operators of the `operator_id` extension
+/// column of `trips.txt`, by trip identifier (see
+/// [manage_trip_operators](fn.manage_trip_operators.html)).
 pub(in crate::gtfs) fn read_routes<H>(
     file_handler: &mut H,
     collections: &mut Collections,
-) -> Result<()>
+    route_type_mappings: &RouteTypeMappings,
+) -> Result<Vec<(String, String)>>
 where
     for<'a> &'a mut H: FileHandler,
 {
     info!("Reading routes.txt");
     let gtfs_routes_collection = read_collection(file_handler, "routes.txt")?;
-    let (commercial_modes, physical_modes) = get_modes_from_gtfs(&gtfs_routes_collection);
+    let (commercial_modes, physical_modes) =
+        get_modes_from_gtfs(&gtfs_routes_collection, route_type_mappings);
     collections.commercial_modes = CollectionWithId::new(commercial_modes)?;
     collections.physical_modes = CollectionWithId::new(physical_modes)?;
 
-    let gtfs_trips = read_objects(file_handler, "trips.txt")?;
+    let gtfs_trips = read_objects_in_parallel(file_handler, "trips.txt")?;
     let map_line_routes = map_line_routes(&gtfs_routes_collection, &gtfs_trips);
-    let lines = make_lines(&map_line_routes, &collections.networks)?;
+    let lines = make_lines(&map_line_routes, &collections.networks, route_type_mappings)?;
     collections.lines = CollectionWithId::new(lines)?;
 
     let routes = make_routes(&gtfs_trips, &map_line_routes);
@@ -1180,12 +2331,17 @@ where
         &gtfs_routes_collection,
         &collections.datasets,
         &collections.networks,
+        route_type_mappings,
     )
     .with_context(|_| format!("Error reading {:?}", "trips.txt"))?;
     collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
     collections.trip_properties = CollectionWithId::new(trip_properties)?;
 
-    Ok(())
+    let trip_operators = gtfs_trips
+        .into_iter()
+        .filter_map(|trip| trip.operator_id.map(|operator_id| (trip.id, operator_id)))
+        .collect();
+    Ok(trip_operators)
 }
 
 #[derivative(Default)]
@@ -1199,7 +2355,7 @@ enum FrequencyPrecision {
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
-struct Frequency {
+pub(in crate::gtfs) struct Frequency {
     trip_id: String,
     start_time: Time,
     end_time: Time,
@@ -1208,10 +2364,61 @@ struct Frequency {
     exact_times: FrequencyPrecision,
 }
 
+// Keeps the frequencies of the GTFS as NTFS frequencies, the stop times of
+// the referenced trips being the ones of the first departure
+fn keep_frequencies(collections: &mut Collections, gtfs_frequencies: Vec<Frequency>) {
+    for frequency in gtfs_frequencies {
+        if frequency.start_time >= frequency.end_time {
+            warn!(
+                "frequency for trip {:?} doesn't end after its start time",
+                frequency.trip_id
+            );
+            continue;
+        }
+        let mut vehicle_journey = skip_error_and_log!(
+            collections
+                .vehicle_journeys
+                .get_mut(&frequency.trip_id)
+                .ok_or_else(|| format_err!(
+                    "frequency mapped to an unexisting trip {:?}",
+                    frequency.trip_id
+                )),
+            LogLevel::Warn
+        );
+        if vehicle_journey.stop_times.is_empty() {
+            warn!(
+                "frequency mapped to trip {:?} with no stop_times",
+                frequency.trip_id
+            );
+            continue;
+        }
+        if frequency.exact_times == FrequencyPrecision::Inexact {
+            for stop_time in &mut vehicle_journey.stop_times {
+                stop_time.datetime_estimated = true;
+            }
+        }
+        collections.frequencies.push(objects::Frequency {
+            vehicle_journey_id: frequency.trip_id,
+            start_time: frequency.start_time,
+            end_time: frequency.end_time,
+            headway_secs: frequency.headway_secs,
+        });
+    }
+}
+
 pub(in crate::gtfs) fn manage_frequencies<H>(
     collections: &mut Collections,
     file_handler: &mut H,
+    keep_frequencies: bool,
 ) -> Result<()>
+where
+    for<'a> &'a mut H: FileHandler,
+{
+    let gtfs_frequencies = read_frequencies(file_handler)?;
+    add_frequencies(collections, gtfs_frequencies, keep_frequencies)
+}
+
+fn read_frequencies<H>(file_handler: &mut H) -> Result<Vec<Frequency>>
 where
     for<'a> &'a mut H: FileHandler,
 {
@@ -1221,159 +2428,426 @@ where
     match reader {
         None => {
             info!("Skipping {}", file);
-            Ok(())
+            Ok(vec![])
         }
         Some(reader) => {
             let mut rdr = csv::Reader::from_reader(reader);
-            let gtfs_frequencies: Vec<Frequency> = rdr
+            let gtfs_frequencies = rdr
                 .deserialize()
                 .collect::<Result<_, _>>()
                 .with_context(|_| format!("Error reading {:?}", path))?;
-            let mut trip_id_sequence: HashMap<String, u32> = HashMap::new();
-            let mut new_vehicle_journeys: Vec<VehicleJourney> = vec![];
-            for frequency in &gtfs_frequencies {
-                if frequency.start_time == frequency.end_time {
-                    warn!(
-                        "frequency for trip {:?} has same start and end time",
-                        frequency.trip_id
-                    );
-                    continue;
-                }
-                let datetime_estimated = match frequency.exact_times {
-                    FrequencyPrecision::Exact => false,
-                    FrequencyPrecision::Inexact => true,
-                };
-                let corresponding_vj = skip_error_and_log!(
-                    collections
-                        .vehicle_journeys
-                        .get(&frequency.trip_id)
-                        .cloned()
-                        .ok_or_else(|| format_err!(
-                            "frequency mapped to an unexisting trip {:?}",
-                            frequency.trip_id
-                        )),
-                    LogLevel::Warn
+            Ok(gtfs_frequencies)
+        }
+    }
+}
+
+// Either keeps the `gtfs_frequencies` or generates a vehicle journey for each
+// departure of the frequencies in place of their trip
+fn add_frequencies(
+    collections: &mut Collections,
+    gtfs_frequencies: Vec<Frequency>,
+    keep_frequencies: bool,
+) -> Result<()> {
+    if gtfs_frequencies.is_empty() {
+        return Ok(());
+    }
+    if keep_frequencies {
+        self::keep_frequencies(collections, gtfs_frequencies);
+        return Ok(());
+    }
+    let mut trip_id_sequence: HashMap<String, u32> = HashMap::new();
+    let mut new_vehicle_journeys: Vec<VehicleJourney> = vec![];
+    for frequency in &gtfs_frequencies {
+        if frequency.start_time == frequency.end_time {
+            warn!(
+                "frequency for trip {:?} has same start and end time",
+                frequency.trip_id
+            );
+            continue;
+        }
+        let datetime_estimated = match frequency.exact_times {
+            FrequencyPrecision::Exact => false,
+            FrequencyPrecision::Inexact => true,
+        };
+        let corresponding_vj = skip_error_and_log!(
+            collections
+                .vehicle_journeys
+                .get(&frequency.trip_id)
+                .cloned()
+                .ok_or_else(|| format_err!(
+                    "frequency mapped to an unexisting trip {:?}",
+                    frequency.trip_id
+                )),
+            LogLevel::Warn
+        );
+        let mut start_time = frequency.start_time;
+        let first_arrival_time = match corresponding_vj.stop_times.iter().min() {
+            None => {
+                warn!(
+                    "frequency mapped to trip {:?} with no stop_times",
+                    frequency.trip_id
                 );
-                let mut start_time = frequency.start_time;
-                let mut arrival_time_delta = match corresponding_vj.stop_times.iter().min() {
-                    None => {
-                        warn!(
-                            "frequency mapped to trip {:?} with no stop_times",
-                            frequency.trip_id
-                        );
-                        continue;
-                    }
-                    Some(st) => st.arrival_time,
-                };
-                while start_time < frequency.end_time {
-                    trip_id_sequence
-                        .entry(frequency.trip_id.clone())
-                        .and_modify(|counter| *counter += 1)
-                        .or_insert(0);
-                    let generated_trip_id = format!(
-                        "{}-{}",
-                        frequency.trip_id, trip_id_sequence[&frequency.trip_id]
-                    );
-                    // the following handles generated trip starting after midnight, we need to generate a
-                    // new service in case the next day is not covered
-                    let service_id = if start_time.hours() >= 24 {
-                        let nb_days = start_time.hours() / 24;
-                        let service = collections
-                            .calendars
-                            .get(&corresponding_vj.service_id)
-                            .cloned()
-                            .unwrap();
-                        let new_service_id = format!("{}:+{}days", service.id, nb_days);
-                        if !collections.calendars.contains_id(&new_service_id) {
-                            arrival_time_delta = arrival_time_delta + Time::new(24, 0, 0);
-                            let new_dates: BTreeSet<_> = service
-                                .dates
-                                .iter()
-                                .map(|d| *d + chrono::Duration::days(i64::from(nb_days)))
-                                .collect();
-
-                            let new_service = objects::Calendar {
-                                id: new_service_id.clone(),
-                                dates: new_dates,
-                            };
-                            collections.calendars.push(new_service)?;
-                        }
-                        new_service_id
-                    } else {
-                        corresponding_vj.service_id.clone()
-                    };
-                    let stop_times: Vec<NtfsStopTime> = corresponding_vj
-                        .stop_times
+                continue;
+            }
+            Some(st) => st.arrival_time,
+        };
+        while start_time < frequency.end_time {
+            trip_id_sequence
+                .entry(frequency.trip_id.clone())
+                .and_modify(|counter| *counter += 1)
+                .or_insert(0);
+            let generated_trip_id = format!(
+                "{}-{}",
+                frequency.trip_id, trip_id_sequence[&frequency.trip_id]
+            );
+            // the following handles generated trip starting after midnight, we need to generate a
+            // new service in case the next day is not covered
+            let nb_days = start_time.day_offset();
+            let service_id = if nb_days > 0 {
+                let service = collections
+                    .calendars
+                    .get(&corresponding_vj.service_id)
+                    .cloned()
+                    .unwrap();
+                let new_service_id = format!("{}:+{}days", service.id, nb_days);
+                if !collections.calendars.contains_id(&new_service_id) {
+                    let new_dates: BTreeSet<_> = service
+                        .dates
                         .iter()
-                        .map(|stop_time| NtfsStopTime {
-                            stop_point_idx: stop_time.stop_point_idx,
-                            sequence: stop_time.sequence,
-                            arrival_time: stop_time.arrival_time + start_time - arrival_time_delta,
-                            departure_time: stop_time.departure_time + start_time
-                                - arrival_time_delta,
-                            boarding_duration: stop_time.boarding_duration,
-                            alighting_duration: stop_time.alighting_duration,
-                            pickup_type: stop_time.pickup_type,
-                            drop_off_type: stop_time.drop_off_type,
-                            datetime_estimated,
-                            local_zone_id: stop_time.local_zone_id,
-                            precision: stop_time.precision.clone(),
-                        })
+                        .map(|d| *d + chrono::Duration::days(i64::from(nb_days)))
                         .collect();
-                    start_time = start_time + Time::new(0, 0, frequency.headway_secs);
-                    let generated_vj = VehicleJourney {
-                        id: generated_trip_id.clone(),
-                        service_id,
-                        stop_times,
-                        ..corresponding_vj.clone()
+
+                    let new_service = objects::Calendar {
+                        id: new_service_id.clone(),
+                        dates: new_dates,
                     };
-                    new_vehicle_journeys.push(generated_vj);
-                    let stop_time_comments: HashMap<(String, u32), String> = corresponding_vj
-                        .stop_times
-                        .iter()
-                        .filter(|stop_time| {
-                            stop_time.pickup_type == 2 || stop_time.drop_off_type == 2
-                        })
-                        .filter_map(|stop_time| {
-                            collections
-                                .stop_time_comments
-                                .get(&(frequency.trip_id.clone(), stop_time.sequence))
-                                .map(|comment_id| {
-                                    (
-                                        (generated_trip_id.clone(), stop_time.sequence),
-                                        comment_id.to_string(),
-                                    )
-                                })
-                        })
-                        .collect();
-                    let stop_time_ids: HashMap<(String, u32), String> = stop_time_comments
-                        .keys()
-                        .map(|(trip_id, sequence)| {
+                    collections.calendars.push(new_service)?;
+                }
+                new_service_id
+            } else {
+                corresponding_vj.service_id.clone()
+            };
+            // the generated trip runs `nb_days` later, so its times
+            // are shifted back by as many days
+            let arrival_time_delta = first_arrival_time + Time::new(24 * nb_days, 0, 0);
+            let stop_times: Vec<NtfsStopTime> = corresponding_vj
+                .stop_times
+                .iter()
+                .map(|stop_time| NtfsStopTime {
+                    stop_point_idx: stop_time.stop_point_idx,
+                    sequence: stop_time.sequence,
+                    arrival_time: stop_time.arrival_time + start_time - arrival_time_delta,
+                    departure_time: stop_time.departure_time + start_time - arrival_time_delta,
+                    boarding_duration: stop_time.boarding_duration,
+                    alighting_duration: stop_time.alighting_duration,
+                    pickup_type: stop_time.pickup_type,
+                    drop_off_type: stop_time.drop_off_type,
+                    datetime_estimated,
+                    local_zone_id: stop_time.local_zone_id,
+                    precision: stop_time.precision.clone(),
+                })
+                .collect();
+            start_time = start_time + Time::new(0, 0, frequency.headway_secs);
+            let generated_vj = VehicleJourney {
+                id: generated_trip_id.clone(),
+                service_id,
+                stop_times,
+                ..corresponding_vj.clone()
+            };
+            new_vehicle_journeys.push(generated_vj);
+            let stop_time_comments: HashMap<(String, u32), String> = corresponding_vj
+                .stop_times
+                .iter()
+                .filter(|stop_time| stop_time.pickup_type == 2 || stop_time.drop_off_type == 2)
+                .filter_map(|stop_time| {
+                    collections
+                        .stop_time_comments
+                        .get(&(frequency.trip_id.clone(), stop_time.sequence))
+                        .map(|comment_id| {
                             (
-                                (trip_id.to_string(), *sequence),
-                                format!("{}-{}", trip_id, sequence),
+                                (generated_trip_id.clone(), stop_time.sequence),
+                                comment_id.to_string(),
                             )
                         })
-                        .collect();
-                    collections.stop_time_comments.extend(stop_time_comments);
-                    collections.stop_time_ids.extend(stop_time_ids);
+                })
+                .collect();
+            let stop_time_ids: HashMap<(String, u32), String> = stop_time_comments
+                .keys()
+                .map(|(trip_id, sequence)| {
+                    (
+                        (trip_id.to_string(), *sequence),
+                        format!("{}-{}", trip_id, sequence),
+                    )
+                })
+                .collect();
+            collections.stop_time_comments.extend(stop_time_comments);
+            collections.stop_time_ids.extend(stop_time_ids);
+        }
+    }
+    let mut vehicle_journeys = collections.vehicle_journeys.take();
+    let trip_ids_to_remove: Vec<_> = gtfs_frequencies.iter().map(|f| &f.trip_id).collect();
+    vehicle_journeys.retain(|vj| !trip_ids_to_remove.contains(&&vj.id));
+    collections
+        .stop_time_ids
+        .retain(|(vj_id, _), _| !trip_ids_to_remove.contains(&&vj_id));
+    collections
+        .stop_time_comments
+        .retain(|(vj_id, _), _| !trip_ids_to_remove.contains(&&vj_id));
+
+    vehicle_journeys.append(&mut new_vehicle_journeys);
+    collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
+    Ok(())
+}
+
+// The UTC offset, in seconds, of the local datetime `local` in `timezone`;
+// the earliest one for an ambiguous local time (when the clocks go back),
+// the one an hour later for a skipped local time (when they go forward)
+fn utc_offset(timezone: Tz, local: &NaiveDateTime) -> i64 {
+    match timezone.offset_from_local_datetime(local) {
+        LocalResult::Single(offset) | LocalResult::Ambiguous(offset, _) => {
+            i64::from(offset.fix().local_minus_utc())
+        }
+        LocalResult::None => utc_offset(timezone, &(*local + chrono::Duration::hours(1))),
+    }
+}
+
+// The shift, in seconds, from `timezone` to `reference` of the times of a
+// vehicle journey leaving at `departure` on `date`
+fn timezone_shift(date: Date, departure: Time, timezone: Tz, reference: Tz) -> i64 {
+    let local =
+        date.and_hms(0, 0, 0) + chrono::Duration::seconds(i64::from(departure.total_seconds()));
+    let offset = utc_offset(timezone, &local);
+    let utc = local - chrono::Duration::seconds(offset);
+    let reference_offset = i64::from(
+        reference
+            .offset_from_utc_datetime(&utc)
+            .fix()
+            .local_minus_utc(),
+    );
+    reference_offset - offset
+}
+
+fn shift_time(time: Time, shift: i64) -> Time {
+    let seconds = (i64::from(time.total_seconds()) + shift).max(0);
+    Time::new(0, 0, seconds as u32)
+}
+
+// The identifier `<id>:tz<n>`, with the smallest `n` from `first` such that
+// the identifier is not used
+fn timezone_split_id<F>(id: &str, first: usize, is_used: F) -> String
+where
+    F: Fn(&str) -> bool,
+{
+    (first..)
+        .map(|n| format!("{}:tz{}", id, n))
+        .find(|split_id| !is_used(split_id))
+        .unwrap()
+}
+
+/// Normalizes the times of the vehicle journeys to the timezone of the
+/// first agency when the agencies have different timezones, the GTFS times
+/// being relative to the timezone of the agency.
+///
+/// The times of a vehicle journey of another timezone are shifted by the
+/// difference of the UTC offsets of the timezones on each date of its
+/// calendar (at its first departure). When a time would be before midnight,
+/// the vehicle journey runs on the previous days, with times shifted by 24
+/// hours. As the timezones may not change to or from daylight saving time
+/// on the same dates, a vehicle journey whose dates have different shifts
+/// is split: the vehicle journey keeps the dates of the most frequent
+/// shift, a copy with the identifier `<trip_id>:tz<n>` (with the first
+/// unused `n` from 1) is created for each other shift. Every network gets
+/// the timezone of the first agency.
+pub(in crate::gtfs) fn normalize_timezones(collections: &mut Collections) -> Result<()> {
+    let reference = match collections
+        .networks
+        .values()
+        .next()
+        .and_then(|network| network.timezone)
+    {
+        Some(reference) => reference,
+        None => return Ok(()),
+    };
+    let network_timezones: HashMap<String, Tz> = collections
+        .networks
+        .values()
+        .filter_map(|network| match network.timezone {
+            Some(timezone) if timezone != reference => Some((network.id.clone(), timezone)),
+            _ => None,
+        })
+        .collect();
+    if network_timezones.is_empty() {
+        return Ok(());
+    }
+    info!(
+        "Normalizing the times of the vehicle journeys to the timezone {}",
+        reference
+    );
+
+    let mut vehicle_journeys = collections.vehicle_journeys.take();
+    let mut vehicle_journey_ids: HashSet<String> =
+        vehicle_journeys.iter().map(|vj| vj.id.clone()).collect();
+    let mut frequencies = collections.frequencies.take();
+    let mut frequency_idxs: HashMap<String, Vec<usize>> = HashMap::new();
+    for (idx, frequency) in frequencies.iter().enumerate() {
+        frequency_idxs
+            .entry(frequency.vehicle_journey_id.clone())
+            .or_insert_with(Vec::new)
+            .push(idx);
+    }
+    let mut new_frequencies = vec![];
+    let mut new_vehicle_journeys = vec![];
+    let mut shifted_calendars: HashMap<(String, BTreeSet<Date>), String> = HashMap::new();
+    for vj in &mut vehicle_journeys {
+        let timezone = match collections
+            .routes
+            .get(&vj.route_id)
+            .and_then(|route| collections.lines.get(&route.line_id))
+            .and_then(|line| network_timezones.get(&line.network_id))
+        {
+            Some(timezone) => *timezone,
+            None => continue,
+        };
+        let departure = match vj.stop_times.first() {
+            Some(stop_time) => stop_time.departure_time,
+            None => continue,
+        };
+        let dates = match collections.calendars.get(&vj.service_id) {
+            Some(calendar) => calendar.dates.clone(),
+            None => continue,
+        };
+        let first_time = vj
+            .stop_times
+            .iter()
+            .map(|stop_time| stop_time.arrival_time.min(stop_time.departure_time))
+            .min()
+            .map_or(0, |time| i64::from(time.total_seconds()));
+
+        // the shifted dates by shift of the times
+        let mut dates_by_shift: BTreeMap<i64, BTreeSet<Date>> = BTreeMap::new();
+        for date in &dates {
+            let shift = timezone_shift(*date, departure, timezone, reference);
+            let days_before = if first_time + shift < 0 {
+                (-(first_time + shift) + 86_399) / 86_400
+            } else {
+                0
+            };
+            dates_by_shift
+                .entry(shift + days_before * 86_400)
+                .or_insert_with(BTreeSet::new)
+                .insert(*date - chrono::Duration::days(days_before));
+        }
+        let mut shifts: Vec<(i64, BTreeSet<Date>)> = dates_by_shift.into_iter().collect();
+        // stable sort, the smallest shift first on a tie
+        shifts.sort_by(|(_, dates1), (_, dates2)| dates2.len().cmp(&dates1.len()));
+
+        let original_vj = vj.clone();
+        let vj_frequency_idxs = frequency_idxs.remove(&vj.id).unwrap_or_else(Vec::new);
+        let original_frequencies: Vec<objects::Frequency> = vj_frequency_idxs
+            .iter()
+            .map(|idx| frequencies[*idx].clone())
+            .collect();
+        for (n, (shift, shifted_dates)) in shifts.into_iter().enumerate() {
+            let service_id = if shifted_dates == dates {
+                original_vj.service_id.clone()
+            } else {
+                let key = (original_vj.service_id.clone(), shifted_dates);
+                match shifted_calendars.get(&key) {
+                    Some(service_id) => service_id.clone(),
+                    None => {
+                        let calendars = &collections.calendars;
+                        let service_id = timezone_split_id(
+                            &original_vj.service_id,
+                            shifted_calendars.len(),
+                            |id| calendars.contains_id(id),
+                        );
+                        collections.calendars.push(objects::Calendar {
+                            id: service_id.clone(),
+                            dates: key.1.clone(),
+                        })?;
+                        shifted_calendars.insert(key, service_id.clone());
+                        service_id
+                    }
+                }
+            };
+            let stop_times = original_vj
+                .stop_times
+                .iter()
+                .map(|stop_time| NtfsStopTime {
+                    arrival_time: shift_time(stop_time.arrival_time, shift),
+                    departure_time: shift_time(stop_time.departure_time, shift),
+                    ..stop_time.clone()
+                })
+                .collect();
+            let shift_frequency = |frequency: &objects::Frequency| objects::Frequency {
+                vehicle_journey_id: frequency.vehicle_journey_id.clone(),
+                start_time: shift_time(frequency.start_time, shift),
+                end_time: shift_time(frequency.end_time, shift),
+                headway_secs: frequency.headway_secs,
+            };
+            if n == 0 {
+                vj.service_id = service_id;
+                vj.stop_times = stop_times;
+                for (idx, frequency) in vj_frequency_idxs.iter().zip(&original_frequencies) {
+                    frequencies[*idx] = shift_frequency(frequency);
                 }
+                continue;
             }
-            let mut vehicle_journeys = collections.vehicle_journeys.take();
-            let trip_ids_to_remove: Vec<_> = gtfs_frequencies.iter().map(|f| &f.trip_id).collect();
-            vehicle_journeys.retain(|vj| !trip_ids_to_remove.contains(&&vj.id));
-            collections
-                .stop_time_ids
-                .retain(|(vj_id, _), _| !trip_ids_to_remove.contains(&&vj_id));
-            collections
-                .stop_time_comments
-                .retain(|(vj_id, _), _| !trip_ids_to_remove.contains(&&vj_id));
-
-            vehicle_journeys.append(&mut new_vehicle_journeys);
-            collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
-            Ok(())
+            let vj_id =
+                timezone_split_id(&original_vj.id, n, |id| vehicle_journey_ids.contains(id));
+            vehicle_journey_ids.insert(vj_id.clone());
+            for frequency in &original_frequencies {
+                new_frequencies.push(objects::Frequency {
+                    vehicle_journey_id: vj_id.clone(),
+                    ..shift_frequency(frequency)
+                });
+            }
+            for stop_time in &original_vj.stop_times {
+                let key = (original_vj.id.clone(), stop_time.sequence);
+                let new_key = (vj_id.clone(), stop_time.sequence);
+                if let Some(headsign) = collections.stop_time_headsigns.get(&key).cloned() {
+                    collections
+                        .stop_time_headsigns
+                        .insert(new_key.clone(), headsign);
+                }
+                if let Some(stop_time_id) = collections.stop_time_ids.get(&key).cloned() {
+                    collections
+                        .stop_time_ids
+                        .insert(new_key.clone(), stop_time_id);
+                }
+                if let Some(comment_id) = collections.stop_time_comments.get(&key).cloned() {
+                    collections
+                        .stop_time_comments
+                        .insert(new_key.clone(), comment_id);
+                }
+                if let Some(continuous_stopping) = collections
+                    .stop_time_continuous_stoppings
+                    .get(&key)
+                    .copied()
+                {
+                    collections
+                        .stop_time_continuous_stoppings
+                        .insert(new_key, continuous_stopping);
+                }
+            }
+            new_vehicle_journeys.push(VehicleJourney {
+                id: vj_id,
+                service_id,
+                stop_times,
+                ..original_vj.clone()
+            });
         }
     }
+    vehicle_journeys.append(&mut new_vehicle_journeys);
+    collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
+    frequencies.append(&mut new_frequencies);
+    collections.frequencies = Collection::new(frequencies);
+
+    let network_idxs: Vec<_> = collections.networks.iter().map(|(idx, _)| idx).collect();
+    for idx in network_idxs {
+        collections.networks.index_mut(idx).timezone = Some(reference);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1381,7 +2855,7 @@ mod tests {
     use super::*;
     use crate::{
         calendars,
-        gtfs::read::EquipmentList,
+        gtfs::{read::EquipmentList, RouteTypeMapping},
         model::Collections,
         objects::*,
         objects::{Calendar, Comment, CommentType, Equipment, Geometry, Rgb, StopTime, Transfer},
@@ -1419,6 +2893,42 @@ mod tests {
         });
     }
 
+    #[test]
+    fn read_feed_lang() {
+        let feed_info_content = "feed_publisher_name,feed_publisher_url,feed_lang\n\
+                                 My publisher,http://my-publisher.com,fr-FR";
+
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "feed_info.txt", feed_info_content);
+            let mut collections = Collections::default();
+            super::manage_feed_lang(&mut handler, &mut collections).unwrap();
+            assert_eq!(Some("fr-FR"), collections.feed_language());
+
+            // the configured language takes precedence
+            let mut collections = Collections::default();
+            collections
+                .feed_infos
+                .insert("feed_lang".to_string(), "en".to_string());
+            super::manage_feed_lang(&mut handler, &mut collections).unwrap();
+            assert_eq!(Some("en"), collections.feed_language());
+        });
+    }
+
+    #[test]
+    fn ignore_invalid_feed_lang() {
+        let feed_info_content = "feed_publisher_name,feed_publisher_url,feed_lang\n\
+                                 My publisher,http://my-publisher.com,fr_FR";
+
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "feed_info.txt", feed_info_content);
+            let mut collections = Collections::default();
+            super::manage_feed_lang(&mut handler, &mut collections).unwrap();
+            assert_eq!(None, collections.feed_language());
+        });
+    }
+
     #[test]
     fn load_standard_agency() {
         let agency_content = "agency_id,agency_name,agency_url,agency_timezone\n\
@@ -1448,6 +2958,11 @@ mod tests {
             assert_eq!(1, networks.len());
             let network = networks.iter().next().unwrap().1;
             assert_eq!("id_1", network.id);
+            assert_eq!(
+                Some("http://my-agency_fare_url.com"),
+                network.fare_url.as_deref()
+            );
+            assert_eq!(Some("my-mail@example.com"), network.email.as_deref());
             assert_eq!(1, companies.len());
         });
     }
@@ -1481,13 +2996,168 @@ mod tests {
                 assert_eq!(captured_logs.len(), 2);
                 assert_eq!(
                     captured_logs[1].body,
-                    "different agency timezone: Europe/London (id_1) - Europe/Paris (id_2)"
+                    "different agency timezone: Europe/London (id_1) - Europe/Paris (id_2), the times are normalized to Europe/London"
                 );
                 assert_eq!(captured_logs[1].level, LogLevel::Warn);
             });
         });
     }
 
+    mod normalize_timezones {
+        use super::*;
+        use crate::model_builder::ModelBuilder;
+        use chrono_tz::{America::New_York, Europe::Paris, Pacific::Auckland};
+
+        fn collections(timezone: Tz, dates: &[Date]) -> Collections {
+            let model = ModelBuilder::default()
+                .line("l1", |line| {
+                    line.network_id = "paris".to_string();
+                })
+                .line("l2", |line| {
+                    line.network_id = "other".to_string();
+                })
+                .route("r1", |route| {
+                    route.line_id = "l1".to_string();
+                })
+                .route("r2", |route| {
+                    route.line_id = "l2".to_string();
+                })
+                .calendar("c1", |calendar| {
+                    calendar.dates.extend(dates);
+                })
+                .vj("vj1", |vj| {
+                    vj.route("r1")
+                        .calendar("c1")
+                        .st("A", "10:00")
+                        .st("B", "10:30");
+                })
+                .vj("vj2", |vj| {
+                    vj.route("r2")
+                        .calendar("c1")
+                        .st("C", "05:00")
+                        .st("D", "10:30");
+                })
+                .build();
+            let mut collections = model.into_collections();
+            for (id, timezone) in &[("paris", Paris), ("other", timezone)] {
+                let idx = collections.networks.get_idx(id).unwrap();
+                collections.networks.index_mut(idx).timezone = Some(*timezone);
+            }
+            collections
+        }
+
+        fn departures(collections: &Collections, vj_id: &str) -> (Time, Vec<Date>) {
+            let vj = collections.vehicle_journeys.get(vj_id).unwrap();
+            let dates = collections
+                .calendars
+                .get(&vj.service_id)
+                .unwrap()
+                .dates
+                .clone();
+            (vj.stop_times[0].departure_time, dates.into_iter().collect())
+        }
+
+        #[test]
+        fn split_over_dst_changes() {
+            // the DST starts on March 8 2020 in New York, on March 29 in Paris
+            let dates = [
+                Date::from_ymd(2020, 3, 7),
+                Date::from_ymd(2020, 3, 10),
+                Date::from_ymd(2020, 3, 30),
+            ];
+            let mut collections = collections(New_York, &dates);
+            super::super::normalize_timezones(&mut collections).unwrap();
+
+            assert_eq!(
+                (Time::new(10, 0, 0), dates.to_vec()),
+                departures(&collections, "vj1")
+            );
+            assert_eq!(
+                (Time::new(11, 0, 0), vec![dates[0], dates[2]]),
+                departures(&collections, "vj2")
+            );
+            assert_eq!(
+                (Time::new(10, 0, 0), vec![dates[1]]),
+                departures(&collections, "vj2:tz1")
+            );
+            let vj2 = collections.vehicle_journeys.get("vj2:tz1").unwrap();
+            assert_eq!(Time::new(15, 30, 0), vj2.stop_times[1].arrival_time);
+            assert!(collections
+                .networks
+                .values()
+                .all(|network| network.timezone == Some(Paris)));
+        }
+
+        #[test]
+        fn split_with_unused_identifiers() {
+            let dates = [
+                Date::from_ymd(2020, 3, 7),
+                Date::from_ymd(2020, 3, 10),
+                Date::from_ymd(2020, 3, 30),
+            ];
+            let mut collections = collections(New_York, &dates);
+            collections
+                .vehicle_journeys
+                .push(VehicleJourney {
+                    id: "vj2:tz1".to_string(),
+                    ..collections.vehicle_journeys.get("vj1").unwrap().clone()
+                })
+                .unwrap();
+            collections
+                .calendars
+                .push(objects::Calendar {
+                    id: "c1:tz0".to_string(),
+                    dates: BTreeSet::new(),
+                })
+                .unwrap();
+            super::super::normalize_timezones(&mut collections).unwrap();
+
+            assert_eq!(
+                (Time::new(11, 0, 0), vec![dates[0], dates[2]]),
+                departures(&collections, "vj2")
+            );
+            assert_eq!(
+                (Time::new(10, 0, 0), vec![dates[1]]),
+                departures(&collections, "vj2:tz2")
+            );
+            assert_eq!(
+                "c1:tz1",
+                collections
+                    .vehicle_journeys
+                    .get("vj2:tz2")
+                    .unwrap()
+                    .service_id
+            );
+        }
+
+        #[test]
+        fn shift_to_previous_day() {
+            let dates = [Date::from_ymd(2020, 1, 15)];
+            let mut collections = collections(Auckland, &dates);
+            super::super::normalize_timezones(&mut collections).unwrap();
+
+            // 05:00 in Auckland (UTC+13) is 17:00 the day before in Paris
+            // (UTC+1)
+            assert_eq!(
+                (Time::new(17, 0, 0), vec![Date::from_ymd(2020, 1, 14)]),
+                departures(&collections, "vj2")
+            );
+            assert_eq!(2, collections.vehicle_journeys.len());
+        }
+
+        #[test]
+        fn same_timezones() {
+            let dates = [Date::from_ymd(2020, 1, 15)];
+            let mut collections = collections(Paris, &dates);
+            super::super::normalize_timezones(&mut collections).unwrap();
+
+            assert_eq!(
+                (Time::new(5, 0, 0), dates.to_vec()),
+                departures(&collections, "vj2")
+            );
+        }
+    }
+
     #[test]
     fn load_one_stop_point() {
         let stops_content = "stop_id,stop_name,stop_code,stop_lat,stop_lon\n\
@@ -1642,7 +3312,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             assert_eq!(4, collections.lines.len());
             assert_eq!(
                 vec!["agency_1", "agency_2", "agency_3", "agency_4"],
@@ -1679,6 +3349,86 @@ mod tests {
         });
     }
 
+    #[test]
+    fn gtfs_routes_with_route_type_mappings() {
+        let routes_content = "route_id,agency_id,route_short_name,route_long_name,route_type\n\
+                              route_1,agency_1,1,My line 1,3\n\
+                              route_2,agency_1,2,My line 2,715\n\
+                              route_3,agency_1,3,My line 3,1500\n\
+                              route_4,agency_1,4,My line 4,1700";
+
+        let trips_content = "trip_id,route_id,direction_id,service_id\n\
+                             1,route_1,0,service_1\n\
+                             2,route_2,0,service_1\n\
+                             3,route_2,1,service_1\n\
+                             4,route_3,0,service_1\n\
+                             5,route_4,0,service_1";
+
+        let mut route_type_mappings = RouteTypeMappings::new();
+        route_type_mappings.insert(
+            715,
+            RouteTypeMapping {
+                physical_mode: "Bus".to_string(),
+                commercial_mode: "OnDemandBus".to_string(),
+                commercial_mode_name: Some("On demand bus".to_string()),
+            },
+        );
+        route_type_mappings.insert(
+            1500,
+            RouteTypeMapping {
+                physical_mode: "Taxi".to_string(),
+                commercial_mode: "Taxi".to_string(),
+                commercial_mode_name: None,
+            },
+        );
+
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "routes.txt", routes_content);
+            create_file_with_content(path, "trips.txt", trips_content);
+            let mut collections = Collections::default();
+            let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
+            collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
+            collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
+            super::read_routes(&mut handler, &mut collections, &route_type_mappings).unwrap();
+
+            let line_mode = |id| {
+                collections
+                    .lines
+                    .get(id)
+                    .unwrap()
+                    .commercial_mode_id
+                    .as_str()
+            };
+            assert_eq!("Bus", line_mode("route_1"));
+            assert_eq!("OnDemandBus", line_mode("route_2"));
+            assert_eq!("Taxi", line_mode("route_3"));
+            // not in the mappings, the default mapping is kept
+            assert_eq!("UnknownMode", line_mode("route_4"));
+            assert_eq!(
+                "On demand bus",
+                collections
+                    .commercial_modes
+                    .get("OnDemandBus")
+                    .unwrap()
+                    .name
+            );
+
+            let vj_mode = |id| {
+                collections
+                    .vehicle_journeys
+                    .get(id)
+                    .unwrap()
+                    .physical_mode_id
+                    .as_str()
+            };
+            assert_eq!("Bus", vj_mode("2"));
+            assert_eq!("Bus", vj_mode("3"));
+            assert_eq!("Taxi", vj_mode("4"));
+            assert_eq!("Bus", vj_mode("5"));
+        });
+    }
+
     #[test]
     fn gtfs_routes_without_agency_id_as_line() {
         let agency_content = "agency_id,agency_name,agency_url,agency_timezone\n\
@@ -1711,7 +3461,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             assert_eq!(3, collections.lines.len());
 
             assert_eq!(5, collections.routes.len());
@@ -1754,7 +3504,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             assert_eq!(3, collections.lines.len());
             assert_eq!(
                 vec![
@@ -1826,7 +3576,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
         });
     }
 
@@ -1857,7 +3607,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
         });
     }
 
@@ -1892,7 +3642,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
 
             assert_eq!(3, collections.lines.len());
             assert_eq!(
@@ -1935,7 +3685,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
 
             assert_eq!(2, collections.lines.len());
 
@@ -1968,7 +3718,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
 
             assert_eq!(2, collections.lines.len());
             assert_eq!(vec!["route_1", "route_3"], extract_ids(&collections.lines));
@@ -2002,7 +3752,7 @@ mod tests {
             let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             assert_eq!(1, collections.lines.len());
             assert_eq!(1, collections.routes.len());
         });
@@ -2072,7 +3822,7 @@ mod tests {
             collections.networks = networks;
             collections.companies = companies;
             collections.comments = comments;
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             super::manage_shapes(&mut collections, &mut handler).unwrap();
             calendars::manage_calendars(&mut handler, &mut collections).unwrap();
 
@@ -2241,7 +3991,7 @@ mod tests {
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             assert_eq!(3, collections.lines.len());
             assert_eq!(3, collections.routes.len());
             assert_eq!(3, collections.vehicle_journeys.len());
@@ -2282,7 +4032,7 @@ mod tests {
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             assert_eq!(3, collections.lines.len());
             assert_eq!(3, collections.routes.len());
             assert_eq!(3, collections.vehicle_journeys.len());
@@ -2315,7 +4065,7 @@ mod tests {
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             assert_eq!(3, collections.lines.len());
             assert_eq!(3, collections.routes.len());
 
@@ -2331,13 +4081,43 @@ mod tests {
     }
 
     #[test]
-    fn gtfs_trips_with_no_accessibility_information() {
+    fn gtfs_trips_with_no_accessibility_information() {
+        let routes_content = "route_id,agency_id,route_short_name,route_long_name,route_type,route_color,route_text_color\n\
+                              route_1,agency_1,1,My line 1,3,8F7A32,FFFFFF";
+        let trips_content =
+            "trip_id,route_id,direction_id,service_id,wheelchair_accessible,bikes_allowed\n\
+             1,route_1,0,service_1,,\n\
+             2,route_1,0,service_2,,";
+
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "routes.txt", routes_content);
+            create_file_with_content(path, "trips.txt", trips_content);
+
+            let mut collections = Collections::default();
+            let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
+            collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
+            collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
+
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
+            assert_eq!(2, collections.vehicle_journeys.len());
+            assert_eq!(0, collections.trip_properties.len());
+            for vj in collections.vehicle_journeys.values() {
+                assert!(vj.trip_property_id.is_none());
+            }
+        });
+    }
+
+    #[test]
+    fn gtfs_trips_with_trip_property_extension_columns() {
         let routes_content = "route_id,agency_id,route_short_name,route_long_name,route_type,route_color,route_text_color\n\
                               route_1,agency_1,1,My line 1,3,8F7A32,FFFFFF";
         let trips_content =
-            "trip_id,route_id,direction_id,service_id,wheelchair_accessible,bikes_allowed\n\
-             1,route_1,0,service_1,,\n\
-             2,route_1,0,service_2,,";
+            "trip_id,route_id,service_id,wheelchair_accessible,air_conditioned,visual_announcement,school_vehicle_type\n\
+             1,route_1,service_1,1,1,,\n\
+             2,route_1,service_1,1,,,\n\
+             3,route_1,service_1,,,2,1\n\
+             4,route_1,service_1,1,1,,";
 
         test_in_tmp_dir(|path| {
             let mut handler = PathFileHandler::new(path.to_path_buf());
@@ -2349,12 +4129,33 @@ mod tests {
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
-            assert_eq!(2, collections.vehicle_journeys.len());
-            assert_eq!(0, collections.trip_properties.len());
-            for vj in collections.vehicle_journeys.values() {
-                assert!(vj.trip_property_id.is_none());
-            }
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
+            assert_eq!(3, collections.trip_properties.len());
+            let trip_property_of = |vj_id: &str| {
+                let vj = collections.vehicle_journeys.get(vj_id).unwrap();
+                collections
+                    .trip_properties
+                    .get(vj.trip_property_id.as_ref().unwrap())
+                    .unwrap()
+            };
+            assert_eq!(trip_property_of("1"), trip_property_of("4"));
+            assert_eq!(
+                Availability::Available,
+                trip_property_of("1").air_conditioned
+            );
+            assert_eq!(
+                Availability::InformationNotAvailable,
+                trip_property_of("2").air_conditioned
+            );
+            let school_trip_property = trip_property_of("3");
+            assert_eq!(
+                Availability::NotAvailable,
+                school_trip_property.visual_announcement
+            );
+            assert_eq!(
+                TransportType::ExclusiveSchool,
+                school_trip_property.school_vehicle_type
+            );
         });
     }
 
@@ -2536,7 +4337,7 @@ mod tests {
                 super::read_stops(&mut handler, &mut comments, &mut equipments).unwrap();
             collections.stop_points = stop_points;
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             super::manage_stop_times(&mut collections, &mut handler, false, None).unwrap();
 
             assert_eq!(
@@ -2622,7 +4423,7 @@ mod tests {
                 super::read_stops(&mut handler, &mut comments, &mut equipments).unwrap();
             collections.stop_points = stop_points;
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             super::manage_stop_times(&mut collections, &mut handler, false, None).unwrap();
 
             assert_eq!(
@@ -2662,6 +4463,68 @@ mod tests {
         });
     }
 
+    #[test]
+    fn gtfs_stop_times_continuous_stoppings() {
+        let routes_content = "route_id,agency_id,route_short_name,route_long_name,route_type\n\
+                              route_1,agency_1,1,My line 1,3";
+        let stops_content = "stop_id,stop_name,stop_lat,stop_lon,location_type\n\
+                             sp:01,my stop point name 1,0.1,1.2,0\n\
+                             sp:02,my stop point name 2,0.2,1.5,0\n\
+                             sp:03,my stop point name 3,0.3,1.8,0";
+        let trips_content = "trip_id,route_id,service_id\n\
+                             1,route_1,service_1";
+        let stop_times_content = "trip_id,arrival_time,departure_time,stop_id,stop_sequence,continuous_pickup,continuous_drop_off\n\
+                                  1,06:00:00,06:00:00,sp:01,1,0,\n\
+                                  1,06:10:00,06:10:00,sp:02,2,5,\n\
+                                  1,06:20:00,06:20:00,sp:03,3,2,3";
+
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "routes.txt", routes_content);
+            create_file_with_content(path, "trips.txt", trips_content);
+            create_file_with_content(path, "stop_times.txt", stop_times_content);
+            create_file_with_content(path, "stops.txt", stops_content);
+
+            let mut collections = Collections::default();
+            let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
+            collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
+            collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
+            let mut comments: CollectionWithId<Comment> = CollectionWithId::default();
+            let mut equipments = EquipmentList::default();
+            let (_, stop_points, _) =
+                super::read_stops(&mut handler, &mut comments, &mut equipments).unwrap();
+            collections.stop_points = stop_points;
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
+            super::manage_stop_times(&mut collections, &mut handler, false, None).unwrap();
+
+            // the invalid value of the second stop time is ignored
+            let mut continuous_stoppings: Vec<_> = collections
+                .stop_time_continuous_stoppings
+                .into_iter()
+                .collect();
+            continuous_stoppings.sort_by_key(|((_, sequence), _)| *sequence);
+            assert_eq!(
+                vec![
+                    (
+                        ("1".to_string(), 1),
+                        ContinuousStopping {
+                            pickup: ContinuousStoppingType::Continuous,
+                            drop_off: ContinuousStoppingType::NotContinuous,
+                        }
+                    ),
+                    (
+                        ("1".to_string(), 3),
+                        ContinuousStopping {
+                            pickup: ContinuousStoppingType::PhoneAgency,
+                            drop_off: ContinuousStoppingType::CoordinateWithDriver,
+                        }
+                    ),
+                ],
+                continuous_stoppings
+            );
+        });
+    }
+
     #[test]
     fn read_tranfers() {
         let stops_content = "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station,wheelchair_boarding\n\
@@ -2935,7 +4798,7 @@ mod tests {
             collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
             collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             // physical mode file should contain only three modes
             // (5,7 => funicular; 2 => train; 6 => suspended cable car)
             assert_eq!(4, collections.lines.len());
@@ -3046,7 +4909,7 @@ mod tests {
                 super::read_stops(&mut handler, &mut comments, &mut equipments).unwrap();
             collections.stop_points = stop_points;
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             super::manage_stop_times(&mut collections, &mut handler, false, None).unwrap();
 
             assert_eq!(
@@ -3117,7 +4980,7 @@ mod tests {
                 super::read_stops(&mut handler, &mut comments, &mut equipments).unwrap();
             collections.stop_points = stop_points;
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             let val = super::manage_stop_times(&mut collections, &mut handler, false, None);
 
             // the first stop time of the vj has no departure/arrival, it's an error
@@ -3125,6 +4988,60 @@ mod tests {
             assert_eq!( "the first stop time of the vj '1' has no departure/arrival, the stop_times.txt file is not valid",format!("{}", err));
         });
     }
+    #[test]
+    fn lenient_stop_times_skip_unknown_references() {
+        let routes_content = "route_id,agency_id,route_short_name,route_long_name,route_type\n\
+                              route_1,agency_1,1,My line 1,3";
+
+        let stops_content = "stop_id,stop_name,stop_lat,stop_lon,location_type\n\
+                             sp:01,my stop point name 1,0.1,1.2,0\n\
+                             sp:02,my stop point name 2,0.2,1.5,0";
+
+        let trips_content = "trip_id,route_id,service_id\n\
+                             1,route_1,service_1";
+
+        let stop_times_content = "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+                                  1,06:00:00,06:00:00,sp:01,1\n\
+                                  1,06:05:00,06:05:00,sp:99,2\n\
+                                  1,06:10:00,06:10:00,sp:02,3\n\
+                                  2,07:00:00,07:00:00,sp:01,1\n\
+                                  1,not a time,06:20:00,sp:02,4";
+
+        test_in_tmp_dir(|path| {
+            create_file_with_content(path, "routes.txt", routes_content);
+            create_file_with_content(path, "trips.txt", trips_content);
+            create_file_with_content(path, "stop_times.txt", stop_times_content);
+            create_file_with_content(path, "stops.txt", stops_content);
+
+            let read_stop_times = |read_mode| {
+                let mut handler =
+                    PathFileHandler::new(path.to_path_buf()).with_read_mode(read_mode);
+                let mut collections = Collections::default();
+                let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
+                collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
+                collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
+                let mut comments: CollectionWithId<Comment> = CollectionWithId::default();
+                let mut equipments = EquipmentList::default();
+                let (_, stop_points, _) =
+                    super::read_stops(&mut handler, &mut comments, &mut equipments).unwrap();
+                collections.stop_points = stop_points;
+                super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new())
+                    .unwrap();
+                super::manage_stop_times(&mut collections, &mut handler, false, None)
+                    .map(|_| collections)
+            };
+
+            assert!(read_stop_times(read_utils::ReadMode::Strict).is_err());
+
+            let collections = read_stop_times(read_utils::ReadMode::Lenient).unwrap();
+            let stop_times = &collections.vehicle_journeys.get("1").unwrap().stop_times;
+            assert_eq!(
+                vec![1, 3],
+                stop_times.iter().map(|st| st.sequence).collect::<Vec<_>>()
+            );
+        });
+    }
+
     #[test]
     fn stop_location_on_stops() {
         let stops_content =
@@ -3250,7 +5167,7 @@ mod tests {
                 super::read_stops(&mut handler, &mut comments, &mut equipments).unwrap();
             collections.stop_points = stop_points;
 
-            super::read_routes(&mut handler, &mut collections).unwrap();
+            super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new()).unwrap();
             super::manage_stop_times(&mut collections, &mut handler, true, None).unwrap();
 
             assert_eq!(
@@ -3299,4 +5216,611 @@ mod tests {
             );
         });
     }
+
+    // A GTFS whose only route has the trips of `trips_content`, running on
+    // the first week of 2018, with the stops of `stops_content`
+    fn create_stream_gtfs(path: &std::path::Path, stops_content: &str, trips_content: &str) {
+        create_file_with_content(
+            path,
+            "agency.txt",
+            "agency_id,agency_name,agency_url,agency_timezone\n\
+             agency_1,My agency,http://my-agency_url.com,Europe/Paris",
+        );
+        create_file_with_content(
+            path,
+            "routes.txt",
+            "route_id,agency_id,route_short_name,route_long_name,route_type\n\
+             route_1,agency_1,R1,My route,3",
+        );
+        create_file_with_content(
+            path,
+            "calendar.txt",
+            "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n\
+             service_1,1,1,1,1,1,1,1,20180101,20180107",
+        );
+        create_file_with_content(path, "stops.txt", stops_content);
+        create_file_with_content(path, "trips.txt", trips_content);
+    }
+
+    fn stream_configuration(prefix_conf: Option<PrefixConfiguration>) -> Configuration {
+        let (contributor, dataset, feed_infos) = read_utils::read_config(None::<&str>).unwrap();
+        Configuration {
+            contributor,
+            dataset,
+            feed_infos,
+            prefix_conf,
+            on_demand_transport: false,
+            on_demand_transport_comment: None,
+            route_type_mappings: BTreeMap::new(),
+            keep_frequencies: false,
+            crs: None,
+            company_parents: Default::default(),
+            network_contacts: Default::default(),
+            read_mode: Default::default(),
+            trip_operators: false,
+        }
+    }
+
+    #[test]
+    fn stream_stop_times_by_trip() {
+        let stops_content = "stop_id,stop_name,stop_lat,stop_lon,location_type\n\
+                             sp:01,my stop point 1,0.1,1.2,0\n\
+                             sp:02,my stop point 2,0.2,1.5,0\n\
+                             sa:03,my stop area,0.3,2.2,1";
+        let trips_content = "trip_id,route_id,service_id\n\
+                             1,route_1,service_1\n\
+                             2,route_1,service_1";
+        let stop_times_content =
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence,stop_headsign,continuous_pickup\n\
+             1,06:00:00,06:00:00,sp:01,1,,0\n\
+             1,06:10:00,06:10:00,sp:02,2,,\n\
+             2,07:10:00,07:10:00,sp:02,2,my headsign,\n\
+             2,07:00:00,07:00:00,sp:01,1,,";
+
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_stream_gtfs(path, stops_content, trips_content);
+            create_file_with_content(path, "stop_times.txt", stop_times_content);
+
+            let mut prefix_conf = PrefixConfiguration::default();
+            prefix_conf.set_data_prefix("my_prefix");
+            let configuration = stream_configuration(Some(prefix_conf));
+            let mut collections =
+                super::super::read_stop_times_references(&mut handler, &configuration).unwrap();
+            let mut output = vec![];
+            super::stream_stop_times(
+                &mut collections,
+                &mut handler,
+                &mut output,
+                &configuration,
+                STOP_TIMES_BATCH_SIZE,
+            )
+            .unwrap();
+            assert_eq!(
+                "stop_id,trip_id,stop_sequence,arrival_time,departure_time,boarding_duration,\
+                 alighting_duration,pickup_type,drop_off_type,datetime_estimated,local_zone_id,\
+                 stop_headsign,stop_time_id,stop_time_precision,continuous_pickup,continuous_drop_off\n\
+                 my_prefix:sp:01,my_prefix:1,1,06:00:00,06:00:00,0,0,0,1,0,,,,0,0,1\n\
+                 my_prefix:sp:02,my_prefix:1,2,06:10:00,06:10:00,0,0,1,0,0,,,,0,,\n\
+                 my_prefix:sp:01,my_prefix:2,1,07:00:00,07:00:00,0,0,0,1,0,,,,0,,\n\
+                 my_prefix:sp:02,my_prefix:2,2,07:10:00,07:10:00,0,0,1,0,0,,my headsign,,0,,\n",
+                String::from_utf8(output).unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn stream_stop_times_not_grouped_by_trip() {
+        let stops_content = "stop_id,stop_name,stop_lat,stop_lon\n\
+                             sp:01,my stop point 1,0.1,1.2\n\
+                             sp:02,my stop point 2,0.2,1.5";
+        let trips_content = "trip_id,route_id,service_id\n\
+                             1,route_1,service_1\n\
+                             2,route_1,service_1";
+        let stop_times_content = "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+                                  1,06:00:00,06:00:00,sp:01,1\n\
+                                  2,07:00:00,07:00:00,sp:01,1\n\
+                                  1,06:10:00,06:10:00,sp:02,2";
+
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_stream_gtfs(path, stops_content, trips_content);
+            create_file_with_content(path, "stop_times.txt", stop_times_content);
+
+            let configuration = stream_configuration(None);
+            let mut collections =
+                super::super::read_stop_times_references(&mut handler, &configuration).unwrap();
+            let mut output = vec![];
+            let error = super::stream_stop_times(
+                &mut collections,
+                &mut handler,
+                &mut output,
+                &configuration,
+                STOP_TIMES_BATCH_SIZE,
+            )
+            .unwrap_err();
+            assert_eq!(
+                "stop times of trip_id=\"1\" are not contiguous in stop_times.txt, they can't be streamed",
+                format!("{}", error)
+            );
+        });
+    }
+
+    #[test]
+    fn stream_stop_times_of_a_block_in_a_batch() {
+        let stops_content = "stop_id,stop_name,stop_lat,stop_lon\n\
+                             sp:01,my stop point 1,0.1,1.2\n\
+                             sp:02,my stop point 2,0.2,1.5\n\
+                             sp:03,my stop point 3,0.3,1.8";
+        let trips_content = "trip_id,route_id,service_id,block_id\n\
+                             1,route_1,service_1,block_1\n\
+                             2,route_1,service_1,\n\
+                             3,route_1,service_1,block_1";
+        let stop_times_content = "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+                                  1,06:00:00,06:00:00,sp:01,1\n\
+                                  1,06:05:00,06:05:00,sp:03,2\n\
+                                  1,06:10:00,06:10:00,sp:02,3\n\
+                                  2,07:00:00,07:00:00,sp:01,1\n\
+                                  2,07:10:00,07:10:00,sp:02,2\n\
+                                  3,06:20:00,06:20:00,sp:03,1\n\
+                                  3,06:30:00,06:30:00,sp:01,2";
+
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_stream_gtfs(path, stops_content, trips_content);
+            create_file_with_content(path, "stop_times.txt", stop_times_content);
+
+            let configuration = stream_configuration(None);
+            let mut collections =
+                super::super::read_stop_times_references(&mut handler, &configuration).unwrap();
+            let mut output = vec![];
+            // the trip 1 waits for the trip 3 of its block, so the stay-in
+            // between them is allowed as when all the stop times are read
+            let stop_points_used = super::stream_stop_times(
+                &mut collections,
+                &mut handler,
+                &mut output,
+                &configuration,
+                1,
+            )
+            .unwrap();
+            assert_eq!(
+                "stop_id,trip_id,stop_sequence,arrival_time,departure_time,boarding_duration,\
+                 alighting_duration,pickup_type,drop_off_type,datetime_estimated,local_zone_id,\
+                 stop_headsign,stop_time_id,stop_time_precision,continuous_pickup,continuous_drop_off\n\
+                 sp:01,2,1,07:00:00,07:00:00,0,0,0,1,0,,,,0,,\n\
+                 sp:02,2,2,07:10:00,07:10:00,0,0,1,0,0,,,,0,,\n\
+                 sp:01,1,1,06:00:00,06:00:00,0,0,0,1,0,,,,0,,\n\
+                 sp:03,1,2,06:05:00,06:05:00,0,0,0,0,0,,,,0,,\n\
+                 sp:02,1,3,06:10:00,06:10:00,0,0,0,0,0,,,,0,,\n\
+                 sp:03,3,1,06:20:00,06:20:00,0,0,0,0,0,,,,0,,\n\
+                 sp:01,3,2,06:30:00,06:30:00,0,0,1,0,0,,,,0,,\n",
+                String::from_utf8(output).unwrap()
+            );
+            assert_eq!(3, stop_points_used.len());
+            // only the first and last stop times of the trips are kept
+            let vj = collections.vehicle_journeys.get("1").unwrap();
+            assert_eq!(
+                vec![1, 3],
+                vj.stop_times
+                    .iter()
+                    .map(|st| st.sequence)
+                    .collect::<Vec<_>>()
+            );
+        });
+    }
+
+    #[test]
+    fn read_translations() {
+        let stops_content = "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n\
+                             sp_1,Gare,0.1,1.2,0,sa_1\n\
+                             sa_1,Gare,0.1,1.2,1,";
+        let translations_content =
+            "table_name,field_name,language,translation,record_id,field_value\n\
+             stops,stop_name,en,Station,sa_1,\n\
+             routes,route_long_name,en,Blue line,,Ligne bleue\n\
+             stops,stop_desc,en,Description,sa_1,\n\
+             stops,stop_name,en,Unknown,unknown,";
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "stops.txt", stops_content);
+            create_file_with_content(path, "translations.txt", translations_content);
+            let mut collections = Collections::default();
+            let mut equipments = EquipmentList::default();
+            let (stop_areas, stop_points, _) =
+                super::read_stops(&mut handler, &mut collections.comments, &mut equipments)
+                    .unwrap();
+            collections.stop_areas = stop_areas;
+            collections.stop_points = stop_points;
+            collections.lines = CollectionWithId::from(Line {
+                id: "line_1".to_string(),
+                name: "Ligne bleue".to_string(),
+                ..Default::default()
+            });
+
+            super::manage_translations(&mut collections, &mut handler).unwrap();
+            let translations: Vec<_> = collections
+                .translations
+                .values()
+                .map(|t| {
+                    (
+                        t.object_type.clone(),
+                        t.object_id.as_str(),
+                        t.field_name.as_str(),
+                        t.translation.as_str(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                vec![
+                    (ObjectType::StopArea, "sa_1", "stop_name", "Station"),
+                    (ObjectType::Line, "line_1", "line_name", "Blue line"),
+                ],
+                translations
+            );
+        });
+    }
+
+    #[test]
+    fn read_attributions() {
+        let attributions_content = "attribution_id,agency_id,route_id,trip_id,organization_name,is_producer,is_operator,is_authority,attribution_url\n\
+             1,,route_1,,Operator Inc,0,1,0,http://operator.example\n\
+             2,,,vj_2,Producer SA,1,,,\n\
+             3,,,,Authority,0,0,1,\n\
+             4,,,unknown,Nobody,1,0,0,";
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "attributions.txt", attributions_content);
+            let mut collections = Collections::default();
+            collections.lines = CollectionWithId::from(Line {
+                id: "line_1".to_string(),
+                ..Default::default()
+            });
+            collections.routes = CollectionWithId::from(objects::Route {
+                id: "route_1".to_string(),
+                line_id: "line_1".to_string(),
+                ..Default::default()
+            });
+            collections.vehicle_journeys = CollectionWithId::new(vec![
+                VehicleJourney {
+                    id: "vj_1".to_string(),
+                    route_id: "route_1".to_string(),
+                    ..Default::default()
+                },
+                VehicleJourney {
+                    id: "vj_2".to_string(),
+                    route_id: "route_1".to_string(),
+                    ..Default::default()
+                },
+            ])
+            .unwrap();
+
+            super::manage_attributions(&mut collections, &mut handler).unwrap();
+            assert_eq!(
+                vec!["attribution:1", "attribution:2", "attribution:3"],
+                extract_ids(&collections.companies)
+            );
+            assert_eq!(
+                Some("http://operator.example"),
+                collections
+                    .companies
+                    .get("attribution:1")
+                    .unwrap()
+                    .url
+                    .as_deref()
+            );
+            let property = |name: &str, value: &str| {
+                vec![(name.to_string(), value.to_string())]
+                    .into_iter()
+                    .collect::<KeysValues>()
+            };
+            assert_eq!(
+                property("attribution_authority", "Authority"),
+                collections.lines.get("line_1").unwrap().object_properties
+            );
+            assert_eq!(
+                property("attribution_operator", "Operator Inc"),
+                collections.routes.get("route_1").unwrap().object_properties
+            );
+            let vj_1 = collections.vehicle_journeys.get("vj_1").unwrap();
+            let vj_2 = collections.vehicle_journeys.get("vj_2").unwrap();
+            assert!(vj_1.object_properties.is_empty());
+            assert_eq!(
+                property("attribution_producer", "Producer SA"),
+                vj_2.object_properties
+            );
+            assert_eq!("attribution:1", vj_1.company_id);
+            assert_eq!("attribution:1", vj_2.company_id);
+        });
+    }
+
+    #[test]
+    fn read_sub_agencies() {
+        let attributions_content = "attribution_id,agency_id,route_id,trip_id,organization_name,is_producer,is_operator,is_authority\n\
+             1,holding,,,Operator Inc,0,1,0\n\
+             2,holding,,,Authority,0,0,1";
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "attributions.txt", attributions_content);
+            let mut collections = Collections::default();
+            collections.companies = CollectionWithId::new(vec![
+                objects::Company {
+                    id: "holding".to_string(),
+                    name: "Holding".to_string(),
+                    ..Default::default()
+                },
+                objects::Company {
+                    id: "other_holding".to_string(),
+                    name: "Other holding".to_string(),
+                    ..Default::default()
+                },
+            ])
+            .unwrap();
+            collections.lines = CollectionWithId::from(Line {
+                id: "line_1".to_string(),
+                network_id: "holding".to_string(),
+                ..Default::default()
+            });
+
+            super::manage_attributions(&mut collections, &mut handler).unwrap();
+            // only the operators are sub-agencies
+            assert_eq!(
+                vec![("attribution:1", "holding")],
+                collections
+                    .company_parents
+                    .values()
+                    .map(|link| (link.company_id.as_str(), link.parent_company_id.as_str()))
+                    .collect::<Vec<_>>()
+            );
+
+            let company_parents = vec![
+                ("attribution:1".to_string(), "other_holding".to_string()),
+                ("unknown".to_string(), "holding".to_string()),
+            ]
+            .into_iter()
+            .collect();
+            super::manage_company_parents(&mut collections, company_parents);
+            assert_eq!(
+                vec!["other_holding"],
+                collections.parent_company_ids("attribution:1")
+            );
+            assert_eq!(1, collections.company_parents.len());
+        });
+    }
+
+    #[test]
+    fn read_trip_operators() {
+        let routes_content = "route_id,agency_id,route_short_name,route_long_name,route_type\n\
+             route_1,agency_1,1,My line 1,3\n\
+             route_2,agency_2,2,My line 2,3";
+        let trips_content = "trip_id,route_id,service_id,operator_id\n\
+             vj_1,route_1,service_1,operator_1\n\
+             vj_2,route_1,service_1,\n\
+             vj_3,route_1,service_1,agency_2\n\
+             vj_4,route_1,service_1,agency_1\n\
+             vj_5,route_2,service_1,operator_1\n\
+             unknown,unknown_route,service_1,operator_2";
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "routes.txt", routes_content);
+            create_file_with_content(path, "trips.txt", trips_content);
+            let mut collections = Collections::default();
+            let (contributor, dataset, _) = read_utils::read_config(None::<&str>).unwrap();
+            collections.contributors = CollectionWithId::new(vec![contributor]).unwrap();
+            collections.datasets = CollectionWithId::new(vec![dataset]).unwrap();
+            let company = |id: &str| objects::Company {
+                id: id.to_string(),
+                name: id.to_string(),
+                ..Default::default()
+            };
+            collections.companies =
+                CollectionWithId::new(vec![company("agency_1"), company("agency_2")]).unwrap();
+
+            let trip_operators =
+                super::read_routes(&mut handler, &mut collections, &RouteTypeMappings::new())
+                    .unwrap();
+            super::manage_trip_operators(&mut collections, trip_operators).unwrap();
+            assert_eq!(
+                vec!["agency_1", "agency_2", "operator_1", "agency_2:operator"],
+                extract_ids(&collections.companies)
+            );
+            assert_eq!(
+                "agency_2",
+                collections.companies.get("agency_2:operator").unwrap().name
+            );
+            let company_of = |vj_id: &str| {
+                collections
+                    .vehicle_journeys
+                    .get(vj_id)
+                    .unwrap()
+                    .company_id
+                    .as_str()
+            };
+            assert_eq!("operator_1", company_of("vj_1"));
+            assert_eq!("agency_1", company_of("vj_2"));
+            assert_eq!("agency_2:operator", company_of("vj_3"));
+            assert_eq!("agency_1", company_of("vj_4"));
+            assert_eq!("operator_1", company_of("vj_5"));
+            // the agencies are never the children of another agency
+            assert_eq!(
+                vec![
+                    ("operator_1", "agency_1"),
+                    ("agency_2:operator", "agency_1")
+                ],
+                collections
+                    .company_parents
+                    .values()
+                    .map(|link| (link.company_id.as_str(), link.parent_company_id.as_str()))
+                    .collect::<Vec<_>>()
+            );
+        });
+    }
+
+    #[test]
+    fn frequencies_over_several_days() {
+        let frequencies_content = "trip_id,start_time,end_time,headway_secs,exact_times\n\
+             trip,06:00:00,06:30:00,3600,1\n\
+             trip,25:00:00,25:30:00,3600,1\n\
+             trip,24:30:00,24:45:00,3600,1\n\
+             trip,49:30:00,50:00:00,3600,1";
+        let model = crate::model_builder::ModelBuilder::default()
+            .calendar("c1", |calendar| {
+                calendar.dates.insert(Date::from_ymd(2020, 1, 1));
+            })
+            .vj("trip", |vj| {
+                vj.calendar("c1").st("A", "10:00").st("B", "10:30");
+            })
+            .build();
+        let mut collections = model.into_collections();
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "frequencies.txt", frequencies_content);
+            super::manage_frequencies(&mut collections, &mut handler, false).unwrap();
+        });
+
+        let vjs: Vec<(&str, &str, Time, Time)> = collections
+            .vehicle_journeys
+            .values()
+            .map(|vj| {
+                (
+                    vj.id.as_str(),
+                    vj.service_id.as_str(),
+                    vj.stop_times[0].departure_time,
+                    vj.stop_times[1].arrival_time,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("trip-0", "c1", Time::new(6, 0, 0), Time::new(6, 30, 0)),
+                (
+                    "trip-1",
+                    "c1:+1days",
+                    Time::new(1, 0, 0),
+                    Time::new(1, 30, 0)
+                ),
+                (
+                    "trip-2",
+                    "c1:+1days",
+                    Time::new(0, 30, 0),
+                    Time::new(1, 0, 0)
+                ),
+                (
+                    "trip-3",
+                    "c1:+2days",
+                    Time::new(1, 30, 0),
+                    Time::new(2, 0, 0)
+                ),
+            ],
+            vjs
+        );
+        assert_eq!(
+            vec![Date::from_ymd(2020, 1, 3)],
+            collections
+                .calendars
+                .get("c1:+2days")
+                .unwrap()
+                .dates
+                .iter()
+                .copied()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn network_contacts() {
+        let mut collections = Collections::default();
+        collections.networks = CollectionWithId::from(objects::Network {
+            id: "agency_1".to_string(),
+            phone: Some("0123456789".to_string()),
+            email: Some("old@example.com".to_string()),
+            ..Default::default()
+        });
+        let network_contacts = vec![
+            (
+                "agency_1".to_string(),
+                crate::gtfs::NetworkContact {
+                    fare_url: Some("https://example.com/fares".to_string()),
+                    email: Some("contact@example.com".to_string()),
+                    phone: None,
+                },
+            ),
+            ("unknown".to_string(), Default::default()),
+        ]
+        .into_iter()
+        .collect();
+        super::manage_network_contacts(&mut collections, network_contacts);
+        let network = collections.networks.get("agency_1").unwrap();
+        assert_eq!(
+            Some("https://example.com/fares"),
+            network.fare_url.as_deref()
+        );
+        assert_eq!(Some("contact@example.com"), network.email.as_deref());
+        assert_eq!(Some("0123456789"), network.phone.as_deref());
+    }
+
+    #[test]
+    fn read_booking_rules() {
+        let booking_rules_content =
+            "booking_rule_id,booking_type,prior_notice_duration_min,phone_number\n\
+             call,1,60,0123456789\n\
+             unused,0,,";
+        let stop_times_content = "trip_id,arrival_time,departure_time,stop_id,stop_sequence,pickup_booking_rule_id,drop_off_booking_rule_id\n\
+             vj_1,06:00:00,06:00:00,sp_1,1,call,\n\
+             vj_1,06:10:00,06:10:00,sp_2,2,,call\n\
+             vj_2,07:00:00,07:00:00,sp_1,1,,\n\
+             vj_2,07:10:00,07:10:00,sp_2,2,unknown,";
+        test_in_tmp_dir(|path| {
+            let mut handler = PathFileHandler::new(path.to_path_buf());
+            create_file_with_content(path, "booking_rules.txt", booking_rules_content);
+            create_file_with_content(path, "stop_times.txt", stop_times_content);
+            let mut collections = Collections::default();
+            collections.vehicle_journeys = CollectionWithId::new(vec![
+                VehicleJourney {
+                    id: "vj_1".to_string(),
+                    ..Default::default()
+                },
+                VehicleJourney {
+                    id: "vj_2".to_string(),
+                    ..Default::default()
+                },
+            ])
+            .unwrap();
+            collections.stop_points = CollectionWithId::new(vec![
+                StopPoint {
+                    id: "sp_1".to_string(),
+                    ..Default::default()
+                },
+                StopPoint {
+                    id: "sp_2".to_string(),
+                    ..Default::default()
+                },
+            ])
+            .unwrap();
+
+            super::manage_stop_times(&mut collections, &mut handler, false, None).unwrap();
+            super::manage_booking_rules(&mut collections, &mut handler).unwrap();
+            assert_eq!(
+                vec!["call", "unused"],
+                extract_ids(&collections.booking_rules)
+            );
+            let booking_rule = collections.booking_rules.get("call").unwrap();
+            assert_eq!(Some(60), booking_rule.deadline_duration);
+            assert_eq!(Some("0123456789".to_string()), booking_rule.phone);
+            let links: Vec<_> = collections
+                .booking_rule_links
+                .values()
+                .map(|link| (link.object_id.as_str(), link.booking_rule_id.as_str()))
+                .collect();
+            assert_eq!(vec![("vj_1", "call")], links);
+
+            let mut prefix_conf = PrefixConfiguration::default();
+            prefix_conf.set_data_prefix("pre");
+            prefix_conf.set_schedule_subprefix("winter");
+            collections.booking_rule_links.prefix(&prefix_conf);
+            let link = collections.booking_rule_links.values().next().unwrap();
+            assert_eq!("pre:winter:vj_1", link.object_id);
+            assert_eq!("pre:call", link.booking_rule_id);
+        });
+    }
 }
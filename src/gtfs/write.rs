@@ -14,7 +14,7 @@
 
 use super::{
     Agency, DirectionType, Route, RouteType, Shape, Stop, StopLocationType, StopTime, Transfer,
-    Trip,
+    Translation, Trip,
 };
 use crate::model::{GetCorresponding, Model};
 use crate::objects;
@@ -49,21 +49,27 @@ pub fn write_transfers(path: &path::Path, transfers: &Collection<NtfsTransfer>)
     Ok(())
 }
 
+/// The `agency_lang` of a network without language is `default_lang`
 pub fn write_agencies(
     path: &path::Path,
     networks: &CollectionWithId<objects::Network>,
+    default_lang: Option<&str>,
 ) -> Result<()> {
     info!("Writing agency.txt");
     let path = path.join("agency.txt");
     let mut wtr =
-        csv::Writer::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
+        csv::Writer::from_path(&path).with_context(|_| format!("Error writing {:?}", path))?;
     for n in networks.values() {
-        wtr.serialize(Agency::from(n))
-            .with_context(|_| format!("Error reading {:?}", path))?;
+        let mut agency = Agency::from(n);
+        if agency.lang.is_none() {
+            agency.lang = default_lang.map(str::to_string);
+        }
+        wtr.serialize(agency)
+            .with_context(|_| format!("Error writing {:?}", path))?;
     }
 
     wtr.flush()
-        .with_context(|_| format!("Error reading {:?}", path))?;
+        .with_context(|_| format!("Error writing {:?}", path))?;
 
     Ok(())
 }
@@ -240,12 +246,17 @@ fn get_gtfs_direction_id_from_ntfs_route(route: &objects::Route) -> DirectionTyp
 fn make_gtfs_trip_from_ntfs_vj(vj: &objects::VehicleJourney, model: &Model) -> Trip {
     let (short_name, headsign) =
         get_gtfs_trip_shortname_and_headsign_from_ntfs_vj(vj, &model.stop_points);
-    let mut wheelchair_and_bike = (Availability::default(), Availability::default());
-    if let Some(tp_id) = &vj.trip_property_id {
-        if let Some(tp) = &model.trip_properties.get(&tp_id) {
-            wheelchair_and_bike = (tp.wheelchair_accessible, tp.bike_accepted);
-        };
-    }
+    let (wheelchair_accessible, bikes_allowed) = model
+        .vehicle_journeys
+        .get_idx(&vj.id)
+        .and_then(|vj_idx| model.trip_properties_of(vj_idx))
+        .map(|trip_property| {
+            (
+                trip_property.wheelchair_accessible,
+                trip_property.bike_accepted,
+            )
+        })
+        .unwrap_or_default();
     let route = &model.routes.get(&vj.route_id).unwrap();
     let line_idx = &model.lines.get_idx(&route.line_id).unwrap();
     let route_id = &get_line_physical_modes(*line_idx, &model.physical_modes, model)
@@ -263,8 +274,16 @@ fn make_gtfs_trip_from_ntfs_vj(vj: &objects::VehicleJourney, model: &Model) -> T
         direction: get_gtfs_direction_id_from_ntfs_route(&route),
         block_id: vj.block_id.clone(),
         shape_id: vj.geometry_id.clone(),
-        wheelchair_accessible: wheelchair_and_bike.0,
-        bikes_allowed: wheelchair_and_bike.1,
+        wheelchair_accessible,
+        bikes_allowed,
+        // the other trip properties and the operator are not written
+        air_conditioned: Availability::default(),
+        visual_announcement: Availability::default(),
+        audible_announcement: Availability::default(),
+        appropriate_escort: Availability::default(),
+        appropriate_signage: Availability::default(),
+        school_vehicle_type: TransportType::default(),
+        operator_id: None,
     }
 }
 
@@ -414,7 +433,7 @@ fn make_gtfs_route_from_ntfs_line(line: &objects::Line, pm: &PhysicalModeWithOrd
         short_name: line.code.clone().unwrap_or_else(|| "".to_string()),
         long_name: line.name.clone(),
         desc: None,
-        route_type: RouteType::from(pm.inner),
+        route_type: RouteType::from(pm.inner).to_gtfs_value(),
         url: None,
         color: line.color.clone(),
         text_color: line.text_color.clone(),
@@ -445,6 +464,7 @@ pub fn write_stop_times(
     vehicle_journeys: &CollectionWithId<VehicleJourney>,
     stop_points: &CollectionWithId<StopPoint>,
     stop_times_headsigns: &HashMap<(String, u32), String>,
+    stop_time_continuous_stoppings: &HashMap<(String, u32), ContinuousStopping>,
 ) -> Result<()> {
     info!("Writing stop_times.txt");
     let stop_times_path = path.join("stop_times.txt");
@@ -452,6 +472,8 @@ pub fn write_stop_times(
         .with_context(|_| format!("Error reading {:?}", stop_times_path))?;
     for (vj_idx, vj) in vehicle_journeys {
         for st in &vj.stop_times {
+            let continuous_stopping =
+                stop_time_continuous_stoppings.get(&(vj.id.clone(), st.sequence));
             st_wtr
                 .serialize(StopTime {
                     stop_id: stop_points[st.stop_point_idx].id.clone(),
@@ -466,6 +488,13 @@ pub fn write_stop_times(
                         .get(&(vehicle_journeys[vj_idx].id.clone(), st.sequence))
                         .cloned(),
                     timepoint: !st.datetime_estimated,
+                    location_id: None,
+                    start_pickup_drop_off_window: None,
+                    end_pickup_drop_off_window: None,
+                    continuous_pickup: continuous_stopping.map(|continuous| continuous.pickup),
+                    continuous_drop_off: continuous_stopping.map(|continuous| continuous.drop_off),
+                    pickup_booking_rule_id: None,
+                    drop_off_booking_rule_id: None,
                 })
                 .with_context(|_| format!("Error reading {:?}", st_wtr))?;
         }
@@ -520,6 +549,70 @@ pub fn write_shapes(
     Ok(())
 }
 
+fn make_gtfs_translation(model: &Model, translation: &objects::Translation) -> Option<Translation> {
+    let by_id = |table_name: &str, field_name: &str| {
+        Some(Translation {
+            table_name: table_name.to_string(),
+            field_name: field_name.to_string(),
+            language: translation.language.clone(),
+            translation: translation.translation.clone(),
+            record_id: Some(translation.object_id.clone()),
+            field_value: None,
+        })
+    };
+    // the GTFS routes are made from the lines, with other identifiers: the
+    // lines are translated by value
+    let by_value = |field_name: &str, field_value: Option<&str>| {
+        field_value.map(|field_value| Translation {
+            table_name: "routes".to_string(),
+            field_name: field_name.to_string(),
+            language: translation.language.clone(),
+            translation: translation.translation.clone(),
+            record_id: None,
+            field_value: Some(field_value.to_string()),
+        })
+    };
+    let line = || model.lines.get(&translation.object_id);
+    match (&translation.object_type, translation.field_name.as_str()) {
+        (ObjectType::StopPoint, "stop_name") | (ObjectType::StopArea, "stop_name") => {
+            by_id("stops", "stop_name")
+        }
+        (ObjectType::Network, "network_name") => by_id("agency", "agency_name"),
+        (ObjectType::Line, "line_name") => {
+            by_value("route_long_name", line().map(|line| line.name.as_str()))
+        }
+        (ObjectType::Line, "line_code") => by_value(
+            "route_short_name",
+            line().and_then(|line| line.code.as_deref()),
+        ),
+        _ => None,
+    }
+}
+
+/// Writes the translations of the names of the stops, lines and networks in
+/// translations.txt
+pub fn write_translations(path: &path::Path, model: &Model) -> Result<()> {
+    let translations: Vec<Translation> = model
+        .translations
+        .values()
+        .filter_map(|translation| make_gtfs_translation(model, translation))
+        .collect();
+    if translations.is_empty() {
+        return Ok(());
+    }
+    info!("Writing translations.txt");
+    let path = path.join("translations.txt");
+    let mut wtr =
+        csv::Writer::from_path(&path).with_context(|_| format!("Error writing {:?}", path))?;
+    for translation in translations {
+        wtr.serialize(translation)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    wtr.flush()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -548,6 +641,8 @@ mod tests {
             phone: Some("0123456789".to_string()),
             address: Some("somewhere".to_string()),
             sort_order: Some(1),
+            fare_url: Some("http://www.vianavigo.com/tarifs".to_string()),
+            email: Some("contact@vianavigo.com".to_string()),
             codes: Default::default(),
         });
 
@@ -558,7 +653,8 @@ mod tests {
             timezone: chrono_tz::Europe::Madrid,
             lang: Some("fr".to_string()),
             phone: Some("0123456789".to_string()),
-            email: None,
+            fare_url: Some("http://www.vianavigo.com/tarifs".to_string()),
+            email: Some("contact@vianavigo.com".to_string()),
         };
 
         assert_eq!(expected_agency, agency);
@@ -575,6 +671,8 @@ mod tests {
             phone: None,
             address: None,
             sort_order: None,
+            fare_url: None,
+            email: None,
             codes: Default::default(),
         });
 
@@ -585,6 +683,7 @@ mod tests {
             timezone: chrono_tz::Europe::Paris,
             lang: None,
             phone: None,
+            fare_url: None,
             email: None,
         };
 
@@ -1011,6 +1110,13 @@ mod tests {
             shape_id: vj.geometry_id.clone(),
             wheelchair_accessible: Availability::Available,
             bikes_allowed: Availability::NotAvailable,
+            air_conditioned: Availability::InformationNotAvailable,
+            visual_announcement: Availability::InformationNotAvailable,
+            audible_announcement: Availability::InformationNotAvailable,
+            appropriate_escort: Availability::InformationNotAvailable,
+            appropriate_signage: Availability::InformationNotAvailable,
+            school_vehicle_type: TransportType::Regular,
+            operator_id: None,
         };
         let model = Model::new(collections).unwrap();
         assert_eq!(expected, make_gtfs_trip_from_ntfs_vj(&vj, &model));
@@ -1243,15 +1349,25 @@ mod tests {
             geometry_id: None,
             stop_times: stop_times_vec,
             journey_pattern_id: Some(String::from("jp:01")),
+            length: None,
         });
         let mut stop_times_headsigns = HashMap::new();
         stop_times_headsigns.insert(("vj:01".to_string(), 1), "somewhere".to_string());
+        let mut continuous_stoppings = HashMap::new();
+        continuous_stoppings.insert(
+            ("vj:01".to_string(), 2),
+            ContinuousStopping {
+                pickup: ContinuousStoppingType::PhoneAgency,
+                drop_off: ContinuousStoppingType::Continuous,
+            },
+        );
         let tmp_dir = tempdir().expect("create temp dir");
         write_stop_times(
             tmp_dir.path(),
             &vehicle_journeys,
             &stop_points,
             &stop_times_headsigns,
+            &continuous_stoppings,
         )
         .unwrap();
         let output_file_path = tmp_dir.path().join("stop_times.txt");
@@ -1260,9 +1376,9 @@ mod tests {
         let mut output_contents = String::new();
         output_file.read_to_string(&mut output_contents).unwrap();
         assert_eq!(
-            "trip_id,arrival_time,departure_time,stop_id,stop_sequence,pickup_type,drop_off_type,local_zone_id,stop_headsign,timepoint\n\
-            vj:01,06:00:00,06:00:00,sp:01,1,0,0,,somewhere,1\n\
-            vj:01,06:06:27,06:06:27,sp:01,2,2,1,3,,0\n",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence,pickup_type,drop_off_type,local_zone_id,stop_headsign,timepoint,continuous_pickup,continuous_drop_off\n\
+            vj:01,06:00:00,06:00:00,sp:01,1,0,0,,somewhere,1,,\n\
+            vj:01,06:06:27,06:06:27,sp:01,2,2,1,3,,0,2,0\n",
             output_contents
         );
         tmp_dir.close().expect("delete temp dir");
@@ -1325,7 +1441,7 @@ mod tests {
             short_name: "".to_string(),
             long_name: "3".to_string(),
             desc: None,
-            route_type: RouteType::Bus,
+            route_type: 3,
             url: None,
             color: None,
             text_color: None,
@@ -1381,7 +1497,7 @@ mod tests {
             short_name: "DEF".to_string(),
             long_name: "DEF".to_string(),
             desc: None,
-            route_type: RouteType::UnknownMode,
+            route_type: 3,
             url: None,
             color: Some(objects::Rgb {
                 red: 155,
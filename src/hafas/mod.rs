@@ -0,0 +1,104 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Reading of the [HAFAS raw data](https://opentransportdata.swiss/en/cookbook/hafas-rohdaten-format-hrdf/)
+//! (HRDF), the timetable format of the Swiss and German operators.
+//!
+//! The files `ECKDATEN` (timetable period), `BITFELD` (days of service),
+//! `BAHNHOF` (stops), `BFKOORD_WGS` (coordinates of the stops) and `FPLAN`
+//! (journeys) are read; the other files of the export are ignored.
+
+mod read;
+
+use crate::{
+    model::{Collections, Model},
+    objects::{Contributor, Dataset},
+    validity_period, AddPrefix, PrefixConfiguration, Result,
+};
+use chrono_tz::Tz;
+use log::info;
+use std::{collections::BTreeMap, path::Path};
+use typed_index_collection::CollectionWithId;
+
+/// Timezone of the HAFAS timetables when not configured
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Europe::Zurich;
+
+/// Parameters of the reading of HAFAS raw data
+#[derive(Default)]
+pub struct Configuration {
+    /// The Contributor providing the Dataset
+    pub contributor: Contributor,
+    /// Describe the Dataset being parsed
+    pub dataset: Dataset,
+    /// Additional key-values for the 'feed_infos.txt'
+    pub feed_infos: BTreeMap<String, String>,
+    /// used to prefix objects
+    pub prefix_conf: Option<PrefixConfiguration>,
+    /// Timezone of the times of `FPLAN`, `Europe/Zurich` if not given
+    pub timezone: Option<Tz>,
+}
+
+/// Imports a `Model` from the HAFAS raw data files in the `path` directory,
+/// with the default [Configuration](struct.Configuration.html).
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Model> {
+    read_with_configuration(path, Configuration::default())
+}
+
+/// Imports a `Model` from the HAFAS raw data files in the `path` directory.
+///
+/// The days of service of the `BITFELD` bitfields are expanded into calendar
+/// dates, the journeys without bitfield run every day of the timetable
+/// period. The journeys repeated every few minutes (`*Z` lines with a cycle)
+/// are expanded into one trip per departure.
+pub fn read_with_configuration<P: AsRef<Path>>(
+    path: P,
+    configuration: Configuration,
+) -> Result<Model> {
+    let path = path.as_ref();
+    info!("Reading HAFAS raw data from {:?}", path);
+    let Configuration {
+        contributor,
+        mut dataset,
+        feed_infos,
+        prefix_conf,
+        timezone,
+    } = configuration;
+
+    let mut collections = Collections::default();
+    let period = read::read_period(path)?;
+    collections.calendars = read::read_bitfields(path, &period)?;
+    let (stop_areas, stop_points) = read::read_stops(path)?;
+    collections.stop_areas = stop_areas;
+    collections.stop_points = stop_points;
+    read::manage_journeys(
+        &mut collections,
+        path,
+        &period,
+        &dataset.id,
+        timezone.unwrap_or(DEFAULT_TIMEZONE),
+    )?;
+    validity_period::compute_dataset_validity_period(&mut dataset, &collections.calendars)?;
+
+    collections.contributors = CollectionWithId::from(contributor);
+    collections.datasets = CollectionWithId::from(dataset);
+    collections.feed_infos = feed_infos;
+
+    //add prefixes
+    if let Some(prefix_conf) = prefix_conf {
+        collections.prefix(&prefix_conf);
+    }
+
+    collections.calendar_deduplication();
+    Model::new(collections)
+}
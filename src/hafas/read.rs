@@ -0,0 +1,579 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use crate::{
+    model::{
+        Collections, BUS_PHYSICAL_MODE, FERRY_PHYSICAL_MODE, FUNICULAR_PHYSICAL_MODE,
+        LOCAL_TRAIN_PHYSICAL_MODE, LONG_DISTANCE_TRAIN_PHYSICAL_MODE, METRO_PHYSICAL_MODE,
+        RAPID_TRANSIT_PHYSICAL_MODE, TRAMWAY_PHYSICAL_MODE,
+    },
+    objects::{
+        Calendar, CommercialMode, Company, Coord, Date, Line, Network, PhysicalMode, Route,
+        StopArea, StopPoint, StopTime, Time, VehicleJourney,
+    },
+    read_utils, Result,
+};
+use chrono::Duration;
+use chrono_tz::Tz;
+use failure::{bail, format_err, ResultExt};
+use log::{info, warn};
+use std::{collections::HashMap, path::Path};
+use typed_index_collection::{CollectionWithId, Idx};
+
+// Bitfield of the journeys running every day of the timetable period
+const EVERY_DAY_BITFIELD: &str = "000000";
+// Category of the journeys without `*G` line
+const UNKNOWN_CATEGORY: &str = "unknown";
+
+/// Period of validity of the timetable, read from `ECKDATEN`
+pub(crate) struct Period {
+    pub start_date: Date,
+    pub end_date: Date,
+}
+
+// Lines of the `file_name` file without the `%` comments and the blank
+// lines. The files are encoded in UTF-8 or, in the older exports, in Latin-1.
+fn read_lines(path: &Path, file_name: &str) -> Result<Vec<String>> {
+    let content = read_utils::read_utf8_or_latin1(&path.join(file_name))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| line.split('%').next())
+        .map(|line| line.trim_end().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+// The characters of `line` from the column `from` to the column `to` (1-based
+// and inclusive, as in the HAFAS specification), trimmed
+fn columns(line: &str, from: usize, to: usize) -> String {
+    line.chars()
+        .skip(from - 1)
+        .take(to + 1 - from)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn parse_date(field: &str) -> Result<Date> {
+    Ok(Date::parse_from_str(field.trim(), "%d.%m.%Y")
+        .with_context(|_| format!("invalid date {:?}", field))?)
+}
+
+// A `HHHMM` time, preceded by `-` when boarding or alighting is forbidden
+fn parse_time(field: &str) -> Result<Option<(Time, bool)>> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let forbidden = field.starts_with('-');
+    let value: u32 = field
+        .trim_start_matches('-')
+        .trim()
+        .parse()
+        .with_context(|_| format!("invalid time {:?}", field))?;
+    Ok(Some((Time::new(value / 100, value % 100, 0), forbidden)))
+}
+
+fn calendar_id(bitfield: &str) -> String {
+    format!("bitfield:{}", bitfield)
+}
+
+pub(crate) fn read_period(path: &Path) -> Result<Period> {
+    info!("Reading ECKDATEN");
+    let lines = read_lines(path, "ECKDATEN")?;
+    if lines.len() < 2 {
+        bail!("ECKDATEN must contain the start and end dates of the timetable");
+    }
+    let period = Period {
+        start_date: parse_date(&lines[0]).context("Error reading ECKDATEN")?,
+        end_date: parse_date(&lines[1]).context("Error reading ECKDATEN")?,
+    };
+    if period.end_date < period.start_date {
+        bail!(
+            "ECKDATEN: the end date {} is before the start date {}",
+            period.end_date,
+            period.start_date
+        );
+    }
+    Ok(period)
+}
+
+// The days of `period` of the hexadecimal `bitfield`, the first bit being
+// the start date of the period
+fn bitfield_dates(bitfield: &str, period: &Period) -> Result<Vec<Date>> {
+    let mut dates = vec![];
+    for (i, digit) in bitfield.chars().enumerate() {
+        let value = digit
+            .to_digit(16)
+            .ok_or_else(|| format_err!("invalid bitfield {:?}", bitfield))?;
+        for bit in 0..4 {
+            if value & (0b1000 >> bit) != 0 {
+                let date = period.start_date + Duration::days((i * 4 + bit) as i64);
+                if date <= period.end_date {
+                    dates.push(date);
+                }
+            }
+        }
+    }
+    Ok(dates)
+}
+
+pub(crate) fn read_bitfields(path: &Path, period: &Period) -> Result<CollectionWithId<Calendar>> {
+    info!("Reading BITFELD");
+    let mut calendars = CollectionWithId::default();
+    for line in read_lines(path, "BITFELD")? {
+        let mut calendar = Calendar::new(calendar_id(&columns(&line, 1, 6)));
+        calendar.dates = bitfield_dates(&columns(&line, 8, 103), period)
+            .with_context(|_| format!("Error reading BITFELD {:?}", calendar.id))?
+            .into_iter()
+            .collect();
+        calendars.push(calendar)?;
+    }
+    Ok(calendars)
+}
+
+fn read_coords(path: &Path) -> Result<HashMap<String, Coord>> {
+    let mut coords = HashMap::new();
+    if !path.join("BFKOORD_WGS").exists() {
+        warn!("BFKOORD_WGS not found, the stops have no coordinates");
+        return Ok(coords);
+    }
+    info!("Reading BFKOORD_WGS");
+    for line in read_lines(path, "BFKOORD_WGS")? {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 {
+            bail!("Error reading BFKOORD_WGS: invalid line {:?}", line);
+        }
+        let parse = |field: &str| -> Result<f64> {
+            Ok(field.parse().with_context(|_| {
+                format!("Error reading BFKOORD_WGS: invalid coordinate {:?}", field)
+            })?)
+        };
+        coords.insert(
+            fields[0].to_string(),
+            Coord {
+                lon: parse(fields[1])?,
+                lat: parse(fields[2])?,
+            },
+        );
+    }
+    Ok(coords)
+}
+
+pub(crate) fn read_stops(
+    path: &Path,
+) -> Result<(CollectionWithId<StopArea>, CollectionWithId<StopPoint>)> {
+    let coords = read_coords(path)?;
+    info!("Reading BAHNHOF");
+    let mut stop_areas = CollectionWithId::default();
+    let mut stop_points = CollectionWithId::default();
+    for line in read_lines(path, "BAHNHOF")? {
+        let id = columns(&line, 1, 7);
+        // the name is followed by its long and abbreviated forms, separated by `$`
+        let name: String = line.chars().skip(7).collect();
+        let name = name
+            .split('$')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let coord = coords.get(&id).cloned().unwrap_or_else(|| {
+            warn!("BAHNHOF: stop {:?} has no coordinates", id);
+            Coord::default()
+        });
+        let stop_point = StopPoint {
+            stop_area_id: format!("Navitia:{}", id),
+            id,
+            name,
+            coord,
+            visible: true,
+            ..Default::default()
+        };
+        stop_areas.push(StopArea::from(stop_point.clone()))?;
+        stop_points.push(stop_point)?;
+    }
+    Ok((stop_areas, stop_points))
+}
+
+#[derive(Debug, Default)]
+struct JourneyStop {
+    stop_id: String,
+    arrival: Option<Time>,
+    departure: Option<Time>,
+    no_alighting: bool,
+    no_boarding: bool,
+}
+
+#[derive(Debug, Default)]
+struct Journey {
+    number: String,
+    administration: String,
+    // number of repetitions of the journey, every `cycle_minutes`
+    cycles: u32,
+    cycle_minutes: u32,
+    category: Option<String>,
+    bitfield: Option<String>,
+    line: Option<String>,
+    stops: Vec<JourneyStop>,
+}
+
+fn parse_journey(line: &str) -> Result<Journey> {
+    let parse_number = |from, to| -> Result<u32> {
+        let field = columns(line, from, to);
+        if field.is_empty() {
+            Ok(0)
+        } else {
+            Ok(field
+                .parse()
+                .with_context(|_| format!("invalid number {:?} in {:?}", field, line))?)
+        }
+    };
+    Ok(Journey {
+        number: columns(line, 4, 9),
+        administration: columns(line, 11, 16),
+        cycles: parse_number(22, 24)?,
+        cycle_minutes: parse_number(26, 28)?,
+        ..Default::default()
+    })
+}
+
+fn parse_journey_stop(line: &str) -> Result<JourneyStop> {
+    let arrival = parse_time(&columns(line, 30, 35))?;
+    let departure = parse_time(&columns(line, 37, 42))?;
+    Ok(JourneyStop {
+        stop_id: columns(line, 1, 7),
+        arrival: arrival.map(|(time, _)| time),
+        departure: departure.map(|(time, _)| time),
+        no_alighting: arrival.map_or(false, |(_, forbidden)| forbidden),
+        no_boarding: departure.map_or(false, |(_, forbidden)| forbidden),
+    })
+}
+
+fn read_journeys(path: &Path) -> Result<Vec<Journey>> {
+    info!("Reading FPLAN");
+    let mut journeys: Vec<Journey> = vec![];
+    for line in read_lines(path, "FPLAN")? {
+        if line.starts_with("*Z") {
+            journeys.push(parse_journey(&line).context("Error reading FPLAN")?);
+            continue;
+        }
+        let journey = match journeys.last_mut() {
+            Some(journey) => journey,
+            None => continue,
+        };
+        // only the first category, bitfield and line of a journey are used
+        if line.starts_with("*G") {
+            if journey.category.is_none() {
+                journey.category = Some(columns(&line, 4, 6));
+            }
+        } else if line.starts_with("*A VE") {
+            if journey.bitfield.is_none() {
+                journey.bitfield = Some(columns(&line, 23, 28)).filter(|b| !b.is_empty());
+            }
+        } else if line.starts_with("*L") {
+            if journey.line.is_none() {
+                journey.line = Some(columns(&line, 4, 11));
+            }
+        } else if !line.starts_with('*') {
+            let stop = parse_journey_stop(&line).context("Error reading FPLAN")?;
+            // the stops without times are passed without stopping
+            if stop.arrival.is_some() || stop.departure.is_some() {
+                journey.stops.push(stop);
+            }
+        }
+    }
+    Ok(journeys)
+}
+
+fn physical_mode(category: &str) -> &'static str {
+    match category.to_uppercase().as_str() {
+        "EC" | "EN" | "IC" | "ICE" | "ICN" | "IR" | "NJ" | "RJ" | "RJX" | "TGV" => {
+            LONG_DISTANCE_TRAIN_PHYSICAL_MODE
+        }
+        "IRE" | "PE" | "R" | "RB" | "RE" => LOCAL_TRAIN_PHYSICAL_MODE,
+        "S" | "SN" => RAPID_TRANSIT_PHYSICAL_MODE,
+        "M" | "U" => METRO_PHYSICAL_MODE,
+        "T" | "NFT" | "TRA" => TRAMWAY_PHYSICAL_MODE,
+        "BAT" | "BAV" | "FAE" | "SCH" => FERRY_PHYSICAL_MODE,
+        "CC" | "FUN" => FUNICULAR_PHYSICAL_MODE,
+        _ => BUS_PHYSICAL_MODE,
+    }
+}
+
+struct JourneyContext<'a> {
+    period: &'a Period,
+    dataset_id: &'a str,
+    timezone: Tz,
+    // number of trips created for each journey identifier
+    trip_counts: HashMap<String, u32>,
+}
+
+// Creates the network, company, modes, line, route and calendar of the
+// journey when they don't exist yet and returns the ids of the route, of
+// the physical mode and of the calendar
+fn manage_journey_objects(
+    collections: &mut Collections,
+    journey: &Journey,
+    stop_point_idxs: &[Idx<StopPoint>],
+    context: &JourneyContext,
+) -> Result<Option<(String, String, String)>> {
+    let bitfield = journey.bitfield.as_deref().unwrap_or(EVERY_DAY_BITFIELD);
+    let service_id = calendar_id(bitfield);
+    if !collections.calendars.contains_id(&service_id) {
+        if bitfield != EVERY_DAY_BITFIELD {
+            return Ok(None);
+        }
+        let mut calendar = Calendar::new(service_id.clone());
+        calendar.dates = (0..)
+            .map(|day| context.period.start_date + Duration::days(day))
+            .take_while(|date| *date <= context.period.end_date)
+            .collect();
+        collections.calendars.push(calendar)?;
+    }
+
+    let administration = &journey.administration;
+    if !collections.networks.contains_id(administration) {
+        collections.networks.push(Network {
+            id: administration.clone(),
+            name: administration.clone(),
+            timezone: Some(context.timezone),
+            ..Default::default()
+        })?;
+        collections.companies.push(Company {
+            id: administration.clone(),
+            name: administration.clone(),
+            ..Default::default()
+        })?;
+    }
+
+    let category = journey
+        .category
+        .clone()
+        .filter(|category| !category.is_empty())
+        .unwrap_or_else(|| UNKNOWN_CATEGORY.to_string());
+    if !collections.commercial_modes.contains_id(&category) {
+        collections.commercial_modes.push(CommercialMode {
+            id: category.clone(),
+            name: category.clone(),
+        })?;
+    }
+    let physical_mode_id = physical_mode(&category);
+    if !collections.physical_modes.contains_id(physical_mode_id) {
+        collections.physical_modes.push(PhysicalMode {
+            id: physical_mode_id.to_string(),
+            name: physical_mode_id.to_string(),
+            co2_emission: None,
+        })?;
+    }
+
+    let line_code = journey.line.clone().filter(|line| !line.is_empty());
+    let line_id = match &line_code {
+        Some(code) => format!("{}:{}:{}", administration, category, code),
+        None => format!("{}:{}", administration, category),
+    };
+    if !collections.lines.contains_id(&line_id) {
+        collections.lines.push(Line {
+            id: line_id.clone(),
+            name: match &line_code {
+                Some(code) => format!("{} {}", category, code),
+                None => category.clone(),
+            },
+            code: line_code,
+            network_id: administration.clone(),
+            commercial_mode_id: category,
+            ..Default::default()
+        })?;
+    }
+
+    let first_stop = &collections.stop_points[stop_point_idxs[0]];
+    let last_stop = &collections.stop_points[stop_point_idxs[stop_point_idxs.len() - 1]];
+    let route_id = format!("{}:{}:{}", line_id, first_stop.id, last_stop.id);
+    if !collections.routes.contains_id(&route_id) {
+        // the route name is computed from its stops when building the model
+        let route = Route {
+            id: route_id.clone(),
+            name: String::new(),
+            line_id,
+            destination_id: Some(last_stop.stop_area_id.clone()),
+            ..Default::default()
+        };
+        collections.routes.push(route)?;
+    }
+    Ok(Some((route_id, physical_mode_id.to_string(), service_id)))
+}
+
+fn add_journey(
+    collections: &mut Collections,
+    journey: &Journey,
+    context: &mut JourneyContext,
+) -> Result<()> {
+    let journey_id = format!("{}:{}", journey.administration, journey.number);
+    let mut stop_point_idxs = vec![];
+    for stop in &journey.stops {
+        match collections.stop_points.get_idx(&stop.stop_id) {
+            Some(idx) => stop_point_idxs.push(idx),
+            None => {
+                warn!(
+                    "FPLAN: journey {:?} skipped, stop {:?} not found in BAHNHOF",
+                    journey_id, stop.stop_id
+                );
+                return Ok(());
+            }
+        }
+    }
+    if stop_point_idxs.len() < 2 {
+        warn!(
+            "FPLAN: journey {:?} skipped, it has less than 2 stops",
+            journey_id
+        );
+        return Ok(());
+    }
+    let (route_id, physical_mode_id, service_id) =
+        match manage_journey_objects(collections, journey, &stop_point_idxs, context)? {
+            Some(ids) => ids,
+            None => {
+                warn!(
+                    "FPLAN: journey {:?} skipped, bitfield {:?} not found in BITFELD",
+                    journey_id,
+                    journey.bitfield.as_deref().unwrap_or_default()
+                );
+                return Ok(());
+            }
+        };
+
+    for cycle in 0..=journey.cycles {
+        let shift = Time::new(0, cycle * journey.cycle_minutes, 0);
+        let count = context.trip_counts.entry(journey_id.clone()).or_insert(0);
+        let id = if *count == 0 {
+            journey_id.clone()
+        } else {
+            format!("{}:{}", journey_id, count)
+        };
+        *count += 1;
+        let stop_times = journey
+            .stops
+            .iter()
+            .zip(&stop_point_idxs)
+            .enumerate()
+            .map(|(sequence, (stop, &stop_point_idx))| {
+                let arrival = stop.arrival.or(stop.departure).unwrap_or_default();
+                let departure = stop.departure.or(stop.arrival).unwrap_or_default();
+                StopTime {
+                    stop_point_idx,
+                    sequence: sequence as u32,
+                    arrival_time: arrival + shift,
+                    departure_time: departure + shift,
+                    boarding_duration: 0,
+                    alighting_duration: 0,
+                    pickup_type: if stop.no_boarding || stop.departure.is_none() {
+                        1
+                    } else {
+                        0
+                    },
+                    drop_off_type: if stop.no_alighting || stop.arrival.is_none() {
+                        1
+                    } else {
+                        0
+                    },
+                    datetime_estimated: false,
+                    local_zone_id: None,
+                    precision: None,
+                }
+            })
+            .collect();
+        collections.vehicle_journeys.push(VehicleJourney {
+            id,
+            route_id: route_id.clone(),
+            physical_mode_id: physical_mode_id.clone(),
+            dataset_id: context.dataset_id.to_string(),
+            service_id: service_id.clone(),
+            short_name: Some(journey.number.clone()),
+            company_id: journey.administration.clone(),
+            stop_times,
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
+
+pub(crate) fn manage_journeys(
+    collections: &mut Collections,
+    path: &Path,
+    period: &Period,
+    dataset_id: &str,
+    timezone: Tz,
+) -> Result<()> {
+    let mut context = JourneyContext {
+        period,
+        dataset_id,
+        timezone,
+        trip_counts: HashMap::new(),
+    };
+    for journey in read_journeys(path)? {
+        add_journey(collections, &journey, &mut context)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn period() -> Period {
+        Period {
+            start_date: Date::from_ymd(2020, 12, 13),
+            end_date: Date::from_ymd(2020, 12, 20),
+        }
+    }
+
+    #[test]
+    fn expand_bitfield() {
+        let dates = bitfield_dates("A1C0", &period()).unwrap();
+        assert_eq!(
+            vec![
+                Date::from_ymd(2020, 12, 13),
+                Date::from_ymd(2020, 12, 15),
+                Date::from_ymd(2020, 12, 20),
+            ],
+            dates
+        );
+        assert!(bitfield_dates("FZ", &period()).is_err());
+    }
+
+    #[test]
+    fn parse_journey_lines() {
+        let journey = parse_journey("*Z 002351 000011 101 012 060").unwrap();
+        assert_eq!("002351", journey.number);
+        assert_eq!("000011", journey.administration);
+        assert_eq!(12, journey.cycles);
+        assert_eq!(60, journey.cycle_minutes);
+
+        let stop = parse_journey_stop("8500218 Olten                 00904 -00906").unwrap();
+        assert_eq!("8500218", stop.stop_id);
+        assert_eq!(Some(Time::new(9, 4, 0)), stop.arrival);
+        assert_eq!(Some(Time::new(9, 6, 0)), stop.departure);
+        assert!(!stop.no_alighting);
+        assert!(stop.no_boarding);
+
+        let stop = parse_journey_stop("8500010 Basel SBB                    02505").unwrap();
+        assert_eq!(None, stop.arrival);
+        assert_eq!(Some(Time::new(25, 5, 0)), stop.departure);
+    }
+
+    #[test]
+    fn columns_beyond_the_line() {
+        assert_eq!("Z 0", columns("*Z 0", 2, 10));
+        assert_eq!("", columns("*Z", 4, 9));
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Language of a dataset, as [BCP-47] language tags
+//!
+//! The default language of a dataset is stored in `feed_infos.txt` with the
+//! `feed_lang` key (read from the `feed_lang` of the GTFS `feed_info.txt`).
+//!
+//! [BCP-47]: https://tools.ietf.org/html/bcp47
+
+use crate::model::Collections;
+
+/// Key of the default language of the dataset in `feed_infos.txt`
+pub const FEED_LANG: &str = "feed_lang";
+
+/// Checks the syntax of a BCP-47 language tag (e.g. `fr`, `en-GB`,
+/// `zh-Hant-TW`).
+///
+/// The primary language subtag has 2 or 3 letters (or 5 to 8 letters for
+/// registered languages), the following subtags 1 to 8 alphanumeric
+/// characters. Private use tags (`x-...`) are accepted. Whether the subtags
+/// are registered in the IANA registry is not checked.
+pub fn is_valid_language_tag(tag: &str) -> bool {
+    let is_valid_subtag = |subtag: &str| {
+        !subtag.is_empty() && subtag.len() <= 8 && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    };
+    let mut subtags = tag.split('-');
+    let primary_is_valid = match subtags.next() {
+        Some(primary) if primary.eq_ignore_ascii_case("x") => tag.len() > primary.len(),
+        Some(primary) => {
+            let len = primary.len();
+            (len == 2 || len == 3 || (5..=8).contains(&len))
+                && primary.chars().all(|c| c.is_ascii_alphabetic())
+        }
+        None => false,
+    };
+    primary_is_valid && subtags.all(is_valid_subtag)
+}
+
+impl Collections {
+    /// Default language of the dataset, from the `feed_lang` of
+    /// `feed_infos.txt`
+    pub fn feed_language(&self) -> Option<&str> {
+        self.feed_infos.get(FEED_LANG).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn valid_language_tags() {
+        for tag in &[
+            "fr",
+            "FR",
+            "deu",
+            "en-GB",
+            "zh-Hant-TW",
+            "es-419",
+            "x-private",
+        ] {
+            assert!(is_valid_language_tag(tag), "{} should be valid", tag);
+        }
+    }
+
+    #[test]
+    fn invalid_language_tags() {
+        for tag in &[
+            "",
+            "f",
+            "french language",
+            "en_GB",
+            "fr-",
+            "en--GB",
+            "x",
+            "1a",
+        ] {
+            assert!(!is_valid_language_tag(tag), "{} should be invalid", tag);
+        }
+    }
+}
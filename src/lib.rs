@@ -15,17 +15,69 @@
 //! The `transit_model` crate proposes a model to manage transit data.
 //! It can import and export data from
 //! [GTFS](https://gtfs.org/reference/static) and
-//! [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md).
+//! [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md),
+//! and import the [HAFAS raw data](hafas/index.html) and
+//! [VDV-452](vdv452/index.html) exports.
 //!
 //! # Features
-//! `transit_model` has 2 possible features: `proj` and `xmllint`.
+//! `transit_model` has 9 possible features: `xml` and `zip` (the default
+//! features), `proj`, `async`, `sqlite`, `parquet-export`, `tar-gz`,
+//! `tar-zst` and `xmllint`.
+//!
+//! ## Minimal core
+//! Without the default features (`default-features = false`), only the core
+//! is built: the [objects](objects/index.html), the
+//! [Model](model/struct.Model.html) and its processing, and the reading and
+//! writing of the datasets in directories (e.g.
+//! [ntfs::read](ntfs/fn.read.html) or
+//! [gtfs::read_from_path](gtfs/fn.read_from_path.html)), for the
+//! applications embedding only the model.
+//!
+//! ## `xml`
+//! `xml` feature adds the XML formats: the reading of the
+//! [SIRI SX](siri_sx/index.html) situations and, with `proj`, the NeTEx
+//! France export.
+//!
+//! ## `zip`
+//! `zip` feature adds the reading and writing of ZIP archives, e.g.
+//! [gtfs::read_from_zip](gtfs/fn.read_from_zip.html) or
+//! [ntfs::write_to_zip](ntfs/fn.write_to_zip.html), and the
+//! [archive](archive/index.html) module.
 //!
 //! ## `proj`
 //! `proj` feature is used for geolocation conversion (see
-//! [Proj]). `proj` feature is used, for example, to export NeTEx France format.
+//! [Proj]). `proj` feature is used, for example, to export NeTEx France format
+//! (with the `xml` feature) or to reproject the coordinates of a dataset (see
+//! [crs](crs/index.html)).
 //!
 //! [Proj]: https://proj.org
 //!
+//! ## `async`
+//! `async` feature adds async variants of the reading and writing functions,
+//! running on the blocking thread pool of [tokio] (see
+//! [asynchronous](asynchronous/index.html)).
+//!
+//! [tokio]: https://tokio.rs
+//!
+//! ## `sqlite`
+//! `sqlite` feature adds the export of a `Model` to a [SQLite] database (see
+//! [sqlite](sqlite/index.html)).
+//!
+//! [SQLite]: https://sqlite.org
+//!
+//! ## `parquet-export`
+//! `parquet-export` feature adds the export of the stops, vehicle journeys
+//! and stop times of a `Model` to [Parquet] files (see
+//! [parquet_export](parquet_export/index.html)).
+//!
+//! [Parquet]: https://parquet.apache.org
+//!
+//! ## `tar-gz` and `tar-zst`
+//! `tar-gz` and `tar-zst` features add the `.tar.gz` and `.tar.zst` formats
+//! to the archives written, e.g. by
+//! [ntfs::write_to_archive](ntfs/fn.write_to_archive.html), in addition to
+//! ZIP (see [archive](archive/index.html)). They enable the `zip` feature.
+//!
 //! ## `xmllint`
 //! Most likely, you don't need this feature as it's only used for additional
 //! tests. It doesn't add any functionality to `transit_model`. If you're a
@@ -41,21 +93,62 @@
 mod utils;
 mod add_prefix;
 pub use add_prefix::{AddPrefix, PrefixConfiguration};
+pub mod apply_rules;
+#[cfg(feature = "zip")]
+pub mod archive;
+#[cfg(feature = "async")]
+pub mod asynchronous;
 pub mod calendars;
+pub mod changelog;
+pub mod clean;
+pub mod comments;
+pub mod crs;
+pub mod diff;
+pub mod distances;
+pub mod dwell_times;
+pub mod error;
+pub mod fares;
+pub mod filter;
+pub mod footnotes;
+pub mod geometries;
 #[macro_use]
 pub mod objects;
 pub mod gtfs;
+pub mod hafas;
+pub mod languages;
+pub mod line_sort_orders;
+pub mod mixed_modes;
 pub mod model;
-#[cfg(feature = "proj")]
+pub mod model_builder;
+#[cfg(all(feature = "proj", feature = "xml"))]
 pub mod netex_france;
+#[cfg(feature = "xml")]
 pub mod netex_utils;
 pub mod ntfs;
+#[cfg(feature = "parquet-export")]
+pub mod parquet_export;
+pub mod pipeline;
 pub mod read_utils;
+pub mod report;
+pub mod ridership;
+pub mod schema;
+pub mod search;
+#[cfg(feature = "xml")]
+pub mod siri_sx;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod statistics;
+pub mod stop_areas;
+pub mod stop_matching;
+pub mod tags;
 #[doc(hidden)]
 pub mod test_utils;
 pub mod transfers;
+pub mod validation;
 pub mod validity_period;
+pub mod vdv452;
 pub mod vptranslator;
+pub mod zones;
 
 /// Current version of the NTFS format
 pub const NTFS_VERSION: &str = "0.11.2";
@@ -80,4 +173,5 @@ pub type Error = failure::Error;
 /// The corresponding result type used by the crate.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+pub use crate::error::TransitModelError;
 pub use crate::model::Model;
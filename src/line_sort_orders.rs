@@ -0,0 +1,293 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Automatic sort order of the lines, see
+//! [Collections::assign_line_sort_orders](../model/struct.Collections.html#method.assign_line_sort_orders),
+//! and [natural_cmp](fn.natural_cmp.html) ordering the line codes.
+
+use crate::{
+    model::Collections,
+    objects::{Line, ObjectType},
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::Deserialize;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+use typed_index_collection::CollectionWithId;
+
+// Splits `s` into its runs of digits and of other characters
+fn chunks(s: &str) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    let mut previous_is_digit = None;
+    for (i, c) in s.char_indices() {
+        let is_digit = c.is_ascii_digit();
+        if previous_is_digit.map_or(false, |previous| previous != is_digit) {
+            chunks.push(&s[start..i]);
+            start = i;
+        }
+        previous_is_digit = Some(is_digit);
+    }
+    if start < s.len() {
+        chunks.push(&s[start..]);
+    }
+    chunks
+}
+
+fn cmp_chunks(chunk1: &str, chunk2: &str) -> Ordering {
+    let is_number = |chunk: &str| chunk.bytes().all(|b| b.is_ascii_digit());
+    if is_number(chunk1) && is_number(chunk2) {
+        let number1 = chunk1.trim_start_matches('0');
+        let number2 = chunk2.trim_start_matches('0');
+        number1
+            .len()
+            .cmp(&number2.len())
+            .then_with(|| number1.cmp(number2))
+    } else {
+        chunk1.to_lowercase().cmp(&chunk2.to_lowercase())
+    }
+}
+
+/// Compares 2 strings in natural order: the runs of digits are compared by
+/// their numeric value (`"2"` < `"10"` < `"10A"` < `"10b"`), the other
+/// characters case-insensitively.
+pub fn natural_cmp(s1: &str, s2: &str) -> Ordering {
+    let (chunks1, chunks2) = (chunks(s1), chunks(s2));
+    chunks1
+        .iter()
+        .zip(&chunks2)
+        .map(|(chunk1, chunk2)| cmp_chunks(chunk1, chunk2))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or_else(|| chunks1.len().cmp(&chunks2.len()))
+        .then_with(|| s1.cmp(s2))
+}
+
+// The code of the line, or its name if it has no code
+fn line_label(line: &Line) -> &str {
+    line.code.as_deref().unwrap_or(&line.name)
+}
+
+#[derive(Debug, Deserialize)]
+struct PinnedSortOrder {
+    line_id: String,
+    line_sort_order: u32,
+}
+
+/// Reads the sort orders pinned for some lines from the CSV file at `path`,
+/// with the columns `line_id` and `line_sort_order`.
+pub fn read_pinned_sort_orders<P: AsRef<Path>>(path: P) -> Result<BTreeMap<String, u32>> {
+    let path = path.as_ref();
+    info!("Reading pinned line sort orders {:?}", path);
+    let mut rdr =
+        csv::Reader::from_path(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let mut sort_orders = BTreeMap::new();
+    for pinned in rdr.deserialize() {
+        let pinned: PinnedSortOrder =
+            pinned.with_context(|_| format!("Error reading {:?}", path))?;
+        sort_orders.insert(pinned.line_id, pinned.line_sort_order);
+    }
+    Ok(sort_orders)
+}
+
+impl Collections {
+    /// Assigns a `sort_order` to the lines without one, so that the display
+    /// order of the lines is stable across conversions.
+    ///
+    /// The lines with a `pinned` sort order get it. In each network, the
+    /// other lines without sort order are numbered after the highest sort
+    /// order of the network, in the [natural order](../line_sort_orders/fn.natural_cmp.html)
+    /// of their code (or name when they have no code). The unknown pinned
+    /// lines are reported, as are the lines left without sort order because
+    /// their network has none left after `u32::MAX`.
+    pub fn assign_line_sort_orders(&mut self, pinned: &BTreeMap<String, u32>, report: &mut Report) {
+        for line_id in pinned.keys() {
+            if !self.lines.contains_id(line_id) {
+                report.add_warning(
+                    format!("line {:?} not found, its sort order is ignored", line_id),
+                    ReportType::ObjectNotFound,
+                );
+            }
+        }
+        let mut lines = self.lines.take();
+        for line in &mut lines {
+            if let Some(&sort_order) = pinned.get(&line.id) {
+                line.sort_order = Some(sort_order);
+            }
+        }
+        // network -> next free sort order, `None` when there is none left
+        let mut next_sort_orders: HashMap<String, Option<u32>> = HashMap::new();
+        for line in &lines {
+            if let Some(sort_order) = line.sort_order {
+                let next = next_sort_orders
+                    .entry(line.network_id.clone())
+                    .or_insert(Some(0));
+                *next = match (*next, sort_order.checked_add(1)) {
+                    (Some(next), Some(after)) => Some(next.max(after)),
+                    _ => None,
+                };
+            }
+        }
+        let mut unsorted: Vec<&mut Line> = lines
+            .iter_mut()
+            .filter(|line| line.sort_order.is_none())
+            .collect();
+        unsorted.sort_by(|line1, line2| {
+            line1
+                .network_id
+                .cmp(&line2.network_id)
+                .then_with(|| natural_cmp(line_label(line1), line_label(line2)))
+                .then_with(|| line1.id.cmp(&line2.id))
+        });
+        for line in unsorted {
+            let next = next_sort_orders
+                .entry(line.network_id.clone())
+                .or_insert(Some(0));
+            let sort_order = match *next {
+                Some(sort_order) => sort_order,
+                None => {
+                    report.add(
+                        Severity::Warning,
+                        ReportEntry::new(
+                            ReportType::InvalidValue,
+                            format!(
+                                "line {:?}: no sort order left in network {:?}, none set",
+                                line.id, line.network_id
+                            ),
+                        )
+                        .with_object(ObjectType::Line, &line.id),
+                    );
+                    continue;
+                }
+            };
+            *next = sort_order.checked_add(1);
+            line.sort_order = Some(sort_order);
+            report.add(
+                Severity::Info,
+                ReportEntry::new(
+                    ReportType::PropertyUpdated,
+                    format!("line {:?}: sort order set to {}", line.id, sort_order),
+                )
+                .with_object(ObjectType::Line, &line.id),
+            );
+        }
+        self.lines = CollectionWithId::new(lines).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn natural_order() {
+        let mut codes = vec!["10b", "T2", "2", "10A", "N1", "010", "1", "t1", "A"];
+        codes.sort_by(|c1, c2| natural_cmp(c1, c2));
+        assert_eq!(
+            vec!["1", "2", "010", "10A", "10b", "A", "N1", "t1", "T2"],
+            codes
+        );
+    }
+
+    fn line(id: &str, network_id: &str, code: &str, sort_order: Option<u32>) -> Line {
+        Line {
+            id: id.to_string(),
+            network_id: network_id.to_string(),
+            code: Some(code.to_string()),
+            sort_order,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn assign_sort_orders() {
+        let mut collections = Collections::default();
+        collections.lines = CollectionWithId::new(vec![
+            line("l1", "n1", "10", None),
+            line("l2", "n1", "2", None),
+            line("l3", "n1", "1", Some(5)),
+            line("l4", "n1", "3", None),
+            line("l5", "n2", "B", None),
+            line("l6", "n2", "A", None),
+        ])
+        .unwrap();
+        let pinned: BTreeMap<String, u32> = vec![("l4".to_string(), 0), ("unknown".to_string(), 1)]
+            .into_iter()
+            .collect();
+        let mut report = Report::default();
+        collections.assign_line_sort_orders(&pinned, &mut report);
+
+        let sort_orders: Vec<(&str, Option<u32>)> = collections
+            .lines
+            .values()
+            .map(|line| (line.id.as_str(), line.sort_order))
+            .collect();
+        assert_eq!(
+            vec![
+                ("l1", Some(7)),
+                ("l2", Some(6)),
+                ("l3", Some(5)),
+                ("l4", Some(0)),
+                ("l5", Some(1)),
+                ("l6", Some(0)),
+            ],
+            sort_orders
+        );
+        assert_eq!(1, report.entries(Severity::Warning).len());
+        assert_eq!(4, report.entries(Severity::Info).len());
+    }
+
+    #[test]
+    fn no_sort_order_left() {
+        let mut collections = Collections::default();
+        collections.lines = CollectionWithId::new(vec![
+            line("l1", "n1", "1", Some(u32::MAX - 1)),
+            line("l2", "n1", "2", None),
+            line("l3", "n1", "3", None),
+            line("l4", "n2", "1", Some(u32::MAX)),
+            line("l5", "n2", "2", None),
+        ])
+        .unwrap();
+        let mut report = Report::default();
+        collections.assign_line_sort_orders(&BTreeMap::new(), &mut report);
+
+        let sort_orders: Vec<(&str, Option<u32>)> = collections
+            .lines
+            .values()
+            .map(|line| (line.id.as_str(), line.sort_order))
+            .collect();
+        assert_eq!(
+            vec![
+                ("l1", Some(u32::MAX - 1)),
+                ("l2", Some(u32::MAX)),
+                ("l3", None),
+                ("l4", Some(u32::MAX)),
+                ("l5", None),
+            ],
+            sort_orders
+        );
+        let warnings: Vec<Option<&str>> = report
+            .entries(Severity::Warning)
+            .iter()
+            .map(|warning| warning.object_id.as_deref())
+            .collect();
+        assert_eq!(vec![Some("l3"), Some("l5")], warnings);
+    }
+}
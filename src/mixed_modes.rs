@@ -0,0 +1,274 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! See functions mixed_mode_routes and split_mixed_mode_routes
+
+use crate::{
+    model::{Collections, Model},
+    objects::{CommercialMode, ObjectType},
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use log::info;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+use typed_index_collection::CollectionWithId;
+
+/// Routes whose vehicle journeys have several physical modes (e.g. a bus
+/// replacement on a rail line), with the number of vehicle journeys of each
+/// physical mode.
+pub fn mixed_mode_routes(collections: &Collections) -> BTreeMap<String, BTreeMap<String, usize>> {
+    let mut route_modes: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+    for vj in collections.vehicle_journeys.values() {
+        *route_modes
+            .entry(vj.route_id.clone())
+            .or_insert_with(BTreeMap::new)
+            .entry(vj.physical_mode_id.clone())
+            .or_insert(0) += 1;
+    }
+    route_modes
+        .into_iter()
+        .filter(|(_, modes)| modes.len() > 1)
+        .collect()
+}
+
+// The physical mode with the most vehicle journeys, the first one by
+// identifier on a tie
+fn main_mode(modes: &BTreeMap<String, usize>) -> &str {
+    modes
+        .iter()
+        .max_by(|(id1, count1), (id2, count2)| count1.cmp(count2).then_with(|| id2.cmp(id1)))
+        .map(|(id, _)| id.as_str())
+        .unwrap_or("")
+}
+
+// The identifier `<id>:<physical_mode_id>` of a copy of an object for a
+// physical mode, suffixed by `:<n>` if it's already used
+fn split_id<T>(collection: &CollectionWithId<T>, id: &str, physical_mode_id: &str) -> String {
+    let split_id = format!("{}:{}", id, physical_mode_id);
+    if !collection.contains_id(&split_id) {
+        return split_id;
+    }
+    (1..)
+        .map(|n| format!("{}:{}", split_id, n))
+        .find(|id| !collection.contains_id(id))
+        .unwrap()
+}
+
+// The commercial mode of the lines split for a physical mode: the one with
+// the identifier of the physical mode, created if needed
+fn split_commercial_mode(
+    collections: &mut Collections,
+    physical_mode_id: &str,
+    report: &mut Report,
+) -> Result<String> {
+    if !collections.commercial_modes.contains_id(physical_mode_id) {
+        let name = collections
+            .physical_modes
+            .get(physical_mode_id)
+            .map_or_else(|| physical_mode_id.to_string(), |mode| mode.name.clone());
+        collections.commercial_modes.push(CommercialMode {
+            id: physical_mode_id.to_string(),
+            name,
+        })?;
+        report.add(
+            Severity::Info,
+            ReportEntry::new(
+                ReportType::ObjectCreated,
+                format!(
+                    "commercial mode {:?} created for the split lines",
+                    physical_mode_id
+                ),
+            ),
+        );
+    }
+    Ok(physical_mode_id.to_string())
+}
+
+// The copy of the line `line_id` for the physical mode, created once by
+// line and physical mode
+fn split_line(
+    collections: &mut Collections,
+    split_line_ids: &mut HashMap<(String, String), String>,
+    line_id: &str,
+    physical_mode_id: &str,
+    report: &mut Report,
+) -> Result<String> {
+    let key = (line_id.to_string(), physical_mode_id.to_string());
+    if let Some(new_line_id) = split_line_ids.get(&key) {
+        return Ok(new_line_id.clone());
+    }
+    let mut line = match collections.lines.get(line_id) {
+        Some(line) => line.clone(),
+        None => return Ok(line_id.to_string()),
+    };
+    let new_line_id = split_id(&collections.lines, line_id, physical_mode_id);
+    line.id = new_line_id.clone();
+    line.codes.clear();
+    line.commercial_mode_id = split_commercial_mode(collections, physical_mode_id, report)?;
+    collections.lines.push(line)?;
+    report.add(
+        Severity::Info,
+        ReportEntry::new(
+            ReportType::ObjectCreated,
+            format!(
+                "line {:?} created for the physical mode {:?} of line {:?}",
+                new_line_id, physical_mode_id, line_id
+            ),
+        )
+        .with_object(ObjectType::Line, &new_line_id),
+    );
+    split_line_ids.insert(key, new_line_id.clone());
+    Ok(new_line_id)
+}
+
+/// Splits the routes whose vehicle journeys have several physical modes.
+///
+/// A route keeps the vehicle journeys of its main physical mode (the one with
+/// the most vehicle journeys). The vehicle journeys of each other physical
+/// mode are moved to a copy of the route, whose identifier is suffixed by the
+/// physical mode (e.g. `RERA:Bus`, followed by a number if this identifier
+/// is already used). With `split_lines`, these copies are attached to a copy
+/// of the line suffixed the same way, so the line of each physical mode can
+/// be filtered on its own. The commercial mode of a copy of a line is the
+/// one with the identifier of the physical mode, created if needed. The
+/// codes of the copies are not copied, as they identify the original
+/// object.
+///
+/// The mixed-mode routes and the objects created are written in
+/// `report_path`, as CSV if it has a `.csv` extension, as JSON otherwise.
+pub fn split_mixed_mode_routes<P: AsRef<Path>>(
+    model: Model,
+    split_lines: bool,
+    report_path: P,
+) -> Result<Model> {
+    info!("Splitting mixed-mode routes...");
+    let mut report = Report::default();
+    let mixed_mode_routes = mixed_mode_routes(&model);
+    let mut collections = model.into_collections();
+
+    let mut new_route_ids: HashMap<(String, String), String> = HashMap::new();
+    let mut split_line_ids: HashMap<(String, String), String> = HashMap::new();
+    for (route_id, modes) in &mixed_mode_routes {
+        let physical_mode_ids: Vec<&str> = modes.keys().map(String::as_str).collect();
+        report.add(
+            Severity::Warning,
+            ReportEntry::new(
+                ReportType::MixedPhysicalModes,
+                format!(
+                    "route {:?} has vehicle journeys of physical modes {}",
+                    route_id,
+                    physical_mode_ids.join(", ")
+                ),
+            )
+            .with_object(ObjectType::Route, route_id),
+        );
+        let route = match collections.routes.get(route_id) {
+            Some(route) => route.clone(),
+            None => continue,
+        };
+        let main_mode = main_mode(modes);
+        for physical_mode_id in physical_mode_ids.into_iter().filter(|id| *id != main_mode) {
+            let mut new_route = route.clone();
+            new_route.id = split_id(&collections.routes, &route.id, physical_mode_id);
+            new_route.codes.clear();
+            if split_lines {
+                new_route.line_id = split_line(
+                    &mut collections,
+                    &mut split_line_ids,
+                    &route.line_id,
+                    physical_mode_id,
+                    &mut report,
+                )?;
+            }
+            report.add(
+                Severity::Info,
+                ReportEntry::new(
+                    ReportType::ObjectCreated,
+                    format!(
+                        "route {:?} created for the physical mode {:?} of route {:?}",
+                        new_route.id, physical_mode_id, route.id
+                    ),
+                )
+                .with_object(ObjectType::Route, &new_route.id),
+            );
+            new_route_ids.insert(
+                (route.id.clone(), physical_mode_id.to_string()),
+                new_route.id.clone(),
+            );
+            collections.routes.push(new_route)?;
+        }
+    }
+
+    let mut vehicle_journeys = collections.vehicle_journeys.take();
+    for vj in &mut vehicle_journeys {
+        let key = (vj.route_id.clone(), vj.physical_mode_id.clone());
+        if let Some(new_route_id) = new_route_ids.get(&key) {
+            vj.route_id = new_route_id.clone();
+        }
+    }
+    collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
+
+    let report_path = report_path.as_ref();
+    info!("Writing report {:?}", report_path);
+    report.write(report_path)?;
+
+    Model::new(collections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Route;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn main_mode_has_the_most_vehicle_journeys() {
+        let modes: BTreeMap<String, usize> = vec![
+            ("Bus".to_string(), 2),
+            ("RapidTransit".to_string(), 5),
+            ("Tramway".to_string(), 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!("RapidTransit", main_mode(&modes));
+    }
+
+    #[test]
+    fn split_id_not_used() {
+        let routes = CollectionWithId::new(vec![
+            Route {
+                id: "R:Bus".to_string(),
+                ..Default::default()
+            },
+            Route {
+                id: "R:Bus:1".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        assert_eq!("R:Metro", split_id(&routes, "R", "Metro"));
+        assert_eq!("R:Bus:2", split_id(&routes, "R", "Bus"));
+    }
+
+    #[test]
+    fn main_mode_on_a_tie() {
+        let modes: BTreeMap<String, usize> = vec![("Train".to_string(), 2), ("Bus".to_string(), 2)]
+            .into_iter()
+            .collect();
+        assert_eq!("Bus", main_mode(&modes));
+    }
+}
@@ -14,7 +14,13 @@
 
 //! Definition of the navitia transit model.
 
-use crate::{objects::*, Error, Result};
+use crate::{
+    error::TransitModelError,
+    objects::*,
+    report::{Report, ReportEntry, ReportType, Severity},
+    search::SearchIndex,
+    Error, Result,
+};
 use chrono::NaiveDate;
 use derivative::Derivative;
 use failure::{bail, format_err};
@@ -27,10 +33,11 @@ use serde::{Deserialize, Serialize};
 use skip_error::skip_error_and_log;
 use std::{
     cmp::{self, Ordering, Reverse},
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     convert::TryFrom,
     iter::FromIterator,
     ops,
+    sync::{Arc, Mutex},
 };
 use typed_index_collection::{Collection, CollectionWithId, Id, Idx};
 
@@ -66,6 +73,11 @@ pub const TAXI_PHYSICAL_MODE: &str = "Taxi";
 pub const TRAIN_PHYSICAL_MODE: &str = "Train";
 /// Physical mode for Tramway
 pub const TRAMWAY_PHYSICAL_MODE: &str = "Tramway";
+/// System of the codes of the vehicle journeys keeping the identifier of
+/// their calendar before
+/// [Collections::consolidate_calendars](struct.Collections.html#method.consolidate_calendars)
+pub const ORIGINAL_SERVICE_ID_SYSTEM: &str = "original_service_id";
+
 lazy_static! {
     static ref CO2_EMISSIONS: std::collections::HashMap<&'static str, f32> = {
         let mut modes_map = std::collections::HashMap::new();
@@ -98,7 +110,7 @@ lazy_static! {
 }
 
 /// The set of collections representing the model.
-#[derive(Derivative, Serialize, Deserialize, Debug)]
+#[derive(Derivative, Serialize, Deserialize, Debug, Clone)]
 #[derivative(Default)]
 #[allow(missing_docs)]
 pub struct Collections {
@@ -117,12 +129,19 @@ pub struct Collections {
     pub feed_infos: BTreeMap<String, String>,
     pub calendars: CollectionWithId<Calendar>,
     pub companies: CollectionWithId<Company>,
+    pub company_parents: Collection<CompanyParent>,
     pub comments: CollectionWithId<Comment>,
     pub equipments: CollectionWithId<Equipment>,
     pub transfers: Collection<Transfer>,
     pub trip_properties: CollectionWithId<TripProperty>,
     pub geometries: CollectionWithId<Geometry>,
     pub admin_stations: Collection<AdminStation>,
+    pub stop_area_memberships: Collection<StopAreaMembership>,
+    pub translations: Collection<Translation>,
+    pub booking_rules: CollectionWithId<BookingRule>,
+    pub booking_rule_links: Collection<BookingRuleLink>,
+    pub disruptions: CollectionWithId<Disruption>,
+    pub disruption_impacts: Collection<DisruptionImpact>,
     #[serde(skip)]
     //HashMap<(vehicle_journey_id, stop_sequence), headsign>,
     pub stop_time_headsigns: HashMap<(String, u32), String>,
@@ -132,6 +151,9 @@ pub struct Collections {
     #[serde(skip)]
     //HashMap<(vehicle_journey_id, stop_sequence), comment_id>
     pub stop_time_comments: HashMap<(String, u32), String>,
+    #[serde(skip)]
+    //HashMap<(vehicle_journey_id, stop_sequence), continuous stopping>
+    pub stop_time_continuous_stoppings: HashMap<(String, u32), ContinuousStopping>,
     pub prices_v1: Collection<PriceV1>,
     pub od_fares_v1: Collection<ODFareV1>,
     pub fares_v1: Collection<FareV1>,
@@ -140,15 +162,63 @@ pub struct Collections {
     pub ticket_prices: Collection<TicketPrice>,
     pub ticket_use_perimeters: Collection<TicketUsePerimeter>,
     pub ticket_use_restrictions: Collection<TicketUseRestriction>,
+    pub fare_zones: CollectionWithId<FareZone>,
     pub pathways: CollectionWithId<Pathway>,
     pub levels: CollectionWithId<Level>,
     pub grid_calendars: CollectionWithId<GridCalendar>,
     pub grid_exception_dates: Collection<GridExceptionDate>,
     pub grid_periods: Collection<GridPeriod>,
     pub grid_rel_calendar_line: Collection<GridRelCalendarLine>,
+    #[serde(skip)]
+    pub extra_columns: ExtraColumns,
 }
 
 impl Collections {
+    /// Stop areas of the stop point `stop_point_idx`: its main stop area
+    /// first, then its secondary stop areas (see
+    /// [StopAreaMembership](../objects/struct.StopAreaMembership.html)).
+    pub fn stop_areas_of_stop_point(&self, stop_point_idx: Idx<StopPoint>) -> Vec<Idx<StopArea>> {
+        let stop_point = &self.stop_points[stop_point_idx];
+        let mut stop_area_idxs: Vec<Idx<StopArea>> = self
+            .stop_areas
+            .get_idx(&stop_point.stop_area_id)
+            .into_iter()
+            .collect();
+        for membership in self.stop_area_memberships.values() {
+            if membership.stop_id != stop_point.id {
+                continue;
+            }
+            if let Some(stop_area_idx) = self.stop_areas.get_idx(&membership.stop_area_id) {
+                if !stop_area_idxs.contains(&stop_area_idx) {
+                    stop_area_idxs.push(stop_area_idx);
+                }
+            }
+        }
+        stop_area_idxs
+    }
+
+    /// Parent companies of the company `company_id`, from its direct parent
+    /// to the top of the hierarchy (see
+    /// [CompanyParent](../objects/struct.CompanyParent.html)).
+    pub fn parent_company_ids<'a>(&'a self, company_id: &'a str) -> Vec<&'a str> {
+        let mut parent_company_ids: Vec<&str> = vec![];
+        let mut current_company_id = company_id;
+        while let Some(link) = self
+            .company_parents
+            .values()
+            .find(|link| link.company_id == current_company_id)
+        {
+            let parent_company_id = link.parent_company_id.as_str();
+            // a hierarchy with a cycle stops before it loops
+            if parent_company_id == company_id || parent_company_ids.contains(&parent_company_id) {
+                break;
+            }
+            parent_company_ids.push(parent_company_id);
+            current_company_id = parent_company_id;
+        }
+        parent_company_ids
+    }
+
     /// Restrict the validity period of the current `Collections` with the start_date and end_date
     pub fn restrict_period(&mut self, start_date: NaiveDate, end_date: NaiveDate) -> Result<()> {
         let mut calendars = self.calendars.take();
@@ -167,12 +237,24 @@ impl Collections {
         }
         self.datasets = CollectionWithId::new(data_sets)?;
         self.calendars = CollectionWithId::new(calendars)?;
+        // the production period is computed again when written
+        self.feed_infos.remove("feed_start_date");
+        self.feed_infos.remove("feed_end_date");
         Ok(())
     }
 
     /// Keep the collections consistent for the new model by purging unreferenced data by
     /// calendars
     pub fn sanitize(&mut self) -> Result<()> {
+        self.sanitize_keeping_stop_points(HashSet::new())
+    }
+
+    // As `sanitize`, the stop points of `stop_points_used` being kept besides
+    // the ones of the stop times
+    fn sanitize_keeping_stop_points(
+        &mut self,
+        mut stop_points_used: HashSet<String>,
+    ) -> Result<()> {
         fn log_object_removed(object_type: &str, id: &str) {
             debug!("{} with ID {} has been removed", object_type, id);
         }
@@ -200,7 +282,6 @@ impl Collections {
         let mut companies_used = HashSet::<String>::new();
         let mut trip_properties_used = HashSet::<String>::new();
         let mut route_ids_used = HashSet::<String>::new();
-        let mut stop_points_used = HashSet::<String>::new();
         let mut data_sets_used = HashSet::<String>::new();
         let mut physical_modes_used = HashSet::<String>::new();
         let mut comments_used = HashSet::<String>::new();
@@ -344,6 +425,12 @@ impl Collections {
                 }
             })
             .collect::<Vec<_>>();
+        stop_area_ids_used.extend(
+            self.stop_area_memberships
+                .values()
+                .filter(|membership| stop_points_used.contains(&membership.stop_id))
+                .map(|membership| membership.stop_area_id.clone()),
+        );
 
         let mut networks_used: HashSet<String> = HashSet::new();
         let mut commercial_modes_used: HashSet<String> = HashSet::new();
@@ -432,6 +519,11 @@ impl Collections {
             }
         }
         self.stop_areas = CollectionWithId::new(stop_areas)?;
+        let (stop_points, stop_areas) = (&self.stop_points, &self.stop_areas);
+        self.stop_area_memberships.retain(|membership| {
+            stop_points.contains_id(&membership.stop_id)
+                && stop_areas.contains_id(&membership.stop_area_id)
+        });
         self.routes = CollectionWithId::new(routes)?;
         let vehicle_journeys_used: HashSet<String> = vjs.iter().map(|vj| vj.id.clone()).collect();
         self.vehicle_journeys = CollectionWithId::new(vjs)?;
@@ -443,6 +535,8 @@ impl Collections {
             .retain(|(vj_id, _), _| vehicle_journeys_used.contains(vj_id));
         self.stop_time_headsigns
             .retain(|(vj_id, _), _| vehicle_journeys_used.contains(vj_id));
+        self.stop_time_continuous_stoppings
+            .retain(|(vj_id, _), _| vehicle_journeys_used.contains(vj_id));
         self.grid_rel_calendar_line
             .retain(|grid_rel_calendar_line| {
                 line_ids_used.contains(&grid_rel_calendar_line.line_id)
@@ -477,10 +571,21 @@ impl Collections {
             .retain(log_predicate("Geometry", |geometry: &Geometry| {
                 geometries_used.contains(&geometry.id)
             }));
+        let parent_companies_used: Vec<String> = companies_used
+            .iter()
+            .flat_map(|company_id| self.parent_company_ids(company_id))
+            .map(|company_id| company_id.to_string())
+            .collect();
+        companies_used.extend(parent_companies_used);
         self.companies
             .retain(log_predicate("Company", |company: &Company| {
                 companies_used.contains(&company.id)
             }));
+        let companies = &self.companies;
+        self.company_parents.retain(|link| {
+            companies.contains_id(&link.company_id)
+                && companies.contains_id(&link.parent_company_id)
+        });
         self.equipments
             .retain(log_predicate("Equipment", |equipment: &Equipment| {
                 equipments_used.contains(&equipment.id)
@@ -505,6 +610,52 @@ impl Collections {
         self.levels
             .retain(|level| level_id_used.contains(&level.id));
         self.calendars.retain(|c| calendars_used.contains(&c.id));
+        let (stop_areas, stop_points) = (&self.stop_areas, &self.stop_points);
+        let (lines, networks) = (&self.lines, &self.networks);
+        self.translations
+            .retain(|translation| match translation.object_type {
+                ObjectType::StopArea => stop_areas.contains_id(&translation.object_id),
+                ObjectType::StopPoint => stop_points.contains_id(&translation.object_id),
+                ObjectType::Line => lines.contains_id(&translation.object_id),
+                ObjectType::Network => networks.contains_id(&translation.object_id),
+                _ => false,
+            });
+        let vehicle_journeys = &self.vehicle_journeys;
+        self.booking_rule_links
+            .retain(|link| match link.object_type {
+                ObjectType::Line => lines.contains_id(&link.object_id),
+                ObjectType::VehicleJourney => vehicle_journeys.contains_id(&link.object_id),
+                _ => false,
+            });
+        let booking_rules_used: HashSet<&str> = self
+            .booking_rule_links
+            .values()
+            .map(|link| link.booking_rule_id.as_str())
+            .collect();
+        self.booking_rules.retain(log_predicate(
+            "Booking Rule",
+            |booking_rule: &BookingRule| booking_rules_used.contains(booking_rule.id.as_str()),
+        ));
+        let disruptions = &self.disruptions;
+        self.disruption_impacts.retain(|impact| {
+            disruptions.contains_id(&impact.disruption_id)
+                && match impact.object_type {
+                    ObjectType::Network => networks.contains_id(&impact.object_id),
+                    ObjectType::Line => lines.contains_id(&impact.object_id),
+                    ObjectType::StopArea => stop_areas.contains_id(&impact.object_id),
+                    ObjectType::StopPoint => stop_points.contains_id(&impact.object_id),
+                    _ => false,
+                }
+        });
+        let disruptions_used: HashSet<&str> = self
+            .disruption_impacts
+            .values()
+            .map(|impact| impact.disruption_id.as_str())
+            .collect();
+        self.disruptions
+            .retain(log_predicate("Disruption", |disruption: &Disruption| {
+                disruptions_used.contains(disruption.id.as_str())
+            }));
         Ok(())
     }
 
@@ -541,7 +692,6 @@ impl Collections {
     pub fn enhance_line_opening_time(&mut self) {
         type TimeTable = BTreeMap<u8, Time>;
         const HOURS_PER_DAY: u8 = 24;
-        const SECONDS_PER_DAY: u32 = 86400;
 
         fn get_vjs_by_line(c: &Collections) -> HashMap<String, IdxSet<VehicleJourney>> {
             c.vehicle_journeys
@@ -590,13 +740,13 @@ impl Collections {
                 .stop_times
                 .first()
                 .map(|st| st.departure_time)
-                .map(|departure_time| departure_time % SECONDS_PER_DAY)
+                .map(Time::time_of_day)
                 .ok_or_else(|| format_err!("undefined departure time for vj {}", vj.id))?;
             let vj_arrival_time = vj
                 .stop_times
                 .last()
                 .map(|st| st.arrival_time)
-                .map(|arrival_time| arrival_time % SECONDS_PER_DAY)
+                .map(Time::time_of_day)
                 .ok_or_else(|| format_err!("undefined arrival time for vj {}", vj.id))?;
             let departure_hour = u8::try_from(vj_departure_time.hours())?;
             let arrival_hour = u8::try_from(vj_arrival_time.hours())?;
@@ -909,6 +1059,60 @@ impl Collections {
         self.vehicle_journeys = CollectionWithId::new(vehicle_journeys).unwrap();
     }
 
+    /// Merges the calendars with the same dates into the first of them: the
+    /// vehicle journeys of the merged calendars are rewired to it, and get a
+    /// code of system [ORIGINAL_SERVICE_ID_SYSTEM](constant.ORIGINAL_SERVICE_ID_SYSTEM.html)
+    /// with the identifier of their former calendar, written in the
+    /// `object_codes.txt` file of a NTFS. Each merge is reported.
+    ///
+    /// Returns the number of calendars merged.
+    pub fn consolidate_calendars(&mut self, report: &mut Report) -> usize {
+        // merged calendar id -> referent calendar id
+        let mut referent_ids: BTreeMap<String, String> = BTreeMap::new();
+        {
+            let mut referents: HashMap<&BTreeSet<Date>, &str> = HashMap::new();
+            for calendar in self.calendars.values() {
+                match referents.get(&calendar.dates) {
+                    Some(referent_id) => {
+                        referent_ids.insert(calendar.id.clone(), referent_id.to_string());
+                    }
+                    None => {
+                        referents.insert(&calendar.dates, &calendar.id);
+                    }
+                }
+            }
+        }
+        if referent_ids.is_empty() {
+            return 0;
+        }
+        let mut vehicle_journeys = self.vehicle_journeys.take();
+        for vehicle_journey in &mut vehicle_journeys {
+            if let Some(referent_id) = referent_ids.get(&vehicle_journey.service_id) {
+                vehicle_journey.codes.insert((
+                    ORIGINAL_SERVICE_ID_SYSTEM.to_string(),
+                    vehicle_journey.service_id.clone(),
+                ));
+                vehicle_journey.service_id = referent_id.clone();
+            }
+        }
+        self.vehicle_journeys = CollectionWithId::new(vehicle_journeys).unwrap();
+        self.calendars
+            .retain(|calendar| !referent_ids.contains_key(&calendar.id));
+        for (merged_id, referent_id) in &referent_ids {
+            report.add(
+                Severity::Info,
+                ReportEntry::new(
+                    ReportType::ObjectMerged,
+                    format!(
+                        "calendar {:?} merged into the identical calendar {:?}",
+                        merged_id, referent_id
+                    ),
+                ),
+            );
+        }
+        referent_ids.len()
+    }
+
     /// Some comments are identical and can be deduplicated
     pub fn comment_deduplication(&mut self) {
         let duplicate2ref = self.get_comment_map_duplicate_to_referent();
@@ -1294,6 +1498,16 @@ impl Collections {
     }
 }
 
+/// A period of dates, both included, see
+/// [Model::split_by_period](struct.Model.html#method.split_by_period).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    /// First date of the period
+    pub start_date: Date,
+    /// Last date of the period
+    pub end_date: Date,
+}
+
 /// The navitia transit model.
 #[derive(GetCorresponding)]
 pub struct Model {
@@ -1326,6 +1540,9 @@ pub struct Model {
     datasets_to_routes: ManyToMany<Dataset, Route>,
     #[get_corresponding(weight = "1.9")]
     datasets_to_physical_modes: ManyToMany<Dataset, PhysicalMode>,
+
+    // built on the first search, see search_index()
+    search_index: Mutex<Option<Arc<SearchIndex>>>,
 }
 
 impl Model {
@@ -1359,10 +1576,20 @@ impl Model {
     /// });
     /// assert!(Model::new(collections).is_ok());
     /// ```
-    pub fn new(mut c: Collections) -> Result<Self> {
+    pub fn new(c: Collections) -> Result<Self> {
+        Self::new_keeping_stop_points(c, HashSet::new())
+    }
+
+    // As `new`, the stop points of `stop_points_used` being kept besides the
+    // ones of the stop times, for vehicle journeys whose stop times are
+    // already written and only partly kept
+    pub(crate) fn new_keeping_stop_points(
+        mut c: Collections,
+        stop_points_used: HashSet<String>,
+    ) -> Result<Self> {
         c.comment_deduplication();
         c.clean_comments();
-        c.sanitize()?;
+        c.sanitize_keeping_stop_points(stop_points_used)?;
 
         let forward_vj_to_sp = c
             .vehicle_journeys
@@ -1379,10 +1606,16 @@ impl Model {
             .map(|(idx, tr)| {
                 let mut stop_points = IdxSet::default();
                 stop_points.insert(c.stop_points.get_idx(&tr.from_stop_id).ok_or_else(|| {
-                    format_err!("Invalid id: transfer.from_stop_id={:?}", tr.from_stop_id)
+                    TransitModelError::ReferentialIntegrity {
+                        field: "transfer.from_stop_id".to_string(),
+                        object_id: tr.from_stop_id.clone(),
+                    }
                 })?);
                 stop_points.insert(c.stop_points.get_idx(&tr.to_stop_id).ok_or_else(|| {
-                    format_err!("Invalid id: transfer.to_stop_id={:?}", tr.to_stop_id)
+                    TransitModelError::ReferentialIntegrity {
+                        field: "transfer.to_stop_id".to_string(),
+                        object_id: tr.to_stop_id.clone(),
+                    }
                 })?);
                 Ok((idx, stop_points))
             })
@@ -1450,6 +1683,7 @@ impl Model {
         c.enhance_route_names(&routes_to_vehicle_journeys);
         c.enhance_route_directions();
         c.check_geometries_coherence();
+        c.compute_vehicle_journey_lengths();
         c.enhance_line_opening_time();
         c.enhance_pickup_dropoff();
 
@@ -1473,9 +1707,19 @@ impl Model {
             companies_to_vehicle_journeys,
             calendars_to_vehicle_journeys,
             collections: c,
+            search_index: Mutex::new(None),
         })
     }
 
+    /// Index of the names and codes of the stop areas, stop points and
+    /// lines, built on the first call, see [search](#method.search).
+    pub fn search_index(&self) -> Arc<SearchIndex> {
+        let mut search_index = self.search_index.lock().unwrap();
+        search_index
+            .get_or_insert_with(|| Arc::new(SearchIndex::new(&self.collections)))
+            .clone()
+    }
+
     /// Consumes collections,
     ///
     /// # Examples
@@ -1501,6 +1745,137 @@ impl Model {
     pub fn into_collections(self) -> Collections {
         self.collections
     }
+
+    /// Splits the model into one model per period of `chunks` (e.g. one per
+    /// week of a yearly dataset), in the same order. The calendars of each
+    /// model are restricted to its period (see
+    /// [Collections::restrict_period](struct.Collections.html#method.restrict_period)),
+    /// so the vehicle journeys not running in the period are removed with
+    /// the objects only they referenced.
+    ///
+    /// Fails if a period ends before it starts.
+    pub fn split_by_period(&self, chunks: Vec<DateRange>) -> Result<Vec<Model>> {
+        chunks
+            .into_iter()
+            .map(|chunk| {
+                if chunk.end_date < chunk.start_date {
+                    bail!(
+                        "invalid period: end date {} is before start date {}",
+                        chunk.end_date,
+                        chunk.start_date
+                    );
+                }
+                let mut collections = self.collections.clone();
+                collections.restrict_period(chunk.start_date, chunk.end_date)?;
+                Model::new(collections)
+            })
+            .collect()
+    }
+
+    /// Physical modes of the vehicle journeys of the line `line_idx`
+    pub fn physical_modes_of_line(&self, line_idx: Idx<Line>) -> IdxSet<PhysicalMode> {
+        self.get_corresponding_from_idx(line_idx)
+    }
+
+    /// Trip properties (accessibility, equipments, school transport) of the
+    /// vehicle journey `vj_idx`, `None` if it has none
+    pub fn trip_properties_of(&self, vj_idx: Idx<VehicleJourney>) -> Option<&TripProperty> {
+        self.vehicle_journeys[vj_idx]
+            .trip_property_id
+            .as_ref()
+            .and_then(|trip_property_id| self.trip_properties.get(trip_property_id))
+    }
+
+    // The most frequent sequence of stop points of the vehicle journeys of
+    // the route, with its vehicle journeys; on a tie, the sequence of the
+    // first vehicle journey
+    fn main_pattern(
+        &self,
+        route_id: &str,
+    ) -> Option<(Vec<Idx<StopPoint>>, Vec<Idx<VehicleJourney>>)> {
+        let route_idx = self.routes.get_idx(route_id)?;
+        let vehicle_journey_idxs: IdxSet<VehicleJourney> =
+            self.get_corresponding_from_idx(route_idx);
+        let mut patterns: Vec<(Vec<Idx<StopPoint>>, Vec<Idx<VehicleJourney>>)> = vec![];
+        for vehicle_journey_idx in vehicle_journey_idxs {
+            let stop_point_idxs: Vec<Idx<StopPoint>> = self.vehicle_journeys[vehicle_journey_idx]
+                .stop_times
+                .iter()
+                .map(|stop_time| stop_time.stop_point_idx)
+                .collect();
+            match patterns
+                .iter_mut()
+                .find(|(pattern, _)| *pattern == stop_point_idxs)
+            {
+                Some((_, idxs)) => idxs.push(vehicle_journey_idx),
+                None => patterns.push((stop_point_idxs, vec![vehicle_journey_idx])),
+            }
+        }
+        let mut main_pattern: Option<(Vec<Idx<StopPoint>>, Vec<Idx<VehicleJourney>>)> = None;
+        for pattern in patterns {
+            if main_pattern
+                .as_ref()
+                .map_or(true, |(_, idxs)| pattern.1.len() > idxs.len())
+            {
+                main_pattern = Some(pattern);
+            }
+        }
+        main_pattern
+    }
+
+    /// Ordered stop points of the route `route_id`: the stop points of the
+    /// most frequent sequence among its vehicle journeys (the sequence of
+    /// the first vehicle journey on a tie).
+    ///
+    /// Empty if the route doesn't exist or has no vehicle journeys.
+    pub fn route_points(&self, route_id: &str) -> Vec<Idx<StopPoint>> {
+        self.main_pattern(route_id)
+            .map(|(stop_point_idxs, _)| stop_point_idxs)
+            .unwrap_or_default()
+    }
+
+    /// Main destination of the route `route_id`: the most frequent headsign
+    /// of the vehicle journeys following its
+    /// [route points](#method.route_points) (their headsign at the first
+    /// stop, given by the `stop_time_headsigns` if any), or the name of the
+    /// last stop point when they have no headsign.
+    ///
+    /// `None` if the route doesn't exist or has no vehicle journeys.
+    pub fn main_destination(&self, route_id: &str) -> Option<&str> {
+        let (stop_point_idxs, vehicle_journey_idxs) = self.main_pattern(route_id)?;
+        let mut headsigns: Vec<(&str, usize)> = vec![];
+        for vehicle_journey_idx in vehicle_journey_idxs {
+            // the headsign at the first stop, where a stop time headsign
+            // replaces the one of the vehicle journey
+            let vehicle_journey = &self.vehicle_journeys[vehicle_journey_idx];
+            let headsign = vehicle_journey
+                .stop_times
+                .first()
+                .and_then(|stop_time| {
+                    vehicle_journey.headsign_at(stop_time.sequence, &self.stop_time_headsigns)
+                })
+                .or_else(|| vehicle_journey.headsign.as_deref());
+            let headsign = match headsign {
+                Some(headsign) => headsign,
+                None => continue,
+            };
+            match headsigns.iter_mut().find(|(h, _)| *h == headsign) {
+                Some((_, count)) => *count += 1,
+                None => headsigns.push((headsign, 1)),
+            }
+        }
+        let mut main_headsign: Option<(&str, usize)> = None;
+        for (headsign, count) in headsigns {
+            if main_headsign.map_or(true, |(_, main_count)| count > main_count) {
+                main_headsign = Some((headsign, count));
+            }
+        }
+        main_headsign.map(|(headsign, _)| headsign).or_else(|| {
+            stop_point_idxs
+                .last()
+                .map(|idx| self.stop_points[*idx].name.as_str())
+        })
+    }
 }
 impl ::serde::Serialize for Model {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -1963,6 +2338,77 @@ mod tests {
         }
     }
 
+    mod consolidate_calendars {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn calendar(id: &str, days: &[u32]) -> Calendar {
+            let mut calendar = Calendar::new(id.to_string());
+            for day in days {
+                calendar.dates.insert(NaiveDate::from_ymd(2019, 10, *day));
+            }
+            calendar
+        }
+
+        fn vehicle_journey(id: &str, service_id: &str) -> VehicleJourney {
+            VehicleJourney {
+                id: id.to_string(),
+                service_id: service_id.to_string(),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn merge_identical_calendars() {
+            let mut collections = Collections::default();
+            collections.calendars = CollectionWithId::new(vec![
+                calendar("service_1", &[1, 2, 3]),
+                calendar("service_2", &[1, 2, 3]),
+                calendar("service_3", &[1, 3]),
+                calendar("service_4", &[1, 2, 3]),
+            ])
+            .unwrap();
+            collections.vehicle_journeys = CollectionWithId::new(vec![
+                vehicle_journey("vj_1", "service_1"),
+                vehicle_journey("vj_2", "service_2"),
+                vehicle_journey("vj_3", "service_3"),
+                vehicle_journey("vj_4", "service_4"),
+            ])
+            .unwrap();
+            let mut report = Report::default();
+
+            assert_eq!(2, collections.consolidate_calendars(&mut report));
+
+            let service_ids: Vec<&str> = collections
+                .vehicle_journeys
+                .values()
+                .map(|vj| vj.service_id.as_str())
+                .collect();
+            assert_eq!(
+                vec!["service_1", "service_1", "service_3", "service_1"],
+                service_ids
+            );
+            let calendar_ids: Vec<&str> = collections
+                .calendars
+                .values()
+                .map(|calendar| calendar.id.as_str())
+                .collect();
+            assert_eq!(vec!["service_1", "service_3"], calendar_ids);
+            let vj_2 = collections.vehicle_journeys.get("vj_2").unwrap();
+            assert!(vj_2.codes.contains(&(
+                ORIGINAL_SERVICE_ID_SYSTEM.to_string(),
+                "service_2".to_string()
+            )));
+            assert!(collections
+                .vehicle_journeys
+                .get("vj_1")
+                .unwrap()
+                .codes
+                .is_empty());
+            assert_eq!(2, report.entries(Severity::Info).len());
+        }
+    }
+
     mod clean_comments {
         use super::*;
         use pretty_assertions::assert_eq;
@@ -2171,6 +2617,7 @@ mod tests {
                 geometry_id: None,
                 stop_times,
                 journey_pattern_id: None,
+                length: None,
             }
         }
 
@@ -2450,4 +2897,133 @@ mod tests {
             assert_relative_eq!(stop_area.coord.lat, 0.0);
         }
     }
+
+    mod route_points {
+        use super::*;
+        use crate::model_builder::ModelBuilder;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn most_frequent_pattern() {
+            let model = ModelBuilder::default()
+                .vj("vj1", |vj| {
+                    vj.route("r1")
+                        .st("A", "10:00")
+                        .st("B", "10:10")
+                        .st("C", "10:20");
+                })
+                .vj("vj2", |vj| {
+                    vj.route("r1").st("A", "11:00").st("B", "11:10");
+                })
+                .vj("vj3", |vj| {
+                    vj.route("r1")
+                        .st("A", "12:00")
+                        .st("B", "12:10")
+                        .st("C", "12:20");
+                })
+                .vj("vj4", |vj| {
+                    vj.route("r1")
+                        .st("A", "13:00")
+                        .st("B", "13:10")
+                        .st("C", "13:20");
+                })
+                .build();
+            let mut collections = model.into_collections();
+            for vj_id in &["vj1", "vj3"] {
+                let idx = collections.vehicle_journeys.get_idx(vj_id).unwrap();
+                collections.vehicle_journeys.index_mut(idx).headsign = Some("Terminus".to_string());
+            }
+            let model = Model::new(collections).unwrap();
+
+            let stop_point_ids: Vec<&str> = model
+                .route_points("r1")
+                .into_iter()
+                .map(|idx| model.stop_points[idx].id.as_str())
+                .collect();
+            assert_eq!(vec!["A", "B", "C"], stop_point_ids);
+            // "Terminus" for vj1 and vj3, no headsign for vj4 (vj2 doesn't
+            // follow the route points)
+            assert_eq!(Some("Terminus"), model.main_destination("r1"));
+        }
+
+        #[test]
+        fn main_destination_with_stop_time_headsigns() {
+            let model = ModelBuilder::default()
+                .vj("vj1", |vj| {
+                    vj.route("r1").st("A", "10:00").st("B", "10:10");
+                })
+                .vj("vj2", |vj| {
+                    vj.route("r1").st("A", "11:00").st("B", "11:10");
+                })
+                .vj("vj3", |vj| {
+                    vj.route("r1").st("A", "12:00").st("B", "12:10");
+                })
+                .build();
+            let mut collections = model.into_collections();
+            for vj_id in &["vj1", "vj2", "vj3"] {
+                let idx = collections.vehicle_journeys.get_idx(vj_id).unwrap();
+                collections.vehicle_journeys.index_mut(idx).headsign = Some("Terminus".to_string());
+            }
+            for vj_id in &["vj2", "vj3"] {
+                collections
+                    .stop_time_headsigns
+                    .insert((vj_id.to_string(), 0), "Depot".to_string());
+            }
+            let model = Model::new(collections).unwrap();
+
+            assert_eq!(Some("Depot"), model.main_destination("r1"));
+        }
+
+        #[test]
+        fn unknown_route() {
+            let model = ModelBuilder::default()
+                .vj("vj1", |vj| {
+                    vj.route("r1").st("A", "10:00").st("B", "10:10");
+                })
+                .build();
+            assert!(model.route_points("r2").is_empty());
+            assert_eq!(None, model.main_destination("r2"));
+            assert_eq!(Some("B"), model.main_destination("r1"));
+        }
+    }
+
+    mod trip_properties_of {
+        use super::*;
+        use crate::model_builder::ModelBuilder;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn trip_properties_of_vehicle_journey() {
+            let model = ModelBuilder::default()
+                .vj("vj1", |vj| {
+                    vj.st("A", "10:00").st("B", "10:10");
+                })
+                .vj("vj2", |vj| {
+                    vj.st("A", "11:00").st("B", "11:10");
+                })
+                .build();
+            let mut collections = model.into_collections();
+            collections.trip_properties = CollectionWithId::from(TripProperty {
+                id: "tp1".to_string(),
+                air_conditioned: Availability::Available,
+                ..Default::default()
+            });
+            let vj1_idx = collections.vehicle_journeys.get_idx("vj1").unwrap();
+            collections
+                .vehicle_journeys
+                .index_mut(vj1_idx)
+                .trip_property_id = Some("tp1".to_string());
+            let model = Model::new(collections).unwrap();
+
+            let vj1_idx = model.vehicle_journeys.get_idx("vj1").unwrap();
+            let vj2_idx = model.vehicle_journeys.get_idx("vj2").unwrap();
+            assert_eq!(
+                Some(Availability::Available),
+                model
+                    .trip_properties_of(vj1_idx)
+                    .map(|trip_property| trip_property.air_conditioned)
+            );
+            assert_eq!(None, model.trip_properties_of(vj2_idx));
+        }
+    }
 }
@@ -0,0 +1,369 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Provides an easy way to build a [Model](../model/struct.Model.html), e.g.
+//! in the tests or to generate synthetic data.
+//!
+//! The objects referenced by the vehicle journeys (stop points and their stop
+//! areas, routes, lines, networks, calendars, etc.) are created with default
+//! values when they are not given. A calendar created this way is active on
+//! the first day of the validity period of its dataset. So the objects given
+//! (e.g. with [route](struct.ModelBuilder.html#method.route)) must be added
+//! before the vehicle journeys referencing them.
+//!
+//! ```
+//! # use transit_model::{model_builder::ModelBuilder, objects::Date};
+//! let model = ModelBuilder::default()
+//!     .calendar("c1", |calendar| {
+//!         calendar.dates.insert(Date::from_ymd(2020, 1, 1));
+//!     })
+//!     .vj("vj1", |vj| {
+//!         vj.route("r1")
+//!             .calendar("c1")
+//!             .st("SP1", "10:00")
+//!             .st("SP2", "10:05");
+//!     })
+//!     .vj("vj2", |vj| {
+//!         vj.st("SP1", "11:00").st_detailed("SP3", "11:10:00", "11:12:00");
+//!     })
+//!     .build();
+//! assert_eq!(3, model.stop_points.len());
+//! assert_eq!(2, model.routes.len());
+//! ```
+
+use crate::{
+    model::{Collections, Model},
+    objects::{Calendar, Line, Route, StopPoint, StopTime, Time, VehicleJourney},
+};
+use typed_index_collection::{Idx, WithId};
+
+/// Builder of a `Model`
+#[derive(Default)]
+pub struct ModelBuilder {
+    collections: Collections,
+}
+
+/// Builder of a vehicle journey, see [ModelBuilder::vj](struct.ModelBuilder.html#method.vj)
+pub struct VehicleJourneyBuilder<'a> {
+    model: &'a mut ModelBuilder,
+    vj_idx: Idx<VehicleJourney>,
+}
+
+impl ModelBuilder {
+    /// Adds the vehicle journey `id`, initialized by `vj_initer`
+    ///
+    /// Panics if the vehicle journey already exists.
+    pub fn vj<F>(mut self, id: &str, vj_initer: F) -> Self
+    where
+        F: FnOnce(VehicleJourneyBuilder),
+    {
+        let vj_idx = self
+            .collections
+            .vehicle_journeys
+            .push(VehicleJourney::with_id(id))
+            .unwrap_or_else(|_| panic!("vehicle journey {} already exists", id));
+        vj_initer(VehicleJourneyBuilder {
+            model: &mut self,
+            vj_idx,
+        });
+        self
+    }
+
+    /// Adds the route `id`, initialized by `route_initer`, if it doesn't
+    /// exist yet
+    pub fn route<F>(mut self, id: &str, route_initer: F) -> Self
+    where
+        F: FnOnce(&mut Route),
+    {
+        if !self.collections.routes.contains_id(id) {
+            let mut route = Route::with_id(id);
+            route_initer(&mut route);
+            self.collections.routes.push(route).unwrap();
+        }
+        self
+    }
+
+    /// Adds the line `id`, initialized by `line_initer`, if it doesn't exist
+    /// yet
+    pub fn line<F>(mut self, id: &str, line_initer: F) -> Self
+    where
+        F: FnOnce(&mut Line),
+    {
+        if !self.collections.lines.contains_id(id) {
+            let mut line = Line::with_id(id);
+            line_initer(&mut line);
+            self.collections.lines.push(line).unwrap();
+        }
+        self
+    }
+
+    /// Adds the calendar `id`, initialized by `calendar_initer`, if it
+    /// doesn't exist yet
+    pub fn calendar<F>(mut self, id: &str, calendar_initer: F) -> Self
+    where
+        F: FnOnce(&mut Calendar),
+    {
+        if !self.collections.calendars.contains_id(id) {
+            let mut calendar = Calendar::with_id(id);
+            calendar_initer(&mut calendar);
+            self.collections.calendars.push(calendar).unwrap();
+        }
+        self
+    }
+
+    /// Adds the stop point `id`, initialized by `stop_point_initer`, if it
+    /// doesn't exist yet. Its stop area is `sa:<id>` by default.
+    pub fn stop_point<F>(mut self, id: &str, stop_point_initer: F) -> Self
+    where
+        F: FnOnce(&mut StopPoint),
+    {
+        if !self.collections.stop_points.contains_id(id) {
+            let mut stop_point = default_stop_point(id);
+            stop_point_initer(&mut stop_point);
+            self.collections
+                .stop_areas
+                .get_or_create(&stop_point.stop_area_id);
+            self.collections.stop_points.push(stop_point).unwrap();
+        }
+        self
+    }
+
+    /// Consumes the builder to create the `Model`
+    ///
+    /// Panics if the `Model` can't be built.
+    pub fn build(self) -> Model {
+        Model::new(self.collections).unwrap()
+    }
+}
+
+fn default_stop_point(id: &str) -> StopPoint {
+    StopPoint {
+        id: id.to_string(),
+        name: id.to_string(),
+        stop_area_id: format!("sa:{}", id),
+        ..Default::default()
+    }
+}
+
+/// Conversion into a `Time` of the times given to a
+/// [VehicleJourneyBuilder](struct.VehicleJourneyBuilder.html)
+pub trait IntoTime {
+    /// The time, a string being parsed as `HH:MM:SS` or `HH:MM`
+    ///
+    /// Panics if the string is not a valid time.
+    fn into_time(self) -> Time;
+}
+
+impl IntoTime for Time {
+    fn into_time(self) -> Time {
+        self
+    }
+}
+
+impl IntoTime for &Time {
+    fn into_time(self) -> Time {
+        *self
+    }
+}
+
+impl IntoTime for &str {
+    fn into_time(self) -> Time {
+        let time = if self.matches(':').count() == 1 {
+            format!("{}:00", self)
+        } else {
+            self.to_string()
+        };
+        time.parse()
+            .unwrap_or_else(|_| panic!("{:?} is not a valid time", self))
+    }
+}
+
+impl VehicleJourneyBuilder<'_> {
+    fn find_or_create_stop_point(&mut self, id: &str) -> Idx<StopPoint> {
+        let collections = &mut self.model.collections;
+        match collections.stop_points.get_idx(id) {
+            Some(idx) => idx,
+            None => {
+                let stop_point = default_stop_point(id);
+                collections
+                    .stop_areas
+                    .get_or_create(&stop_point.stop_area_id);
+                collections.stop_points.push(stop_point).unwrap()
+            }
+        }
+    }
+
+    fn vj_mut(&mut self) -> impl std::ops::DerefMut<Target = VehicleJourney> + '_ {
+        self.model
+            .collections
+            .vehicle_journeys
+            .index_mut(self.vj_idx)
+    }
+
+    /// Adds a stop time at the stop point `stop_point_id`, arriving and
+    /// leaving at `time`. The stop point is created if it doesn't exist.
+    pub fn st(self, stop_point_id: &str, time: impl IntoTime) -> Self {
+        let time = time.into_time();
+        self.st_detailed(stop_point_id, time, time)
+    }
+
+    /// Adds a stop time at the stop point `stop_point_id`, with its
+    /// `arrival` and `departure` times. The stop point is created if it
+    /// doesn't exist.
+    pub fn st_detailed(
+        mut self,
+        stop_point_id: &str,
+        arrival: impl IntoTime,
+        departure: impl IntoTime,
+    ) -> Self {
+        let stop_point_idx = self.find_or_create_stop_point(stop_point_id);
+        let mut vj = self.vj_mut();
+        let sequence = vj.stop_times.len() as u32;
+        vj.stop_times.push(StopTime {
+            stop_point_idx,
+            sequence,
+            arrival_time: arrival.into_time(),
+            departure_time: departure.into_time(),
+            boarding_duration: 0,
+            alighting_duration: 0,
+            pickup_type: 0,
+            drop_off_type: 0,
+            datetime_estimated: false,
+            local_zone_id: None,
+            precision: None,
+        });
+        drop(vj);
+        self
+    }
+
+    /// Sets the route of the vehicle journey
+    pub fn route(mut self, id: &str) -> Self {
+        self.vj_mut().route_id = id.to_string();
+        self
+    }
+
+    /// Sets the calendar (the `service_id`) of the vehicle journey
+    pub fn calendar(mut self, id: &str) -> Self {
+        self.vj_mut().service_id = id.to_string();
+        self
+    }
+
+    /// Sets the physical mode of the vehicle journey
+    pub fn physical_mode(mut self, id: &str) -> Self {
+        self.vj_mut().physical_mode_id = id.to_string();
+        self
+    }
+
+    /// Sets the company of the vehicle journey
+    pub fn company(mut self, id: &str) -> Self {
+        self.vj_mut().company_id = id.to_string();
+        self
+    }
+}
+
+impl Drop for VehicleJourneyBuilder<'_> {
+    // adds the objects referenced by the vehicle journey which are missing
+    fn drop(&mut self) {
+        let collections = &mut self.model.collections;
+        let vj = &collections.vehicle_journeys[self.vj_idx];
+        let dataset = collections.datasets.get_or_create(&vj.dataset_id);
+        collections
+            .contributors
+            .get_or_create(&dataset.contributor_id);
+        if !collections.calendars.contains_id(&vj.service_id) {
+            let mut calendar = Calendar::with_id(&vj.service_id);
+            calendar.dates.insert(dataset.start_date);
+            collections.calendars.push(calendar).unwrap();
+        }
+        collections.companies.get_or_create(&vj.company_id);
+        collections
+            .physical_modes
+            .get_or_create(&vj.physical_mode_id);
+
+        let route = collections.routes.get_or_create(&vj.route_id);
+        let line = collections.lines.get_or_create(&route.line_id);
+        collections
+            .commercial_modes
+            .get_or_create(&line.commercial_mode_id);
+        collections.networks.get_or_create(&line.network_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use relational_types::IdxSet;
+
+    fn stop_point_idxs(model: &Model, ids: &[&str]) -> IdxSet<StopPoint> {
+        ids.iter()
+            .map(|id| model.stop_points.get_idx(id).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn model_with_default_objects() {
+        let model = ModelBuilder::default()
+            .vj("vj1", |vj| {
+                vj.st("A", "10:00").st("B", "11:00:00");
+            })
+            .vj("vj2", |vj| {
+                vj.st("A", "10:00").st_detailed("C", "11:00", "11:01");
+            })
+            .build();
+
+        assert_eq!(
+            stop_point_idxs(&model, &["A", "B"]),
+            model.get_corresponding_from_idx(model.vehicle_journeys.get_idx("vj1").unwrap())
+        );
+        assert_eq!(3, model.stop_points.len());
+        assert_eq!(3, model.stop_areas.len());
+        assert_eq!(1, model.routes.len());
+        assert_eq!(1, model.lines.len());
+        assert_eq!(1, model.networks.len());
+        assert_eq!(1, model.calendars.len());
+        let vj2 = model.vehicle_journeys.get("vj2").unwrap();
+        assert_eq!(Time::new(11, 0, 0), vj2.stop_times[1].arrival_time);
+        assert_eq!(Time::new(11, 1, 0), vj2.stop_times[1].departure_time);
+        assert_eq!(1, vj2.stop_times[1].sequence);
+    }
+
+    #[test]
+    fn model_with_given_objects() {
+        let model = ModelBuilder::default()
+            .line("l1", |line| {
+                line.name = "Line 1".to_string();
+            })
+            .route("r1", |route| {
+                route.line_id = "l1".to_string();
+            })
+            .stop_point("A", |stop_point| {
+                stop_point.stop_area_id = "sa".to_string();
+            })
+            .vj("vj1", |vj| {
+                vj.route("r1").st("A", "10:00").st("B", "11:00");
+            })
+            .vj("vj2", |vj| {
+                vj.route("r2").st("A", "10:00").st("C", "11:00");
+            })
+            .build();
+
+        assert_eq!(2, model.routes.len());
+        assert_eq!("l1", model.routes.get("r1").unwrap().line_id);
+        assert_eq!("Line 1", model.lines.get("l1").unwrap().name);
+        assert_eq!("sa", model.stop_points.get("A").unwrap().stop_area_id);
+        let stop_area_ids: Vec<&str> = model.stop_areas.values().map(|sa| sa.id.as_str()).collect();
+        assert_eq!(vec!["sa", "sa:B", "sa:C"], stop_area_ids);
+    }
+}
@@ -415,18 +415,10 @@ impl<'a> OfferExporter<'a> {
     }
 
     fn export_timetabled_passing_time(stop_time: &'a StopTime) -> Element {
-        let arrival_day_offset = stop_time.arrival_time.hours() / 24;
-        let arrival_time = Time::new(
-            stop_time.arrival_time.hours() % 24,
-            stop_time.arrival_time.minutes(),
-            stop_time.arrival_time.seconds(),
-        );
-        let departure_day_offset = stop_time.departure_time.hours() / 24;
-        let departure_time = Time::new(
-            stop_time.departure_time.hours() % 24,
-            stop_time.departure_time.minutes(),
-            stop_time.departure_time.seconds(),
-        );
+        let arrival_day_offset = stop_time.arrival_day_offset();
+        let arrival_time = stop_time.arrival_time.time_of_day();
+        let departure_day_offset = stop_time.departure_day_offset();
+        let departure_time = stop_time.departure_time.time_of_day();
         Element::builder(ObjectType::TimetabledPassingTime.to_string())
             .append(Self::generate_arrival_time(arrival_time))
             .append(Self::generate_arrival_day_offset(arrival_day_offset))
@@ -0,0 +1,155 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Columns of the NTFS files unknown to the model, kept to be written back
+
+use super::explain;
+use crate::{objects::ExtraColumns, Result};
+use failure::ResultExt;
+use log::info;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path,
+};
+
+/// The files whose extra columns are kept, with their identifier column
+const FILES: &[(&str, &str)] = &[
+    ("contributors.txt", "contributor_id"),
+    ("datasets.txt", "dataset_id"),
+    ("networks.txt", "network_id"),
+    ("commercial_modes.txt", "commercial_mode_id"),
+    ("companies.txt", "company_id"),
+    ("lines.txt", "line_id"),
+    ("physical_modes.txt", "physical_mode_id"),
+    ("equipments.txt", "equipment_id"),
+    ("routes.txt", "route_id"),
+    ("trip_properties.txt", "trip_property_id"),
+    ("trips.txt", "trip_id"),
+    ("stops.txt", "stop_id"),
+    ("comments.txt", "comment_id"),
+    ("tickets.txt", "ticket_id"),
+    ("ticket_uses.txt", "ticket_use_id"),
+    ("fare_zones.txt", "fare_zone_id"),
+    ("pathways.txt", "pathway_id"),
+    ("levels.txt", "level_id"),
+    ("grid_calendars.txt", "grid_calendar_id"),
+];
+
+fn id_column(file_name: &str) -> Option<&'static str> {
+    FILES
+        .iter()
+        .find(|(name, _)| *name == file_name)
+        .map(|(_, id_column)| *id_column)
+}
+
+/// Reads the columns of the NTFS files in `path` which are unknown to the
+/// model, by file name and object identifier.
+pub(crate) fn read_extra_columns(path: &path::Path) -> Result<ExtraColumns> {
+    let mut extra_columns = ExtraColumns::new();
+    for (file_name, id_column) in FILES {
+        let file_path = path.join(file_name);
+        if !file_path.exists() {
+            continue;
+        }
+        let known_columns: HashSet<&str> = explain(file_name)
+            .map(|schema| schema.columns.iter().map(|column| column.name).collect())
+            .unwrap_or_default();
+        let mut rdr = csv::Reader::from_path(&file_path)
+            .with_context(|_| format!("Error reading {:?}", file_path))?;
+        let headers = rdr
+            .headers()
+            .with_context(|_| format!("Error reading {:?}", file_path))?
+            .clone();
+        let id_position = match headers.iter().position(|header| header == *id_column) {
+            Some(id_position) => id_position,
+            None => continue,
+        };
+        let extra_positions: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| !known_columns.contains(header))
+            .map(|(position, _)| position)
+            .collect();
+        if extra_positions.is_empty() {
+            continue;
+        }
+        info!("Keeping the extra columns of {}", file_name);
+        let objects = extra_columns.entry(file_name.to_string()).or_default();
+        for record in rdr.records() {
+            let record = record.with_context(|_| format!("Error reading {:?}", file_path))?;
+            let values = extra_positions
+                .iter()
+                .map(|position| {
+                    (
+                        headers[*position].to_string(),
+                        record[*position].to_string(),
+                    )
+                })
+                .collect();
+            objects.insert(record[id_position].to_string(), values);
+        }
+    }
+    Ok(extra_columns)
+}
+
+/// Appends the `extra_columns` to the NTFS files written in `path`, matching
+/// the rows by object identifier.
+pub(crate) fn write_extra_columns(path: &path::Path, extra_columns: &ExtraColumns) -> Result<()> {
+    for (file_name, objects) in extra_columns {
+        let file_path = path.join(file_name);
+        let id_column = match id_column(file_name) {
+            Some(id_column) if file_path.exists() => id_column,
+            _ => continue,
+        };
+        info!("Writing the extra columns of {}", file_name);
+        let mut rdr = csv::Reader::from_path(&file_path)
+            .with_context(|_| format!("Error reading {:?}", file_path))?;
+        let headers = rdr
+            .headers()
+            .with_context(|_| format!("Error reading {:?}", file_path))?
+            .clone();
+        let id_position = match headers.iter().position(|header| header == id_column) {
+            Some(id_position) => id_position,
+            None => continue,
+        };
+        let written_columns: HashSet<&str> = headers.iter().collect();
+        let new_columns: BTreeSet<&str> = objects
+            .values()
+            .flat_map(|values| values.keys())
+            .map(String::as_str)
+            .filter(|column| !written_columns.contains(column))
+            .collect();
+        let records = rdr
+            .records()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .with_context(|_| format!("Error writing {:?}", file_path))?;
+
+        let mut wtr = csv::Writer::from_path(&file_path)
+            .with_context(|_| format!("Error writing {:?}", file_path))?;
+        wtr.write_record(headers.iter().chain(new_columns.iter().cloned()))
+            .with_context(|_| format!("Error writing {:?}", file_path))?;
+        let no_values = BTreeMap::new();
+        for record in &records {
+            let values = objects.get(&record[id_position]).unwrap_or(&no_values);
+            let extra_values = new_columns
+                .iter()
+                .map(|column| values.get(*column).map(String::as_str).unwrap_or(""));
+            wtr.write_record(record.iter().chain(extra_values))
+                .with_context(|_| format!("Error writing {:?}", file_path))?;
+        }
+        wtr.flush()
+            .with_context(|_| format!("Error writing {:?}", file_path))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Manifest of the files of a NTFS: which files are mandatory, optional or
+//! part of an extension of the specification
+
+use self::FileRequirement::*;
+use failure::Fail;
+use log::info;
+use std::{fmt, path};
+
+/// Requirement of a file of a NTFS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileRequirement {
+    /// The file must be present
+    Mandatory,
+    /// The file must be present, unless the given alternative file is
+    Alternative(&'static str),
+    /// The file may be absent
+    Optional,
+    /// The file belongs to the given extension of the specification and may
+    /// be absent
+    Extension(&'static str),
+}
+
+/// A file of the manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManifestFile {
+    /// Name of the file, e.g. `stops.txt`
+    pub name: &'static str,
+    /// Requirement of the file
+    pub requirement: FileRequirement,
+}
+
+const fn file(name: &'static str, requirement: FileRequirement) -> ManifestFile {
+    ManifestFile { name, requirement }
+}
+
+/// The files read by [read](fn.read.html)
+pub const MANIFEST: &[ManifestFile] = &[
+    file("contributors.txt", Mandatory),
+    file("datasets.txt", Mandatory),
+    file("networks.txt", Mandatory),
+    file("commercial_modes.txt", Mandatory),
+    file("lines.txt", Mandatory),
+    file("routes.txt", Mandatory),
+    file("trips.txt", Mandatory),
+    file("physical_modes.txt", Mandatory),
+    file("companies.txt", Mandatory),
+    file("stops.txt", Mandatory),
+    file("stop_times.txt", Mandatory),
+    file("feed_infos.txt", Mandatory),
+    file("calendar.txt", Alternative("calendar_dates.txt")),
+    file("calendar_dates.txt", Alternative("calendar.txt")),
+    file("frequencies.txt", Optional),
+    file("equipments.txt", Optional),
+    file("trip_properties.txt", Optional),
+    file("transfers.txt", Optional),
+    file("admin_stations.txt", Optional),
+    file("geometries.txt", Optional),
+    file("object_codes.txt", Optional),
+    file("comments.txt", Optional),
+    file("comment_links.txt", Optional),
+    file("object_properties.txt", Optional),
+    file("pathways.txt", Optional),
+    file("levels.txt", Optional),
+    file("grid_calendars.txt", Optional),
+    file("grid_exception_dates.txt", Optional),
+    file("grid_periods.txt", Optional),
+    file("grid_rel_calendar_line.txt", Optional),
+    file("tickets.txt", Extension("fares V2")),
+    file("ticket_uses.txt", Extension("fares V2")),
+    file("ticket_prices.txt", Extension("fares V2")),
+    file("ticket_use_perimeters.txt", Extension("fares V2")),
+    file("ticket_use_restrictions.txt", Extension("fares V2")),
+    file("fare_zones.txt", Extension("fares V2")),
+    file("prices.csv", Extension("fares V1")),
+    file("od_fares.csv", Extension("fares V1")),
+    file("fares.csv", Extension("fares V1")),
+    file(
+        "stop_area_memberships.txt",
+        Extension("secondary stop areas"),
+    ),
+    file("company_parents.txt", Extension("company hierarchy")),
+    file("translations.txt", Extension("translations")),
+    file("booking_rules.txt", Extension("booking rules")),
+    file("booking_rule_links.txt", Extension("booking rules")),
+    file("disruptions.txt", Extension("disruptions")),
+    file("disruption_impacts.txt", Extension("disruptions")),
+];
+
+/// Requirement of the NTFS file `file_name`, or `None` if the file is not
+/// part of the [MANIFEST](constant.MANIFEST.html).
+pub fn file_requirement(file_name: &str) -> Option<FileRequirement> {
+    MANIFEST
+        .iter()
+        .find(|file| file.name == file_name)
+        .map(|file| file.requirement)
+}
+
+/// Error about the files of a NTFS
+#[derive(Debug, PartialEq)]
+pub enum ManifestError {
+    /// Mandatory files are missing
+    MissingMandatoryFiles(Vec<String>),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManifestError::MissingMandatoryFiles(files) => {
+                write!(f, "mandatory NTFS files missing: {}", files.join(", "))
+            }
+        }
+    }
+}
+
+impl Fail for ManifestError {}
+
+/// Checks that the mandatory files of the manifest are in `path`.
+pub(crate) fn check_mandatory_files(path: &path::Path) -> Result<(), ManifestError> {
+    let exists = |name: &str| path.join(name).exists();
+    let missing_files: Vec<String> = MANIFEST
+        .iter()
+        .filter_map(|file| match file.requirement {
+            Mandatory if !exists(file.name) => Some(file.name.to_string()),
+            // Reported once, on the first file of the alternative
+            Alternative(alternative)
+                if file.name < alternative && !exists(file.name) && !exists(alternative) =>
+            {
+                Some(format!("{} or {}", file.name, alternative))
+            }
+            _ => None,
+        })
+        .collect();
+    if missing_files.is_empty() {
+        Ok(())
+    } else {
+        Err(ManifestError::MissingMandatoryFiles(missing_files))
+    }
+}
+
+/// Returns `true` if the non mandatory file `file_name` is missing in `path`,
+/// logging it according to its requirement.
+pub(crate) fn skip_missing_file(path: &path::Path, file_name: &str) -> bool {
+    if path.join(file_name).exists() {
+        return false;
+    }
+    match file_requirement(file_name) {
+        Some(Extension(extension)) => {
+            info!(
+                "Skipping {} ({} extension not provided)",
+                file_name, extension
+            )
+        }
+        _ => info!("Skipping {}", file_name),
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn requirement_of_files() {
+        assert_eq!(Some(Mandatory), file_requirement("stops.txt"));
+        assert_eq!(Some(Optional), file_requirement("transfers.txt"));
+        assert_eq!(Some(Extension("fares V2")), file_requirement("tickets.txt"));
+        assert_eq!(None, file_requirement("shapes.txt"));
+    }
+
+    #[test]
+    fn missing_mandatory_files() {
+        test_in_tmp_dir(|path| {
+            for file in MANIFEST {
+                if file.requirement == Mandatory && file.name != "stops.txt" {
+                    create_file_with_content(path, file.name, "");
+                }
+            }
+            assert_eq!(
+                Err(ManifestError::MissingMandatoryFiles(vec![
+                    "stops.txt".to_string(),
+                    "calendar.txt or calendar_dates.txt".to_string()
+                ])),
+                check_mandatory_files(path)
+            );
+            create_file_with_content(path, "stops.txt", "");
+            create_file_with_content(path, "calendar_dates.txt", "");
+            assert_eq!(Ok(()), check_mandatory_files(path));
+        });
+    }
+}
@@ -15,46 +15,70 @@
 //! [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
 //! format management.
 
+mod extra_columns;
+mod manifest;
+mod precheck;
 mod read;
+mod versions;
 mod write;
 
+pub use self::manifest::{
+    file_requirement, FileRequirement, ManifestError, ManifestFile, MANIFEST,
+};
+pub use self::precheck::{precheck, precheck_with_read_mode};
+pub use self::versions::{
+    adapt_to_current_version, detect_version, Adaptation, NtfsVersion, SpecificationChange,
+    SPECIFICATION_CHANGES,
+};
+pub(crate) use self::write::make_stop_times;
+
+#[cfg(feature = "zip")]
+use crate::archive::{archive_to, ArchiveFormat};
 use crate::{
-    calendars::{manage_calendars, write_calendar_dates},
+    calendars::{self, manage_calendars, write_calendar_dates},
     model::{Collections, Model},
     objects::*,
-    read_utils,
+    read_utils::{self, ReadMode},
+    report::{Report, ReportType, Severity},
+    schema::{self, FileSchema},
     utils::*,
     Result,
 };
 use chrono::{DateTime, FixedOffset};
 use chrono_tz::Tz;
 use derivative::Derivative;
-use log::info;
+use failure::{bail, format_err, ResultExt};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use std::path;
+use std::{collections::BTreeSet, fs, path};
 use tempfile::tempdir;
+use typed_index_collection::{Collection, CollectionWithId};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct StopTime {
-    stop_id: String,
-    trip_id: String,
-    stop_sequence: u32,
-    arrival_time: Time,
-    departure_time: Time,
+pub(crate) struct StopTime {
+    pub(crate) stop_id: String,
+    pub(crate) trip_id: String,
+    pub(crate) stop_sequence: u32,
+    pub(crate) arrival_time: Time,
+    pub(crate) departure_time: Time,
     #[serde(default)]
-    boarding_duration: u16,
+    pub(crate) boarding_duration: u16,
     #[serde(default)]
-    alighting_duration: u16,
+    pub(crate) alighting_duration: u16,
     #[serde(default)]
-    pickup_type: u8,
+    pub(crate) pickup_type: u8,
     #[serde(default)]
-    drop_off_type: u8,
-    datetime_estimated: Option<u8>,
-    local_zone_id: Option<u16>,
-    stop_headsign: Option<String>,
-    stop_time_id: Option<String>,
+    pub(crate) drop_off_type: u8,
+    pub(crate) datetime_estimated: Option<u8>,
+    pub(crate) local_zone_id: Option<u16>,
+    pub(crate) stop_headsign: Option<String>,
+    pub(crate) stop_time_id: Option<String>,
     #[serde(rename = "stop_time_precision")]
-    precision: Option<StopTimePrecision>,
+    pub(crate) precision: Option<StopTimePrecision>,
+    #[serde(default)]
+    pub(crate) continuous_pickup: Option<ContinuousStoppingType>,
+    #[serde(default)]
+    pub(crate) continuous_drop_off: Option<ContinuousStoppingType>,
 }
 
 #[derivative(Default)]
@@ -171,54 +195,438 @@ fn has_fares_v2(collections: &Collections) -> bool {
 fn has_fares_v1(collections: &Collections) -> bool {
     !collections.prices_v1.is_empty()
 }
+/// Fields of the objects that [read_with_options](fn.read_with_options.html)
+/// can skip, saving the memory and time of their reading when they are not
+/// needed (e.g. when only the topology and the schedules are analyzed)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SkippedField {
+    /// The comments (`comments.txt` and `comment_links.txt`)
+    Comments,
+    /// The geometries (`geometries.txt` and the `geometry_id` of the lines,
+    /// routes, trips and stops)
+    Geometries,
+    /// The colors of the lines (`line_color` and `line_text_color`)
+    Colors,
+    /// The codes (`object_codes.txt`)
+    Codes,
+    /// The object properties (`object_properties.txt`)
+    ObjectProperties,
+}
+
+/// Options of [read_with_options](fn.read_with_options.html)
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// Keeps the columns unknown to the model of the files describing objects
+    /// with an identifier (`lines.txt`, `stops.txt`, etc.), to write them back
+    /// with [write](fn.write.html). The columns are kept as long as the
+    /// identifiers of the objects don't change.
+    pub keep_extra_columns: bool,
+    /// Runs the [precheck](fn.precheck.html) of the references of the trips
+    /// and stop times before reading the files. With the strict `read_mode`,
+    /// the reading is aborted with all the missing references; with the
+    /// lenient one, they are logged and the trips and stop times referencing
+    /// them are skipped
+    pub precheck: bool,
+    /// Fields of the objects which are not read, left empty in the model
+    pub skipped_fields: BTreeSet<SkippedField>,
+    /// How the records of the files describing objects (`lines.txt`,
+    /// `transfers.txt`, `stop_times.txt`, etc.) which can't be read are
+    /// handled; in [lenient](../read_utils/enum.ReadMode.html#variant.Lenient)
+    /// mode, they are skipped and added to the report of
+    /// [read_with_report](fn.read_with_report.html), as are the trips of
+    /// unknown routes and the stop times of unknown trips or stops
+    pub read_mode: ReadMode,
+}
+
+impl ReadOptions {
+    fn skips(&self, field: SkippedField) -> bool {
+        self.skipped_fields.contains(&field)
+    }
+
+    // The report of the invalid records skipped, in lenient mode
+    fn invalid_records_report<'r>(&self, report: &'r mut Report) -> Option<&'r mut Report> {
+        match self.read_mode {
+            ReadMode::Strict => None,
+            ReadMode::Lenient => Some(report),
+        }
+    }
+
+    // The columns of `file_name` which are not read because of the skipped
+    // fields
+    fn skipped_columns(&self, file_name: &str) -> Vec<&'static str> {
+        let mut skipped_columns = vec![];
+        if self.skips(SkippedField::Geometries) {
+            match file_name {
+                "lines.txt" | "routes.txt" | "trips.txt" | "stops.txt" => {
+                    skipped_columns.push("geometry_id")
+                }
+                _ => {}
+            }
+        }
+        if self.skips(SkippedField::Colors) && file_name == "lines.txt" {
+            skipped_columns.extend(&["line_color", "line_text_color"]);
+        }
+        skipped_columns
+    }
+}
+
 /// Imports a `Model` from the
 /// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
 /// files in the given directory.
+///
+/// Fails with a [ManifestError](enum.ManifestError.html) if files marked as
+/// mandatory in the [MANIFEST](constant.MANIFEST.html) are missing.
 pub fn read<P: AsRef<path::Path>>(path: P) -> Result<Model> {
-    let path = path.as_ref();
-    let mut file_handle = read_utils::PathFileHandler::new(path.to_path_buf());
+    read_with_options(path, &ReadOptions::default())
+}
+
+/// Imports a `Model` from the
+/// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
+/// files in the given directory, with the given `options`.
+///
+/// The detected version of the NTFS, its adaptations (see
+/// [read_with_report](fn.read_with_report.html)) and the records skipped in
+/// lenient mode are logged.
+pub fn read_with_options<P: AsRef<path::Path>>(path: P, options: &ReadOptions) -> Result<Model> {
+    let mut report = Report::default();
+    let model = read_with_report(path, options, &mut report)?;
+    for entry in report.entries(Severity::Info) {
+        info!("{}", entry.message);
+    }
+    for entry in report
+        .entries(Severity::Warning)
+        .iter()
+        .chain(report.entries(Severity::Error))
+    {
+        warn!("{}", entry.message);
+    }
+    Ok(model)
+}
+
+/// Imports a `Model` from the
+/// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
+/// files in the given directory, with the given `options`.
+///
+/// The NTFS of an older version of the specification is first adapted to the
+/// current version (see
+/// [adapt_to_current_version](fn.adapt_to_current_version.html)), its version
+/// and adaptations being added to the `report`.
+pub fn read_with_report<P: AsRef<path::Path>>(
+    path: P,
+    options: &ReadOptions,
+    report: &mut Report,
+) -> Result<Model> {
+    let collections = read_collections_with_report(path, options, report)?;
+    info!("Indexing");
+    let res = Model::new(collections)?;
+    info!("Loading NTFS done");
+    Ok(res)
+}
 
+/// Imports the `Collections` of the
+/// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
+/// files in the given directory, as [read_with_report](fn.read_with_report.html)
+/// but without building the `Model`.
+///
+/// The collections are neither sanitized nor indexed: their
+/// [consistency](../model/struct.Collections.html#method.check_consistency)
+/// can be checked before `Model::new` drops or rejects the inconsistent
+/// objects.
+pub fn read_collections_with_report<P: AsRef<path::Path>>(
+    path: P,
+    options: &ReadOptions,
+    report: &mut Report,
+) -> Result<Collections> {
+    let path = path.as_ref();
     info!("Loading NTFS from {:?}", path);
+    let adapted_dir = adapt_to_current_version(path, SPECIFICATION_CHANGES, report)?;
+    let path = adapted_dir.as_ref().map_or(path, |dir| dir.path());
+    let mut file_handle =
+        read_utils::PathFileHandler::new(path.to_path_buf()).with_read_mode(options.read_mode);
+
+    manifest::check_mandatory_files(path)?;
+    if options.precheck {
+        let report = precheck_with_read_mode(path, options.read_mode)?;
+        let missing_references: Vec<String> = report
+            .entries(Severity::Error)
+            .iter()
+            .map(|entry| format!("{} ({} rows)", entry.message, entry.count))
+            .collect();
+        if !missing_references.is_empty() {
+            if options.read_mode == ReadMode::Strict {
+                bail!(
+                    "{} missing references found in {:?}:\n{}",
+                    missing_references.len(),
+                    path,
+                    missing_references.join("\n")
+                );
+            }
+            for missing_reference in missing_references {
+                warn!("{}", missing_reference);
+            }
+        }
+    }
+    // The objects of the file; in lenient mode, the records which can't be
+    // read are skipped and added to the report
+    macro_rules! objects {
+        ($file:expr) => {
+            read_objects_skipping_columns(
+                path,
+                $file,
+                &options.skipped_columns($file),
+                options.invalid_records_report(report),
+            )?
+        };
+    }
+    macro_rules! opt_objects {
+        ($file:expr) => {
+            if path.join($file).exists() {
+                objects!($file)
+            } else {
+                info!("Skipping {}", $file);
+                vec![]
+            }
+        };
+    }
     let mut collections = Collections::default();
-    collections.contributors = make_collection_with_id(path, "contributors.txt")?;
-    collections.datasets = make_collection_with_id(path, "datasets.txt")?;
-    collections.commercial_modes = make_collection_with_id(path, "commercial_modes.txt")?;
-    collections.networks = make_collection_with_id(path, "networks.txt")?;
-    collections.lines = make_collection_with_id(path, "lines.txt")?;
-    collections.routes = make_collection_with_id(path, "routes.txt")?;
-    collections.vehicle_journeys = make_collection_with_id(path, "trips.txt")?;
-    collections.frequencies = make_opt_collection(path, "frequencies.txt")?;
-    collections.physical_modes = make_collection_with_id(path, "physical_modes.txt")?;
-    collections.companies = make_collection_with_id(path, "companies.txt")?;
-    collections.equipments = make_opt_collection_with_id(path, "equipments.txt")?;
-    collections.trip_properties = make_opt_collection_with_id(path, "trip_properties.txt")?;
-    collections.transfers = make_opt_collection(path, "transfers.txt")?;
-    collections.admin_stations = make_opt_collection(path, "admin_stations.txt")?;
-    collections.tickets = make_opt_collection_with_id(path, "tickets.txt")?;
-    collections.ticket_uses = make_opt_collection_with_id(path, "ticket_uses.txt")?;
-    collections.ticket_prices = make_opt_collection(path, "ticket_prices.txt")?;
-    collections.ticket_use_perimeters = make_opt_collection(path, "ticket_use_perimeters.txt")?;
-    collections.ticket_use_restrictions = make_opt_collection(path, "ticket_use_restrictions.txt")?;
-    collections.levels = make_opt_collection_with_id(path, "levels.txt")?;
-    collections.grid_calendars = make_opt_collection_with_id(path, "grid_calendars.txt")?;
-    collections.grid_exception_dates = make_opt_collection(path, "grid_exception_dates.txt")?;
-    collections.grid_periods = make_opt_collection(path, "grid_periods.txt")?;
-    collections.grid_rel_calendar_line = make_opt_collection(path, "grid_rel_calendar_line.txt")?;
+    collections.contributors = CollectionWithId::new(objects!("contributors.txt"))?;
+    collections.datasets = CollectionWithId::new(objects!("datasets.txt"))?;
+    collections.commercial_modes = CollectionWithId::new(objects!("commercial_modes.txt"))?;
+    collections.networks = CollectionWithId::new(objects!("networks.txt"))?;
+    collections.lines = CollectionWithId::new(objects!("lines.txt"))?;
+    collections.routes = CollectionWithId::new(objects!("routes.txt"))?;
+    collections.vehicle_journeys = CollectionWithId::new(objects!("trips.txt"))?;
+    if let Some(report) = options.invalid_records_report(report) {
+        let routes = &collections.routes;
+        collections.vehicle_journeys.retain(|vj| {
+            let known_route = routes.contains_id(&vj.route_id);
+            if !known_route {
+                report.add_error(
+                    format!(
+                        "Problem reading \"trips.txt\": route_id={:?} not found",
+                        vj.route_id
+                    ),
+                    ReportType::DanglingReference,
+                );
+            }
+            known_route
+        });
+    }
+    collections.frequencies = Collection::new(opt_objects!("frequencies.txt"));
+    collections.physical_modes = CollectionWithId::new(objects!("physical_modes.txt"))?;
+    collections.companies = CollectionWithId::new(objects!("companies.txt"))?;
+    collections.equipments = CollectionWithId::new(opt_objects!("equipments.txt"))?;
+    collections.trip_properties = CollectionWithId::new(opt_objects!("trip_properties.txt"))?;
+    collections.transfers = Collection::new(opt_objects!("transfers.txt"));
+    collections.admin_stations = Collection::new(opt_objects!("admin_stations.txt"));
+    collections.stop_area_memberships = Collection::new(opt_objects!("stop_area_memberships.txt"));
+    collections.company_parents = Collection::new(opt_objects!("company_parents.txt"));
+    collections.translations = Collection::new(opt_objects!("translations.txt"));
+    collections.booking_rules = CollectionWithId::new(opt_objects!("booking_rules.txt"))?;
+    collections.booking_rule_links = Collection::new(opt_objects!("booking_rule_links.txt"));
+    collections.disruptions = CollectionWithId::new(opt_objects!("disruptions.txt"))?;
+    collections.disruption_impacts = Collection::new(opt_objects!("disruption_impacts.txt"));
+    collections.tickets = CollectionWithId::new(opt_objects!("tickets.txt"))?;
+    collections.ticket_uses = CollectionWithId::new(opt_objects!("ticket_uses.txt"))?;
+    collections.ticket_prices = Collection::new(opt_objects!("ticket_prices.txt"));
+    collections.ticket_use_perimeters = Collection::new(opt_objects!("ticket_use_perimeters.txt"));
+    collections.ticket_use_restrictions =
+        Collection::new(opt_objects!("ticket_use_restrictions.txt"));
+    collections.fare_zones = CollectionWithId::new(opt_objects!("fare_zones.txt"))?;
+    collections.levels = CollectionWithId::new(opt_objects!("levels.txt"))?;
+    collections.grid_calendars = CollectionWithId::new(opt_objects!("grid_calendars.txt"))?;
+    collections.grid_exception_dates = Collection::new(opt_objects!("grid_exception_dates.txt"));
+    collections.grid_periods = Collection::new(opt_objects!("grid_periods.txt"));
+    collections.grid_rel_calendar_line =
+        Collection::new(opt_objects!("grid_rel_calendar_line.txt"));
     manage_calendars(&mut file_handle, &mut collections)?;
-    read::manage_geometries(&mut collections, path)?;
+    if !options.skips(SkippedField::Geometries) {
+        read::manage_geometries(&mut collections, path)?;
+    }
     read::manage_feed_infos(&mut collections, path)?;
-    read::manage_stops(&mut collections, path)?;
+    read::manage_stops_skipping_columns(
+        &mut collections,
+        path,
+        &options.skipped_columns("stops.txt"),
+    )?;
+    read::manage_ticket_use_restrictions(&mut collections, report);
+    read::manage_levels(&mut collections);
     read::manage_pathways(&mut collections, path)?;
-    read::manage_stop_times(&mut collections, path)?;
-    read::manage_codes(&mut collections, path)?;
-    read::manage_comments(&mut collections, path)?;
-    read::manage_object_properties(&mut collections, path)?;
+    read::manage_stop_times(
+        &mut collections,
+        path,
+        options.invalid_records_report(report),
+    )?;
+    if !options.skips(SkippedField::Codes) {
+        read::manage_codes(&mut collections, path)?;
+    }
+    if !options.skips(SkippedField::Comments) {
+        read::manage_comments(&mut collections, path)?;
+    }
+    if !options.skips(SkippedField::ObjectProperties) {
+        read::manage_object_properties(&mut collections, path)?;
+    }
     read::manage_fares_v1(&mut collections, path)?;
     read::manage_companies_on_vj(&mut collections)?;
-    info!("Indexing");
-    let res = Model::new(collections)?;
-    info!("Loading NTFS done");
-    Ok(res)
+    if options.keep_extra_columns {
+        collections.extra_columns = extra_columns::read_extra_columns(path)?;
+    }
+    Ok(collections)
+}
+
+/// Describes the columns expected by [read](fn.read.html) in the NTFS file
+/// `file_name` (e.g. `stops.txt`), or `None` if the file is not read.
+pub fn explain(file_name: &str) -> Option<FileSchema> {
+    let columns = match file_name {
+        "contributors.txt" => schema::columns::<Contributor>(),
+        "datasets.txt" => schema::columns::<Dataset>(),
+        "commercial_modes.txt" => schema::columns::<CommercialMode>(),
+        "networks.txt" => schema::columns::<Network>(),
+        "lines.txt" => schema::columns::<Line>(),
+        "routes.txt" => schema::columns::<Route>(),
+        "trips.txt" => schema::columns::<VehicleJourney>(),
+        "frequencies.txt" => schema::columns::<Frequency>(),
+        "physical_modes.txt" => schema::columns::<PhysicalMode>(),
+        "companies.txt" => schema::columns::<Company>(),
+        "equipments.txt" => schema::columns::<Equipment>(),
+        "trip_properties.txt" => schema::columns::<TripProperty>(),
+        "transfers.txt" => schema::columns::<Transfer>(),
+        "admin_stations.txt" => schema::columns::<AdminStation>(),
+        "stop_area_memberships.txt" => schema::columns::<StopAreaMembership>(),
+        "company_parents.txt" => schema::columns::<CompanyParent>(),
+        "translations.txt" => schema::columns::<Translation>(),
+        "booking_rules.txt" => schema::columns::<BookingRule>(),
+        "booking_rule_links.txt" => schema::columns::<BookingRuleLink>(),
+        "disruptions.txt" => schema::columns::<Disruption>(),
+        "disruption_impacts.txt" => schema::columns::<DisruptionImpact>(),
+        "tickets.txt" => schema::columns::<Ticket>(),
+        "ticket_uses.txt" => schema::columns::<TicketUse>(),
+        "ticket_prices.txt" => schema::columns::<TicketPrice>(),
+        "ticket_use_perimeters.txt" => schema::columns::<TicketUsePerimeter>(),
+        "ticket_use_restrictions.txt" => schema::columns::<TicketUseRestriction>(),
+        "fare_zones.txt" => schema::columns::<FareZone>(),
+        "levels.txt" => schema::columns::<Level>(),
+        "grid_calendars.txt" => schema::columns::<GridCalendar>(),
+        "grid_exception_dates.txt" => schema::columns::<GridExceptionDate>(),
+        "grid_periods.txt" => schema::columns::<GridPeriod>(),
+        "grid_rel_calendar_line.txt" => schema::columns::<GridRelCalendarLine>(),
+        "calendar.txt" => schema::columns::<calendars::Calendar>(),
+        "calendar_dates.txt" => schema::columns::<calendars::CalendarDate>(),
+        "geometries.txt" => schema::columns::<Geometry>(),
+        "feed_infos.txt" => schema::columns::<read::FeedInfo>(),
+        "stops.txt" => schema::columns::<Stop>(),
+        "pathways.txt" => schema::columns::<Pathway>(),
+        "stop_times.txt" => schema::columns::<StopTime>(),
+        "object_codes.txt" => schema::columns::<Code>(),
+        "comments.txt" => schema::columns::<Comment>(),
+        "comment_links.txt" => schema::columns::<CommentLink>(),
+        "object_properties.txt" => schema::columns::<ObjectProperty>(),
+        _ => return None,
+    };
+    Some(FileSchema {
+        file_name: file_name.to_string(),
+        columns,
+    })
+}
+
+// Columns referencing the objects of another file:
+// (file, column, referenced file)
+const REFERENCE_COLUMNS: &[(&str, &str, &str)] = &[
+    ("lines.txt", "geometry_id", "geometries.txt"),
+    ("routes.txt", "geometry_id", "geometries.txt"),
+    ("trips.txt", "geometry_id", "geometries.txt"),
+    ("trips.txt", "trip_property_id", "trip_properties.txt"),
+    ("stops.txt", "geometry_id", "geometries.txt"),
+    ("stops.txt", "equipment_id", "equipments.txt"),
+    ("stops.txt", "level_id", "levels.txt"),
+    ("stops.txt", "fare_zone_id", "fare_zones.txt"),
+    ("transfers.txt", "equipment_id", "equipments.txt"),
+];
+
+/// Options of [write_with_options](fn.write_with_options.html)
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Writes the legacy fares V1 files (`prices.csv`, `od_fares.csv` and
+    /// `fares.csv`), converted from the fares V2 if there are some, for the
+    /// engines not supporting fares V2. The origin-destination restrictions of
+    /// the fares V2 are flattened into conditions of `fares.csv`. `true` by
+    /// default.
+    pub fares_v1: bool,
+    /// Fields of the objects which are not written (their files and columns)
+    pub skipped_fields: BTreeSet<SkippedField>,
+    /// Extensions of the [MANIFEST](constant.MANIFEST.html) whose files are
+    /// not written, e.g. `"fares V2"`
+    pub skipped_extensions: BTreeSet<String>,
+    /// Writes only the files marked as mandatory in the
+    /// [MANIFEST](constant.MANIFEST.html), and `frequencies.txt`. `false` by
+    /// default.
+    pub mandatory_files_only: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            fares_v1: true,
+            skipped_fields: BTreeSet::new(),
+            skipped_extensions: BTreeSet::new(),
+            mandatory_files_only: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Options of the minimal NTFS profile: only the mandatory files (and
+    /// `frequencies.txt`, as the vehicle journeys it defines would be lost
+    /// otherwise) are written, without the columns referencing the other
+    /// files (e.g. the `geometry_id` of the lines).
+    pub fn minimal() -> Self {
+        WriteOptions {
+            fares_v1: false,
+            mandatory_files_only: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the file `file_name` is written
+    pub fn writes(&self, file_name: &str) -> bool {
+        let skipped_field = match file_name {
+            "comments.txt" | "comment_links.txt" => Some(SkippedField::Comments),
+            "geometries.txt" => Some(SkippedField::Geometries),
+            "object_codes.txt" => Some(SkippedField::Codes),
+            "object_properties.txt" => Some(SkippedField::ObjectProperties),
+            _ => None,
+        };
+        if skipped_field.map_or(false, |field| self.skipped_fields.contains(&field)) {
+            return false;
+        }
+        match file_requirement(file_name) {
+            None | Some(FileRequirement::Mandatory) | Some(FileRequirement::Alternative(_)) => true,
+            // the vehicle journeys of the frequencies would be lost without
+            // them
+            Some(FileRequirement::Optional) => {
+                !self.mandatory_files_only || file_name == "frequencies.txt"
+            }
+            Some(FileRequirement::Extension(extension)) => {
+                !self.mandatory_files_only && !self.skipped_extensions.contains(extension)
+            }
+        }
+    }
+
+    // The columns of `file_name` which are not written: the skipped fields
+    // and the references to the files not written
+    fn skipped_columns(&self, file_name: &str) -> Vec<&'static str> {
+        let mut skipped_columns: Vec<&'static str> = REFERENCE_COLUMNS
+            .iter()
+            .filter(|(file, _, referenced_file)| {
+                *file == file_name && !self.writes(referenced_file)
+            })
+            .map(|(_, column, _)| *column)
+            .collect();
+        if self.skipped_fields.contains(&SkippedField::Colors) && file_name == "lines.txt" {
+            skipped_columns.extend(&["line_color", "line_text_color"]);
+        }
+        skipped_columns
+    }
 }
 
 /// Exports a `Model` to the
@@ -229,86 +637,294 @@ pub fn write<P: AsRef<path::Path>>(
     path: P,
     current_datetime: DateTime<FixedOffset>,
 ) -> Result<()> {
-    let path = path.as_ref();
+    write_with_options(model, path, current_datetime, &WriteOptions::default())
+}
+
+/// Exports a `Model` to the
+/// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
+/// files in the given directory, with the given `options`.
+pub fn write_with_options<P: AsRef<path::Path>>(
+    model: &Model,
+    path: P,
+    current_datetime: DateTime<FixedOffset>,
+    options: &WriteOptions,
+) -> Result<()> {
+    write_files(model, path.as_ref(), current_datetime, options, true)
+}
+
+// Without the stop times, the trips are written alone, their stop times
+// being already written in `stop_times.txt`
+pub(crate) fn write_files(
+    model: &Model,
+    path: &path::Path,
+    current_datetime: DateTime<FixedOffset>,
+    options: &WriteOptions,
+    with_stop_times: bool,
+) -> Result<()> {
     info!("Writing NTFS to {:?}", path);
 
+    // the files not written with the options are skipped, as are the columns
+    // referencing them
+    macro_rules! write_collection_with_id {
+        ($file:expr, $collection:expr) => {
+            if options.writes($file) {
+                write_collection_with_id_skipping_columns(
+                    path,
+                    $file,
+                    $collection,
+                    &options.skipped_columns($file),
+                )?;
+            }
+        };
+    }
+    macro_rules! write_collection {
+        ($file:expr, $collection:expr) => {
+            if options.writes($file) {
+                write_collection_skipping_columns(
+                    path,
+                    $file,
+                    $collection,
+                    &options.skipped_columns($file),
+                )?;
+            }
+        };
+    }
     write::write_feed_infos(path, &model, current_datetime)?;
-    write_collection_with_id(path, "contributors.txt", &model.contributors)?;
-    write_collection_with_id(path, "datasets.txt", &model.datasets)?;
-    write_collection_with_id(path, "networks.txt", &model.networks)?;
-    write_collection_with_id(path, "commercial_modes.txt", &model.commercial_modes)?;
-    write_collection_with_id(path, "companies.txt", &model.companies)?;
-    write_collection_with_id(path, "lines.txt", &model.lines)?;
-    write_collection_with_id(path, "physical_modes.txt", &model.physical_modes)?;
-    write_collection_with_id(path, "equipments.txt", &model.equipments)?;
-    write_collection_with_id(path, "routes.txt", &model.routes)?;
-    write_collection_with_id(path, "trip_properties.txt", &model.trip_properties)?;
-    write_collection_with_id(path, "geometries.txt", &model.geometries)?;
-    write_collection(path, "transfers.txt", &model.transfers)?;
-    write_collection(path, "admin_stations.txt", &model.admin_stations)?;
-    write_collection_with_id(path, "tickets.txt", &model.tickets)?;
-    write_collection_with_id(path, "ticket_uses.txt", &model.ticket_uses)?;
-    write_collection(path, "ticket_prices.txt", &model.ticket_prices)?;
-    write_collection(
-        path,
-        "ticket_use_perimeters.txt",
-        &model.ticket_use_perimeters,
-    )?;
-    write_collection(
-        path,
+    write_collection_with_id!("contributors.txt", &model.contributors);
+    write_collection_with_id!("datasets.txt", &model.datasets);
+    write_collection_with_id!("networks.txt", &model.networks);
+    write_collection_with_id!("commercial_modes.txt", &model.commercial_modes);
+    write_collection_with_id!("companies.txt", &model.companies);
+    write_collection_with_id!("lines.txt", &model.lines);
+    write_collection_with_id!("physical_modes.txt", &model.physical_modes);
+    write_collection_with_id!("equipments.txt", &model.equipments);
+    write_collection_with_id!("routes.txt", &model.routes);
+    write_collection_with_id!("trip_properties.txt", &model.trip_properties);
+    write_collection_with_id!("geometries.txt", &model.geometries);
+    write_collection!("transfers.txt", &model.transfers);
+    write_collection!("admin_stations.txt", &model.admin_stations);
+    write_collection!("stop_area_memberships.txt", &model.stop_area_memberships);
+    write_collection!("company_parents.txt", &model.company_parents);
+    write_collection!("translations.txt", &model.translations);
+    write_collection_with_id!("booking_rules.txt", &model.booking_rules);
+    write_collection!("booking_rule_links.txt", &model.booking_rule_links);
+    write_collection_with_id!("disruptions.txt", &model.disruptions);
+    write_collection!("disruption_impacts.txt", &model.disruption_impacts);
+    write_collection_with_id!("tickets.txt", &model.tickets);
+    write_collection_with_id!("ticket_uses.txt", &model.ticket_uses);
+    write_collection!("ticket_prices.txt", &model.ticket_prices);
+    write_collection!("ticket_use_perimeters.txt", &model.ticket_use_perimeters);
+    write_collection!(
         "ticket_use_restrictions.txt",
-        &model.ticket_use_restrictions,
-    )?;
-    write_collection_with_id(path, "grid_calendars.txt", &model.grid_calendars)?;
-    write_collection(
-        path,
-        "grid_exception_dates.txt",
-        &model.grid_exception_dates,
-    )?;
-    write_collection(path, "grid_periods.txt", &model.grid_periods)?;
-    write_collection(
-        path,
-        "grid_rel_calendar_line.txt",
-        &model.grid_rel_calendar_line,
-    )?;
-    write::write_vehicle_journeys_and_stop_times(
-        path,
-        &model.vehicle_journeys,
-        &model.stop_points,
-        &model.stop_time_headsigns,
-        &model.stop_time_ids,
-    )?;
-    write_collection(path, "frequencies.txt", &model.frequencies)?;
+        &model.ticket_use_restrictions
+    );
+    write_collection_with_id!("fare_zones.txt", &model.fare_zones);
+    write_collection_with_id!("grid_calendars.txt", &model.grid_calendars);
+    write_collection!("grid_exception_dates.txt", &model.grid_exception_dates);
+    write_collection!("grid_periods.txt", &model.grid_periods);
+    write_collection!("grid_rel_calendar_line.txt", &model.grid_rel_calendar_line);
+    if with_stop_times {
+        write::write_vehicle_journeys_and_stop_times(
+            path,
+            &model.vehicle_journeys,
+            &model.stop_points,
+            &model.stop_time_headsigns,
+            &model.stop_time_ids,
+            &model.stop_time_continuous_stoppings,
+            &options.skipped_columns("trips.txt"),
+        )?;
+    } else {
+        write_collection_with_id!("trips.txt", &model.vehicle_journeys);
+    }
+    write_collection!("frequencies.txt", &model.frequencies);
     write_calendar_dates(path, &model.calendars)?;
     write::write_stops(
         path,
         &model.stop_points,
         &model.stop_areas,
         &model.stop_locations,
+        &options.skipped_columns("stops.txt"),
     )?;
-    write::write_comments(path, model)?;
-    write::write_codes(path, model)?;
-    write::write_object_properties(path, model)?;
-    write::write_fares_v1(path, &model)?;
-    write_collection_with_id(path, "pathways.txt", &model.pathways)?;
-    write_collection_with_id(path, "levels.txt", &model.levels)?;
+    if options.writes("comments.txt") {
+        write::write_comments(path, model)?;
+    }
+    if options.writes("object_codes.txt") {
+        write::write_codes(path, model)?;
+    }
+    if options.writes("object_properties.txt") {
+        write::write_object_properties(path, model)?;
+    }
+    if options.fares_v1 && options.writes("prices.csv") {
+        write::write_fares_v1(path, &model)?;
+    }
+    write_collection_with_id!("pathways.txt", &model.pathways);
+    write_collection_with_id!("levels.txt", &model.levels);
+    extra_columns::write_extra_columns(path, &model.extra_columns)?;
+
+    Ok(())
+}
+
+/// Checks that the
+/// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
+/// files in the given directory can be read again (which checks the
+/// referential integrity) and contain as many objects as `model`.
+pub fn validate<P: AsRef<path::Path>>(model: &Model, path: P) -> Result<()> {
+    validate_with_options(model, path, &WriteOptions::default())
+}
+
+// The collections of the files not written with the `options` are not
+// compared
+fn validate_with_options<P: AsRef<path::Path>>(
+    model: &Model,
+    path: P,
+    options: &WriteOptions,
+) -> Result<()> {
+    let path = path.as_ref();
+    info!("Validating NTFS {:?}", path);
+    let written =
+        read(path).map_err(|e| format_err!("NTFS {:?} can't be read again: {}", path, e))?;
+
+    let mut mismatches = vec![];
+    macro_rules! check_count {
+        ($($collection:ident),*) => {
+            $(
+                if options.writes(concat!(stringify!($collection), ".txt"))
+                    && model.$collection.len() != written.$collection.len()
+                {
+                    mismatches.push(format!(
+                        "{}: {} expected, {} written",
+                        stringify!($collection),
+                        model.$collection.len(),
+                        written.$collection.len()
+                    ));
+                }
+            )*
+        };
+    }
+    check_count!(
+        contributors,
+        datasets,
+        networks,
+        commercial_modes,
+        lines,
+        routes,
+        vehicle_journeys,
+        frequencies,
+        physical_modes,
+        stop_areas,
+        stop_points,
+        stop_locations,
+        calendars,
+        companies,
+        comments,
+        equipments,
+        transfers,
+        trip_properties,
+        geometries,
+        admin_stations,
+        stop_area_memberships,
+        company_parents,
+        translations,
+        booking_rules,
+        booking_rule_links,
+        disruptions,
+        disruption_impacts,
+        tickets,
+        ticket_uses,
+        ticket_prices,
+        ticket_use_perimeters,
+        ticket_use_restrictions,
+        fare_zones,
+        pathways,
+        levels,
+        grid_calendars,
+        grid_exception_dates,
+        grid_periods,
+        grid_rel_calendar_line
+    );
+    let count_stop_times = |model: &Model| -> usize {
+        model
+            .vehicle_journeys
+            .values()
+            .map(|vj| vj.stop_times.len())
+            .sum()
+    };
+    if count_stop_times(model) != count_stop_times(&written) {
+        mismatches.push(format!(
+            "stop_times: {} expected, {} written",
+            count_stop_times(model),
+            count_stop_times(&written)
+        ));
+    }
+
+    if !mismatches.is_empty() {
+        bail!(
+            "NTFS {:?} doesn't match the model: {}",
+            path,
+            mismatches.join(", ")
+        );
+    }
+    Ok(())
+}
 
+/// Exports a `Model` to the
+/// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
+/// files in the given directory, with the given `options`, checking the
+/// result with [validate](fn.validate.html) before.
+///
+/// The files are first written in a temporary directory and are only copied
+/// to `path` once validated (the directory being created if missing), so
+/// `path` never contains a corrupt NTFS.
+pub fn write_validated<P: AsRef<path::Path>>(
+    model: &Model,
+    path: P,
+    current_datetime: DateTime<FixedOffset>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let path = path.as_ref();
+    let tmp_dir = tempdir()?;
+    write_with_options(model, tmp_dir.path(), current_datetime, options)?;
+    validate_with_options(model, tmp_dir.path(), options)?;
+    info!("Copying validated NTFS to {:?}", path);
+    fs::create_dir_all(path).with_context(|_| format!("Error writing {:?}", path))?;
+    for entry in fs::read_dir(tmp_dir.path())? {
+        let entry = entry?;
+        let target = path.join(entry.file_name());
+        fs::copy(entry.path(), &target).with_context(|_| format!("Error writing {:?}", target))?;
+    }
+    tmp_dir.close()?;
     Ok(())
 }
 
 /// Exports a `Model` to a
 /// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
-/// ZIP archive at the given full path.
+/// ZIP archive at the given full path. It needs the `zip` feature.
+#[cfg(feature = "zip")]
 pub fn write_to_zip<P: AsRef<path::Path>>(
     model: &Model,
     path: P,
     current_datetime: DateTime<FixedOffset>,
+) -> Result<()> {
+    write_to_archive(model, path, current_datetime, ArchiveFormat::Zip)
+}
+
+/// Exports a `Model` to a
+/// [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md)
+/// archive at the given full path, in the given
+/// [format](../archive/enum.ArchiveFormat.html) (e.g. a `.tar.zst` archive
+/// with the `tar-zst` feature). It needs the `zip` feature.
+#[cfg(feature = "zip")]
+pub fn write_to_archive<P: AsRef<path::Path>>(
+    model: &Model,
+    path: P,
+    current_datetime: DateTime<FixedOffset>,
+    format: ArchiveFormat,
 ) -> Result<()> {
     let path = path.as_ref();
-    info!("Writing NTFS to ZIP File {:?}", path);
+    info!("Writing NTFS to {:?} archive {:?}", format, path);
     let input_tmp_dir = tempdir()?;
     write(model, input_tmp_dir.path(), current_datetime)?;
-    zip_to(input_tmp_dir.path(), path)?;
+    archive_to(input_tmp_dir.path(), path, format)?;
     input_tmp_dir.close()?;
     Ok(())
 }
@@ -327,7 +943,7 @@ mod tests {
         collections::{BTreeMap, BTreeSet, HashMap},
         fmt::Debug,
     };
-    use typed_index_collection::{Collection, CollectionWithId, Id};
+    use typed_index_collection::{CollectionWithId, Id};
 
     fn test_serialize_deserialize_collection_with_id<T>(objects: Vec<T>)
     where
@@ -350,7 +966,9 @@ mod tests {
         let collection = Collection::new(objects);
         test_in_tmp_dir(|path| {
             write_collection(path, "file.txt", &collection).unwrap();
-            let des_collection = make_opt_collection(path, "file.txt").unwrap();
+            let des_collection = Collection::new(
+                read_objects_skipping_columns(path, "file.txt", &[], None).unwrap(),
+            );
             assert_eq!(collection, des_collection);
         });
     }
@@ -392,6 +1010,9 @@ mod tests {
                     ),
                     ("feed_creation_time".to_string(), "17:19:00".to_string()),
                     ("feed_end_date".to_string(), "20180131".to_string()),
+                    ("feed_nb_lines".to_string(), "0".to_string()),
+                    ("feed_nb_stop_areas".to_string(), "0".to_string()),
+                    ("feed_nb_stop_points".to_string(), "0".to_string()),
                     ("feed_publisher_name".to_string(), "Nicaragua".to_string()),
                     ("feed_start_date".to_string(), "20180130".to_string()),
                     ("ntfs_version".to_string(), "0.11.2".to_string()),
@@ -406,6 +1027,42 @@ mod tests {
         });
     }
 
+    #[test]
+    fn computed_feed_infos_replace_stale_values() {
+        let mut collections = Collections::default();
+        collections.datasets = CollectionWithId::from(Dataset {
+            id: "Foo:0".to_string(),
+            contributor_id: "Foo".to_string(),
+            start_date: chrono::NaiveDate::from_ymd(2018, 1, 30),
+            end_date: chrono::NaiveDate::from_ymd(2018, 1, 31),
+            ..Default::default()
+        });
+        collections.feed_infos = vec![
+            ("feed_creation_date", "20200101"),
+            ("feed_start_date", "20200102"),
+            ("feed_end_date", "20200103"),
+            ("feed_nb_lines", "12"),
+            ("feed_publisher_name", "Nicaragua"),
+            ("ntfs_version", "0.9"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+        test_in_tmp_dir(|path| {
+            write::write_feed_infos(path, &collections, get_test_datetime()).unwrap();
+            read::manage_feed_infos(&mut collections, path).unwrap();
+        });
+        let feed_info = |key: &str| collections.feed_infos[key].as_str();
+        assert_eq!("20190403", feed_info("feed_creation_date"));
+        assert_eq!("17:19:00", feed_info("feed_creation_time"));
+        assert_eq!("20180130", feed_info("feed_start_date"));
+        assert_eq!("20180131", feed_info("feed_end_date"));
+        assert_eq!("0", feed_info("feed_nb_lines"));
+        assert_eq!("Nicaragua", feed_info("feed_publisher_name"));
+        assert_eq!(crate::NTFS_VERSION, feed_info("ntfs_version"));
+    }
+
     #[test]
     fn networks_serialization_deserialization() {
         test_serialize_deserialize_collection_with_id(vec![
@@ -418,6 +1075,8 @@ mod tests {
                 phone: Some("0123456789".to_string()),
                 address: Some("somewhere".to_string()),
                 sort_order: Some(1),
+                fare_url: Some("http://www.vianavigo.com/tarifs".to_string()),
+                email: Some("contact@vianavigo.com".to_string()),
                 codes: KeysValues::default(),
             },
             Network {
@@ -429,6 +1088,8 @@ mod tests {
                 phone: None,
                 address: None,
                 sort_order: None,
+                fare_url: None,
+                email: None,
                 codes: KeysValues::default(),
             },
         ]);
@@ -650,6 +1311,7 @@ mod tests {
                     },
                 ],
                 journey_pattern_id: Some(String::from("OIF:JP:1")),
+                length: None,
             },
             VehicleJourney {
                 id: "OIF:90014407-1_425283-1".to_string(),
@@ -668,6 +1330,7 @@ mod tests {
                 geometry_id: None,
                 stop_times: vec![],
                 journey_pattern_id: Some(String::from("OIF:JP:1")),
+                length: None,
             },
         ])
         .unwrap();
@@ -682,6 +1345,14 @@ mod tests {
             ("OIF:87604986-1_11595-1".to_string(), 0),
             "StopTime:OIF:87604986-1_11595-1:0".to_string(),
         );
+        let mut continuous_stoppings = HashMap::new();
+        continuous_stoppings.insert(
+            ("OIF:87604986-1_11595-1".to_string(), 0),
+            ContinuousStopping {
+                pickup: ContinuousStoppingType::Continuous,
+                drop_off: ContinuousStoppingType::CoordinateWithDriver,
+            },
+        );
 
         test_in_tmp_dir(|path| {
             write::write_vehicle_journeys_and_stop_times(
@@ -690,6 +1361,8 @@ mod tests {
                 &stop_points,
                 &headsigns,
                 &stop_time_ids,
+                &continuous_stoppings,
+                &[],
             )
             .unwrap();
 
@@ -698,10 +1371,14 @@ mod tests {
                 make_collection_with_id::<VehicleJourney>(path, "trips.txt").unwrap();
             collections.stop_points = stop_points;
 
-            read::manage_stop_times(&mut collections, path).unwrap();
+            read::manage_stop_times(&mut collections, path, None).unwrap();
             assert_eq!(vehicle_journeys, collections.vehicle_journeys);
             assert_eq!(collections.stop_time_headsigns, headsigns);
             assert_eq!(collections.stop_time_ids, stop_time_ids);
+            assert_eq!(
+                collections.stop_time_continuous_stoppings,
+                continuous_stoppings
+            );
         });
     }
 
@@ -786,6 +1463,62 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn pathways_serialization_deserialization() {
+        use rust_decimal_macros::dec;
+
+        test_serialize_deserialize_collection_with_id(vec![
+            Pathway {
+                id: "pw_1".to_string(),
+                from_stop_id: "sp_1".to_string(),
+                from_stop_type: StopType::default(),
+                to_stop_id: "sp_2".to_string(),
+                to_stop_type: StopType::default(),
+                pathway_mode: PathwayMode::Stairs,
+                is_bidirectional: true,
+                length: Some(dec!(12.5)),
+                traversal_time: Some(45),
+                stair_count: Some(-20),
+                max_slope: None,
+                min_width: Some(1.5),
+                signposted_as: Some("Exit".to_string()),
+                reversed_signposted_as: Some("Platform".to_string()),
+            },
+            Pathway {
+                id: "pw_2".to_string(),
+                from_stop_id: "sp_2".to_string(),
+                from_stop_type: StopType::default(),
+                to_stop_id: "sp_3".to_string(),
+                to_stop_type: StopType::default(),
+                pathway_mode: PathwayMode::Walkway,
+                is_bidirectional: false,
+                length: None,
+                traversal_time: None,
+                stair_count: None,
+                max_slope: Some(0.1),
+                min_width: None,
+                signposted_as: None,
+                reversed_signposted_as: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn levels_serialization_deserialization() {
+        test_serialize_deserialize_collection_with_id(vec![
+            Level {
+                id: "level_1".to_string(),
+                level_index: -1.5,
+                level_name: Some("Mezzanine".to_string()),
+            },
+            Level {
+                id: "level_2".to_string(),
+                level_index: 0.0,
+                level_name: None,
+            },
+        ]);
+    }
+
     #[test]
     fn calendar_serialization_deserialization() {
         let mut dates1 = ::std::collections::BTreeSet::new();
@@ -892,7 +1625,7 @@ mod tests {
         let stop_locations: CollectionWithId<StopLocation> = CollectionWithId::default();
 
         test_in_tmp_dir(|path| {
-            write::write_stops(path, &stop_points, &stop_areas, &stop_locations).unwrap();
+            write::write_stops(path, &stop_points, &stop_areas, &stop_locations, &[]).unwrap();
 
             let mut collections = Collections::default();
             read::manage_stops(&mut collections, path).unwrap();
@@ -1057,6 +1790,7 @@ mod tests {
                 precision: None,
             }],
             journey_pattern_id: None,
+            length: None,
         });
 
         let networks = CollectionWithId::from(Network {
@@ -1068,6 +1802,8 @@ mod tests {
             phone: None,
             address: None,
             sort_order: None,
+            fare_url: None,
+            email: None,
             codes: KeysValues::default(),
         });
 
@@ -1094,6 +1830,7 @@ mod tests {
                 &ser_collections.stop_points,
                 &ser_collections.stop_areas,
                 &ser_collections.stop_locations,
+                &[],
             )
             .unwrap();
             write_collection_with_id(path, "routes.txt", &ser_collections.routes).unwrap();
@@ -1104,6 +1841,8 @@ mod tests {
                 &ser_collections.stop_points,
                 &ser_collections.stop_time_headsigns,
                 &ser_collections.stop_time_ids,
+                &ser_collections.stop_time_continuous_stoppings,
+                &[],
             )
             .unwrap();
             write::write_comments(path, &ser_collections).unwrap();
@@ -1116,7 +1855,7 @@ mod tests {
             des_collections.vehicle_journeys = make_collection_with_id(path, "trips.txt").unwrap();
             des_collections.networks = make_collection_with_id(path, "networks.txt").unwrap();
             read::manage_stops(&mut des_collections, path).unwrap();
-            read::manage_stop_times(&mut des_collections, path).unwrap();
+            read::manage_stop_times(&mut des_collections, path, None).unwrap();
             read::manage_comments(&mut des_collections, path).unwrap();
             read::manage_codes(&mut des_collections, path).unwrap();
             read::manage_object_properties(&mut des_collections, path).unwrap();
@@ -1443,14 +2182,14 @@ mod tests {
             TicketPrice {
                 ticket_id: "PF1:Ticket1".to_string(),
                 price: dec!(150.0),
-                currency: "EUR".to_string(),
+                currency: "EUR".parse().unwrap(),
                 ticket_validity_start: chrono::NaiveDate::from_ymd(2019, 1, 1),
                 ticket_validity_end: chrono::NaiveDate::from_ymd(2019, 12, 31),
             },
             TicketPrice {
                 ticket_id: "PF2:Ticket2".to_string(),
                 price: dec!(900.0),
-                currency: "GHS".to_string(),
+                currency: "GHS".parse().unwrap(),
                 ticket_validity_start: chrono::NaiveDate::from_ymd(2019, 1, 1),
                 ticket_validity_end: chrono::NaiveDate::from_ymd(2019, 12, 31),
             },
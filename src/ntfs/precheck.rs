@@ -0,0 +1,195 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Fast check of the references of the trips and stop times of a NTFS,
+//! before building the model
+
+use super::StopLocationType;
+use crate::{
+    objects::ObjectType,
+    read_utils::{for_each_record, lenient_report, ReadMode},
+    report::{Report, ReportEntry, ReportType, Severity},
+    utils::de_with_empty_default,
+    Result,
+};
+use failure::ResultExt;
+use log::info;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::HashSet, path};
+
+#[derive(Deserialize)]
+struct StopRow {
+    stop_id: String,
+    #[serde(default, deserialize_with = "de_with_empty_default")]
+    location_type: StopLocationType,
+}
+
+#[derive(Deserialize)]
+struct RouteRow {
+    route_id: String,
+}
+
+#[derive(Deserialize)]
+struct ServiceRow {
+    service_id: String,
+}
+
+#[derive(Deserialize)]
+struct TripRow {
+    trip_id: String,
+    route_id: String,
+    service_id: String,
+}
+
+#[derive(Deserialize)]
+struct StopTimeRow {
+    trip_id: String,
+    stop_id: String,
+}
+
+// The absent files are skipped: the mandatory ones are checked by the
+// manifest. In lenient mode, the rows which can't be read are skipped, they
+// are reported when the files are read.
+fn for_each_row<T, F>(
+    path: &path::Path,
+    file_name: &str,
+    read_mode: ReadMode,
+    mut f: F,
+) -> Result<()>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    let file_path = path.join(file_name);
+    if !file_path.exists() {
+        return Ok(());
+    }
+    let mut rdr = csv::Reader::from_path(&file_path)
+        .with_context(|_| format!("Error reading {:?}", file_path))?;
+    let mut skipped = Report::default();
+    for_each_record(
+        &mut rdr,
+        &file_path,
+        lenient_report(read_mode, &mut skipped),
+        |row: T, _| {
+            f(row);
+            Ok(())
+        },
+    )
+}
+
+fn missing_reference(
+    report: &mut Report,
+    file_name: &str,
+    field: &str,
+    object_type: Option<ObjectType>,
+    id: &str,
+) {
+    let entry = ReportEntry::new(
+        ReportType::DanglingReference,
+        format!("{}: {}={:?} not found", file_name, field, id),
+    );
+    let entry = match object_type {
+        Some(object_type) => entry.with_object(object_type, id),
+        None => entry,
+    };
+    report.add(Severity::Error, entry);
+}
+
+/// Checks that the routes, calendars and stops referenced by the trips and
+/// stop times of the NTFS in `path` exist, without building the model.
+///
+/// Only the identifiers are read, so the check takes a fraction of the time
+/// of [read](fn.read.html) and reports all the missing references at once
+/// (as errors of the report, with the number of rows concerned) instead of
+/// failing at the first one.
+pub fn precheck<P: AsRef<path::Path>>(path: P) -> Result<Report> {
+    precheck_with_read_mode(path, ReadMode::Strict)
+}
+
+/// Checks the references of the NTFS in `path` as
+/// [precheck](fn.precheck.html) does, skipping the rows which can't be read
+/// in [lenient](../read_utils/enum.ReadMode.html#variant.Lenient) mode.
+pub fn precheck_with_read_mode<P: AsRef<path::Path>>(
+    path: P,
+    read_mode: ReadMode,
+) -> Result<Report> {
+    let path = path.as_ref();
+    info!("Checking the references of trips.txt and stop_times.txt");
+    let mut report = Report::default();
+
+    let mut route_ids = HashSet::new();
+    for_each_row(path, "routes.txt", read_mode, |row: RouteRow| {
+        route_ids.insert(row.route_id);
+    })?;
+    let mut service_ids = HashSet::new();
+    for file_name in &["calendar.txt", "calendar_dates.txt"] {
+        for_each_row(path, file_name, read_mode, |row: ServiceRow| {
+            service_ids.insert(row.service_id);
+        })?;
+    }
+    let mut stop_point_ids = HashSet::new();
+    for_each_row(path, "stops.txt", read_mode, |row: StopRow| {
+        match row.location_type {
+            StopLocationType::StopPoint | StopLocationType::GeographicArea => {
+                stop_point_ids.insert(row.stop_id);
+            }
+            _ => {}
+        }
+    })?;
+
+    let mut trip_ids = HashSet::new();
+    for_each_row(path, "trips.txt", read_mode, |row: TripRow| {
+        if !route_ids.contains(&row.route_id) {
+            missing_reference(
+                &mut report,
+                "trips.txt",
+                "route_id",
+                Some(ObjectType::Route),
+                &row.route_id,
+            );
+        }
+        if !service_ids.contains(&row.service_id) {
+            missing_reference(
+                &mut report,
+                "trips.txt",
+                "service_id",
+                None,
+                &row.service_id,
+            );
+        }
+        trip_ids.insert(row.trip_id);
+    })?;
+    for_each_row(path, "stop_times.txt", read_mode, |row: StopTimeRow| {
+        if !trip_ids.contains(&row.trip_id) {
+            missing_reference(
+                &mut report,
+                "stop_times.txt",
+                "trip_id",
+                Some(ObjectType::VehicleJourney),
+                &row.trip_id,
+            );
+        }
+        if !stop_point_ids.contains(&row.stop_id) {
+            missing_reference(
+                &mut report,
+                "stop_times.txt",
+                "stop_id",
+                Some(ObjectType::StopPoint),
+                &row.stop_id,
+            );
+        }
+    })?;
+    Ok(report)
+}
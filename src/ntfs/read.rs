@@ -13,10 +13,14 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>
 
 use super::{Code, CommentLink, ObjectProperty, Stop, StopLocationType, StopTime};
+use crate::error::TransitModelError;
+use crate::fares;
 use crate::model::Collections;
-use crate::ntfs::has_fares_v2;
+use crate::ntfs::{has_fares_v2, manifest};
 use crate::objects::*;
-use crate::utils::make_collection_with_id;
+use crate::read_utils::{deserialize_records, skip_dangling_reference};
+use crate::report::{Report, ReportEntry, ReportType, Severity};
+use crate::utils::{csv_reader_skipping_columns, make_collection_with_id};
 use crate::Result;
 use failure::{bail, ensure, format_err, ResultExt};
 use log::{error, info, warn, Level as LogLevel};
@@ -152,10 +156,17 @@ impl TryFrom<Stop> for StopLocation {
 }
 
 pub fn manage_stops(collections: &mut Collections, path: &path::Path) -> Result<()> {
+    manage_stops_skipping_columns(collections, path, &[])
+}
+
+pub fn manage_stops_skipping_columns(
+    collections: &mut Collections,
+    path: &path::Path,
+    skipped_columns: &[&str],
+) -> Result<()> {
     info!("Reading stops.txt");
     let path = path.join("stops.txt");
-    let mut rdr =
-        csv::Reader::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
+    let mut rdr = csv_reader_skipping_columns(&path, skipped_columns)?;
 
     let mut stop_areas = vec![];
     let mut stop_points = vec![];
@@ -192,21 +203,84 @@ pub fn manage_stops(collections: &mut Collections, path: &path::Path) -> Result<
     Ok(())
 }
 
+// The restrictions referencing unknown stop areas (OD) or fare zones (zone)
+// are ignored, each of them being added to the `report`
+pub fn manage_ticket_use_restrictions(collections: &mut Collections, report: &mut Report) {
+    let fare_zones = fares::fare_zones(collections);
+    let restrictions = collections
+        .ticket_use_restrictions
+        .take()
+        .into_iter()
+        .filter(|restriction| {
+            let exists = fares::restriction_objects_exist(collections, &fare_zones, restriction);
+            if !exists {
+                let message = format!(
+                    "the restriction of the ticket use {} from {} to {} references an unknown origin or destination, it is ignored",
+                    restriction.ticket_use_id, restriction.use_origin, restriction.use_destination
+                );
+                let mut entry = ReportEntry::new(ReportType::DanglingReference, message);
+                if let Some(ticket_use) = collections.ticket_uses.get(&restriction.ticket_use_id) {
+                    entry = entry.with_object(ObjectType::Ticket, &ticket_use.ticket_id);
+                }
+                report.add(Severity::Warning, entry);
+            }
+            exists
+        })
+        .collect();
+    collections.ticket_use_restrictions = Collection::new(restrictions);
+}
+
+// The level references of stops not declared in levels.txt are removed
+pub fn manage_levels(collections: &mut Collections) {
+    let levels = &collections.levels;
+    remove_unknown_levels(&mut collections.stop_points, levels, |sp| &mut sp.level_id);
+    remove_unknown_levels(&mut collections.stop_areas, levels, |sa| &mut sa.level_id);
+    remove_unknown_levels(&mut collections.stop_locations, levels, |sl| {
+        &mut sl.level_id
+    });
+}
+
+fn remove_unknown_levels<T, F>(
+    collection: &mut CollectionWithId<T>,
+    levels: &CollectionWithId<Level>,
+    level_id: F,
+) where
+    T: Id<T>,
+    F: Fn(&mut T) -> &mut Option<String>,
+{
+    let idxs: Vec<Idx<T>> = collection.iter().map(|(idx, _)| idx).collect();
+    for idx in idxs {
+        let mut object = collection.index_mut(idx);
+        let object_id = object.id().to_string();
+        let level = level_id(&mut object);
+        let is_unknown = level.as_ref().map_or(false, |id| !levels.contains_id(id));
+        if is_unknown {
+            let unknown_level_id = level.take();
+            warn!(
+                "level_id={:?} of stop {} not found in levels.txt, it is ignored",
+                unknown_level_id, object_id
+            );
+        }
+    }
+}
+
 pub fn manage_fares_v1(collections: &mut Collections, base_path: &path::Path) -> Result<()> {
     let file_prices = "prices.csv";
     let file_od_fares = "od_fares.csv";
     let file_fares = "fares.csv";
 
-    if !base_path.join(file_prices).exists()
-        || !base_path.join(file_od_fares).exists()
-        || has_fares_v2(collections)
-    {
+    if has_fares_v2(collections) {
         info!(
-            "Skipping {}, {} and {}",
+            "Skipping {}, {} and {} as fares V2 are provided",
             file_prices, file_od_fares, file_fares
         );
         return Ok(());
     }
+    let missing_prices = manifest::skip_missing_file(base_path, file_prices);
+    let missing_od_fares = manifest::skip_missing_file(base_path, file_od_fares);
+    if missing_prices || missing_od_fares {
+        return Ok(());
+    }
 
     let mut builder = csv::ReaderBuilder::new();
     builder.delimiter(b';');
@@ -236,8 +310,7 @@ pub fn manage_fares_v1(collections: &mut Collections, base_path: &path::Path) ->
         .with_context(|_| format!("Error reading {:?}", path))?;
     collections.od_fares_v1 = Collection::new(od_fares_v1);
 
-    if !base_path.join(file_fares).exists() {
-        info!("Skipping {}", file_fares);
+    if manifest::skip_missing_file(base_path, file_fares) {
         return Ok(());
     }
 
@@ -255,36 +328,56 @@ pub fn manage_fares_v1(collections: &mut Collections, base_path: &path::Path) ->
     Ok(())
 }
 
-pub fn manage_stop_times(collections: &mut Collections, path: &path::Path) -> Result<()> {
+// In lenient mode (when there is a `report`), the stop times which can't be
+// read or reference an unknown trip or stop are skipped and added to the
+// `report`
+pub fn manage_stop_times(
+    collections: &mut Collections,
+    path: &path::Path,
+    mut report: Option<&mut Report>,
+) -> Result<()> {
     info!("Reading stop_times.txt");
     let path = path.join("stop_times.txt");
     let mut rdr =
         csv::Reader::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
     let mut headsigns = HashMap::new();
     let mut stop_time_ids = HashMap::new();
-    for stop_time in rdr.deserialize() {
-        let stop_time: StopTime =
-            stop_time.with_context(|_| format!("Error reading {:?}", path))?;
-        let stop_point_idx = collections
-            .stop_points
-            .get_idx(&stop_time.stop_id)
-            .ok_or_else(|| {
-                format_err!(
+    let mut continuous_stoppings = HashMap::new();
+    let stop_times: Vec<StopTime> =
+        deserialize_records(&mut rdr, &path, report.as_mut().map(|report| &mut **report))?;
+    for stop_time in stop_times {
+        let stop_point_idx = match collections.stop_points.get_idx(&stop_time.stop_id) {
+            Some(stop_point_idx) => stop_point_idx,
+            None => {
+                let message = format!(
                     "Problem reading {:?}: stop_id={:?} not found",
-                    path,
-                    stop_time.stop_id
-                )
-            })?;
-        let vj_idx = collections
-            .vehicle_journeys
-            .get_idx(&stop_time.trip_id)
-            .ok_or_else(|| {
-                format_err!(
+                    path, stop_time.stop_id
+                );
+                skip_dangling_reference(
+                    message,
+                    "stop_time.stop_id",
+                    &stop_time.stop_id,
+                    &mut report,
+                )?;
+                continue;
+            }
+        };
+        let vj_idx = match collections.vehicle_journeys.get_idx(&stop_time.trip_id) {
+            Some(vj_idx) => vj_idx,
+            None => {
+                let message = format!(
                     "Problem reading {:?}: trip_id={:?} not found",
-                    path,
-                    stop_time.trip_id
-                )
-            })?;
+                    path, stop_time.trip_id
+                );
+                skip_dangling_reference(
+                    message,
+                    "stop_time.trip_id",
+                    &stop_time.trip_id,
+                    &mut report,
+                )?;
+                continue;
+            }
+        };
 
         if let Some(headsign) = stop_time.stop_headsign {
             headsigns.insert(
@@ -314,6 +407,15 @@ pub fn manage_stop_times(collections: &mut Collections, path: &path::Path) -> Re
                 stop_time_id,
             );
         }
+        if let Some(continuous_stopping) = ContinuousStopping::from_values(
+            stop_time.continuous_pickup,
+            stop_time.continuous_drop_off,
+        ) {
+            continuous_stoppings.insert(
+                (stop_time.trip_id.clone(), stop_time.stop_sequence),
+                continuous_stopping,
+            );
+        }
 
         collections
             .vehicle_journeys
@@ -335,6 +437,7 @@ pub fn manage_stop_times(collections: &mut Collections, path: &path::Path) -> Re
     }
     collections.stop_time_headsigns = headsigns;
     collections.stop_time_ids = stop_time_ids;
+    collections.stop_time_continuous_stoppings = continuous_stoppings;
     let mut vehicle_journeys = collections.vehicle_journeys.take();
     for vj in &mut vehicle_journeys {
         vj.stop_times.sort_unstable_by_key(|st| st.sequence);
@@ -372,8 +475,7 @@ where
 
 pub fn manage_codes(collections: &mut Collections, path: &path::Path) -> Result<()> {
     let file = "object_codes.txt";
-    if !path.join(file).exists() {
-        info!("Skipping {}", file);
+    if manifest::skip_missing_file(path, file) {
         return Ok(());
     }
     info!("Reading {}", file);
@@ -400,7 +502,7 @@ pub fn manage_codes(collections: &mut Collections, path: &path::Path) -> Result<
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct FeedInfo {
+pub(super) struct FeedInfo {
     #[serde(rename = "feed_info_param")]
     info_param: String,
     #[serde(rename = "feed_info_value")]
@@ -449,10 +551,14 @@ where
         }
     };
     if !comments.contains_id(&comment_link.comment_id) {
-        bail!(
-            "comment.txt: comment_id={} not found",
-            comment_link.comment_id
-        );
+        return Err(TransitModelError::dangling(
+            "comment_link.comment_id",
+            &comment_link.comment_id,
+            format!(
+                "comment.txt: comment_id={} not found",
+                comment_link.comment_id
+            ),
+        ));
     } else {
     }
     collection
@@ -472,10 +578,14 @@ fn insert_stop_time_comment_link(
         if comments.contains_id(&comment_link.comment_id) {
             stop_time_comments.insert(vehicle_journey_id.clone(), comment_link.comment_id.clone());
         } else {
-            bail!(
-                "comment.txt: comment_id={} not found",
-                comment_link.comment_id
-            )
+            return Err(TransitModelError::dangling(
+                "comment_link.comment_id",
+                &comment_link.comment_id,
+                format!(
+                    "comment.txt: comment_id={} not found",
+                    comment_link.comment_id
+                ),
+            ));
         }
     } else {
         error!(
@@ -570,11 +680,10 @@ where
 
 pub fn manage_object_properties(collections: &mut Collections, path: &path::Path) -> Result<()> {
     let file = "object_properties.txt";
-    let path = path.join(file);
-    if !path.exists() {
-        info!("Skipping {}", file);
+    if manifest::skip_missing_file(path, file) {
         return Ok(());
     }
+    let path = path.join(file);
     info!("Reading {}", file);
     let mut rdr =
         csv::Reader::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
@@ -601,11 +710,10 @@ pub fn manage_object_properties(collections: &mut Collections, path: &path::Path
 
 pub fn manage_geometries(collections: &mut Collections, path: &path::Path) -> Result<()> {
     let file = "geometries.txt";
-    let path = path.join(file);
-    if !path.exists() {
-        info!("Skipping {}", file);
+    if manifest::skip_missing_file(path, file) {
         return Ok(());
     }
+    let path = path.join(file);
 
     info!("Reading {}", file);
 
@@ -647,11 +755,10 @@ pub fn manage_companies_on_vj(collections: &mut Collections) -> Result<()> {
 
 pub fn manage_pathways(collections: &mut Collections, path: &path::Path) -> Result<()> {
     let file = "pathways.txt";
-    let pathway_path = path.join(file);
-    if !pathway_path.exists() {
-        info!("Skipping {}", file);
+    if manifest::skip_missing_file(path, file) {
         return Ok(());
     }
+    let pathway_path = path.join(file);
 
     info!("Reading {}", file);
     let mut pathways = vec![];
@@ -810,7 +917,7 @@ mod tests {
             collections.companies = make_collection_with_id(path, "companies.txt").unwrap();
             calendars::manage_calendars(&mut file_handle, &mut collections).unwrap();
             manage_stops(&mut collections, path).unwrap();
-            manage_stop_times(&mut collections, path).unwrap();
+            manage_stop_times(&mut collections, path, None).unwrap();
 
             assert_eq!(
                 vec![
@@ -884,4 +991,63 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn read_stops_with_unknown_level() {
+        let stops_content =
+            "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station,level_id\n\
+             sa:01,my stop area,0.1,1.2,1,,level_1\n\
+             sp:01,my stop point,0.1,1.2,0,sa:01,level_2";
+
+        test_in_tmp_dir(|path| {
+            create_file_with_content(path, "stops.txt", stops_content);
+            let mut collections = Collections::default();
+            collections.levels = CollectionWithId::from(Level {
+                id: "level_1".to_string(),
+                level_index: 0.0,
+                level_name: None,
+            });
+            manage_stops(&mut collections, path).unwrap();
+            manage_levels(&mut collections);
+            let stop_area = collections.stop_areas.get("sa:01").unwrap();
+            assert_eq!(Some("level_1"), stop_area.level_id.as_deref());
+            let stop_point = collections.stop_points.get("sp:01").unwrap();
+            assert_eq!(None, stop_point.level_id);
+        });
+    }
+
+    #[test]
+    fn report_ticket_use_restrictions_with_unknown_stop_areas() {
+        let stops_content = "stop_id,stop_name,stop_lat,stop_lon,location_type\n\
+                             sa:01,stop area 1,0.1,1.2,1\n\
+                             sa:02,stop area 2,0.1,1.2,1";
+
+        test_in_tmp_dir(|path| {
+            create_file_with_content(path, "stops.txt", stops_content);
+            let mut collections = Collections::default();
+            manage_stops(&mut collections, path).unwrap();
+            collections.ticket_uses = CollectionWithId::from(TicketUse {
+                id: "TU1".to_string(),
+                ticket_id: "T1".to_string(),
+                max_transfers: None,
+                boarding_time_limit: None,
+                alighting_time_limit: None,
+            });
+            let restriction = |use_destination: &str| TicketUseRestriction {
+                ticket_use_id: "TU1".to_string(),
+                restriction_type: RestrictionType::OriginDestination,
+                use_origin: "sa:01".to_string(),
+                use_destination: use_destination.to_string(),
+            };
+            collections.ticket_use_restrictions =
+                Collection::new(vec![restriction("sa:02"), restriction("sa:03")]);
+            let mut report = Report::default();
+            manage_ticket_use_restrictions(&mut collections, &mut report);
+            assert_eq!(1, collections.ticket_use_restrictions.len());
+            let warnings = report.entries(Severity::Warning);
+            assert_eq!(1, warnings.len());
+            assert_eq!(ReportType::DanglingReference, warnings[0].category);
+            assert_eq!(Some("T1"), warnings[0].object_id.as_deref());
+        });
+    }
 }
@@ -0,0 +1,508 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Versions of the NTFS specification, and the adaptation of the files of an
+//! older NTFS to the current version before reading them
+
+use crate::{
+    report::{Report, ReportType},
+    Result, NTFS_VERSION,
+};
+use failure::{format_err, ResultExt};
+use log::info;
+use std::{fmt, fs, path, str::FromStr};
+use tempfile::TempDir;
+
+/// A version of the NTFS specification, e.g. `0.11.2`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NtfsVersion {
+    /// Major version
+    pub major: u32,
+    /// Minor version
+    pub minor: u32,
+    /// Patch version, 0 when omitted (e.g. `0.6`)
+    pub patch: u32,
+}
+
+impl NtfsVersion {
+    /// The version written by [write](fn.write.html)
+    pub fn current() -> Self {
+        NTFS_VERSION
+            .parse()
+            .expect("NTFS_VERSION is not a valid version")
+    }
+}
+
+impl fmt::Display for NtfsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for NtfsVersion {
+    type Err = failure::Error;
+
+    /// Parses `major.minor.patch` or `major.minor`, with an optional `v`
+    /// prefix
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let numbers = s.trim_start_matches('v');
+        let numbers: Vec<u32> = numbers
+            .split('.')
+            .map(str::parse)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| format_err!("invalid NTFS version {:?}", s))?;
+        match numbers.as_slice() {
+            [major, minor] => Ok(NtfsVersion {
+                major: *major,
+                minor: *minor,
+                patch: 0,
+            }),
+            [major, minor, patch] => Ok(NtfsVersion {
+                major: *major,
+                minor: *minor,
+                patch: *patch,
+            }),
+            _ => Err(format_err!("invalid NTFS version {:?}", s)),
+        }
+    }
+}
+
+/// A change of the files of the NTFS specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adaptation {
+    /// A file was renamed from `old` to `new`
+    RenamedFile {
+        /// Name of the file before the change
+        old: &'static str,
+        /// Name of the file since the change
+        new: &'static str,
+    },
+    /// A column of `file` was renamed from `old` to `new`
+    RenamedColumn {
+        /// Name of the file
+        file: &'static str,
+        /// Name of the column before the change
+        old: &'static str,
+        /// Name of the column since the change
+        new: &'static str,
+    },
+    /// A mandatory `column` was added to `file`, `default` being the value
+    /// given to the rows of an older NTFS
+    AddedColumn {
+        /// Name of the file
+        file: &'static str,
+        /// Name of the added column
+        column: &'static str,
+        /// Value of the column for the rows of an older NTFS
+        default: &'static str,
+    },
+}
+
+impl fmt::Display for Adaptation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Adaptation::RenamedFile { old, new } => write!(f, "{} renamed to {}", old, new),
+            Adaptation::RenamedColumn { file, old, new } => {
+                write!(f, "column {} of {} renamed to {}", old, file, new)
+            }
+            Adaptation::AddedColumn {
+                file,
+                column,
+                default,
+            } => write!(f, "column {} added to {} with {:?}", column, file, default),
+        }
+    }
+}
+
+/// A change of the specification, undone when reading an older NTFS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpecificationChange {
+    /// First version of the specification with the change
+    pub version: NtfsVersion,
+    /// The change
+    pub adaptation: Adaptation,
+}
+
+const fn change(major: u32, minor: u32, adaptation: Adaptation) -> SpecificationChange {
+    SpecificationChange {
+        version: NtfsVersion {
+            major,
+            minor,
+            patch: 0,
+        },
+        adaptation,
+    }
+}
+
+/// The changes of the specification adapted when reading an older NTFS, as
+/// listed for each version in the
+/// [NTFS changelog](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_changelog_fr.md)
+pub const SPECIFICATION_CHANGES: &[SpecificationChange] = &[
+    // NTFS changelog, version 0.7: codes.txt renamed to object_codes.txt
+    change(
+        0,
+        7,
+        Adaptation::RenamedFile {
+            old: "codes.txt",
+            new: "object_codes.txt",
+        },
+    ),
+    // NTFS changelog, version 0.7: stop_times.date_time_estimated renamed to
+    // datetime_estimated
+    change(
+        0,
+        7,
+        Adaptation::RenamedColumn {
+            file: "stop_times.txt",
+            old: "date_time_estimated",
+            new: "datetime_estimated",
+        },
+    ),
+];
+
+/// Version of the NTFS in `path`, from the `ntfs_version` of
+/// `feed_infos.txt`, or `None` if it's not given
+pub fn detect_version(path: &path::Path) -> Result<Option<NtfsVersion>> {
+    let file_path = path.join("feed_infos.txt");
+    if !file_path.exists() {
+        return Ok(None);
+    }
+    let (param, value) = match (
+        column_position(&file_path, "feed_info_param")?,
+        column_position(&file_path, "feed_info_value")?,
+    ) {
+        (Some(param), Some(value)) => (param, value),
+        _ => return Ok(None),
+    };
+    let mut rdr = csv::Reader::from_path(&file_path)
+        .with_context(|_| format!("Error reading {:?}", file_path))?;
+    for record in rdr.records() {
+        let record = record.with_context(|_| format!("Error reading {:?}", file_path))?;
+        if record.get(param).map(str::trim) == Some("ntfs_version") {
+            let version = record.get(value).unwrap_or("").parse()?;
+            return Ok(Some(version));
+        }
+    }
+    Ok(None)
+}
+
+// Rewrites the CSV file with `update` applied to its header (with `true`)
+// and its rows (with `false`). The rows are streamed to a new file replacing
+// `file_path`, so a file linked to the original NTFS is never written
+fn rewrite_file<F>(file_path: &path::Path, update: F) -> Result<()>
+where
+    F: Fn(&mut Vec<String>, bool),
+{
+    let mut rdr = csv::Reader::from_path(file_path)
+        .with_context(|_| format!("Error reading {:?}", file_path))?;
+    let mut headers: Vec<String> = rdr
+        .headers()
+        .with_context(|_| format!("Error reading {:?}", file_path))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    update(&mut headers, true);
+
+    let new_path = file_path.with_extension("adapted");
+    let mut wtr = csv::Writer::from_path(&new_path)
+        .with_context(|_| format!("Error writing {:?}", new_path))?;
+    wtr.write_record(&headers)
+        .with_context(|_| format!("Error writing {:?}", new_path))?;
+    for record in rdr.records() {
+        let record = record.with_context(|_| format!("Error reading {:?}", file_path))?;
+        let mut row: Vec<String> = record.iter().map(str::to_string).collect();
+        update(&mut row, false);
+        wtr.write_record(&row)
+            .with_context(|_| format!("Error writing {:?}", new_path))?;
+    }
+    wtr.flush()
+        .with_context(|_| format!("Error writing {:?}", new_path))?;
+    fs::rename(&new_path, file_path).with_context(|_| format!("Error writing {:?}", file_path))?;
+    Ok(())
+}
+
+fn apply(path: &path::Path, adaptation: Adaptation) -> Result<bool> {
+    match adaptation {
+        Adaptation::RenamedFile { old, new } => {
+            if !path.join(old).exists() || path.join(new).exists() {
+                return Ok(false);
+            }
+            fs::rename(path.join(old), path.join(new))
+                .with_context(|_| format!("Error renaming {:?} to {:?}", old, new))?;
+        }
+        Adaptation::RenamedColumn { file, old, new } => {
+            let file_path = path.join(file);
+            if !file_path.exists() || column_position(&file_path, old)?.is_none() {
+                return Ok(false);
+            }
+            rewrite_file(&file_path, |row, is_header| {
+                if is_header {
+                    for column in row.iter_mut().filter(|column| column.as_str() == old) {
+                        *column = new.to_string();
+                    }
+                }
+            })?;
+        }
+        Adaptation::AddedColumn {
+            file,
+            column,
+            default,
+        } => {
+            let file_path = path.join(file);
+            if !file_path.exists() || column_position(&file_path, column)?.is_some() {
+                return Ok(false);
+            }
+            rewrite_file(&file_path, |row, is_header| {
+                row.push(if is_header { column } else { default }.to_string())
+            })?;
+        }
+    }
+    Ok(true)
+}
+
+fn column_position(file_path: &path::Path, column: &str) -> Result<Option<usize>> {
+    let mut rdr = csv::Reader::from_path(file_path)
+        .with_context(|_| format!("Error reading {:?}", file_path))?;
+    let headers = rdr
+        .headers()
+        .with_context(|_| format!("Error reading {:?}", file_path))?;
+    Ok(headers.iter().position(|header| header.trim() == column))
+}
+
+/// Adapts the files of the NTFS of `path` with the `changes` made to the
+/// specification since its version, in a temporary directory: the files are
+/// hard linked there (or copied when they can't be linked, e.g. on another
+/// file system) and the adapted ones are streamed to new files, the NTFS of
+/// `path` being untouched.
+///
+/// The detected version is added to the `report`, with the adaptations
+/// applied. Returns `None` when the NTFS needs no adaptation: its version is
+/// unknown, or none of the `changes` happened after it.
+pub fn adapt_to_current_version(
+    path: &path::Path,
+    changes: &[SpecificationChange],
+    report: &mut Report,
+) -> Result<Option<TempDir>> {
+    let version = match detect_version(path) {
+        Ok(Some(version)) => version,
+        Err(e) => {
+            report.add_warning(
+                format!("{}, read as a NTFS {}", e, NTFS_VERSION),
+                ReportType::SpecificationVersion,
+            );
+            return Ok(None);
+        }
+        Ok(None) => {
+            report.add_warning(
+                format!(
+                    "no ntfs_version in feed_infos.txt, read as a NTFS {}",
+                    NTFS_VERSION
+                ),
+                ReportType::SpecificationVersion,
+            );
+            return Ok(None);
+        }
+    };
+    report.add_info(
+        format!("NTFS version {} detected", version),
+        ReportType::SpecificationVersion,
+    );
+    if version > NtfsVersion::current() {
+        report.add_warning(
+            format!(
+                "NTFS version {} is newer than the supported version {}",
+                version, NTFS_VERSION
+            ),
+            ReportType::SpecificationVersion,
+        );
+    }
+    let changes: Vec<&SpecificationChange> = changes
+        .iter()
+        .filter(|change| change.version > version)
+        .collect();
+    if changes.is_empty() {
+        return Ok(None);
+    }
+
+    info!(
+        "Adapting the NTFS {} to the version {}",
+        version, NTFS_VERSION
+    );
+    let tmp_dir = tempfile::tempdir()?;
+    for entry in fs::read_dir(path).with_context(|_| format!("Error reading {:?}", path))? {
+        let entry = entry.with_context(|_| format!("Error reading {:?}", path))?;
+        if entry.path().is_file() {
+            let linked_path = tmp_dir.path().join(entry.file_name());
+            if fs::hard_link(entry.path(), &linked_path).is_err() {
+                fs::copy(entry.path(), &linked_path)
+                    .with_context(|_| format!("Error writing {:?}", linked_path))?;
+            }
+        }
+    }
+    for change in changes {
+        if apply(tmp_dir.path(), change.adaptation)? {
+            report.add_info(
+                format!(
+                    "adapted to the NTFS {}: {}",
+                    change.version, change.adaptation
+                ),
+                ReportType::SpecificationVersion,
+            );
+        }
+    }
+    Ok(Some(tmp_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{report::Severity, test_utils::*};
+    use pretty_assertions::assert_eq;
+
+    fn version(major: u32, minor: u32, patch: u32) -> NtfsVersion {
+        NtfsVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(version(0, 11, 2), "0.11.2".parse().unwrap());
+        assert_eq!(version(0, 6, 0), "v0.6".parse().unwrap());
+        assert!("0.6.a".parse::<NtfsVersion>().is_err());
+        assert!("1".parse::<NtfsVersion>().is_err());
+        assert!(version(0, 6, 0) < version(0, 10, 0));
+        assert_eq!("0.11.2", NtfsVersion::current().to_string());
+    }
+
+    #[test]
+    fn detect_the_version_in_feed_infos() {
+        test_in_tmp_dir(|path| {
+            assert_eq!(None, detect_version(path).unwrap());
+            create_file_with_content(
+                path,
+                "feed_infos.txt",
+                "feed_info_param,feed_info_value\nntfs_version,0.6\n",
+            );
+            assert_eq!(Some(version(0, 6, 0)), detect_version(path).unwrap());
+        });
+    }
+
+    #[test]
+    fn adapt_an_older_ntfs() {
+        test_in_tmp_dir(|path| {
+            create_file_with_content(
+                path,
+                "feed_infos.txt",
+                "feed_info_param,feed_info_value\nntfs_version,0.6\n",
+            );
+            create_file_with_content(path, "codes.txt", "object_type,object_id\nline,l1\n");
+            create_file_with_content(
+                path,
+                "stops.txt",
+                "stop_id,stop_name\nsp1,Stop 1\nsp2,Stop 2\n",
+            );
+            let changes = [
+                change(
+                    0,
+                    7,
+                    Adaptation::RenamedFile {
+                        old: "codes.txt",
+                        new: "object_codes.txt",
+                    },
+                ),
+                change(
+                    0,
+                    7,
+                    Adaptation::RenamedColumn {
+                        file: "stops.txt",
+                        old: "stop_name",
+                        new: "name",
+                    },
+                ),
+                change(
+                    0,
+                    7,
+                    Adaptation::AddedColumn {
+                        file: "stops.txt",
+                        column: "location_type",
+                        default: "0",
+                    },
+                ),
+                change(
+                    0,
+                    7,
+                    Adaptation::AddedColumn {
+                        file: "trips.txt",
+                        column: "company_id",
+                        default: "default_company",
+                    },
+                ),
+                change(
+                    0,
+                    5,
+                    Adaptation::RenamedFile {
+                        old: "stops.txt",
+                        new: "stop_points.txt",
+                    },
+                ),
+            ];
+            let mut report = Report::default();
+            let tmp_dir = adapt_to_current_version(path, &changes, &mut report)
+                .unwrap()
+                .unwrap();
+            let adapted = tmp_dir.path();
+            assert!(!adapted.join("codes.txt").exists());
+            assert!(adapted.join("object_codes.txt").exists());
+            assert_eq!(
+                "stop_id,name,location_type\nsp1,Stop 1,0\nsp2,Stop 2,0\n",
+                fs::read_to_string(adapted.join("stops.txt")).unwrap()
+            );
+            // the original NTFS is untouched
+            assert!(path.join("codes.txt").exists());
+            assert_eq!(
+                "stop_id,stop_name\nsp1,Stop 1\nsp2,Stop 2\n",
+                fs::read_to_string(path.join("stops.txt")).unwrap()
+            );
+            // the version and the 3 adaptations of existing files
+            assert_eq!(4, report.entries(Severity::Info).len());
+            assert_eq!(
+                "NTFS version 0.6.0 detected",
+                report.entries(Severity::Info)[0].message
+            );
+        });
+    }
+
+    #[test]
+    fn no_adaptation_of_a_current_ntfs() {
+        test_in_tmp_dir(|path| {
+            create_file_with_content(
+                path,
+                "feed_infos.txt",
+                "feed_info_param,feed_info_value\nntfs_version,0.11.2\n",
+            );
+            let mut report = Report::default();
+            assert!(
+                adapt_to_current_version(path, SPECIFICATION_CHANGES, &mut report)
+                    .unwrap()
+                    .is_none()
+            );
+            assert_eq!(1, report.entries(Severity::Info).len());
+            assert!(report.entries(Severity::Warning).is_empty());
+        });
+    }
+}
@@ -16,9 +16,9 @@ use super::{Code, CommentLink, ObjectProperty, Result, Stop, StopLocationType, S
 use crate::model::Collections;
 use crate::ntfs::{has_fares_v1, has_fares_v2};
 use crate::objects::*;
+use crate::utils::CsvWriterSkippingColumns;
 use crate::NTFS_VERSION;
 use chrono::{DateTime, Duration, FixedOffset};
-use csv::Writer;
 use failure::{bail, format_err, ResultExt};
 use log::{info, warn};
 use rust_decimal::{prelude::ToPrimitive, Decimal};
@@ -27,6 +27,10 @@ use std::fs::File;
 use std::path;
 use typed_index_collection::{Collection, CollectionWithId, Id};
 
+// The feed infos derived from the data are computed again, replacing the ones
+// of the collections: the production period from the calendars, the creation
+// date and time from `current_datetime` and the number of stop areas, stop
+// points and lines
 pub fn write_feed_infos(
     path: &path::Path,
     collections: &Collections,
@@ -35,28 +39,32 @@ pub fn write_feed_infos(
     info!("Writing feed_infos.txt");
     let path = path.join("feed_infos.txt");
     let mut feed_infos = collections.feed_infos.clone();
-    feed_infos.insert(
-        "feed_creation_date".to_string(),
+    let mut fill = |key: &str, value: String| {
+        feed_infos.insert(key.to_string(), value);
+    };
+    fill(
+        "feed_creation_date",
         current_datetime.format("%Y%m%d").to_string(),
     );
-    feed_infos.insert(
-        "feed_creation_time".to_string(),
+    fill(
+        "feed_creation_time",
         current_datetime.format("%T").to_string(),
     );
-    feed_infos.insert(
-        "feed_creation_datetime".to_string(),
-        current_datetime.to_rfc3339(),
-    );
-    feed_infos.insert("ntfs_version".to_string(), NTFS_VERSION.to_string());
-    let (start_date, end_date) = collections.calculate_validity_period()?;
-    feed_infos.insert(
-        "feed_start_date".to_string(),
-        start_date.format("%Y%m%d").to_string(),
+    fill("feed_creation_datetime", current_datetime.to_rfc3339());
+    fill(
+        "feed_nb_stop_areas",
+        collections.stop_areas.len().to_string(),
     );
-    feed_infos.insert(
-        "feed_end_date".to_string(),
-        end_date.format("%Y%m%d").to_string(),
+    fill(
+        "feed_nb_stop_points",
+        collections.stop_points.len().to_string(),
     );
+    fill("feed_nb_lines", collections.lines.len().to_string());
+    let (start_date, end_date) = collections.calculate_validity_period()?;
+    fill("feed_start_date", start_date.format("%Y%m%d").to_string());
+    fill("feed_end_date", end_date.format("%Y%m%d").to_string());
+    // the version is the one of the NTFS written
+    feed_infos.insert("ntfs_version".to_string(), NTFS_VERSION.to_string());
 
     let mut wtr =
         csv::Writer::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
@@ -77,48 +85,31 @@ pub fn write_vehicle_journeys_and_stop_times(
     stop_points: &CollectionWithId<StopPoint>,
     stop_time_headsigns: &HashMap<(String, u32), String>,
     stop_time_ids: &HashMap<(String, u32), String>,
+    stop_time_continuous_stoppings: &HashMap<(String, u32), ContinuousStopping>,
+    skipped_trip_columns: &[&str],
 ) -> Result<()> {
     info!("Writing trips.txt and stop_times.txt");
     let trip_path = path.join("trips.txt");
     let stop_times_path = path.join("stop_times.txt");
-    let mut vj_wtr = csv::Writer::from_path(&trip_path)
-        .with_context(|_| format!("Error reading {:?}", trip_path))?;
+    let vj_wtr = csv::Writer::from_path(&trip_path)
+        .with_context(|_| format!("Error writing {:?}", trip_path))?;
+    let mut vj_wtr = CsvWriterSkippingColumns::new(vj_wtr, skipped_trip_columns);
     let mut st_wtr = csv::Writer::from_path(&stop_times_path)
         .with_context(|_| format!("Error reading {:?}", stop_times_path))?;
-    for (vj_idx, vj) in vehicle_journeys.iter() {
+    for vj in vehicle_journeys.values() {
         vj_wtr
             .serialize(vj)
             .with_context(|_| format!("Error reading {:?}", trip_path))?;
 
-        for st in &vj.stop_times {
-            let precision = st.precision.clone().or_else(|| {
-                if st.datetime_estimated {
-                    Some(StopTimePrecision::Estimated)
-                } else {
-                    Some(StopTimePrecision::Exact)
-                }
-            });
+        for stop_time in make_stop_times(
+            vj,
+            stop_points,
+            stop_time_headsigns,
+            stop_time_ids,
+            stop_time_continuous_stoppings,
+        ) {
             st_wtr
-                .serialize(StopTime {
-                    stop_id: stop_points[st.stop_point_idx].id.clone(),
-                    trip_id: vj.id.clone(),
-                    stop_sequence: st.sequence,
-                    arrival_time: st.arrival_time,
-                    departure_time: st.departure_time,
-                    boarding_duration: st.boarding_duration,
-                    alighting_duration: st.alighting_duration,
-                    pickup_type: st.pickup_type,
-                    drop_off_type: st.drop_off_type,
-                    datetime_estimated: Some(st.datetime_estimated as u8),
-                    local_zone_id: st.local_zone_id,
-                    stop_headsign: stop_time_headsigns
-                        .get(&(vehicle_journeys[vj_idx].id.clone(), st.sequence))
-                        .cloned(),
-                    stop_time_id: stop_time_ids
-                        .get(&(vehicle_journeys[vj_idx].id.clone(), st.sequence))
-                        .cloned(),
-                    precision,
-                })
+                .serialize(stop_time)
                 .with_context(|_| format!("Error reading {:?}", st_wtr))?;
         }
     }
@@ -132,6 +123,45 @@ pub fn write_vehicle_journeys_and_stop_times(
     Ok(())
 }
 
+// The NTFS stop times of the vehicle journey `vj`
+pub(crate) fn make_stop_times<'a>(
+    vj: &'a VehicleJourney,
+    stop_points: &'a CollectionWithId<StopPoint>,
+    stop_time_headsigns: &'a HashMap<(String, u32), String>,
+    stop_time_ids: &'a HashMap<(String, u32), String>,
+    stop_time_continuous_stoppings: &'a HashMap<(String, u32), ContinuousStopping>,
+) -> impl Iterator<Item = StopTime> + 'a {
+    vj.stop_times.iter().map(move |st| {
+        let precision = st.precision.clone().or_else(|| {
+            if st.datetime_estimated {
+                Some(StopTimePrecision::Estimated)
+            } else {
+                Some(StopTimePrecision::Exact)
+            }
+        });
+        let key = (vj.id.clone(), st.sequence);
+        let continuous_stopping = stop_time_continuous_stoppings.get(&key);
+        StopTime {
+            stop_id: stop_points[st.stop_point_idx].id.clone(),
+            trip_id: vj.id.clone(),
+            stop_sequence: st.sequence,
+            arrival_time: st.arrival_time,
+            departure_time: st.departure_time,
+            boarding_duration: st.boarding_duration,
+            alighting_duration: st.alighting_duration,
+            pickup_type: st.pickup_type,
+            drop_off_type: st.drop_off_type,
+            datetime_estimated: Some(st.datetime_estimated as u8),
+            local_zone_id: st.local_zone_id,
+            stop_headsign: stop_time_headsigns.get(&key).cloned(),
+            stop_time_id: stop_time_ids.get(&key).cloned(),
+            precision,
+            continuous_pickup: continuous_stopping.map(|continuous| continuous.pickup),
+            continuous_drop_off: continuous_stopping.map(|continuous| continuous.drop_off),
+        }
+    })
+}
+
 fn do_write_fares_v1(
     base_path: &path::Path,
     prices_v1: &Collection<PriceV1>,
@@ -340,7 +370,7 @@ fn construct_fare_v1_from_v2(fares: &Fares) -> Result<(BTreeSet<PriceV1>, BTreeS
             // - fare v1 needs prices to be all in the same currency
             // - if we want to support several currencies, we would need to have access to currency exchange rates here
             //   and it's unclear how to provide this information (which evolves over time)
-            if price.currency != "EUR" {
+            if price.currency.code() != "EUR" {
                 warn!(
                     "The price {:?} is ignored as it has an unsupported currency : {}. \
                      Only EUR currency supported in conversion from fare v2 to fare v1.",
@@ -527,9 +557,10 @@ pub fn write_stops(
     stop_points: &CollectionWithId<StopPoint>,
     stop_areas: &CollectionWithId<StopArea>,
     stop_locations: &CollectionWithId<StopLocation>,
+    skipped_columns: &[&str],
 ) -> Result<()> {
     fn write_stop_locations(
-        wtr: &mut Writer<File>,
+        wtr: &mut CsvWriterSkippingColumns<File>,
         stop_locations: &CollectionWithId<StopLocation>,
     ) -> Result<()> {
         for sl in stop_locations.values() {
@@ -556,8 +587,9 @@ pub fn write_stops(
     let file = "stops.txt";
     info!("Writing {}", file);
     let path = path.join(file);
-    let mut wtr =
-        csv::Writer::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
+    let wtr =
+        csv::Writer::from_path(&path).with_context(|_| format!("Error writing {:?}", path))?;
+    let mut wtr = CsvWriterSkippingColumns::new(wtr, skipped_columns);
     for st in stop_points.values() {
         let location_type = if st.stop_type == StopType::Zone {
             StopLocationType::GeographicArea
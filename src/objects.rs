@@ -16,21 +16,25 @@
 
 #![allow(missing_docs)]
 
-use crate::{utils::*, AddPrefix, PrefixConfiguration};
-use chrono::NaiveDate;
+use crate::{
+    distances::{haversine_distance, EARTH_RADIUS},
+    utils::*,
+    AddPrefix, PrefixConfiguration,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use chrono_tz::Tz;
 use derivative::Derivative;
 use geo::{Geometry as GeoGeometry, Point as GeoPoint};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::hash::{Hash, Hasher};
 use std::ops::{Add, Div, Rem, Sub};
 use std::str::FromStr;
 use typed_index_collection::{impl_id, impl_with_id, Idx, WithId};
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum ObjectType {
     StopArea,
@@ -68,6 +72,10 @@ impl ObjectType {
 // We use a BTreeSet<(String,String)> because Hash{Map,Set} are memory costy.
 pub type KeysValues = BTreeSet<(String, String)>;
 
+// Values of the columns unknown to the model:
+// BTreeMap<file_name, BTreeMap<object_id, BTreeMap<column, value>>>
+pub type ExtraColumns = BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>>;
+
 pub trait Codes {
     fn codes(&self) -> &KeysValues;
     fn codes_mut(&mut self) -> &mut KeysValues;
@@ -132,7 +140,7 @@ macro_rules! impl_comment_links {
     };
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Contributor {
     #[serde(rename = "contributor_id")]
     pub id: String,
@@ -164,7 +172,7 @@ impl Default for Contributor {
 impl_with_id!(Contributor);
 impl_id!(Contributor);
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum DatasetType {
     #[serde(rename = "0")]
     Theorical,
@@ -195,7 +203,7 @@ impl Default for ValidityPeriod {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Dataset {
     #[serde(rename = "dataset_id")]
     pub id: String,
@@ -277,7 +285,7 @@ impl WithId for Dataset {
 }
 
 #[derivative(Default)]
-#[derive(Derivative, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Derivative, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CommercialMode {
     #[derivative(Default(value = "\"default_commercial_mode\".into()"))]
     #[serde(rename = "commercial_mode_id")]
@@ -356,6 +364,10 @@ pub struct Network {
     pub address: Option<String>,
     #[serde(rename = "network_sort_order")]
     pub sort_order: Option<u32>,
+    #[serde(rename = "network_fare_url")]
+    pub fare_url: Option<String>,
+    #[serde(rename = "network_email")]
+    pub email: Option<String>,
 }
 
 impl_id!(Network);
@@ -592,6 +604,68 @@ impl GetObjectType for Route {
     }
 }
 
+/// A labeled variant of a route (e.g. a summer route or a detour) and the
+/// period when it runs, stored in the object properties of the route
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteVariant {
+    pub label: String,
+    pub start_date: Date,
+    pub end_date: Date,
+}
+
+impl RouteVariant {
+    pub const LABEL_PROPERTY: &'static str = "variant_label";
+    pub const START_DATE_PROPERTY: &'static str = "variant_start_date";
+    pub const END_DATE_PROPERTY: &'static str = "variant_end_date";
+
+    /// The variant of the `route`, if its object properties describe one
+    pub fn of_route(route: &Route) -> Option<Self> {
+        let property = |name: &str| {
+            route
+                .object_properties
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let date = |name: &str| {
+            property(name).and_then(|value| NaiveDate::parse_from_str(value, "%Y%m%d").ok())
+        };
+        Some(RouteVariant {
+            label: property(Self::LABEL_PROPERTY)?.to_string(),
+            start_date: date(Self::START_DATE_PROPERTY)?,
+            end_date: date(Self::END_DATE_PROPERTY)?,
+        })
+    }
+
+    /// The object properties describing the variant, dates being written
+    /// `YYYYMMDD`
+    pub fn properties(&self) -> Vec<(String, String)> {
+        vec![
+            (Self::LABEL_PROPERTY.to_string(), self.label.clone()),
+            (
+                Self::START_DATE_PROPERTY.to_string(),
+                self.start_date.format("%Y%m%d").to_string(),
+            ),
+            (
+                Self::END_DATE_PROPERTY.to_string(),
+                self.end_date.format("%Y%m%d").to_string(),
+            ),
+        ]
+    }
+
+    /// Labels the `route` with the variant, replacing its previous variant
+    pub fn set_on_route(&self, route: &mut Route) {
+        let properties = self.properties();
+        route.object_properties = route
+            .object_properties
+            .iter()
+            .filter(|(key, _)| !properties.iter().any(|(name, _)| name == key))
+            .cloned()
+            .chain(properties)
+            .collect();
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct VehicleJourney {
     #[serde(rename = "trip_id")]
@@ -617,6 +691,9 @@ pub struct VehicleJourney {
     #[serde(skip)]
     pub stop_times: Vec<StopTime>,
     pub journey_pattern_id: Option<String>,
+    // in meters, see Collections::compute_vehicle_journey_lengths
+    #[serde(skip)]
+    pub length: Option<f64>,
 }
 impl Default for VehicleJourney {
     fn default() -> VehicleJourney {
@@ -637,9 +714,42 @@ impl Default for VehicleJourney {
             geometry_id: None,
             stop_times: vec![],
             journey_pattern_id: None,
+            length: None,
         }
     }
 }
+impl VehicleJourney {
+    /// Headsign of the vehicle journey at its stop time of sequence
+    /// `stop_sequence`, when the headsign changes along the trip: the last
+    /// of the `stop_time_headsigns` (see `Collections::stop_time_headsigns`)
+    /// given at or before this stop time, or else the headsign of the
+    /// vehicle journey.
+    pub fn headsign_at<'a>(
+        &'a self,
+        stop_sequence: u32,
+        stop_time_headsigns: &'a HashMap<(String, u32), String>,
+    ) -> Option<&'a str> {
+        if stop_time_headsigns.is_empty() {
+            return self.headsign.as_deref();
+        }
+        // the identifier is copied once in the key of all the lookups
+        let mut key = (self.id.clone(), 0);
+        self.stop_times
+            .iter()
+            .filter(|stop_time| stop_time.sequence <= stop_sequence)
+            .filter_map(|stop_time| {
+                key.1 = stop_time.sequence;
+                stop_time_headsigns
+                    .get(&key)
+                    .map(|headsign| (stop_time.sequence, headsign))
+            })
+            .max_by_key(|(sequence, _)| *sequence)
+            .map(|(_, headsign)| headsign)
+            .or_else(|| self.headsign.as_ref())
+            .map(String::as_str)
+    }
+}
+
 impl_id!(VehicleJourney);
 impl_id!(VehicleJourney, Route, route_id);
 impl_id!(VehicleJourney, PhysicalMode, physical_mode_id);
@@ -662,6 +772,10 @@ impl AddPrefix for VehicleJourney {
             .geometry_id
             .take()
             .map(|id| prefix_conf.schedule_prefix(id.as_str()));
+        self.journey_pattern_id = self
+            .journey_pattern_id
+            .take()
+            .map(|id| prefix_conf.schedule_prefix(id.as_str()));
         self.comment_links.prefix(prefix_conf);
     }
 }
@@ -683,7 +797,7 @@ impl GetObjectType for VehicleJourney {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Frequency {
     #[serde(rename = "trip_id")]
     pub vehicle_journey_id: String,
@@ -725,6 +839,11 @@ impl Error for TimeError {
     }
 }
 
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Time of a stop time, relative to the beginning of the day of the
+/// service, so it may be after `24:00:00` (e.g. `49:30:00` for a night train
+/// running over 3 days)
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Time(u32);
 impl Time {
@@ -743,6 +862,14 @@ impl Time {
     pub fn total_seconds(self) -> u32 {
         self.0
     }
+    /// Number of days after the day of the service (`1` for `25:10:00`)
+    pub fn day_offset(self) -> u32 {
+        self.0 / SECONDS_PER_DAY
+    }
+    /// Time in the day of the offset (`01:10:00` for `25:10:00`)
+    pub fn time_of_day(self) -> Time {
+        Time(self.0 % SECONDS_PER_DAY)
+    }
 }
 impl Add for Time {
     type Output = Time;
@@ -846,6 +973,17 @@ pub struct StopTime {
     pub precision: Option<StopTimePrecision>,
 }
 
+impl StopTime {
+    /// Number of days after the day of the service of the arrival
+    pub fn arrival_day_offset(&self) -> u32 {
+        self.arrival_time.day_offset()
+    }
+    /// Number of days after the day of the service of the departure
+    pub fn departure_day_offset(&self) -> u32 {
+        self.departure_time.day_offset()
+    }
+}
+
 impl Ord for StopTime {
     fn cmp(&self, other: &StopTime) -> Ordering {
         self.sequence.cmp(&other.sequence)
@@ -864,7 +1002,7 @@ impl GetObjectType for StopTime {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
 pub enum StopTimePrecision {
     #[serde(rename = "0")]
     Exact,
@@ -874,6 +1012,60 @@ pub enum StopTimePrecision {
     Estimated,
 }
 
+/// Availability of a continuous pickup or drop off, the values of the GTFS
+/// `continuous_pickup` and `continuous_drop_off`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContinuousStoppingType {
+    /// Continuous stopping
+    #[serde(rename = "0")]
+    Continuous,
+    /// No continuous stopping
+    #[serde(rename = "1")]
+    NotContinuous,
+    /// Phone the agency to arrange a continuous stopping
+    #[serde(rename = "2")]
+    PhoneAgency,
+    /// Coordinate with the driver to arrange a continuous stopping
+    #[serde(rename = "3")]
+    CoordinateWithDriver,
+}
+
+/// Continuous stopping from a stop time to the next one of its vehicle
+/// journey, e.g. a bus picking up or dropping off passengers anywhere along
+/// its path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContinuousStopping {
+    pub pickup: ContinuousStoppingType,
+    pub drop_off: ContinuousStoppingType,
+}
+
+impl Default for ContinuousStopping {
+    fn default() -> Self {
+        ContinuousStopping {
+            pickup: ContinuousStoppingType::NotContinuous,
+            drop_off: ContinuousStoppingType::NotContinuous,
+        }
+    }
+}
+
+impl ContinuousStopping {
+    /// The continuous stopping of the optional `pickup` and `drop_off`
+    /// values, `None` if both are missing
+    pub fn from_values(
+        pickup: Option<ContinuousStoppingType>,
+        drop_off: Option<ContinuousStoppingType>,
+    ) -> Option<Self> {
+        if pickup.is_none() && drop_off.is_none() {
+            return None;
+        }
+        let default = ContinuousStopping::default();
+        Some(ContinuousStopping {
+            pickup: pickup.unwrap_or(default.pickup),
+            drop_off: drop_off.unwrap_or(default.drop_off),
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
 pub struct Coord {
     pub lon: f64,
@@ -914,9 +1106,6 @@ impl From<Coord> for (String, String) {
     }
 }
 
-// Mean Earth radius in meters
-const EARTH_RADIUS: f64 = 6_371_000.0;
-
 impl From<GeoPoint<f64>> for Coord {
     fn from(point: GeoPoint<f64>) -> Self {
         Coord {
@@ -936,15 +1125,7 @@ impl Coord {
     /// Calculate the orthodromic distance in meters
     /// between 2 geographic coordinates
     pub fn distance_to(&self, other: &Self) -> f64 {
-        let phi1 = self.lat.to_radians();
-        let phi2 = other.lat.to_radians();
-        let lambda1 = self.lon.to_radians();
-        let lambda2 = other.lon.to_radians();
-
-        let x = f64::sin((phi2 - phi1) / 2.).powi(2);
-        let y = f64::cos(phi1) * f64::cos(phi2) * f64::sin((lambda2 - lambda1) / 2.).powi(2);
-
-        2. * EARTH_RADIUS * f64::asin(f64::sqrt(x + y))
+        haversine_distance(self, other)
     }
 
     /// Returns a proxy object allowing to compute approximate
@@ -1127,6 +1308,10 @@ impl AddPrefix for StopPoint {
             .level_id
             .take()
             .map(|id| prefix_conf.referential_prefix(id.as_str()));
+        self.fare_zone_id = self
+            .fare_zone_id
+            .take()
+            .map(|id| prefix_conf.referential_prefix(id.as_str()));
         self.comment_links.prefix(prefix_conf);
     }
 }
@@ -1294,7 +1479,7 @@ impl WithId for Calendar {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Company {
     #[serde(rename = "company_id")]
     pub id: String,
@@ -1329,11 +1514,26 @@ impl AddPrefix for Company {
     }
 }
 
+/// Link of a company (e.g. an operator) to its parent company (e.g. the
+/// holding the operator belongs to)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CompanyParent {
+    pub company_id: String,
+    pub parent_company_id: String,
+}
+
+impl AddPrefix for CompanyParent {
+    fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
+        self.company_id = prefix_conf.referential_prefix(self.company_id.as_str());
+        self.parent_company_id = prefix_conf.referential_prefix(self.parent_company_id.as_str());
+    }
+}
+
 impl_with_id!(Company);
 
 #[derive(Derivative)]
 #[derivative(Default(bound = ""))]
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum CommentType {
     #[derivative(Default)]
@@ -1341,7 +1541,7 @@ pub enum CommentType {
     OnDemandTransport,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Comment {
     #[serde(rename = "comment_id")]
     pub id: String,
@@ -1430,7 +1630,9 @@ impl Equipment {
 pub struct Transfer {
     pub from_stop_id: String,
     pub to_stop_id: String,
+    #[serde(default, deserialize_with = "de_option_negative_as_none")]
     pub min_transfer_time: Option<u32>,
+    #[serde(default, deserialize_with = "de_option_negative_as_none")]
     pub real_min_transfer_time: Option<u32>,
     pub equipment_id: Option<String>,
 }
@@ -1446,7 +1648,7 @@ impl AddPrefix for Transfer {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Derivative, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Derivative, PartialEq, Eq, PartialOrd, Ord, Clone)]
 #[derivative(Default)]
 pub enum TransportType {
     #[derivative(Default)]
@@ -1501,7 +1703,7 @@ impl TripProperty {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Geometry {
     #[serde(rename = "geometry_id")]
     pub id: String,
@@ -1521,7 +1723,7 @@ impl AddPrefix for Geometry {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct AdminStation {
     pub admin_id: String,
     pub admin_name: String,
@@ -1535,7 +1737,129 @@ impl AddPrefix for AdminStation {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Ord, PartialOrd)]
+/// Membership of a stop point in a secondary stop area (e.g. an interchange
+/// grouping), in addition to its main stop area `stop_area_id`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StopAreaMembership {
+    pub stop_id: String,
+    pub stop_area_id: String,
+}
+
+impl AddPrefix for StopAreaMembership {
+    fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
+        self.stop_id = prefix_conf.referential_prefix(self.stop_id.as_str());
+        self.stop_area_id = prefix_conf.referential_prefix(self.stop_area_id.as_str());
+    }
+}
+
+/// Translation of a field (e.g. `stop_name`) of a stop area, a stop point, a
+/// line or a network in a language given as a BCP-47 tag
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Translation {
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub field_name: String,
+    pub language: String,
+    pub translation: String,
+}
+
+impl AddPrefix for Translation {
+    fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
+        self.object_id = prefix_conf.referential_prefix(self.object_id.as_str());
+    }
+}
+
+/// Booking conditions of an on-demand transport service
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default, Clone)]
+pub struct BookingRule {
+    #[serde(rename = "booking_rule_id")]
+    pub id: String,
+    pub name: Option<String>,
+    pub phone: Option<String>,
+    pub info_url: Option<String>,
+    pub booking_url: Option<String>,
+    pub message: Option<String>,
+    /// Minimum duration in minutes between the booking and the departure
+    pub deadline_duration: Option<u32>,
+    /// Number of days before the day of travel of the last possible booking,
+    /// at `deadline_time`
+    pub deadline_day: Option<u32>,
+    pub deadline_time: Option<Time>,
+}
+
+impl AddPrefix for BookingRule {
+    fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
+        self.id = prefix_conf.referential_prefix(self.id.as_str());
+    }
+}
+impl_id!(BookingRule);
+
+/// Booking rule of a line or a vehicle journey
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct BookingRuleLink {
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub booking_rule_id: String,
+}
+
+impl AddPrefix for BookingRuleLink {
+    fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
+        self.object_id = match self.object_type {
+            ObjectType::VehicleJourney => prefix_conf.schedule_prefix(self.object_id.as_str()),
+            _ => prefix_conf.referential_prefix(self.object_id.as_str()),
+        };
+        self.booking_rule_id = prefix_conf.referential_prefix(self.booking_rule_id.as_str());
+    }
+}
+
+/// A disruption of the service (a service alert), e.g. read from a SIRI-SX
+/// situation, impacting the objects of its
+/// [DisruptionImpact](struct.DisruptionImpact.html)s
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default, Clone)]
+pub struct Disruption {
+    #[serde(rename = "disruption_id")]
+    pub id: String,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub severity: Option<String>,
+    pub cause: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "de_option_datetime",
+        serialize_with = "ser_option_datetime"
+    )]
+    pub start_datetime: Option<DateTime<FixedOffset>>,
+    #[serde(
+        default,
+        deserialize_with = "de_option_datetime",
+        serialize_with = "ser_option_datetime"
+    )]
+    pub end_datetime: Option<DateTime<FixedOffset>>,
+}
+
+impl AddPrefix for Disruption {
+    fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
+        self.id = prefix_conf.referential_prefix(self.id.as_str());
+    }
+}
+impl_id!(Disruption);
+
+/// Network, line, stop area or stop point impacted by a disruption
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DisruptionImpact {
+    pub disruption_id: String,
+    pub object_type: ObjectType,
+    pub object_id: String,
+}
+
+impl AddPrefix for DisruptionImpact {
+    fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
+        self.disruption_id = prefix_conf.referential_prefix(self.disruption_id.as_str());
+        self.object_id = prefix_conf.referential_prefix(self.object_id.as_str());
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct PriceV1 {
     pub id: String,
     #[serde(
@@ -1561,7 +1885,7 @@ impl AddPrefix for PriceV1 {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ODFareV1 {
     #[serde(rename = "Origin ID")]
     pub origin_stop_area_id: String,
@@ -1588,7 +1912,7 @@ impl AddPrefix for ODFareV1 {
     }
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct FareV1 {
     #[serde(rename = "avant changement")]
     pub before_change: String,
@@ -1610,7 +1934,7 @@ impl AddPrefix for FareV1 {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Ticket {
     #[serde(rename = "ticket_id")]
     pub id: String,
@@ -1633,17 +1957,87 @@ impl AddPrefix for Ticket {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// A currency, identified by its ISO 4217 code (e.g. `EUR`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Currency(&'static str);
+
+impl Currency {
+    /// The 3-letters ISO 4217 code of the currency
+    pub fn code(&self) -> &'static str {
+        self.0
+    }
+}
+
+#[derive(Debug)]
+pub struct CurrencyError(String);
+
+impl std::fmt::Display for CurrencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unrecognized currency code (ISO 4217): {:?}", self.0)
+    }
+}
+
+impl std::error::Error for CurrencyError {}
+
+impl FromStr for Currency {
+    type Err = CurrencyError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        iso4217::alpha3(code)
+            .map(|currency_code| Currency(currency_code.alpha3))
+            .ok_or_else(|| CurrencyError(code.to_string()))
+    }
+}
+
+impl std::fmt::Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl ::serde::Serialize for Currency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> ::serde::Deserialize<'de> for Currency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let code = String::deserialize(deserializer)?;
+        Currency::from_str(&code).map_err(Error::custom)
+    }
+}
+
+/// An amount of money in a currency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Price {
+    pub amount: Decimal,
+    pub currency: Currency,
+}
+
+impl std::fmt::Display for Price {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TicketPrice {
     pub ticket_id: String,
     #[serde(rename = "ticket_price", deserialize_with = "de_positive_decimal")]
     pub price: Decimal,
-    #[serde(
-        rename = "ticket_currency",
-        serialize_with = "ser_currency_code",
-        deserialize_with = "de_currency_code"
-    )]
-    pub currency: String,
+    /// Since 0.32.0, a `Currency` rather than a `String`: a ticket price
+    /// can't hold an invalid ISO 4217 code anymore
+    #[serde(rename = "ticket_currency")]
+    pub currency: Currency,
     #[serde(
         deserialize_with = "de_from_date_string",
         serialize_with = "ser_from_naive_date"
@@ -1656,13 +2050,23 @@ pub struct TicketPrice {
     pub ticket_validity_end: Date,
 }
 
+impl TicketPrice {
+    /// The price of the ticket with its currency
+    pub fn ticket_price(&self) -> Price {
+        Price {
+            amount: self.price,
+            currency: self.currency,
+        }
+    }
+}
+
 impl AddPrefix for TicketPrice {
     fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
         self.ticket_id = prefix_conf.referential_prefix(self.ticket_id.as_str());
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TicketUse {
     #[serde(rename = "ticket_use_id")]
     pub id: String,
@@ -1680,7 +2084,7 @@ impl AddPrefix for TicketUse {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum PerimeterAction {
     #[serde(rename = "1")]
     Included,
@@ -1688,7 +2092,7 @@ pub enum PerimeterAction {
     Excluded,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TicketUsePerimeter {
     pub ticket_use_id: String,
     pub object_type: ObjectType,
@@ -1703,7 +2107,7 @@ impl AddPrefix for TicketUsePerimeter {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum RestrictionType {
     #[serde(rename = "zone")]
     Zone,
@@ -1711,7 +2115,7 @@ pub enum RestrictionType {
     OriginDestination,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TicketUseRestriction {
     pub ticket_use_id: String,
     pub restriction_type: RestrictionType,
@@ -1727,7 +2131,24 @@ impl AddPrefix for TicketUseRestriction {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A fare zone, referenced by the stop points and by the zone restrictions
+/// of the ticket uses
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default, Clone)]
+pub struct FareZone {
+    #[serde(rename = "fare_zone_id")]
+    pub id: String,
+    #[serde(rename = "fare_zone_name")]
+    pub name: Option<String>,
+}
+
+impl AddPrefix for FareZone {
+    fn prefix(&mut self, prefix_conf: &PrefixConfiguration) {
+        self.id = prefix_conf.referential_prefix(self.id.as_str());
+    }
+}
+impl_id!(FareZone);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GridCalendar {
     #[serde(rename = "grid_calendar_id")]
     pub id: String,
@@ -1755,7 +2176,7 @@ impl AddPrefix for GridCalendar {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GridExceptionDate {
     pub grid_calendar_id: String,
     #[serde(
@@ -1774,7 +2195,7 @@ impl AddPrefix for GridExceptionDate {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GridPeriod {
     pub grid_calendar_id: String,
     #[serde(
@@ -1796,7 +2217,7 @@ impl AddPrefix for GridPeriod {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GridRelCalendarLine {
     pub grid_calendar_id: String,
     pub line_id: String,
@@ -1818,6 +2239,61 @@ mod tests {
     use approx::assert_relative_eq;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn prefix_vehicle_journey_references() {
+        let mut prefix_conf = PrefixConfiguration::default();
+        prefix_conf.set_data_prefix("pre");
+        prefix_conf.set_schedule_subprefix("winter");
+        let mut vj = VehicleJourney {
+            id: "vj:1".to_string(),
+            journey_pattern_id: Some("jp:1".to_string()),
+            ..Default::default()
+        };
+        vj.prefix(&prefix_conf);
+        assert_eq!("pre:winter:vj:1", vj.id);
+        assert_eq!(Some("pre:winter:jp:1"), vj.journey_pattern_id.as_deref());
+    }
+
+    mod currency {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[derive(Debug, Serialize, Deserialize)]
+        struct CurrencyWrapper {
+            pub currency: Currency,
+        }
+
+        #[test]
+        fn serde_valid_currency() {
+            let wrapper = CurrencyWrapper {
+                currency: "EUR".parse().unwrap(),
+            };
+            let json = serde_json::to_string(&wrapper).unwrap();
+            assert_eq!("{\"currency\":\"EUR\"}", json);
+            let wrapper: CurrencyWrapper = serde_json::from_str(&json).unwrap();
+            assert_eq!("EUR", wrapper.currency.code());
+        }
+
+        #[test]
+        fn de_invalid_currency() {
+            let result: Result<CurrencyWrapper, _> = serde_json::from_str("{\"currency\":\"XXX\"}");
+            let err_msg = result.unwrap_err().to_string();
+            assert_eq!(
+                "unrecognized currency code (ISO 4217): \"XXX\" at line 1 column 17",
+                err_msg
+            );
+        }
+
+        #[test]
+        fn price_display() {
+            let price = Price {
+                amount: Decimal::new(150, 2),
+                currency: "EUR".parse().unwrap(),
+            };
+            assert_eq!("1.50 EUR", price.to_string());
+        }
+    }
+
     #[test]
     fn rgb_serialization() {
         let white = Rgb {
@@ -1891,6 +2367,39 @@ mod tests {
         assert_eq!("13:37:00", ser(13, 37, 0));
         assert_eq!("00:00:00", ser(0, 0, 0));
         assert_eq!("25:42:42", ser(25, 42, 42));
+        assert_eq!("49:30:00", ser(49, 30, 0));
+    }
+
+    #[test]
+    fn headsign_at() {
+        let model = crate::model_builder::ModelBuilder::default()
+            .vj("vj1", |vj| {
+                vj.st("A", "10:00")
+                    .st("B", "10:10")
+                    .st("C", "10:20")
+                    .st("D", "10:30");
+            })
+            .build();
+        let mut vj = model.vehicle_journeys.get("vj1").unwrap().clone();
+        vj.headsign = Some("Terminus".to_string());
+        let mut headsigns = HashMap::new();
+        headsigns.insert(("vj1".to_string(), 2), "Via Center".to_string());
+        headsigns.insert(("vj2".to_string(), 0), "Elsewhere".to_string());
+
+        assert_eq!(Some("Terminus"), vj.headsign_at(0, &headsigns));
+        assert_eq!(Some("Terminus"), vj.headsign_at(1, &headsigns));
+        assert_eq!(Some("Via Center"), vj.headsign_at(2, &headsigns));
+        assert_eq!(Some("Via Center"), vj.headsign_at(3, &headsigns));
+        assert_eq!(Some("Terminus"), vj.headsign_at(3, &HashMap::new()));
+    }
+
+    #[test]
+    fn time_day_offset() {
+        let time = Time::new(49, 30, 5);
+        assert_eq!(2, time.day_offset());
+        assert_eq!(Time::new(1, 30, 5), time.time_of_day());
+        assert_eq!(0, Time::new(23, 59, 59).day_offset());
+        assert_eq!(1, Time::new(24, 0, 0).day_offset());
     }
 
     #[test]
@@ -1900,6 +2409,7 @@ mod tests {
         assert_eq!(Time::new(13, 37, 0), de("13:37:00").unwrap());
         assert_eq!(Time::new(0, 0, 0), de("0:0:0").unwrap());
         assert_eq!(Time::new(25, 42, 42), de("25:42:42").unwrap());
+        assert_eq!(Time::new(72, 0, 1), de("72:00:01").unwrap());
 
         assert!(de("").is_err());
         assert!(de("13:37").is_err());
@@ -0,0 +1,244 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Export of the largest collections of a `Model` to
+//! [Apache Parquet](https://parquet.apache.org) files, for the analytics
+//! pipelines (e.g. Spark or DuckDB) scanning them much faster than CSV.
+//!
+//! The files follow the columns of their NTFS counterpart, with typed
+//! values:
+//! - `stops.parquet`: the stop areas (`location_type` 1) and stop points
+//!   (`location_type` 0)
+//! - `trips.parquet`: the vehicle journeys
+//! - `stop_times.parquet`: the stop times of the vehicle journeys, the times
+//!   being a number of seconds since midnight of the day of the service
+//!
+//! This module needs the `parquet-export` feature.
+
+use crate::{
+    model::Model,
+    objects::{Coord, VehicleJourney},
+    Result,
+};
+use arrow::{
+    array::{
+        ArrayRef, BooleanArray, Float64Array, StringArray, UInt16Array, UInt32Array, UInt8Array,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use failure::ResultExt;
+use log::info;
+use parquet::arrow::ArrowWriter;
+use std::{fs::File, path::Path, sync::Arc};
+
+// Number of stop times of each row group of `stop_times.parquet`
+const STOP_TIMES_BATCH_SIZE: usize = 1 << 20;
+
+fn string_array<'a, I: IntoIterator<Item = &'a str>>(values: I) -> ArrayRef {
+    Arc::new(StringArray::from(values.into_iter().collect::<Vec<_>>()))
+}
+
+fn opt_string_array<'a, I: IntoIterator<Item = Option<&'a str>>>(values: I) -> ArrayRef {
+    Arc::new(StringArray::from(values.into_iter().collect::<Vec<_>>()))
+}
+
+fn write_batches<I>(path: &Path, schema: SchemaRef, batches: I) -> Result<()>
+where
+    I: IntoIterator<Item = Vec<ArrayRef>>,
+{
+    info!("Writing {:?}", path);
+    let file = File::create(path).with_context(|_| format!("Error writing {:?}", path))?;
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    for columns in batches {
+        let batch = RecordBatch::try_new(schema.clone(), columns)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+        writer
+            .write(&batch)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    writer
+        .close()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
+fn write_stops(model: &Model, path: &Path) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("stop_id", DataType::Utf8, false),
+        Field::new("stop_name", DataType::Utf8, false),
+        Field::new("stop_lat", DataType::Float64, false),
+        Field::new("stop_lon", DataType::Float64, false),
+        Field::new("location_type", DataType::UInt8, false),
+        Field::new("parent_station", DataType::Utf8, true),
+        Field::new("fare_zone_id", DataType::Utf8, true),
+    ]));
+    // (id, name, coord, location_type, parent_station, fare_zone_id)
+    let stops: Vec<(&str, &str, &Coord, u8, Option<&str>, Option<&str>)> = model
+        .stop_areas
+        .values()
+        .map(|sa| (sa.id.as_str(), sa.name.as_str(), &sa.coord, 1, None, None))
+        .chain(model.stop_points.values().map(|sp| {
+            (
+                sp.id.as_str(),
+                sp.name.as_str(),
+                &sp.coord,
+                0,
+                Some(sp.stop_area_id.as_str()),
+                sp.fare_zone_id.as_deref(),
+            )
+        }))
+        .collect();
+    let columns: Vec<ArrayRef> = vec![
+        string_array(stops.iter().map(|stop| stop.0)),
+        string_array(stops.iter().map(|stop| stop.1)),
+        Arc::new(Float64Array::from(
+            stops.iter().map(|stop| stop.2.lat).collect::<Vec<_>>(),
+        )),
+        Arc::new(Float64Array::from(
+            stops.iter().map(|stop| stop.2.lon).collect::<Vec<_>>(),
+        )),
+        Arc::new(UInt8Array::from(
+            stops.iter().map(|stop| stop.3).collect::<Vec<_>>(),
+        )),
+        opt_string_array(stops.iter().map(|stop| stop.4)),
+        opt_string_array(stops.iter().map(|stop| stop.5)),
+    ];
+    write_batches(path, schema, vec![columns])
+}
+
+fn write_trips(model: &Model, path: &Path) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("trip_id", DataType::Utf8, false),
+        Field::new("route_id", DataType::Utf8, false),
+        Field::new("service_id", DataType::Utf8, false),
+        Field::new("company_id", DataType::Utf8, false),
+        Field::new("physical_mode_id", DataType::Utf8, false),
+        Field::new("dataset_id", DataType::Utf8, false),
+        Field::new("trip_headsign", DataType::Utf8, true),
+        Field::new("block_id", DataType::Utf8, true),
+        Field::new("trip_property_id", DataType::Utf8, true),
+        Field::new("geometry_id", DataType::Utf8, true),
+    ]));
+    let vjs: Vec<&VehicleJourney> = model.vehicle_journeys.values().collect();
+    let columns: Vec<ArrayRef> = vec![
+        string_array(vjs.iter().map(|vj| vj.id.as_str())),
+        string_array(vjs.iter().map(|vj| vj.route_id.as_str())),
+        string_array(vjs.iter().map(|vj| vj.service_id.as_str())),
+        string_array(vjs.iter().map(|vj| vj.company_id.as_str())),
+        string_array(vjs.iter().map(|vj| vj.physical_mode_id.as_str())),
+        string_array(vjs.iter().map(|vj| vj.dataset_id.as_str())),
+        opt_string_array(vjs.iter().map(|vj| vj.headsign.as_deref())),
+        opt_string_array(vjs.iter().map(|vj| vj.block_id.as_deref())),
+        opt_string_array(vjs.iter().map(|vj| vj.trip_property_id.as_deref())),
+        opt_string_array(vjs.iter().map(|vj| vj.geometry_id.as_deref())),
+    ];
+    write_batches(path, schema, vec![columns])
+}
+
+fn write_stop_times(model: &Model, path: &Path) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("trip_id", DataType::Utf8, false),
+        Field::new("stop_sequence", DataType::UInt32, false),
+        Field::new("stop_id", DataType::Utf8, false),
+        Field::new("arrival_time", DataType::UInt32, false),
+        Field::new("departure_time", DataType::UInt32, false),
+        Field::new("boarding_duration", DataType::UInt16, false),
+        Field::new("alighting_duration", DataType::UInt16, false),
+        Field::new("pickup_type", DataType::UInt8, false),
+        Field::new("drop_off_type", DataType::UInt8, false),
+        Field::new("datetime_estimated", DataType::Boolean, false),
+        Field::new("local_zone_id", DataType::UInt16, true),
+    ]));
+    let stop_times: Vec<_> = model
+        .vehicle_journeys
+        .values()
+        .flat_map(|vj| vj.stop_times.iter().map(move |st| (vj, st)))
+        .collect();
+    let batches = stop_times.chunks(STOP_TIMES_BATCH_SIZE).map(|chunk| {
+        let columns: Vec<ArrayRef> = vec![
+            string_array(chunk.iter().map(|(vj, _)| vj.id.as_str())),
+            Arc::new(UInt32Array::from(
+                chunk.iter().map(|(_, st)| st.sequence).collect::<Vec<_>>(),
+            )),
+            string_array(
+                chunk
+                    .iter()
+                    .map(|(_, st)| model.stop_points[st.stop_point_idx].id.as_str()),
+            ),
+            Arc::new(UInt32Array::from(
+                chunk
+                    .iter()
+                    .map(|(_, st)| st.arrival_time.total_seconds())
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt32Array::from(
+                chunk
+                    .iter()
+                    .map(|(_, st)| st.departure_time.total_seconds())
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt16Array::from(
+                chunk
+                    .iter()
+                    .map(|(_, st)| st.boarding_duration)
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt16Array::from(
+                chunk
+                    .iter()
+                    .map(|(_, st)| st.alighting_duration)
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt8Array::from(
+                chunk
+                    .iter()
+                    .map(|(_, st)| st.pickup_type)
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt8Array::from(
+                chunk
+                    .iter()
+                    .map(|(_, st)| st.drop_off_type)
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(BooleanArray::from(
+                chunk
+                    .iter()
+                    .map(|(_, st)| st.datetime_estimated)
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(UInt16Array::from(
+                chunk
+                    .iter()
+                    .map(|(_, st)| st.local_zone_id)
+                    .collect::<Vec<_>>(),
+            )),
+        ];
+        columns
+    });
+    write_batches(path, schema, batches)
+}
+
+/// Exports the stops, vehicle journeys and stop times of a `Model` to
+/// Parquet files in the given directory.
+pub fn write<P: AsRef<Path>>(model: &Model, path: P) -> Result<()> {
+    let path = path.as_ref();
+    info!("Writing Parquet files to {:?}", path);
+    write_stops(model, &path.join("stops.parquet"))?;
+    write_trips(model, &path.join("trips.parquet"))?;
+    write_stop_times(model, &path.join("stop_times.parquet"))?;
+    Ok(())
+}
@@ -0,0 +1,301 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Composable conversion pipelines, assembling in a library call the flows
+//! of the binaries (e.g. read a GTFS, generate the transfers, apply rules,
+//! merge fares and write a NTFS) with a single [Report](../report/struct.Report.html).
+//!
+//! Each step consumes the pipeline and returns it with the transformed
+//! model, the report of each step being merged in a section of the report
+//! of the pipeline (see [Report::merge_as](../report/struct.Report.html#method.merge_as)). The model is enhanced (headsigns, route names, etc.) each time
+//! it's built, as in the binaries.
+//!
+//! ```no_run
+//! # use transit_model::{
+//! #     apply_rules::Rules,
+//! #     fares::{FaresMode, FaresOptions},
+//! #     gtfs,
+//! #     pipeline::Pipeline,
+//! # };
+//! # fn run(configuration: gtfs::Configuration) -> transit_model::Result<()> {
+//! let pipeline = Pipeline::from_gtfs("gtfs.zip", configuration)?
+//!     .generate_transfers(300., 0.785, 60)?
+//!     .apply_rules(&Rules::default())?
+//!     .merge_fares("fares.zip", FaresMode::Merge, &FaresOptions::default())?
+//!     .clean_transfers()?;
+//! let report = pipeline.write_ntfs("ntfs/", chrono::Utc::now().into())?;
+//! report.write("report.json")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    apply_rules::{self, Rules},
+    fares::{self, FaresMode, FaresOptions},
+    gtfs, ntfs,
+    objects::StopPoint,
+    report::Report,
+    stop_areas::StopAreaGenerationConfiguration,
+    stop_matching::{self, ExternalStop, StopMatcher},
+    transfers::{self, TransferTimesConfiguration},
+    Model, Result,
+};
+use chrono::{DateTime, FixedOffset};
+use std::{collections::BTreeMap, path::Path};
+
+/// A model being converted, with the report of the steps applied to it
+pub struct Pipeline {
+    model: Model,
+    report: Report,
+}
+
+impl Pipeline {
+    /// Starts a pipeline from a `model`
+    pub fn new(model: Model) -> Self {
+        Pipeline {
+            model,
+            report: Report::default(),
+        }
+    }
+
+    /// Starts a pipeline from the GTFS at `path`, a directory or a ZIP
+    /// archive (with the `zip` feature). The corrupted files of the ZIP
+    /// archive skipped in lenient `read_mode` are reported in the `gtfs`
+    /// section.
+    pub fn from_gtfs<P: AsRef<Path>>(path: P, configuration: gtfs::Configuration) -> Result<Self> {
+        let path = path.as_ref();
+        #[cfg(feature = "zip")]
+        {
+            if path.is_file() {
+                let mut report = Report::default();
+                let model = gtfs::read_from_zip_with_report(path, configuration, &mut report)?;
+                return Ok(Pipeline::new(model).merge_report("gtfs", report));
+            }
+        }
+        let model = gtfs::read_from_path(path, configuration)?;
+        Ok(Pipeline::new(model))
+    }
+
+    /// Starts a pipeline from the NTFS directory at `path`
+    pub fn from_ntfs<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Pipeline::new(ntfs::read(path)?))
+    }
+
+    /// Applies a custom step on the model, its report being merged in the
+    /// `section` of the report
+    pub fn step<F>(self, section: &str, step: F) -> Result<Self>
+    where
+        F: FnOnce(Model, &mut Report) -> Result<Model>,
+    {
+        let Pipeline { model, mut report } = self;
+        let mut step_report = Report::default();
+        let model = step(model, &mut step_report)?;
+        report.merge_as(section, step_report);
+        Ok(Pipeline { model, report })
+    }
+
+    /// Merges the `report` of an operation made outside of the pipeline in
+    /// the `section` of the report
+    pub fn merge_report(mut self, section: &str, report: Report) -> Self {
+        self.report.merge_as(section, report);
+        self
+    }
+
+    /// Groups the stop points without parent station into generated stop
+    /// areas, see
+    /// [Collections::generate_stop_areas](../model/struct.Collections.html#method.generate_stop_areas).
+    /// The changes are reported in the `stop_areas` section.
+    pub fn generate_stop_areas(
+        self,
+        configuration: &StopAreaGenerationConfiguration,
+    ) -> Result<Self> {
+        self.step("stop_areas", |model, report| {
+            let mut collections = model.into_collections();
+            collections.generate_stop_areas(configuration, report)?;
+            Model::new(collections)
+        })
+    }
+
+    /// Merges the identifiers of the stops of an external referential as
+    /// codes `(object_system, id)` of the stop points they match, see
+    /// [add_matched_codes](../stop_matching/fn.add_matched_codes.html). The
+    /// unmatched and ambiguous external stops are reported in the
+    /// `stop_matching` section.
+    pub fn merge_stop_point_codes<M>(
+        self,
+        external_stops: &[ExternalStop],
+        object_system: &str,
+        matcher: &M,
+    ) -> Result<Self>
+    where
+        M: StopMatcher<StopPoint> + ?Sized,
+    {
+        self.step("stop_matching", |model, report| {
+            let mut collections = model.into_collections();
+            stop_matching::add_matched_codes(
+                &mut collections.stop_points,
+                external_stops,
+                object_system,
+                matcher,
+                report,
+            );
+            Model::new(collections)
+        })
+    }
+
+    /// Generates the transfers between the stop points closer than
+    /// `max_distance`, see
+    /// [generates_transfers](../transfers/fn.generates_transfers.html). The
+    /// numbers of transfers generated and skipped are reported in the
+    /// `transfers` section.
+    pub fn generate_transfers(
+        self,
+        max_distance: f64,
+        walking_speed: f64,
+        waiting_time: u32,
+    ) -> Result<Self> {
+        self.step("transfers", |model, report| {
+            transfers::generates_transfers_with_report(
+                model,
+                max_distance,
+                walking_speed,
+                waiting_time,
+                None,
+                report,
+            )
+        })
+    }
+
+    /// Fixes the too small and absurdly large transfer times, see
+    /// [fix_transfer_times](../transfers/fn.fix_transfer_times.html). The
+    /// transfers fixed are reported in the `transfer_times` section.
+    pub fn fix_transfer_times(self, configuration: &TransferTimesConfiguration) -> Result<Self> {
+        self.step("transfer_times", |model, report| {
+            transfers::fix_transfer_times(model, configuration, report)
+        })
+    }
+
+    /// Applies the `rules`, see
+    /// [apply_rules](../apply_rules/fn.apply_rules.html). The errors and
+    /// changes are reported in the `rules` section.
+    pub fn apply_rules(self, rules: &Rules) -> Result<Self> {
+        self.step("rules", |model, report| {
+            let (collections, _) = apply_rules::run(model, rules, report)?;
+            Model::new(collections)
+        })
+    }
+
+    /// Merges the fares V2 of the directory or ZIP archive at `path`, see
+    /// [read_farev2](../fares/fn.read_farev2.html). The ignored objects and
+    /// conflicts are reported in the `fares` section, along with the skipped
+    /// records and files in lenient `read_mode` of the `options`.
+    pub fn merge_fares<P: AsRef<Path>>(
+        self,
+        path: P,
+        mode: FaresMode,
+        options: &FaresOptions,
+    ) -> Result<Self> {
+        self.step("fares", |model, report| {
+            let mut collections = model.into_collections();
+            fares::read_farev2(&mut collections, path, mode, options, report)?;
+            Model::new(collections)
+        })
+    }
+
+    /// Merges the fare archives of the JSON manifest at `path`, each one
+    /// covering its networks, see
+    /// [read_farev2_manifest](../fares/fn.read_farev2_manifest.html). The
+    /// perimeters outside of the networks of their archive, the ignored
+    /// objects and conflicts are reported in the `fares` section, with a
+    /// subsection per archive.
+    pub fn merge_fares_manifest<P: AsRef<Path>>(
+        self,
+        path: P,
+        mode: FaresMode,
+        options: &FaresOptions,
+    ) -> Result<Self> {
+        self.step("fares", |model, report| {
+            let manifest = fares::read_fares_manifest(path)?;
+            let mut collections = model.into_collections();
+            fares::read_farev2_manifest(&mut collections, &manifest, mode, options, report);
+            Model::new(collections)
+        })
+    }
+
+    /// Assigns a sort order to the lines without one, see
+    /// [Collections::assign_line_sort_orders](../model/struct.Collections.html#method.assign_line_sort_orders).
+    /// The sort orders set are reported in the `line_sort_orders` section.
+    pub fn assign_line_sort_orders(self, pinned: &BTreeMap<String, u32>) -> Result<Self> {
+        self.step("line_sort_orders", |model, report| {
+            let mut collections = model.into_collections();
+            collections.assign_line_sort_orders(pinned, report);
+            Model::new(collections)
+        })
+    }
+
+    /// Merges the calendars with the same dates, see
+    /// [Collections::consolidate_calendars](../model/struct.Collections.html#method.consolidate_calendars).
+    /// The merges are reported in the `calendars` section.
+    pub fn consolidate_calendars(self) -> Result<Self> {
+        self.step("calendars", |model, report| {
+            let mut collections = model.into_collections();
+            collections.consolidate_calendars(report);
+            Model::new(collections)
+        })
+    }
+
+    /// The model in its current state
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// The report of the steps applied so far
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Ends the pipeline, returning the model and the report
+    pub fn into_parts(self) -> (Model, Report) {
+        (self.model, self.report)
+    }
+
+    /// Removes the duplicated and invalid transfers, see
+    /// [Collections::clean_transfers](../model/struct.Collections.html#method.clean_transfers).
+    /// The transfers removed are reported in the `transfers` section.
+    pub fn clean_transfers(self) -> Result<Self> {
+        self.step("transfers", |model, report| {
+            let mut collections = model.into_collections();
+            collections.clean_transfers(report);
+            Model::new(collections)
+        })
+    }
+
+    /// Ends the pipeline, writing the model as a NTFS in the directory
+    /// `path`, and returns the report
+    pub fn write_ntfs<P: AsRef<Path>>(
+        self,
+        path: P,
+        current_datetime: DateTime<FixedOffset>,
+    ) -> Result<Report> {
+        ntfs::write(&self.model, path, current_datetime)?;
+        Ok(self.report)
+    }
+
+    /// Ends the pipeline, writing the model as a GTFS in the directory
+    /// `path`, and returns the report
+    pub fn write_gtfs<P: AsRef<Path>>(self, path: P) -> Result<Report> {
+        gtfs::write(self.model, path)?;
+        Ok(self.report)
+    }
+}
@@ -14,13 +14,18 @@
 //! Some utilities for input dataset to the library.
 
 use crate::{
+    error::TransitModelError,
     objects::{self, Contributor},
+    report::{Report, ReportType, Severity},
     Result,
 };
 use failure::{format_err, ResultExt};
-use log::info;
+use log::{info, warn};
+use rayon::prelude::*;
 use serde::Deserialize;
 use std::collections::BTreeMap;
+#[cfg(feature = "zip")]
+use std::collections::BTreeSet;
 use std::fs::File;
 use std::path;
 use std::path::{Path, PathBuf};
@@ -36,6 +41,8 @@ struct Config {
     contributor: objects::Contributor,
     dataset: ConfigDataset,
     feed_infos: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    trip_operators: bool,
 }
 
 /// Read a JSON configuration file to facilitate the creation of:
@@ -69,10 +76,36 @@ pub fn read_config<P: AsRef<path::Path>>(
     objects::Contributor,
     objects::Dataset,
     BTreeMap<String, String>,
+)> {
+    let (contributor, dataset, feed_infos, _) = read_gtfs_config(config_path)?;
+    Ok((contributor, dataset, feed_infos))
+}
+
+/// Reads the JSON configuration file of [read_config](fn.read_config.html)
+/// with the options of the GTFS converters: the `trip_operators` option,
+/// `false` when not given. When `true`, the GTFS reader creates a company
+/// for each operator of the `trips.operator_id` column (see the
+/// `trip_operators` field of
+/// [gtfs::Configuration](../gtfs/struct.Configuration.html)).
+/// ```text
+/// {
+///     "contributor": { ... },
+///     "dataset": { ... },
+///     "trip_operators": true
+/// }
+/// ```
+pub fn read_gtfs_config<P: AsRef<path::Path>>(
+    config_path: Option<P>,
+) -> Result<(
+    objects::Contributor,
+    objects::Dataset,
+    BTreeMap<String, String>,
+    bool,
 )> {
     let contributor;
     let dataset;
     let mut feed_infos = BTreeMap::default();
+    let mut trip_operators = false;
 
     if let Some(config_path) = config_path {
         let config_path = config_path.as_ref();
@@ -85,12 +118,30 @@ pub fn read_config<P: AsRef<path::Path>>(
         if let Some(config_feed_infos) = config.feed_infos {
             feed_infos = config_feed_infos;
         }
+        trip_operators = config.trip_operators;
     } else {
         contributor = Contributor::default();
         dataset = objects::Dataset::default();
     }
 
-    Ok((contributor, dataset, feed_infos))
+    Ok((contributor, dataset, feed_infos, trip_operators))
+}
+
+/// How a reader handles the records of the files which can't be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadMode {
+    /// A record which can't be read fails the reading
+    Strict,
+    /// A record which can't be read is skipped, and reported (in the report
+    /// of the reader when it has one, as a warning in the logs otherwise);
+    /// a corrupted file of a zip file is handled as a missing file
+    Lenient,
+}
+
+impl Default for ReadMode {
+    fn default() -> Self {
+        ReadMode::Strict
+    }
 }
 
 pub(crate) trait FileHandler
@@ -101,10 +152,15 @@ where
 
     fn get_file_if_exists(self, name: &str) -> Result<(Option<Self::Reader>, PathBuf)>;
 
+    /// How the records which can't be read are handled
+    fn read_mode(&self) -> ReadMode {
+        ReadMode::Strict
+    }
+
     fn get_file(self, name: &str) -> Result<(Self::Reader, PathBuf)> {
         let (reader, path) = self.get_file_if_exists(name)?;
         Ok((
-            reader.ok_or_else(|| format_err!("file {:?} not found", path))?,
+            reader.ok_or_else(|| TransitModelError::MissingFile { path: path.clone() })?,
             path,
         ))
     }
@@ -113,11 +169,20 @@ where
 /// PathFileHandler is used to read files for a directory
 pub(crate) struct PathFileHandler<P: AsRef<Path>> {
     base_path: P,
+    read_mode: ReadMode,
 }
 
 impl<P: AsRef<Path>> PathFileHandler<P> {
     pub(crate) fn new(path: P) -> Self {
-        PathFileHandler { base_path: path }
+        PathFileHandler {
+            base_path: path,
+            read_mode: ReadMode::Strict,
+        }
+    }
+
+    pub(crate) fn with_read_mode(mut self, read_mode: ReadMode) -> Self {
+        self.read_mode = read_mode;
+        self
     }
 }
 
@@ -134,20 +199,54 @@ impl<'a, P: AsRef<Path>> FileHandler for &'a mut PathFileHandler<P> {
             Ok((None, f))
         }
     }
+
+    fn read_mode(&self) -> ReadMode {
+        self.read_mode
+    }
 }
 
-/// ZipHandler is a wrapper around a ZipArchive
+/// ZipHandler is a wrapper around a ZipArchive, it needs the `zip` feature
 /// It provides a way to access the archive's file by their names
 ///
 /// Unlike ZipArchive, it gives access to a file by its name not regarding its path in the ZipArchive
-/// It thus cannot be correct if there are 2 files with the same name in the archive,
-/// but for transport data if will make it possible to handle a zip with a sub directory
+/// nor its case (`Tickets.TXT` is found as `tickets.txt`), so that a zip with sub directories can be
+/// read. If several files of the archive match a name, the least nested one is used (see
+/// [candidates](#method.candidates) to report the ambiguity).
+///
+/// In lenient mode, a file of the archive is checked when it's first accessed, and handled as
+/// missing if it's corrupted (see [corrupted_files](#method.corrupted_files)).
+#[cfg(feature = "zip")]
 pub(crate) struct ZipHandler {
     archive: zip::ZipArchive<File>,
     archive_path: PathBuf,
-    index_by_name: BTreeMap<String, usize>,
+    // the files of the archive (path and index) by lowercase file name, least nested first
+    index_by_name: BTreeMap<String, Vec<(String, usize)>>,
+    read_mode: ReadMode,
+    // the files of the archive checked in lenient mode
+    checked_files: BTreeSet<usize>,
+    // the files of the archive which can't be decompressed, with the error
+    corrupted_files: Vec<(String, String)>,
+}
+
+#[cfg(feature = "zip")]
+fn lowercase_file_name(name: &str) -> Option<String> {
+    Some(Path::new(name).file_name()?.to_str()?.to_lowercase())
+}
+
+#[cfg(feature = "zip")]
+fn depth(name: &str) -> usize {
+    Path::new(name).components().count()
+}
+
+// Decompresses the file `index` of the `archive`, checking its CRC
+#[cfg(feature = "zip")]
+fn check_zip_file(archive: &mut zip::ZipArchive<File>, index: usize) -> Result<()> {
+    let mut file = archive.by_index(index)?;
+    std::io::copy(&mut file, &mut std::io::sink())?;
+    Ok(())
 }
 
+#[cfg(feature = "zip")]
 impl ZipHandler {
     pub(crate) fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = File::open(path.as_ref())?;
@@ -156,31 +255,283 @@ impl ZipHandler {
             index_by_name: Self::files_by_name(&mut archive),
             archive,
             archive_path: path.as_ref().to_path_buf(),
+            read_mode: ReadMode::Strict,
+            checked_files: BTreeSet::new(),
+            corrupted_files: vec![],
         })
     }
 
-    fn files_by_name(archive: &mut zip::ZipArchive<File>) -> BTreeMap<String, usize> {
-        (0..archive.len())
-            .filter_map(|i| {
-                let file = archive.by_index(i).ok()?;
-                // we get the name of the file, not regarding its path in the ZipArchive
-                let real_name = Path::new(file.name()).file_name()?;
-                let real_name: String = real_name.to_str()?.into();
-                Some((real_name, i))
+    /// In lenient mode, the files of the archive are checked when they are accessed and the
+    /// corrupted ones are handled as missing files
+    pub(crate) fn with_read_mode(mut self, read_mode: ReadMode) -> Self {
+        self.read_mode = read_mode;
+        self
+    }
+
+    // Whether the file `index` of the archive can be read, the corrupted
+    // files being recorded in lenient mode (each file is only checked once)
+    fn is_readable(&mut self, file_name: &str, index: usize) -> bool {
+        if self.read_mode == ReadMode::Strict || !self.checked_files.insert(index) {
+            return !self
+                .corrupted_files
+                .iter()
+                .any(|(corrupted_file, _)| corrupted_file == file_name);
+        }
+        match check_zip_file(&mut self.archive, index) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(
+                    "{} is corrupted in {:?}, it is ignored: {}",
+                    file_name, self.archive_path, e
+                );
+                self.corrupted_files
+                    .push((file_name.to_string(), e.to_string()));
+                false
+            }
+        }
+    }
+
+    /// Paths in the archive of the corrupted files ignored in lenient mode, with the error. The
+    /// files are checked when they are accessed, so only the ones accessed so far are listed.
+    pub(crate) fn corrupted_files(&self) -> &[(String, String)] {
+        &self.corrupted_files
+    }
+
+    fn files_by_name(
+        archive: &mut zip::ZipArchive<File>,
+    ) -> BTreeMap<String, Vec<(String, usize)>> {
+        let mut files_by_name: BTreeMap<String, Vec<(String, usize)>> = BTreeMap::new();
+        for i in 0..archive.len() {
+            let file_name = match archive.by_index(i) {
+                Ok(ref file) if !file.name().ends_with('/') => file.name().to_string(),
+                _ => continue,
+            };
+            // we get the name of the file, not regarding its path in the ZipArchive
+            if let Some(real_name) = lowercase_file_name(&file_name) {
+                files_by_name
+                    .entry(real_name)
+                    .or_insert_with(Vec::new)
+                    .push((file_name, i));
+            }
+        }
+        for files in files_by_name.values_mut() {
+            files.sort_by(|(name1, _), (name2, _)| {
+                (depth(name1), name1).cmp(&(depth(name2), name2))
+            });
+        }
+        files_by_name
+    }
+
+    fn matching_files<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a (String, usize)> {
+        let lowercase_name = name.to_lowercase();
+        lowercase_file_name(name)
+            .and_then(|file_name| self.index_by_name.get(&file_name))
+            .into_iter()
+            .flatten()
+            .filter(move |(file_name, _)| {
+                // a name with a directory (e.g. `folder/file.txt`) must match the end of the path
+                let file_name = file_name.to_lowercase();
+                file_name == lowercase_name || file_name.ends_with(&format!("/{}", lowercase_name))
             })
+    }
+
+    /// Paths in the archive of all the files matching `name`, least nested first
+    pub(crate) fn candidates(&self, name: &str) -> Vec<&str> {
+        self.matching_files(name)
+            .map(|(file_name, _)| file_name.as_str())
             .collect()
     }
 }
 
+#[cfg(feature = "zip")]
 impl<'a> FileHandler for &'a mut ZipHandler {
     type Reader = zip::read::ZipFile<'a>;
     fn get_file_if_exists(self, name: &str) -> Result<(Option<Self::Reader>, PathBuf)> {
-        let p = self.archive_path.join(name);
-        match self.index_by_name.get(name) {
-            None => Ok((None, p)),
-            Some(i) => Ok((Some(self.archive.by_index(*i)?), p)),
+        let candidates = self.candidates(name);
+        if candidates.len() > 1 {
+            warn!(
+                "several files match {} in {:?}, {} is used: {}",
+                name,
+                self.archive_path,
+                candidates[0],
+                candidates.join(", ")
+            );
+        }
+        let matching_files: Vec<(String, usize)> = self.matching_files(name).cloned().collect();
+        let readable_file = matching_files
+            .into_iter()
+            .find(|(file_name, i)| self.is_readable(file_name, *i));
+        match readable_file {
+            None => Ok((None, self.archive_path.join(name))),
+            Some((file_name, i)) => {
+                let path = self.archive_path.join(file_name);
+                Ok((Some(self.archive.by_index(i)?), path))
+            }
+        }
+    }
+
+    fn read_mode(&self) -> ReadMode {
+        self.read_mode
+    }
+}
+
+// Handles the `error` of a record: with a `report`, the error is reported
+// and the record skipped, otherwise the reading fails. The I/O errors always
+// fail the reading.
+fn skip_or_fail(
+    error: TransitModelError,
+    io_error: bool,
+    report: &mut Option<&mut Report>,
+) -> Result<()> {
+    match report {
+        Some(report) if !io_error => {
+            report.add_error(error.to_string(), ReportType::InvalidValue);
+            Ok(())
+        }
+        _ => Err(error.into()),
+    }
+}
+
+// The deserialized object of the `record`, or `None` if it can't be
+// deserialized and is skipped (see `skip_or_fail`)
+fn check_record<O>(
+    path: &Path,
+    record: &csv::ByteRecord,
+    result: csv::Result<O>,
+    report: &mut Option<&mut Report>,
+) -> Result<Option<O>> {
+    match result {
+        Ok(object) => Ok(Some(object)),
+        Err(e) => {
+            let error = TransitModelError::invalid_record(path, record, e);
+            skip_or_fail(error, false, report).map(|_| None)
+        }
+    }
+}
+
+// The `record` read, or `None` if it can't be read and is skipped (see
+// `skip_or_fail`)
+fn check_read(
+    path: &Path,
+    record: csv::Result<csv::ByteRecord>,
+    report: &mut Option<&mut Report>,
+) -> Result<Option<csv::ByteRecord>> {
+    match record {
+        Ok(record) => Ok(Some(record)),
+        Err(e) => {
+            let io_error = matches!(e.kind(), csv::ErrorKind::Io(_));
+            skip_or_fail(TransitModelError::csv(path, e), io_error, report).map(|_| None)
+        }
+    }
+}
+
+fn read_headers<R: std::io::Read>(
+    rdr: &mut csv::Reader<R>,
+    path: &Path,
+) -> Result<csv::ByteRecord> {
+    let headers = rdr
+        .byte_headers()
+        .map_err(|e| TransitModelError::csv(path, e))?;
+    Ok(headers.clone())
+}
+
+/// Deserializes the records of the CSV file `path` read by `rdr`.
+///
+/// A record which can't be read fails with a
+/// [CsvParse](../error/enum.TransitModelError.html#variant.CsvParse) error
+/// giving the file, the line and the content of the record. When a `report`
+/// is given, the record is skipped and the error added to the report instead
+/// (skip-and-report mode).
+pub fn deserialize_records<R, O>(
+    rdr: &mut csv::Reader<R>,
+    path: &Path,
+    report: Option<&mut Report>,
+) -> Result<Vec<O>>
+where
+    R: std::io::Read,
+    O: for<'de> serde::Deserialize<'de>,
+{
+    let mut objects = vec![];
+    for_each_record(rdr, path, report, |object: O, _| {
+        objects.push(object);
+        Ok(())
+    })?;
+    Ok(objects)
+}
+
+// Deserializes the records as `deserialize_records` does, handing each object
+// to `f` (with the `report`) in the order of the file instead of collecting
+// them
+pub(crate) fn for_each_record<R, O, F>(
+    rdr: &mut csv::Reader<R>,
+    path: &Path,
+    mut report: Option<&mut Report>,
+    mut f: F,
+) -> Result<()>
+where
+    R: std::io::Read,
+    O: for<'de> serde::Deserialize<'de>,
+    F: FnMut(O, &mut Option<&mut Report>) -> Result<()>,
+{
+    let headers = read_headers(rdr, path)?;
+    for record in rdr.byte_records() {
+        if let Some(record) = check_read(path, record, &mut report)? {
+            let result = record.deserialize(Some(&headers));
+            if let Some(object) = check_record(path, &record, result, &mut report)? {
+                f(object, &mut report)?;
+            }
         }
     }
+    Ok(())
+}
+
+// The report of the records skipped in `skipped`, in lenient mode
+pub(crate) fn lenient_report(read_mode: ReadMode, skipped: &mut Report) -> Option<&mut Report> {
+    match read_mode {
+        ReadMode::Strict => None,
+        ReadMode::Lenient => Some(skipped),
+    }
+}
+
+// Skips a record whose `field` references the unknown object `object_id`
+// (described by `message`) in lenient mode, when there is a `report`; fails
+// with a referential integrity error otherwise
+pub(crate) fn skip_dangling_reference(
+    message: String,
+    field: &str,
+    object_id: &str,
+    report: &mut Option<&mut Report>,
+) -> Result<()> {
+    match report {
+        Some(report) => {
+            report.add_error(message, ReportType::DanglingReference);
+            Ok(())
+        }
+        None => Err(TransitModelError::dangling(field, object_id, message)),
+    }
+}
+
+pub(crate) fn warn_skipped_records(skipped: &Report) {
+    for entry in skipped.entries(Severity::Error) {
+        warn!("{}, record skipped", entry.message);
+    }
+}
+
+// Deserializes the records of `rdr` with the `read_mode`, the records skipped
+// in lenient mode being logged
+fn deserialize_records_with_mode<R, O>(
+    rdr: &mut csv::Reader<R>,
+    path: &Path,
+    read_mode: ReadMode,
+) -> Result<Vec<O>>
+where
+    R: std::io::Read,
+    O: for<'de> serde::Deserialize<'de>,
+{
+    let mut skipped = Report::default();
+    let objects = deserialize_records(rdr, path, lenient_report(read_mode, &mut skipped))?;
+    warn_skipped_records(&skipped);
+    Ok(objects)
 }
 
 /// Read a vector of objects from a zip in a file_handler
@@ -189,15 +540,86 @@ where
     for<'a> &'a mut H: FileHandler,
     O: for<'de> serde::Deserialize<'de>,
 {
+    let read_mode = file_handler.read_mode();
     let (reader, path) = file_handler.get_file(file_name)?;
     let file_name = path.file_name();
     let basename = file_name.map_or(path.to_string_lossy(), |b| b.to_string_lossy());
     info!("Reading {}", basename);
     let mut rdr = csv::Reader::from_reader(reader);
-    Ok(rdr
-        .deserialize()
-        .collect::<Result<_, _>>()
-        .with_context(|_| format!("Error reading {:?}", path))?)
+    deserialize_records_with_mode(&mut rdr, &path, read_mode)
+}
+
+// The number of records deserialized in parallel at a time
+const PARALLEL_CHUNK_SIZE: usize = 10_000;
+
+/// Deserializes the CSV records of the file `path` read by `rdr` in
+/// parallel, like [deserialize_records](fn.deserialize_records.html): the
+/// records are split by the current thread (which handles the quoted fields),
+/// then deserialized by the threads of the [rayon] pool. The objects are in
+/// the order of the file.
+///
+/// The records are read and deserialized by chunks, only the records of a
+/// chunk are held in memory besides the objects.
+pub(crate) fn deserialize_in_parallel<R, O>(
+    rdr: &mut csv::Reader<R>,
+    path: &Path,
+    mut report: Option<&mut Report>,
+) -> Result<Vec<O>>
+where
+    R: std::io::Read,
+    O: for<'de> serde::Deserialize<'de> + Send,
+{
+    let headers = read_headers(rdr, path)?;
+    let mut objects = vec![];
+    let mut records = rdr.byte_records();
+    let mut chunk = Vec::with_capacity(PARALLEL_CHUNK_SIZE);
+    loop {
+        chunk.clear();
+        for record in records.by_ref() {
+            if let Some(record) = check_read(path, record, &mut report)? {
+                chunk.push(record);
+                if chunk.len() == PARALLEL_CHUNK_SIZE {
+                    break;
+                }
+            }
+        }
+        if chunk.is_empty() {
+            return Ok(objects);
+        }
+        let results: Vec<csv::Result<O>> = chunk
+            .par_iter()
+            .map(|record| record.deserialize(Some(&headers)))
+            .collect();
+        for (record, result) in chunk.iter().zip(results) {
+            if let Some(object) = check_record(path, record, result, &mut report)? {
+                objects.push(object);
+            }
+        }
+    }
+}
+
+/// Read a vector of objects from a file_handler, like
+/// [read_objects](fn.read_objects.html) but deserializing the records in
+/// parallel
+pub(crate) fn read_objects_in_parallel<H, O>(
+    file_handler: &mut H,
+    file_name: &str,
+) -> Result<Vec<O>>
+where
+    for<'a> &'a mut H: FileHandler,
+    O: for<'de> serde::Deserialize<'de> + Send,
+{
+    let read_mode = file_handler.read_mode();
+    let (reader, path) = file_handler.get_file(file_name)?;
+    let file_name = path.file_name();
+    let basename = file_name.map_or(path.to_string_lossy(), |b| b.to_string_lossy());
+    info!("Reading {}", basename);
+    let mut rdr = csv::Reader::from_reader(reader);
+    let mut skipped = Report::default();
+    let objects =
+        deserialize_in_parallel(&mut rdr, &path, lenient_report(read_mode, &mut skipped))?;
+    warn_skipped_records(&skipped);
+    Ok(objects)
 }
 
 pub(crate) fn read_opt_objects<H, O>(file_handler: &mut H, file_name: &str) -> Result<Vec<O>>
@@ -205,6 +627,7 @@ where
     for<'a> &'a mut H: FileHandler,
     O: for<'de> serde::Deserialize<'de>,
 {
+    let read_mode = file_handler.read_mode();
     let (reader, path) = file_handler.get_file_if_exists(file_name)?;
     let file_name = path.file_name();
     let basename = file_name.map_or(path.to_string_lossy(), |b| b.to_string_lossy());
@@ -217,10 +640,7 @@ where
         Some(reader) => {
             info!("Reading {}", basename);
             let mut rdr = csv::Reader::from_reader(reader);
-            Ok(rdr
-                .deserialize()
-                .collect::<Result<_, _>>()
-                .with_context(|_| format!("Error reading {:?}", path))?)
+            deserialize_records_with_mode(&mut rdr, &path, read_mode)
         }
     }
 }
@@ -250,6 +670,17 @@ where
     CollectionWithId::new(vec).map_err(|e| format_err!("{}", e))
 }
 
+/// Reads the text file at `path`, decoded as UTF-8 or, when it is not valid
+/// UTF-8, as Latin-1 (ISO 8859-1), the encoding of many German and Swiss
+/// exports.
+pub(crate) fn read_utf8_or_latin1(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path).with_context(|_| format!("Error reading {:?}", path))?;
+    Ok(match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(error) => error.into_bytes().into_iter().map(char::from).collect(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -271,6 +702,7 @@ mod tests {
         assert_eq!("world\n", world_str);
     }
 
+    #[cfg(feature = "zip")]
     #[test]
     fn zip_file_handler() {
         let mut file_handler =
@@ -290,4 +722,146 @@ mod tests {
             assert_eq!("world\n", world_str);
         }
     }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_file_handler_case_insensitive_and_nested() {
+        let mut file_handler =
+            ZipHandler::new(PathBuf::from("tests/fixtures/file-handler-nested.zip")).unwrap();
+
+        assert_eq!(
+            vec!["data/Tickets.TXT", "data/old/deeper/tickets.txt"],
+            file_handler.candidates("tickets.txt")
+        );
+        assert_eq!(
+            vec!["data/old/deeper/tickets.txt"],
+            file_handler.candidates("deeper/TICKETS.txt")
+        );
+        assert!(file_handler.candidates("prices.txt").is_empty());
+
+        {
+            let (mut tickets, path) = file_handler.get_file("tickets.txt").unwrap();
+            let mut tickets_str = String::new();
+            tickets.read_to_string(&mut tickets_str).unwrap();
+            assert_eq!("new tickets\n", tickets_str);
+            assert_eq!(
+                Path::new("tests/fixtures/file-handler-nested.zip/data/Tickets.TXT"),
+                path
+            );
+        }
+
+        {
+            let (mut tickets, _) = file_handler.get_file("deeper/tickets.txt").unwrap();
+            let mut tickets_str = String::new();
+            tickets.read_to_string(&mut tickets_str).unwrap();
+            assert_eq!("old tickets\n", tickets_str);
+        }
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_file_handler_skipping_corrupted_files() {
+        use std::io::Write;
+        crate::test_utils::test_in_tmp_dir(|path| {
+            let zip_path = path.join("archive.zip");
+            let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+            let options = zip::write::FileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored);
+            zip.start_file("hello.txt", options).unwrap();
+            zip.write_all(b"hello\n").unwrap();
+            zip.start_file("world.txt", options).unwrap();
+            zip.write_all(b"world\n").unwrap();
+            zip.finish().unwrap();
+            // the content of world.txt no longer matches its CRC
+            let mut bytes = std::fs::read(&zip_path).unwrap();
+            let position = bytes
+                .windows(6)
+                .position(|window| window == b"world\n")
+                .unwrap();
+            bytes[position] = b'W';
+            std::fs::write(&zip_path, bytes).unwrap();
+
+            let mut file_handler = ZipHandler::new(&zip_path).unwrap();
+            assert!(file_handler.corrupted_files().is_empty());
+            {
+                let (mut world, _) = file_handler.get_file("world.txt").unwrap();
+                let mut world_str = String::new();
+                assert!(world.read_to_string(&mut world_str).is_err());
+            }
+
+            let mut file_handler = file_handler.with_read_mode(ReadMode::Lenient);
+            // the files are only checked when they are accessed
+            assert!(file_handler.corrupted_files().is_empty());
+            {
+                let (mut hello, _) = file_handler.get_file("hello.txt").unwrap();
+                let mut hello_str = String::new();
+                hello.read_to_string(&mut hello_str).unwrap();
+                assert_eq!("hello\n", hello_str);
+            }
+            assert!(file_handler.corrupted_files().is_empty());
+            let (world, _) = file_handler.get_file_if_exists("world.txt").unwrap();
+            assert!(world.is_none());
+            assert_eq!(1, file_handler.corrupted_files().len());
+            assert_eq!("world.txt", file_handler.corrupted_files()[0].0);
+            // a file is only checked once
+            let (world, _) = file_handler.get_file_if_exists("world.txt").unwrap();
+            assert!(world.is_none());
+            assert_eq!(1, file_handler.corrupted_files().len());
+        });
+    }
+
+    #[test]
+    fn deserialize_records_in_parallel() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Row {
+            id: String,
+            value: u32,
+        }
+        // more records than a chunk, with an invalid one in the first chunk
+        let nb_rows = PARALLEL_CHUNK_SIZE + 10;
+        let mut content = String::from("id,value\n");
+        for i in 0..nb_rows {
+            content.push_str(&format!("\"row\n{}\",{}\n", i, i));
+            if i == 10 {
+                content.push_str("invalid,-1\n");
+            }
+        }
+
+        let path = Path::new("rows.txt");
+        let mut rdr = csv::Reader::from_reader(content.as_bytes());
+        assert!(deserialize_in_parallel::<_, Row>(&mut rdr, path, None).is_err());
+
+        let mut skipped = Report::default();
+        let mut rdr = csv::Reader::from_reader(content.as_bytes());
+        let rows = deserialize_in_parallel::<_, Row>(&mut rdr, path, Some(&mut skipped)).unwrap();
+        assert_eq!(nb_rows, rows.len());
+        for (i, row) in rows.iter().enumerate() {
+            let expected = Row {
+                id: format!("row\n{}", i),
+                value: i as u32,
+            };
+            assert_eq!(&expected, row);
+        }
+        assert_eq!(1, skipped.entries(Severity::Error).len());
+    }
+
+    #[test]
+    fn read_objects_with_read_mode() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Row {
+            id: String,
+            value: u32,
+        }
+        crate::test_utils::test_in_tmp_dir(|path| {
+            std::fs::write(path.join("rows.txt"), "id,value\nr1,1\nr2,-2\nr3,3\n").unwrap();
+
+            let mut file_handler = PathFileHandler::new(path.to_path_buf());
+            assert!(read_objects::<_, Row>(&mut file_handler, "rows.txt").is_err());
+
+            let mut file_handler = file_handler.with_read_mode(ReadMode::Lenient);
+            let rows: Vec<Row> = read_objects(&mut file_handler, "rows.txt").unwrap();
+            let ids: Vec<&str> = rows.iter().map(|row| row.id.as_str()).collect();
+            assert_eq!(vec!["r1", "r3"], ids);
+        });
+    }
 }
@@ -0,0 +1,423 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+//! Report of the operations applied on a `Model` or of its validation: errors
+//! found, warnings and changes made, serializable as JSON or CSV
+
+use crate::{objects::ObjectType, Result};
+use failure::ResultExt;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    path::Path,
+};
+
+/// Category of an entry of the report
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportType {
+    /// The object referenced by a rule doesn't exist
+    ObjectNotFound,
+    /// A value of an input file is invalid
+    InvalidValue,
+    /// A property rule refers to an unknown property
+    UnknownPropertyName,
+    /// The current value of a property is not the one expected by a rule
+    OldPropertyValueDoesNotMatch,
+    /// A property has been updated by a rule
+    PropertyUpdated,
+    /// No vehicle journey matches a rule
+    UnmatchedVehicleJourney,
+    /// Several vehicle journeys match a rule
+    AmbiguousVehicleJourney,
+    /// Several stops match a stop of an external referential
+    AmbiguousStopMatch,
+    /// An object references an object that doesn't exist
+    DanglingReference,
+    /// The stop times of a vehicle journey are not in increasing order
+    StopTimesNotIncreasing,
+    /// The calendar of a vehicle journey doesn't exist or has no date
+    VehicleJourneyWithoutCalendar,
+    /// An object references a geometry that doesn't exist
+    MissingGeometry,
+    /// Several transfers exist between the same stop points
+    OverlappingTransfers,
+    /// An object with the same identifier but a different content already
+    /// exists
+    Conflict,
+    /// An input file couldn't be read
+    UnreadableFile,
+    /// The vehicle journeys of a route have several physical modes
+    MixedPhysicalModes,
+    /// The physical mode of a vehicle journey is not allowed for the
+    /// commercial mode of its line
+    InconsistentPhysicalMode,
+    /// A fare perimeter or restriction is outside of the networks declared
+    /// for its fares
+    PerimeterLeak,
+    /// An object has been created
+    ObjectCreated,
+    /// An object has been removed
+    ObjectRemoved,
+    /// An object has been merged into an identical one
+    ObjectMerged,
+    /// A line has fewer vehicle journeys than expected on some days
+    InsufficientService,
+    /// The average speed of a vehicle journey is too high for its physical
+    /// mode
+    UnrealisticSpeed,
+    /// A transfer time is too small between distinct stop points, or
+    /// absurdly large
+    UnrealisticTransferTime,
+    /// Version of the specification of an input, and how it was adapted
+    SpecificationVersion,
+}
+
+/// Severity of an entry of the report
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Something done on the model, for information
+    Info,
+    /// Something suspicious, which didn't prevent the operation
+    Warning,
+    /// Something that prevented (part of) the operation
+    Error,
+}
+
+/// An entry of the report
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ReportEntry {
+    /// Category of the entry
+    pub category: ReportType,
+    /// Type of the object concerned by the entry, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_type: Option<ObjectType>,
+    /// Identifier of the object concerned by the entry, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_id: Option<String>,
+    /// Description of the entry
+    pub message: String,
+    /// Number of times the same entry has been reported
+    pub count: usize,
+}
+
+impl ReportEntry {
+    /// Creates an entry of the given `category`
+    pub fn new(category: ReportType, message: String) -> Self {
+        ReportEntry {
+            category,
+            object_type: None,
+            object_id: None,
+            message,
+            count: 1,
+        }
+    }
+
+    /// Attaches the object concerned by the entry
+    pub fn with_object(mut self, object_type: ObjectType, object_id: &str) -> Self {
+        self.object_type = Some(object_type);
+        self.object_id = Some(object_id.to_string());
+        self
+    }
+}
+
+// An entry of the report as a row of the CSV export
+#[derive(Serialize)]
+struct ReportRow<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    section: Option<&'a str>,
+    severity: Severity,
+    category: ReportType,
+    object_type: Option<&'a str>,
+    object_id: Option<&'a str>,
+    message: &'a str,
+    count: usize,
+}
+
+type EntryKey = (
+    Severity,
+    ReportType,
+    Option<ObjectType>,
+    Option<String>,
+    String,
+);
+
+/// Errors, warnings and changes made while applying operations on a `Model`.
+///
+/// Identical entries are only reported once, with the number of times they
+/// occurred. The entries concerning a part of the operation (e.g. one of its
+/// input files) can be grouped in a named [section](#method.section).
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub(crate) errors: Vec<ReportEntry>,
+    pub(crate) warnings: Vec<ReportEntry>,
+    pub(crate) infos: Vec<ReportEntry>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    sections: BTreeMap<String, Report>,
+    #[serde(skip)]
+    positions: HashMap<EntryKey, usize>,
+}
+
+impl Report {
+    fn entries_mut(&mut self, severity: Severity) -> &mut Vec<ReportEntry> {
+        match severity {
+            Severity::Error => &mut self.errors,
+            Severity::Warning => &mut self.warnings,
+            Severity::Info => &mut self.infos,
+        }
+    }
+
+    /// Adds an entry with the given `severity` to the report
+    pub fn add(&mut self, severity: Severity, entry: ReportEntry) {
+        let key = (
+            severity,
+            entry.category,
+            entry.object_type.clone(),
+            entry.object_id.clone(),
+            entry.message.clone(),
+        );
+        match self.positions.get(&key) {
+            Some(&position) => self.entries_mut(severity)[position].count += entry.count,
+            None => {
+                let entries = self.entries_mut(severity);
+                entries.push(entry);
+                let position = entries.len() - 1;
+                self.positions.insert(key, position);
+            }
+        }
+    }
+
+    /// Adds an error of the given `category` to the report
+    pub fn add_error(&mut self, message: String, category: ReportType) {
+        self.add(Severity::Error, ReportEntry::new(category, message));
+    }
+
+    /// Adds a warning of the given `category` to the report
+    pub fn add_warning(&mut self, message: String, category: ReportType) {
+        self.add(Severity::Warning, ReportEntry::new(category, message));
+    }
+
+    /// Adds an information, like a change applied to the model, to the report
+    pub fn add_info(&mut self, message: String, category: ReportType) {
+        self.add(Severity::Info, ReportEntry::new(category, message));
+    }
+
+    /// Entries of the report with the given `severity`
+    pub fn entries(&self, severity: Severity) -> &[ReportEntry] {
+        match severity {
+            Severity::Error => &self.errors,
+            Severity::Warning => &self.warnings,
+            Severity::Info => &self.infos,
+        }
+    }
+
+    /// The section of the report with the given `name`, created if needed
+    pub fn section(&mut self, name: &str) -> &mut Report {
+        self.sections.entry(name.to_string()).or_default()
+    }
+
+    /// Sections of the report, ordered by name
+    pub fn sections(&self) -> impl Iterator<Item = (&str, &Report)> {
+        self.sections
+            .iter()
+            .map(|(name, section)| (name.as_str(), section))
+    }
+
+    /// Adds the entries and the sections of `other` to the report, the
+    /// entries already in the report being counted once
+    pub fn merge(&mut self, other: Report) {
+        let Report {
+            errors,
+            warnings,
+            infos,
+            sections,
+            ..
+        } = other;
+        for (severity, entries) in vec![
+            (Severity::Error, errors),
+            (Severity::Warning, warnings),
+            (Severity::Info, infos),
+        ] {
+            for entry in entries {
+                self.add(severity, entry);
+            }
+        }
+        for (name, section) in sections {
+            self.section(&name).merge(section);
+        }
+    }
+
+    /// Adds the entries of `other` to the section `name` of the report, e.g.
+    /// the report of a step of a conversion. The sections of `other` are
+    /// added as `<name>/<section>`, so that the report has a single level of
+    /// sections.
+    pub fn merge_as(&mut self, name: &str, other: Report) {
+        let Report {
+            errors,
+            warnings,
+            infos,
+            sections,
+            ..
+        } = other;
+        self.section(name).merge(Report {
+            errors,
+            warnings,
+            infos,
+            ..Default::default()
+        });
+        for (section_name, section) in sections {
+            self.merge_as(&format!("{}/{}", name, section_name), section);
+        }
+    }
+
+    /// Writes the report as CSV if `path` has a `.csv` extension, as JSON
+    /// otherwise.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        match path.extension() {
+            Some(extension) if extension == "csv" => self.write_csv(path),
+            _ => self.write_json(path),
+        }
+    }
+
+    /// Writes the report as JSON, with the entries grouped by severity and
+    /// the sections by name
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|_| format!("Error writing {:?}", path))?;
+        serde_json::to_writer_pretty(file, self)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+        Ok(())
+    }
+
+    /// Writes the report as CSV, one entry per row. If the report has
+    /// sections, a first `section` column gives the section of each entry
+    /// (empty for the entries outside of any section).
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut wtr =
+            csv::Writer::from_path(path).with_context(|_| format!("Error writing {:?}", path))?;
+        let mut reports = vec![(None, self)];
+        if !self.sections.is_empty() {
+            reports[0].0 = Some("");
+            reports.extend(self.sections().map(|(name, section)| (Some(name), section)));
+        }
+        for (section, report) in reports {
+            for &severity in &[Severity::Error, Severity::Warning, Severity::Info] {
+                for entry in report.entries(severity) {
+                    wtr.serialize(ReportRow {
+                        section,
+                        severity,
+                        category: entry.category,
+                        object_type: entry.object_type.as_ref().map(ObjectType::as_str),
+                        object_id: entry.object_id.as_deref(),
+                        message: &entry.message,
+                        count: entry.count,
+                    })
+                    .with_context(|_| format!("Error writing {:?}", path))?;
+                }
+            }
+        }
+        wtr.flush()
+            .with_context(|_| format!("Error writing {:?}", path))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    fn report() -> Report {
+        let mut report = Report::default();
+        for _ in 0..2 {
+            report.add(
+                Severity::Error,
+                ReportEntry::new(ReportType::ObjectNotFound, "line not found".to_string())
+                    .with_object(ObjectType::Line, "line:1"),
+            );
+        }
+        report.add_warning("suspicious value".to_string(), ReportType::InvalidValue);
+        report.add_info("name updated".to_string(), ReportType::PropertyUpdated);
+        report
+    }
+
+    #[test]
+    fn deduplicate_entries() {
+        let report = report();
+        assert_eq!(1, report.entries(Severity::Error).len());
+        assert_eq!(2, report.entries(Severity::Error)[0].count);
+        assert_eq!(1, report.entries(Severity::Warning).len());
+        assert_eq!(1, report.entries(Severity::Info).len());
+    }
+
+    #[test]
+    fn merge_reports() {
+        let mut merged = report();
+        merged.merge(report());
+        assert_eq!(1, merged.entries(Severity::Error).len());
+        assert_eq!(4, merged.entries(Severity::Error)[0].count);
+        assert_eq!(2, merged.entries(Severity::Warning)[0].count);
+
+        let mut step_report = report();
+        step_report
+            .section("fares.zip")
+            .add_error("ticket conflict".to_string(), ReportType::Conflict);
+        merged.merge_as("fares", step_report);
+        let sections: Vec<(&str, usize)> = merged
+            .sections()
+            .map(|(name, section)| (name, section.entries(Severity::Error).len()))
+            .collect();
+        assert_eq!(vec![("fares", 1), ("fares/fares.zip", 1)], sections);
+        assert_eq!(4, merged.entries(Severity::Error)[0].count);
+    }
+
+    #[test]
+    fn write_report_as_csv() {
+        test_in_tmp_dir(|path| {
+            let report_path = path.join("report.csv");
+            report().write_csv(&report_path).unwrap();
+            assert_eq!(
+                "severity,category,object_type,object_id,message,count\n\
+                 error,object_not_found,line,line:1,line not found,2\n\
+                 warning,invalid_value,,,suspicious value,1\n\
+                 info,property_updated,,,name updated,1\n",
+                fs::read_to_string(report_path).unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn write_report_with_sections_as_csv() {
+        test_in_tmp_dir(|path| {
+            let report_path = path.join("report.csv");
+            let mut report = Report::default();
+            report.add_warning("suspicious value".to_string(), ReportType::InvalidValue);
+            report
+                .section("fares.zip")
+                .add_error("ticket conflict".to_string(), ReportType::Conflict);
+            report.write_csv(&report_path).unwrap();
+            assert_eq!(
+                "section,severity,category,object_type,object_id,message,count\n\
+                 ,warning,invalid_value,,,suspicious value,1\n\
+                 fares.zip,error,conflict,,,ticket conflict,1\n",
+                fs::read_to_string(report_path).unwrap()
+            );
+        });
+    }
+}
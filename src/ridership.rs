@@ -0,0 +1,80 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! See function read_ridership
+
+use crate::{model::Model, objects::Line, Result};
+use failure::ResultExt;
+use log::{info, warn};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Name of the object property holding the average daily boardings of a line
+pub const AVERAGE_DAILY_BOARDINGS: &str = "average_daily_boardings";
+
+#[derive(Debug, Deserialize)]
+struct Ridership {
+    line_id: String,
+    average_daily_boardings: f64,
+}
+
+/// Imports the ridership of lines from the CSV file `path`, with the columns
+/// `line_id` and `average_daily_boardings`.
+///
+/// The ridership is stored in the object properties of the lines, with the
+/// key [AVERAGE_DAILY_BOARDINGS](constant.AVERAGE_DAILY_BOARDINGS.html),
+/// replacing any previous value. Unknown lines and negative values are
+/// logged and ignored.
+pub fn read_ridership<P: AsRef<Path>>(model: Model, path: P) -> Result<Model> {
+    let path = path.as_ref();
+    info!("Reading ridership {:?}", path);
+    let mut collections = model.into_collections();
+    let mut rdr =
+        csv::Reader::from_path(path).with_context(|_| format!("Error reading {:?}", path))?;
+    for ridership in rdr.deserialize() {
+        let ridership: Ridership =
+            ridership.with_context(|_| format!("Error reading {:?}", path))?;
+        if ridership.average_daily_boardings < 0.0 {
+            warn!(
+                "negative average daily boardings {} for line {:?}, ignored",
+                ridership.average_daily_boardings, ridership.line_id
+            );
+            continue;
+        }
+        let line_idx = match collections.lines.get_idx(&ridership.line_id) {
+            Some(line_idx) => line_idx,
+            None => {
+                warn!("line {:?} not found, ridership ignored", ridership.line_id);
+                continue;
+            }
+        };
+        let mut line = collections.lines.index_mut(line_idx);
+        line.object_properties
+            .retain(|(key, _)| key != AVERAGE_DAILY_BOARDINGS);
+        line.object_properties.insert((
+            AVERAGE_DAILY_BOARDINGS.to_string(),
+            ridership.average_daily_boardings.to_string(),
+        ));
+    }
+    Model::new(collections)
+}
+
+/// Average daily boardings of `line`, as imported by
+/// [read_ridership](fn.read_ridership.html).
+pub fn line_ridership(line: &Line) -> Option<f64> {
+    line.object_properties
+        .iter()
+        .find(|(key, _)| key == AVERAGE_DAILY_BOARDINGS)
+        .and_then(|(_, value)| value.parse().ok())
+}
@@ -0,0 +1,455 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+//! Description of the columns of the files read by the crate. The
+//! descriptions are not written by hand: they are computed by probing the
+//! deserialization of the structures used to read each file, so they can't
+//! diverge from what the readers actually expect.
+//!
+//! See [ntfs::explain](../ntfs/fn.explain.html) and
+//! [gtfs::explain](../gtfs/fn.explain.html).
+
+use serde::{
+    de::{
+        self, value::StrDeserializer, DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess,
+        IntoDeserializer, MapAccess, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any,
+};
+use std::{cell::RefCell, fmt, vec};
+
+/// A column of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Name of the column
+    pub name: &'static str,
+    /// Expected type of the values of the column
+    pub column_type: String,
+    /// Whether the column must be present in the file
+    pub mandatory: bool,
+}
+
+/// Description of the columns of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSchema {
+    /// Name of the file
+    pub file_name: String,
+    /// Columns of the file
+    pub columns: Vec<Column>,
+}
+
+impl fmt::Display for FileSchema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.file_name)?;
+        for column in &self.columns {
+            let presence = if column.mandatory {
+                "mandatory"
+            } else {
+                "optional"
+            };
+            writeln!(
+                f,
+                "  {}: {} ({})",
+                column.name, column.column_type, presence
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Values tried, in this order, when a column is deserialized from a string,
+// with the type reported when the value is accepted.
+const CANDIDATES: &[(&str, &str)] = &[
+    ("", "string"),
+    ("1", "number"),
+    ("00:00:00", "time (HH:MM:SS)"),
+    ("20200101", "date (YYYYMMDD)"),
+    ("FFFFFF", "color (RRGGBB)"),
+    ("Europe/Paris", "timezone"),
+    ("EUR", "currency code (ISO 4217)"),
+    ("POINT(0 0)", "geometry (WKT)"),
+];
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Text,
+    Integer,
+    Decimal,
+    Boolean,
+    Enum(&'static [&'static str]),
+}
+
+impl Kind {
+    fn describe(&self, attempt: usize) -> String {
+        match self {
+            Kind::Text => CANDIDATES[attempt].1.to_string(),
+            Kind::Integer => "integer".to_string(),
+            Kind::Decimal => "decimal".to_string(),
+            Kind::Boolean => "boolean".to_string(),
+            Kind::Enum(variants) => format!("one of {}", variants.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ProbeError {
+    Fields(&'static [&'static str]),
+    MissingField(&'static str),
+    Custom(String),
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProbeError::Fields(fields) => write!(f, "fields {:?}", fields),
+            ProbeError::MissingField(field) => write!(f, "missing field {:?}", field),
+            ProbeError::Custom(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl de::Error for ProbeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ProbeError::Custom(msg.to_string())
+    }
+    fn missing_field(field: &'static str) -> Self {
+        ProbeError::MissingField(field)
+    }
+}
+
+// Deserializer of the value of a column: records the kind of value
+// requested and answers with the `attempt`-th candidate.
+struct ProbeValue<'a> {
+    attempt: usize,
+    kind: &'a RefCell<Option<Kind>>,
+}
+
+impl<'a> ProbeValue<'a> {
+    fn record(&self, kind: Kind) {
+        let mut recorded_kind = self.kind.borrow_mut();
+        if recorded_kind.is_none() {
+            *recorded_kind = Some(kind);
+        }
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for ProbeValue<'a> {
+    type Error = ProbeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.record(Kind::Text);
+        visitor.visit_str(CANDIDATES[self.attempt].0)
+    }
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.record(Kind::Boolean);
+        visitor.visit_bool(self.attempt % 2 == 1)
+    }
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_i64(visitor)
+    }
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_i64(visitor)
+    }
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_i64(visitor)
+    }
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.record(Kind::Integer);
+        visitor.visit_i64(self.attempt as i64)
+    }
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_u64(visitor)
+    }
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_u64(visitor)
+    }
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_u64(visitor)
+    }
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.record(Kind::Integer);
+        visitor.visit_u64(self.attempt as u64)
+    }
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_f64(visitor)
+    }
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.record(Kind::Decimal);
+        visitor.visit_f64(self.attempt as f64)
+    }
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_any(visitor)
+    }
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_any(visitor)
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_some(self)
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.record(Kind::Enum(variants));
+        match variants.first() {
+            Some(variant) => visitor.visit_enum(ProbeVariant(variant)),
+            None => Err(ProbeError::Custom("enum without variant".to_string())),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        char bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct ProbeVariant(&'static str);
+
+impl<'de> EnumAccess<'de> for ProbeVariant {
+    type Error = ProbeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), ProbeError> {
+        let deserializer: StrDeserializer<ProbeError> = self.0.into_deserializer();
+        let variant = seed.deserialize(deserializer)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ProbeVariant {
+    type Error = ProbeError;
+
+    fn unit_variant(self) -> Result<(), ProbeError> {
+        Ok(())
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        _seed: T,
+    ) -> Result<T::Value, ProbeError> {
+        Err(ProbeError::Custom("unsupported enum variant".to_string()))
+    }
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        Err(ProbeError::Custom("unsupported enum variant".to_string()))
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        Err(ProbeError::Custom("unsupported enum variant".to_string()))
+    }
+}
+
+// Deserializer of a whole row: without entries, it only returns the names of
+// the fields of the structure, otherwise it gives the entries as a map.
+struct ProbeRow<'a> {
+    entries: Option<Vec<(&'static str, ProbeValue<'a>)>>,
+}
+
+impl<'de, 'a> Deserializer<'de> for ProbeRow<'a> {
+    type Error = ProbeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, ProbeError> {
+        Err(ProbeError::Custom("a structure is expected".to_string()))
+    }
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        match self.entries {
+            None => Err(ProbeError::Fields(fields)),
+            Some(entries) => visitor.visit_map(ProbeMap {
+                entries: entries.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum
+        identifier ignored_any
+    }
+}
+
+struct ProbeMap<'a> {
+    entries: vec::IntoIter<(&'static str, ProbeValue<'a>)>,
+    value: Option<ProbeValue<'a>>,
+}
+
+impl<'de, 'a> MapAccess<'de> for ProbeMap<'a> {
+    type Error = ProbeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ProbeError> {
+        match self.entries.next() {
+            Some((name, value)) => {
+                self.value = Some(value);
+                let deserializer: StrDeserializer<ProbeError> = name.into_deserializer();
+                seed.deserialize(deserializer).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ProbeError> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(ProbeError::Custom("value without column".to_string())),
+        }
+    }
+}
+
+fn deserialize_row<T: DeserializeOwned>(
+    values: Vec<(&'static str, usize)>,
+    kind: &RefCell<Option<Kind>>,
+) -> Result<T, ProbeError> {
+    let entries = values
+        .into_iter()
+        .map(|(name, attempt)| (name, ProbeValue { attempt, kind }))
+        .collect();
+    T::deserialize(ProbeRow {
+        entries: Some(entries),
+    })
+}
+
+// Finds the first candidate accepted for the column, with the kind of value
+// requested by the deserialization.
+fn probe_column<T: DeserializeOwned>(name: &'static str) -> Option<(usize, Kind)> {
+    (0..CANDIDATES.len()).find_map(|attempt| {
+        let kind = RefCell::new(None);
+        match deserialize_row::<T>(vec![(name, attempt)], &kind) {
+            Ok(_) | Err(ProbeError::MissingField(_)) => kind.into_inner().map(|k| (attempt, k)),
+            Err(_) => None,
+        }
+    })
+}
+
+/// Computes the columns of a file read with the structure `T`.
+///
+/// A column is mandatory if a row can't be read without it; its type is
+/// deduced from the values accepted when reading it.
+pub(crate) fn columns<T: DeserializeOwned>() -> Vec<Column> {
+    let names = match T::deserialize(ProbeRow { entries: None }) {
+        Err(ProbeError::Fields(names)) => names,
+        _ => return vec![],
+    };
+    let probes: Vec<_> = names.iter().map(|&name| probe_column::<T>(name)).collect();
+    names
+        .iter()
+        .zip(&probes)
+        .map(|(&name, probe)| {
+            let others = names
+                .iter()
+                .zip(&probes)
+                .filter(|(other, _)| **other != name)
+                .filter_map(|(&other, probe)| probe.as_ref().map(|(attempt, _)| (other, *attempt)))
+                .collect();
+            let mandatory = match deserialize_row::<T>(others, &RefCell::new(None)) {
+                Err(ProbeError::MissingField(missing)) => missing == name,
+                _ => false,
+            };
+            let column_type = probe.as_ref().map_or_else(
+                || "unknown".to_string(),
+                |(attempt, kind)| kind.describe(*attempt),
+            );
+            Column {
+                name,
+                column_type,
+                mandatory,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Contributor, Geometry, Network, PhysicalMode, Transfer};
+    use pretty_assertions::assert_eq;
+
+    fn describe(columns: Vec<Column>) -> Vec<(&'static str, String, bool)> {
+        columns
+            .into_iter()
+            .map(|c| (c.name, c.column_type, c.mandatory))
+            .collect()
+    }
+
+    #[test]
+    fn columns_of_contributor() {
+        assert_eq!(
+            vec![
+                ("contributor_id", "string".to_string(), true),
+                ("contributor_name", "string".to_string(), true),
+                ("contributor_license", "string".to_string(), false),
+                ("contributor_website", "string".to_string(), false),
+            ],
+            describe(columns::<Contributor>())
+        );
+    }
+
+    #[test]
+    fn columns_of_physical_mode() {
+        assert_eq!(
+            vec![
+                ("physical_mode_id", "string".to_string(), true),
+                ("physical_mode_name", "string".to_string(), true),
+                ("co2_emission", "decimal".to_string(), false),
+            ],
+            describe(columns::<PhysicalMode>())
+        );
+    }
+
+    #[test]
+    fn columns_with_parsed_values() {
+        let network_columns = describe(columns::<Network>());
+        assert_eq!(
+            ("network_timezone", "timezone".to_string(), false),
+            network_columns[3]
+        );
+        let geometry_columns = describe(columns::<Geometry>());
+        assert_eq!(
+            ("geometry_wkt", "geometry (WKT)".to_string(), true),
+            geometry_columns[1]
+        );
+        let transfer_columns = describe(columns::<Transfer>());
+        assert_eq!(
+            ("min_transfer_time", "integer".to_string(), false),
+            transfer_columns[2]
+        );
+    }
+}
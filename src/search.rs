@@ -0,0 +1,370 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! In-memory text search over the names and codes of the stop areas, stop
+//! points and lines, e.g. to offer autocomplete in interactive tools, see
+//! [Model::search](../model/struct.Model.html#method.search).
+//!
+//! The texts are split into words, compared case-insensitively and without
+//! accents. Each word of a query must match a word of the object, either
+//! exactly, as a prefix or, for the words of at least 4 characters, with a
+//! few typos.
+
+use crate::{
+    model::{Collections, Model},
+    objects::{KeysValues, ObjectType},
+};
+use std::collections::{BTreeMap, HashMap};
+use typed_index_collection::{CollectionWithId, Id};
+
+/// An object found by a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    /// Identifier of the object
+    pub id: String,
+    /// Name of the object
+    pub name: String,
+    /// Relevance of the match, the lower the better (0 when all the words
+    /// of the query match exactly)
+    pub score: u32,
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Default)]
+struct ObjectIndex {
+    entries: Vec<Entry>,
+    // normalized words, with the entries containing them
+    words: BTreeMap<String, Vec<usize>>,
+}
+
+/// Index of the names and codes of the stop areas, stop points and lines
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    stop_areas: ObjectIndex,
+    stop_points: ObjectIndex,
+    lines: ObjectIndex,
+}
+
+fn fold_char(c: char) -> &'static str {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => "a",
+        'ç' => "c",
+        'è' | 'é' | 'ê' | 'ë' => "e",
+        'ì' | 'í' | 'î' | 'ï' => "i",
+        'ñ' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => "o",
+        'ù' | 'ú' | 'û' | 'ü' => "u",
+        'ý' | 'ÿ' => "y",
+        'æ' => "ae",
+        'œ' => "oe",
+        'ß' => "ss",
+        _ => "",
+    }
+}
+
+// Lowercase words of `text`, without accents
+fn normalized_words(text: &str) -> Vec<String> {
+    let mut normalized = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match fold_char(c) {
+            "" if c.is_alphanumeric() => normalized.push(c),
+            "" => normalized.push(' '),
+            folded => normalized.push_str(folded),
+        }
+    }
+    normalized.split_whitespace().map(str::to_string).collect()
+}
+
+// Levenshtein distance between `s1` and `s2`, or `None` if it is above
+// `max_distance`
+fn bounded_distance(s1: &str, s2: &str, max_distance: usize) -> Option<usize> {
+    let s1: Vec<char> = s1.chars().collect();
+    let s2: Vec<char> = s2.chars().collect();
+    if s1.len().max(s2.len()) - s1.len().min(s2.len()) > max_distance {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=s2.len()).collect();
+    for (i, c1) in s1.iter().enumerate() {
+        let mut current = vec![i + 1; s2.len() + 1];
+        for (j, c2) in s2.iter().enumerate() {
+            let substitution = previous[j] + if c1 == c2 { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().min().map_or(true, |&min| min > max_distance) {
+            return None;
+        }
+        previous = current;
+    }
+    Some(previous[s2.len()]).filter(|&distance| distance <= max_distance)
+}
+
+// Number of typos allowed in a word of the query
+fn max_typos(word: &str) -> usize {
+    match word.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+impl ObjectIndex {
+    fn new<T, F>(objects: &CollectionWithId<T>, texts: F) -> Self
+    where
+        T: Id<T>,
+        F: Fn(&T) -> (String, Vec<String>),
+    {
+        let mut index = ObjectIndex::default();
+        for object in objects.values() {
+            let (name, codes) = texts(object);
+            let entry_idx = index.entries.len();
+            let words = std::iter::once(&name)
+                .chain(&codes)
+                .flat_map(|text| normalized_words(text));
+            for word in words {
+                let entries = index.words.entry(word).or_insert_with(Vec::new);
+                if entries.last() != Some(&entry_idx) {
+                    entries.push(entry_idx);
+                }
+            }
+            index.entries.push(Entry {
+                id: object.id().to_string(),
+                name,
+            });
+        }
+        index
+    }
+
+    // Best score of each entry having a word matching `query_word`
+    fn matches(&self, query_word: &str) -> HashMap<usize, u32> {
+        let mut scores = HashMap::new();
+        let mut add = |entries: &[usize], score: u32| {
+            for &entry_idx in entries {
+                let best = scores.entry(entry_idx).or_insert(score);
+                *best = (*best).min(score);
+            }
+        };
+        for (word, entries) in self.words.range(query_word.to_string()..) {
+            if !word.starts_with(query_word) {
+                break;
+            }
+            add(entries, if word == query_word { 0 } else { 1 });
+        }
+        let max_typos = max_typos(query_word);
+        if max_typos > 0 {
+            for (word, entries) in &self.words {
+                if let Some(distance) = bounded_distance(query_word, word, max_typos) {
+                    if distance > 0 {
+                        add(entries, 1 + distance as u32);
+                    }
+                }
+            }
+        }
+        scores
+    }
+
+    fn search(&self, query: &str) -> Vec<SearchResult> {
+        let mut scores: Option<HashMap<usize, u32>> = None;
+        for query_word in normalized_words(query) {
+            let matches = self.matches(&query_word);
+            scores = Some(match scores {
+                None => matches,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(entry_idx, score)| {
+                        matches.get(&entry_idx).map(|s| (entry_idx, score + s))
+                    })
+                    .collect(),
+            });
+        }
+        let mut results: Vec<SearchResult> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(entry_idx, score)| {
+                let entry = &self.entries[entry_idx];
+                SearchResult {
+                    id: entry.id.clone(),
+                    name: entry.name.clone(),
+                    score,
+                }
+            })
+            .collect();
+        results.sort_by(|r1, r2| {
+            r1.score
+                .cmp(&r2.score)
+                .then_with(|| r1.name.cmp(&r2.name))
+                .then_with(|| r1.id.cmp(&r2.id))
+        });
+        results
+    }
+}
+
+fn code_values(codes: &KeysValues) -> impl Iterator<Item = String> + '_ {
+    codes.iter().map(|(_, value)| value.clone())
+}
+
+impl SearchIndex {
+    /// Indexes the names and codes of the stop areas, stop points and lines
+    /// of the `collections`
+    pub fn new(collections: &Collections) -> Self {
+        SearchIndex {
+            stop_areas: ObjectIndex::new(&collections.stop_areas, |stop_area| {
+                (
+                    stop_area.name.clone(),
+                    code_values(&stop_area.codes).collect(),
+                )
+            }),
+            stop_points: ObjectIndex::new(&collections.stop_points, |stop_point| {
+                let codes = stop_point
+                    .code
+                    .iter()
+                    .cloned()
+                    .chain(code_values(&stop_point.codes))
+                    .collect();
+                (stop_point.name.clone(), codes)
+            }),
+            lines: ObjectIndex::new(&collections.lines, |line| {
+                let codes = line
+                    .code
+                    .iter()
+                    .cloned()
+                    .chain(code_values(&line.codes))
+                    .collect();
+                (line.name.clone(), codes)
+            }),
+        }
+    }
+
+    /// Objects of `object_type` whose names or codes match the `query`,
+    /// the most relevant first. Only the stop areas, stop points and lines
+    /// are indexed, nothing is found for the other object types.
+    pub fn search(&self, query: &str, object_type: ObjectType) -> Vec<SearchResult> {
+        match object_type {
+            ObjectType::StopArea => self.stop_areas.search(query),
+            ObjectType::StopPoint => self.stop_points.search(query),
+            ObjectType::Line => self.lines.search(query),
+            _ => vec![],
+        }
+    }
+}
+
+impl Model {
+    /// Objects of `object_type` (stop areas, stop points or lines) whose
+    /// names or codes match the `query`, the most relevant first, see
+    /// [SearchIndex::search](../search/struct.SearchIndex.html#method.search).
+    ///
+    /// The search index is built on the first search and kept in the model.
+    ///
+    /// ```
+    /// # use transit_model::objects::ObjectType;
+    /// # fn run() -> transit_model::Result<()> {
+    /// let model = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/")?;
+    /// let results = model.search("chat", ObjectType::StopArea);
+    /// assert_eq!("Châtelet", results[0].name);
+    /// # Ok(())
+    /// # }
+    /// # run().unwrap();
+    /// ```
+    pub fn search(&self, query: &str, object_type: ObjectType) -> Vec<SearchResult> {
+        self.search_index().search(query, object_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Line, StopArea};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn normalize_words() {
+        assert_eq!(
+            vec!["zurich", "hb", "strasse", "12"],
+            normalized_words("Zürich HB-Straße (12)")
+        );
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(Some(1), bounded_distance("chatlet", "chatelet", 1));
+        assert_eq!(Some(2), bounded_distance("shatlet", "chatelet", 2));
+        assert_eq!(None, bounded_distance("shatlet", "chatelet", 1));
+        assert_eq!(None, bounded_distance("gare", "nation", 1));
+    }
+
+    fn collections() -> Collections {
+        let stop_area = |id: &str, name: &str| StopArea {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut collections = Collections::default();
+        collections.stop_areas = CollectionWithId::new(vec![
+            stop_area("sa:1", "Gare de Lyon"),
+            stop_area("sa:2", "Gare du Nord"),
+            stop_area("sa:3", "Lyon Perrache"),
+            stop_area("sa:4", "Châtelet"),
+            stop_area("sa:5", "Lyonnais"),
+        ])
+        .unwrap();
+        collections.lines = CollectionWithId::from(Line {
+            id: "line:1".to_string(),
+            name: "Château de Vincennes - La Défense".to_string(),
+            code: Some("M1".to_string()),
+            ..Default::default()
+        });
+        collections
+    }
+
+    fn ids(results: Vec<SearchResult>) -> Vec<String> {
+        results.into_iter().map(|result| result.id).collect()
+    }
+
+    #[test]
+    fn search_by_prefix() {
+        let index = SearchIndex::new(&collections());
+        assert_eq!(
+            vec!["sa:1", "sa:2"],
+            ids(index.search("gare", ObjectType::StopArea))
+        );
+        // exact matches first
+        assert_eq!(
+            vec!["sa:1", "sa:3", "sa:5"],
+            ids(index.search("lyon", ObjectType::StopArea))
+        );
+        assert_eq!(
+            vec!["sa:1"],
+            ids(index.search("gare ly", ObjectType::StopArea))
+        );
+        assert_eq!(vec!["line:1"], ids(index.search("m1", ObjectType::Line)));
+        assert!(index.search("gare", ObjectType::Network).is_empty());
+        assert!(index.search("", ObjectType::StopArea).is_empty());
+    }
+
+    #[test]
+    fn search_with_typos() {
+        let index = SearchIndex::new(&collections());
+        let results = index.search("chatlet", ObjectType::StopArea);
+        assert_eq!(vec!["sa:4"], ids(results.clone()));
+        assert_eq!(2, results[0].score);
+        assert_eq!(
+            vec!["line:1"],
+            ids(index.search("defence", ObjectType::Line))
+        );
+    }
+}
@@ -0,0 +1,361 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Reading of the SIRI-SX situations (the service alerts of the SIRI
+//! Situation Exchange service) into the
+//! [disruptions](../objects/struct.Disruption.html) of a `Collections`.
+//!
+//! The objects impacted by a situation are found by their codes (the
+//! `object_codes.txt` file of a NTFS): the `NetworkRef`, `LineRef`,
+//! `StopPlaceRef` and `StopPointRef` of the `Affects` of the situation are
+//! matched with the networks, lines, stop areas and stop points having a code
+//! of the same value, whatever its system.
+
+use crate::{
+    model::Collections,
+    objects::{Codes, Disruption, DisruptionImpact, ObjectType},
+    report::{Report, ReportType},
+    Result,
+};
+use chrono::{DateTime, FixedOffset};
+use failure::{format_err, ResultExt};
+use log::info;
+use minidom::Element;
+use std::{collections::HashMap, fs, path::Path};
+use typed_index_collection::{Collection, CollectionWithId, Id};
+
+fn child<'a>(element: &'a Element, name: &str) -> Option<&'a Element> {
+    element.children().find(|child| child.name() == name)
+}
+
+fn child_text(element: &Element, name: &str) -> Option<String> {
+    child(element, name)
+        .map(|child| child.text().trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+// The descendants of `element` named `name` (not searched inside each other)
+fn descendants<'a>(element: &'a Element, name: &str, found: &mut Vec<&'a Element>) {
+    for child in element.children() {
+        if child.name() == name {
+            found.push(child);
+        } else {
+            descendants(child, name, found);
+        }
+    }
+}
+
+// Identifiers of the objects by value of their codes
+fn ids_by_code<T: Id<T> + Codes>(collection: &CollectionWithId<T>) -> HashMap<&str, Vec<&str>> {
+    let mut ids: HashMap<&str, Vec<&str>> = HashMap::new();
+    for object in collection.values() {
+        for (_, value) in object.codes() {
+            ids.entry(value.as_str()).or_default().push(object.id());
+        }
+    }
+    ids
+}
+
+fn datetime(
+    element: &Element,
+    name: &str,
+    situation_id: &str,
+    report: &mut Report,
+) -> Option<DateTime<FixedOffset>> {
+    let text = child_text(element, name)?;
+    match DateTime::parse_from_rfc3339(&text) {
+        Ok(datetime) => Some(datetime),
+        Err(_) => {
+            report.add_warning(
+                format!(
+                    "situation {:?}: invalid {} {:?}, ignored",
+                    situation_id, name, text
+                ),
+                ReportType::InvalidValue,
+            );
+            None
+        }
+    }
+}
+
+// The reason given by name, or else the value of the first typed reason
+// (`MiscellaneousReason`, `PersonnelReason`, etc.)
+fn cause(situation: &Element) -> Option<String> {
+    child_text(situation, "ReasonName").or_else(|| {
+        situation
+            .children()
+            .filter(|child| child.name().ends_with("Reason"))
+            .map(|child| child.text().trim().to_string())
+            .find(|text| !text.is_empty())
+    })
+}
+
+fn impacts(
+    situation: &Element,
+    situation_id: &str,
+    code_indexes: &[(ObjectType, &str, HashMap<&str, Vec<&str>>)],
+    report: &mut Report,
+) -> Vec<DisruptionImpact> {
+    let mut impacts: Vec<DisruptionImpact> = vec![];
+    let affects = match child(situation, "Affects") {
+        Some(affects) => affects,
+        None => return impacts,
+    };
+    for (object_type, reference_name, ids) in code_indexes {
+        let mut references = vec![];
+        descendants(affects, reference_name, &mut references);
+        for reference in references {
+            let code = reference.text().trim().to_string();
+            let object_ids = match ids.get(code.as_str()) {
+                Some(object_ids) => object_ids,
+                None => {
+                    report.add_warning(
+                        format!(
+                            "situation {:?}: no {} with the code {:?}",
+                            situation_id,
+                            object_type.as_str(),
+                            code
+                        ),
+                        ReportType::ObjectNotFound,
+                    );
+                    continue;
+                }
+            };
+            for object_id in object_ids {
+                let impact = DisruptionImpact {
+                    disruption_id: situation_id.to_string(),
+                    object_type: object_type.clone(),
+                    object_id: object_id.to_string(),
+                };
+                if !impacts.contains(&impact) {
+                    impacts.push(impact);
+                }
+            }
+        }
+    }
+    impacts
+}
+
+/// Reads the situations of the SIRI-SX file at `path` into the disruptions
+/// of `collections`, identified by their `SituationNumber`.
+///
+/// The situations without number, already read or impacting no known object
+/// are ignored and reported, as well as the references to unknown objects.
+pub fn read<P: AsRef<Path>>(
+    path: P,
+    collections: &mut Collections,
+    report: &mut Report,
+) -> Result<()> {
+    let path = path.as_ref();
+    info!("Reading SIRI-SX situations {:?}", path);
+    let xml = fs::read_to_string(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let root: Element = xml
+        .parse()
+        .map_err(|e| format_err!("Error reading {:?}: {}", path, e))?;
+    let mut situations = vec![];
+    descendants(&root, "PtSituationElement", &mut situations);
+
+    let mut disruptions: Vec<Disruption> = vec![];
+    let mut disruption_impacts = vec![];
+    {
+        let code_indexes = [
+            (
+                ObjectType::Network,
+                "NetworkRef",
+                ids_by_code(&collections.networks),
+            ),
+            (ObjectType::Line, "LineRef", ids_by_code(&collections.lines)),
+            (
+                ObjectType::StopArea,
+                "StopPlaceRef",
+                ids_by_code(&collections.stop_areas),
+            ),
+            (
+                ObjectType::StopPoint,
+                "StopPointRef",
+                ids_by_code(&collections.stop_points),
+            ),
+        ];
+        for situation in situations {
+            let id = match child_text(situation, "SituationNumber") {
+                Some(id) => id,
+                None => {
+                    report.add_warning(
+                        "situation without SituationNumber, ignored".to_string(),
+                        ReportType::InvalidValue,
+                    );
+                    continue;
+                }
+            };
+            if collections.disruptions.contains_id(&id)
+                || disruptions.iter().any(|disruption| disruption.id == id)
+            {
+                report.add_warning(
+                    format!("disruption {:?} already exists, situation ignored", id),
+                    ReportType::Conflict,
+                );
+                continue;
+            }
+            let situation_impacts = impacts(situation, &id, &code_indexes, report);
+            if situation_impacts.is_empty() {
+                report.add_warning(
+                    format!("situation {:?} impacts no known object, ignored", id),
+                    ReportType::ObjectNotFound,
+                );
+                continue;
+            }
+            let period = child(situation, "ValidityPeriod");
+            disruptions.push(Disruption {
+                summary: child_text(situation, "Summary"),
+                description: child_text(situation, "Description"),
+                severity: child_text(situation, "Severity"),
+                cause: cause(situation),
+                start_datetime: period.and_then(|p| datetime(p, "StartTime", &id, report)),
+                end_datetime: period.and_then(|p| datetime(p, "EndTime", &id, report)),
+                id,
+            });
+            disruption_impacts.extend(situation_impacts);
+        }
+    }
+
+    for disruption in disruptions {
+        collections.disruptions.push(disruption)?;
+    }
+    let mut impacts = collections.disruption_impacts.take();
+    impacts.extend(disruption_impacts);
+    collections.disruption_impacts = Collection::new(impacts);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        objects::{Line, StopPoint},
+        report::Severity,
+        test_utils::test_in_tmp_dir,
+    };
+    use pretty_assertions::assert_eq;
+
+    const SITUATIONS: &str = r#"<Siri xmlns="http://www.siri.org.uk/siri" version="2.0">
+  <ServiceDelivery>
+    <SituationExchangeDelivery>
+      <Situations>
+        <PtSituationElement>
+          <SituationNumber>works</SituationNumber>
+          <ValidityPeriod>
+            <StartTime>2020-03-01T05:00:00+01:00</StartTime>
+            <EndTime>not a date</EndTime>
+          </ValidityPeriod>
+          <MiscellaneousReason>roadworks</MiscellaneousReason>
+          <Severity>severe</Severity>
+          <Summary>Works</Summary>
+          <Description>The stop is moved.</Description>
+          <Affects>
+            <Networks>
+              <AffectedNetwork>
+                <AffectedLine>
+                  <LineRef>L1</LineRef>
+                </AffectedLine>
+                <AffectedLine>
+                  <LineRef>L9</LineRef>
+                </AffectedLine>
+              </AffectedNetwork>
+            </Networks>
+            <StopPoints>
+              <AffectedStopPoint>
+                <StopPointRef>SP1</StopPointRef>
+              </AffectedStopPoint>
+            </StopPoints>
+          </Affects>
+        </PtSituationElement>
+        <PtSituationElement>
+          <SituationNumber>unknown</SituationNumber>
+          <Affects>
+            <StopPoints>
+              <AffectedStopPoint>
+                <StopPointRef>SP9</StopPointRef>
+              </AffectedStopPoint>
+            </StopPoints>
+          </Affects>
+        </PtSituationElement>
+      </Situations>
+    </SituationExchangeDelivery>
+  </ServiceDelivery>
+</Siri>"#;
+
+    #[test]
+    fn read_situations() {
+        let mut collections = Collections::default();
+        let mut line = Line {
+            id: "line:1".to_string(),
+            ..Default::default()
+        };
+        line.codes.insert(("source".to_string(), "L1".to_string()));
+        collections.lines = CollectionWithId::from(line);
+        let mut stop_point = StopPoint {
+            id: "sp:1".to_string(),
+            ..Default::default()
+        };
+        stop_point
+            .codes
+            .insert(("source".to_string(), "SP1".to_string()));
+        collections.stop_points = CollectionWithId::from(stop_point);
+        let mut report = Report::default();
+        test_in_tmp_dir(|path| {
+            let siri_path = path.join("siri_sx.xml");
+            fs::write(&siri_path, SITUATIONS).unwrap();
+            read(&siri_path, &mut collections, &mut report).unwrap();
+        });
+
+        assert_eq!(1, collections.disruptions.len());
+        let disruption = collections.disruptions.get("works").unwrap();
+        assert_eq!(Some("Works"), disruption.summary.as_deref());
+        assert_eq!(Some("roadworks"), disruption.cause.as_deref());
+        assert_eq!(Some("severe"), disruption.severity.as_deref());
+        assert_eq!(
+            Some("2020-03-01T05:00:00+01:00".to_string()),
+            disruption
+                .start_datetime
+                .map(|datetime| datetime.to_rfc3339())
+        );
+        assert_eq!(None, disruption.end_datetime);
+        let impacts: Vec<(ObjectType, &str)> = collections
+            .disruption_impacts
+            .values()
+            .map(|impact| (impact.object_type.clone(), impact.object_id.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (ObjectType::Line, "line:1"),
+                (ObjectType::StopPoint, "sp:1"),
+            ],
+            impacts
+        );
+        let warnings: Vec<ReportType> = report
+            .entries(Severity::Warning)
+            .iter()
+            .map(|entry| entry.category)
+            .collect();
+        assert_eq!(
+            vec![
+                ReportType::ObjectNotFound,
+                ReportType::InvalidValue,
+                ReportType::ObjectNotFound,
+                ReportType::ObjectNotFound,
+            ],
+            warnings
+        );
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Export of a `Model` to a [SQLite](https://sqlite.org) database, so the
+//! converted datasets can be queried directly.
+//!
+//! The database is normalized the same way as the
+//! [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md):
+//! each NTFS file is a table named after the file (e.g. `stops` for
+//! `stops.txt`) with the same columns, the identifiers being primary keys and
+//! the references between objects foreign keys. This module needs the
+//! `sqlite` feature.
+
+use crate::{
+    ntfs::{self, WriteOptions},
+    Model, Result,
+};
+use chrono::{DateTime, FixedOffset};
+use failure::ResultExt;
+use log::info;
+use rusqlite::{params_from_iter, Connection};
+use std::{fs, path::Path};
+use tempfile::tempdir;
+
+// (table, primary key)
+const PRIMARY_KEYS: &[(&str, &str)] = &[
+    ("booking_rules", "booking_rule_id"),
+    ("calendar", "service_id"),
+    ("comments", "comment_id"),
+    ("commercial_modes", "commercial_mode_id"),
+    ("companies", "company_id"),
+    ("contributors", "contributor_id"),
+    ("datasets", "dataset_id"),
+    ("disruptions", "disruption_id"),
+    ("equipments", "equipment_id"),
+    ("fare_zones", "fare_zone_id"),
+    ("geometries", "geometry_id"),
+    ("grid_calendars", "grid_calendar_id"),
+    ("levels", "level_id"),
+    ("lines", "line_id"),
+    ("networks", "network_id"),
+    ("pathways", "pathway_id"),
+    ("physical_modes", "physical_mode_id"),
+    ("routes", "route_id"),
+    ("stops", "stop_id"),
+    ("ticket_uses", "ticket_use_id"),
+    ("tickets", "ticket_id"),
+    ("trip_properties", "trip_property_id"),
+    ("trips", "trip_id"),
+];
+
+// (table, column, referenced table)
+const FOREIGN_KEYS: &[(&str, &str, &str)] = &[
+    ("admin_stations", "stop_id", "stops"),
+    ("booking_rule_links", "booking_rule_id", "booking_rules"),
+    ("calendar_dates", "service_id", "calendar"),
+    ("comment_links", "comment_id", "comments"),
+    ("company_parents", "company_id", "companies"),
+    ("company_parents", "parent_company_id", "companies"),
+    ("datasets", "contributor_id", "contributors"),
+    ("disruption_impacts", "disruption_id", "disruptions"),
+    ("frequencies", "trip_id", "trips"),
+    ("grid_exception_dates", "grid_calendar_id", "grid_calendars"),
+    ("grid_periods", "grid_calendar_id", "grid_calendars"),
+    (
+        "grid_rel_calendar_line",
+        "grid_calendar_id",
+        "grid_calendars",
+    ),
+    ("grid_rel_calendar_line", "line_id", "lines"),
+    ("lines", "commercial_mode_id", "commercial_modes"),
+    ("lines", "geometry_id", "geometries"),
+    ("lines", "network_id", "networks"),
+    ("pathways", "from_stop_id", "stops"),
+    ("pathways", "to_stop_id", "stops"),
+    ("routes", "destination_id", "stops"),
+    ("routes", "geometry_id", "geometries"),
+    ("routes", "line_id", "lines"),
+    ("stop_area_memberships", "stop_area_id", "stops"),
+    ("stop_area_memberships", "stop_id", "stops"),
+    ("stop_times", "stop_id", "stops"),
+    ("stop_times", "trip_id", "trips"),
+    ("stops", "equipment_id", "equipments"),
+    ("stops", "geometry_id", "geometries"),
+    ("stops", "level_id", "levels"),
+    ("stops", "parent_station", "stops"),
+    ("ticket_prices", "ticket_id", "tickets"),
+    ("ticket_use_perimeters", "ticket_use_id", "ticket_uses"),
+    ("ticket_use_restrictions", "ticket_use_id", "ticket_uses"),
+    ("ticket_uses", "ticket_id", "tickets"),
+    ("transfers", "from_stop_id", "stops"),
+    ("transfers", "to_stop_id", "stops"),
+    ("trips", "company_id", "companies"),
+    ("trips", "dataset_id", "datasets"),
+    ("trips", "geometry_id", "geometries"),
+    ("trips", "physical_mode_id", "physical_modes"),
+    ("trips", "route_id", "routes"),
+    ("trips", "service_id", "calendar"),
+    ("trips", "trip_property_id", "trip_properties"),
+];
+
+// SQLite type of a column, from the type expected by the NTFS reader
+fn column_type(file_name: &str, column: &str) -> &'static str {
+    let column_type = ntfs::explain(file_name).and_then(|schema| {
+        schema
+            .columns
+            .into_iter()
+            .find(|c| c.name == column)
+            .map(|c| c.column_type)
+    });
+    match column_type.as_deref() {
+        Some("integer") | Some("boolean") => "INTEGER",
+        Some("decimal") | Some("number") => "NUMERIC",
+        _ => "TEXT",
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn create_table_statement(table: &str, file_name: &str, headers: &[String]) -> String {
+    let mut definitions: Vec<String> = headers
+        .iter()
+        .map(|header| {
+            let mut definition = format!("{} {}", quote(header), column_type(file_name, header));
+            if PRIMARY_KEYS.contains(&(table, header.as_str())) {
+                definition.push_str(" PRIMARY KEY");
+            }
+            definition
+        })
+        .collect();
+    let foreign_keys = FOREIGN_KEYS
+        .iter()
+        .filter(|(t, c, _)| *t == table && headers.iter().any(|header| header == c))
+        .filter_map(|(_, column, referenced_table)| {
+            PRIMARY_KEYS
+                .iter()
+                .find(|(t, _)| t == referenced_table)
+                .map(|(_, referenced_column)| (column, referenced_table, referenced_column))
+        });
+    for (column, referenced_table, referenced_column) in foreign_keys {
+        definitions.push(format!(
+            "FOREIGN KEY ({}) REFERENCES {} ({})",
+            quote(column),
+            quote(referenced_table),
+            quote(referenced_column)
+        ));
+    }
+    format!("CREATE TABLE {} ({})", quote(table), definitions.join(", "))
+}
+
+fn load_file(connection: &mut Connection, path: &Path) -> Result<()> {
+    let file_name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(file_name) => file_name,
+        None => return Ok(()),
+    };
+    let table = file_name.trim_end_matches(".txt");
+    let mut rdr =
+        csv::Reader::from_path(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let headers: Vec<String> = rdr
+        .headers()
+        .with_context(|_| format!("Error reading {:?}", path))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    let transaction = connection.transaction()?;
+    transaction
+        .execute(&create_table_statement(table, file_name, &headers), [])
+        .with_context(|_| format!("Error creating the table {:?}", table))?;
+    {
+        let mut statement = transaction.prepare(&format!(
+            "INSERT INTO {} VALUES ({})",
+            quote(table),
+            vec!["?"; headers.len()].join(", ")
+        ))?;
+        for record in rdr.records() {
+            let record = record.with_context(|_| format!("Error reading {:?}", path))?;
+            // empty values are NULL, as they are missing values in the NTFS
+            let values = record
+                .iter()
+                .map(|value| if value.is_empty() { None } else { Some(value) });
+            statement
+                .execute(params_from_iter(values))
+                .with_context(|_| format!("Error inserting into the table {:?}", table))?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Exports a `Model` to a SQLite database at the given full path, replacing
+/// the database if it already exists.
+///
+/// The foreign keys are declared but, as SQLite does by default, not
+/// enforced; enable them with `PRAGMA foreign_keys = ON` to check the
+/// references of the dataset.
+pub fn write<P: AsRef<Path>>(
+    model: &Model,
+    path: P,
+    current_datetime: DateTime<FixedOffset>,
+) -> Result<()> {
+    let path = path.as_ref();
+    info!("Writing SQLite database {:?}", path);
+    let ntfs_tmp_dir = tempdir()?;
+    // the fares V1 files aren't NTFS objects but their legacy encoding
+    let options = WriteOptions {
+        fares_v1: false,
+        ..Default::default()
+    };
+    ntfs::write_with_options(model, ntfs_tmp_dir.path(), current_datetime, &options)?;
+    if path.exists() {
+        fs::remove_file(path).with_context(|_| format!("Error removing {:?}", path))?;
+    }
+    let mut connection =
+        Connection::open(path).with_context(|_| format!("Error opening {:?}", path))?;
+    let mut file_paths = Vec::new();
+    for entry in fs::read_dir(ntfs_tmp_dir.path())? {
+        let file_path = entry?.path();
+        if file_path.extension().map_or(false, |ext| ext == "txt") {
+            file_paths.push(file_path);
+        }
+    }
+    file_paths.sort();
+    for file_path in file_paths {
+        load_file(&mut connection, &file_path)?;
+    }
+    ntfs_tmp_dir.close()?;
+    Ok(())
+}
@@ -0,0 +1,355 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Statistics on the content of a `Model`
+
+use crate::{
+    model::{GetCorresponding, Model},
+    objects::{Route, StopTime, VehicleJourney},
+    ridership::line_ridership,
+    zones, Result,
+};
+use failure::ResultExt;
+use relational_types::IdxSet;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
+
+/// Statistics of a line
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LineStatistics {
+    /// Identifier of the line
+    pub line_id: String,
+    /// Number of routes of the line
+    pub nb_routes: usize,
+    /// Number of vehicle journeys of the line
+    pub nb_vehicle_journeys: usize,
+    /// Number of stop times of the line
+    pub nb_stop_times: usize,
+    /// Number of zones served by the line, for the zonal on-demand services
+    /// (see [zones](../zones/index.html))
+    pub nb_zones: usize,
+    /// Sum of the known lengths of the vehicle journeys of the line, rounded
+    /// to the meter, if any is known
+    pub total_length: Option<u64>,
+    /// Average daily boardings of the line, if known
+    pub average_daily_boardings: Option<f64>,
+}
+
+/// Computes the statistics of each line of the `model`, sorted by line
+/// identifier.
+pub fn line_statistics(model: &Model) -> Vec<LineStatistics> {
+    let mut statistics: Vec<LineStatistics> = model
+        .lines
+        .iter()
+        .map(|(line_idx, line)| {
+            let routes: IdxSet<Route> = model.get_corresponding_from_idx(line_idx);
+            let vehicle_journeys: IdxSet<VehicleJourney> =
+                model.get_corresponding_from_idx(line_idx);
+            LineStatistics {
+                line_id: line.id.clone(),
+                nb_routes: routes.len(),
+                nb_vehicle_journeys: vehicle_journeys.len(),
+                nb_stop_times: vehicle_journeys
+                    .iter()
+                    .map(|vj_idx| model.vehicle_journeys[*vj_idx].stop_times.len())
+                    .sum(),
+                nb_zones: zones::line_zones(model, line_idx).len(),
+                total_length: vehicle_journeys
+                    .iter()
+                    .filter_map(|vj_idx| model.vehicle_journeys[*vj_idx].length)
+                    .fold(None, |total, length| Some(total.unwrap_or(0.) + length))
+                    .map(|total: f64| total.round() as u64),
+                average_daily_boardings: line_ridership(line),
+            }
+        })
+        .collect();
+    statistics.sort_by(|s1, s2| s1.line_id.cmp(&s2.line_id));
+    statistics
+}
+
+/// Writes the statistics of each line of the `model` as CSV in `path`.
+pub fn write_line_statistics<P: AsRef<Path>>(model: &Model, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let mut wtr =
+        csv::Writer::from_path(path).with_context(|_| format!("Error writing {:?}", path))?;
+    for statistics in line_statistics(model) {
+        wtr.serialize(statistics)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    wtr.flush()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
+/// Statistics of an operator group: a company with its subsidiaries (see
+/// [CompanyParent](../objects/struct.CompanyParent.html))
+#[derive(Debug, Serialize, PartialEq)]
+pub struct OperatorGroupStatistics {
+    /// Identifier of the company at the top of the group
+    pub company_id: String,
+    /// Number of companies of the group operating vehicle journeys
+    pub nb_operators: usize,
+    /// Number of lines operated by the group
+    pub nb_lines: usize,
+    /// Number of vehicle journeys operated by the group
+    pub nb_vehicle_journeys: usize,
+    /// Number of stop times of the vehicle journeys operated by the group
+    pub nb_stop_times: usize,
+}
+
+/// Computes the statistics of each operator group of the `model`, sorted by
+/// the identifier of the company at the top of the group.
+pub fn operator_group_statistics(model: &Model) -> Vec<OperatorGroupStatistics> {
+    let mut groups: BTreeMap<&str, (BTreeSet<&str>, BTreeSet<&str>, usize, usize)> =
+        BTreeMap::new();
+    let mut group_ids: BTreeMap<&str, &str> = BTreeMap::new();
+    for vj in model.vehicle_journeys.values() {
+        let group_id = *group_ids.entry(vj.company_id.as_str()).or_insert_with(|| {
+            model
+                .parent_company_ids(&vj.company_id)
+                .last()
+                .cloned()
+                .unwrap_or(vj.company_id.as_str())
+        });
+        let (operators, lines, nb_vehicle_journeys, nb_stop_times) =
+            groups.entry(group_id).or_default();
+        operators.insert(vj.company_id.as_str());
+        if let Some(route) = model.routes.get(&vj.route_id) {
+            lines.insert(route.line_id.as_str());
+        }
+        *nb_vehicle_journeys += 1;
+        *nb_stop_times += vj.stop_times.len();
+    }
+    groups
+        .into_iter()
+        .map(
+            |(company_id, (operators, lines, nb_vehicle_journeys, nb_stop_times))| {
+                OperatorGroupStatistics {
+                    company_id: company_id.to_string(),
+                    nb_operators: operators.len(),
+                    nb_lines: lines.len(),
+                    nb_vehicle_journeys,
+                    nb_stop_times,
+                }
+            },
+        )
+        .collect()
+}
+
+/// Writes the statistics of each operator group of the `model` as CSV in
+/// `path`.
+pub fn write_operator_group_statistics<P: AsRef<Path>>(model: &Model, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let mut wtr =
+        csv::Writer::from_path(path).with_context(|_| format!("Error writing {:?}", path))?;
+    for statistics in operator_group_statistics(model) {
+        wtr.serialize(statistics)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    wtr.flush()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
+/// A cell of the line × physical mode × network matrix: the vehicle journeys
+/// of a line running with a physical mode
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LineModeNetworkStatistics {
+    /// Identifier of the line
+    pub line_id: String,
+    /// Identifier of the commercial mode of the line
+    pub commercial_mode_id: String,
+    /// Identifier of the network of the line
+    pub network_id: String,
+    /// Identifier of the physical mode of the vehicle journeys
+    pub physical_mode_id: String,
+    /// Number of vehicle journeys of the line with the physical mode
+    pub nb_vehicle_journeys: usize,
+}
+
+/// Computes the line × physical mode × network matrix of the `model`, with
+/// the number of vehicle journeys of each cell, sorted by line and physical
+/// mode identifiers. Empty cells are omitted.
+pub fn line_mode_network_statistics(model: &Model) -> Vec<LineModeNetworkStatistics> {
+    let mut matrix: BTreeMap<(&str, &str, &str, &str), usize> = BTreeMap::new();
+    for vj in model.vehicle_journeys.values() {
+        let line = match model
+            .routes
+            .get(&vj.route_id)
+            .and_then(|route| model.lines.get(&route.line_id))
+        {
+            Some(line) => line,
+            None => continue,
+        };
+        *matrix
+            .entry((
+                line.id.as_str(),
+                line.commercial_mode_id.as_str(),
+                line.network_id.as_str(),
+                vj.physical_mode_id.as_str(),
+            ))
+            .or_insert(0) += 1;
+    }
+    matrix
+        .into_iter()
+        .map(
+            |((line_id, commercial_mode_id, network_id, physical_mode_id), nb_vehicle_journeys)| {
+                LineModeNetworkStatistics {
+                    line_id: line_id.to_string(),
+                    commercial_mode_id: commercial_mode_id.to_string(),
+                    network_id: network_id.to_string(),
+                    physical_mode_id: physical_mode_id.to_string(),
+                    nb_vehicle_journeys,
+                }
+            },
+        )
+        .collect()
+}
+
+/// Writes the line × physical mode × network matrix of the `model` as CSV in
+/// `path`.
+pub fn write_line_mode_network_statistics<P: AsRef<Path>>(model: &Model, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let mut wtr =
+        csv::Writer::from_path(path).with_context(|_| format!("Error writing {:?}", path))?;
+    for statistics in line_mode_network_statistics(model) {
+        wtr.serialize(statistics)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    wtr.flush()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
+/// Direct journeys from a stop area to another one on a line, for capacity
+/// planning
+#[derive(Debug, Serialize, PartialEq)]
+pub struct OdStatistics {
+    /// Identifier of the line
+    pub line_id: String,
+    /// Identifier of the stop area where the journeys start
+    pub origin_stop_area_id: String,
+    /// Identifier of the stop area where the journeys end
+    pub destination_stop_area_id: String,
+    /// Average number of journeys per day over the period of the calendars,
+    /// rounded to 2 decimals
+    pub daily_journeys: f64,
+    /// Average travel time of the journeys in seconds
+    pub average_travel_time: u32,
+}
+
+/// Computes, for each pair of stop areas served in this order by a vehicle
+/// journey of a line, the average daily number of direct journeys and their
+/// average travel time, sorted by line, origin and destination identifiers.
+///
+/// A journey boards where the pickup is allowed and alights where the drop
+/// off is allowed; the fastest one is kept when a vehicle journey serves the
+/// pair several times. A vehicle journey with frequencies runs once per
+/// headway of each of them, from their start time (included) to their end
+/// time (excluded). The days are counted over the period from the first to
+/// the last date of the calendars.
+pub fn od_statistics(model: &Model) -> Vec<OdStatistics> {
+    let dates = model
+        .calendars
+        .values()
+        .flat_map(|calendar| calendar.dates.iter());
+    let nb_days = match (dates.clone().min(), dates.max()) {
+        (Some(first_date), Some(last_date)) => (*last_date - *first_date).num_days() + 1,
+        _ => return vec![],
+    };
+    // vehicle journey -> number of runs per day, for the vehicle journeys
+    // with frequencies
+    let mut nb_runs: HashMap<&str, u64> = HashMap::new();
+    for frequency in model.frequencies.values() {
+        let runs = nb_runs
+            .entry(frequency.vehicle_journey_id.as_str())
+            .or_default();
+        if frequency.headway_secs > 0 && frequency.start_time < frequency.end_time {
+            let duration = (frequency.end_time - frequency.start_time).total_seconds();
+            *runs += u64::from((duration + frequency.headway_secs - 1) / frequency.headway_secs);
+        }
+    }
+    // (line, origin, destination) -> (number of journeys, total travel time)
+    let mut journeys: BTreeMap<(&str, &str, &str), (u64, u64)> = BTreeMap::new();
+    for vj in model.vehicle_journeys.values() {
+        let nb_dates = model
+            .calendars
+            .get(&vj.service_id)
+            .map_or(0, |calendar| calendar.dates.len() as u64)
+            * nb_runs.get(vj.id.as_str()).cloned().unwrap_or(1);
+        let line_id = match model.routes.get(&vj.route_id) {
+            Some(route) if nb_dates > 0 => route.line_id.as_str(),
+            _ => continue,
+        };
+        let stop_area_id =
+            |st: &StopTime| model.stop_points[st.stop_point_idx].stop_area_id.as_str();
+        let mut travel_times: BTreeMap<(&str, &str), u32> = BTreeMap::new();
+        for (i, boarding) in vj.stop_times.iter().enumerate() {
+            if boarding.pickup_type == 1 {
+                continue;
+            }
+            for alighting in &vj.stop_times[i + 1..] {
+                let (origin, destination) = (stop_area_id(boarding), stop_area_id(alighting));
+                if alighting.drop_off_type == 1
+                    || origin == destination
+                    || alighting.arrival_time < boarding.departure_time
+                {
+                    continue;
+                }
+                let travel_time =
+                    (alighting.arrival_time - boarding.departure_time).total_seconds();
+                let fastest = travel_times
+                    .entry((origin, destination))
+                    .or_insert(travel_time);
+                *fastest = (*fastest).min(travel_time);
+            }
+        }
+        for ((origin, destination), travel_time) in travel_times {
+            let (nb_journeys, total_travel_time) =
+                journeys.entry((line_id, origin, destination)).or_default();
+            *nb_journeys += nb_dates;
+            *total_travel_time += nb_dates * u64::from(travel_time);
+        }
+    }
+    journeys
+        .into_iter()
+        .map(
+            |((line_id, origin, destination), (nb_journeys, total_travel_time))| OdStatistics {
+                line_id: line_id.to_string(),
+                origin_stop_area_id: origin.to_string(),
+                destination_stop_area_id: destination.to_string(),
+                daily_journeys: (nb_journeys as f64 / nb_days as f64 * 100.).round() / 100.,
+                average_travel_time: (total_travel_time / nb_journeys) as u32,
+            },
+        )
+        .collect()
+}
+
+/// Writes the direct journeys between the stop areas of each line of the
+/// `model` (see [od_statistics](fn.od_statistics.html)) as CSV in `path`.
+pub fn write_od_statistics<P: AsRef<Path>>(model: &Model, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let mut wtr =
+        csv::Writer::from_path(path).with_context(|_| format!("Error writing {:?}", path))?;
+    for statistics in od_statistics(model) {
+        wtr.serialize(statistics)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    wtr.flush()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
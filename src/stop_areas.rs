@@ -0,0 +1,412 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! See function generate_stop_areas
+
+use crate::{
+    model::{Collections, Model},
+    objects::{Coord, ObjectType, StopArea, StopPoint},
+    report::{Report, ReportEntry, ReportType, Severity},
+    Result,
+};
+use log::info;
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+use typed_index_collection::{CollectionWithId, Idx};
+
+/// Object system of the codes added to a generated stop area, one for each
+/// of the stop points it groups
+pub const SOURCE_STOP_POINT_SYSTEM: &str = "source_stop_point";
+
+/// Rules to group stop points into a generated stop area
+#[derive(Debug, Clone)]
+pub struct StopAreaGenerationConfiguration {
+    /// Maximum distance in meters between 2 grouped stop points
+    pub max_distance: f64,
+    /// Minimum similarity of the names of 2 grouped stop points, between 0
+    /// (no word in common) and 1 (same words)
+    pub min_name_similarity: f64,
+}
+
+impl Default for StopAreaGenerationConfiguration {
+    fn default() -> Self {
+        StopAreaGenerationConfiguration {
+            max_distance: 200.0,
+            min_name_similarity: 0.5,
+        }
+    }
+}
+
+/// Groups the stop points of the `model` without parent station into
+/// generated stop areas, see
+/// [Collections::generate_stop_areas](../model/struct.Collections.html#method.generate_stop_areas).
+///
+/// The changes are written in `report_path`, as CSV if it has a `.csv`
+/// extension, as JSON otherwise.
+pub fn generate_stop_areas<P: AsRef<Path>>(
+    model: Model,
+    configuration: &StopAreaGenerationConfiguration,
+    report_path: P,
+) -> Result<Model> {
+    info!("Generating stop areas...");
+    let mut report = Report::default();
+    let mut collections = model.into_collections();
+    collections.generate_stop_areas(configuration, &mut report)?;
+
+    let report_path = report_path.as_ref();
+    info!("Writing report {:?}", report_path);
+    report.write(report_path)?;
+
+    Model::new(collections)
+}
+
+fn name_words(name: &str) -> BTreeSet<String> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Ratio of the words in common among all the words of the 2 names
+fn name_similarity(words1: &BTreeSet<String>, words2: &BTreeSet<String>) -> f64 {
+    let union = words1.union(words2).count();
+    if union == 0 {
+        return 0.0;
+    }
+    words1.intersection(words2).count() as f64 / union as f64
+}
+
+// Whether the stop area `stop_area_id` was created by a reader for the stop
+// point `stop_point_id` without parent station: `Navitia:<stop point id>`,
+// the prefix of the dataset being before `Navitia:` once prefixed
+fn is_generated_for(stop_area_id: &str, stop_point_id: &str) -> bool {
+    const GENERATED_PREFIX: &str = "Navitia:";
+    match stop_area_id.find(GENERATED_PREFIX) {
+        Some(position) => {
+            let prefix = &stop_area_id[..position];
+            let suffix = &stop_area_id[position + GENERATED_PREFIX.len()..];
+            stop_point_id.len() == prefix.len() + suffix.len()
+                && stop_point_id.starts_with(prefix)
+                && stop_point_id.ends_with(suffix)
+        }
+        None => false,
+    }
+}
+
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+impl Collections {
+    /// Groups the stop points without parent station into generated stop
+    /// areas.
+    ///
+    /// A stop point has no parent station when it's alone in the stop area
+    /// created for it by the reader (`Navitia:<stop point id>`), the other
+    /// stop areas being left untouched. Such stop points are grouped when
+    /// they are within `max_distance` of each other and their names are
+    /// similar enough, directly or through other stop points of the group.
+    ///
+    /// The stop area of the first stop point of each group (by identifier)
+    /// is kept, moved to the centroid of the group and given a
+    /// `source_stop_point` code for each of the grouped stop points. The
+    /// other stop areas of the group are removed and reported, their codes,
+    /// object properties and comments being moved to the kept stop area.
+    pub fn generate_stop_areas(
+        &mut self,
+        configuration: &StopAreaGenerationConfiguration,
+        report: &mut Report,
+    ) -> Result<()> {
+        let mut nb_stop_points: HashMap<&str, usize> = HashMap::new();
+        for stop_point in self.stop_points.values() {
+            *nb_stop_points
+                .entry(stop_point.stop_area_id.as_str())
+                .or_insert(0) += 1;
+        }
+        let is_without_parent = |stop_point: &StopPoint| {
+            nb_stop_points.get(stop_point.stop_area_id.as_str()) == Some(&1)
+                && self.stop_areas.contains_id(&stop_point.stop_area_id)
+                && is_generated_for(&stop_point.stop_area_id, &stop_point.id)
+        };
+        let mut candidates: Vec<(Idx<StopPoint>, &StopPoint, BTreeSet<String>)> = self
+            .stop_points
+            .iter()
+            .filter(|(_, stop_point)| is_without_parent(stop_point))
+            .map(|(idx, stop_point)| (idx, stop_point, name_words(&stop_point.name)))
+            .collect();
+        candidates.sort_by(|(_, sp1, _), (_, sp2, _)| {
+            sp1.coord
+                .lat
+                .partial_cmp(&sp2.coord.lat)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        // the candidates are sorted by latitude, so only the following ones
+        // less than max_distance north can be close enough
+        let max_lat_delta = configuration.max_distance / crate::distances::EARTH_RADIUS;
+        let max_lat_delta = max_lat_delta.to_degrees();
+        let mut parents: Vec<usize> = (0..candidates.len()).collect();
+        for (i, (_, sp1, words1)) in candidates.iter().enumerate() {
+            for (j, (_, sp2, words2)) in candidates.iter().enumerate().skip(i + 1) {
+                if sp2.coord.lat - sp1.coord.lat > max_lat_delta {
+                    break;
+                }
+                if sp1.coord.distance_to(&sp2.coord) <= configuration.max_distance
+                    && name_similarity(words1, words2) >= configuration.min_name_similarity
+                {
+                    let (root1, root2) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                    parents[root1] = root2;
+                }
+            }
+        }
+        let mut groups: HashMap<usize, Vec<Idx<StopPoint>>> = HashMap::new();
+        for (i, (idx, _, _)) in candidates.iter().enumerate() {
+            let root = find_root(&mut parents, i);
+            groups.entry(root).or_insert_with(Vec::new).push(*idx);
+        }
+
+        // stop area kept for each grouped stop point, with its new
+        // coordinates and codes
+        let mut new_stop_area_ids: HashMap<Idx<StopPoint>, String> = HashMap::new();
+        let mut updates: HashMap<String, (Coord, Vec<(String, String)>)> = HashMap::new();
+        // kept stop area by removed stop area
+        let mut removed_ids: HashMap<String, String> = HashMap::new();
+        for group in groups.values_mut() {
+            if group.len() < 2 {
+                continue;
+            }
+            group.sort_by(|idx1, idx2| self.stop_points[*idx1].id.cmp(&self.stop_points[*idx2].id));
+            let kept_id = self.stop_points[group[0]].stop_area_id.clone();
+            let nb = group.len() as f64;
+            let mut coord = Coord::default();
+            let mut codes = vec![];
+            for idx in &group {
+                let stop_point = &self.stop_points[*idx];
+                coord.lon += stop_point.coord.lon / nb;
+                coord.lat += stop_point.coord.lat / nb;
+                codes.push((SOURCE_STOP_POINT_SYSTEM.to_string(), stop_point.id.clone()));
+                if stop_point.stop_area_id != kept_id {
+                    report.add(
+                        Severity::Info,
+                        ReportEntry::new(
+                            ReportType::ObjectMerged,
+                            format!(
+                                "stop area {:?} of stop point {:?} merged into the generated stop area {:?}",
+                                stop_point.stop_area_id, stop_point.id, kept_id
+                            ),
+                        )
+                        .with_object(ObjectType::StopArea, &stop_point.stop_area_id),
+                    );
+                    removed_ids.insert(stop_point.stop_area_id.clone(), kept_id.clone());
+                    new_stop_area_ids.insert(*idx, kept_id.clone());
+                }
+            }
+            updates.insert(kept_id, (coord, codes));
+        }
+        if removed_ids.is_empty() {
+            return Ok(());
+        }
+        info!(
+            "{} stop points grouped into {} generated stop areas",
+            removed_ids.len() + updates.len(),
+            updates.len()
+        );
+
+        for (idx, stop_area_id) in new_stop_area_ids {
+            self.stop_points.index_mut(idx).stop_area_id = stop_area_id;
+        }
+        let (removed_stop_areas, mut stop_areas): (Vec<StopArea>, Vec<StopArea>) = self
+            .stop_areas
+            .take()
+            .into_iter()
+            .partition(|stop_area| removed_ids.contains_key(&stop_area.id));
+        // the codes, object properties and comments of the removed stop areas
+        // by kept stop area
+        let mut removed_by_kept_id: HashMap<&str, Vec<StopArea>> = HashMap::new();
+        for stop_area in removed_stop_areas {
+            removed_by_kept_id
+                .entry(removed_ids[&stop_area.id].as_str())
+                .or_insert_with(Vec::new)
+                .push(stop_area);
+        }
+        for stop_area in &mut stop_areas {
+            if let Some((coord, codes)) = updates.remove(&stop_area.id) {
+                stop_area.coord = coord;
+                stop_area.codes.extend(codes);
+            }
+            for removed in removed_by_kept_id
+                .remove(stop_area.id.as_str())
+                .unwrap_or_else(Vec::new)
+            {
+                stop_area.codes.extend(removed.codes);
+                stop_area
+                    .object_properties
+                    .extend(removed.object_properties);
+                stop_area.comment_links.extend(removed.comment_links);
+            }
+        }
+        self.stop_areas = CollectionWithId::<StopArea>::new(stop_areas)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn collections() -> Collections {
+        let mut collections = Collections::default();
+        let mut push = |id: &str, name: &str, lon: f64, stop_area_id: Option<&str>| {
+            let mut stop_point = StopPoint {
+                id: id.to_string(),
+                name: name.to_string(),
+                coord: Coord { lon, lat: 48.85 },
+                ..Default::default()
+            };
+            match stop_area_id {
+                Some(stop_area_id) => stop_point.stop_area_id = stop_area_id.to_string(),
+                None => {
+                    let stop_area = StopArea::from(stop_point.clone());
+                    stop_point.stop_area_id = stop_area.id.clone();
+                    collections.stop_areas.push(stop_area).unwrap();
+                }
+            }
+            collections.stop_points.push(stop_point).unwrap();
+        };
+        // about 73 m between each stop point
+        push("sp:1", "Gare de Lyon", 2.371, None);
+        push("sp:2", "Gare de Lyon - Quai 2", 2.372, None);
+        push("sp:3", "Gare de Lyon", 2.373, None);
+        push("sp:4", "Nation", 2.374, None);
+        push("sp:5", "Gare de Lyon", 2.380, None);
+        push("sp:6", "Gare de Lyon", 2.370, Some("sa:1"));
+        // a stop area of the data, with the name and coordinates of its
+        // only stop point
+        push("sp:7", "Gare de Lyon", 2.3715, Some("sa:2"));
+        for (id, name, lon) in &[
+            ("sa:1", "Gare de Lyon (station)", 2.370),
+            ("sa:2", "Gare de Lyon", 2.3715),
+        ] {
+            collections
+                .stop_areas
+                .push(StopArea {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    coord: Coord {
+                        lon: *lon,
+                        lat: 48.85,
+                    },
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let idx = collections.stop_areas.get_idx("Navitia:sp:2").unwrap();
+        let mut stop_area = collections.stop_areas.index_mut(idx);
+        stop_area
+            .codes
+            .insert(("source".to_string(), "quai_2".to_string()));
+        stop_area.comment_links.insert("comment:1".to_string());
+        drop(stop_area);
+        collections
+    }
+
+    #[test]
+    fn stop_areas_generated_by_the_readers() {
+        assert!(is_generated_for("Navitia:sp:1", "sp:1"));
+        assert!(is_generated_for("pre:Navitia:sp:1", "pre:sp:1"));
+        assert!(!is_generated_for("Navitia:sp:1", "sp:2"));
+        assert!(!is_generated_for("sa:1", "sp:1"));
+    }
+
+    #[test]
+    fn words_similarity() {
+        let similarity = name_similarity(
+            &name_words("Gare de Lyon"),
+            &name_words("gare de lyon - quai 2"),
+        );
+        assert_eq!(0.6, similarity);
+        assert_eq!(0.0, name_similarity(&name_words(""), &name_words("")));
+    }
+
+    #[test]
+    fn group_close_stop_points_with_similar_names() {
+        let mut collections = collections();
+        let mut report = Report::default();
+        collections
+            .generate_stop_areas(&StopAreaGenerationConfiguration::default(), &mut report)
+            .unwrap();
+
+        let stop_area_ids: Vec<(&str, &str)> = collections
+            .stop_points
+            .values()
+            .map(|sp| (sp.id.as_str(), sp.stop_area_id.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("sp:1", "Navitia:sp:1"),
+                ("sp:2", "Navitia:sp:1"),
+                ("sp:3", "Navitia:sp:1"),
+                ("sp:4", "Navitia:sp:4"),
+                ("sp:5", "Navitia:sp:5"),
+                ("sp:6", "sa:1"),
+                ("sp:7", "sa:2"),
+            ],
+            stop_area_ids
+        );
+        let stop_area = collections.stop_areas.get("Navitia:sp:1").unwrap();
+        assert!((stop_area.coord.lon - 2.372).abs() < 1e-9);
+        let codes: Vec<&str> = stop_area
+            .codes
+            .iter()
+            .map(|(_, code)| code.as_str())
+            .collect();
+        // the code of the removed stop area of sp:2 is carried over
+        assert_eq!(vec!["quai_2", "sp:1", "sp:2", "sp:3"], codes);
+        assert!(stop_area.comment_links.contains("comment:1"));
+        assert!(!collections.stop_areas.contains_id("Navitia:sp:2"));
+        assert!(!collections.stop_areas.contains_id("Navitia:sp:3"));
+        assert_eq!(5, collections.stop_areas.len());
+        assert_eq!(2, report.entries(Severity::Info).len());
+    }
+
+    #[test]
+    fn no_grouping_with_a_strict_configuration() {
+        let mut collections = collections();
+        let mut report = Report::default();
+        let configuration = StopAreaGenerationConfiguration {
+            max_distance: 200.0,
+            min_name_similarity: 1.0,
+        };
+        collections
+            .generate_stop_areas(&configuration, &mut report)
+            .unwrap();
+        // only sp:1 and sp:3 have the same name
+        assert_eq!(
+            "Navitia:sp:1",
+            collections.stop_points.get("sp:3").unwrap().stop_area_id
+        );
+        assert_eq!(
+            "Navitia:sp:2",
+            collections.stop_points.get("sp:2").unwrap().stop_area_id
+        );
+        assert_eq!(1, report.entries(Severity::Info).len());
+    }
+}
@@ -0,0 +1,450 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Matching of the stops of an external referential with the stops of a
+//! model, see [match_stops](fn.match_stops.html), and merge of the
+//! identifiers of the external stops as codes of the matched stops, see
+//! [add_matched_codes](fn.add_matched_codes.html)
+
+use crate::{
+    objects::{Codes, Coord, GetObjectType, StopArea, StopPoint},
+    report::{Report, ReportEntry, ReportType, Severity},
+};
+use std::collections::{BTreeMap, HashMap};
+use typed_index_collection::{CollectionWithId, Id, Idx};
+
+/// A stop of an external referential
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExternalStop {
+    /// Identifier of the stop in the external referential
+    pub id: String,
+    /// Name of the stop, if known
+    pub name: Option<String>,
+    /// Geolocation of the stop, if known
+    pub coord: Option<Coord>,
+}
+
+/// The stops of a model that can be matched: stop areas and stop points
+pub trait MatchableStop: Id<Self> + Codes + GetObjectType + Sized {
+    /// Name of the stop
+    fn name(&self) -> &str;
+    /// Geolocation of the stop
+    fn coord(&self) -> &Coord;
+}
+
+impl MatchableStop for StopArea {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn coord(&self) -> &Coord {
+        &self.coord
+    }
+}
+
+impl MatchableStop for StopPoint {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn coord(&self) -> &Coord {
+        &self.coord
+    }
+}
+
+/// Finds the stops matching a stop of an external referential.
+///
+/// [CodeMatcher](struct.CodeMatcher.html) and
+/// [NameDistanceMatcher](struct.NameDistanceMatcher.html) are provided; any
+/// other matching logic can be used by implementing this trait.
+pub trait StopMatcher<T> {
+    /// Returns the stops of `stops` matching `external_stop`: none, one, or
+    /// several when the match is ambiguous.
+    fn match_stop(&self, stops: &CollectionWithId<T>, external_stop: &ExternalStop) -> Vec<Idx<T>>;
+}
+
+/// Matches the stops having the code `(object_system, id)`, `id` being the
+/// identifier of the external stop.
+#[derive(Debug, Clone)]
+pub struct CodeMatcher {
+    /// System of the codes of the external referential (e.g. `UIC`)
+    pub object_system: String,
+}
+
+impl<T: MatchableStop> StopMatcher<T> for CodeMatcher {
+    fn match_stop(&self, stops: &CollectionWithId<T>, external_stop: &ExternalStop) -> Vec<Idx<T>> {
+        stops
+            .iter()
+            .filter(|(_, stop)| {
+                stop.codes().iter().any(|(system, code)| {
+                    *system == self.object_system && *code == external_stop.id
+                })
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+/// Matches the stops with the same name (ignoring the case and the
+/// surrounding spaces) as the external stop, within `max_distance` meters of
+/// it when it's geolocated. An external stop without name matches no stop.
+///
+/// The stops are indexed on a grid of cells of about `max_distance` when the
+/// matcher is built, only the stops of the cells around a geolocated
+/// external stop being compared to it.
+#[derive(Debug, Clone)]
+pub struct NameDistanceMatcher<T> {
+    max_distance: f64,
+    // size of the cells of the grid, in degrees
+    cell_size: f64,
+    grid: HashMap<(i64, i64), Vec<Idx<T>>>,
+}
+
+// Smallest length in meters of a degree of latitude, the cells being a bit
+// larger than the maximum distance
+const METERS_BY_DEGREE: f64 = 110_000.;
+
+fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+impl<T: MatchableStop> NameDistanceMatcher<T> {
+    /// Builds the matcher of the `stops` within `max_distance` meters of the
+    /// external stops
+    pub fn new(stops: &CollectionWithId<T>, max_distance: f64) -> Self {
+        let cell_size = max_distance.max(1.) / METERS_BY_DEGREE;
+        let mut grid = HashMap::new();
+        for (idx, stop) in stops.iter() {
+            grid.entry(Self::cell(cell_size, stop.coord()))
+                .or_insert_with(Vec::new)
+                .push(idx);
+        }
+        NameDistanceMatcher {
+            max_distance,
+            cell_size,
+            grid,
+        }
+    }
+
+    fn cell(cell_size: f64, coord: &Coord) -> (i64, i64) {
+        (
+            (coord.lon / cell_size).floor() as i64,
+            (coord.lat / cell_size).floor() as i64,
+        )
+    }
+
+    // The stops of the cells within `max_distance` of `coord`: the adjacent
+    // cells in latitude, more cells in longitude as the meridians converge
+    fn stops_around(&self, coord: &Coord) -> Vec<Idx<T>> {
+        let (lon_cell, lat_cell) = Self::cell(self.cell_size, coord);
+        let max_lat = (coord.lat.abs() + self.cell_size).min(89.);
+        let lon_cells = (1. / max_lat.to_radians().cos()).ceil() as i64;
+        let mut idxs = vec![];
+        for lon in lon_cell - lon_cells..=lon_cell + lon_cells {
+            for lat in lat_cell - 1..=lat_cell + 1 {
+                if let Some(cell_idxs) = self.grid.get(&(lon, lat)) {
+                    idxs.extend(cell_idxs);
+                }
+            }
+        }
+        idxs.sort();
+        idxs
+    }
+}
+
+impl<T: MatchableStop> StopMatcher<T> for NameDistanceMatcher<T> {
+    fn match_stop(&self, stops: &CollectionWithId<T>, external_stop: &ExternalStop) -> Vec<Idx<T>> {
+        let name = match &external_stop.name {
+            Some(name) => normalize_name(name),
+            None => return vec![],
+        };
+        let same_name = |idx: &Idx<T>| normalize_name(stops[*idx].name()) == name;
+        match external_stop.coord {
+            Some(coord) => self
+                .stops_around(&coord)
+                .into_iter()
+                .filter(same_name)
+                .filter(|idx| coord.distance_to(stops[*idx].coord()) <= self.max_distance)
+                .collect(),
+            None => stops.iter().map(|(idx, _)| idx).filter(same_name).collect(),
+        }
+    }
+}
+
+/// Matches each of the `external_stops` with the `stops` using the `matcher`,
+/// returning the stop matched by the identifier of each external stop.
+///
+/// The external stops matching no stop, or several stops, are not in the
+/// result: they are added to the warnings of the `report`.
+pub fn match_stops<T, M>(
+    stops: &CollectionWithId<T>,
+    external_stops: &[ExternalStop],
+    matcher: &M,
+    report: &mut Report,
+) -> BTreeMap<String, Idx<T>>
+where
+    T: MatchableStop,
+    M: StopMatcher<T> + ?Sized,
+{
+    let mut matches = BTreeMap::new();
+    for external_stop in external_stops {
+        let idxs = matcher.match_stop(stops, external_stop);
+        match idxs.as_slice() {
+            [] => report.add_warning(
+                format!("external stop {:?} matches no stop", external_stop.id),
+                ReportType::ObjectNotFound,
+            ),
+            [idx] => {
+                matches.insert(external_stop.id.clone(), *idx);
+            }
+            _ => {
+                let stop_ids: Vec<&str> = idxs.iter().map(|idx| stops[*idx].id()).collect();
+                report.add(
+                    Severity::Warning,
+                    ReportEntry::new(
+                        ReportType::AmbiguousStopMatch,
+                        format!(
+                            "external stop {:?} matches several stops: {}",
+                            external_stop.id,
+                            stop_ids.join(", ")
+                        ),
+                    )
+                    .with_object(T::get_object_type(), stop_ids[0]),
+                );
+            }
+        }
+    }
+    matches
+}
+
+/// Adds the identifier of each of the `external_stops` as a code
+/// `(object_system, id)` of the stop it matches with the `matcher`, see
+/// [match_stops](fn.match_stops.html).
+///
+/// Returns the number of codes added, the stops already having the code
+/// being unchanged.
+pub fn add_matched_codes<T, M>(
+    stops: &mut CollectionWithId<T>,
+    external_stops: &[ExternalStop],
+    object_system: &str,
+    matcher: &M,
+    report: &mut Report,
+) -> usize
+where
+    T: MatchableStop,
+    M: StopMatcher<T> + ?Sized,
+{
+    let matches = match_stops(stops, external_stops, matcher, report);
+    let mut added = 0;
+    for (external_id, idx) in matches {
+        if stops
+            .index_mut(idx)
+            .codes_mut()
+            .insert((object_system.to_string(), external_id))
+        {
+            added += 1;
+        }
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn stop_point(id: &str, name: &str, lon: f64, code: Option<&str>) -> StopPoint {
+        StopPoint {
+            id: id.to_string(),
+            name: name.to_string(),
+            coord: Coord { lon, lat: 48.85 },
+            codes: code
+                .map(|code| ("UIC".to_string(), code.to_string()))
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn stop_points() -> CollectionWithId<StopPoint> {
+        CollectionWithId::new(vec![
+            stop_point("sp:1", "Gare de Lyon", 2.373, Some("8768600")),
+            // about 730 m east of sp:1
+            stop_point("sp:2", "Gare de Lyon", 2.383, Some("8768600")),
+            stop_point("sp:3", "Nation", 2.396, Some("8775860")),
+        ])
+        .unwrap()
+    }
+
+    fn external_stop(id: &str, name: Option<&str>, lon: Option<f64>) -> ExternalStop {
+        ExternalStop {
+            id: id.to_string(),
+            name: name.map(str::to_string),
+            coord: lon.map(|lon| Coord { lon, lat: 48.85 }),
+        }
+    }
+
+    fn matched_ids(
+        stop_points: &CollectionWithId<StopPoint>,
+        matches: &BTreeMap<String, Idx<StopPoint>>,
+    ) -> Vec<(String, String)> {
+        matches
+            .iter()
+            .map(|(id, idx)| (id.clone(), stop_points[*idx].id.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn match_by_code() {
+        let stop_points = stop_points();
+        let matcher = CodeMatcher {
+            object_system: "UIC".to_string(),
+        };
+        let mut report = Report::default();
+        let matches = match_stops(
+            &stop_points,
+            &[
+                external_stop("8775860", None, None),
+                external_stop("8768600", None, None),
+                external_stop("0000000", None, None),
+            ],
+            &matcher,
+            &mut report,
+        );
+        assert_eq!(
+            vec![("8775860".to_string(), "sp:3".to_string())],
+            matched_ids(&stop_points, &matches)
+        );
+        let categories: Vec<ReportType> = report
+            .entries(Severity::Warning)
+            .iter()
+            .map(|entry| entry.category)
+            .collect();
+        assert_eq!(
+            vec![ReportType::AmbiguousStopMatch, ReportType::ObjectNotFound],
+            categories
+        );
+        assert_eq!(
+            "external stop \"8768600\" matches several stops: sp:1, sp:2",
+            report.entries(Severity::Warning)[0].message
+        );
+    }
+
+    #[test]
+    fn match_by_name_and_distance() {
+        let stop_points = stop_points();
+        let matcher = NameDistanceMatcher::new(&stop_points, 500.0);
+        let mut report = Report::default();
+        let matches = match_stops(
+            &stop_points,
+            &[
+                external_stop("lyon_west", Some(" gare de LYON"), Some(2.372)),
+                external_stop("lyon", Some("Gare de Lyon"), None),
+                external_stop("nation", Some("Nation"), Some(2.373)),
+                external_stop("no_name", None, Some(2.396)),
+            ],
+            &matcher,
+            &mut report,
+        );
+        assert_eq!(
+            vec![("lyon_west".to_string(), "sp:1".to_string())],
+            matched_ids(&stop_points, &matches)
+        );
+        assert_eq!(3, report.entries(Severity::Warning).len());
+    }
+
+    #[test]
+    fn match_by_distance_across_cells() {
+        // the cells are about 7 m wide in longitude: sp:1 and sp:2, 9 m
+        // apart, are in different cells around the external stop
+        let stop_points = CollectionWithId::new(vec![
+            stop_point("sp:1", "Nation", 2.396, None),
+            stop_point("sp:2", "Nation", 2.395_88, None),
+        ])
+        .unwrap();
+        let matcher = NameDistanceMatcher::new(&stop_points, 10.0);
+        let idxs = matcher.match_stop(
+            &stop_points,
+            &external_stop("nation", Some("Nation"), Some(2.395_94)),
+        );
+        let ids: Vec<&str> = idxs
+            .iter()
+            .map(|idx| stop_points[*idx].id.as_str())
+            .collect();
+        assert_eq!(vec!["sp:1", "sp:2"], ids);
+    }
+
+    #[test]
+    fn add_codes_of_matched_stops() {
+        let mut stop_points = stop_points();
+        let matcher = CodeMatcher {
+            object_system: "UIC".to_string(),
+        };
+        let mut report = Report::default();
+        let added = add_matched_codes(
+            &mut stop_points,
+            &[
+                external_stop("8775860", None, None),
+                external_stop("8768600", None, None),
+            ],
+            "UIC",
+            &matcher,
+            &mut report,
+        );
+        // sp:3 already has the code, the other one is ambiguous
+        assert_eq!(0, added);
+
+        let matcher = NameDistanceMatcher::new(&stop_points, 500.0);
+        let added = add_matched_codes(
+            &mut stop_points,
+            &[external_stop("N1", Some("Nation"), Some(2.397))],
+            "external",
+            &matcher,
+            &mut report,
+        );
+        assert_eq!(1, added);
+        assert!(stop_points
+            .get("sp:3")
+            .unwrap()
+            .codes
+            .contains(&("external".to_string(), "N1".to_string())));
+    }
+
+    struct FirstStopMatcher;
+
+    impl StopMatcher<StopPoint> for FirstStopMatcher {
+        fn match_stop(
+            &self,
+            stops: &CollectionWithId<StopPoint>,
+            _: &ExternalStop,
+        ) -> Vec<Idx<StopPoint>> {
+            stops.iter().map(|(idx, _)| idx).take(1).collect()
+        }
+    }
+
+    #[test]
+    fn custom_matcher() {
+        let stop_points = stop_points();
+        let mut report = Report::default();
+        let matcher: Box<dyn StopMatcher<StopPoint>> = Box::new(FirstStopMatcher);
+        let matches = match_stops(
+            &stop_points,
+            &[external_stop("any", None, None)],
+            matcher.as_ref(),
+            &mut report,
+        );
+        assert_eq!(
+            vec![("any".to_string(), "sp:1".to_string())],
+            matched_ids(&stop_points, &matches)
+        );
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Tags attached to the objects (lines, routes, trips, stop areas and stop
+//! points), e.g. to mark the school lines and later keep only them with
+//! [keep_tagged_lines](../filter/fn.keep_tagged_lines.html).
+//!
+//! The tags are stored as object properties named `tag`, so they are
+//! persisted in the `object_properties.txt` file of a NTFS.
+
+use crate::objects::Properties;
+
+/// Name of the object properties holding the tags
+pub const TAG_PROPERTY: &str = "tag";
+
+/// Tags of the `object`
+pub fn tags<T: Properties>(object: &T) -> impl Iterator<Item = &str> {
+    object
+        .properties()
+        .iter()
+        .filter(|(name, _)| name == TAG_PROPERTY)
+        .map(|(_, tag)| tag.as_str())
+}
+
+/// Whether the `object` has the `tag`
+pub fn has_tag<T: Properties>(object: &T, tag: &str) -> bool {
+    object
+        .properties()
+        .contains(&(TAG_PROPERTY.to_string(), tag.to_string()))
+}
+
+/// Attaches the `tag` to the `object`, returns `false` if it already had it
+pub fn add_tag<T: Properties>(object: &mut T, tag: &str) -> bool {
+    object
+        .properties_mut()
+        .insert((TAG_PROPERTY.to_string(), tag.to_string()))
+}
+
+/// Removes the `tag` from the `object`, returns `false` if it didn't have it
+pub fn remove_tag<T: Properties>(object: &mut T, tag: &str) -> bool {
+    object
+        .properties_mut()
+        .remove(&(TAG_PROPERTY.to_string(), tag.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Line;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn tag_objects() {
+        let mut line = Line::default();
+        line.object_properties
+            .insert(("color".to_string(), "red".to_string()));
+        assert!(add_tag(&mut line, "school"));
+        assert!(!add_tag(&mut line, "school"));
+        assert!(add_tag(&mut line, "night"));
+        assert!(has_tag(&line, "school"));
+        assert!(!has_tag(&line, "red"));
+        assert_eq!(vec!["night", "school"], tags(&line).collect::<Vec<_>>());
+        assert!(remove_tag(&mut line, "night"));
+        assert!(!remove_tag(&mut line, "night"));
+        assert_eq!(vec!["school"], tags(&line).collect::<Vec<_>>());
+    }
+}
@@ -12,19 +12,33 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>
 
-//! See function generates_transfers
+//! See functions generates_transfers and generates_transfers_by_physical_mode,
+//! and fix_transfer_times to repair the transfer times of the source data
 
 use crate::{
-    model::Model,
-    objects::{Coord, StopPoint, Transfer},
+    model::{GetCorresponding, Model},
+    objects::{Coord, PhysicalMode, StopPoint, Transfer},
+    report::{Report, ReportEntry, ReportType, Severity},
     Result,
 };
+use failure::ResultExt;
 use log::{info, warn};
-use std::collections::HashMap;
+use relational_types::IdxSet;
+use serde::Deserialize;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    path::Path,
+};
 use typed_index_collection::{Collection, CollectionWithId, Idx};
 
 type TransferMap = HashMap<(Idx<StopPoint>, Idx<StopPoint>), Transfer>;
 
+/// Transfer time of a transfer not possible (e.g. a GTFS transfer of
+/// `transfer_type` 3)
+pub const NOT_POSSIBLE_TRANSFER_TIME: u32 = 86400;
+
 /// The closure that will determine whether a connection should be created between 2 stops.
 /// See [generates_transfers](./fn.generates_transfers.html).
 pub type NeedTransfer<'a> = Box<dyn 'a + Fn(&Model, Idx<StopPoint>, Idx<StopPoint>) -> bool>;
@@ -47,52 +61,138 @@ fn make_transfers_map(
         .collect()
 }
 
-fn generate_transfers_from_sp(
+// Adds the missing transfers between the stop points of the `model`; the
+// numbers of transfers generated, and skipped because a stop point is not
+// geolocated, are reported
+fn generate_transfers_from_sp<F>(
     transfers_map: &mut TransferMap,
     model: &Model,
-    max_distance: f64,
-    walking_speed: f64,
-    waiting_time: u32,
+    get_parameters: F,
     need_transfer: Option<NeedTransfer>,
-) {
+    report: &mut Report,
+) where
+    F: Fn(Idx<StopPoint>, Idx<StopPoint>) -> Option<TransferParameters>,
+{
     info!("Adding missing transfers from stop points.");
-    let sq_max_distance = max_distance * max_distance;
+    let mut nb_generated = 0;
+    let mut nb_skipped = 0;
     for (idx1, sp1) in model.stop_points.iter() {
         if sp1.coord == Coord::default() {
             warn!("Stop Point {} geolocation is (0, 0), no transfer from this StopPoint will be generated.", sp1.id);
+            nb_skipped += model.stop_points.len();
             continue;
         }
         let approx = sp1.coord.approx();
         for (idx2, sp2) in model.stop_points.iter() {
             if sp2.coord == Coord::default() {
                 warn!("Stop Point {} geolocation is (0, 0), no transfer to this StopPoint will be generated.", sp2.id);
+                nb_skipped += 1;
                 continue;
             }
             if transfers_map.contains_key(&(idx1, idx2)) {
                 continue;
             }
+            let parameters = match get_parameters(idx1, idx2) {
+                Some(parameters) => parameters,
+                None => continue,
+            };
             if let Some(ref f) = need_transfer {
                 if !f(model, idx1, idx2) {
                     continue;
                 }
             }
             let sq_distance = approx.sq_distance_to(&sp2.coord);
-            if sq_distance > sq_max_distance {
+            if sq_distance > parameters.max_distance * parameters.max_distance {
                 continue;
             }
-            let transfer_time = (sq_distance.sqrt() / walking_speed) as u32;
+            let transfer_time = (sq_distance.sqrt() / parameters.walking_speed) as u32;
             transfers_map.insert(
                 (idx1, idx2),
                 Transfer {
                     from_stop_id: sp1.id.clone(),
                     to_stop_id: sp2.id.clone(),
                     min_transfer_time: Some(transfer_time),
-                    real_min_transfer_time: Some(transfer_time + waiting_time),
+                    real_min_transfer_time: Some(transfer_time + parameters.waiting_time),
                     equipment_id: None,
                 },
             );
+            nb_generated += 1;
         }
     }
+    if nb_generated > 0 {
+        report.add(
+            Severity::Info,
+            ReportEntry {
+                count: nb_generated,
+                ..ReportEntry::new(ReportType::ObjectCreated, "transfer generated".to_string())
+            },
+        );
+    }
+    if nb_skipped > 0 {
+        report.add(
+            Severity::Warning,
+            ReportEntry {
+                count: nb_skipped,
+                ..ReportEntry::new(
+                    ReportType::InvalidValue,
+                    "transfer skipped, from or to a stop point geolocated at (0, 0)".to_string(),
+                )
+            },
+        );
+    }
+}
+
+fn make_model_with_transfers(model: Model, transfers_map: TransferMap) -> Result<Model> {
+    let mut new_transfers: Vec<_> = transfers_map.into_iter().map(|(_, v)| v).collect();
+    new_transfers.sort_unstable_by(|t1, t2| {
+        (&t1.from_stop_id, &t1.to_stop_id).cmp(&(&t2.from_stop_id, &t2.to_stop_id))
+    });
+
+    let mut collections = model.into_collections();
+    collections.transfers = Collection::new(new_transfers);
+    Ok(Model::new(collections)?)
+}
+
+// Identifiers of the physical modes of each stop point
+fn stop_point_physical_modes(model: &Model) -> HashMap<Idx<StopPoint>, BTreeSet<&str>> {
+    model
+        .stop_points
+        .iter()
+        .map(|(sp_idx, _)| {
+            let physical_modes: IdxSet<PhysicalMode> = model.get_corresponding_from_idx(sp_idx);
+            let physical_mode_ids = physical_modes
+                .into_iter()
+                .map(|pm_idx| model.physical_modes[pm_idx].id.as_str())
+                .collect();
+            (sp_idx, physical_mode_ids)
+        })
+        .collect()
+}
+
+/// Parameters of the generation of transfers
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct TransferParameters {
+    /// The max distance in meters to compute the transfer.
+    pub max_distance: f64,
+    /// The walking speed in meters per second.
+    pub walking_speed: f64,
+    /// The waiting time at stop in seconds.
+    pub waiting_time: u32,
+}
+
+/// Configuration of
+/// [generates_transfers_by_physical_mode](./fn.generates_transfers_by_physical_mode.html)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TransfersConfiguration {
+    /// Parameters of the stop points whose physical modes are not configured
+    pub default: TransferParameters,
+    /// Parameters by physical mode identifier
+    #[serde(default)]
+    pub physical_modes: HashMap<String, TransferParameters>,
+    /// Pairs of physical mode identifiers between which no transfer is
+    /// generated
+    #[serde(default)]
+    pub excluded_physical_mode_pairs: Vec<(String, String)>,
 }
 
 /// Generates missing transfers
@@ -127,24 +227,288 @@ pub fn generates_transfers(
     walking_speed: f64,
     waiting_time: u32,
     need_transfer: Option<NeedTransfer>,
+) -> Result<Model> {
+    generates_transfers_with_report(
+        model,
+        max_distance,
+        walking_speed,
+        waiting_time,
+        need_transfer,
+        &mut Report::default(),
+    )
+}
+
+/// Generates missing transfers as
+/// [generates_transfers](./fn.generates_transfers.html) does, the number of
+/// transfers generated and the number of transfers skipped because a stop
+/// point is geolocated at (0, 0) being added to the `report`.
+pub fn generates_transfers_with_report(
+    model: Model,
+    max_distance: f64,
+    walking_speed: f64,
+    waiting_time: u32,
+    need_transfer: Option<NeedTransfer>,
+    report: &mut Report,
 ) -> Result<Model> {
     info!("Generating transfers...");
     let mut transfers_map = make_transfers_map(model.transfers.clone(), &model.stop_points);
-    generate_transfers_from_sp(
-        &mut transfers_map,
-        &model,
+    let parameters = TransferParameters {
         max_distance,
         walking_speed,
         waiting_time,
+    };
+    generate_transfers_from_sp(
+        &mut transfers_map,
+        &model,
+        |_, _| Some(parameters),
         need_transfer,
+        report,
     );
+    make_model_with_transfers(model, transfers_map)
+}
 
-    let mut new_transfers: Vec<_> = transfers_map.into_iter().map(|(_, v)| v).collect();
-    new_transfers.sort_unstable_by(|t1, t2| {
-        (&t1.from_stop_id, &t1.to_stop_id).cmp(&(&t2.from_stop_id, &t2.to_stop_id))
-    });
+/// Reads a [TransfersConfiguration](struct.TransfersConfiguration.html)
+/// from a JSON file.
+///
+/// # Example
+///
+/// ```json
+/// {
+///     "default": { "max_distance": 500, "walking_speed": 0.785, "waiting_time": 60 },
+///     "physical_modes": {
+///         "Train": { "max_distance": 800, "walking_speed": 0.785, "waiting_time": 180 }
+///     },
+///     "excluded_physical_mode_pairs": [["Air", "Bus"]]
+/// }
+/// ```
+pub fn read_transfers_configuration<P: AsRef<Path>>(path: P) -> Result<TransfersConfiguration> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let configuration =
+        serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+    Ok(configuration)
+}
+
+/// Generates missing transfers, with parameters depending on the physical
+/// modes of the stop points.
+///
+/// The parameters of a stop point are the ones of its physical mode with the
+/// largest `max_distance`, or the default ones if none of its physical modes
+/// is configured. A transfer between 2 stop points uses the parameters of the
+/// stop point with the largest `max_distance`.
+///
+/// No transfer is generated between 2 stop points if every pair of their
+/// physical modes is excluded (in any order).
+///
+/// See [generates_transfers](./fn.generates_transfers.html) for `need_transfer`.
+pub fn generates_transfers_by_physical_mode(
+    model: Model,
+    configuration: &TransfersConfiguration,
+    need_transfer: Option<NeedTransfer>,
+) -> Result<Model> {
+    info!("Generating transfers by physical mode...");
+    let transfers_map = {
+        let mut transfers_map = make_transfers_map(model.transfers.clone(), &model.stop_points);
+
+        let stop_point_modes = stop_point_physical_modes(&model);
+        let stop_point_parameters: HashMap<Idx<StopPoint>, TransferParameters> = stop_point_modes
+            .iter()
+            .map(|(sp_idx, physical_mode_ids)| {
+                let parameters = physical_mode_ids
+                    .iter()
+                    .filter_map(|physical_mode_id| {
+                        configuration.physical_modes.get(*physical_mode_id)
+                    })
+                    .max_by(|p1, p2| {
+                        p1.max_distance
+                            .partial_cmp(&p2.max_distance)
+                            .unwrap_or(Ordering::Equal)
+                    })
+                    .unwrap_or(&configuration.default);
+                (*sp_idx, *parameters)
+            })
+            .collect();
+        let is_excluded = |modes1: &BTreeSet<&str>, modes2: &BTreeSet<&str>| {
+            !modes1.is_empty()
+                && !modes2.is_empty()
+                && modes1.iter().all(|mode1| {
+                    modes2.iter().all(|mode2| {
+                        configuration.excluded_physical_mode_pairs.iter().any(
+                            |(excluded1, excluded2)| {
+                                (excluded1 == mode1 && excluded2 == mode2)
+                                    || (excluded1 == mode2 && excluded2 == mode1)
+                            },
+                        )
+                    })
+                })
+        };
+
+        generate_transfers_from_sp(
+            &mut transfers_map,
+            &model,
+            |idx1, idx2| {
+                if is_excluded(&stop_point_modes[&idx1], &stop_point_modes[&idx2]) {
+                    return None;
+                }
+                let (parameters1, parameters2) =
+                    (stop_point_parameters[&idx1], stop_point_parameters[&idx2]);
+                if parameters1.max_distance >= parameters2.max_distance {
+                    Some(parameters1)
+                } else {
+                    Some(parameters2)
+                }
+            },
+            need_transfer,
+            &mut Report::default(),
+        );
+        transfers_map
+    };
+    make_model_with_transfers(model, transfers_map)
+}
+
+/// Transfer time between the stop points of 2 physical modes, see
+/// [TransferTimesConfiguration](struct.TransferTimesConfiguration.html)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PhysicalModePairTransferTime {
+    /// Identifiers of the physical modes, in any order
+    pub physical_modes: (String, String),
+    /// Transfer time in seconds
+    pub min_transfer_time: u32,
+}
+
+/// Configuration of [fix_transfer_times](./fn.fix_transfer_times.html)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TransferTimesConfiguration {
+    /// Smallest sensible transfer time in seconds, the smaller ones are
+    /// raised to it
+    pub min_transfer_time: u32,
+    /// Smallest sensible transfer times between the stop points of some
+    /// physical modes
+    #[serde(default)]
+    pub physical_mode_pairs: Vec<PhysicalModePairTransferTime>,
+    /// Largest sensible transfer time in seconds, the larger ones are
+    /// clamped to it
+    pub max_transfer_time: u32,
+}
+
+impl TransferTimesConfiguration {
+    // The smallest sensible transfer time between stop points with these
+    // physical modes, the largest of the matching pairs
+    fn min_transfer_time(&self, modes1: &BTreeSet<&str>, modes2: &BTreeSet<&str>) -> u32 {
+        self.physical_mode_pairs
+            .iter()
+            .filter(|pair| {
+                let (mode1, mode2) = (
+                    pair.physical_modes.0.as_str(),
+                    pair.physical_modes.1.as_str(),
+                );
+                (modes1.contains(mode1) && modes2.contains(mode2))
+                    || (modes1.contains(mode2) && modes2.contains(mode1))
+            })
+            .map(|pair| pair.min_transfer_time)
+            .max()
+            .unwrap_or(self.min_transfer_time)
+    }
+}
+
+/// Reads a [TransferTimesConfiguration](struct.TransferTimesConfiguration.html)
+/// from a JSON file.
+///
+/// # Example
+///
+/// ```json
+/// {
+///     "min_transfer_time": 60,
+///     "physical_mode_pairs": [
+///         { "physical_modes": ["Train", "Bus"], "min_transfer_time": 300 }
+///     ],
+///     "max_transfer_time": 3600
+/// }
+/// ```
+pub fn read_transfer_times_configuration<P: AsRef<Path>>(
+    path: P,
+) -> Result<TransferTimesConfiguration> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let configuration =
+        serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+    Ok(configuration)
+}
+
+/// Fixes the transfer times which break the journey planners: the too small
+/// transfer times between distinct stop points and the absurdly large ones.
+/// The negative times of the source data are ignored when read.
+///
+/// A transfer time smaller than the `min_transfer_time` of the pair of
+/// physical modes of the stop points, or than the default one, is raised to
+/// it; a transfer time larger than `max_transfer_time` is clamped to it. A
+/// zero transfer time (a timed transfer) and
+/// [NOT_POSSIBLE_TRANSFER_TIME](constant.NOT_POSSIBLE_TRANSFER_TIME.html)
+/// (a transfer not possible) are kept. The `real_min_transfer_time` is
+/// raised to the fixed `min_transfer_time` if lower. Each transfer fixed is
+/// reported as a warning.
+pub fn fix_transfer_times(
+    model: Model,
+    configuration: &TransferTimesConfiguration,
+    report: &mut Report,
+) -> Result<Model> {
+    info!("Fixing transfer times...");
+    let min_transfer_times: Vec<u32> = {
+        let stop_point_modes = stop_point_physical_modes(&model);
+        let no_modes = BTreeSet::new();
+        let modes = |stop_id: &str| {
+            model
+                .stop_points
+                .get_idx(stop_id)
+                .and_then(|sp_idx| stop_point_modes.get(&sp_idx))
+                .unwrap_or(&no_modes)
+        };
+        model
+            .transfers
+            .values()
+            .map(|transfer| {
+                configuration
+                    .min_transfer_time(modes(&transfer.from_stop_id), modes(&transfer.to_stop_id))
+            })
+            .collect()
+    };
 
     let mut collections = model.into_collections();
-    collections.transfers = Collection::new(new_transfers);
-    Ok(Model::new(collections)?)
+    let mut transfers = collections.transfers.take();
+    for (transfer, min_transfer_time) in transfers.iter_mut().zip(min_transfer_times) {
+        let is_self_transfer = transfer.from_stop_id == transfer.to_stop_id;
+        let fix = |time: Option<u32>| match time {
+            Some(0) | Some(NOT_POSSIBLE_TRANSFER_TIME) => time,
+            Some(time) if time < min_transfer_time && !is_self_transfer => Some(min_transfer_time),
+            Some(time) if time > configuration.max_transfer_time => {
+                Some(configuration.max_transfer_time)
+            }
+            _ => time,
+        };
+        let old_times = (transfer.min_transfer_time, transfer.real_min_transfer_time);
+        let min = fix(transfer.min_transfer_time);
+        let mut real = fix(transfer.real_min_transfer_time);
+        if let (Some(min_time), Some(real_time)) = (min, real) {
+            if min != old_times.0 && real_time < min_time {
+                real = min;
+            }
+        }
+        if (min, real) == old_times {
+            continue;
+        }
+        report.add(
+            Severity::Warning,
+            ReportEntry::new(
+                ReportType::UnrealisticTransferTime,
+                format!(
+                    "transfer from {:?} to {:?}: min_transfer_time {:?} and real_min_transfer_time {:?} fixed to {:?} and {:?}",
+                    transfer.from_stop_id, transfer.to_stop_id, old_times.0, old_times.1, min, real
+                ),
+            ),
+        );
+        transfer.min_transfer_time = min;
+        transfer.real_min_transfer_time = real;
+    }
+    collections.transfers = Collection::new(transfers);
+    Model::new(collections)
 }
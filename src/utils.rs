@@ -12,48 +12,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>
 
-use crate::objects::Date;
-use chrono::NaiveDate;
+use crate::{
+    error::TransitModelError, objects::Date, read_utils::deserialize_records, report::Report,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use failure::{format_err, ResultExt};
-use log::{debug, error, info};
+use log::{error, info, warn};
 use rust_decimal::Decimal;
 use std::fs;
-use std::io::{Read, Write};
 use std::path;
 use typed_index_collection::{Collection, CollectionWithId, Id};
-use walkdir::WalkDir;
 use wkt::{self, conversion::try_into_geometry, ToWkt};
 
-pub fn zip_to<P, R>(source_path: P, zip_file: R) -> crate::Result<()>
-where
-    P: AsRef<path::Path>,
-    R: AsRef<path::Path>,
-{
-    let source_path = source_path.as_ref();
-    let file = fs::File::create(zip_file.as_ref())?;
-    let mut zip = zip::ZipWriter::new(file);
-    let options =
-        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
-    let mut buffer = Vec::new();
-    for entry in WalkDir::new(source_path) {
-        let path = entry?.path().to_owned();
-        if path.is_file() {
-            let name = path.strip_prefix(path::Path::new(source_path))?.to_owned();
-            if let Some(name) = name.to_str() {
-                debug!("adding {:?} as {:?} ...", path, name);
-                zip.start_file(name, options)?;
-                let mut f = fs::File::open(path)?;
-
-                f.read_to_end(&mut buffer)?;
-                zip.write_all(&*buffer)?;
-                buffer.clear();
-            }
-        }
-    }
-    zip.finish()?;
-    Ok(())
-}
-
 pub fn de_from_u8<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -111,6 +81,31 @@ where
     serializer.serialize_str(&s)
 }
 
+pub fn de_option_datetime<'de, D>(de: D) -> Result<Option<DateTime<FixedOffset>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+    Option::<String>::deserialize(de)?
+        .filter(|s| !s.is_empty())
+        .map(|s| DateTime::parse_from_rfc3339(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+// The signature of the function must pass by reference for 'serde' to be able to use the function
+pub fn ser_option_datetime<S>(
+    datetime: &Option<DateTime<FixedOffset>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match datetime {
+        Some(datetime) => serializer.serialize_str(&datetime.to_rfc3339()),
+        None => serializer.serialize_none(),
+    }
+}
+
 pub fn de_with_empty_default<'de, T: Default, D>(de: D) -> Result<T, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -240,40 +235,31 @@ where
     }
 }
 
-pub fn de_currency_code<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    use serde::{
-        de::{Error, Unexpected::Other},
-        Deserialize,
-    };
-    let string = String::deserialize(deserializer)?;
-    let currency_code = iso4217::alpha3(&string).ok_or_else(|| {
-        D::Error::invalid_value(
-            Other("unrecognized currency code (ISO-4217)"),
-            &"3-letters currency code (ISO-4217)",
-        )
-    })?;
-    Ok(String::from(currency_code.alpha3))
-}
-
-pub fn ser_currency_code<S>(currency_code: &str, serializer: S) -> Result<S::Ok, S::Error>
+pub fn ser_geometry<S>(geometry: &geo::Geometry<f64>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
-    use serde::ser::Error;
-    let currency_code = iso4217::alpha3(currency_code)
-        .ok_or_else(|| S::Error::custom("The String is not a valid currency code (ISO-4217)"))?;
-    serializer.serialize_str(&currency_code.alpha3.to_string())
+    let wkt = geometry.to_wkt();
+    serializer.serialize_str(&format!("{}", wkt.items[0]))
 }
 
-pub fn ser_geometry<S>(geometry: &geo::Geometry<f64>, serializer: S) -> Result<S::Ok, S::Error>
+/// Deserializes a duration in seconds, a negative duration being ignored
+/// (with a warning)
+pub fn de_option_negative_as_none<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
 where
-    S: serde::Serializer,
+    D: serde::Deserializer<'de>,
 {
-    let wkt = geometry.to_wkt();
-    serializer.serialize_str(&format!("{}", wkt.items[0]))
+    use serde::{de::Error, Deserialize};
+    use std::convert::TryFrom;
+    let option = <Option<i64> as Deserialize<'de>>::deserialize(deserializer)?;
+    match option {
+        Some(number) if number < 0 => {
+            warn!("negative duration {} ignored", number);
+            Ok(None)
+        }
+        Some(number) => u32::try_from(number).map(Some).map_err(D::Error::custom),
+        None => Ok(None),
+    }
 }
 
 pub fn de_option_empty_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
@@ -294,70 +280,160 @@ where
     }
 }
 
-pub fn make_opt_collection_with_id<T>(
+/// Opens the CSV file `path`, the `skipped_columns` being read as if they
+/// were absent from the file: their values are never deserialized.
+pub fn csv_reader_skipping_columns(
+    path: &path::Path,
+    skipped_columns: &[&str],
+) -> crate::Result<csv::Reader<fs::File>> {
+    let mut rdr = csv::Reader::from_path(path).map_err(|e| TransitModelError::csv(path, e))?;
+    if !skipped_columns.is_empty() {
+        // an empty header matches no field of the objects
+        let headers: csv::StringRecord = rdr
+            .headers()
+            .map_err(|e| TransitModelError::csv(path, e))?
+            .iter()
+            .map(|header| {
+                if skipped_columns.contains(&header) {
+                    ""
+                } else {
+                    header
+                }
+            })
+            .collect();
+        rdr.set_headers(headers);
+    }
+    Ok(rdr)
+}
+
+pub fn make_collection_with_id<T>(
     path: &path::Path,
     file: &str,
 ) -> crate::Result<CollectionWithId<T>>
 where
     for<'de> T: Id<T> + serde::Deserialize<'de>,
 {
-    if !path.join(file).exists() {
-        info!("Skipping {}", file);
-        Ok(CollectionWithId::default())
-    } else {
-        make_collection_with_id(path, file)
-    }
+    make_collection_with_id_skipping_columns(path, file, &[])
 }
 
-pub fn make_collection_with_id<T>(
+pub fn make_collection_with_id_skipping_columns<T>(
     path: &path::Path,
     file: &str,
+    skipped_columns: &[&str],
 ) -> crate::Result<CollectionWithId<T>>
 where
     for<'de> T: Id<T> + serde::Deserialize<'de>,
 {
-    info!("Reading {}", file);
-    let path = path.join(file);
-    let mut rdr =
-        csv::Reader::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
-    let vec = rdr
-        .deserialize()
-        .collect::<Result<_, _>>()
-        .with_context(|_| format!("Error reading {:?}", path))?;
+    let vec = read_objects_skipping_columns(path, file, skipped_columns, None)?;
     CollectionWithId::new(vec).map_err(|e| format_err!("{}", e))
 }
 
-pub fn make_opt_collection<T>(path: &path::Path, file: &str) -> crate::Result<Collection<T>>
+/// Reads the objects of the CSV file `file` of the directory `path`, the
+/// `skipped_columns` being read as if they were absent from the file.
+///
+/// The records which can't be read fail the reading, or are skipped and
+/// added to the `report` when one is given (see
+/// [deserialize_records](../read_utils/fn.deserialize_records.html)).
+pub fn read_objects_skipping_columns<T>(
+    path: &path::Path,
+    file: &str,
+    skipped_columns: &[&str],
+    report: Option<&mut Report>,
+) -> crate::Result<Vec<T>>
 where
     for<'de> T: serde::Deserialize<'de>,
 {
-    if !path.join(file).exists() {
-        info!("Skipping {}", file);
-        Ok(Collection::default())
-    } else {
-        make_collection(path, file)
+    info!("Reading {}", file);
+    let path = path.join(file);
+    let mut rdr = csv_reader_skipping_columns(&path, skipped_columns)?;
+    deserialize_records(&mut rdr, &path, report)
+}
+
+/// A CSV writer of objects leaving out some of their columns, the
+/// counterpart of `csv_reader_skipping_columns`
+pub struct CsvWriterSkippingColumns<'a, W: std::io::Write> {
+    wtr: csv::Writer<W>,
+    skipped_columns: &'a [&'a str],
+    // positions of the columns written, known once the headers are written
+    kept_columns: Option<Vec<usize>>,
+}
+
+impl<'a, W: std::io::Write> CsvWriterSkippingColumns<'a, W> {
+    /// Writes with `wtr` the objects without their `skipped_columns`
+    pub fn new(wtr: csv::Writer<W>, skipped_columns: &'a [&'a str]) -> Self {
+        CsvWriterSkippingColumns {
+            wtr,
+            skipped_columns,
+            kept_columns: None,
+        }
+    }
+
+    /// Serializes the `object` without the skipped columns, preceded by the
+    /// headers for the first one
+    pub fn serialize<T: serde::Serialize>(&mut self, object: T) -> csv::Result<()> {
+        if self.skipped_columns.is_empty() {
+            return self.wtr.serialize(object);
+        }
+        // the object is serialized alone with its headers, to know the
+        // positions of its columns
+        let mut buffer = csv::Writer::from_writer(vec![]);
+        buffer.serialize(object)?;
+        let buffer = buffer
+            .into_inner()
+            .map_err(|e| csv::Error::from(e.into_error()))?;
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(buffer.as_slice());
+        let mut records = rdr.byte_records();
+        let (headers, record) = match (records.next(), records.next()) {
+            (Some(headers), Some(record)) => (headers?, record?),
+            _ => return Ok(()),
+        };
+        if self.kept_columns.is_none() {
+            let skipped_columns = self.skipped_columns;
+            let kept_columns: Vec<usize> = headers
+                .iter()
+                .enumerate()
+                .filter(|(_, header)| {
+                    !skipped_columns
+                        .iter()
+                        .any(|column| column.as_bytes() == *header)
+                })
+                .map(|(i, _)| i)
+                .collect();
+            self.wtr
+                .write_record(kept_columns.iter().map(|&i| &headers[i]))?;
+            self.kept_columns = Some(kept_columns);
+        }
+        if let Some(kept_columns) = &self.kept_columns {
+            self.wtr
+                .write_record(kept_columns.iter().map(|&i| &record[i]))?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.wtr.flush()
     }
 }
 
-pub fn make_collection<T>(path: &path::Path, file: &str) -> crate::Result<Collection<T>>
+pub fn write_collection_with_id<T>(
+    path: &path::Path,
+    file: &str,
+    collection: &CollectionWithId<T>,
+) -> crate::Result<()>
 where
-    for<'de> T: serde::Deserialize<'de>,
+    T: Id<T> + serde::Serialize,
 {
-    info!("Reading {}", file);
-    let path = path.join(file);
-    let mut rdr =
-        csv::Reader::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
-    let vec = rdr
-        .deserialize()
-        .collect::<Result<_, _>>()
-        .with_context(|_| format!("Error reading {:?}", path))?;
-    Ok(Collection::new(vec))
+    write_collection_with_id_skipping_columns(path, file, collection, &[])
 }
 
-pub fn write_collection_with_id<T>(
+pub fn write_collection_with_id_skipping_columns<T>(
     path: &path::Path,
     file: &str,
     collection: &CollectionWithId<T>,
+    skipped_columns: &[&str],
 ) -> crate::Result<()>
 where
     T: Id<T> + serde::Serialize,
@@ -367,14 +443,15 @@ where
     }
     info!("Writing {}", file);
     let path = path.join(file);
-    let mut wtr =
-        csv::Writer::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
+    let wtr =
+        csv::Writer::from_path(&path).with_context(|_| format!("Error writing {:?}", path))?;
+    let mut wtr = CsvWriterSkippingColumns::new(wtr, skipped_columns);
     for obj in collection.values() {
         wtr.serialize(obj)
-            .with_context(|_| format!("Error reading {:?}", path))?;
+            .with_context(|_| format!("Error writing {:?}", path))?;
     }
     wtr.flush()
-        .with_context(|_| format!("Error reading {:?}", path))?;
+        .with_context(|_| format!("Error writing {:?}", path))?;
 
     Ok(())
 }
@@ -384,6 +461,18 @@ pub fn write_collection<T>(
     file: &str,
     collection: &Collection<T>,
 ) -> crate::Result<()>
+where
+    T: serde::Serialize,
+{
+    write_collection_skipping_columns(path, file, collection, &[])
+}
+
+pub fn write_collection_skipping_columns<T>(
+    path: &path::Path,
+    file: &str,
+    collection: &Collection<T>,
+    skipped_columns: &[&str],
+) -> crate::Result<()>
 where
     T: serde::Serialize,
 {
@@ -392,14 +481,15 @@ where
     }
     info!("Writing {}", file);
     let path = path.join(file);
-    let mut wtr =
-        csv::Writer::from_path(&path).with_context(|_| format!("Error reading {:?}", path))?;
+    let wtr =
+        csv::Writer::from_path(&path).with_context(|_| format!("Error writing {:?}", path))?;
+    let mut wtr = CsvWriterSkippingColumns::new(wtr, skipped_columns);
     for obj in collection.values() {
         wtr.serialize(obj)
-            .with_context(|_| format!("Error reading {:?}", path))?;
+            .with_context(|_| format!("Error writing {:?}", path))?;
     }
     wtr.flush()
-        .with_context(|_| format!("Error reading {:?}", path))?;
+        .with_context(|_| format!("Error writing {:?}", path))?;
 
     Ok(())
 }
@@ -408,51 +498,63 @@ where
 mod tests {
     use super::*;
 
-    mod serde_currency {
-        use super::*;
-        use pretty_assertions::assert_eq;
-        use serde::{Deserialize, Serialize};
-
-        #[derive(Debug, Serialize, Deserialize)]
-        struct CurrencyCodeWrapper {
-            #[serde(
-                serialize_with = "ser_currency_code",
-                deserialize_with = "de_currency_code"
-            )]
-            pub currency_code: String,
-        }
-
-        #[test]
-        fn test_serde_valid_currency_code() {
-            let wrapper = CurrencyCodeWrapper {
-                currency_code: "EUR".to_string(),
-            };
-            let json = serde_json::to_string(&wrapper).unwrap();
-            let wrapper: CurrencyCodeWrapper = serde_json::from_str(&json).unwrap();
-
-            assert_eq!("EUR", wrapper.currency_code);
-        }
-
-        #[test]
-        fn test_de_invalid_currency_code() {
-            let result: Result<CurrencyCodeWrapper, _> =
-                serde_json::from_str("{\"currency_code\":\"XXX\"}");
-            let err_msg = result.unwrap_err().to_string();
-            assert_eq!( "invalid value: unrecognized currency code (ISO-4217), expected 3-letters currency code (ISO-4217) at line 1 column 23",err_msg);
-        }
-
-        #[test]
-        fn test_ser_invalid_currency_code() {
-            let wrapper = CurrencyCodeWrapper {
-                currency_code: "XXX".to_string(),
-            };
-            let result = serde_json::to_string(&wrapper);
-            let err_msg = result.unwrap_err().to_string();
-            assert_eq!(
-                "The String is not a valid currency code (ISO-4217)",
-                err_msg
+    #[test]
+    fn skip_columns() {
+        use crate::{objects::Line, test_utils::*};
+        test_in_tmp_dir(|path| {
+            create_file_with_content(
+                path,
+                "lines.txt",
+                "line_id,line_name,line_color,line_text_color,network_id,commercial_mode_id\n\
+                 M1,Metro 1,FFCE00,000000,TGN,Metro\n",
             );
-        }
+            let lines: CollectionWithId<Line> = make_collection_with_id_skipping_columns(
+                path,
+                "lines.txt",
+                &["line_color", "line_text_color"],
+            )
+            .unwrap();
+            let line = lines.get("M1").unwrap();
+            assert_eq!("Metro 1", line.name);
+            assert_eq!(None, line.color);
+            assert_eq!(None, line.text_color);
+        });
+    }
+
+    #[test]
+    fn write_skipping_columns() {
+        use crate::{objects::Line, test_utils::*};
+        test_in_tmp_dir(|path| {
+            let lines = CollectionWithId::from(Line {
+                id: "M1".to_string(),
+                name: "Metro 1".to_string(),
+                color: Some(crate::objects::Rgb {
+                    red: 255,
+                    green: 206,
+                    blue: 0,
+                }),
+                network_id: "TGN".to_string(),
+                commercial_mode_id: "Metro".to_string(),
+                ..Default::default()
+            });
+            write_collection_with_id_skipping_columns(
+                path,
+                "lines.txt",
+                &lines,
+                &["line_color", "line_text_color"],
+            )
+            .unwrap();
+            let mut rdr = csv::Reader::from_path(path.join("lines.txt")).unwrap();
+            let headers = rdr.headers().unwrap().clone();
+            assert!(headers.iter().all(|header| header != "line_color"));
+            let record = rdr.records().next().unwrap().unwrap();
+            assert_eq!(headers.len(), record.len());
+            let written: CollectionWithId<Line> =
+                make_collection_with_id(path, "lines.txt").unwrap();
+            let line = written.get("M1").unwrap();
+            assert_eq!("Metro 1", line.name);
+            assert_eq!(None, line.color);
+        });
     }
 
     mod deserialize_decimal {
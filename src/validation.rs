@@ -0,0 +1,882 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Consistency checks of the collections of a `Model`, see
+//! [Collections::check_consistency](../model/struct.Collections.html#method.check_consistency)
+//! and
+//! [Collections::check_service_thresholds](../model/struct.Collections.html#method.check_service_thresholds)
+
+use crate::{
+    languages::is_valid_language_tag,
+    model::{
+        Collections, AIR_PHYSICAL_MODE, LOCAL_TRAIN_PHYSICAL_MODE,
+        LONG_DISTANCE_TRAIN_PHYSICAL_MODE, RAPID_TRANSIT_PHYSICAL_MODE, TRAIN_PHYSICAL_MODE,
+    },
+    objects::{Date, Line, ObjectType},
+    report::{Report, ReportEntry, ReportType, Severity},
+    transfers::NOT_POSSIBLE_TRANSFER_TIME,
+    Result,
+};
+use chrono::{Datelike, Weekday};
+use failure::ResultExt;
+use log::debug;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::File,
+    path::Path,
+};
+use typed_index_collection::{CollectionWithId, Id};
+
+/// A consistency check, adding its violations to the report
+type Check = fn(&Collections, &mut Report);
+
+/// The catalogue of the consistency checks, run in this order
+const CHECKS: &[(&str, Check)] = &[
+    ("references", check_references),
+    ("stop_times", check_stop_times),
+    ("calendars", check_calendars),
+    ("geometries", check_geometries),
+    ("speeds", check_speeds),
+    ("transfers", check_transfers),
+    ("languages", check_languages),
+];
+
+fn dangling_reference<T: Id<T>>(
+    report: &mut Report,
+    object_type: Option<ObjectType>,
+    object: &T,
+    field: &str,
+    reference: &str,
+) {
+    let mut entry = ReportEntry::new(
+        ReportType::DanglingReference,
+        format!(
+            "{}={:?} of {:?} doesn't exist",
+            field,
+            reference,
+            object.id()
+        ),
+    );
+    if let Some(object_type) = object_type {
+        entry = entry.with_object(object_type, object.id());
+    }
+    report.add(Severity::Error, entry);
+}
+
+fn check_reference<T: Id<T>, U>(
+    report: &mut Report,
+    object_type: Option<ObjectType>,
+    object: &T,
+    field: &str,
+    reference: &str,
+    targets: &CollectionWithId<U>,
+) {
+    if !targets.contains_id(reference) {
+        dangling_reference(report, object_type, object, field, reference);
+    }
+}
+
+fn check_references(c: &Collections, report: &mut Report) {
+    for dataset in c.datasets.values() {
+        check_reference(
+            report,
+            None,
+            dataset,
+            "contributor_id",
+            &dataset.contributor_id,
+            &c.contributors,
+        );
+    }
+    for line in c.lines.values() {
+        let line_type = Some(ObjectType::Line);
+        check_reference(
+            report,
+            line_type.clone(),
+            line,
+            "network_id",
+            &line.network_id,
+            &c.networks,
+        );
+        check_reference(
+            report,
+            line_type,
+            line,
+            "commercial_mode_id",
+            &line.commercial_mode_id,
+            &c.commercial_modes,
+        );
+    }
+    for route in c.routes.values() {
+        check_reference(
+            report,
+            Some(ObjectType::Route),
+            route,
+            "line_id",
+            &route.line_id,
+            &c.lines,
+        );
+    }
+    for vj in c.vehicle_journeys.values() {
+        let vj_type = Some(ObjectType::VehicleJourney);
+        check_reference(
+            report,
+            vj_type.clone(),
+            vj,
+            "route_id",
+            &vj.route_id,
+            &c.routes,
+        );
+        check_reference(
+            report,
+            vj_type.clone(),
+            vj,
+            "physical_mode_id",
+            &vj.physical_mode_id,
+            &c.physical_modes,
+        );
+        check_reference(
+            report,
+            vj_type.clone(),
+            vj,
+            "dataset_id",
+            &vj.dataset_id,
+            &c.datasets,
+        );
+        check_reference(
+            report,
+            vj_type.clone(),
+            vj,
+            "company_id",
+            &vj.company_id,
+            &c.companies,
+        );
+        if let Some(trip_property_id) = &vj.trip_property_id {
+            check_reference(
+                report,
+                vj_type,
+                vj,
+                "trip_property_id",
+                trip_property_id,
+                &c.trip_properties,
+            );
+        }
+    }
+    for stop_point in c.stop_points.values() {
+        check_reference(
+            report,
+            Some(ObjectType::StopPoint),
+            stop_point,
+            "stop_area_id",
+            &stop_point.stop_area_id,
+            &c.stop_areas,
+        );
+    }
+    for stop_location in c.stop_locations.values() {
+        if let Some(parent_id) = &stop_location.parent_id {
+            if !c.stop_areas.contains_id(parent_id) && !c.stop_points.contains_id(parent_id) {
+                dangling_reference(report, None, stop_location, "parent_id", parent_id);
+            }
+        }
+    }
+    for frequency in c.frequencies.values() {
+        if !c
+            .vehicle_journeys
+            .contains_id(&frequency.vehicle_journey_id)
+        {
+            report.add_error(
+                format!(
+                    "trip_id={:?} of a frequency doesn't exist",
+                    frequency.vehicle_journey_id
+                ),
+                ReportType::DanglingReference,
+            );
+        }
+    }
+    for admin_station in c.admin_stations.values() {
+        if !c.stop_areas.contains_id(&admin_station.stop_id) {
+            report.add_error(
+                format!(
+                    "stop_id={:?} of admin station {:?} doesn't exist",
+                    admin_station.stop_id, admin_station.admin_id
+                ),
+                ReportType::DanglingReference,
+            );
+        }
+    }
+}
+
+fn check_stop_times(c: &Collections, report: &mut Report) {
+    for vj in c.vehicle_journeys.values() {
+        let mut add_error = |message: String| {
+            report.add(
+                Severity::Error,
+                ReportEntry::new(ReportType::StopTimesNotIncreasing, message)
+                    .with_object(ObjectType::VehicleJourney, &vj.id),
+            )
+        };
+        for stop_time in &vj.stop_times {
+            if stop_time.arrival_time > stop_time.departure_time {
+                add_error(format!(
+                    "arrival_time {} is after departure_time {} at stop_sequence {}",
+                    stop_time.arrival_time, stop_time.departure_time, stop_time.sequence
+                ));
+            }
+        }
+        for window in vj.stop_times.windows(2) {
+            let (previous, next) = (&window[0], &window[1]);
+            if previous.sequence >= next.sequence {
+                add_error(format!(
+                    "stop_sequence {} is not after stop_sequence {}",
+                    next.sequence, previous.sequence
+                ));
+            }
+            if previous.departure_time > next.arrival_time {
+                add_error(format!(
+                    "arrival_time {} at stop_sequence {} is before departure_time {} at stop_sequence {}",
+                    next.arrival_time, next.sequence, previous.departure_time, previous.sequence
+                ));
+            }
+        }
+    }
+}
+
+fn check_calendars(c: &Collections, report: &mut Report) {
+    for vj in c.vehicle_journeys.values() {
+        let message = match c.calendars.get(&vj.service_id) {
+            None => format!("service_id={:?} doesn't exist", vj.service_id),
+            Some(calendar) if calendar.dates.is_empty() => {
+                format!("service_id={:?} has no date", vj.service_id)
+            }
+            Some(_) => continue,
+        };
+        report.add(
+            Severity::Error,
+            ReportEntry::new(ReportType::VehicleJourneyWithoutCalendar, message)
+                .with_object(ObjectType::VehicleJourney, &vj.id),
+        );
+    }
+}
+
+fn check_geometries(c: &Collections, report: &mut Report) {
+    macro_rules! check_object_geometries {
+        ($collection:expr, $object_type:expr) => {
+            for object in $collection.values() {
+                if let Some(geometry_id) = &object.geometry_id {
+                    if !c.geometries.contains_id(geometry_id) {
+                        report.add(
+                            Severity::Error,
+                            ReportEntry::new(
+                                ReportType::MissingGeometry,
+                                format!("geometry_id={:?} doesn't exist", geometry_id),
+                            )
+                            .with_object($object_type, &object.id),
+                        );
+                    }
+                }
+            }
+        };
+    }
+    check_object_geometries!(c.lines, ObjectType::Line);
+    check_object_geometries!(c.routes, ObjectType::Route);
+    check_object_geometries!(c.vehicle_journeys, ObjectType::VehicleJourney);
+    check_object_geometries!(c.stop_points, ObjectType::StopPoint);
+    check_object_geometries!(c.stop_areas, ObjectType::StopArea);
+}
+
+// Maximum average speed in km/h of a vehicle journey
+fn max_speed(physical_mode_id: &str) -> f64 {
+    match physical_mode_id {
+        AIR_PHYSICAL_MODE => 1_000.,
+        LONG_DISTANCE_TRAIN_PHYSICAL_MODE | TRAIN_PHYSICAL_MODE => 350.,
+        LOCAL_TRAIN_PHYSICAL_MODE | RAPID_TRANSIT_PHYSICAL_MODE => 200.,
+        _ => 150.,
+    }
+}
+
+fn check_speeds(c: &Collections, report: &mut Report) {
+    for vj in c.vehicle_journeys.values() {
+        let (length, first, last) = match (vj.length, vj.stop_times.first(), vj.stop_times.last()) {
+            (Some(length), Some(first), Some(last)) => (length, first, last),
+            _ => continue,
+        };
+        // the stop times not increasing are reported by check_stop_times
+        if last.arrival_time <= first.departure_time {
+            continue;
+        }
+        let duration = (last.arrival_time - first.departure_time).total_seconds();
+        let speed = length / f64::from(duration) * 3.6;
+        let max_speed = max_speed(&vj.physical_mode_id);
+        if speed > max_speed {
+            report.add(
+                Severity::Error,
+                ReportEntry::new(
+                    ReportType::UnrealisticSpeed,
+                    format!(
+                        "average speed of {:.0} km/h over {:.0} m is above {} km/h for physical_mode_id={:?}",
+                        speed, length, max_speed, vj.physical_mode_id
+                    ),
+                )
+                .with_object(ObjectType::VehicleJourney, &vj.id),
+            );
+        }
+    }
+}
+
+fn check_transfers(c: &Collections, report: &mut Report) {
+    let mut stop_pairs = HashSet::new();
+    for transfer in c.transfers.values() {
+        for stop_id in &[&transfer.from_stop_id, &transfer.to_stop_id] {
+            if !c.stop_points.contains_id(stop_id) {
+                report.add_error(
+                    format!("stop_id={:?} of a transfer doesn't exist", stop_id),
+                    ReportType::DanglingReference,
+                );
+            }
+        }
+        if !stop_pairs.insert((&transfer.from_stop_id, &transfer.to_stop_id)) {
+            report.add_error(
+                format!(
+                    "several transfers from {:?} to {:?}",
+                    transfer.from_stop_id, transfer.to_stop_id
+                ),
+                ReportType::OverlappingTransfers,
+            );
+        }
+        // a transfer not possible takes NOT_POSSIBLE_TRANSFER_TIME, a longer
+        // transfer time makes no sense
+        let times = [transfer.min_transfer_time, transfer.real_min_transfer_time];
+        if times
+            .iter()
+            .any(|time| time.map_or(false, |time| time > NOT_POSSIBLE_TRANSFER_TIME))
+        {
+            report.add_error(
+                format!(
+                    "transfer time from {:?} to {:?} longer than a day",
+                    transfer.from_stop_id, transfer.to_stop_id
+                ),
+                ReportType::UnrealisticTransferTime,
+            );
+        }
+    }
+}
+
+fn check_languages(c: &Collections, report: &mut Report) {
+    if let Some(feed_lang) = c.feed_language() {
+        if !is_valid_language_tag(feed_lang) {
+            report.add_error(
+                format!(
+                    "feed_lang={:?} is not a valid BCP-47 language tag",
+                    feed_lang
+                ),
+                ReportType::InvalidValue,
+            );
+        }
+    }
+    for network in c.networks.values() {
+        if let Some(lang) = &network.lang {
+            if !is_valid_language_tag(lang) {
+                report.add(
+                    Severity::Error,
+                    ReportEntry::new(
+                        ReportType::InvalidValue,
+                        format!("network_lang={:?} is not a valid BCP-47 language tag", lang),
+                    )
+                    .with_object(ObjectType::Network, &network.id),
+                );
+            }
+        }
+    }
+}
+
+/// Minimum service expected from a line
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct LineThresholds {
+    /// Minimum number of vehicle journeys on each day from monday to friday
+    pub min_vehicle_journeys_per_weekday: Option<usize>,
+    /// Minimum number of vehicle journeys on each saturday
+    pub min_vehicle_journeys_per_saturday: Option<usize>,
+    /// Minimum number of vehicle journeys on each sunday
+    pub min_vehicle_journeys_per_sunday: Option<usize>,
+}
+
+/// Service expected from the lines, checked by
+/// [check_service_thresholds](../model/struct.Collections.html#method.check_service_thresholds).
+///
+/// The thresholds of a line are the ones of its identifier, or else the
+/// ones of its network, or else the default ones.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ServiceThresholds {
+    /// Thresholds of the lines without specific thresholds
+    #[serde(default)]
+    pub default: Option<LineThresholds>,
+    /// Thresholds by network identifier
+    #[serde(default)]
+    pub networks: HashMap<String, LineThresholds>,
+    /// Thresholds by line identifier
+    #[serde(default)]
+    pub lines: HashMap<String, LineThresholds>,
+}
+
+impl ServiceThresholds {
+    fn of_line(&self, line: &Line) -> Option<&LineThresholds> {
+        self.lines
+            .get(&line.id)
+            .or_else(|| self.networks.get(&line.network_id))
+            .or_else(|| self.default.as_ref())
+    }
+}
+
+/// Reads [ServiceThresholds](struct.ServiceThresholds.html) from a JSON file.
+///
+/// # Example
+///
+/// ```json
+/// {
+///     "default": { "min_vehicle_journeys_per_weekday": 10 },
+///     "networks": {
+///         "urban": {
+///             "min_vehicle_journeys_per_weekday": 60,
+///             "min_vehicle_journeys_per_saturday": 40
+///         }
+///     },
+///     "lines": {
+///         "school_line": { "min_vehicle_journeys_per_weekday": 2 }
+///     }
+/// }
+/// ```
+pub fn read_service_thresholds<P: AsRef<Path>>(path: P) -> Result<ServiceThresholds> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let thresholds =
+        serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+    Ok(thresholds)
+}
+
+// Number of vehicle journeys of each line on each date
+fn count_vehicle_journeys(c: &Collections) -> HashMap<&str, HashMap<Date, usize>> {
+    let mut counts: HashMap<&str, HashMap<Date, usize>> = HashMap::new();
+    for vj in c.vehicle_journeys.values() {
+        let (route, calendar) = match (c.routes.get(&vj.route_id), c.calendars.get(&vj.service_id))
+        {
+            (Some(route), Some(calendar)) => (route, calendar),
+            _ => continue,
+        };
+        let line_counts = counts.entry(route.line_id.as_str()).or_default();
+        for date in &calendar.dates {
+            *line_counts.entry(*date).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+impl Collections {
+    /// Checks that each line has at least the number of vehicle journeys
+    /// given by the `thresholds` on each day of service of the dataset (the
+    /// days with at least one vehicle journey). The lines that don't meet
+    /// their thresholds are added to the errors of the `report`.
+    pub fn check_service_thresholds(&self, thresholds: &ServiceThresholds, report: &mut Report) {
+        let service_dates: BTreeSet<Date> = self
+            .vehicle_journeys
+            .values()
+            .filter_map(|vj| self.calendars.get(&vj.service_id))
+            .flat_map(|calendar| calendar.dates.iter().cloned())
+            .collect();
+        let counts = count_vehicle_journeys(self);
+        let no_count = HashMap::new();
+        for line in self.lines.values() {
+            let line_thresholds = match thresholds.of_line(line) {
+                Some(line_thresholds) => line_thresholds,
+                None => continue,
+            };
+            let line_counts = counts.get(line.id.as_str()).unwrap_or(&no_count);
+            // the days below the threshold, by type of day
+            let mut insufficient_days: BTreeMap<&str, (usize, Vec<(Date, usize)>)> =
+                BTreeMap::new();
+            for date in &service_dates {
+                let (day_type, minimum) = match date.weekday() {
+                    Weekday::Sat => (
+                        "saturday",
+                        line_thresholds.min_vehicle_journeys_per_saturday,
+                    ),
+                    Weekday::Sun => ("sunday", line_thresholds.min_vehicle_journeys_per_sunday),
+                    _ => ("weekday", line_thresholds.min_vehicle_journeys_per_weekday),
+                };
+                let minimum = match minimum {
+                    Some(minimum) => minimum,
+                    None => continue,
+                };
+                let count = line_counts.get(date).cloned().unwrap_or(0);
+                if count < minimum {
+                    insufficient_days
+                        .entry(day_type)
+                        .or_insert_with(|| (minimum, vec![]))
+                        .1
+                        .push((*date, count));
+                }
+            }
+            for (day_type, (minimum, days)) in insufficient_days {
+                let (date, count) = days
+                    .iter()
+                    .min_by_key(|(_, count)| *count)
+                    .cloned()
+                    .unwrap();
+                report.add(
+                    Severity::Error,
+                    ReportEntry::new(
+                        ReportType::InsufficientService,
+                        format!(
+                            "{} {}(s) with fewer than {} vehicle journeys, the fewest on {}: {}",
+                            days.len(),
+                            day_type,
+                            minimum,
+                            date,
+                            count
+                        ),
+                    )
+                    .with_object(ObjectType::Line, &line.id),
+                );
+            }
+        }
+    }
+
+    /// Runs the catalogue of consistency checks on the collections: dangling
+    /// references, stop times not increasing, vehicle journeys without
+    /// calendar, geometries referenced but absent, overlapping transfers,
+    /// transfer times longer than a day and invalid languages.
+    ///
+    /// The violations are returned as the errors of a
+    /// [Report](../report/struct.Report.html). As a `Model` dereferences to
+    /// its `Collections`, the checks can also be run on a `Model`.
+    pub fn check_consistency(&self) -> Report {
+        let mut report = Report::default();
+        for (name, check) in CHECKS {
+            debug!("Running consistency check {:?}", name);
+            check(self, &mut report);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{
+        Calendar, Network, Route, StopArea, StopPoint, StopTime, Time, Transfer, VehicleJourney,
+    };
+    use pretty_assertions::assert_eq;
+    use typed_index_collection::{Collection, Idx};
+
+    fn categories(report: &Report) -> Vec<ReportType> {
+        report
+            .entries(Severity::Error)
+            .iter()
+            .map(|e| e.category)
+            .collect()
+    }
+
+    fn stop_time(
+        stop_point_idx: Idx<StopPoint>,
+        sequence: u32,
+        arrival: Time,
+        departure: Time,
+    ) -> StopTime {
+        StopTime {
+            stop_point_idx,
+            sequence,
+            arrival_time: arrival,
+            departure_time: departure,
+            boarding_duration: 0,
+            alighting_duration: 0,
+            pickup_type: 0,
+            drop_off_type: 0,
+            datetime_estimated: false,
+            local_zone_id: None,
+            precision: None,
+        }
+    }
+
+    #[test]
+    fn consistent_collections() {
+        let collections = Collections::default();
+        assert!(collections.check_consistency().errors.is_empty());
+    }
+
+    #[test]
+    fn detect_violations() {
+        let mut collections = Collections::default();
+        collections.stop_points = CollectionWithId::new(vec![StopPoint {
+            id: "sp:1".to_string(),
+            stop_area_id: "sa:1".to_string(),
+            ..Default::default()
+        }])
+        .unwrap();
+        let sp_idx = collections.stop_points.get_idx("sp:1").unwrap();
+        let mut calendar = Calendar::new("service:1".to_string());
+        calendar
+            .dates
+            .insert(chrono::NaiveDate::from_ymd(2020, 1, 1));
+        collections.calendars = CollectionWithId::new(vec![calendar]).unwrap();
+        collections.vehicle_journeys = CollectionWithId::new(vec![VehicleJourney {
+            id: "vj:1".to_string(),
+            service_id: "unknown".to_string(),
+            geometry_id: Some("geo:1".to_string()),
+            stop_times: vec![
+                stop_time(sp_idx, 1, Time::new(10, 0, 0), Time::new(10, 0, 0)),
+                stop_time(sp_idx, 2, Time::new(9, 0, 0), Time::new(9, 0, 0)),
+            ],
+            ..Default::default()
+        }])
+        .unwrap();
+        let transfer = Transfer {
+            from_stop_id: "sp:1".to_string(),
+            to_stop_id: "sp:1".to_string(),
+            min_transfer_time: None,
+            real_min_transfer_time: None,
+            equipment_id: None,
+        };
+        collections.transfers = Collection::new(vec![transfer.clone(), transfer]);
+
+        let report = collections.check_consistency();
+        let mut categories = categories(&report);
+        categories.dedup();
+        assert_eq!(
+            vec![
+                ReportType::DanglingReference,
+                ReportType::StopTimesNotIncreasing,
+                ReportType::VehicleJourneyWithoutCalendar,
+                ReportType::MissingGeometry,
+                ReportType::OverlappingTransfers,
+            ],
+            categories
+        );
+        assert!(report
+            .entries(Severity::Error)
+            .iter()
+            .any(|e| e.message == "stop_area_id=\"sa:1\" of \"sp:1\" doesn't exist"));
+    }
+
+    #[test]
+    fn detect_unrealistic_transfer_times() {
+        let mut collections = Collections::default();
+        collections.stop_areas = CollectionWithId::new(vec![StopArea {
+            id: "sa:1".to_string(),
+            ..Default::default()
+        }])
+        .unwrap();
+        let stop_point = |id: &str| StopPoint {
+            id: id.to_string(),
+            stop_area_id: "sa:1".to_string(),
+            ..Default::default()
+        };
+        collections.stop_points =
+            CollectionWithId::new(vec![stop_point("sp:1"), stop_point("sp:2")]).unwrap();
+        let transfer = |to_stop_id: &str, time: u32| Transfer {
+            from_stop_id: "sp:1".to_string(),
+            to_stop_id: to_stop_id.to_string(),
+            min_transfer_time: Some(time),
+            real_min_transfer_time: Some(time),
+            equipment_id: None,
+        };
+        // a timed transfer, a transfer not possible and a transfer longer
+        // than a day
+        collections.transfers = Collection::new(vec![
+            transfer("sp:1", 0),
+            transfer("sp:2", NOT_POSSIBLE_TRANSFER_TIME),
+            Transfer {
+                from_stop_id: "sp:2".to_string(),
+                ..transfer("sp:1", 90000)
+            },
+        ]);
+
+        let report = collections.check_consistency();
+        let errors = report.entries(Severity::Error);
+        assert_eq!(
+            vec![ReportType::UnrealisticTransferTime],
+            categories(&report)
+        );
+        assert_eq!(
+            "transfer time from \"sp:2\" to \"sp:1\" longer than a day",
+            errors[0].message
+        );
+    }
+
+    #[test]
+    fn detect_invalid_languages() {
+        let mut collections = Collections::default();
+        collections
+            .feed_infos
+            .insert("feed_lang".to_string(), "fr_FR".to_string());
+        collections.networks = CollectionWithId::new(vec![
+            Network {
+                id: "network:1".to_string(),
+                lang: Some("en-GB".to_string()),
+                ..Default::default()
+            },
+            Network {
+                id: "network:2".to_string(),
+                lang: Some("english (UK)".to_string()),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+
+        let report = collections.check_consistency();
+        let errors = report.entries(Severity::Error);
+        assert_eq!(2, errors.len());
+        assert!(errors
+            .iter()
+            .all(|e| e.category == ReportType::InvalidValue));
+        assert_eq!(Some("network:2"), errors[1].object_id.as_deref());
+    }
+
+    #[test]
+    fn detect_insufficient_service() {
+        let mut collections = Collections::default();
+        collections.lines = CollectionWithId::new(vec![
+            Line {
+                id: "urban".to_string(),
+                network_id: "network:1".to_string(),
+                ..Default::default()
+            },
+            Line {
+                id: "school".to_string(),
+                network_id: "network:1".to_string(),
+                ..Default::default()
+            },
+            Line {
+                id: "rural".to_string(),
+                network_id: "network:2".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        collections.routes = CollectionWithId::new(vec![
+            Route {
+                id: "urban_route".to_string(),
+                line_id: "urban".to_string(),
+                ..Default::default()
+            },
+            Route {
+                id: "school_route".to_string(),
+                line_id: "school".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        // monday 2020-01-06 and tuesday 2020-01-07, saturday 2020-01-11
+        let mut everyday = Calendar::new("everyday".to_string());
+        everyday
+            .dates
+            .insert(chrono::NaiveDate::from_ymd(2020, 1, 6));
+        everyday
+            .dates
+            .insert(chrono::NaiveDate::from_ymd(2020, 1, 7));
+        everyday
+            .dates
+            .insert(chrono::NaiveDate::from_ymd(2020, 1, 11));
+        let mut monday = Calendar::new("monday".to_string());
+        monday.dates.insert(chrono::NaiveDate::from_ymd(2020, 1, 6));
+        collections.calendars = CollectionWithId::new(vec![everyday, monday]).unwrap();
+        let vj = |id: &str, route_id: &str, service_id: &str| VehicleJourney {
+            id: id.to_string(),
+            route_id: route_id.to_string(),
+            service_id: service_id.to_string(),
+            ..Default::default()
+        };
+        collections.vehicle_journeys = CollectionWithId::new(vec![
+            vj("vj:1", "urban_route", "everyday"),
+            vj("vj:2", "urban_route", "monday"),
+            vj("vj:3", "school_route", "monday"),
+        ])
+        .unwrap();
+        let thresholds: ServiceThresholds = serde_json::from_str(
+            r#"{
+                "default": { "min_vehicle_journeys_per_sunday": 1 },
+                "networks": {
+                    "network:1": {
+                        "min_vehicle_journeys_per_weekday": 2,
+                        "min_vehicle_journeys_per_saturday": 1
+                    },
+                    "network:2": { "min_vehicle_journeys_per_weekday": 1 }
+                },
+                "lines": { "school": { "min_vehicle_journeys_per_weekday": 1 } }
+            }"#,
+        )
+        .unwrap();
+
+        let mut report = Report::default();
+        collections.check_service_thresholds(&thresholds, &mut report);
+        let errors: Vec<_> = report
+            .entries(Severity::Error)
+            .iter()
+            .map(|e| (e.object_id.as_ref().unwrap().as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "urban",
+                    "1 weekday(s) with fewer than 2 vehicle journeys, the fewest on 2020-01-07: 1"
+                ),
+                (
+                    "school",
+                    "1 weekday(s) with fewer than 1 vehicle journeys, the fewest on 2020-01-07: 0"
+                ),
+                (
+                    "rural",
+                    "2 weekday(s) with fewer than 1 vehicle journeys, the fewest on 2020-01-06: 0"
+                ),
+            ],
+            errors
+        );
+        assert!(report
+            .entries(Severity::Error)
+            .iter()
+            .all(|e| e.category == ReportType::InsufficientService));
+    }
+
+    #[test]
+    fn detect_unrealistic_speed() {
+        let mut collections = Collections::default();
+        collections.stop_points = CollectionWithId::new(vec![StopPoint {
+            id: "sp:1".to_string(),
+            ..Default::default()
+        }])
+        .unwrap();
+        let sp_idx = collections.stop_points.get_idx("sp:1").unwrap();
+        let vj = |id: &str, physical_mode_id: &str| VehicleJourney {
+            id: id.to_string(),
+            physical_mode_id: physical_mode_id.to_string(),
+            stop_times: vec![
+                stop_time(sp_idx, 1, Time::new(10, 0, 0), Time::new(10, 0, 0)),
+                stop_time(sp_idx, 2, Time::new(10, 30, 0), Time::new(10, 30, 0)),
+            ],
+            // 300 km/h
+            length: Some(150_000.),
+            ..Default::default()
+        };
+        collections.vehicle_journeys =
+            CollectionWithId::new(vec![vj("bus", "Bus"), vj("train", "Train")]).unwrap();
+
+        let mut report = Report::default();
+        check_speeds(&collections, &mut report);
+        let errors = report.entries(Severity::Error);
+        assert_eq!(1, errors.len());
+        assert_eq!(ReportType::UnrealisticSpeed, errors[0].category);
+        assert_eq!(
+            "average speed of 300 km/h over 150000 m is above 150 km/h for physical_mode_id=\"Bus\"",
+            errors[0].message
+        );
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Reading of the VDV-452 exports (`.x10` files) of the German scheduling
+//! systems.
+//!
+//! The following tables are read, whatever the names of the `.x10` files
+//! containing them:
+//! - `REC_ORT`: the stops (the places of type 1) and their stop areas
+//! - `REC_LID` and `LID_VERLAUF`: the line variants and their places
+//! - `REC_FRT`: the trips, with `SEL_FZT_FELD` (run times), `ORT_HZTF`
+//!   (dwell times) and `REC_FRT_HZT` (dwell times of a trip) to compute the
+//!   times of their stops
+//! - `MENGE_TAGESART` and `FIRMENKALENDER`: the day types and their dates
+//!
+//! The operating areas (`BEREICH_NR`) are ignored: the numbers of the
+//! places, lines and time groups must be unique in the export. Only the
+//! records of one version of the base data (`BASIS_VERSION`) are read, see
+//! [Configuration](struct.Configuration.html).
+
+mod read;
+
+use crate::{
+    model::{Collections, Model},
+    objects::{Contributor, Dataset},
+    validity_period, AddPrefix, PrefixConfiguration, Result,
+};
+use chrono_tz::Tz;
+use log::info;
+use std::{collections::BTreeMap, path::Path};
+use typed_index_collection::CollectionWithId;
+
+/// Timezone of the VDV-452 exports when not configured
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Europe::Berlin;
+
+/// Parameters of the reading of a VDV-452 export
+#[derive(Default)]
+pub struct Configuration {
+    /// The Contributor providing the Dataset
+    pub contributor: Contributor,
+    /// Describe the Dataset being parsed
+    pub dataset: Dataset,
+    /// Additional key-values for the 'feed_infos.txt'
+    pub feed_infos: BTreeMap<String, String>,
+    /// used to prefix objects
+    pub prefix_conf: Option<PrefixConfiguration>,
+    /// Timezone of the trip times, `Europe/Berlin` if not given
+    pub timezone: Option<Tz>,
+    /// Version of the base data (`BASIS_VERSION`) to read, the highest one
+    /// of the export if not given
+    pub basis_version: Option<u32>,
+}
+
+/// Imports a `Model` from the VDV-452 `.x10` files in the `path` directory,
+/// with the default [Configuration](struct.Configuration.html).
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Model> {
+    read_with_configuration(path, Configuration::default())
+}
+
+/// Imports a `Model` from the VDV-452 `.x10` files in the `path` directory.
+///
+/// The day types are converted into calendars with the dates of the company
+/// calendar (`FIRMENKALENDER`). All the lines belong to a default network
+/// and all the trips to a default company, by bus.
+pub fn read_with_configuration<P: AsRef<Path>>(
+    path: P,
+    configuration: Configuration,
+) -> Result<Model> {
+    let path = path.as_ref();
+    info!("Reading VDV-452 export from {:?}", path);
+    let Configuration {
+        contributor,
+        mut dataset,
+        feed_infos,
+        prefix_conf,
+        timezone,
+        basis_version,
+    } = configuration;
+
+    let mut tables = read::read_tables(path)?;
+    read::select_basis_version(&mut tables, basis_version)?;
+    let mut collections = Collections::default();
+    collections.calendars = read::read_day_types(&tables)?;
+    let (stop_areas, stop_points) = read::read_places(&tables)?;
+    collections.stop_areas = stop_areas;
+    collections.stop_points = stop_points;
+    read::manage_lines(
+        &mut collections,
+        &tables,
+        timezone.unwrap_or(DEFAULT_TIMEZONE),
+    )?;
+    read::manage_trips(&mut collections, &tables, &dataset.id)?;
+    validity_period::compute_dataset_validity_period(&mut dataset, &collections.calendars)?;
+
+    collections.contributors = CollectionWithId::from(contributor);
+    collections.datasets = CollectionWithId::from(dataset);
+    collections.feed_infos = feed_infos;
+
+    //add prefixes
+    if let Some(prefix_conf) = prefix_conf {
+        collections.prefix(&prefix_conf);
+    }
+
+    collections.calendar_deduplication();
+    Model::new(collections)
+}
@@ -0,0 +1,667 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use crate::{
+    model::{Collections, BUS_PHYSICAL_MODE},
+    objects::{
+        Calendar, CommercialMode, Company, Coord, Date, Line, Network, PhysicalMode, Route,
+        StopArea, StopPoint, StopTime, Time, VehicleJourney,
+    },
+    read_utils, Result,
+};
+use chrono_tz::Tz;
+use failure::{bail, format_err, ResultExt};
+use log::{info, warn};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+use typed_index_collection::CollectionWithId;
+
+// Type of the places which are stops (`Haltepunkt`), the other places are
+// only used to compute the times
+const STOP_PLACE_TYPE: &str = "1";
+const BUS_COMMERCIAL_MODE: &str = "Bus";
+
+/// A table of a VDV-452 export, with its columns (`atr` line) and records
+/// (`rec` lines)
+#[derive(Debug, Default)]
+pub(crate) struct Table {
+    name: String,
+    columns: Vec<String>,
+    records: Vec<Vec<String>>,
+}
+
+/// The tables of a VDV-452 export, by name
+pub(crate) type Tables = HashMap<String, Table>;
+
+struct Record<'a> {
+    table: &'a Table,
+    values: &'a [String],
+}
+
+impl Table {
+    fn records(&self) -> impl Iterator<Item = Record<'_>> {
+        self.records.iter().map(move |values| Record {
+            table: self,
+            values,
+        })
+    }
+}
+
+impl<'a> Record<'a> {
+    // The value of the `column`, `None` if empty or if the table has no such
+    // column
+    fn value(&self, column: &str) -> Option<&'a str> {
+        let values = self.values;
+        self.table
+            .columns
+            .iter()
+            .position(|c| c == column)
+            .and_then(|i| values.get(i))
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    fn required(&self, column: &str) -> Result<&'a str> {
+        self.value(column)
+            .ok_or_else(|| format_err!("{}: missing value of {}", self.table.name, column))
+    }
+
+    fn number(&self, column: &str) -> Result<u32> {
+        let value = self.required(column)?;
+        Ok(value
+            .parse()
+            .with_context(|_| format!("{}: invalid {} {:?}", self.table.name, column, value))?)
+    }
+
+    // The place (type and number) of the `ONR_TYP_NR` and `ORT_NR` columns
+    fn place(&self) -> Result<Place> {
+        Ok((
+            self.required("ONR_TYP_NR")?.to_string(),
+            self.required("ORT_NR")?.to_string(),
+        ))
+    }
+}
+
+// Type and number of a place
+type Place = (String, String);
+
+fn table<'a>(tables: &'a Tables, name: &str) -> Result<&'a Table> {
+    tables
+        .get(name)
+        .ok_or_else(|| format_err!("table {} not found in the VDV-452 export", name))
+}
+
+fn optional_records<'a>(tables: &'a Tables, name: &str) -> impl Iterator<Item = Record<'a>> {
+    tables.get(name).into_iter().flat_map(Table::records)
+}
+
+// Fields of a line, separated by `;`, the strings being enclosed in `"`
+// (with `""` for a quote inside a string)
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_string = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_string && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            ';' if !in_string => {
+                fields.push(field.trim().to_string());
+                field.clear();
+            }
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+fn insert_table(tables: &mut Tables, table: Table) {
+    match tables.get_mut(&table.name) {
+        // a table can be split in several files
+        Some(existing) => existing.records.extend(table.records),
+        None => {
+            tables.insert(table.name.clone(), table);
+        }
+    }
+}
+
+fn parse_tables(content: &str, tables: &mut Tables) -> Result<()> {
+    let mut table: Option<Table> = None;
+    for line in content.lines() {
+        let mut fields = split_fields(line);
+        let kind = fields.remove(0).to_lowercase();
+        match kind.as_str() {
+            "tbl" => {
+                if let Some(table) = table.take() {
+                    insert_table(tables, table);
+                }
+                table = Some(Table {
+                    name: fields.first().cloned().unwrap_or_default().to_uppercase(),
+                    ..Default::default()
+                });
+            }
+            "atr" => match table.as_mut() {
+                Some(table) => table.columns = fields.iter().map(|c| c.to_uppercase()).collect(),
+                None => bail!("columns (atr) outside of a table"),
+            },
+            "rec" => match table.as_mut() {
+                Some(table) => table.records.push(fields),
+                None => bail!("record (rec) outside of a table"),
+            },
+            "end" => {
+                if let Some(table) = table.take() {
+                    insert_table(tables, table);
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(table) = table {
+        insert_table(tables, table);
+    }
+    Ok(())
+}
+
+/// Reads the tables of all the `.x10` files of the `path` directory
+pub(crate) fn read_tables(path: &Path) -> Result<Tables> {
+    let mut file_paths = vec![];
+    for entry in fs::read_dir(path).with_context(|_| format!("Error reading {:?}", path))? {
+        let file_path: PathBuf = entry
+            .with_context(|_| format!("Error reading {:?}", path))?
+            .path();
+        let is_x10 = file_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("x10"));
+        if is_x10 {
+            file_paths.push(file_path);
+        }
+    }
+    file_paths.sort();
+    let mut tables = Tables::new();
+    for file_path in file_paths {
+        info!("Reading {:?}", file_path);
+        let content = read_utils::read_utf8_or_latin1(&file_path)?;
+        parse_tables(&content, &mut tables)
+            .with_context(|_| format!("Error reading {:?}", file_path))?;
+    }
+    Ok(tables)
+}
+
+/// Keeps the records of the version `basis_version` of the base data
+/// (`BASIS_VERSION` column), the highest one of the export if not given; the
+/// records without version are kept. Fails if `basis_version` is not in the
+/// export.
+pub(crate) fn select_basis_version(tables: &mut Tables, basis_version: Option<u32>) -> Result<()> {
+    let mut versions = BTreeSet::new();
+    for table in tables.values() {
+        for record in table.records() {
+            if record.value("BASIS_VERSION").is_some() {
+                versions.insert(record.number("BASIS_VERSION")?);
+            }
+        }
+    }
+    let version = match (basis_version, versions.iter().next_back()) {
+        (Some(version), _) if versions.contains(&version) => version,
+        (Some(version), _) => bail!("BASIS_VERSION {} not found in the VDV-452 export", version),
+        (None, Some(&highest)) => highest,
+        (None, None) => return Ok(()),
+    };
+    if versions.len() > 1 {
+        info!(
+            "Reading BASIS_VERSION {} of the versions {:?}",
+            version, versions
+        );
+    }
+    for table in tables.values_mut() {
+        if let Some(i) = table.columns.iter().position(|c| c == "BASIS_VERSION") {
+            table.records.retain(|values| {
+                values.get(i).map_or(true, |value| {
+                    value.is_empty() || value.parse::<u32>().ok() == Some(version)
+                })
+            });
+        }
+    }
+    Ok(())
+}
+
+fn day_type_id(day_type: &str) -> String {
+    format!("day_type:{}", day_type)
+}
+
+fn route_id(line_number: &str, variant: &str) -> String {
+    format!("{}:{}", line_number, variant)
+}
+
+/// Calendars of the day types, with their dates in `FIRMENKALENDER`
+pub(crate) fn read_day_types(tables: &Tables) -> Result<CollectionWithId<Calendar>> {
+    let mut calendars: BTreeMap<&str, Calendar> = BTreeMap::new();
+    for record in table(tables, "FIRMENKALENDER")?.records() {
+        let day_type = record.required("TAGESART_NR")?;
+        let day = record.required("BETRIEBSTAG")?;
+        let date = Date::parse_from_str(day, "%Y%m%d")
+            .with_context(|_| format!("FIRMENKALENDER: invalid BETRIEBSTAG {:?}", day))?;
+        calendars
+            .entry(day_type)
+            .or_insert_with(|| Calendar::new(day_type_id(day_type)))
+            .dates
+            .insert(date);
+    }
+    for record in optional_records(tables, "MENGE_TAGESART") {
+        let day_type = record.required("TAGESART_NR")?;
+        if !calendars.contains_key(day_type) {
+            warn!(
+                "day type {} ({}) has no date in FIRMENKALENDER",
+                day_type,
+                record.value("TAGESART_TEXT").unwrap_or_default()
+            );
+        }
+    }
+    Ok(CollectionWithId::new(
+        calendars
+            .into_iter()
+            .map(|(_, calendar)| calendar)
+            .collect(),
+    )?)
+}
+
+// A WGS84 coordinate in the VDV-452 format `[-]DDDMMSSsss` (degrees,
+// minutes, seconds and thousandths of second)
+fn parse_coordinate(value: &str) -> Result<f64> {
+    let number: i64 = value
+        .parse()
+        .with_context(|_| format!("REC_ORT: invalid coordinate {:?}", value))?;
+    let abs = number.abs();
+    let coordinate = (abs / 10_000_000) as f64
+        + (abs / 100_000 % 100) as f64 / 60.0
+        + (abs % 100_000) as f64 / 1000.0 / 3600.0;
+    Ok(if number < 0 { -coordinate } else { coordinate })
+}
+
+/// Stop points and stop areas of the places of `REC_ORT` which are stops,
+/// the stop area being the referenced stop (`ORT_REF_ORT`)
+pub(crate) fn read_places(
+    tables: &Tables,
+) -> Result<(CollectionWithId<StopArea>, CollectionWithId<StopPoint>)> {
+    let mut stop_areas = CollectionWithId::default();
+    let mut stop_points = CollectionWithId::default();
+    for record in table(tables, "REC_ORT")?.records() {
+        if record.required("ONR_TYP_NR")? != STOP_PLACE_TYPE {
+            continue;
+        }
+        let id = record.required("ORT_NR")?;
+        let coord = match (
+            record.value("ORT_POS_LAENGE"),
+            record.value("ORT_POS_BREITE"),
+        ) {
+            (Some(lon), Some(lat)) => Coord {
+                lon: parse_coordinate(lon)?,
+                lat: parse_coordinate(lat)?,
+            },
+            _ => {
+                warn!("REC_ORT: stop {:?} has no coordinates", id);
+                Coord::default()
+            }
+        };
+        let name = record.value("ORT_NAME").unwrap_or(id);
+        let stop_area = match record.value("ORT_REF_ORT") {
+            Some(stop_area_number) => StopArea {
+                id: format!("stop_area:{}", stop_area_number),
+                name: record.value("ORT_REF_ORT_NAME").unwrap_or(name).to_string(),
+                coord,
+                visible: true,
+                ..Default::default()
+            },
+            None => StopArea {
+                id: format!("Navitia:{}", id),
+                name: name.to_string(),
+                coord,
+                visible: true,
+                ..Default::default()
+            },
+        };
+        stop_points.push(StopPoint {
+            id: id.to_string(),
+            name: name.to_string(),
+            coord,
+            stop_area_id: stop_area.id.clone(),
+            visible: true,
+            ..Default::default()
+        })?;
+        if !stop_areas.contains_id(&stop_area.id) {
+            stop_areas.push(stop_area)?;
+        }
+    }
+    Ok((stop_areas, stop_points))
+}
+
+/// Lines and routes of the line variants of `REC_LID`, with a default
+/// network, company and modes
+pub(crate) fn manage_lines(
+    collections: &mut Collections,
+    tables: &Tables,
+    timezone: Tz,
+) -> Result<()> {
+    let network = Network {
+        timezone: Some(timezone),
+        ..Default::default()
+    };
+    let network_id = network.id.clone();
+    collections.networks = CollectionWithId::from(network);
+    collections.companies = CollectionWithId::from(Company::default());
+    collections.commercial_modes = CollectionWithId::from(CommercialMode {
+        id: BUS_COMMERCIAL_MODE.to_string(),
+        name: BUS_COMMERCIAL_MODE.to_string(),
+    });
+    collections.physical_modes = CollectionWithId::from(PhysicalMode {
+        id: BUS_PHYSICAL_MODE.to_string(),
+        name: BUS_PHYSICAL_MODE.to_string(),
+        co2_emission: None,
+    });
+
+    for record in table(tables, "REC_LID")?.records() {
+        let line_number = record.required("LI_NR")?;
+        if !collections.lines.contains_id(line_number) {
+            let code = record.value("LI_KUERZEL").unwrap_or(line_number);
+            collections.lines.push(Line {
+                id: line_number.to_string(),
+                code: Some(code.to_string()),
+                name: code.to_string(),
+                network_id: network_id.clone(),
+                commercial_mode_id: BUS_COMMERCIAL_MODE.to_string(),
+                ..Default::default()
+            })?;
+        }
+        let direction_type = match record.value("LI_RI_NR") {
+            Some("1") => Some("forward".to_string()),
+            Some("2") => Some("backward".to_string()),
+            _ => None,
+        };
+        collections.routes.push(Route {
+            id: route_id(line_number, record.required("STR_LI_VAR")?),
+            name: record.value("LIDNAME").unwrap_or_default().to_string(),
+            direction_type,
+            line_id: line_number.to_string(),
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
+
+// Run and dwell times, in seconds
+#[derive(Default)]
+struct Durations {
+    // by time group (`FGR_NR`), from a place to the next one
+    run_times: HashMap<(String, Place, Place), u32>,
+    // by time group, at a place
+    dwell_times: HashMap<(String, Place), u32>,
+    // by trip, at a place, overriding the dwell times of the time group
+    trip_dwell_times: HashMap<(String, Place), u32>,
+}
+
+impl Durations {
+    fn read(tables: &Tables) -> Result<Self> {
+        let mut durations = Durations::default();
+        for record in table(tables, "SEL_FZT_FELD")?.records() {
+            let to = (
+                record.required("SEL_ZIEL_TYP")?.to_string(),
+                record.required("SEL_ZIEL")?.to_string(),
+            );
+            durations.run_times.insert(
+                (record.required("FGR_NR")?.to_string(), record.place()?, to),
+                record.number("SEL_FZT")?,
+            );
+        }
+        for record in optional_records(tables, "ORT_HZTF") {
+            durations.dwell_times.insert(
+                (record.required("FGR_NR")?.to_string(), record.place()?),
+                record.number("HP_HZT")?,
+            );
+        }
+        for record in optional_records(tables, "REC_FRT_HZT") {
+            durations.trip_dwell_times.insert(
+                (record.required("FRT_FID")?.to_string(), record.place()?),
+                record.number("FRT_HZT_ZEIT")?,
+            );
+        }
+        Ok(durations)
+    }
+
+    // Stop times of the trip leaving the first of `places` at `start` (in
+    // seconds after midnight); the places which are not stops are only
+    // used to accumulate the run times
+    fn stop_times(
+        &self,
+        trip_id: &str,
+        time_group: &str,
+        start: u32,
+        places: &[Place],
+        stop_points: &CollectionWithId<StopPoint>,
+    ) -> Result<Vec<StopTime>> {
+        let mut stop_times = vec![];
+        let mut time = start;
+        for (i, place) in places.iter().enumerate() {
+            if i > 0 {
+                let key = (time_group.to_string(), places[i - 1].clone(), place.clone());
+                time += self.run_times.get(&key).ok_or_else(|| {
+                    format_err!(
+                        "no run time from place {:?} to place {:?} for the time group {}",
+                        places[i - 1],
+                        place,
+                        time_group
+                    )
+                })?;
+            }
+            let arrival = time;
+            time += self
+                .trip_dwell_times
+                .get(&(trip_id.to_string(), place.clone()))
+                .or_else(|| {
+                    self.dwell_times
+                        .get(&(time_group.to_string(), place.clone()))
+                })
+                .cloned()
+                .unwrap_or(0);
+            if place.0 != STOP_PLACE_TYPE {
+                continue;
+            }
+            if let Some(stop_point_idx) = stop_points.get_idx(&place.1) {
+                stop_times.push(StopTime {
+                    stop_point_idx,
+                    sequence: stop_times.len() as u32,
+                    arrival_time: Time::new(0, 0, arrival),
+                    departure_time: Time::new(0, 0, time),
+                    boarding_duration: 0,
+                    alighting_duration: 0,
+                    pickup_type: 0,
+                    drop_off_type: 0,
+                    datetime_estimated: false,
+                    local_zone_id: None,
+                    precision: None,
+                });
+            }
+        }
+        Ok(stop_times)
+    }
+}
+
+/// Trips of `REC_FRT`, their stop times computed from the places of their
+/// line variant (`LID_VERLAUF`) and the durations of their time group
+pub(crate) fn manage_trips(
+    collections: &mut Collections,
+    tables: &Tables,
+    dataset_id: &str,
+) -> Result<()> {
+    let mut route_places: HashMap<String, Vec<(u32, Place)>> = HashMap::new();
+    for record in table(tables, "LID_VERLAUF")?.records() {
+        route_places
+            .entry(route_id(
+                record.required("LI_NR")?,
+                record.required("STR_LI_VAR")?,
+            ))
+            .or_insert_with(Vec::new)
+            .push((record.number("LI_LFD_NR")?, record.place()?));
+    }
+    let route_places: HashMap<String, Vec<Place>> = route_places
+        .into_iter()
+        .map(|(route_id, mut places)| {
+            places.sort();
+            (
+                route_id,
+                places.into_iter().map(|(_, place)| place).collect(),
+            )
+        })
+        .collect();
+    let durations = Durations::read(tables)?;
+    // the trips are operated by the default company of manage_lines()
+    let company_id = Company::default().id;
+
+    for record in table(tables, "REC_FRT")?.records() {
+        let trip_id = record.required("FRT_FID")?;
+        let route_id = route_id(record.required("LI_NR")?, record.required("STR_LI_VAR")?);
+        let service_id = day_type_id(record.required("TAGESART_NR")?);
+        if !collections.calendars.contains_id(&service_id) {
+            warn!(
+                "REC_FRT: trip {:?} skipped, its day type has no date",
+                trip_id
+            );
+            continue;
+        }
+        let places = match route_places.get(&route_id) {
+            Some(places) if collections.routes.contains_id(&route_id) => places,
+            _ => {
+                warn!(
+                    "REC_FRT: trip {:?} skipped, line variant {:?} not found",
+                    trip_id, route_id
+                );
+                continue;
+            }
+        };
+        let stop_times = match durations.stop_times(
+            trip_id,
+            record.required("FGR_NR")?,
+            record.number("FRT_START")?,
+            places,
+            &collections.stop_points,
+        ) {
+            Ok(stop_times) if stop_times.len() >= 2 => stop_times,
+            Ok(_) => {
+                warn!(
+                    "REC_FRT: trip {:?} skipped, it has less than 2 stops",
+                    trip_id
+                );
+                continue;
+            }
+            Err(e) => {
+                warn!("REC_FRT: trip {:?} skipped, {}", trip_id, e);
+                continue;
+            }
+        };
+        collections.vehicle_journeys.push(VehicleJourney {
+            id: trip_id.to_string(),
+            route_id,
+            physical_mode_id: BUS_PHYSICAL_MODE.to_string(),
+            dataset_id: dataset_id.to_string(),
+            service_id,
+            company_id: company_id.clone(),
+            stop_times,
+            ..Default::default()
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn split_line_fields() {
+        assert_eq!(
+            vec!["rec", "1", "100", "Am \"Markt\"; Süd", ""],
+            split_fields("rec; 1; 100; \"Am \"\"Markt\"\"; Süd\"; ")
+        );
+    }
+
+    #[test]
+    fn parse_vdv_coordinates() {
+        assert_relative_eq!(
+            13.410034,
+            parse_coordinate("132436123").unwrap(),
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(
+            -0.5,
+            parse_coordinate("-0003000000").unwrap(),
+            epsilon = 1e-6
+        );
+        assert!(parse_coordinate("13°24'").is_err());
+    }
+
+    #[test]
+    fn parse_tables_of_a_file() {
+        let content = "mod; DD.MM.YYYY; HH:MM:SS; free\n\
+                       tbl; MENGE_TAGESART\n\
+                       atr; BASIS_VERSION; TAGESART_NR; TAGESART_TEXT\n\
+                       frm; num[9.0]; num[3.0]; char[40]\n\
+                       rec; 1; 1; \"Montag - Freitag\"\n\
+                       rec; 1; 7; \"Sonntag\"\n\
+                       end; 2\n\
+                       eof; 1\n";
+        let mut tables = Tables::new();
+        parse_tables(content, &mut tables).unwrap();
+        let day_types = table(&tables, "MENGE_TAGESART").unwrap();
+        let texts: Vec<&str> = day_types
+            .records()
+            .map(|record| record.required("TAGESART_TEXT").unwrap())
+            .collect();
+        assert_eq!(vec!["Montag - Freitag", "Sonntag"], texts);
+        assert!(parse_tables("rec; 1; 2\n", &mut tables).is_err());
+    }
+
+    #[test]
+    fn select_the_basis_version() {
+        let content = "tbl; MENGE_TAGESART\n\
+                       atr; BASIS_VERSION; TAGESART_NR; TAGESART_TEXT\n\
+                       rec; 1; 1; \"Montag - Freitag\"\n\
+                       rec; 2; 1; \"Werktag\"\n\
+                       rec; 10; 1; \"Schultag\"\n\
+                       rec; ; 7; \"Sonntag\"\n\
+                       end; 4\n";
+        let texts = |basis_version| {
+            let mut tables = Tables::new();
+            parse_tables(content, &mut tables).unwrap();
+            select_basis_version(&mut tables, basis_version).map(|_| {
+                table(&tables, "MENGE_TAGESART")
+                    .unwrap()
+                    .records()
+                    .map(|record| record.required("TAGESART_TEXT").unwrap().to_string())
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(vec!["Schultag", "Sonntag"], texts(None).unwrap());
+        assert_eq!(vec!["Werktag", "Sonntag"], texts(Some(2)).unwrap());
+        assert!(texts(Some(3)).is_err());
+    }
+}
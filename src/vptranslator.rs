@@ -110,23 +110,19 @@ fn clean_extra_dates(start_date: Date, end_date: Date, dates: &mut Vec<Exception
     dates.retain(|d| d.date >= start_date && d.date <= end_date);
 }
 
-///Allows you to present a list of dates in a readable way.
-pub fn translate(dates: &BTreeSet<Date>) -> BlockPattern {
-    let start_date = match dates.iter().next() {
-        Some(d) => *d,
-        None => return BlockPattern::default(),
-    };
-    let end_date: Date = *dates.iter().next_back().unwrap();
-
+///Lists the exceptions of the dates of `validity_pattern` to a weekly pattern
+fn compute_exceptions(
+    start_date: Date,
+    end_date: Date,
+    validity_pattern: &[u8],
+    week_pattern: u8,
+) -> Vec<ExceptionDate> {
     let mut monday_ref = get_prev_monday(start_date);
-    let validity_pattern = compute_validity_pattern(monday_ref, end_date, &dates);
-    let best_week = get_min_week_pattern(&validity_pattern);
-    let operating_days = get_operating_days(best_week);
     let mut exceptions_list = Vec::new();
 
-    for week in validity_pattern {
-        if week != best_week {
-            let exception: u8 = (!best_week) & week;
+    for &week in validity_pattern {
+        if week != week_pattern {
+            let exception: u8 = (!week_pattern) & week;
             fill_exceptions(
                 monday_ref,
                 exception,
@@ -134,7 +130,7 @@ pub fn translate(dates: &BTreeSet<Date>) -> BlockPattern {
                 &mut exceptions_list,
             );
 
-            let exception: u8 = (week ^ best_week) & best_week;
+            let exception: u8 = (week ^ week_pattern) & week_pattern;
             fill_exceptions(
                 monday_ref,
                 exception,
@@ -145,8 +141,44 @@ pub fn translate(dates: &BTreeSet<Date>) -> BlockPattern {
         monday_ref += Duration::days(7);
     }
     clean_extra_dates(start_date, end_date, &mut exceptions_list);
+    exceptions_list
+}
+
+///Allows you to present a list of dates in a readable way.
+///
+///When the exceptions outnumber the dates given by the weekly pattern (e.g.
+///a service interrupted by a long holiday period), every weekly pattern is
+///tried and the one with the fewest exceptions is kept.
+pub fn translate(dates: &BTreeSet<Date>) -> BlockPattern {
+    let start_date = match dates.iter().next() {
+        Some(d) => *d,
+        None => return BlockPattern::default(),
+    };
+    let end_date: Date = *dates.iter().next_back().unwrap();
+
+    let validity_pattern = compute_validity_pattern(get_prev_monday(start_date), end_date, &dates);
+    let mut best_week = get_min_week_pattern(&validity_pattern);
+    let mut exceptions_list =
+        compute_exceptions(start_date, end_date, &validity_pattern, best_week);
+
+    let added_days = exceptions_list
+        .iter()
+        .filter(|e| e.exception_type == ExceptionType::Add)
+        .count();
+    let regular_days = dates.len() - added_days;
+    if exceptions_list.len() > regular_days {
+        for week_pattern in 0..128 {
+            let exceptions =
+                compute_exceptions(start_date, end_date, &validity_pattern, week_pattern);
+            if exceptions.len() < exceptions_list.len() {
+                best_week = week_pattern;
+                exceptions_list = exceptions;
+            }
+        }
+    }
+
     BlockPattern {
-        operating_days,
+        operating_days: get_operating_days(best_week),
         validity_period: Some(ValidityPeriod {
             start_date,
             end_date,
@@ -626,4 +658,39 @@ mod tests {
             res.validity_period.unwrap()
         )
     }
+
+    #[test]
+    fn fewer_exceptions_than_min_week_pattern() {
+        // a sunday then a monday: one exception with the sunday pattern
+        let res = translate(&get_dates_from_bitset(
+            Date::from_ymd(2018, 1, 1),
+            &format!("{}{}", "0000001", "1000000"),
+        ));
+
+        assert_eq!(0b000_0001, get_week_from_weekday(res.operating_days));
+        assert_eq!(
+            vec![ExceptionDate {
+                date: Date::from_ymd(2018, 1, 8),
+                exception_type: ExceptionType::Add,
+            }],
+            res.exceptions
+        );
+    }
+
+    #[test]
+    fn long_interruption() {
+        // a week of service, 6 weeks of interruption and another week:
+        // listing the 10 dates is shorter than removing 30 weekdays
+        let res = translate(&get_dates_from_bitset(
+            Date::from_ymd(2012, 7, 2),
+            &format!("{}{}{}", "1111100", "0000000".repeat(6), "1111100"),
+        ));
+
+        assert_eq!(0b000_0000, get_week_from_weekday(res.operating_days));
+        assert_eq!(10, res.exceptions.len());
+        assert!(res
+            .exceptions
+            .iter()
+            .all(|e| e.exception_type == ExceptionType::Add));
+    }
 }
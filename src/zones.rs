@@ -0,0 +1,296 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+//! Zones of the zonal on-demand transport services (flexible areas).
+//!
+//! A zone is a stop point of type [Zone](../objects/enum.StopType.html),
+//! with the polygon of its area as geometry, in a stop area of its own. The
+//! vehicle journeys of a zonal service serve zones by their stop times,
+//! instead of fixed stops. In a NTFS, the zones are written as stops of
+//! `location_type` 2 (geographic areas) with their polygons in
+//! `geometries.txt`.
+
+use crate::{
+    model::{Collections, GetCorresponding, Model},
+    objects::{Coord, Geometry, Line, StopArea, StopPoint, StopType, VehicleJourney},
+    Result,
+};
+use failure::ResultExt;
+use geo::{algorithm::centroid::Centroid, LineString, Point};
+use log::{info, warn, Level as LogLevel};
+use relational_types::IdxSet;
+use serde::Deserialize;
+use skip_error::skip_error_and_log;
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+use typed_index_collection::{CollectionWithId, Idx};
+
+/// A zone: its stop point, its stop area and its geometry
+pub(crate) type Zone = (StopPoint, StopArea, Geometry);
+
+#[derive(Deserialize, Debug)]
+struct Locations {
+    features: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct LocationProperties {
+    stop_name: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+enum LocationGeometry {
+    Polygon {
+        coordinates: Vec<Vec<Vec<f64>>>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<Vec<Vec<f64>>>>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct Location {
+    id: String,
+    #[serde(default)]
+    properties: LocationProperties,
+    geometry: LocationGeometry,
+}
+
+fn make_polygon(rings: &[Vec<Vec<f64>>]) -> Option<geo::Polygon<f64>> {
+    let mut rings = rings.iter().map(|ring| {
+        LineString::from(
+            ring.iter()
+                .filter(|position| position.len() >= 2)
+                .map(|position| (position[0], position[1]))
+                .collect::<Vec<_>>(),
+        )
+    });
+    let exterior = rings.next()?;
+    Some(geo::Polygon::new(exterior, rings.collect()))
+}
+
+pub(crate) fn make_zone(
+    id: String,
+    name: String,
+    geometry: geo::Geometry<f64>,
+    centroid: Option<Point<f64>>,
+) -> Zone {
+    let geometry = Geometry {
+        id: format!("zone:{}", id),
+        geometry,
+    };
+    let coord = centroid
+        .map(|point| Coord {
+            lon: point.x(),
+            lat: point.y(),
+        })
+        .unwrap_or_default();
+    let mut stop_point = StopPoint {
+        id,
+        name,
+        visible: true,
+        coord,
+        geometry_id: Some(geometry.id.clone()),
+        stop_type: StopType::Zone,
+        ..Default::default()
+    };
+    let stop_area = StopArea::from(stop_point.clone());
+    stop_point.stop_area_id = stop_area.id.clone();
+    (stop_point, stop_area, geometry)
+}
+
+// Reads the zones of the GeoJSON features (with an `id`, a `Polygon` or
+// `MultiPolygon` geometry and an optional `stop_name` or `name` property)
+pub(crate) fn read_geojson_zones<R: Read>(reader: R, path: &Path) -> Result<Vec<Zone>> {
+    let locations: Locations =
+        serde_json::from_reader(reader).with_context(|_| format!("Error reading {:?}", path))?;
+    let mut zones = vec![];
+    for feature in locations.features {
+        let location: Location = skip_error_and_log!(
+            serde_json::from_value(feature).with_context(|_| format!("Error reading {:?}", path)),
+            LogLevel::Warn
+        );
+        let (centroid, geometry) = match location.geometry {
+            LocationGeometry::Polygon { coordinates } => match make_polygon(&coordinates) {
+                Some(polygon) => (polygon.centroid(), geo::Geometry::Polygon(polygon)),
+                None => {
+                    warn!("{:?}: location {} has no geometry", path, location.id);
+                    continue;
+                }
+            },
+            LocationGeometry::MultiPolygon { coordinates } => {
+                let multi_polygon = geo::MultiPolygon(
+                    coordinates
+                        .iter()
+                        .filter_map(|polygon| make_polygon(polygon))
+                        .collect(),
+                );
+                (
+                    multi_polygon.centroid(),
+                    geo::Geometry::MultiPolygon(multi_polygon),
+                )
+            }
+        };
+        let properties = location.properties;
+        let name = properties
+            .stop_name
+            .or(properties.name)
+            .unwrap_or_else(|| location.id.clone());
+        zones.push(make_zone(location.id, name, geometry, centroid));
+    }
+    Ok(zones)
+}
+
+// The identifier `id`, or `<id>:<n>` with the smallest `n` such that the
+// identifier is not used in the `collection`
+fn unused_id<T>(id: String, collection: &CollectionWithId<T>) -> String {
+    if !collection.contains_id(&id) {
+        return id;
+    }
+    (1..)
+        .map(|n| format!("{}:{}", id, n))
+        .find(|unused_id| !collection.contains_id(unused_id))
+        .unwrap()
+}
+
+// Adds the `zones` to the `collections`, the ones with the identifier of an
+// existing stop point are ignored; the identifiers of their stop area and
+// geometry are suffixed with `:<n>` when already used
+pub(crate) fn add_zones(collections: &mut Collections, zones: Vec<Zone>) -> Result<()> {
+    for (mut stop_point, mut stop_area, mut geometry) in zones {
+        if collections.stop_points.contains_id(&stop_point.id) {
+            warn!(
+                "zone {} ignored, a stop with the same identifier exists",
+                stop_point.id
+            );
+            continue;
+        }
+        geometry.id = unused_id(geometry.id, &collections.geometries);
+        stop_point.geometry_id = Some(geometry.id.clone());
+        stop_area.id = unused_id(stop_area.id, &collections.stop_areas);
+        stop_point.stop_area_id = stop_area.id.clone();
+        collections.geometries.push(geometry)?;
+        collections.stop_areas.push(stop_area)?;
+        collections.stop_points.push(stop_point)?;
+    }
+    Ok(())
+}
+
+/// Reads the zones of the GeoJSON file at `path` into the `collections`.
+///
+/// Each feature of the `FeatureCollection` is a zone, identified by its `id`,
+/// with a `Polygon` or `MultiPolygon` geometry and optionally a `name` (or
+/// `stop_name`) property. The zones with the identifier of an existing stop
+/// point are ignored.
+pub fn read_zones<P: AsRef<Path>>(path: P, collections: &mut Collections) -> Result<()> {
+    let path = path.as_ref();
+    info!("Reading zones {:?}", path);
+    let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    let zones = read_geojson_zones(BufReader::new(file), path)?;
+    add_zones(collections, zones)
+}
+
+/// Whether the `vehicle_journey` serves at least one zone
+pub fn serves_zones(
+    vehicle_journey: &VehicleJourney,
+    stop_points: &CollectionWithId<StopPoint>,
+) -> bool {
+    vehicle_journey
+        .stop_times
+        .iter()
+        .any(|stop_time| stop_points[stop_time.stop_point_idx].stop_type == StopType::Zone)
+}
+
+/// The zones served by the vehicle journeys of the line `line_idx`
+pub fn line_zones(model: &Model, line_idx: Idx<Line>) -> IdxSet<StopPoint> {
+    let stop_point_idxs: IdxSet<StopPoint> = model.get_corresponding_from_idx(line_idx);
+    stop_point_idxs
+        .into_iter()
+        .filter(|stop_point_idx| model.stop_points[*stop_point_idx].stop_type == StopType::Zone)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn read_features() {
+        let geojson = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "id": "zone:1",
+                    "type": "Feature",
+                    "properties": {"name": "North"},
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [[[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0], [0.0, 0.0]]]
+                    }
+                },
+                {
+                    "id": "zone:2",
+                    "type": "Feature",
+                    "geometry": {"type": "Point", "coordinates": [1.0, 1.0]}
+                }
+            ]
+        }"#;
+        let zones = read_geojson_zones(geojson.as_bytes(), Path::new("zones.geojson")).unwrap();
+        assert_eq!(1, zones.len());
+        let (stop_point, stop_area, geometry) = &zones[0];
+        assert_eq!("North", stop_point.name);
+        assert_eq!(StopType::Zone, stop_point.stop_type);
+        assert_eq!(Coord { lon: 1.0, lat: 1.0 }, stop_point.coord);
+        assert_eq!(stop_area.id, stop_point.stop_area_id);
+        assert_eq!(Some(&geometry.id), stop_point.geometry_id.as_ref());
+    }
+
+    #[test]
+    fn add_zones_with_used_identifiers() {
+        let mut collections = Collections::default();
+        let (stop_point, stop_area, geometry) = make_zone(
+            "zone:1".to_string(),
+            "North".to_string(),
+            geo::Geometry::Point(Point::new(1.0, 1.0)),
+            None,
+        );
+        collections
+            .geometries
+            .push(Geometry {
+                id: geometry.id.clone(),
+                geometry: geo::Geometry::Point(Point::new(0.0, 0.0)),
+            })
+            .unwrap();
+        collections
+            .stop_areas
+            .push(StopArea {
+                id: stop_area.id.clone(),
+                ..Default::default()
+            })
+            .unwrap();
+        add_zones(&mut collections, vec![(stop_point, stop_area, geometry)]).unwrap();
+
+        let stop_point = collections.stop_points.get("zone:1").unwrap();
+        assert_eq!("Navitia:zone:1:1", stop_point.stop_area_id);
+        assert!(collections.stop_areas.contains_id("Navitia:zone:1:1"));
+        assert_eq!(Some("zone:zone:1:1".to_string()), stop_point.geometry_id);
+        assert!(collections.geometries.contains_id("zone:zone:1:1"));
+    }
+}
@@ -0,0 +1,347 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use chrono::NaiveDate;
+use pretty_assertions::assert_eq;
+use std::fs::File;
+use transit_model::{
+    clean::{self, CleanOptions},
+    ntfs,
+    objects::{Calendar, Comment, Geometry, Time, Transfer},
+    report::{Report, ReportType, Severity},
+    test_utils::*,
+    Model,
+};
+use typed_index_collection::Collection;
+
+fn comment(id: &str, name: &str) -> Comment {
+    Comment {
+        id: id.to_string(),
+        name: name.to_string(),
+        ..Default::default()
+    }
+}
+
+fn geometry(id: &str, x: f64) -> Geometry {
+    Geometry {
+        id: id.to_string(),
+        geometry: geo::Geometry::LineString(geo::line_string![
+            (x: x, y: 48.85),
+            (x: x + 0.01, y: 48.86)
+        ]),
+    }
+}
+
+#[test]
+fn dedup_comments() {
+    let mut collections = ntfs::read("tests/fixtures/minimal_ntfs/")
+        .unwrap()
+        .into_collections();
+    collections
+        .comments
+        .push(comment("C1", "Bikes allowed"))
+        .unwrap();
+    collections
+        .comments
+        .push(comment("C2", "Bikes allowed"))
+        .unwrap();
+    collections
+        .comments
+        .push(comment("C3", "School days only"))
+        .unwrap();
+    let mut m1 = collections.lines.get_mut("M1").unwrap();
+    m1.comment_links.insert("C2".to_string());
+    m1.comment_links.insert("C3".to_string());
+    drop(m1);
+    collections
+        .stop_time_comments
+        .insert(("M1F1".to_string(), 1), "C2".to_string());
+    collections
+        .routes
+        .get_mut("M1F")
+        .unwrap()
+        .comment_links
+        .insert("C1".to_string());
+    let model = Model::new(collections).unwrap();
+
+    let options = CleanOptions {
+        dedup_comments: true,
+        ..Default::default()
+    };
+    let model = clean::clean(model, &options).unwrap();
+    let mut comment_ids: Vec<&str> = model.comments.values().map(|c| c.id.as_str()).collect();
+    comment_ids.sort();
+    assert_eq!(vec!["C1", "C3"], comment_ids);
+    let m1_comments: Vec<&str> = model
+        .lines
+        .get("M1")
+        .unwrap()
+        .comment_links
+        .iter()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(vec!["C1", "C3"], m1_comments);
+    assert_eq!(
+        "C1",
+        model.stop_time_comments[&("M1F1".to_string(), 1)].as_str()
+    );
+}
+
+#[test]
+fn fix_stop_time_order() {
+    let mut collections = ntfs::read("tests/fixtures/minimal_ntfs/")
+        .unwrap()
+        .into_collections();
+    let mut vj = collections.vehicle_journeys.get_mut("M1F1").unwrap();
+    // stop times 1 and 2 are swapped
+    vj.stop_times[1].arrival_time = Time::new(9, 20, 0);
+    vj.stop_times[1].departure_time = Time::new(9, 20, 0);
+    vj.stop_times[2].arrival_time = Time::new(9, 10, 0);
+    vj.stop_times[2].departure_time = Time::new(9, 10, 0);
+    drop(vj);
+    collections
+        .stop_time_ids
+        .insert(("M1F1".to_string(), 1), "ST-GDLM".to_string());
+    collections
+        .stop_time_ids
+        .insert(("M1F1".to_string(), 2), "ST-CHAM".to_string());
+    let model = Model::new(collections).unwrap();
+
+    let options = CleanOptions {
+        fix_stop_time_order: true,
+        ..Default::default()
+    };
+    let model = clean::clean(model, &options).unwrap();
+    let vj = model.vehicle_journeys.get("M1F1").unwrap();
+    let stops: Vec<(u32, &str)> = vj
+        .stop_times
+        .iter()
+        .map(|st| {
+            (
+                st.sequence,
+                model.stop_points[st.stop_point_idx].id.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![(0, "NATM"), (1, "CHAM"), (2, "GDLM"), (3, "CDGM")],
+        stops
+    );
+    assert_eq!(
+        "ST-CHAM",
+        model.stop_time_ids[&("M1F1".to_string(), 1)].as_str()
+    );
+    assert_eq!(
+        "ST-GDLM",
+        model.stop_time_ids[&("M1F1".to_string(), 2)].as_str()
+    );
+}
+
+#[test]
+fn restrict_validity_period() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let options = CleanOptions {
+        validity_period: Some((
+            NaiveDate::from_ymd(2030, 1, 1),
+            NaiveDate::from_ymd(2030, 12, 31),
+        )),
+        ..Default::default()
+    };
+    let model = clean::clean(model, &options).unwrap();
+    assert!(model.vehicle_journeys.is_empty());
+}
+
+#[test]
+fn dedup_geometries() {
+    let mut collections = ntfs::read("tests/fixtures/minimal_ntfs/")
+        .unwrap()
+        .into_collections();
+    collections.geometries.push(geometry("G1", 2.35)).unwrap();
+    collections.geometries.push(geometry("G2", 2.35)).unwrap();
+    collections.geometries.push(geometry("G3", 2.37)).unwrap();
+    collections.routes.get_mut("M1F").unwrap().geometry_id = Some("G1".to_string());
+    collections
+        .vehicle_journeys
+        .get_mut("M1F1")
+        .unwrap()
+        .geometry_id = Some("G2".to_string());
+    collections.lines.get_mut("M1").unwrap().geometry_id = Some("G3".to_string());
+    let model = Model::new(collections).unwrap();
+    assert_eq!(3, model.geometries.len());
+
+    let options = CleanOptions {
+        dedup_geometries: true,
+        ..Default::default()
+    };
+    let model = clean::clean(model, &options).unwrap();
+    let mut geometry_ids: Vec<&str> = model.geometries.values().map(|g| g.id.as_str()).collect();
+    geometry_ids.sort();
+    assert_eq!(vec!["G1", "G3"], geometry_ids);
+    assert_eq!(
+        Some("G1".to_string()),
+        model.vehicle_journeys.get("M1F1").unwrap().geometry_id
+    );
+    assert_eq!(
+        Some("G3".to_string()),
+        model.lines.get("M1").unwrap().geometry_id
+    );
+}
+
+#[test]
+fn dedup_vehicle_journeys() {
+    let mut collections = ntfs::read("tests/fixtures/minimal_ntfs/")
+        .unwrap()
+        .into_collections();
+    let calendar = |id: &str, dates: &[(i32, u32, u32)]| Calendar {
+        id: id.to_string(),
+        dates: dates
+            .iter()
+            .map(|(y, m, d)| NaiveDate::from_ymd(*y, *m, *d))
+            .collect(),
+    };
+    // a monday of the calendar "Week" and a saturday
+    collections
+        .calendars
+        .push(calendar("Overlap", &[(2018, 1, 1), (2018, 1, 6)]))
+        .unwrap();
+    collections
+        .calendars
+        .push(calendar("Sunday", &[(2018, 1, 7)]))
+        .unwrap();
+    // an existing calendar with the identifier of the merged calendar, of a
+    // vehicle journey not identical to M1F1
+    collections
+        .calendars
+        .push(calendar("M1F1:merged", &[(2019, 1, 1)]))
+        .unwrap();
+    collections
+        .comments
+        .push(comment("comment:copy", "copied trip"))
+        .unwrap();
+    let m1f1 = collections.vehicle_journeys.get("M1F1").unwrap().clone();
+    let mut copy = m1f1.clone();
+    copy.id = "M1F1:copy".to_string();
+    copy.service_id = "Overlap".to_string();
+    copy.codes
+        .insert(("source".to_string(), "copy".to_string()));
+    copy.comment_links.insert("comment:copy".to_string());
+    // not overlapping the calendar of M1F1 but the calendar of the copy
+    let mut sunday = m1f1.clone();
+    sunday.id = "M1F1:sunday".to_string();
+    sunday.service_id = "Sunday".to_string();
+    let mut other_headsign = m1f1;
+    other_headsign.id = "M1F1:other_headsign".to_string();
+    other_headsign.service_id = "M1F1:merged".to_string();
+    other_headsign.headsign = Some("Other headsign".to_string());
+    // pushed before the copies of M1F1, but merged after them, B42F1 being
+    // after M1F1 in the collection
+    let mut b42f1_copy = collections.vehicle_journeys.get("B42F1").unwrap().clone();
+    b42f1_copy.id = "B42F1:copy".to_string();
+    collections.vehicle_journeys.push(b42f1_copy).unwrap();
+    collections.vehicle_journeys.push(copy).unwrap();
+    collections.vehicle_journeys.push(sunday).unwrap();
+    collections.vehicle_journeys.push(other_headsign).unwrap();
+    let model = Model::new(collections).unwrap();
+    let nb_week_dates = model.calendars.get("Week").unwrap().dates.len();
+
+    test_in_tmp_dir(|path| {
+        let report_path = path.join("report.json");
+        let model = clean::dedup_vehicle_journeys(model, &report_path).unwrap();
+
+        assert!(!model.vehicle_journeys.contains_id("M1F1:copy"));
+        assert!(!model.vehicle_journeys.contains_id("M1F1:sunday"));
+        assert!(!model.vehicle_journeys.contains_id("B42F1:copy"));
+        assert!(model.vehicle_journeys.contains_id("M1F1:other_headsign"));
+        let m1f1 = model.vehicle_journeys.get("M1F1").unwrap();
+        assert_eq!("M1F1:merged:1", m1f1.service_id);
+        let dates = &model.calendars.get("M1F1:merged:1").unwrap().dates;
+        assert_eq!(nb_week_dates + 2, dates.len());
+        assert!(dates.contains(&NaiveDate::from_ymd(2018, 1, 6)));
+        assert!(dates.contains(&NaiveDate::from_ymd(2018, 1, 7)));
+        assert_eq!(1, model.calendars.get("M1F1:merged").unwrap().dates.len());
+        assert!(m1f1
+            .codes
+            .contains(&("source".to_string(), "copy".to_string())));
+        assert!(m1f1.comment_links.contains("comment:copy"));
+
+        let report: serde_json::Value =
+            serde_json::from_reader(File::open(report_path).unwrap()).unwrap();
+        let infos = report["infos"].as_array().unwrap();
+        let merged_ids: Vec<&str> = infos
+            .iter()
+            .map(|info| {
+                assert_eq!("object_merged", info["category"]);
+                info["object_id"].as_str().unwrap()
+            })
+            .collect();
+        // in the order of the kept vehicle journeys in the collection
+        assert_eq!(vec!["M1F1:copy", "M1F1:sunday", "B42F1:copy"], merged_ids);
+    });
+}
+
+#[test]
+fn clean_transfers() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut collections = model.into_collections();
+    let transfer = |from: &str, to: &str, min_time, real_min_time| Transfer {
+        from_stop_id: from.to_string(),
+        to_stop_id: to.to_string(),
+        min_transfer_time: min_time,
+        real_min_transfer_time: real_min_time,
+        equipment_id: None,
+    };
+    collections.transfers = Collection::new(vec![
+        transfer("NATM", "GDLM", Some(120), Some(180)),
+        transfer("NATM", "GDLM", Some(60), None),
+        transfer("NATM", "NATM", Some(7200), Some(7200)),
+        transfer("GDLM", "GDLM", Some(120), Some(60)),
+        transfer("CHAM", "CHAM", Some(60), Some(120)),
+        // a transfer not possible
+        transfer("GDLM", "GDLM", Some(86400), Some(86400)),
+        transfer("UNKNOWN", "NATM", Some(60), None),
+    ]);
+    let mut report = Report::default();
+    collections.clean_transfers(&mut report);
+
+    assert_eq!(
+        vec![
+            transfer("NATM", "GDLM", Some(120), Some(180)),
+            transfer("CHAM", "CHAM", Some(60), Some(120)),
+            transfer("GDLM", "GDLM", Some(86400), Some(86400)),
+        ],
+        collections.transfers.values().cloned().collect::<Vec<_>>()
+    );
+    let warnings: Vec<(ReportType, &str)> = report
+        .entries(Severity::Warning)
+        .iter()
+        .map(|warning| (warning.category, warning.message.as_str()))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                ReportType::DanglingReference,
+                "1 transfer(s) referencing unknown stop points removed"
+            ),
+            (
+                ReportType::InvalidValue,
+                "2 transfer(s) from a stop point to itself with nonsensical times removed"
+            ),
+            (
+                ReportType::OverlappingTransfers,
+                "1 duplicated transfer(s) removed"
+            ),
+        ],
+        warnings
+    );
+}
@@ -0,0 +1,79 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use chrono::{FixedOffset, TimeZone};
+use pretty_assertions::assert_eq;
+use transit_model::{
+    diff::{self, FieldChange, FileDiff},
+    ntfs,
+    objects::Network,
+    test_utils::*,
+    Model,
+};
+
+#[test]
+fn ntfs_diff() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut collections = model.into_collections();
+    collections
+        .networks
+        .push(Network {
+            id: "NEW".to_string(),
+            name: "New Network".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    collections.lines.get_mut("M1").unwrap().name = "Métro 1".to_string();
+    collections.lines.get_mut("B42").unwrap().network_id = "NEW".to_string();
+    let model = Model::new(collections).unwrap();
+
+    test_in_tmp_dir(|path| {
+        ntfs::write(&model, path, FixedOffset::east(0).timestamp(0, 0)).unwrap();
+        let diffs = diff::ntfs_diff("tests/fixtures/minimal_ntfs/", path).unwrap();
+        let file_diff = |file_name: &str| {
+            diffs
+                .iter()
+                .find(|file_diff| file_diff.file_name == file_name)
+                .unwrap()
+        };
+
+        assert_eq!(
+            &FileDiff {
+                file_name: "lines.txt".to_string(),
+                added: vec![],
+                removed: vec![],
+                modified: vec!["B42".to_string(), "M1".to_string()],
+                changes: vec![
+                    FieldChange {
+                        object_id: "B42".to_string(),
+                        field: "network_id".to_string(),
+                        old_value: Some("TGN".to_string()),
+                        new_value: Some("NEW".to_string()),
+                    },
+                    FieldChange {
+                        object_id: "M1".to_string(),
+                        field: "line_name".to_string(),
+                        old_value: Some("Metro 1".to_string()),
+                        new_value: Some("Métro 1".to_string()),
+                    },
+                ],
+            },
+            file_diff("lines.txt")
+        );
+        assert_eq!(vec!["NEW"], file_diff("networks.txt").added);
+        assert!(diffs
+            .iter()
+            .all(|file_diff| file_diff.file_name != "stop_times.txt"));
+    });
+}
@@ -0,0 +1,57 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use transit_model::{dwell_times, ntfs, test_utils::*, Model};
+
+fn durations(model: &Model, vj_id: &str, sequence: u32) -> (u16, u16) {
+    let stop_time = model
+        .vehicle_journeys
+        .get(vj_id)
+        .unwrap()
+        .stop_times
+        .iter()
+        .find(|st| st.sequence == sequence)
+        .unwrap();
+    (stop_time.boarding_duration, stop_time.alighting_duration)
+}
+
+#[test]
+fn read_dwell_times() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut result = None;
+    test_in_tmp_dir(|path| {
+        create_file_with_content(
+            path,
+            "dwell_times.txt",
+            "physical_mode_id,stop_id,boarding_duration,alighting_duration\n\
+             Metro,,20,10\n\
+             Metro,GDL,60,30\n\
+             Metro,NATM,90,45\n\
+             RapidTransit,,30,30\n\
+             UNKNOWN,,1,1\n\
+             Metro,UNKNOWN,1,1\n",
+        );
+        result = Some(dwell_times::read_dwell_times(model, path.join("dwell_times.txt")).unwrap());
+    });
+    let model = result.unwrap();
+    // stop point, then stop area, then physical mode
+    assert_eq!((90, 45), durations(&model, "M1F1", 0));
+    assert_eq!((60, 30), durations(&model, "M1F1", 1));
+    assert_eq!((20, 10), durations(&model, "M1F1", 2));
+    // other physical mode
+    assert_eq!((0, 0), durations(&model, "B42F1", 10));
+    // stop time already having a dwell
+    assert_eq!((0, 0), durations(&model, "RERAF1", 1));
+}
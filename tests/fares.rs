@@ -0,0 +1,250 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+use std::fs::File;
+use transit_model::{
+    fares::{self, FaresMode, FaresOptions},
+    ntfs,
+    test_utils::*,
+    Model,
+};
+
+fn enrich_with_max_error_ratio(
+    fare_path: &str,
+    mode: FaresMode,
+    max_error_ratio: Option<f64>,
+) -> (Model, serde_json::Value) {
+    let model = ntfs::read("tests/fixtures/ntfs/").unwrap();
+    let mut result = None;
+    test_in_tmp_dir(|path| {
+        let report_path = path.join("report.json");
+        let model = fares::enrich_with_farev2(
+            model,
+            fare_path,
+            mode,
+            &FaresOptions {
+                max_error_ratio,
+                ..Default::default()
+            },
+            &report_path,
+        )
+        .unwrap();
+        let report = serde_json::from_reader(File::open(report_path).unwrap()).unwrap();
+        result = Some((model, report));
+    });
+    result.unwrap()
+}
+
+fn enrich(fare_path: &str, mode: FaresMode) -> (Model, serde_json::Value) {
+    enrich_with_max_error_ratio(fare_path, mode, None)
+}
+
+fn report_categories(report: &serde_json::Value, severity: &str) -> Vec<String> {
+    let mut categories: Vec<String> = report[severity]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["category"].as_str().unwrap().to_string())
+        .collect();
+    categories.sort();
+    categories
+}
+
+fn ticket_ids(model: &Model) -> Vec<&str> {
+    let mut ticket_ids: Vec<&str> = model.tickets.values().map(|t| t.id.as_str()).collect();
+    ticket_ids.sort();
+    ticket_ids
+}
+
+#[test]
+fn merge_fares_v2() {
+    let (model, report) = enrich("tests/fixtures/fares/merge/", FaresMode::Merge);
+
+    assert_eq!(
+        vec!["ticket.1", "ticket.2", "ticket.3", "ticket.4", "ticket.5"],
+        ticket_ids(&model)
+    );
+    let prices = |ticket_id| {
+        model
+            .ticket_prices
+            .values()
+            .filter(|p| p.ticket_id == ticket_id)
+            .map(|p| p.price)
+            .collect::<Vec<_>>()
+    };
+    // the conflicting price of ticket.2 is not applied
+    assert_eq!(vec![dec!(1.13)], prices("ticket.2"));
+    assert_eq!(vec![dec!(10.00)], prices("ticket.5"));
+
+    assert_eq!(5, model.ticket_uses.len());
+    assert!(!model.ticket_uses.contains_id("ticket-use.6"));
+    let perimeters: Vec<&str> = model
+        .ticket_use_perimeters
+        .values()
+        .filter(|p| p.ticket_use_id == "ticket-use.5")
+        .map(|p| p.object_id.as_str())
+        .collect();
+    assert_eq!(vec!["RERA"], perimeters);
+    assert_eq!(6, model.ticket_use_perimeters.len());
+
+    assert_eq!(
+        vec!["conflict", "dangling_reference"],
+        report_categories(&report, "errors")
+    );
+    assert_eq!(
+        vec!["object_not_found"],
+        report_categories(&report, "warnings")
+    );
+    assert_eq!("ticket", report["errors"][0]["object_type"]);
+    assert_eq!("ticket.2", report["errors"][0]["object_id"]);
+}
+
+#[test]
+fn replace_fares_v2() {
+    let (model, report) = enrich("tests/fixtures/fares/merge/", FaresMode::Replace);
+
+    assert_eq!(vec!["ticket.1", "ticket.2", "ticket.5"], ticket_ids(&model));
+    assert_eq!(3, model.ticket_prices.len());
+    assert_eq!(2, model.ticket_uses.len());
+    assert_eq!(2, model.ticket_use_perimeters.len());
+    assert!(model.ticket_use_restrictions.is_empty());
+    assert_eq!(
+        vec!["dangling_reference"],
+        report_categories(&report, "errors")
+    );
+}
+
+#[test]
+fn merge_directory_of_fare_zips() {
+    let (model, report) = enrich("tests/fixtures/fares/networks/", FaresMode::Merge);
+
+    assert_eq!(
+        vec!["ticket.1", "ticket.2", "ticket.3", "ticket.4", "ticket.5", "ticket.6"],
+        ticket_ids(&model)
+    );
+    assert_eq!(6, model.ticket_uses.len());
+    assert_eq!(7, model.ticket_use_perimeters.len());
+    assert_eq!(5, model.ticket_use_restrictions.len());
+
+    let sections = &report["sections"];
+    assert_eq!(
+        vec!["conflict"],
+        report_categories(&sections["network_a.zip"], "errors")
+    );
+    assert_eq!(
+        vec!["object_not_found"],
+        report_categories(&sections["network_b.zip"], "warnings")
+    );
+    assert_eq!(
+        vec!["unreadable_file"],
+        report_categories(&sections["network_c.zip"], "errors")
+    );
+    assert!(sections.get("README.txt").is_none());
+}
+
+#[test]
+fn replace_with_directory_of_fare_zips() {
+    let (model, report) = enrich("tests/fixtures/fares/networks/", FaresMode::Replace);
+
+    // only the first zip replaces the existing fares, the next ones are merged
+    assert_eq!(vec!["ticket.2", "ticket.5", "ticket.6"], ticket_ids(&model));
+    assert!(report["sections"]["network_a.zip"]["errors"]
+        .as_array()
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn reject_fare_zips_above_max_error_ratio() {
+    // 1 of the 3 perimeters and restrictions of network_b.zip references an
+    // unknown line
+    let (model, report) = enrich_with_max_error_ratio(
+        "tests/fixtures/fares/networks/",
+        FaresMode::Merge,
+        Some(0.2),
+    );
+
+    assert!(!model.tickets.contains_id("ticket.6"));
+    assert!(model.tickets.contains_id("ticket.5"));
+    assert_eq!(
+        vec!["unreadable_file"],
+        report_categories(&report["sections"]["network_b.zip"], "errors")
+    );
+
+    let (model, _) = enrich_with_max_error_ratio(
+        "tests/fixtures/fares/networks/",
+        FaresMode::Merge,
+        Some(0.5),
+    );
+    assert!(model.tickets.contains_id("ticket.6"));
+}
+
+#[test]
+fn price_summaries() {
+    let model = ntfs::read("tests/fixtures/ntfs/").unwrap();
+    let summaries = fares::price_summaries(&model);
+    let summary = |object_id: &str| {
+        summaries
+            .iter()
+            .find(|summary| summary.object_id == object_id)
+            .map(|summary| {
+                (
+                    summary.min_price,
+                    summary.max_price,
+                    summary.ticket_names.clone(),
+                )
+            })
+            .unwrap()
+    };
+    assert_eq!(
+        vec!["TGN", "B42", "M1", "RERA"],
+        summaries
+            .iter()
+            .map(|summary| summary.object_id.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert!(summaries
+        .iter()
+        .all(|summary| summary.currency.code() == "EUR"));
+    let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        (dec!(0.90), dec!(1.38), names(&["ticket.1", "ticket.3"])),
+        summary("TGN")
+    );
+    // ticket.3 excludes the line B42
+    assert_eq!(
+        (dec!(0.90), dec!(1.13), names(&["ticket.1", "ticket.2"])),
+        summary("B42")
+    );
+    assert_eq!(
+        (
+            dec!(0.90),
+            dec!(1.38),
+            names(&["ticket.1", "ticket.3", "ticket.4"])
+        ),
+        summary("M1")
+    );
+
+    test_in_tmp_dir(|path| {
+        let summaries_path = path.join("price_summaries.json");
+        fares::write_price_summaries(&model, &summaries_path).unwrap();
+        let summaries: serde_json::Value =
+            serde_json::from_reader(File::open(summaries_path).unwrap()).unwrap();
+        assert_eq!("network", summaries[0]["object_type"]);
+        assert_eq!("EUR", summaries[0]["currency"]);
+        assert_eq!(4, summaries.as_array().unwrap().len());
+    });
+}
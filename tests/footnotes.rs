@@ -0,0 +1,92 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use std::fs;
+use transit_model::{footnotes, ntfs, test_utils::*, Model};
+
+fn model_with_footnotes(footnotes: &str) -> Model {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut result = None;
+    test_in_tmp_dir(|path| {
+        create_file_with_content(path, "footnotes.txt", footnotes);
+        result = Some(footnotes::read_footnotes(model, path.join("footnotes.txt")).unwrap());
+    });
+    result.unwrap()
+}
+
+fn label(model: &Model, comment_id: &str) -> Option<String> {
+    model.comments.get(comment_id).unwrap().label.clone()
+}
+
+#[test]
+fn read_and_write_footnotes() {
+    let mut model = model_with_footnotes(
+        "trip_id,stop_sequence,footnote_code,footnote_text\n\
+         M1F1,,,Not on public holidays\n\
+         B42F1,20,,Request stop\n\
+         M1B1,,,Not on public holidays\n\
+         B42F1,,a,Bicycles allowed\n\
+         UNKNOWN,,,Ignored\n\
+         M1F1,42,,Ignored\n",
+    );
+    assert_eq!(3, model.comments.len());
+    let m1f1 = model.vehicle_journeys.get("M1F1").unwrap();
+    let m1b1 = model.vehicle_journeys.get("M1B1").unwrap();
+    assert_eq!(m1f1.comment_links, m1b1.comment_links);
+    let request_stop = model.stop_time_comments[&("B42F1".to_string(), 20)].clone();
+
+    let mut collections = model.into_collections();
+    collections.assign_footnote_codes();
+    model = Model::new(collections).unwrap();
+    let holidays = model
+        .vehicle_journeys
+        .get("M1F1")
+        .unwrap()
+        .comment_links
+        .iter()
+        .next()
+        .unwrap()
+        .clone();
+    // "a" is already used by the bicycles footnote
+    assert_eq!(Some("b".to_string()), label(&model, &holidays));
+    assert_eq!(Some("c".to_string()), label(&model, &request_stop));
+
+    test_in_tmp_dir(|path| {
+        let footnotes_path = path.join("footnotes.txt");
+        footnotes::write_footnotes(&model, &footnotes_path).unwrap();
+        assert_eq!(
+            "trip_id,stop_sequence,footnote_code,footnote_text\n\
+             B42F1,,a,Bicycles allowed\n\
+             B42F1,20,c,Request stop\n\
+             M1B1,,b,Not on public holidays\n\
+             M1F1,,b,Not on public holidays\n",
+            fs::read_to_string(footnotes_path).unwrap()
+        );
+    });
+}
+
+#[test]
+fn same_text_shares_the_same_code() {
+    let model = model_with_footnotes(
+        "trip_id,stop_sequence,footnote_code,footnote_text\n\
+         M1F1,,x,Runs on school days\n\
+         M1B1,,,Runs on school days\n",
+    );
+    let mut collections = model.into_collections();
+    collections.assign_footnote_codes();
+    for comment in collections.comments.values() {
+        assert_eq!(Some("x".to_string()), comment.label);
+    }
+}
@@ -14,9 +14,11 @@
 
 use std::collections::BTreeMap;
 use transit_model::{
-    gtfs, ntfs,
-    objects::{Contributor, Dataset},
+    crs::CoordinateSystem,
+    filter, gtfs, ntfs,
+    objects::{Contributor, Dataset, StopType, Time},
     read_utils::read_config,
+    statistics,
     test_utils::*,
     PrefixConfiguration,
 };
@@ -29,6 +31,13 @@ fn default_configuration() -> gtfs::Configuration {
         prefix_conf: None,
         on_demand_transport: false,
         on_demand_transport_comment: None,
+        route_type_mappings: BTreeMap::new(),
+        keep_frequencies: false,
+        crs: None,
+        company_parents: Default::default(),
+        network_contacts: Default::default(),
+        read_mode: Default::default(),
+        trip_operators: false,
     }
 }
 
@@ -48,6 +57,13 @@ fn test_gtfs() {
             prefix_conf: Some(prefix_conf),
             on_demand_transport: false,
             on_demand_transport_comment: None,
+            route_type_mappings: BTreeMap::new(),
+            keep_frequencies: false,
+            crs: None,
+            company_parents: Default::default(),
+            network_contacts: Default::default(),
+            read_mode: Default::default(),
+            trip_operators: false,
         };
         let model = transit_model::gtfs::read_from_path(input_dir, configuration).unwrap();
         transit_model::ntfs::write(&model, path, get_test_datetime()).unwrap();
@@ -65,6 +81,27 @@ fn test_minimal_gtfs() {
     });
 }
 
+#[test]
+fn test_gtfs_with_prefixed_fare_zones() {
+    let input_dir = "./tests/fixtures/gtfs2ntfs/prefix/input";
+    let mut prefix_conf = PrefixConfiguration::default();
+    prefix_conf.set_data_prefix("pre");
+    let configuration = gtfs::Configuration {
+        prefix_conf: Some(prefix_conf),
+        ..default_configuration()
+    };
+    let model = gtfs::read_from_path(input_dir, configuration).unwrap();
+    let fare_zones: Vec<(&str, Option<&str>)> = model
+        .stop_points
+        .values()
+        .map(|stop_point| (stop_point.id.as_str(), stop_point.fare_zone_id.as_deref()))
+        .collect();
+    assert_eq!(
+        vec![("pre:stop:1", Some("pre:zone:1")), ("pre:stop:2", None)],
+        fare_zones
+    );
+}
+
 #[test]
 fn test_gtfs_physical_modes() {
     test_in_tmp_dir(|path| {
@@ -105,6 +142,7 @@ fn test_gtfs_remove_vjs_with_no_traffic() {
     });
 }
 
+#[cfg(feature = "zip")]
 #[test]
 fn test_minimal_ziped_gtfs() {
     test_in_tmp_dir(|path| {
@@ -115,6 +153,7 @@ fn test_minimal_ziped_gtfs() {
     });
 }
 
+#[cfg(feature = "zip")]
 #[test]
 fn test_minimal_ziped_sub_dir_gtfs() {
     test_in_tmp_dir(|path| {
@@ -125,6 +164,7 @@ fn test_minimal_ziped_sub_dir_gtfs() {
     });
 }
 
+#[cfg(feature = "zip")]
 #[test]
 fn test_minimal_ziped_sub_dir_gtfs_with_hidden_files() {
     test_in_tmp_dir(|path| {
@@ -135,6 +175,55 @@ fn test_minimal_ziped_sub_dir_gtfs_with_hidden_files() {
     });
 }
 
+#[cfg(feature = "zip")]
+#[test]
+fn test_minimal_ziped_gtfs_with_corrupted_file() {
+    use std::{fs, io::Write};
+    use transit_model::{
+        read_utils::ReadMode,
+        report::{Report, ReportType, Severity},
+    };
+
+    test_in_tmp_dir(|path| {
+        let zip_path = path.join("gtfs.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let input_dir = "./tests/fixtures/gtfs2ntfs/minimal/input";
+        for entry in fs::read_dir(input_dir).unwrap() {
+            let entry = entry.unwrap();
+            zip.start_file(entry.file_name().to_string_lossy(), options)
+                .unwrap();
+            zip.write_all(&fs::read(entry.path()).unwrap()).unwrap();
+        }
+        zip.start_file("transfers.txt", options).unwrap();
+        zip.write_all(b"from_stop_id,to_stop_id,transfer_type\n")
+            .unwrap();
+        zip.finish().unwrap();
+        // the content of transfers.txt no longer matches its CRC
+        let mut bytes = fs::read(&zip_path).unwrap();
+        let position = bytes
+            .windows(13)
+            .position(|window| window == b"transfer_type")
+            .unwrap();
+        bytes[position] = b'T';
+        fs::write(&zip_path, bytes).unwrap();
+
+        assert!(gtfs::read_from_zip(&zip_path, default_configuration()).is_err());
+
+        let configuration = gtfs::Configuration {
+            read_mode: ReadMode::Lenient,
+            ..default_configuration()
+        };
+        let mut report = Report::default();
+        let model = gtfs::read_from_zip_with_report(&zip_path, configuration, &mut report).unwrap();
+        assert!(model.transfers.is_empty());
+        let errors = report.entries(Severity::Error);
+        assert_eq!(1, errors.len());
+        assert_eq!(ReportType::UnreadableFile, errors[0].category);
+    });
+}
+
 #[test]
 fn test_minimal_gtfs_with_odt_comment() {
     test_in_tmp_dir(|path| {
@@ -150,6 +239,13 @@ fn test_minimal_gtfs_with_odt_comment() {
             on_demand_transport_comment: Some(
                 "Service à réservation {agency_name} {agency_phone}".to_string(),
             ),
+            route_type_mappings: BTreeMap::new(),
+            keep_frequencies: false,
+            crs: None,
+            company_parents: Default::default(),
+            network_contacts: Default::default(),
+            read_mode: Default::default(),
+            trip_operators: false,
         };
         let model = gtfs::read_from_path(input_dir, configuration).unwrap();
         ntfs::write(&model, path, get_test_datetime()).unwrap();
@@ -176,6 +272,13 @@ fn test_minimal_gtfs_frequencies_with_odt_comment() {
             on_demand_transport_comment: Some(
                 "Service à réservation {agency_name} {agency_phone}".to_string(),
             ),
+            route_type_mappings: BTreeMap::new(),
+            keep_frequencies: false,
+            crs: None,
+            company_parents: Default::default(),
+            network_contacts: Default::default(),
+            read_mode: Default::default(),
+            trip_operators: false,
         };
 
         let model = gtfs::read_from_path(input_dir, configuration).unwrap();
@@ -187,3 +290,183 @@ fn test_minimal_gtfs_frequencies_with_odt_comment() {
         );
     });
 }
+
+#[test]
+fn test_keep_frequencies() {
+    test_in_tmp_dir(|path| {
+        let input_dir = "./tests/fixtures/gtfs2ntfs/frequencies/input";
+        let configuration = gtfs::Configuration {
+            keep_frequencies: true,
+            ..default_configuration()
+        };
+        let model = gtfs::read_from_path(input_dir, configuration).unwrap();
+        assert!(model.vehicle_journeys.contains_id("trip:1"));
+        assert!(!model.vehicle_journeys.contains_id("trip:1-0"));
+        let estimated = |vj_id: &str| {
+            model.vehicle_journeys.get(vj_id).unwrap().stop_times[0].datetime_estimated
+        };
+        assert!(estimated("trip:1"));
+        assert!(!estimated("trip:2"));
+        ntfs::write(&model, path, get_test_datetime()).unwrap();
+        compare_output_dir_with_expected(
+            &path,
+            Some(vec!["frequencies.txt"]),
+            "./tests/fixtures/gtfs2ntfs/frequencies/output_kept",
+        );
+    });
+}
+
+#[cfg(feature = "proj")]
+#[test]
+fn test_gtfs_in_lambert93() {
+    // the minimal GTFS, with the coordinates of its stops in Lambert 93
+    let input_dir = "./tests/fixtures/gtfs2ntfs/projected/input";
+    let configuration = gtfs::Configuration {
+        crs: Some(CoordinateSystem::Lambert93),
+        ..default_configuration()
+    };
+    let model = gtfs::read_from_path(input_dir, configuration).unwrap();
+    let expected = gtfs::read_from_path(
+        "./tests/fixtures/gtfs2ntfs/minimal/input",
+        default_configuration(),
+    )
+    .unwrap();
+    assert_eq!(expected.stop_points.len(), model.stop_points.len());
+    for stop_point in model.stop_points.values() {
+        let expected_coord = expected.stop_points.get(&stop_point.id).unwrap().coord;
+        assert!((stop_point.coord.lon - expected_coord.lon).abs() < 1e-6);
+        assert!((stop_point.coord.lat - expected_coord.lat).abs() < 1e-6);
+    }
+    let stop_area = model.stop_areas.get("stoparea:1").unwrap();
+    assert!((stop_area.coord.lon - 2.372987).abs() < 1e-6);
+    assert!((stop_area.coord.lat - 48.844746).abs() < 1e-6);
+    // the coordinates are in WGS84 after the reading
+    assert_eq!(
+        CoordinateSystem::Wgs84,
+        model.into_collections().coordinate_system().unwrap()
+    );
+}
+
+#[cfg(not(feature = "proj"))]
+#[test]
+fn test_gtfs_in_lambert93_without_proj() {
+    let input_dir = "./tests/fixtures/gtfs2ntfs/projected/input";
+    let configuration = gtfs::Configuration {
+        crs: Some(CoordinateSystem::Lambert93),
+        ..default_configuration()
+    };
+    assert!(gtfs::read_from_path(input_dir, configuration).is_err());
+}
+
+#[test]
+fn test_gtfs_flex_zones() {
+    let input_dir = "./tests/fixtures/gtfs2ntfs/flex/input";
+    let model = gtfs::read_from_path(input_dir, default_configuration()).unwrap();
+
+    let zone_a = model.stop_points.get("zone_a").unwrap();
+    assert_eq!(StopType::Zone, zone_a.stop_type);
+    assert_eq!("Quartier nord", zone_a.name);
+    assert_eq!(Some("zone:zone_a".to_string()), zone_a.geometry_id);
+    assert!(model.geometries.contains_id("zone:zone_a"));
+    let area_b = model.stop_points.get("area_b").unwrap();
+    assert_eq!(StopType::Zone, area_b.stop_type);
+    assert_eq!("Centre", area_b.name);
+    // an area not served by a stop time is not a zone
+    assert!(!model.stop_points.contains_id("area_fare"));
+
+    let stop_times = &model.vehicle_journeys.get("trip:1").unwrap().stop_times;
+    assert_eq!(3, stop_times.len());
+    let zonal_stop_time = &stop_times[1];
+    assert_eq!(
+        model.stop_points.get_idx("zone_a").unwrap(),
+        zonal_stop_time.stop_point_idx
+    );
+    assert_eq!(Time::new(6, 10, 0), zonal_stop_time.arrival_time);
+    assert_eq!(Time::new(6, 40, 0), zonal_stop_time.departure_time);
+    assert_eq!(
+        (2, 2),
+        (zonal_stop_time.pickup_type, zonal_stop_time.drop_off_type)
+    );
+    assert!(zonal_stop_time.datetime_estimated);
+
+    let statistics = statistics::line_statistics(&model);
+    assert_eq!(2, statistics[0].nb_zones);
+    let model = filter::keep_zonal_lines(model).unwrap();
+    assert_eq!(1, model.lines.len());
+}
+
+#[test]
+fn stream_as_the_full_conversion() {
+    fn sorted_lines(path: &std::path::Path) -> Vec<String> {
+        let mut lines: Vec<String> = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        lines.sort();
+        lines
+    }
+    fn file_names(path: &std::path::Path) -> Vec<std::ffi::OsString> {
+        let mut file_names: Vec<_> = std::fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        file_names.sort();
+        file_names
+    }
+    // flex zones, continuous stoppings, frequencies, agencies of different
+    // timezones, a block and trips without running days or stop times
+    let input_dir = "./tests/fixtures/gtfs2ntfs/stream/input";
+    let configuration = || {
+        let mut prefix_conf = PrefixConfiguration::default();
+        prefix_conf.set_data_prefix("pre");
+        gtfs::Configuration {
+            prefix_conf: Some(prefix_conf),
+            on_demand_transport: true,
+            on_demand_transport_comment: Some("Call {agency_phone}".to_string()),
+            ..default_configuration()
+        }
+    };
+    test_in_tmp_dir(|path| {
+        let full_path = path.join("full");
+        let stream_path = path.join("stream");
+        std::fs::create_dir(&full_path).unwrap();
+        std::fs::create_dir(&stream_path).unwrap();
+        let model = gtfs::read_from_path(input_dir, configuration()).unwrap();
+        ntfs::write(&model, &full_path, get_test_datetime()).unwrap();
+        gtfs::stream_to_ntfs(
+            input_dir,
+            &stream_path,
+            configuration(),
+            get_test_datetime(),
+        )
+        .unwrap();
+
+        assert_eq!(file_names(&full_path), file_names(&stream_path));
+        for file_name in file_names(&full_path) {
+            assert_eq!(
+                sorted_lines(&full_path.join(&file_name)),
+                sorted_lines(&stream_path.join(&file_name)),
+                "{:?} differs",
+                file_name
+            );
+        }
+        let stop_times = sorted_lines(&stream_path.join("stop_times.txt"));
+        let trip_ids: std::collections::BTreeSet<&str> = stop_times
+            .iter()
+            .filter(|line| !line.starts_with("stop_id,"))
+            .map(|line| line.split(',').nth(1).unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                "pre:trip_1",
+                "pre:trip_2-0",
+                "pre:trip_2-1",
+                "pre:trip_3",
+                "pre:trip_3:tz1",
+                "pre:trip_6"
+            ],
+            trip_ids.into_iter().collect::<Vec<_>>()
+        );
+    });
+}
@@ -0,0 +1,95 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use std::fs::File;
+use transit_model::{mixed_modes, ntfs, test_utils::*, Model};
+
+// RERAF1 (RapidTransit) and RERAB1 (Bus) on the same route RERAF
+fn mixed_model() -> Model {
+    let mut collections = ntfs::read("tests/fixtures/ntfs/")
+        .unwrap()
+        .into_collections();
+    collections
+        .vehicle_journeys
+        .get_mut("RERAB1")
+        .unwrap()
+        .route_id = "RERAF".to_string();
+    Model::new(collections).unwrap()
+}
+
+fn split(split_lines: bool) -> (Model, serde_json::Value) {
+    let model = mixed_model();
+    let mut result = None;
+    test_in_tmp_dir(|path| {
+        let report_path = path.join("report.json");
+        let model = mixed_modes::split_mixed_mode_routes(model, split_lines, &report_path).unwrap();
+        let report = serde_json::from_reader(File::open(report_path).unwrap()).unwrap();
+        result = Some((model, report));
+    });
+    result.unwrap()
+}
+
+#[test]
+fn detect_mixed_mode_routes() {
+    let model = mixed_model();
+    let mixed_mode_routes = mixed_modes::mixed_mode_routes(&model);
+    assert_eq!(vec!["RERAF"], mixed_mode_routes.keys().collect::<Vec<_>>());
+    assert_eq!(
+        vec![("Bus", 1), ("RapidTransit", 1)],
+        mixed_mode_routes["RERAF"]
+            .iter()
+            .map(|(mode, count)| (mode.as_str(), *count))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn split_mixed_mode_routes() {
+    let (model, report) = split(false);
+    // on a tie, the main physical mode is the first one by identifier
+    assert_eq!(
+        "RERAF",
+        model.vehicle_journeys.get("RERAB1").unwrap().route_id
+    );
+    assert_eq!(
+        "RERAF:RapidTransit",
+        model.vehicle_journeys.get("RERAF1").unwrap().route_id
+    );
+    assert_eq!(
+        "RERA",
+        model.routes.get("RERAF:RapidTransit").unwrap().line_id
+    );
+    assert!(mixed_modes::mixed_mode_routes(&model).is_empty());
+    assert_eq!("mixed_physical_modes", report["warnings"][0]["category"]);
+    assert_eq!("RERAF", report["warnings"][0]["object_id"]);
+    assert_eq!("object_created", report["infos"][0]["category"]);
+}
+
+#[test]
+fn split_mixed_mode_routes_and_lines() {
+    let (model, report) = split(true);
+    let route = model.routes.get("RERAF:RapidTransit").unwrap();
+    assert_eq!("RERA:RapidTransit", route.line_id);
+    let line = model.lines.get("RERA:RapidTransit").unwrap();
+    assert_eq!("RER A", line.name);
+    assert!(line.codes.is_empty());
+    assert_eq!("RapidTransit", line.commercial_mode_id);
+    assert_eq!(
+        "Rapid Transit",
+        model.commercial_modes.get("RapidTransit").unwrap().name
+    );
+    // the commercial mode, the line and the route created
+    assert_eq!(3, report["infos"].as_array().unwrap().len());
+}
@@ -0,0 +1,149 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use transit_model::{
+    apply_rules::Rules,
+    fares::{FaresMode, FaresOptions},
+    model_builder::ModelBuilder,
+    objects::Coord,
+    pipeline::Pipeline,
+    report::{ReportType, Severity},
+    stop_matching::{ExternalStop, NameDistanceMatcher},
+    test_utils::*,
+};
+
+#[test]
+fn ntfs_pipeline() {
+    test_in_tmp_dir(|path| {
+        create_file_with_content(
+            path,
+            "complementary_codes.csv",
+            "object_type,object_id,object_system,object_code\n\
+             line,M1,source,L1\n\
+             line,UNKNOWN,source,L2\n",
+        );
+        let rules = Rules {
+            complementary_code_files: vec![path.join("complementary_codes.csv")],
+            ..Default::default()
+        };
+        let (model, report) = Pipeline::from_ntfs("tests/fixtures/ntfs/")
+            .unwrap()
+            .apply_rules(&rules)
+            .unwrap()
+            .merge_fares(
+                "tests/fixtures/fares/merge/",
+                FaresMode::Merge,
+                &FaresOptions::default(),
+            )
+            .unwrap()
+            .into_parts();
+
+        assert!(model
+            .lines
+            .get("M1")
+            .unwrap()
+            .codes
+            .contains(&("source".to_string(), "L1".to_string())));
+        assert_eq!(5, model.tickets.len());
+
+        let sections: Vec<&str> = report.sections().map(|(name, _)| name).collect();
+        assert_eq!(vec!["fares", "rules"], sections);
+        let (_, rules_report) = report
+            .sections()
+            .find(|(name, _)| *name == "rules")
+            .unwrap();
+        assert_eq!(
+            vec![ReportType::ObjectNotFound],
+            rules_report
+                .entries(Severity::Error)
+                .iter()
+                .map(|entry| entry.category)
+                .collect::<Vec<_>>()
+        );
+    });
+}
+
+#[test]
+fn pipeline_merging_stop_point_codes() {
+    let model = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let matcher = NameDistanceMatcher::new(&model.stop_points, 50.0);
+    let external_stops = vec![
+        ExternalStop {
+            id: "ext:1".to_string(),
+            name: Some("gare de lyon (rer)".to_string()),
+            coord: Some(Coord {
+                lon: 2.373,
+                lat: 48.8447,
+            }),
+        },
+        ExternalStop {
+            id: "ext:2".to_string(),
+            name: Some("Unknown".to_string()),
+            coord: None,
+        },
+    ];
+    let (model, report) = Pipeline::new(model)
+        .merge_stop_point_codes(&external_stops, "external", &matcher)
+        .unwrap()
+        .into_parts();
+
+    assert!(model
+        .stop_points
+        .get("GDLR")
+        .unwrap()
+        .codes
+        .contains(&("external".to_string(), "ext:1".to_string())));
+    let (_, stop_matching_report) = report
+        .sections()
+        .find(|(name, _)| *name == "stop_matching")
+        .unwrap();
+    assert_eq!(1, stop_matching_report.entries(Severity::Warning).len());
+}
+
+#[test]
+fn pipeline_generating_transfers() {
+    let coord = |lon, lat| Coord { lon, lat };
+    let model = ModelBuilder::default()
+        .stop_point("sp1", |stop_point| {
+            stop_point.coord = coord(2.3730, 48.8447)
+        })
+        .stop_point("sp2", |stop_point| {
+            stop_point.coord = coord(2.3731, 48.8447)
+        })
+        .stop_point("sp3", |stop_point| stop_point.coord = Coord::default())
+        .vj("vj1", |vj| {
+            vj.st("sp1", "10:00").st("sp2", "10:05").st("sp3", "10:10");
+        })
+        .build();
+    let (model, report) = Pipeline::new(model)
+        .generate_transfers(100., 0.785, 60)
+        .unwrap()
+        .into_parts();
+
+    // sp1 and sp2 to themselves and to each other
+    assert_eq!(4, model.transfers.len());
+    let (_, transfers_report) = report
+        .sections()
+        .find(|(name, _)| *name == "transfers")
+        .unwrap();
+    let generated = transfers_report.entries(Severity::Info);
+    assert_eq!(1, generated.len());
+    assert_eq!(ReportType::ObjectCreated, generated[0].category);
+    assert_eq!(4, generated[0].count);
+    // the 9 transfers between the 3 stop points, but the 4 ones generated
+    let skipped = transfers_report.entries(Severity::Warning);
+    assert_eq!(1, skipped.len());
+    assert_eq!(5, skipped[0].count);
+}
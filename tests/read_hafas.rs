@@ -0,0 +1,120 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use transit_model::{
+    hafas,
+    model::{LONG_DISTANCE_TRAIN_PHYSICAL_MODE, RAPID_TRANSIT_PHYSICAL_MODE},
+    objects::{Coord, Date, Time},
+    Model,
+};
+
+fn model() -> Model {
+    hafas::read("tests/fixtures/hafas/input").unwrap()
+}
+
+#[test]
+fn read_stops() {
+    let model = model();
+    let zurich = model.stop_points.get("8503000").unwrap();
+    // BAHNHOF is encoded in Latin-1
+    assert_eq!("Zürich HB", zurich.name);
+    assert_eq!("Navitia:8503000", zurich.stop_area_id);
+    assert_eq!(
+        Coord {
+            lon: 8.540192,
+            lat: 47.378177
+        },
+        zurich.coord
+    );
+    assert!(model.stop_areas.contains_id("Navitia:8503000"));
+}
+
+#[test]
+fn read_journeys() {
+    let model = model();
+    let mut vj_ids: Vec<&str> = model
+        .vehicle_journeys
+        .values()
+        .map(|vj| vj.id.as_str())
+        .collect();
+    vj_ids.sort();
+    // the journey with an unknown bitfield is skipped
+    assert_eq!(
+        vec![
+            "000011:000101",
+            "000033:000201",
+            "000033:000201:1",
+            "000033:000201:2"
+        ],
+        vj_ids
+    );
+
+    let ic = model.vehicle_journeys.get("000011:000101").unwrap();
+    assert_eq!(LONG_DISTANCE_TRAIN_PHYSICAL_MODE, ic.physical_mode_id);
+    assert_eq!("000011", ic.company_id);
+    // the stop without times is passed without stopping
+    let stops: Vec<(&str, Time, Time, u8)> = ic
+        .stop_times
+        .iter()
+        .map(|st| {
+            (
+                model.stop_points[st.stop_point_idx].id.as_str(),
+                st.arrival_time,
+                st.departure_time,
+                st.pickup_type,
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            ("8500010", Time::new(8, 0, 0), Time::new(8, 0, 0), 0),
+            ("8500218", Time::new(8, 24, 0), Time::new(8, 26, 0), 1),
+            ("8503000", Time::new(8, 56, 0), Time::new(8, 56, 0), 1),
+        ],
+        stops
+    );
+    let route = model.routes.get(&ic.route_id).unwrap();
+    let line = model.lines.get(&route.line_id).unwrap();
+    assert_eq!("IC", line.commercial_mode_id);
+    assert_eq!("000011", line.network_id);
+
+    let last_cycle = model.vehicle_journeys.get("000033:000201:2").unwrap();
+    assert_eq!(RAPID_TRANSIT_PHYSICAL_MODE, last_cycle.physical_mode_id);
+    assert_eq!(Time::new(8, 0, 0), last_cycle.stop_times[0].departure_time);
+    let line = model
+        .lines
+        .get(&model.routes.get(&last_cycle.route_id).unwrap().line_id)
+        .unwrap();
+    assert_eq!(Some("12"), line.code.as_deref());
+}
+
+#[test]
+fn expand_bitfields() {
+    let model = model();
+    let dates = |vj_id| {
+        let vj = model.vehicle_journeys.get(vj_id).unwrap();
+        let calendar = model.calendars.get(&vj.service_id).unwrap();
+        calendar.dates.iter().cloned().collect::<Vec<Date>>()
+    };
+    assert_eq!(
+        vec![Date::from_ymd(2021, 6, 1), Date::from_ymd(2021, 6, 2)],
+        dates("000011:000101")
+    );
+    // without bitfield, the journey runs every day of ECKDATEN
+    assert_eq!(7, dates("000033:000201").len());
+    let dataset = model.datasets.values().next().unwrap();
+    assert_eq!(Date::from_ymd(2021, 6, 1), dataset.start_date);
+    assert_eq!(Date::from_ymd(2021, 6, 7), dataset.end_date);
+}
@@ -16,6 +16,7 @@ use pretty_assertions::assert_eq;
 use relational_types::IdxSet;
 use std::collections::HashMap;
 use transit_model::model::{Collections, GetCorresponding, Model};
+use transit_model::ntfs::SkippedField;
 use transit_model::objects::*;
 use transit_model::test_utils::*;
 use typed_index_collection::{CollectionWithId, Id, Idx};
@@ -123,6 +124,83 @@ fn test_minimal_fares_stay_same() {
     });
 }
 
+#[test]
+fn write_fares_v1_from_fares_v2_as_an_option() {
+    let ntm = transit_model::ntfs::read("tests/fixtures/ntfs/").unwrap();
+    let fares_v1_files = ["prices.csv", "od_fares.csv", "fares.csv"];
+    test_in_tmp_dir(|output_dir| {
+        transit_model::ntfs::write(&ntm, output_dir, get_test_datetime()).unwrap();
+        for file in &fares_v1_files {
+            assert!(output_dir.join(file).exists());
+        }
+    });
+    test_in_tmp_dir(|output_dir| {
+        let options = transit_model::ntfs::WriteOptions {
+            fares_v1: false,
+            ..Default::default()
+        };
+        transit_model::ntfs::write_with_options(&ntm, output_dir, get_test_datetime(), &options)
+            .unwrap();
+        for file in &fares_v1_files {
+            assert!(!output_dir.join(file).exists());
+        }
+        assert!(output_dir.join("tickets.txt").exists());
+    });
+}
+
+#[test]
+fn write_minimal_profile() {
+    let ntm = transit_model::ntfs::read("tests/fixtures/ntfs/").unwrap();
+    test_in_tmp_dir(|output_dir| {
+        let options = transit_model::ntfs::WriteOptions::minimal();
+        transit_model::ntfs::write_validated(&ntm, output_dir, get_test_datetime(), &options)
+            .unwrap();
+        for file in &[
+            "lines.txt",
+            "trips.txt",
+            "stop_times.txt",
+            "stops.txt",
+            "frequencies.txt",
+        ] {
+            assert!(output_dir.join(file).exists());
+        }
+        for file in &["comments.txt", "tickets.txt", "prices.csv"] {
+            assert!(!output_dir.join(file).exists());
+        }
+        let mut rdr = csv::Reader::from_path(output_dir.join("lines.txt")).unwrap();
+        assert!(!rdr.headers().unwrap().iter().any(|h| h == "geometry_id"));
+        let mut rdr = csv::Reader::from_path(output_dir.join("stops.txt")).unwrap();
+        let headers = rdr.headers().unwrap().clone();
+        assert!(!headers.iter().any(|h| h == "equipment_id"));
+        for record in rdr.records() {
+            assert_eq!(headers.len(), record.unwrap().len());
+        }
+        let written = transit_model::ntfs::read(output_dir).unwrap();
+        assert_eq!(ntm.lines.len(), written.lines.len());
+        assert_eq!(ntm.frequencies.len(), written.frequencies.len());
+        assert!(written.comments.is_empty());
+    });
+}
+
+#[test]
+fn write_without_skipped_contents() {
+    let ntm = transit_model::ntfs::read("tests/fixtures/ntfs/").unwrap();
+    test_in_tmp_dir(|output_dir| {
+        let options = transit_model::ntfs::WriteOptions {
+            skipped_fields: vec![SkippedField::Comments].into_iter().collect(),
+            skipped_extensions: vec!["fares V2".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        transit_model::ntfs::write_validated(&ntm, output_dir, get_test_datetime(), &options)
+            .unwrap();
+        for file in &["comments.txt", "comment_links.txt", "tickets.txt"] {
+            assert!(!output_dir.join(file).exists());
+        }
+        assert!(output_dir.join("frequencies.txt").exists());
+        assert!(output_dir.join("prices.csv").exists());
+    });
+}
+
 #[test]
 fn test_minimal_platforms_stay_same() {
     let ntm = transit_model::ntfs::read("tests/fixtures/ntfs2ntfs/platforms").unwrap();
@@ -202,6 +280,96 @@ fn ntfs() {
     assert_eq!(stop_time_comments, pt_objects.stop_time_comments);
 }
 
+#[test]
+fn keep_extra_columns() {
+    let options = transit_model::ntfs::ReadOptions {
+        keep_extra_columns: true,
+        ..Default::default()
+    };
+    let ntm =
+        transit_model::ntfs::read_with_options("tests/fixtures/ntfs2ntfs/extra_columns", &options)
+            .unwrap();
+    test_in_tmp_dir(|path| {
+        transit_model::ntfs::write(&ntm, path, get_test_datetime()).unwrap();
+        let mut rdr = csv::Reader::from_path(path.join("lines.txt")).unwrap();
+        let headers = rdr.headers().unwrap().clone();
+        let column = |name| headers.iter().position(|header| header == name).unwrap();
+        let (id, fleet, owner) = (
+            column("line_id"),
+            column("line_fleet"),
+            column("line_owner"),
+        );
+        let mut lines: Vec<(String, String, String)> = rdr
+            .records()
+            .map(|record| {
+                let record = record.unwrap();
+                (
+                    record[id].to_string(),
+                    record[fleet].to_string(),
+                    record[owner].to_string(),
+                )
+            })
+            .collect();
+        lines.sort();
+        let line = |id: &str, fleet: &str, owner: &str| {
+            (id.to_string(), fleet.to_string(), owner.to_string())
+        };
+        assert_eq!(
+            vec![
+                line("B42", "", "RATP"),
+                line("M1", "MP89", "RATP"),
+                line("RERA", "MI09", "RATP/SNCF"),
+            ],
+            lines
+        );
+    });
+}
+
+#[test]
+fn skip_fields() {
+    let options = transit_model::ntfs::ReadOptions {
+        skipped_fields: vec![
+            SkippedField::Comments,
+            SkippedField::Geometries,
+            SkippedField::Codes,
+            SkippedField::ObjectProperties,
+        ]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+    let ntm = transit_model::ntfs::read_with_options(
+        "tests/fixtures/restrict-validity-period/output",
+        &options,
+    )
+    .unwrap();
+    assert!(ntm.geometries.is_empty());
+    assert!(ntm.lines.values().all(|line| line.geometry_id.is_none()));
+    assert!(ntm
+        .vehicle_journeys
+        .values()
+        .all(|vj| vj.geometry_id.is_none()));
+    assert!(ntm.comments.is_empty());
+    assert!(ntm.stop_areas.values().all(|sa| sa.codes.is_empty()));
+    assert!(ntm
+        .stop_areas
+        .values()
+        .all(|sa| sa.object_properties.is_empty()));
+    // the other fields are read
+    assert!(ntm.lines.contains_id("B42"));
+    assert!(!ntm.vehicle_journeys.is_empty());
+}
+
+#[test]
+fn extra_columns_dropped_by_default() {
+    let ntm = transit_model::ntfs::read("tests/fixtures/ntfs2ntfs/extra_columns").unwrap();
+    test_in_tmp_dir(|path| {
+        transit_model::ntfs::write(&ntm, path, get_test_datetime()).unwrap();
+        let lines = std::fs::read_to_string(path.join("lines.txt")).unwrap();
+        assert!(!lines.contains("line_owner"));
+    });
+}
+
 #[test]
 fn optional_empty_collections_not_created() {
     let ntm = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
@@ -225,6 +393,57 @@ fn optional_empty_collections_not_created() {
     });
 }
 
+#[test]
+fn write_validated_ntfs() {
+    let ntm = transit_model::ntfs::read("tests/fixtures/ntfs/").unwrap();
+    test_in_tmp_dir(|output_dir| {
+        transit_model::ntfs::write_validated(
+            &ntm,
+            output_dir,
+            get_test_datetime(),
+            &transit_model::ntfs::WriteOptions::default(),
+        )
+        .unwrap();
+        let written = transit_model::ntfs::read(output_dir).unwrap();
+        assert_eq!(ntm.vehicle_journeys.len(), written.vehicle_journeys.len());
+        assert_eq!(ntm.stop_points.len(), written.stop_points.len());
+    });
+}
+
+#[test]
+fn write_validated_ntfs_in_missing_directory() {
+    let ntm = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    test_in_tmp_dir(|path| {
+        let output_dir = path.join("missing").join("ntfs");
+        transit_model::ntfs::write_validated(
+            &ntm,
+            &output_dir,
+            get_test_datetime(),
+            &transit_model::ntfs::WriteOptions::default(),
+        )
+        .unwrap();
+        assert!(output_dir.join("stop_times.txt").exists());
+    });
+}
+
+#[test]
+fn validate_detects_missing_rows() {
+    let ntm = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    test_in_tmp_dir(|output_dir| {
+        transit_model::ntfs::write(&ntm, output_dir, get_test_datetime()).unwrap();
+        transit_model::ntfs::validate(&ntm, output_dir).unwrap();
+
+        let stop_times_path = output_dir.join("stop_times.txt");
+        let stop_times = std::fs::read_to_string(&stop_times_path).unwrap();
+        let mut lines: Vec<&str> = stop_times.lines().collect();
+        lines.pop();
+        std::fs::write(&stop_times_path, lines.join("\n") + "\n").unwrap();
+
+        let error = transit_model::ntfs::validate(&ntm, output_dir).unwrap_err();
+        assert!(format!("{}", error).contains("stop_times"));
+    });
+}
+
 #[test]
 fn preserve_frequencies() {
     let ntm = transit_model::ntfs::read("tests/fixtures/ntfs/").unwrap();
@@ -354,3 +573,307 @@ fn sanitize_grid_with_line_external_code() {
     assert_eq!(1, collections.grid_calendars.len());
     assert_eq!(1, collections.grid_rel_calendar_line.len());
 }
+
+#[test]
+fn precheck_without_missing_references() {
+    let report = transit_model::ntfs::precheck("tests/fixtures/minimal_ntfs").unwrap();
+    assert!(report
+        .entries(transit_model::report::Severity::Error)
+        .is_empty());
+}
+
+#[test]
+fn precheck_reports_all_missing_references() {
+    let report =
+        transit_model::ntfs::precheck("tests/fixtures/ntfs2ntfs/dangling_references").unwrap();
+    let missing_references: Vec<(&str, usize)> = report
+        .entries(transit_model::report::Severity::Error)
+        .iter()
+        .map(|entry| (entry.message.as_str(), entry.count))
+        .collect();
+    assert_eq!(
+        vec![
+            ("trips.txt: route_id=\"B42X\" not found", 1),
+            ("trips.txt: service_id=\"Weekend\" not found", 1),
+            ("stop_times.txt: trip_id=\"M1X1\" not found", 2),
+            ("stop_times.txt: stop_id=\"NATX\" not found", 1),
+        ],
+        missing_references
+    );
+}
+
+// Writes the minimal NTFS in `path` with an additional line whose sort order
+// is invalid
+fn write_ntfs_with_invalid_line(path: &std::path::Path) {
+    let model = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    transit_model::ntfs::write(&model, path, get_test_datetime()).unwrap();
+    let lines_path = path.join("lines.txt");
+    let mut content = std::fs::read_to_string(&lines_path).unwrap();
+    let record: Vec<&str> = content
+        .lines()
+        .next()
+        .unwrap()
+        .split(',')
+        .map(|column| match column {
+            "line_id" => "L9",
+            "line_name" => "Line 9",
+            "network_id" => "TGN",
+            "commercial_mode_id" => "Bus",
+            "line_sort_order" => "first",
+            _ => "",
+        })
+        .collect();
+    content.push_str(&record.join(","));
+    content.push('\n');
+    std::fs::write(&lines_path, content).unwrap();
+}
+
+#[test]
+fn invalid_record_fails_reading() {
+    test_in_tmp_dir(|path| {
+        write_ntfs_with_invalid_line(path);
+        let error = transit_model::ntfs::read(path).unwrap_err();
+        match transit_model::error::find(&error) {
+            Some(transit_model::TransitModelError::CsvParse { line, record, .. }) => {
+                assert_eq!(&Some(5), line);
+                assert!(record.as_ref().unwrap().starts_with("L9,Line 9,"));
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    });
+}
+
+#[test]
+fn invalid_record_skipped_and_reported() {
+    test_in_tmp_dir(|path| {
+        write_ntfs_with_invalid_line(path);
+        let options = transit_model::ntfs::ReadOptions {
+            read_mode: transit_model::read_utils::ReadMode::Lenient,
+            ..Default::default()
+        };
+        let mut report = transit_model::report::Report::default();
+        let model = transit_model::ntfs::read_with_report(path, &options, &mut report).unwrap();
+        assert_eq!(3, model.lines.len());
+        let errors = report.entries(transit_model::report::Severity::Error);
+        assert_eq!(1, errors.len());
+        assert!(errors[0].message.contains("lines.txt"));
+    });
+}
+
+#[test]
+fn strict_precheck_aborts_reading() {
+    let options = transit_model::ntfs::ReadOptions {
+        precheck: true,
+        ..Default::default()
+    };
+    let error = transit_model::ntfs::read_with_options(
+        "tests/fixtures/ntfs2ntfs/dangling_references",
+        &options,
+    )
+    .unwrap_err();
+    let message = error.to_string();
+    assert!(message.starts_with("4 missing references found"));
+    assert!(message.contains("stop_times.txt: trip_id=\"M1X1\" not found (2 rows)"));
+}
+
+#[test]
+fn lenient_precheck_skips_dangling_references() {
+    let options = transit_model::ntfs::ReadOptions {
+        precheck: true,
+        read_mode: transit_model::read_utils::ReadMode::Lenient,
+        ..Default::default()
+    };
+    let mut report = transit_model::report::Report::default();
+    let model = transit_model::ntfs::read_with_report(
+        "tests/fixtures/ntfs2ntfs/dangling_references",
+        &options,
+        &mut report,
+    )
+    .unwrap();
+    assert!(!model.vehicle_journeys.contains_id("B42B1"));
+    let m1b1 = &model.vehicle_journeys.get("M1B1").unwrap();
+    assert_eq!(4, m1b1.stop_times.len());
+    let errors: Vec<&str> = report
+        .entries(transit_model::report::Severity::Error)
+        .iter()
+        .map(|entry| entry.message.as_str())
+        .collect();
+    for missing_reference in &[
+        "route_id=\"B42X\" not found",
+        "trip_id=\"B42B1\" not found",
+        "trip_id=\"M1X1\" not found",
+        "stop_id=\"NATX\" not found",
+    ] {
+        assert!(errors.iter().any(|error| error.contains(missing_reference)));
+    }
+}
+
+#[test]
+fn lenient_read_skips_malformed_stop_times() {
+    test_in_tmp_dir(|path| {
+        for entry in std::fs::read_dir("tests/fixtures/minimal_ntfs").unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), path.join(entry.file_name())).unwrap();
+        }
+        let stop_times_path = path.join("stop_times.txt");
+        let mut stop_times = std::fs::read_to_string(&stop_times_path).unwrap();
+        stop_times.push_str("RERAB1,60,NATR,not_a_time,09:50:00,\nRERAB1,61\n");
+        std::fs::write(&stop_times_path, stop_times).unwrap();
+
+        let strict_options = transit_model::ntfs::ReadOptions {
+            precheck: true,
+            ..Default::default()
+        };
+        assert!(transit_model::ntfs::read_with_options(path, &strict_options).is_err());
+
+        let options = transit_model::ntfs::ReadOptions {
+            precheck: true,
+            read_mode: transit_model::read_utils::ReadMode::Lenient,
+            ..Default::default()
+        };
+        let mut report = transit_model::report::Report::default();
+        let model = transit_model::ntfs::read_with_report(path, &options, &mut report).unwrap();
+        let rerab1 = model.vehicle_journeys.get("RERAB1").unwrap();
+        assert_eq!(7, rerab1.stop_times.len());
+        let errors = report.entries(transit_model::report::Severity::Error);
+        assert_eq!(2, errors.len());
+        assert!(errors
+            .iter()
+            .all(|error| error.message.contains("stop_times.txt")));
+    });
+}
+
+#[test]
+fn check_consistency_before_building_the_model() {
+    test_in_tmp_dir(|path| {
+        for entry in std::fs::read_dir("tests/fixtures/minimal_ntfs").unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), path.join(entry.file_name())).unwrap();
+        }
+        let trips_path = path.join("trips.txt");
+        let trips = std::fs::read_to_string(&trips_path).unwrap();
+        std::fs::write(
+            &trips_path,
+            trips.replace("RERAB,Week,RERAB1", "RERAB,Weekend,RERAB1"),
+        )
+        .unwrap();
+
+        let collections = transit_model::ntfs::read_collections_with_report(
+            path,
+            &Default::default(),
+            &mut transit_model::report::Report::default(),
+        )
+        .unwrap();
+        let report = collections.check_consistency();
+        let without_calendar: Vec<&str> = report
+            .entries(transit_model::report::Severity::Error)
+            .iter()
+            .filter(|entry| {
+                entry.category == transit_model::report::ReportType::VehicleJourneyWithoutCalendar
+            })
+            .filter_map(|entry| entry.object_id.as_deref())
+            .collect();
+        assert_eq!(vec!["RERAB1"], without_calendar);
+
+        // the vehicle journey is silently dropped when building the model
+        let model = transit_model::ntfs::read(path).unwrap();
+        assert!(!model.vehicle_journeys.contains_id("RERAB1"));
+    });
+}
+
+#[test]
+fn secondary_stop_areas() {
+    let model = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut collections = model.into_collections();
+    collections
+        .stop_areas
+        .push(StopArea {
+            id: String::from("GDL_CHA"),
+            name: String::from("Gare de Lyon - Châtelet"),
+            ..Default::default()
+        })
+        .unwrap();
+    for stop_id in &["GDLM", "CHAM", "UNKNOWN"] {
+        collections.stop_area_memberships.push(StopAreaMembership {
+            stop_id: stop_id.to_string(),
+            stop_area_id: String::from("GDL_CHA"),
+        });
+    }
+    let model = Model::new(collections).unwrap();
+    assert_eq!(2, model.stop_area_memberships.len());
+    let stop_area_ids: Vec<&str> = model
+        .stop_areas_of_stop_point(model.stop_points.get_idx("GDLM").unwrap())
+        .into_iter()
+        .map(|sa_idx| model.stop_areas[sa_idx].id.as_str())
+        .collect();
+    assert_eq!(vec!["GDL", "GDL_CHA"], stop_area_ids);
+
+    test_in_tmp_dir(|output_dir| {
+        transit_model::ntfs::write(&model, output_dir, get_test_datetime()).unwrap();
+        let written = transit_model::ntfs::read(output_dir).unwrap();
+        assert_eq!(2, written.stop_area_memberships.len());
+        assert!(written.stop_areas.contains_id("GDL_CHA"));
+    });
+
+    // the grouping is removed with the stop points it gathers
+    let model = transit_model::filter::keep_lines(model, |line| line.id == "B42").unwrap();
+    assert!(model.stop_area_memberships.is_empty());
+    assert!(!model.stop_areas.contains_id("GDL_CHA"));
+}
+
+#[test]
+fn booking_rules() {
+    let model = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut collections = model.into_collections();
+    for id in &["call", "unused"] {
+        collections
+            .booking_rules
+            .push(BookingRule {
+                id: id.to_string(),
+                phone: Some(String::from("0123456789")),
+                deadline_duration: Some(60),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    for (object_type, object_id) in &[
+        (ObjectType::Line, "B42"),
+        (ObjectType::VehicleJourney, "M1F1"),
+        (ObjectType::Line, "UNKNOWN"),
+    ] {
+        collections.booking_rule_links.push(BookingRuleLink {
+            object_type: object_type.clone(),
+            object_id: object_id.to_string(),
+            booking_rule_id: String::from("call"),
+        });
+    }
+    let model = Model::new(collections).unwrap();
+    assert_eq!(2, model.booking_rule_links.len());
+    assert!(!model.booking_rules.contains_id("unused"));
+
+    test_in_tmp_dir(|output_dir| {
+        transit_model::ntfs::write(&model, output_dir, get_test_datetime()).unwrap();
+        let written = transit_model::ntfs::read(output_dir).unwrap();
+        assert_eq!(2, written.booking_rule_links.len());
+        assert_eq!(
+            Some(60),
+            written.booking_rules.get("call").unwrap().deadline_duration
+        );
+    });
+}
+
+#[test]
+fn keep_tagged_lines() {
+    let model = transit_model::ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut collections = model.into_collections();
+    transit_model::tags::add_tag(&mut *collections.lines.get_mut("M1").unwrap(), "school");
+    let model = Model::new(collections).unwrap();
+
+    test_in_tmp_dir(|output_dir| {
+        transit_model::ntfs::write(&model, output_dir, get_test_datetime()).unwrap();
+        let written = transit_model::ntfs::read(output_dir).unwrap();
+        let model = transit_model::filter::keep_tagged_lines(written, "school").unwrap();
+        let line_ids: Vec<&str> = model.lines.values().map(|line| line.id.as_str()).collect();
+        assert_eq!(vec!["M1"], line_ids);
+    });
+}
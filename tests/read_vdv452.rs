@@ -0,0 +1,119 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use transit_model::{
+    objects::{Date, Time},
+    vdv452, Model,
+};
+
+fn model() -> Model {
+    vdv452::read("tests/fixtures/vdv452/input").unwrap()
+}
+
+#[test]
+fn read_places() {
+    let model = model();
+    let stop_point = model.stop_points.get("1001").unwrap();
+    assert_eq!("Hauptbahnhof Steig A", stop_point.name);
+    assert_eq!("stop_area:100", stop_point.stop_area_id);
+    assert_eq!(
+        "Hauptbahnhof",
+        model.stop_areas.get("stop_area:100").unwrap().name
+    );
+    // the file of REC_ORT is encoded in ISO 8859-1
+    let airport = model.stop_points.get("3001").unwrap();
+    assert_eq!("Flughafen Süd", airport.name);
+    assert_eq!("Navitia:3001", airport.stop_area_id);
+    // the places which are not stops are ignored
+    assert!(!model.stop_points.contains_id("9001"));
+}
+
+#[test]
+fn read_lines() {
+    let model = model();
+    let line = model.lines.get("42").unwrap();
+    assert_eq!(Some("42"), line.code.as_deref());
+    let route = model.routes.get("42:2").unwrap();
+    assert_eq!("Flughafen - Hauptbahnhof", route.name);
+    assert_eq!(Some("backward"), route.direction_type.as_deref());
+}
+
+#[test]
+fn compute_stop_times() {
+    let model = model();
+    let mut vj_ids: Vec<&str> = model
+        .vehicle_journeys
+        .values()
+        .map(|vj| vj.id.as_str())
+        .collect();
+    vj_ids.sort();
+    // without run times or dates, the trips are skipped
+    assert_eq!(vec!["1000001", "1000002", "1000004"], vj_ids);
+
+    let stop_times = |vj_id| {
+        model
+            .vehicle_journeys
+            .get(vj_id)
+            .unwrap()
+            .stop_times
+            .iter()
+            .map(|st| {
+                (
+                    model.stop_points[st.stop_point_idx].id.as_str(),
+                    st.arrival_time,
+                    st.departure_time,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    // the run times go through the place 9001 which is not a stop
+    assert_eq!(
+        vec![
+            ("1001", Time::new(7, 0, 0), Time::new(7, 0, 0)),
+            ("2001", Time::new(7, 5, 0), Time::new(7, 6, 0)),
+            ("3001", Time::new(7, 16, 0), Time::new(7, 16, 0)),
+        ],
+        stop_times("1000001")
+    );
+    // the dwell time of the trip replaces the one of its time group
+    assert_eq!(
+        vec![
+            ("1001", Time::new(7, 30, 0), Time::new(7, 30, 0)),
+            ("2001", Time::new(7, 35, 0), Time::new(7, 37, 0)),
+            ("3001", Time::new(7, 47, 0), Time::new(7, 47, 0)),
+        ],
+        stop_times("1000002")
+    );
+}
+
+#[test]
+fn read_day_types() {
+    let model = model();
+    let weekdays = model.calendars.get("day_type:1").unwrap();
+    assert_eq!(5, weekdays.dates.len());
+    let sunday = &model.vehicle_journeys.get("1000004").unwrap().service_id;
+    let dates: Vec<Date> = model
+        .calendars
+        .get(sunday)
+        .unwrap()
+        .dates
+        .iter()
+        .cloned()
+        .collect();
+    assert_eq!(vec![Date::from_ymd(2021, 6, 13)], dates);
+    let dataset = model.datasets.values().next().unwrap();
+    assert_eq!(Date::from_ymd(2021, 6, 7), dataset.start_date);
+    assert_eq!(Date::from_ymd(2021, 6, 13), dataset.end_date);
+}
@@ -14,7 +14,7 @@
 
 use chrono::NaiveDate;
 use std::path::Path;
-use transit_model::model::Model;
+use transit_model::model::{DateRange, Model};
 use transit_model::test_utils::*;
 
 #[test]
@@ -57,3 +57,54 @@ fn test_restrict_no_panic() {
         transit_model::ntfs::write(&new_model, path, get_test_datetime()).unwrap();
     });
 }
+
+#[test]
+fn test_split_by_period() {
+    let model =
+        transit_model::ntfs::read(Path::new("./tests/fixtures/restrict-validity-period/input"))
+            .unwrap();
+    let chunks = vec![
+        DateRange {
+            start_date: NaiveDate::from_ymd(2018, 5, 1),
+            end_date: NaiveDate::from_ymd(2018, 8, 5),
+        },
+        DateRange {
+            start_date: NaiveDate::from_ymd(2018, 8, 6),
+            end_date: NaiveDate::from_ymd(2019, 7, 31),
+        },
+    ];
+    let models = model.split_by_period(chunks.clone()).unwrap();
+    assert_eq!(2, models.len());
+    for (chunk, model) in chunks.iter().zip(&models) {
+        assert!(!model.calendars.is_empty());
+        assert!(!model.vehicle_journeys.is_empty());
+        for calendar in model.calendars.values() {
+            assert!(!calendar.dates.is_empty());
+            assert!(calendar
+                .dates
+                .iter()
+                .all(|date| *date >= chunk.start_date && *date <= chunk.end_date));
+        }
+        for vj in model.vehicle_journeys.values() {
+            assert!(model.calendars.contains_id(&vj.service_id));
+            assert!(!vj.stop_times.is_empty());
+        }
+    }
+    // the input model is left untouched
+    assert!(model.calendars.values().any(|calendar| calendar
+        .dates
+        .iter()
+        .any(|date| *date < chunks[0].start_date)));
+}
+
+#[test]
+fn test_split_by_invalid_period() {
+    let model =
+        transit_model::ntfs::read(Path::new("./tests/fixtures/restrict-validity-period/input"))
+            .unwrap();
+    let chunks = vec![DateRange {
+        start_date: NaiveDate::from_ymd(2018, 8, 5),
+        end_date: NaiveDate::from_ymd(2018, 5, 1),
+    }];
+    assert!(model.split_by_period(chunks).is_err());
+}
@@ -0,0 +1,270 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use std::fs;
+use transit_model::{
+    filter,
+    model_builder::ModelBuilder,
+    ntfs,
+    objects::{Company, CompanyParent, Date, Frequency, Time},
+    ridership,
+    statistics::{
+        self, LineModeNetworkStatistics, LineStatistics, OdStatistics, OperatorGroupStatistics,
+    },
+    test_utils::*,
+    Model,
+};
+use typed_index_collection::CollectionWithId;
+
+fn model_with_ridership(ridership: &str) -> Model {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut result = None;
+    test_in_tmp_dir(|path| {
+        create_file_with_content(path, "ridership.csv", ridership);
+        result = Some(ridership::read_ridership(model, path.join("ridership.csv")).unwrap());
+    });
+    result.unwrap()
+}
+
+#[test]
+fn read_ridership() {
+    let model = model_with_ridership(
+        "line_id,average_daily_boardings\n\
+         M1,12000\n\
+         B42,-3\n\
+         UNKNOWN,12\n",
+    );
+    let ridership = |id| ridership::line_ridership(model.lines.get(id).unwrap());
+    assert_eq!(Some(12000.0), ridership("M1"));
+    assert_eq!(None, ridership("B42"));
+    assert_eq!(None, ridership("RERA"));
+}
+
+#[test]
+fn keep_top_lines() {
+    let model = model_with_ridership(
+        "line_id,average_daily_boardings\n\
+         M1,12000\n\
+         B42,800\n\
+         RERA,30000\n",
+    );
+    let model = filter::keep_top_lines(model, 2).unwrap();
+    let mut line_ids: Vec<&str> = model.lines.values().map(|l| l.id.as_str()).collect();
+    line_ids.sort();
+    assert_eq!(vec!["M1", "RERA"], line_ids);
+    assert_eq!(4, model.routes.len());
+    assert_eq!(4, model.vehicle_journeys.len());
+}
+
+#[test]
+fn line_statistics() {
+    let model = model_with_ridership(
+        "line_id,average_daily_boardings\n\
+         RERA,30000\n",
+    );
+    let statistics = statistics::line_statistics(&model);
+    assert_eq!(
+        LineStatistics {
+            line_id: "B42".to_string(),
+            nb_routes: 2,
+            nb_vehicle_journeys: 2,
+            nb_stop_times: 4,
+            nb_zones: 0,
+            total_length: Some(7511),
+            average_daily_boardings: None,
+        },
+        statistics[0]
+    );
+    assert_eq!(
+        LineStatistics {
+            line_id: "RERA".to_string(),
+            nb_routes: 2,
+            nb_vehicle_journeys: 2,
+            nb_stop_times: 11,
+            nb_zones: 0,
+            total_length: Some(31279),
+            average_daily_boardings: Some(30000.0),
+        },
+        statistics[2]
+    );
+    test_in_tmp_dir(|path| {
+        let statistics_path = path.join("statistics.csv");
+        statistics::write_line_statistics(&model, &statistics_path).unwrap();
+        assert_eq!(
+            "line_id,nb_routes,nb_vehicle_journeys,nb_stop_times,nb_zones,total_length,average_daily_boardings\n\
+             B42,2,2,4,0,7511,\n\
+             M1,2,2,8,0,78490,\n\
+             RERA,2,2,11,0,31279,30000.0\n",
+            fs::read_to_string(statistics_path).unwrap()
+        );
+    });
+}
+
+#[test]
+fn operator_group_statistics() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let mut collections = model.into_collections();
+    for (id, name) in &[("SUB", "Subsidiary"), ("OTHER", "Other")] {
+        collections
+            .companies
+            .push(Company {
+                id: id.to_string(),
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    collections.company_parents.push(CompanyParent {
+        company_id: "SUB".to_string(),
+        parent_company_id: "TGC".to_string(),
+    });
+    let mut vehicle_journeys = collections.vehicle_journeys.take();
+    for vj in &mut vehicle_journeys {
+        if vj.route_id.starts_with("RERA") {
+            vj.company_id = "SUB".to_string();
+        } else if vj.route_id.starts_with("B42") {
+            vj.company_id = "OTHER".to_string();
+        }
+    }
+    collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys).unwrap();
+    let model = Model::new(collections).unwrap();
+
+    assert_eq!(
+        vec![
+            OperatorGroupStatistics {
+                company_id: "OTHER".to_string(),
+                nb_operators: 1,
+                nb_lines: 1,
+                nb_vehicle_journeys: 2,
+                nb_stop_times: 4,
+            },
+            OperatorGroupStatistics {
+                company_id: "TGC".to_string(),
+                nb_operators: 2,
+                nb_lines: 2,
+                nb_vehicle_journeys: 4,
+                nb_stop_times: 19,
+            },
+        ],
+        statistics::operator_group_statistics(&model)
+    );
+}
+
+#[test]
+fn line_mode_network_statistics() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let statistics = statistics::line_mode_network_statistics(&model);
+    let cell =
+        |line_id: &str, physical_mode_id: &str, nb_vehicle_journeys| LineModeNetworkStatistics {
+            line_id: line_id.to_string(),
+            commercial_mode_id: model.lines.get(line_id).unwrap().commercial_mode_id.clone(),
+            network_id: "TGN".to_string(),
+            physical_mode_id: physical_mode_id.to_string(),
+            nb_vehicle_journeys,
+        };
+    assert_eq!(
+        vec![
+            cell("B42", "Bus", 2),
+            cell("M1", "Metro", 2),
+            cell("RERA", "Bus", 1),
+            cell("RERA", "RapidTransit", 1),
+        ],
+        statistics
+    );
+
+    test_in_tmp_dir(|path| {
+        let matrix_path = path.join("matrix.csv");
+        statistics::write_line_mode_network_statistics(&model, &matrix_path).unwrap();
+        assert_eq!(
+            "line_id,commercial_mode_id,network_id,physical_mode_id,nb_vehicle_journeys\n\
+             B42,Bus,TGN,Bus,2\n\
+             M1,Metro,TGN,Metro,2\n\
+             RERA,RER,TGN,Bus,1\n\
+             RERA,RER,TGN,RapidTransit,1\n",
+            fs::read_to_string(matrix_path).unwrap()
+        );
+    });
+}
+
+#[test]
+fn od_statistics() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    let statistics: Vec<OdStatistics> = statistics::od_statistics(&model)
+        .into_iter()
+        .filter(|od| od.line_id == "B42")
+        .collect();
+    // 261 weekdays in 2018
+    let od = |origin: &str, destination: &str| OdStatistics {
+        line_id: "B42".to_string(),
+        origin_stop_area_id: origin.to_string(),
+        destination_stop_area_id: destination.to_string(),
+        daily_journeys: 0.72,
+        average_travel_time: 600,
+    };
+    assert_eq!(vec![od("GDL", "MTP"), od("MTP", "GDL")], statistics);
+
+    test_in_tmp_dir(|path| {
+        let od_path = path.join("od.csv");
+        statistics::write_od_statistics(&model, &od_path).unwrap();
+        let content = fs::read_to_string(od_path).unwrap();
+        assert!(content.starts_with(
+            "line_id,origin_stop_area_id,destination_stop_area_id,daily_journeys,average_travel_time\n"
+        ));
+        assert!(content.contains("B42,GDL,MTP,0.72,600\n"));
+    });
+}
+
+#[test]
+fn od_statistics_with_frequencies() {
+    let mut collections = ModelBuilder::default()
+        .calendar("c1", |calendar| {
+            calendar.dates.insert(Date::from_ymd(2020, 1, 1));
+            calendar.dates.insert(Date::from_ymd(2020, 1, 2));
+        })
+        .route("r1", |route| route.line_id = "l1".to_string())
+        .vj("vj1", |vj| {
+            vj.route("r1")
+                .calendar("c1")
+                .st("SP1", "10:00")
+                .st("SP2", "10:10");
+        })
+        .vj("vj2", |vj| {
+            vj.route("r1")
+                .calendar("c1")
+                .st("SP1", "12:00")
+                .st("SP2", "12:20");
+        })
+        .build()
+        .into_collections();
+    // vj1 runs every 10 minutes from 08:00 to 08:55
+    collections.frequencies.push(Frequency {
+        vehicle_journey_id: "vj1".to_string(),
+        start_time: Time::new(8, 0, 0),
+        end_time: Time::new(9, 0, 0),
+        headway_secs: 600,
+    });
+    let model = Model::new(collections).unwrap();
+    // 6 runs of vj1 and 1 of vj2 a day
+    assert_eq!(
+        vec![OdStatistics {
+            line_id: "l1".to_string(),
+            origin_stop_area_id: "sa:SP1".to_string(),
+            destination_stop_area_id: "sa:SP2".to_string(),
+            daily_journeys: 7.,
+            average_travel_time: 685,
+        }],
+        statistics::od_statistics(&model)
+    );
+}
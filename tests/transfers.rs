@@ -90,3 +90,151 @@ fn test_generates_transfers_with_closure_inter_contributors() {
         );
     });
 }
+
+#[test]
+fn test_generates_transfers_by_physical_mode() {
+    use std::collections::HashMap;
+    use transfers::{TransferParameters, TransfersConfiguration};
+
+    let parameters = |max_distance| TransferParameters {
+        max_distance,
+        walking_speed: 0.785,
+        waiting_time: 120,
+    };
+    let mut physical_modes = HashMap::new();
+    physical_modes.insert("Train".to_string(), parameters(250.0));
+    let mut configuration = TransfersConfiguration {
+        default: parameters(100.0),
+        physical_modes,
+        excluded_physical_mode_pairs: vec![],
+    };
+    let input_dir = "tests/fixtures/transfers/mono_contributor/input";
+    let transfer_stops = |model: &transit_model::Model| -> Vec<(String, String)> {
+        model
+            .transfers
+            .values()
+            .map(|t| (t.from_stop_id.clone(), t.to_stop_id.clone()))
+            .collect()
+    };
+
+    let model = transit_model::ntfs::read(input_dir).unwrap();
+    let model =
+        transfers::generates_transfers_by_physical_mode(model, &configuration, None).unwrap();
+    let stops = transfer_stops(&model);
+    assert!(stops.contains(&("sp_2".to_string(), "sp_3".to_string())));
+    assert!(stops.contains(&("sp_3".to_string(), "sp_1".to_string())));
+
+    configuration.excluded_physical_mode_pairs = vec![("Train".to_string(), "Train".to_string())];
+    let model = transit_model::ntfs::read(input_dir).unwrap();
+    let model =
+        transfers::generates_transfers_by_physical_mode(model, &configuration, None).unwrap();
+    assert_eq!(
+        vec![
+            ("sp_1".to_string(), "sp_2".to_string()),
+            ("sp_1".to_string(), "sp_3".to_string())
+        ],
+        transfer_stops(&model)
+    );
+}
+
+#[test]
+fn test_read_transfers_configuration() {
+    test_in_tmp_dir(|path| {
+        create_file_with_content(
+            path,
+            "transfers.json",
+            r#"{
+                "default": { "max_distance": 500, "walking_speed": 0.785, "waiting_time": 60 },
+                "physical_modes": {
+                    "Train": { "max_distance": 800, "walking_speed": 0.785, "waiting_time": 180 }
+                },
+                "excluded_physical_mode_pairs": [["Air", "Bus"]]
+            }"#,
+        );
+        let configuration =
+            transfers::read_transfers_configuration(path.join("transfers.json")).unwrap();
+        assert_eq!(500.0, configuration.default.max_distance);
+        assert_eq!(800.0, configuration.physical_modes["Train"].max_distance);
+        assert_eq!(
+            vec![("Air".to_string(), "Bus".to_string())],
+            configuration.excluded_physical_mode_pairs
+        );
+    });
+}
+
+#[test]
+fn test_fix_transfer_times() {
+    use transfers::{PhysicalModePairTransferTime, TransferTimesConfiguration};
+    use transit_model::report::{Report, ReportType, Severity};
+
+    test_in_tmp_dir(|path| {
+        let input_dir = "tests/fixtures/transfers/mono_contributor/input";
+        for entry in std::fs::read_dir(input_dir).unwrap() {
+            let file_path = entry.unwrap().path();
+            std::fs::copy(&file_path, path.join(file_path.file_name().unwrap())).unwrap();
+        }
+        create_file_with_content(
+            path,
+            "transfers.txt",
+            "from_stop_id,to_stop_id,min_transfer_time,real_min_transfer_time,equipment_id\n\
+             sp_1,sp_2,30,40,\n\
+             sp_1,sp_3,200,90000,\n\
+             sp_2,sp_1,0,0,\n\
+             sp_2,sp_2,0,0,\n\
+             sp_2,sp_3,86400,86400,\n\
+             sp_3,sp_2,-30,,\n",
+        );
+        let model = transit_model::ntfs::read(path).unwrap();
+        assert!(model
+            .check_consistency()
+            .entries(Severity::Error)
+            .iter()
+            .any(|e| e.category == ReportType::UnrealisticTransferTime));
+
+        let configuration = TransferTimesConfiguration {
+            min_transfer_time: 60,
+            physical_mode_pairs: vec![
+                PhysicalModePairTransferTime {
+                    physical_modes: ("Bus".to_string(), "Train".to_string()),
+                    min_transfer_time: 300,
+                },
+                PhysicalModePairTransferTime {
+                    physical_modes: ("Train".to_string(), "Train".to_string()),
+                    min_transfer_time: 180,
+                },
+            ],
+            max_transfer_time: 3600,
+        };
+        let mut report = Report::default();
+        let model = transfers::fix_transfer_times(model, &configuration, &mut report).unwrap();
+        let times: Vec<(&str, &str, Option<u32>, Option<u32>)> = model
+            .transfers
+            .values()
+            .map(|t| {
+                (
+                    t.from_stop_id.as_str(),
+                    t.to_stop_id.as_str(),
+                    t.min_transfer_time,
+                    t.real_min_transfer_time,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("sp_1", "sp_2", Some(180), Some(180)),
+                ("sp_1", "sp_3", Some(200), Some(3600)),
+                ("sp_2", "sp_1", Some(0), Some(0)),
+                ("sp_2", "sp_2", Some(0), Some(0)),
+                ("sp_2", "sp_3", Some(86400), Some(86400)),
+                // the negative transfer times are ignored
+                ("sp_3", "sp_2", None, None),
+            ],
+            times
+        );
+        let warnings = report.entries(Severity::Warning);
+        assert_eq!(2, warnings.len());
+        assert!(warnings
+            .iter()
+            .all(|w| w.category == ReportType::UnrealisticTransferTime));
+    });
+}
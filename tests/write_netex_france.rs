@@ -70,6 +70,13 @@ fn test_write_netex_france_from_gtfs() {
         prefix_conf: None,
         on_demand_transport: false,
         on_demand_transport_comment: None,
+        route_type_mappings: BTreeMap::new(),
+        keep_frequencies: false,
+        crs: None,
+        company_parents: Default::default(),
+        network_contacts: Default::default(),
+        read_mode: Default::default(),
+        trip_operators: false,
     };
 
     let model =
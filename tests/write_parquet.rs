@@ -0,0 +1,76 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use pretty_assertions::assert_eq;
+use std::{fs::File, path::Path};
+use transit_model::{ntfs, parquet_export, test_utils::*};
+
+// (column names, number of rows) of a Parquet file
+fn describe(path: &Path) -> (Vec<String>, i64) {
+    let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+    let metadata = reader.metadata().file_metadata();
+    let columns = metadata
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect();
+    (columns, metadata.num_rows())
+}
+
+#[test]
+fn write_parquet() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    test_in_tmp_dir(|path| {
+        parquet_export::write(&model, path).unwrap();
+
+        let (columns, nb_stops) = describe(&path.join("stops.parquet"));
+        assert_eq!("stop_id", columns[0]);
+        assert_eq!(
+            (model.stop_areas.len() + model.stop_points.len()) as i64,
+            nb_stops
+        );
+
+        let (columns, nb_trips) = describe(&path.join("trips.parquet"));
+        assert_eq!("trip_id", columns[0]);
+        assert_eq!(6, nb_trips);
+
+        let (columns, nb_stop_times) = describe(&path.join("stop_times.parquet"));
+        assert_eq!(
+            vec![
+                "trip_id",
+                "stop_sequence",
+                "stop_id",
+                "arrival_time",
+                "departure_time",
+                "boarding_duration",
+                "alighting_duration",
+                "pickup_type",
+                "drop_off_type",
+                "datetime_estimated",
+                "local_zone_id",
+            ],
+            columns
+        );
+        assert_eq!(
+            model
+                .vehicle_journeys
+                .values()
+                .map(|vj| vj.stop_times.len() as i64)
+                .sum::<i64>(),
+            nb_stop_times
+        );
+    });
+}
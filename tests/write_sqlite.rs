@@ -0,0 +1,81 @@
+// Copyright (C) 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU Affero General Public License as published by the
+// Free Software Foundation, version 3.
+
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more
+// details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>
+
+use pretty_assertions::assert_eq;
+use rusqlite::Connection;
+use transit_model::{ntfs, sqlite, test_utils::*};
+
+#[test]
+fn write_sqlite() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    test_in_tmp_dir(|path| {
+        let database_path = path.join("minimal.sqlite");
+        sqlite::write(&model, &database_path, get_test_datetime()).unwrap();
+        let connection = Connection::open(&database_path).unwrap();
+
+        let mut statement = connection
+            .prepare(
+                "SELECT lines.line_id, COUNT(*) FROM trips \
+                 JOIN routes ON trips.route_id = routes.route_id \
+                 JOIN lines ON routes.line_id = lines.line_id \
+                 GROUP BY lines.line_id ORDER BY lines.line_id",
+            )
+            .unwrap();
+        let nb_trips: Vec<(String, i64)> = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            vec![
+                ("B42".to_string(), 2),
+                ("M1".to_string(), 2),
+                ("RERA".to_string(), 2)
+            ],
+            nb_trips
+        );
+
+        let nb_stop_times: i64 = connection
+            .query_row("SELECT COUNT(*) FROM stop_times", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(
+            model
+                .vehicle_journeys
+                .values()
+                .map(|vj| vj.stop_times.len() as i64)
+                .sum::<i64>(),
+            nb_stop_times
+        );
+
+        // the references of the dataset are valid
+        let mut statement = connection.prepare("PRAGMA foreign_key_check").unwrap();
+        let mut violations = statement.query([]).unwrap();
+        assert!(violations.next().unwrap().is_none());
+    });
+}
+
+#[test]
+fn rewrite_sqlite() {
+    let model = ntfs::read("tests/fixtures/minimal_ntfs/").unwrap();
+    test_in_tmp_dir(|path| {
+        let database_path = path.join("minimal.sqlite");
+        sqlite::write(&model, &database_path, get_test_datetime()).unwrap();
+        sqlite::write(&model, &database_path, get_test_datetime()).unwrap();
+        let connection = Connection::open(&database_path).unwrap();
+        let nb_lines: i64 = connection
+            .query_row("SELECT COUNT(*) FROM lines", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(3, nb_lines);
+    });
+}
@@ -0,0 +1,121 @@
+// Copyright 2017 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+use failure::bail;
+use log::info;
+use slog::{slog_o, Drain};
+use slog_async::OverflowStrategy;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use transit_model::{
+    report::{Report, Severity},
+    Result,
+};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "transit_model_validator",
+    about = "Check the consistency of a NTFS."
+)]
+struct Opt {
+    /// Input directory.
+    #[structopt(short = "i", long = "input", parse(from_os_str), default_value = ".")]
+    input: PathBuf,
+
+    /// Output file of the report of the violations, as CSV if it has a
+    /// `.csv` extension, as JSON otherwise.
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// JSON file of the minimum number of vehicle journeys expected by day
+    /// for the lines, by default, by network or by line.
+    #[structopt(short = "t", long = "thresholds", parse(from_os_str))]
+    thresholds: Option<PathBuf>,
+}
+
+fn init_logger() -> slog_scope::GlobalLoggerGuard {
+    let decorator = slog_term::TermDecorator::new().stdout().build();
+    let drain = slog_term::CompactFormat::new(decorator).build().fuse();
+    let mut builder = slog_envlogger::LogBuilder::new(drain).filter(None, slog::FilterLevel::Info);
+    if let Ok(s) = std::env::var("RUST_LOG") {
+        builder = builder.parse(&s);
+    }
+    let drain = slog_async::Async::new(builder.build())
+        .chan_size(256) // Double the default size
+        .overflow_strategy(OverflowStrategy::Block)
+        .build()
+        .fuse();
+    let logger = slog::Logger::root(drain, slog_o!());
+
+    let scope_guard = slog_scope::set_global_logger(logger);
+    slog_stdlog::init().unwrap();
+    scope_guard
+}
+
+fn run(opt: Opt) -> Result<()> {
+    info!("Launching transit_model_validator...");
+
+    let thresholds = opt
+        .thresholds
+        .map(transit_model::validation::read_service_thresholds)
+        .transpose()?;
+    // the missing references of the trips and stop times prevent the
+    // collections from being read: they are all reported by the precheck
+    let report = transit_model::ntfs::precheck(&opt.input)?;
+    let report = if report.entries(Severity::Error).is_empty() {
+        // the collections are checked before building the model, which
+        // drops or rejects the inconsistent objects
+        let mut collections = transit_model::ntfs::read_collections_with_report(
+            opt.input,
+            &Default::default(),
+            &mut Report::default(),
+        )?;
+        // the lengths checked against the speeds are computed by Model::new
+        collections.compute_vehicle_journey_lengths();
+        let mut report = collections.check_consistency();
+        if let Some(thresholds) = thresholds {
+            collections.check_service_thresholds(&thresholds, &mut report);
+        }
+        report
+    } else {
+        report
+    };
+
+    match opt.output {
+        Some(output) => report.write(output)?,
+        None => {
+            for entry in report.entries(Severity::Error) {
+                println!("{}", entry.message);
+            }
+        }
+    }
+    let nb_errors = report.entries(Severity::Error).len();
+    if nb_errors > 0 {
+        bail!("{} consistency violations found", nb_errors);
+    }
+    info!("No consistency violation found");
+    Ok(())
+}
+
+fn main() {
+    let _log_guard = init_logger();
+    if let Err(err) = run(Opt::from_args()) {
+        for cause in err.iter_chain() {
+            eprintln!("{}", cause);
+        }
+        std::process::exit(1);
+    }
+}